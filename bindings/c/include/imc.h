@@ -0,0 +1,147 @@
+/* C interface to imc-rs. Generated by cbindgen (see cbindgen.toml), do not edit. */
+
+#ifndef IMC_H
+#define IMC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Status returned by functions which can fail
+ */
+typedef enum ImcStatus {
+  /**
+   * The function succeeded
+   */
+  IMC_STATUS_OK = 0,
+  /**
+   * A required pointer was NULL
+   */
+  IMC_STATUS_NULL_POINTER = 1,
+  /**
+   * An argument was invalid (e.g. a path which is not UTF-8, or a channel index out of range)
+   */
+  IMC_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The buffer supplied was too small for the data
+   */
+  IMC_STATUS_BUFFER_TOO_SMALL = 3,
+  /**
+   * The data could not be read (e.g. the file is missing or not a valid .mcd file)
+   */
+  IMC_STATUS_READ_FAILED = 4,
+  /**
+   * An unexpected internal error occurred
+   */
+  IMC_STATUS_PANIC = 5,
+} ImcStatus;
+
+/**
+ * Acquisition within an .mcd file, borrowed from an `ImcMcd`
+ */
+typedef struct ImcAcquisition ImcAcquisition;
+
+/**
+ * Handle to an opened .mcd file
+ */
+typedef struct ImcMcd ImcMcd;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open the .mcd file at `path` (a NUL-terminated UTF-8 string). When `use_dcm` is non-zero, a temporary file
+ * (.dcm) is created alongside the .mcd file, if not already present, for faster access to channel images.
+ *
+ * Returns NULL if the file could not be opened (see `imc_last_error`). The handle must be released with
+ * `imc_mcd_free`. Handles can be shared between threads.
+ */
+ImcMcd *imc_mcd_open(const char *path, int32_t use_dcm);
+
+/**
+ * Release a handle returned by `imc_mcd_open`, along with all acquisitions borrowed from it. Passing NULL does
+ * nothing.
+ */
+void imc_mcd_free(ImcMcd *mcd);
+
+/**
+ * Returns the number of acquisitions in the file (0 if `mcd` is NULL)
+ */
+size_t imc_mcd_num_acquisitions(const ImcMcd *mcd);
+
+/**
+ * Returns the acquisition at `index` (ordered by ID, from 0 to `imc_mcd_num_acquisitions` - 1), or NULL if the
+ * index is out of range. The acquisition is valid until `mcd` is freed.
+ */
+const ImcAcquisition *imc_mcd_acquisition(const ImcMcd *mcd, size_t index);
+
+/**
+ * Returns the ID of the acquisition (0 if `acquisition` is NULL)
+ */
+uint16_t imc_acquisition_id(const ImcAcquisition *acquisition);
+
+/**
+ * Copy the description of the acquisition (e.g. ROI_001) into `buffer` (of `length` bytes) as a NUL-terminated
+ * UTF-8 string, truncating it if necessary. Returns the length of the description in bytes (excluding the
+ * NUL), so that the required buffer size can be found by passing a NULL buffer.
+ */
+size_t imc_acquisition_description(const ImcAcquisition *acquisition, char *buffer, size_t length);
+
+/**
+ * Returns the width of the acquisition in pixels (0 if `acquisition` is NULL)
+ */
+int32_t imc_acquisition_width(const ImcAcquisition *acquisition);
+
+/**
+ * Returns the height of the acquisition in pixels (0 if `acquisition` is NULL)
+ */
+int32_t imc_acquisition_height(const ImcAcquisition *acquisition);
+
+/**
+ * Returns the number of channels in the acquisition (0 if `acquisition` is NULL)
+ */
+size_t imc_acquisition_num_channels(const ImcAcquisition *acquisition);
+
+/**
+ * Copy the name of the channel at `index` (e.g. Ir191) into `buffer`, as `imc_acquisition_description`. An
+ * empty string is returned if the index is out of range.
+ */
+size_t imc_acquisition_channel_name(const ImcAcquisition *acquisition,
+                                    size_t index,
+                                    char *buffer,
+                                    size_t length);
+
+/**
+ * Copy the label of the channel at `index` (e.g. DNA1) into `buffer`, as `imc_acquisition_description`. An
+ * empty string is returned if the index is out of range.
+ */
+size_t imc_acquisition_channel_label(const ImcAcquisition *acquisition,
+                                     size_t index,
+                                     char *buffer,
+                                     size_t length);
+
+/**
+ * Read the intensities of the channel at `index` into `buffer`, which must hold at least width * height
+ * values, in row-major order. Pixels which were not acquired (e.g. if the acquisition was stopped early) are
+ * NaN.
+ */
+ImcStatus imc_acquisition_read_channel(const ImcAcquisition *acquisition,
+                                       size_t index,
+                                       float *buffer,
+                                       size_t length);
+
+/**
+ * Copy a description of the most recent error on the calling thread into `buffer`, as
+ * `imc_acquisition_description`. An empty string is returned if no error has occurred.
+ */
+size_t imc_last_error(char *buffer, size_t length);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* IMC_H */
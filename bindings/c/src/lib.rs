@@ -0,0 +1,438 @@
+#![warn(missing_docs)]
+
+//! C bindings for imc-rs, a library for accessing imaging mass cytometry data.
+//!
+//! The declarations are in `include/imc.h`. Files are opened with `imc_mcd_open`, which returns a handle that is
+//! released with `imc_mcd_free`. Acquisitions are borrowed from the handle, and are valid until it is freed.
+//! Functions which can fail return an `ImcStatus`, or NULL, and a description of the most recent error on the
+//! calling thread is available from `imc_last_error`.
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use imc_rs::{Acquisition, ChannelIdentifier, MCD};
+
+/// Status returned by functions which can fail
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImcStatus {
+    /// The function succeeded
+    Ok = 0,
+    /// A required pointer was NULL
+    NullPointer = 1,
+    /// An argument was invalid (e.g. a path which is not UTF-8, or a channel index out of range)
+    InvalidArgument = 2,
+    /// The buffer supplied was too small for the data
+    BufferTooSmall = 3,
+    /// The data could not be read (e.g. the file is missing or not a valid .mcd file)
+    ReadFailed = 4,
+    /// An unexpected internal error occurred
+    Panic = 5,
+}
+
+/// Handle to an opened .mcd file
+pub struct ImcMcd {
+    mcd: MCD<File>,
+}
+
+/// Acquisition within an .mcd file, borrowed from an `ImcMcd`
+#[repr(transparent)]
+pub struct ImcAcquisition(Acquisition<File>);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn set_last_error<S: Into<String>>(message: S) {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message.into()));
+}
+
+/// Record the error, returning the status
+fn fail<S: Into<String>>(status: ImcStatus, message: S) -> ImcStatus {
+    set_last_error(message);
+    status
+}
+
+/// Run `operation`, converting any panic to `ImcStatus::Panic` rather than unwinding into the caller
+fn guard<F: FnOnce() -> ImcStatus>(operation: F) -> ImcStatus {
+    panic::catch_unwind(AssertUnwindSafe(operation))
+        .unwrap_or_else(|_| fail(ImcStatus::Panic, "unexpected internal error"))
+}
+
+/// Copy `value` into the buffer of `length` bytes as a NUL-terminated string, truncating it if necessary (as
+/// `snprintf`), and return the length of `value` in bytes (excluding the NUL)
+///
+/// # Safety
+///
+/// `buffer` must be NULL or valid for writes of `length` bytes.
+unsafe fn copy_string(value: &str, buffer: *mut c_char, length: usize) -> usize {
+    if !buffer.is_null() && length > 0 {
+        let copied = value.len().min(length - 1);
+
+        ptr::copy_nonoverlapping(value.as_ptr() as *const c_char, buffer, copied);
+        *buffer.add(copied) = 0;
+    }
+
+    value.len()
+}
+
+/// Open the .mcd file at `path` (a NUL-terminated UTF-8 string). When `use_dcm` is non-zero, a temporary file
+/// (.dcm) is created alongside the .mcd file, if not already present, for faster access to channel images.
+///
+/// Returns NULL if the file could not be opened (see `imc_last_error`). The handle must be released with
+/// `imc_mcd_free`. Handles can be shared between threads.
+///
+/// # Safety
+///
+/// `path` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn imc_mcd_open(path: *const c_char, use_dcm: i32) -> *mut ImcMcd {
+    let mut mcd = ptr::null_mut();
+
+    guard(|| {
+        if path.is_null() {
+            return fail(ImcStatus::NullPointer, "path is NULL");
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => return fail(ImcStatus::InvalidArgument, "path is not valid UTF-8"),
+        };
+
+        let opened = MCD::from_path(path).and_then(|opened| {
+            if use_dcm != 0 {
+                opened.with_dcm()
+            } else {
+                Ok(opened)
+            }
+        });
+
+        match opened {
+            Ok(opened) => {
+                mcd = Box::into_raw(Box::new(ImcMcd { mcd: opened }));
+                ImcStatus::Ok
+            }
+            Err(error) => fail(ImcStatus::ReadFailed, error.to_string()),
+        }
+    });
+
+    mcd
+}
+
+/// Release a handle returned by `imc_mcd_open`, along with all acquisitions borrowed from it. Passing NULL does
+/// nothing.
+///
+/// # Safety
+///
+/// `mcd` must be NULL or a handle returned by `imc_mcd_open` which has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn imc_mcd_free(mcd: *mut ImcMcd) {
+    if !mcd.is_null() {
+        drop(Box::from_raw(mcd));
+    }
+}
+
+/// Returns the number of acquisitions in the file (0 if `mcd` is NULL)
+///
+/// # Safety
+///
+/// `mcd` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn imc_mcd_num_acquisitions(mcd: *const ImcMcd) -> usize {
+    mcd.as_ref().map_or(0, |mcd| mcd.mcd.acquisitions().len())
+}
+
+/// Returns the acquisition at `index` (ordered by ID, from 0 to `imc_mcd_num_acquisitions` - 1), or NULL if the
+/// index is out of range. The acquisition is valid until `mcd` is freed.
+///
+/// # Safety
+///
+/// `mcd` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn imc_mcd_acquisition(
+    mcd: *const ImcMcd,
+    index: usize,
+) -> *const ImcAcquisition {
+    match mcd
+        .as_ref()
+        .and_then(|mcd| mcd.mcd.acquisitions().get(index).copied())
+    {
+        Some(acquisition) => acquisition as *const Acquisition<File> as *const ImcAcquisition,
+        None => ptr::null(),
+    }
+}
+
+/// Returns the ID of the acquisition (0 if `acquisition` is NULL)
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_id(acquisition: *const ImcAcquisition) -> u16 {
+    acquisition
+        .as_ref()
+        .map_or(0, |acquisition| acquisition.0.id())
+}
+
+/// Copy the description of the acquisition (e.g. ROI_001) into `buffer` (of `length` bytes) as a NUL-terminated
+/// UTF-8 string, truncating it if necessary. Returns the length of the description in bytes (excluding the
+/// NUL), so that the required buffer size can be found by passing a NULL buffer.
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition, and `buffer` must be NULL or valid for writes of `length`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_description(
+    acquisition: *const ImcAcquisition,
+    buffer: *mut c_char,
+    length: usize,
+) -> usize {
+    let description = acquisition
+        .as_ref()
+        .map_or("", |acquisition| acquisition.0.description());
+
+    copy_string(description, buffer, length)
+}
+
+/// Returns the width of the acquisition in pixels (0 if `acquisition` is NULL)
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_width(acquisition: *const ImcAcquisition) -> i32 {
+    acquisition
+        .as_ref()
+        .map_or(0, |acquisition| acquisition.0.width())
+}
+
+/// Returns the height of the acquisition in pixels (0 if `acquisition` is NULL)
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_height(acquisition: *const ImcAcquisition) -> i32 {
+    acquisition
+        .as_ref()
+        .map_or(0, |acquisition| acquisition.0.height())
+}
+
+/// Returns the number of channels in the acquisition (0 if `acquisition` is NULL)
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_num_channels(acquisition: *const ImcAcquisition) -> usize {
+    acquisition
+        .as_ref()
+        .map_or(0, |acquisition| acquisition.0.channels().len())
+}
+
+/// Copy the name of the channel at `index` (e.g. Ir191) into `buffer`, as `imc_acquisition_description`. An
+/// empty string is returned if the index is out of range.
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition, and `buffer` must be NULL or valid for writes of `length`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_channel_name(
+    acquisition: *const ImcAcquisition,
+    index: usize,
+    buffer: *mut c_char,
+    length: usize,
+) -> usize {
+    let name = acquisition
+        .as_ref()
+        .and_then(|acquisition| acquisition.0.channels().get(index))
+        .map_or("", |channel| channel.name());
+
+    copy_string(name, buffer, length)
+}
+
+/// Copy the label of the channel at `index` (e.g. DNA1) into `buffer`, as `imc_acquisition_description`. An
+/// empty string is returned if the index is out of range.
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition, and `buffer` must be NULL or valid for writes of `length`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_channel_label(
+    acquisition: *const ImcAcquisition,
+    index: usize,
+    buffer: *mut c_char,
+    length: usize,
+) -> usize {
+    let label = acquisition
+        .as_ref()
+        .and_then(|acquisition| acquisition.0.channels().get(index))
+        .map_or("", |channel| channel.label());
+
+    copy_string(label, buffer, length)
+}
+
+/// Read the intensities of the channel at `index` into `buffer`, which must hold at least width * height
+/// values, in row-major order. Pixels which were not acquired (e.g. if the acquisition was stopped early) are
+/// NaN.
+///
+/// # Safety
+///
+/// `acquisition` must be NULL or a valid acquisition, and `buffer` must be NULL or valid for writes of `length`
+/// floats.
+#[no_mangle]
+pub unsafe extern "C" fn imc_acquisition_read_channel(
+    acquisition: *const ImcAcquisition,
+    index: usize,
+    buffer: *mut f32,
+    length: usize,
+) -> ImcStatus {
+    guard(|| {
+        let acquisition = match acquisition.as_ref() {
+            Some(acquisition) => &acquisition.0,
+            None => return fail(ImcStatus::NullPointer, "acquisition is NULL"),
+        };
+        if buffer.is_null() {
+            return fail(ImcStatus::NullPointer, "buffer is NULL");
+        }
+
+        let channel = match acquisition.channels().get(index) {
+            Some(channel) => channel,
+            None => {
+                return fail(
+                    ImcStatus::InvalidArgument,
+                    format!(
+                        "channel index {} is out of range (acquisition {} has {} channels)",
+                        index,
+                        acquisition.id(),
+                        acquisition.channels().len()
+                    ),
+                )
+            }
+        };
+
+        let num_pixels = acquisition.width().max(0) as usize * acquisition.height().max(0) as usize;
+        if length < num_pixels {
+            return fail(
+                ImcStatus::BufferTooSmall,
+                format!(
+                    "buffer holds {} values, but {} are required",
+                    length, num_pixels
+                ),
+            );
+        }
+
+        let image = match acquisition.channel_image(ChannelIdentifier::name(channel.name()), None) {
+            Ok(image) => image,
+            Err(error) => return fail(ImcStatus::ReadFailed, error.to_string()),
+        };
+
+        let mut intensities = image.into_intensities();
+        intensities.resize(num_pixels, f32::NAN);
+        ptr::copy_nonoverlapping(intensities.as_ptr(), buffer, num_pixels);
+
+        ImcStatus::Ok
+    })
+}
+
+/// Copy a description of the most recent error on the calling thread into `buffer`, as
+/// `imc_acquisition_description`. An empty string is returned if no error has occurred.
+///
+/// # Safety
+///
+/// `buffer` must be NULL or valid for writes of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imc_last_error(buffer: *mut c_char, length: usize) -> usize {
+    LAST_ERROR.with(|last_error| {
+        copy_string(
+            last_error.borrow().as_deref().unwrap_or_default(),
+            buffer,
+            length,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::io::BufWriter;
+
+    use imc_rs::{AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+
+    use super::*;
+
+    #[test]
+    fn read_through_c_interface() -> imc_rs::error::Result<()> {
+        let path = std::env::temp_dir().join(format!("imc-capi-{}.mcd", std::process::id()));
+
+        let mut writer = MCDWriter::new(BufWriter::new(File::create(&path)?));
+        writer.add_slide(SlideSpec::new(1))?;
+        writer.add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 100.0, 100.0))?;
+        writer.add_acquisition(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .description("ROI_001")
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            &[1.0, 2.0, 3.0, 4.0],
+        )?;
+        drop(writer.finish()?);
+
+        let c_path = CString::new(path.to_str().expect("Temporary path should be UTF-8"))
+            .expect("Path should not contain NUL");
+
+        unsafe {
+            let mcd = imc_mcd_open(c_path.as_ptr(), 0);
+            assert!(!mcd.is_null());
+            assert_eq!(imc_mcd_num_acquisitions(mcd), 1);
+            assert!(imc_mcd_acquisition(mcd, 1).is_null());
+
+            let acquisition = imc_mcd_acquisition(mcd, 0);
+            assert_eq!(imc_acquisition_id(acquisition), 1);
+            assert_eq!(
+                (
+                    imc_acquisition_width(acquisition),
+                    imc_acquisition_height(acquisition)
+                ),
+                (2, 1)
+            );
+            assert_eq!(imc_acquisition_num_channels(acquisition), 2);
+
+            // Strings are truncated to fit the buffer, returning the full length
+            let mut name = [0 as c_char; 4];
+            assert_eq!(
+                imc_acquisition_channel_name(acquisition, 1, name.as_mut_ptr(), name.len()),
+                5
+            );
+            assert_eq!(CStr::from_ptr(name.as_ptr()).to_str(), Ok("Er1"));
+            assert_eq!(
+                imc_acquisition_description(acquisition, ptr::null_mut(), 0),
+                7
+            );
+
+            let mut intensities = [0.0; 2];
+            assert_eq!(
+                imc_acquisition_read_channel(acquisition, 1, intensities.as_mut_ptr(), 2),
+                ImcStatus::Ok
+            );
+            assert_eq!(intensities, [2.0, 4.0]);
+
+            assert_eq!(
+                imc_acquisition_read_channel(acquisition, 1, intensities.as_mut_ptr(), 1),
+                ImcStatus::BufferTooSmall
+            );
+            assert!(imc_last_error(ptr::null_mut(), 0) > 0);
+
+            imc_mcd_free(mcd);
+        }
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -4,13 +4,18 @@
 //! python bindings for imc-rs, a library for accessing imaging mass cytometry data.
 
 use imc_rs::error::MCDError;
-use imc_rs::ChannelIdentifier;
+use imc_rs::halo::{self, ColumnData};
+use imc_rs::render::Colormap;
+use imc_rs::segmentation;
 use imc_rs::MCD;
+use imc_rs::{AcquisitionIdentifier, ChannelIdentifier, ChannelImage, Region};
 use numpy::ndarray::Array;
-use numpy::PyArray2;
+use numpy::ndarray::Dimension;
+use numpy::{PyArray, PyArray2, PyArrayDyn, PyReadonlyArray2};
 use pyo3::exceptions;
 use pyo3::exceptions::PyIOError;
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PySlice, PyTuple};
 
 // For passing back images/data
 use numpy::{IntoPyArray, PyArray3};
@@ -42,8 +47,8 @@ impl From<PyMcdError> for PyErr {
 impl Mcd {
     /// Parse an .mcd file, returning an object providing access to IMC data and accompanying metadata
     #[staticmethod]
-    pub fn parse(filename: &str) -> PyResult<Self> {
-        let mcd = match MCD::from_path(filename) {
+    pub fn parse(filename: &str, py: Python) -> PyResult<Self> {
+        let mcd = match py.allow_threads(|| MCD::from_path(filename)) {
             Ok(mcd) => mcd,
             Err(error) => return Err(PyMcdError::from(error).into()),
         };
@@ -54,13 +59,8 @@ impl Mcd {
     /// Parse an .mcd file, generating a temporary file for fast channel image access if one is not present, and
     /// returning an object providing access to IMC data and accompanying metadata
     #[staticmethod]
-    pub fn parse_with_dcm(filename: &str) -> PyResult<Self> {
-        let mcd = match MCD::from_path(filename) {
-            Ok(mcd) => mcd,
-            Err(error) => return Err(PyMcdError::from(error).into()),
-        };
-
-        let mcd = match mcd.with_dcm() {
+    pub fn parse_with_dcm(filename: &str, py: Python) -> PyResult<Self> {
+        let mcd = match py.allow_threads(|| MCD::from_path(filename)?.with_dcm()) {
             Ok(mcd) => mcd,
             Err(error) => return Err(PyMcdError::from(error).into()),
         };
@@ -87,8 +87,8 @@ impl Mcd {
     }
 
     /// Returns the XML data found within the .mcd file.
-    pub fn xml(&self) -> PyResult<String> {
-        match self.mcd.xml() {
+    pub fn xml(&self, py: Python) -> PyResult<String> {
+        match py.allow_threads(|| self.mcd.xml()) {
             Ok(xml) => Ok(xml),
             Err(error) => Err(PyMcdError::from(error).into()),
         }
@@ -141,25 +141,82 @@ impl Mcd {
     }
 
     pub fn acquisition(&self, id: u16) -> PyResult<Acquisition> {
-        for slide in self.mcd.slides() {
-            for panorama in slide.panoramas() {
-                for acquisition in panorama.acquisitions() {
-                    if acquisition.id() == id {
-                        return Ok(Acquisition {
-                            mcd: self.mcd.clone(),
-                            id,
-                            panorama_id: panorama.id(),
-                            slide_id: slide.id(),
-                        });
-                    }
+        let identifier = AcquisitionIdentifier::Id(id);
+
+        match self.mcd.acquisitions_matching(identifier.clone()).first() {
+            Some(acquisition) => Ok(Acquisition::new(&self.mcd, acquisition)),
+            // The error suggests acquisitions with similar IDs
+            None => Err(PyErr::new::<exceptions::PyValueError, _>(
+                MCDError::InvalidAcquisition {
+                    suggestions: self.mcd.acquisition_suggestions(&identifier),
+                    acquisition: identifier,
                 }
-            }
+                .to_string(),
+            )),
         }
+    }
 
-        Err(PyErr::new::<exceptions::PyValueError, _>(format!(
-            "No such acquisition with id {}",
-            id
-        )))
+    /// Returns all slides in the .mcd data, ordered by ID
+    pub fn slides(&self) -> Vec<Slide> {
+        self.mcd
+            .slide_ids()
+            .into_iter()
+            .map(|id| Slide {
+                mcd: self.mcd.clone(),
+                id,
+            })
+            .collect()
+    }
+
+    /// Returns all acquisitions in the .mcd data, ordered by ID
+    pub fn acquisitions(&self) -> Vec<Acquisition> {
+        self.mcd
+            .acquisitions()
+            .into_iter()
+            .map(|acquisition| Acquisition::new(&self.mcd, acquisition))
+            .collect()
+    }
+
+    /// Returns a dict of lazily evaluated dask arrays (channels, height, width) of every channel of each
+    /// acquisition, keyed by acquisition ID (see `Acquisition.to_dask`). Requires dask to be installed.
+    pub fn to_dask<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let arrays = PyDict::new(py);
+
+        for acquisition in self.acquisitions() {
+            arrays.set_item(acquisition.id, acquisition.to_dask(None, None, py)?)?;
+        }
+
+        Ok(arrays)
+    }
+
+    fn __len__(&self) -> usize {
+        self.mcd.acquisitions().len()
+    }
+
+    /// Iterate over all acquisitions in the .mcd data, ordered by ID
+    fn __iter__(&self, py: Python) -> PyResult<ItemIterator> {
+        ItemIterator::new(py, self.acquisitions())
+    }
+
+    /// Returns the acquisition with the given ID (int) or description (str, e.g. `mcd["ROI_001"]`)
+    fn __getitem__(&self, key: &PyAny) -> PyResult<Acquisition> {
+        let identifier = match key.extract::<u16>() {
+            Ok(id) => AcquisitionIdentifier::Id(id),
+            Err(_) => AcquisitionIdentifier::description(key.extract()?),
+        };
+
+        match self.mcd.find_acquisition(identifier) {
+            Ok(acquisition) => Ok(Acquisition::new(&self.mcd, acquisition)),
+            Err(error) => Err(exceptions::PyKeyError::new_err(error.to_string())),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Mcd(slides={}, acquisitions={})",
+            self.mcd.slide_ids().len(),
+            self.mcd.acquisitions().len()
+        )
     }
 
     pub fn channels(&self) -> Vec<AcquisitionChannel> {
@@ -182,6 +239,15 @@ struct AcquisitionChannel {
     label: String,
 }
 
+impl From<&imc_rs::AcquisitionChannel> for AcquisitionChannel {
+    fn from(channel: &imc_rs::AcquisitionChannel) -> Self {
+        AcquisitionChannel {
+            name: channel.name().to_string(),
+            label: channel.label().to_string(),
+        }
+    }
+}
+
 #[pymethods]
 impl AcquisitionChannel {
     pub fn name(&self) -> &str {
@@ -191,6 +257,46 @@ impl AcquisitionChannel {
     pub fn label(&self) -> &str {
         &self.label
     }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "AcquisitionChannel(name={:?}, label={:?})",
+            self.name, self.label
+        )
+    }
+}
+
+/// Iterator over the slides, panoramas, acquisitions or channels of their parent
+#[pyclass]
+struct ItemIterator {
+    items: std::vec::IntoIter<PyObject>,
+}
+
+impl ItemIterator {
+    fn new<T: pyo3::PyClass + Into<PyClassInitializer<T>>>(
+        py: Python,
+        items: Vec<T>,
+    ) -> PyResult<Self> {
+        let items = items
+            .into_iter()
+            .map(|item| Ok(Py::new(py, item)?.into_py(py)))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(ItemIterator {
+            items: items.into_iter(),
+        })
+    }
+}
+
+#[pymethods]
+impl ItemIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
+        slf.items.next()
+    }
 }
 
 #[pyclass]
@@ -278,7 +384,7 @@ impl Slide {
     pub fn image<'py>(&self, py: Python<'py>) -> &'py PyArray3<u8> {
         let slide = self.get_slide();
 
-        let image = slide.image().as_rgba8().unwrap();
+        let image = py.allow_threads(|| slide.image()).as_rgba8().unwrap();
         let width = image.width() as usize;
         let height = image.height() as usize;
         let raw_image = image.into_raw();
@@ -298,14 +404,15 @@ impl Slide {
     ) -> PyResult<&'py PyArray3<u8>> {
         let slide = self.get_slide();
 
-        let overview_image = match channel {
-            Some(channel) => {
-                let identifier = ChannelIdentifier::Name(channel.name.clone());
-
-                slide.create_overview_image(width.unwrap_or(7500), Some((&identifier, max_value)))
-            }
-            None => slide.create_overview_image(width.unwrap_or(7500), None),
-        };
+        let identifier = channel.map(|channel| ChannelIdentifier::Name(channel.name.clone()));
+        let overview_image = py.allow_threads(|| {
+            slide.create_overview_image(
+                width.unwrap_or(7500),
+                identifier
+                    .as_ref()
+                    .map(|identifier| (identifier, max_value)),
+            )
+        });
 
         let image = match overview_image {
             Ok(image) => image,
@@ -323,6 +430,54 @@ impl Slide {
         let array = Array::from_shape_vec((height, width, 4), raw_image).unwrap();
         Ok(array.into_pyarray(py))
     }
+
+    /// Returns the panoramas on the slide, ordered by ID
+    pub fn panoramas(&self) -> Vec<Panorama> {
+        self.get_slide()
+            .panorama_ids()
+            .into_iter()
+            .map(|id| Panorama {
+                mcd: self.mcd.clone(),
+                id,
+                slide_id: self.id,
+            })
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.get_slide().panorama_ids().len()
+    }
+
+    /// Iterate over the panoramas on the slide, ordered by ID
+    fn __iter__(&self, py: Python) -> PyResult<ItemIterator> {
+        ItemIterator::new(py, self.panoramas())
+    }
+
+    /// Returns the panorama on the slide with the given ID
+    fn __getitem__(&self, id: u16) -> PyResult<Panorama> {
+        match self.get_slide().panorama(id) {
+            Some(_) => Ok(Panorama {
+                mcd: self.mcd.clone(),
+                id,
+                slide_id: self.id,
+            }),
+            None => Err(exceptions::PyKeyError::new_err(format!(
+                "No such panorama with id {} on slide {}",
+                id, self.id
+            ))),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let slide = self.get_slide();
+
+        format!(
+            "Slide(id={}, description={:?}, panoramas={})",
+            self.id,
+            slide.description(),
+            slide.panorama_ids().len()
+        )
+    }
 }
 
 #[pyclass]
@@ -356,7 +511,11 @@ impl Panorama {
     pub fn image<'py>(&self, py: Python<'py>) -> &'py PyArray3<u8> {
         let panorama = self.get_panorama();
 
-        let image = panorama.image().unwrap().as_rgba8().unwrap();
+        let image = py
+            .allow_threads(|| panorama.image())
+            .unwrap()
+            .as_rgba8()
+            .unwrap();
         let width = image.width() as usize;
         let height = image.height() as usize;
         let raw_image = image.into_raw();
@@ -370,9 +529,65 @@ impl Panorama {
     pub fn acquisition_ids(&self) -> PyResult<Vec<u16>> {
         Ok(self.get_panorama().acquisition_ids())
     }
+
+    /// Returns the acquisitions within the panorama, ordered by ID
+    pub fn acquisitions(&self) -> Vec<Acquisition> {
+        self.get_panorama()
+            .acquisitions()
+            .into_iter()
+            .map(|acquisition| Acquisition::new(&self.mcd, acquisition))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.get_panorama().acquisition_ids().len()
+    }
+
+    /// Iterate over the acquisitions within the panorama, ordered by ID
+    fn __iter__(&self, py: Python) -> PyResult<ItemIterator> {
+        ItemIterator::new(py, self.acquisitions())
+    }
+
+    /// Returns the acquisition within the panorama with the given ID (int) or description (str)
+    fn __getitem__(&self, key: &PyAny) -> PyResult<Acquisition> {
+        let panorama = self.get_panorama();
+
+        let acquisition = match key.extract::<u16>() {
+            Ok(id) => panorama.acquisition(id),
+            Err(_) => {
+                let description: &str = key.extract()?;
+
+                panorama
+                    .acquisitions()
+                    .into_iter()
+                    .find(|acquisition| acquisition.description() == description)
+            }
+        };
+
+        match acquisition {
+            Some(acquisition) => Ok(Acquisition::new(&self.mcd, acquisition)),
+            None => Err(exceptions::PyKeyError::new_err(format!(
+                "No such acquisition {} in panorama {}",
+                key, self.id
+            ))),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let panorama = self.get_panorama();
+
+        format!(
+            "Panorama(id={}, slide_id={}, description={:?}, acquisitions={})",
+            self.id,
+            self.slide_id,
+            panorama.description(),
+            panorama.acquisition_ids().len()
+        )
+    }
 }
 
 #[pyclass]
+#[derive(Clone)]
 struct Acquisition {
     mcd: Arc<imc_rs::MCD<File>>,
 
@@ -382,6 +597,17 @@ struct Acquisition {
 }
 
 impl Acquisition {
+    fn new(mcd: &Arc<MCD<File>>, acquisition: &imc_rs::Acquisition<File>) -> Self {
+        let reference = acquisition.reference();
+
+        Acquisition {
+            mcd: mcd.clone(),
+            id: reference.id(),
+            panorama_id: reference.panorama(),
+            slide_id: reference.slide(),
+        }
+    }
+
     fn get_acquisition(&self) -> &imc_rs::Acquisition<File> {
         self.mcd
             .slide(self.slide_id)
@@ -391,6 +617,35 @@ impl Acquisition {
             .acquisition(self.id)
             .expect("Should be valid acquisition id")
     }
+
+    /// Convert a region (x, y, width, height) to a `Region`, checking that it lies within the acquisition
+    fn region(&self, region: Option<(u32, u32, u32, u32)>) -> PyResult<Option<Region>> {
+        let acquisition = self.get_acquisition();
+
+        match region {
+            Some((x, y, width, height)) => {
+                let region = Region {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+
+                if x + width > acquisition.width().max(0) as u32
+                    || y + height > acquisition.height().max(0) as u32
+                {
+                    return Err(PyMcdError::from(MCDError::InvalidRegion {
+                        region,
+                        acquisition: acquisition.reference(),
+                    })
+                    .into());
+                }
+
+                Ok(Some(region))
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 #[pymethods]
@@ -422,7 +677,10 @@ impl Acquisition {
     pub fn before_ablation_image<'py>(&self, py: Python<'py>) -> &'py PyArray3<u8> {
         let acquisition = self.get_acquisition();
 
-        let image = acquisition.before_ablation_image().as_rgba8().unwrap();
+        let image = py
+            .allow_threads(|| acquisition.before_ablation_image())
+            .as_rgba8()
+            .unwrap();
         let width = image.width() as usize;
         let height = image.height() as usize;
         let raw_image = image.into_raw();
@@ -436,7 +694,10 @@ impl Acquisition {
     pub fn after_ablation_image<'py>(&self, py: Python<'py>) -> &'py PyArray3<u8> {
         let acquisition = self.get_acquisition();
 
-        let image = acquisition.after_ablation_image().as_rgba8().unwrap();
+        let image = py
+            .allow_threads(|| acquisition.after_ablation_image())
+            .as_rgba8()
+            .unwrap();
         let width = image.width() as usize;
         let height = image.height() as usize;
         let raw_image = image.into_raw();
@@ -462,46 +723,750 @@ impl Acquisition {
         channels
     }
 
+    fn __len__(&self) -> usize {
+        self.get_acquisition().channels().len()
+    }
+
+    /// Iterate over the channels of the acquisition
+    fn __iter__(&self, py: Python) -> PyResult<ItemIterator> {
+        ItemIterator::new(py, self.channels())
+    }
+
+    /// Returns the channel at the given index (int), or with the given name or label (str, e.g.
+    /// `acquisition["Ir191"]` or `acquisition["DNA1"]`)
+    fn __getitem__(&self, key: &PyAny) -> PyResult<AcquisitionChannel> {
+        let acquisition = self.get_acquisition();
+
+        let channel = match key.extract::<usize>() {
+            Ok(index) => acquisition.channels().get(index).ok_or_else(|| {
+                exceptions::PyKeyError::new_err(format!(
+                    "No such channel {} in acquisition {}",
+                    index, self.id
+                ))
+            })?,
+            Err(_) => {
+                let text: &str = key.extract()?;
+
+                acquisition
+                    .channel(ChannelIdentifier::name(text))
+                    .or_else(|| acquisition.channel(ChannelIdentifier::label(text)))
+                    .ok_or_else(|| {
+                        // Lists the available channels and the closest matches
+                        let error = acquisition.channel_not_found(ChannelIdentifier::name(text));
+                        exceptions::PyKeyError::new_err(error.to_string())
+                    })?
+            }
+        };
+
+        Ok(AcquisitionChannel::from(channel))
+    }
+
+    fn __repr__(&self) -> String {
+        let acquisition = self.get_acquisition();
+
+        format!(
+            "Acquisition(id={}, description={:?}, width={}, height={}, channels={})",
+            self.id,
+            acquisition.description(),
+            acquisition.width(),
+            acquisition.height(),
+            acquisition.channels().len()
+        )
+    }
+
+    /// Returns the intensities of the channel as a C-contiguous float32 array (height, width). Pixels which were
+    /// not acquired are NaN. If `out` is provided, the intensities are written into it (it must be a
+    /// C-contiguous float32 array of the same shape) and it is returned, so that buffers can be reused.
     pub fn channel_data<'py>(
         &self,
         channel: &'py AcquisitionChannel,
+        out: Option<&'py PyArray2<f32>>,
         py: Python<'py>,
     ) -> PyResult<&'py PyArray2<f32>> {
+        self.channel_image(channel, None, out, py)
+    }
+
+    /// Returns the intensities of the channel as a C-contiguous float32 array (height, width), optionally limited
+    /// to the region (x, y, width, height) in pixels. Pixels which were not acquired are NaN. If `out` is
+    /// provided, the intensities are written into it (it must be a C-contiguous float32 array of the same shape)
+    /// and it is returned, so that buffers can be reused.
+    pub fn channel_image<'py>(
+        &self,
+        channel: &'py AcquisitionChannel,
+        region: Option<(u32, u32, u32, u32)>,
+        out: Option<&'py PyArray2<f32>>,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyArray2<f32>> {
+        let region = self.region(region)?;
+        let identifier = ChannelIdentifier::Name(channel.name.clone());
+        let acquisition = self.get_acquisition();
+        let channel_image = py
+            .allow_threads(|| acquisition.channel_image(&identifier, region))
+            .map_err(PyMcdError::from)?;
+
+        let shape = [
+            channel_image.height() as usize,
+            channel_image.width() as usize,
+        ];
+
+        match out {
+            Some(out) => {
+                copy_into(out, &shape, &[channel_image])?;
+                Ok(out)
+            }
+            // The decoded intensities are handed to numpy without copying
+            None => PyArray::from_vec(py, channel_image.into_intensities()).reshape(shape),
+        }
+    }
+
+    /// Returns the intensities of the channels as a C-contiguous float32 array (channels, height, width), in the
+    /// order given, optionally limited to the region (x, y, width, height) in pixels. Pixels which were not
+    /// acquired are NaN. The channels are read together, which is considerably faster than reading each in turn.
+    /// If `out` is provided, the intensities are written into it (it must be a C-contiguous float32 array of the
+    /// same shape) and it is returned, so that buffers can be reused.
+    pub fn channel_stack<'py>(
+        &self,
+        channels: Vec<PyRef<'py, AcquisitionChannel>>,
+        region: Option<(u32, u32, u32, u32)>,
+        out: Option<&'py PyArray3<f32>>,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyArray3<f32>> {
+        let acquisition = self.get_acquisition();
+        let region = self.region(region)?;
+
+        let identifiers: Vec<_> = channels
+            .iter()
+            .map(|channel| ChannelIdentifier::Name(channel.name.clone()))
+            .collect();
+        let channel_images = py
+            .allow_threads(|| acquisition.channel_images(&identifiers, region))
+            .map_err(PyMcdError::from)?;
+
+        let (width, height) = match region {
+            Some(region) => (region.width as usize, region.height as usize),
+            None => (
+                acquisition.width().max(0) as usize,
+                acquisition.height().max(0) as usize,
+            ),
+        };
+        let shape = [identifiers.len(), height, width];
+
+        if let Some(out) = out {
+            copy_into(out, &shape, &channel_images)?;
+            return Ok(out);
+        }
+
+        let mut data = Vec::with_capacity(shape.iter().product());
+        for channel_image in channel_images {
+            data.extend(channel_image.into_intensities());
+        }
+
+        PyArray::from_vec(py, data).reshape(shape)
+    }
+
+    /// Returns the width and height (in pixels) of the chunks in which the channel images are stored in the .dcm
+    /// file, or None if the data is read from the .mcd file (see `Mcd.parse_with_dcm`)
+    pub fn chunk_size(&self) -> Option<u32> {
+        self.get_acquisition().dcm_chunk_size()
+    }
+
+    /// Returns an array-like view (channels, height, width) of the channels (defaulting to all channels), which
+    /// only reads the intensities when it is indexed. This can be wrapped by dask (`dask.array.from_array`) or
+    /// zarr-like consumers; see `to_dask`.
+    pub fn lazy(&self, channels: Option<Vec<PyRef<AcquisitionChannel>>>) -> LazyAcquisition {
+        let acquisition = self.get_acquisition();
+
+        let identifiers = match channels {
+            Some(channels) => channels
+                .iter()
+                .map(|channel| ChannelIdentifier::Name(channel.name.clone()))
+                .collect(),
+            None => acquisition
+                .channels()
+                .iter()
+                .map(|channel| ChannelIdentifier::Name(channel.name().to_string()))
+                .collect(),
+        };
+
+        LazyAcquisition {
+            acquisition: self.clone(),
+            identifiers,
+            width: acquisition.width().max(0) as usize,
+            height: acquisition.height().max(0) as usize,
+        }
+    }
+
+    /// Returns a lazily evaluated dask array (channels, height, width) of the channels (defaulting to all
+    /// channels), for browsing large acquisitions (e.g. in napari) without reading them into memory. Pixels
+    /// which were not acquired are NaN. The chunks (rows, columns) default to the chunks of the .dcm file, with
+    /// each channel in its own chunk, or to bands of 256 rows including every channel when reading from the .mcd
+    /// file (as all channels of a pixel are stored together). Requires dask to be installed.
+    pub fn to_dask<'py>(
+        &self,
+        channels: Option<Vec<PyRef<AcquisitionChannel>>>,
+        chunks: Option<(usize, usize)>,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyAny> {
+        let lazy = self.lazy(channels);
+
+        let chunks = match (chunks, self.chunk_size()) {
+            (Some((rows, columns)), _) => (1, rows, columns),
+            (None, Some(chunk_size)) => (1, chunk_size as usize, chunk_size as usize),
+            (None, None) => (lazy.identifiers.len().max(1), 256, lazy.width.max(1)),
+        };
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("chunks", chunks)?;
+        kwargs.set_item(
+            "name",
+            format!(
+                "pyimc-{}-{}-{}-{}",
+                self.slide_id,
+                self.panorama_id,
+                self.id,
+                lazy.identifiers.len()
+            ),
+        )?;
+        // Avoid dask indexing the array to determine its type, which would read data
+        kwargs.set_item(
+            "meta",
+            py.import("numpy")?
+                .call_method1("empty", ((0, 0, 0), "float32"))?,
+        )?;
+
+        py.import("dask.array")?
+            .call_method("from_array", (Py::new(py, lazy)?,), Some(kwargs))
+    }
+
+    /// Returns an iterator over the intensities of the channel in chunks of `rows` rows (defaulting to 64), each
+    /// a tuple of the first row of the chunk and a float32 array (rows, width). Pixels which were not acquired are
+    /// NaN. Each chunk is read without holding the GIL, so reading can be interleaved with other work (e.g. by
+    /// fetching each chunk in an executor of an asyncio event loop).
+    pub fn channel_chunks(
+        &self,
+        channel: &AcquisitionChannel,
+        rows: Option<u32>,
+    ) -> PyResult<ChannelChunks> {
+        let rows = rows.unwrap_or(64);
+        if rows == 0 {
+            return Err(exceptions::PyValueError::new_err(
+                "rows must be greater than 0",
+            ));
+        }
+
+        Ok(ChannelChunks {
+            acquisition: Acquisition {
+                mcd: self.mcd.clone(),
+                id: self.id,
+                panorama_id: self.panorama_id,
+                slide_id: self.slide_id,
+            },
+            identifier: ChannelIdentifier::Name(channel.name.clone()),
+            rows,
+            next_row: 0,
+        })
+    }
+
+    /// Render the channel as an RGBA image (height, width, 4) using the specified colormap (viridis, magma,
+    /// grayscale, red, green, blue or a hex colour such as #00ff00). Intensities are scaled between `min_value`
+    /// and `max_value`, which default to the intensity range of the channel.
+    pub fn channel_rgba<'py>(
+        &self,
+        channel: &'py AcquisitionChannel,
+        colormap: Option<&str>,
+        min_value: Option<f32>,
+        max_value: Option<f32>,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyArray3<u8>> {
         let acquisition = self.get_acquisition();
 
+        let colormap: Colormap = colormap
+            .unwrap_or("viridis")
+            .parse()
+            .map_err(|error: MCDError| exceptions::PyValueError::new_err(error.to_string()))?;
+
         let identifier = ChannelIdentifier::Name(channel.name.clone());
-        let channel_data = match acquisition.channel_image(&identifier, None) {
-            Ok(channel_data) => channel_data,
-            Err(error) => {
-                return Err(exceptions::PyIOError::new_err(error.to_string()));
+        let channel_image = py
+            .allow_threads(|| acquisition.channel_image(&identifier, None))
+            .map_err(PyMcdError::from)?;
+
+        let (min_intensity, max_intensity) = channel_image.intensity_range();
+        let range = (
+            min_value.unwrap_or(min_intensity),
+            max_value.unwrap_or(max_intensity),
+        );
+
+        let image = channel_image.to_rgba(colormap, Some(range));
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+
+        let array = Array::from_shape_vec((height, width, 4), image.into_raw()).unwrap();
+        Ok(array.into_pyarray(py))
+    }
+}
+
+/// Array-like view (channels, height, width) of the channels of an acquisition, which reads the intensities only
+/// when indexed (see `Acquisition.lazy`)
+#[pyclass]
+struct LazyAcquisition {
+    acquisition: Acquisition,
+    identifiers: Vec<ChannelIdentifier>,
+    width: usize,
+    height: usize,
+}
+
+#[pymethods]
+impl LazyAcquisition {
+    #[getter]
+    fn shape(&self) -> (usize, usize, usize) {
+        (self.identifiers.len(), self.height, self.width)
+    }
+
+    #[getter]
+    fn ndim(&self) -> usize {
+        3
+    }
+
+    #[getter]
+    fn dtype<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        py.import("numpy")?.call_method1("dtype", ("float32",))
+    }
+
+    fn __len__(&self) -> usize {
+        self.identifiers.len()
+    }
+
+    /// Read the intensities selected by integers and slices (with any step) along each dimension
+    fn __getitem__<'py>(&self, key: &PyAny, py: Python<'py>) -> PyResult<&'py PyArrayDyn<f32>> {
+        let key: Vec<&PyAny> = match key.downcast::<PyTuple>() {
+            Ok(key) => key.iter().collect(),
+            Err(_) => vec![key],
+        };
+        if key.len() > 3 {
+            return Err(exceptions::PyIndexError::new_err(
+                "too many indices for array (channels, height, width)",
+            ));
+        }
+
+        let (num_channels, height, width) = self.shape();
+        let mut selections = Vec::with_capacity(3);
+        for (axis, length) in [num_channels, height, width].into_iter().enumerate() {
+            selections.push(match key.get(axis) {
+                Some(index) => Selection::from_index(index, length)?,
+                None => Selection::all(length),
+            });
+        }
+
+        let (channels, rows, columns) = (&selections[0], &selections[1], &selections[2]);
+        let shape: Vec<_> = selections
+            .iter()
+            .filter(|selection| !selection.is_integer)
+            .map(|selection| selection.indices.len())
+            .collect();
+
+        let num_values = channels.indices.len() * rows.indices.len() * columns.indices.len();
+        if num_values == 0 {
+            return PyArray::from_vec(py, Vec::new()).reshape(shape);
+        }
+
+        // Read the bounding region of the selected pixels, then take every step-th pixel
+        let region = Region {
+            x: columns.min() as u32,
+            y: rows.min() as u32,
+            width: (columns.max() - columns.min() + 1) as u32,
+            height: (rows.max() - rows.min() + 1) as u32,
+        };
+        let identifiers: Vec<_> = channels
+            .indices
+            .iter()
+            .map(|&index| self.identifiers[index].clone())
+            .collect();
+
+        let acquisition = self.acquisition.get_acquisition();
+        let channel_images = py
+            .allow_threads(|| acquisition.channel_images(&identifiers, Some(region)))
+            .map_err(PyMcdError::from)?;
+
+        let mut data = Vec::with_capacity(num_values);
+        for channel_image in channel_images {
+            let intensities = channel_image.intensities();
+
+            for &y in &rows.indices {
+                for &x in &columns.indices {
+                    let index =
+                        (y - region.y as usize) * region.width as usize + (x - region.x as usize);
+                    data.push(intensities.get(index).copied().unwrap_or(f32::NAN));
+                }
             }
+        }
+
+        PyArray::from_vec(py, data).reshape(shape)
+    }
+}
+
+/// The indices selected along one dimension of a `LazyAcquisition`
+struct Selection {
+    indices: Vec<usize>,
+    /// Whether the dimension was selected by an integer (and so is removed from the result)
+    is_integer: bool,
+}
+
+impl Selection {
+    fn all(length: usize) -> Self {
+        Selection {
+            indices: (0..length).collect(),
+            is_integer: false,
+        }
+    }
+
+    fn from_index(index: &PyAny, length: usize) -> PyResult<Self> {
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let indices = slice.indices(length as _)?;
+
+            let mut selected = Vec::with_capacity(indices.slicelength as usize);
+            let mut current = indices.start;
+            for _ in 0..indices.slicelength {
+                selected.push(current as usize);
+                current += indices.step;
+            }
+
+            return Ok(Selection {
+                indices: selected,
+                is_integer: false,
+            });
+        }
+
+        let position: isize = index.extract()?;
+        let resolved = if position < 0 {
+            position + length as isize
+        } else {
+            position
+        };
+        if resolved < 0 || resolved as usize >= length {
+            return Err(exceptions::PyIndexError::new_err(format!(
+                "index {} is out of bounds for axis with size {}",
+                position, length
+            )));
+        }
+
+        Ok(Selection {
+            indices: vec![resolved as usize],
+            is_integer: true,
+        })
+    }
+
+    fn min(&self) -> usize {
+        self.indices.iter().copied().min().unwrap_or(0)
+    }
+
+    fn max(&self) -> usize {
+        self.indices.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// Iterator over the intensities of a channel in chunks of rows (see `Acquisition.channel_chunks`)
+#[pyclass]
+struct ChannelChunks {
+    acquisition: Acquisition,
+    identifier: ChannelIdentifier,
+    rows: u32,
+    next_row: u32,
+}
+
+#[pymethods]
+impl ChannelChunks {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let chunks: &ChannelChunks = &slf;
+        let acquisition = chunks.acquisition.get_acquisition();
+        let (width, height) = (
+            acquisition.width().max(0) as u32,
+            acquisition.height().max(0) as u32,
+        );
+
+        let y = chunks.next_row;
+        if y >= height {
+            return Ok(None);
+        }
+
+        let region = Region {
+            x: 0,
+            y,
+            width,
+            height: chunks.rows.min(height - y),
         };
+        let channel_image = py
+            .allow_threads(|| acquisition.channel_image(&chunks.identifier, Some(region)))
+            .map_err(PyMcdError::from)?;
+        slf.next_row += region.height;
+
+        let shape = [region.height as usize, region.width as usize];
+        let array = PyArray::from_vec(py, channel_image.into_intensities()).reshape(shape)?;
+
+        Ok(Some((y, array).into_py(py)))
+    }
+}
+
+/// Cell segmentation and analysis data produced by HALO (stored as .csv)
+#[pyclass]
+struct CellData {
+    data: halo::CellData,
+}
+
+#[pymethods]
+impl CellData {
+    /// Parse the .csv file produced by HALO cell detection
+    #[staticmethod]
+    pub fn parse(filename: &str, py: Python) -> PyResult<Self> {
+        let data = py
+            .allow_threads(|| halo::parse_from_path(filename))
+            .map_err(PyMcdError::from)?;
 
-        let data = match !channel_data.is_complete() {
-            true => channel_data.intensities().to_vec(),
-            false => {
-                let mut data =
-                    vec![0.0; acquisition.height() as usize * acquisition.width() as usize];
+        Ok(CellData { data })
+    }
 
-                let intensities = channel_data.intensities();
+    /// Returns the names of the columns, in the order in which they appear in the file
+    pub fn columns(&self) -> Vec<String> {
+        self.data
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect()
+    }
 
-                for (i, &intensity) in intensities.iter().enumerate() {
-                    data[i] = intensity;
+    /// Returns the data in the column with the given name, as a numpy array (float64, int64 or bool) or, for text
+    /// columns, a list of str. Missing values are NaN, so integer columns with missing values are returned as
+    /// float64, and binary columns with missing values as a list of bool or None.
+    pub fn column(&self, name: &str, py: Python) -> PyResult<PyObject> {
+        let column_data = self
+            .data
+            .header(name)
+            .and_then(|column| self.data.column_data(column.column_number()))
+            .ok_or_else(|| exceptions::PyKeyError::new_err(format!("No such column {}", name)))?;
+
+        Ok(match column_data {
+            ColumnData::Text(ids) => ids
+                .iter()
+                .map(|&id| self.data.text(id).unwrap_or_default())
+                .collect::<Vec<_>>()
+                .into_py(py),
+            ColumnData::Binary(data) => match data.iter().copied().collect::<Option<Vec<_>>>() {
+                Some(data) => PyArray::from_vec(py, data).into_py(py),
+                None => data.clone().into_py(py),
+            },
+            ColumnData::Integer(data) => match data.iter().copied().collect::<Option<Vec<_>>>() {
+                Some(data) => PyArray::from_vec(py, data).into_py(py),
+                None => {
+                    let data: Vec<_> = data
+                        .iter()
+                        .map(|value| value.map_or(f64::NAN, |value| value as f64))
+                        .collect();
+
+                    PyArray::from_vec(py, data).into_py(py)
                 }
+            },
+            ColumnData::Float(data) => PyArray::from_slice(py, data).into_py(py),
+        })
+    }
+
+    /// Returns a dict of the data in each column (see `column`), which can be passed directly to
+    /// `pandas.DataFrame`
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
 
-                data
+        for name in self.columns() {
+            dict.set_item(&name, self.column(&name, py)?)?;
+        }
+
+        Ok(dict)
+    }
+
+    /// Returns the bounding box of each cell as an int64 array (cells, 4) of (min_x, min_y, width, height)
+    pub fn boundaries<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray2<i64>> {
+        let data: Vec<_> = self
+            .data
+            .boundaries()
+            .map_err(PyMcdError::from)?
+            .flat_map(|boundary| {
+                [
+                    boundary.min_x,
+                    boundary.min_y,
+                    boundary.width,
+                    boundary.height,
+                ]
+            })
+            .collect();
+
+        PyArray::from_vec(py, data).reshape([self.data.num_cells(), 4])
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.num_cells()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CellData(cells={}, columns={})",
+            self.data.num_cells(),
+            self.data.columns().len()
+        )
+    }
+}
+
+/// A cell segmentation mask, where each pixel holds the ID of the cell it belongs to (0 being background)
+#[pyclass]
+struct CellMask {
+    mask: segmentation::CellMask,
+}
+
+#[pymethods]
+impl CellMask {
+    /// Create a mask from a 2-D array (height, width) of cell IDs
+    #[staticmethod]
+    pub fn from_array(labels: PyReadonlyArray2<u32>) -> PyResult<Self> {
+        let labels = labels.as_array();
+        let (height, width) = labels.dim();
+        let labels = labels.iter().copied().collect();
+
+        let mask = segmentation::CellMask::new(width as u32, height as u32, labels)
+            .map_err(PyMcdError::from)?;
+
+        Ok(CellMask { mask })
+    }
+
+    /// Read a mask from a single channel TIFF with integer pixels
+    #[staticmethod]
+    pub fn from_path(filename: &str, py: Python) -> PyResult<Self> {
+        let mask = py
+            .allow_threads(|| segmentation::CellMask::from_path(filename))
+            .map_err(PyMcdError::from)?;
+
+        Ok(CellMask { mask })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.mask.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.mask.height()
+    }
+
+    /// Returns the cell ID of each pixel as a uint32 array (height, width)
+    pub fn labels<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray2<u32>> {
+        PyArray::from_slice(py, self.mask.labels())
+            .reshape([self.mask.height() as usize, self.mask.width() as usize])
+    }
+
+    /// Returns the IDs of all cells in the mask, in ascending order
+    pub fn cell_ids(&self) -> Vec<u32> {
+        self.mask.cell_ids()
+    }
+
+    /// Summarise the intensities of every channel of the acquisition within each cell, returning a dict of
+    /// numpy arrays (one value per cell) which can be passed directly to `pandas.DataFrame`. The dict holds the
+    /// `cell_id`, `area` (in pixels) and `centroid_x` and `centroid_y` (in pixels) of each cell, followed by the
+    /// `statistic` (mean, median or std, defaulting to mean) of each channel, keyed by the channel label (or name,
+    /// if the channel has no label).
+    pub fn summarise<'py>(
+        &self,
+        acquisition: &Acquisition,
+        statistic: Option<&str>,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyDict> {
+        let statistic: fn(&segmentation::Summary) -> f32 = match statistic.unwrap_or("mean") {
+            "mean" => |summary| summary.mean(),
+            "median" => |summary| summary.median(),
+            "std" => |summary| summary.std_dev(),
+            statistic => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "Unknown statistic {} (expected mean, median or std)",
+                    statistic
+                )))
             }
         };
 
-        let array = Array::from_shape_vec(
-            (acquisition.height() as usize, acquisition.width() as usize),
-            data,
+        let acquisition = acquisition.get_acquisition();
+        let cells = py
+            .allow_threads(|| self.mask.summarise(acquisition))
+            .map_err(PyMcdError::from)?;
+
+        let dict = PyDict::new(py);
+
+        let cell_ids: Vec<_> = cells.iter().map(|cell| cell.id()).collect();
+        dict.set_item("cell_id", PyArray::from_vec(py, cell_ids))?;
+        let areas: Vec<_> = cells.iter().map(|cell| cell.num_pixels() as u64).collect();
+        dict.set_item("area", PyArray::from_vec(py, areas))?;
+        let (centroid_x, centroid_y): (Vec<_>, Vec<_>) =
+            cells.iter().map(|cell| cell.centroid()).unzip();
+        dict.set_item("centroid_x", PyArray::from_vec(py, centroid_x))?;
+        dict.set_item("centroid_y", PyArray::from_vec(py, centroid_y))?;
+
+        for (index, channel) in acquisition.channels().iter().enumerate() {
+            let name = if channel.label().is_empty() {
+                channel.name()
+            } else {
+                channel.label()
+            };
+            let values: Vec<_> = cells
+                .iter()
+                .map(|cell| cell.markers().get(index).map_or(f32::NAN, statistic))
+                .collect();
+
+            dict.set_item(name, PyArray::from_vec(py, values))?;
+        }
+
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CellMask(width={}, height={}, cells={})",
+            self.mask.width(),
+            self.mask.height(),
+            self.mask.cell_ids().len()
         )
-        .unwrap();
-        Ok(array.into_pyarray(py))
     }
 }
 
+/// Copy the intensities of the channel images (one after another) into `out`, which must be a C-contiguous array
+/// of the given shape. Pixels which were not acquired are NaN.
+fn copy_into<D: Dimension>(
+    out: &PyArray<f32, D>,
+    shape: &[usize],
+    channel_images: &[ChannelImage],
+) -> PyResult<()> {
+    if out.shape() != shape {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "out has shape {:?}, but {:?} is required",
+            out.shape(),
+            shape
+        )));
+    }
+
+    // SAFETY: the GIL is held and no other reference to the contents of `out` is taken while it is written
+    let buffer = unsafe { out.as_slice_mut() }
+        .map_err(|error| exceptions::PyValueError::new_err(error.to_string()))?;
+
+    // Each image is (height, width), the last two dimensions
+    let image_size: usize = shape[shape.len() - 2..].iter().product();
+    for (channel_image, buffer) in channel_images
+        .iter()
+        .zip(buffer.chunks_mut(image_size.max(1)))
+    {
+        let intensities = channel_image.intensities();
+        let num_stored = intensities.len().min(buffer.len());
+
+        buffer[..num_stored].copy_from_slice(&intensities[..num_stored]);
+        buffer[num_stored..].fill(f32::NAN);
+    }
+
+    Ok(())
+}
+
 /// A Python module for reading and processing imaging mass cytometry data (stored in .mcd format).
 ///
 /// # Quick start
@@ -529,6 +1494,12 @@ impl Acquisition {
 #[pymodule]
 fn pyimc(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Mcd>()?;
+    m.add_class::<Slide>()?;
+    m.add_class::<Panorama>()?;
+    m.add_class::<Acquisition>()?;
+    m.add_class::<AcquisitionChannel>()?;
+    m.add_class::<CellData>()?;
+    m.add_class::<CellMask>()?;
 
     Ok(())
 }
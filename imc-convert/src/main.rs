@@ -0,0 +1,266 @@
+use std::{error::Error, path::Path};
+
+use clap::{Parser, ValueEnum};
+use imc_rs::{
+    convert::{self, CancellationToken, Codec, ConvertOptions, Progress},
+    error::MCDError,
+    export::{CollisionPolicy, ExportFormat, ExportOptions, Exporter, NameSanitizer},
+    AcquisitionIdentifier, AcquisitionRef, MCD,
+};
+
+/// imc-convert creates .dcm files (for fast access to channel images) and converts IMC data sets stored in the
+/// *.mcd format into other formats (OME-TIFF and HDF5).
+#[derive(Parser)]
+#[clap(version = "0.1", author = "Alan Race <alan.race@uni-marburg.de>")]
+struct Opts {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Parser)]
+enum Command {
+    Dcm(Dcm),
+    OmeTiff(OmeTiff),
+    Hdf5(Hdf5),
+}
+
+/// Create the .dcm file of each *.mcd file, next to the *.mcd file (the .dcm file always includes every
+/// acquisition)
+#[derive(Parser)]
+struct Dcm {
+    /// *.mcd filenames
+    #[clap(required = true)]
+    filenames: Vec<String>,
+    /// Codec used to compress each chunk
+    #[clap(long, value_enum, default_value = "lz4")]
+    codec: CodecArg,
+    /// Compression level, from 0 (fastest) to 9 (smallest). Only used by the deflate codec
+    #[clap(long)]
+    level: Option<u32>,
+    /// Width and height (in pixels) of each chunk
+    #[clap(long, default_value = "256")]
+    chunk_size: u32,
+    /// Don't delta encode the position (X, Y, Z) channels
+    #[clap(long)]
+    no_delta_coordinates: bool,
+    /// Recreate the .dcm file even if a valid one already exists (e.g. to change the codec)
+    #[clap(long)]
+    force: bool,
+}
+
+/// Export the acquisitions of an *.mcd file as OME-TIFF, one file per acquisition
+#[derive(Parser)]
+struct OmeTiff {
+    /// *.mcd filename
+    filename: String,
+    /// Directory the OME-TIFF files are written to
+    #[clap(short, long, default_value = ".")]
+    out: String,
+    #[clap(flatten)]
+    selection: Selection,
+    /// Template used to name the exported files, with the placeholders {slide}, {panorama}, {acquisition},
+    /// {description} and {ext}
+    #[clap(long)]
+    template: Option<String>,
+    /// Resume a previous (interrupted) export to the same directory
+    #[clap(long)]
+    resume: bool,
+}
+
+/// Export the slides, panoramas and acquisitions of an *.mcd file as HDF5 (requires the `hdf5` feature)
+#[derive(Parser)]
+struct Hdf5 {
+    /// *.mcd filename
+    filename: String,
+    /// HDF5 filename
+    #[clap(short, long)]
+    out: String,
+    #[clap(flatten)]
+    selection: Selection,
+}
+
+/// Options shared by the exports
+#[derive(Parser)]
+struct Selection {
+    /// Acquisition to include, as an ID, a description or a glob pattern matching descriptions (e.g. `ROI_0*`).
+    /// Can be used multiple times. If not specified, all acquisitions are included
+    #[clap(short, long)]
+    acquisition: Vec<AcquisitionIdentifier>,
+    /// Read channel images from the .dcm file, creating it (with the default options) if needed
+    #[clap(long)]
+    dcm: bool,
+    /// How channels with the same label (e.g. duplicate metals) are named: number, isotope, error or skip
+    #[clap(long, default_value = "number")]
+    on_collision: CollisionPolicy,
+}
+
+/// Codec used to compress each chunk of the .dcm file
+#[derive(Clone, Copy, ValueEnum)]
+enum CodecArg {
+    /// No compression (fastest to read, largest file)
+    None,
+    /// LZ4 compression (fast to decompress)
+    Lz4,
+    /// Deflate compression (smaller but slower than LZ4)
+    Deflate,
+}
+
+impl From<CodecArg> for Codec {
+    fn from(codec: CodecArg) -> Self {
+        match codec {
+            CodecArg::None => Codec::None,
+            CodecArg::Lz4 => Codec::Lz4,
+            CodecArg::Deflate => Codec::Deflate,
+        }
+    }
+}
+
+/// Print the acquisition being converted
+struct PrintProgress;
+
+impl Progress for PrintProgress {
+    fn acquisition_started(&self, acquisition_id: u16, index: usize, num_acquisitions: usize) {
+        println!(
+            "Converting acquisition {} ({}/{})",
+            acquisition_id,
+            index + 1,
+            num_acquisitions
+        );
+    }
+}
+
+impl Selection {
+    fn identifiers(&self) -> Option<Vec<AcquisitionIdentifier>> {
+        (!self.acquisition.is_empty()).then(|| self.acquisition.clone())
+    }
+
+    fn open(&self, filename: &str) -> Result<MCD<std::fs::File>, MCDError> {
+        let mcd = MCD::from_path(filename)?;
+
+        if self.dcm {
+            mcd.with_dcm_with_progress(&PrintProgress, &CancellationToken::new())
+        } else {
+            Ok(mcd)
+        }
+    }
+}
+
+fn dcm(opts: &Dcm) -> Result<(), Box<dyn Error>> {
+    let mut options = ConvertOptions::new()
+        .codec(opts.codec.into())
+        .chunk_size(opts.chunk_size)
+        .delta_coordinates(!opts.no_delta_coordinates);
+    if let Some(level) = opts.level {
+        options = options.level(level);
+    }
+
+    for filename in &opts.filenames {
+        let mut mcd = MCD::from_path(filename)?;
+
+        let dcm_path = Path::new(filename).with_extension("dcm");
+        if opts.force && dcm_path.exists() {
+            std::fs::remove_file(&dcm_path)?;
+        }
+
+        println!("Creating {}", dcm_path.display());
+        convert::open_with_options(
+            &mut mcd,
+            &options,
+            &PrintProgress,
+            &CancellationToken::new(),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn ome_tiff(opts: &OmeTiff) -> Result<(), Box<dyn Error>> {
+    let mcd = opts.selection.open(&opts.filename)?;
+
+    let mut options = ExportOptions::new(ExportFormat::OmeTiff)
+        .resume(opts.resume)
+        .name_sanitizer(NameSanitizer::new().collision_policy(opts.selection.on_collision));
+    if let Some(identifiers) = opts.selection.identifiers() {
+        options = options.acquisitions(identifiers);
+    }
+    if let Some(template) = &opts.template {
+        options = options.file_name_template(template);
+    }
+
+    for file in Exporter::export(&mcd, &options, &opts.out)? {
+        println!("{}", file.display());
+    }
+
+    Ok(())
+}
+
+fn hdf5(opts: &Hdf5) -> Result<(), Box<dyn Error>> {
+    let mcd = opts.selection.open(&opts.filename)?;
+
+    let acquisitions = match opts.selection.identifiers() {
+        Some(identifiers) => {
+            let mut acquisitions: Vec<AcquisitionRef> = Vec::new();
+
+            for identifier in identifiers {
+                let matching = mcd.acquisitions_matching(identifier.clone());
+                if matching.is_empty() {
+                    return Err(MCDError::InvalidAcquisition {
+                        suggestions: mcd.acquisition_suggestions(&identifier),
+                        acquisition: identifier,
+                    }
+                    .into());
+                }
+
+                acquisitions.extend(matching.iter().map(|acquisition| acquisition.reference()));
+            }
+
+            Some(acquisitions)
+        }
+        None => None,
+    };
+
+    write_hdf5(
+        &mcd,
+        &opts.out,
+        acquisitions.as_deref(),
+        opts.selection.on_collision,
+    )
+}
+
+#[cfg(feature = "hdf5")]
+fn write_hdf5(
+    mcd: &MCD<std::fs::File>,
+    path: &str,
+    acquisitions: Option<&[AcquisitionRef]>,
+    collision_policy: CollisionPolicy,
+) -> Result<(), Box<dyn Error>> {
+    imc_hdf5::write_hdf5(mcd, path, acquisitions, collision_policy)?;
+    println!("{}", path);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn write_hdf5(
+    _mcd: &MCD<std::fs::File>,
+    _path: &str,
+    _acquisitions: Option<&[AcquisitionRef]>,
+    _collision_policy: CollisionPolicy,
+) -> Result<(), Box<dyn Error>> {
+    Err("imc-convert was built without HDF5 support (enable the `hdf5` feature)".into())
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let result = match &opts.command {
+        Command::Dcm(dcm_opts) => dcm(dcm_opts),
+        Command::OmeTiff(ome_tiff_opts) => ome_tiff(ome_tiff_opts),
+        Command::Hdf5(hdf5_opts) => hdf5(hdf5_opts),
+    };
+
+    if let Err(err) = result {
+        println!("Error: {:?}", err.to_string());
+        std::process::exit(1);
+    }
+}
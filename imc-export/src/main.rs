@@ -0,0 +1,190 @@
+use clap::{Parser, ValueEnum};
+use imc_rs::{
+    error::MCDError,
+    export::{
+        ChannelSet, CollisionPolicy, ExportFormat, ExportOptions, Exporter, Georeference,
+        NameSanitizer,
+    },
+    render::Colormap,
+    AcquisitionIdentifier, ChannelIdentifier, Region, MCD,
+};
+
+/// imc-export writes channel images from IMC data sets stored in the *.mcd format as TIFF, OME-TIFF, PNG or
+/// NumPy (.npy) files.
+#[derive(Parser)]
+#[clap(version = "0.1", author = "Alan Race <alan.race@uni-marburg.de>")]
+struct Opts {
+    /// *.mcd filename
+    filename: String,
+    /// Directory the images are written to
+    #[clap(short, long, default_value = ".")]
+    out: String,
+    /// Acquisition to export, as an ID, a description or a glob pattern matching descriptions (e.g. `ROI_0*`).
+    /// Can be used multiple times. If not specified, all acquisitions are exported
+    #[clap(short, long)]
+    acquisition: Vec<AcquisitionIdentifier>,
+    /// Channel to export, as a channel name (e.g. `Ir(191)`) or label. Can be used multiple times. If not
+    /// specified, all channels are exported
+    #[clap(short, long)]
+    channel: Vec<String>,
+    /// Only export the region `x,y,width,height` (in pixels) of each acquisition
+    #[clap(long, parse(try_from_str = parse_region))]
+    region: Option<Region>,
+    /// Format of the exported images
+    #[clap(short, long, value_enum, default_value = "tiff")]
+    format: Format,
+    /// Template used to name the exported files, with the placeholders {slide}, {panorama}, {acquisition},
+    /// {description}, {channel} and {ext} (e.g. `{description}/{channel}.{ext}`)
+    #[clap(long)]
+    template: Option<String>,
+    /// Colormap used for PNG images (viridis, magma, grayscale, red, green, blue or a hex colour such as #00ff00)
+    #[clap(long, default_value = "grayscale")]
+    colormap: Colormap,
+    /// Locate the exported TIFF and PNG images on the slide with world files (.tfw/.pgw) or GeoTIFF tags
+    #[clap(long, value_enum, default_value = "none")]
+    georeference: GeoreferenceArg,
+    /// Only export the channels measured in every exported acquisition
+    #[clap(long)]
+    intersection: bool,
+    /// Value written for pixels which were not acquired (NaN by default)
+    #[clap(long)]
+    missing_value: Option<f32>,
+    /// Resume a previous (interrupted) export to the same directory
+    #[clap(long)]
+    resume: bool,
+    /// How channels with the same label (e.g. duplicate metals) are named: number, isotope, error or skip
+    #[clap(long, default_value = "number")]
+    on_collision: CollisionPolicy,
+}
+
+/// Format of the exported images
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One 32-bit floating point TIFF per channel
+    Tiff,
+    /// One multi-page OME-TIFF per acquisition
+    OmeTiff,
+    /// One 8-bit PNG per channel, rendered with the colormap
+    Png,
+    /// One NumPy array of 32-bit floats per channel
+    Npy,
+}
+
+/// How exported images are located on the slide
+#[derive(Clone, Copy, ValueEnum)]
+enum GeoreferenceArg {
+    /// Images are not georeferenced
+    None,
+    /// Write a world file alongside each image
+    WorldFile,
+    /// Embed GeoTIFF tags in each TIFF (PNG images are given a world file)
+    Geotiff,
+}
+
+impl From<GeoreferenceArg> for Georeference {
+    fn from(georeference: GeoreferenceArg) -> Self {
+        match georeference {
+            GeoreferenceArg::None => Georeference::None,
+            GeoreferenceArg::WorldFile => Georeference::WorldFile,
+            GeoreferenceArg::Geotiff => Georeference::GeoTiff,
+        }
+    }
+}
+
+impl From<Format> for ExportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Tiff => ExportFormat::Tiff,
+            Format::OmeTiff => ExportFormat::OmeTiff,
+            Format::Png => ExportFormat::Png,
+            Format::Npy => ExportFormat::Npy,
+        }
+    }
+}
+
+fn parse_region(region: &str) -> Result<Region, String> {
+    let values = region
+        .split(',')
+        .map(|value| value.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+
+    match values.as_slice() {
+        &[x, y, width, height] => Ok(Region {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => Err("expected x,y,width,height".to_string()),
+    }
+}
+
+/// Interpret the channel as a name if any acquisition has a channel with that name, otherwise as a label
+fn channel_identifier<R>(mcd: &MCD<R>, channel: &str) -> ChannelIdentifier {
+    let is_name = mcd.acquisitions().iter().any(|acquisition| {
+        acquisition
+            .channels()
+            .iter()
+            .any(|acquisition_channel| acquisition_channel.name() == channel)
+    });
+
+    if is_name {
+        ChannelIdentifier::name(channel)
+    } else {
+        ChannelIdentifier::label(channel)
+    }
+}
+
+fn export(opts: &Opts) -> Result<usize, MCDError> {
+    let mcd = MCD::from_path(&opts.filename)?;
+
+    let mut options = ExportOptions::new(opts.format.into())
+        .colormap(opts.colormap)
+        .georeference(opts.georeference.into())
+        .name_sanitizer(NameSanitizer::new().collision_policy(opts.on_collision))
+        .resume(opts.resume);
+
+    if !opts.acquisition.is_empty() {
+        options = options.acquisitions(opts.acquisition.clone());
+    }
+    if !opts.channel.is_empty() {
+        options = options.channels(
+            opts.channel
+                .iter()
+                .map(|channel| channel_identifier(&mcd, channel))
+                .collect(),
+        );
+    }
+    if let Some(region) = opts.region {
+        options = options.region(region);
+    }
+    if let Some(template) = &opts.template {
+        options = options.file_name_template(template);
+    }
+    if opts.intersection {
+        options = options.channel_set(ChannelSet::Intersection);
+    }
+    if let Some(value) = opts.missing_value {
+        options = options.missing_value(value);
+    }
+
+    let files = Exporter::export(&mcd, &options, &opts.out)?;
+    for file in &files {
+        println!("{}", file.display());
+    }
+
+    Ok(files.len())
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    match export(&opts) {
+        Ok(num_files) => println!("Written {} files to {}", num_files, opts.out),
+        Err(err) => {
+            println!("Error: {:?}", err.to_string());
+            std::process::exit(1);
+        }
+    }
+}
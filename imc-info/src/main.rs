@@ -1,5 +1,13 @@
-use clap::Parser;
-use imc_rs::MCD;
+use std::{fmt::Display, fs::File, io::BufWriter};
+
+use clap::{Parser, ValueEnum};
+use imc_rs::{
+    describe::{Describe, Description, Table, Value},
+    error::MCDError,
+    gating::{cells_to_csv, parse_rules},
+    segmentation::CellMask,
+    validation, AcquisitionIdentifier, MCD,
+};
 
 /// imc-info extracts information from IMC data sets stored in the *.mcd format.
 #[derive(Parser)]
@@ -10,18 +18,67 @@ struct Opts {
     //#[clap(short, long, default_value = "default.conf")]
     //config: String,
     /// *.mcd filename
-    filename: String,
+    filename: Option<String>,
     /// A level of verbosity, and can be used multiple times
     #[clap(short, long, parse(from_occurrences))]
     verbose: i32,
+    /// Format used when printing information
+    #[clap(long, value_enum, default_value = "table", global = true)]
+    format: Format,
 
     #[clap(subcommand)]
     slide_command: Option<SlideCommand>,
 }
 
+/// Format used when printing information
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Human-readable tables
+    Table,
+    /// JSON, for use in scripts
+    Json,
+    /// YAML, for use in scripts
+    Yaml,
+}
+
 #[derive(Parser)]
 enum SlideCommand {
     Slide(Slide),
+    Channels(Channels),
+    Phenotype(Phenotype),
+    Validate(Validate),
+}
+
+/// List all channels, with whether each was measured in each acquisition
+#[derive(Parser)]
+struct Channels {}
+
+/// Classify the cells of a segmentation mask into phenotypes using gating rules, writing one row per cell
+#[derive(Parser)]
+struct Phenotype {
+    /// *.mcd filename
+    filename: String,
+    /// Cell segmentation mask (TIFF, with the ID of the cell in each pixel) matching the acquisition
+    #[clap(long)]
+    mask: String,
+    /// Rules file, with one `description: rule` per line (e.g. `T cell: CD3 > 1.0 and CD45 > 0.5`)
+    #[clap(long)]
+    rules: String,
+    /// Output .csv file
+    #[clap(long)]
+    out: String,
+    /// ID of the acquisition the mask was generated from. If not specified, the (single) acquisition with the
+    /// same dimensions as the mask is used
+    #[clap(long)]
+    acquisition: Option<u16>,
+}
+
+/// Check the XML metadata against the known versions of the MCDSchema XSD, reporting missing elements, unknown
+/// tags and inconsistent IDs. Works even for files which cannot otherwise be opened
+#[derive(Parser)]
+struct Validate {
+    /// *.mcd filename
+    filename: String,
 }
 
 /// A subcommand for controlling slides
@@ -61,9 +118,136 @@ struct Acquisition {
     id: u16,
 }
 
+/// Print the item in the specified format
+fn print<D: Describe + Display>(item: &D, format: Format) {
+    print_description(&item.describe(), item, format);
+}
+
+fn print_description<D: Display>(description: &Description, text: &D, format: Format) {
+    match format {
+        Format::Table => println!("{}", text),
+        Format::Json => println!("{}", description.to_json()),
+        Format::Yaml => print!("{}", description.to_yaml()),
+    }
+}
+
+/// Describe which channels are present in which acquisitions
+fn describe_channels<R>(mcd: &MCD<R>) -> Description {
+    let presence = mcd.channel_presence();
+
+    let mut columns = vec!["Channel".to_string()];
+    columns.extend(
+        presence
+            .acquisitions()
+            .iter()
+            .map(|acquisition| acquisition.to_string()),
+    );
+    let columns: Vec<_> = columns.iter().map(|column| column.as_str()).collect();
+
+    let table =
+        presence
+            .channels()
+            .iter()
+            .fold(Table::new("Channels", &columns), |table, channel| {
+                let mut row = vec![Value::from(channel)];
+                row.extend(
+                    presence
+                        .acquisitions()
+                        .iter()
+                        .map(|&acquisition| Value::from(presence.is_present(acquisition, channel))),
+                );
+
+                table.row(row)
+            });
+
+    Description::new("Channel presence")
+        .field("Acquisitions", presence.acquisitions().len())
+        .field("In all acquisitions", presence.intersection())
+        .table(table)
+}
+
+/// Summarise the cells of the mask, apply the rules and write the result to the output file
+fn phenotype(opts: &Phenotype) -> Result<usize, MCDError> {
+    let mcd = MCD::from_path(&opts.filename)?;
+    let mask = CellMask::from_path(&opts.mask)?;
+    let phenotypes = parse_rules(&std::fs::read_to_string(&opts.rules)?)?;
+
+    let acquisition = match opts.acquisition {
+        Some(id) => mcd.find_acquisition(AcquisitionIdentifier::Id(id))?,
+        None => {
+            let matching: Vec<_> = mcd
+                .acquisitions()
+                .into_iter()
+                .filter(|acquisition| {
+                    acquisition.width() == mask.width() as i32
+                        && acquisition.height() == mask.height() as i32
+                })
+                .collect();
+
+            match matching.as_slice() {
+                [acquisition] => *acquisition,
+                _ => {
+                    return Err(MCDError::InvalidMask {
+                        reason: format!(
+                            "{} acquisitions are {}x{} pixels, specify one with --acquisition",
+                            matching.len(),
+                            mask.width(),
+                            mask.height()
+                        ),
+                    })
+                }
+            }
+        }
+    };
+
+    let cells = mask.summarise(acquisition)?;
+    let writer = BufWriter::new(File::create(&opts.out)?);
+    cells_to_csv(writer, acquisition.channels(), &cells, &phenotypes)?;
+
+    Ok(cells.len())
+}
+
 fn main() {
     let opts: Opts = Opts::parse();
 
+    if let Some(SlideCommand::Phenotype(phenotype_opts)) = &opts.slide_command {
+        match phenotype(phenotype_opts) {
+            Ok(num_cells) => println!("Written {} cells to {}", num_cells, phenotype_opts.out),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(SlideCommand::Validate(validate_opts)) = &opts.slide_command {
+        let report = match File::open(&validate_opts.filename)
+            .map_err(MCDError::from)
+            .and_then(validation::validate)
+        {
+            Ok(report) => report,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        };
+
+        print(&report, opts.format);
+        if !report.is_valid() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let filename = match &opts.filename {
+        Some(filename) => filename,
+        None => {
+            println!("Error: no *.mcd filename specified");
+            return;
+        }
+    };
+
     // Gets a value for config if supplied by user, or defaults to "default.conf"
     //println!("Value for config: {}", opts.config);
     //println!("Using input file: {}", opts.filename);
@@ -77,7 +261,7 @@ fn main() {
         _ => println!("Don't be ridiculous"),
     }*/
 
-    let mcd = match MCD::from_path(&opts.filename) {
+    let mcd = match MCD::from_path(filename) {
         Ok(mcd) => mcd,
         Err(err) => {
             println!("Error: {:?}", err.to_string());
@@ -126,20 +310,27 @@ fn main() {
                                 }
                             };
 
-                            println!("{}", acquisition);
+                            print(acquisition, opts.format);
                         }
                         None => {
-                            println!("{}", panorama);
+                            print(panorama, opts.format);
                         }
                     }
                 }
                 None => {
-                    println!("{}", slide);
+                    print(slide, opts.format);
                 }
             }
         }
+        Some(SlideCommand::Channels(_)) => {
+            let description = describe_channels(&mcd);
+            print_description(&description, &description, opts.format);
+        }
+        Some(SlideCommand::Phenotype(_)) | Some(SlideCommand::Validate(_)) => {
+            unreachable!("handled above")
+        }
         None => {
-            println!("{}", mcd);
+            print(&mcd, opts.format);
         }
     }
 
@@ -0,0 +1,153 @@
+//! Benchmarks of the reader, run with `cargo bench` from this directory. A synthetic .mcd file is written to the
+//! temporary directory, so that no test data is required.
+
+use std::{io::Cursor, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use imc_rs::{
+    convert, AcquisitionRef, AcquisitionSpec, ChannelIdentifier, MCDWriter, PanoramaSpec, Region,
+    SlideSpec, MCD,
+};
+
+const WIDTH: u32 = 500;
+const HEIGHT: u32 = 500;
+const NUM_CHANNELS: usize = 40;
+const NUM_ACQUISITIONS: u16 = 4;
+
+fn channel_name(channel: usize) -> String {
+    format!("Ch{}", 100 + channel)
+}
+
+/// Returns a synthetic .mcd file with `NUM_ACQUISITIONS` acquisitions of `NUM_CHANNELS` channels each
+fn synthetic_mcd() -> Vec<u8> {
+    let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+    writer.add_slide(SlideSpec::new(1)).unwrap();
+    writer
+        .add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 10000.0, 10000.0))
+        .unwrap();
+
+    let num_pixels = (WIDTH * HEIGHT) as usize;
+    let spectra: Vec<f32> = (0..num_pixels * NUM_CHANNELS)
+        .map(|index| (index % 97) as f32)
+        .collect();
+
+    for id in 1..=NUM_ACQUISITIONS {
+        let mut acquisition = AcquisitionSpec::new(id, 1, WIDTH as i32, HEIGHT as i32);
+        for channel in 0..NUM_CHANNELS {
+            let name = channel_name(channel);
+            acquisition = acquisition.channel(&name, &name);
+        }
+
+        writer.add_acquisition(acquisition, &spectra).unwrap();
+    }
+
+    writer.finish().unwrap().into_inner()
+}
+
+/// Write the synthetic .mcd file to the temporary directory (removing any .dcm file from a previous run), so
+/// that it can be opened with a .dcm file
+fn synthetic_mcd_path(data: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join("imc-rs-bench.mcd");
+    std::fs::write(&path, data).unwrap();
+    let _ = std::fs::remove_file(path.with_extension("dcm"));
+
+    path
+}
+
+fn parse(c: &mut Criterion) {
+    let data = synthetic_mcd();
+    let xml_len = MCD::from_bytes(data.clone()).unwrap().xml().unwrap().len();
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(xml_len as u64));
+    group.bench_function("xml", |b| {
+        b.iter_batched(
+            || data.clone(),
+            |data| MCD::from_bytes(data).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn convert_dcm(c: &mut Criterion) {
+    let data = synthetic_mcd();
+    let mcd = MCD::from_bytes(data.clone()).unwrap();
+
+    let mut group = c.benchmark_group("convert");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("dcm", |b| {
+        b.iter(|| {
+            let mut dcm = Cursor::new(Vec::with_capacity(data.len()));
+            convert::convert(&mcd, &mut dcm).unwrap();
+            dcm
+        })
+    });
+    group.finish();
+}
+
+fn channel_image(c: &mut Criterion) {
+    let data = synthetic_mcd();
+    let path = synthetic_mcd_path(&data);
+    let identifier = ChannelIdentifier::Name(channel_name(NUM_CHANNELS / 2));
+
+    let mut group = c.benchmark_group("channel_image");
+
+    // Without a .dcm file, the spectra of the whole acquisition are read for each image
+    let mcd = MCD::from_bytes(data).unwrap();
+    let acquisition = mcd.find_acquisition(AcquisitionRef::new(1, 1, 1)).unwrap();
+    group.sample_size(10);
+    group.bench_function("mcd", |b| {
+        b.iter(|| acquisition.channel_image(&identifier, None).unwrap())
+    });
+
+    let mcd = MCD::from_path(&path).unwrap().with_dcm().unwrap();
+    let acquisition = mcd.find_acquisition(AcquisitionRef::new(1, 1, 1)).unwrap();
+    group.sample_size(100);
+    group.bench_function("dcm", |b| {
+        b.iter(|| acquisition.channel_image(&identifier, None).unwrap())
+    });
+
+    let mcd = MCD::from_path(&path)
+        .unwrap()
+        .with_dcm()
+        .unwrap()
+        .with_channel_cache(64 * 1024 * 1024);
+    let acquisition = mcd.find_acquisition(AcquisitionRef::new(1, 1, 1)).unwrap();
+    group.bench_function("dcm_cached", |b| {
+        b.iter(|| acquisition.channel_image(&identifier, None).unwrap())
+    });
+    group.finish();
+}
+
+fn region(c: &mut Criterion) {
+    let data = synthetic_mcd();
+    let path = synthetic_mcd_path(&data);
+    let mcd = MCD::from_path(&path).unwrap().with_dcm().unwrap();
+    let acquisition = mcd.find_acquisition(AcquisitionRef::new(1, 1, 1)).unwrap();
+    let identifier = ChannelIdentifier::Name(channel_name(NUM_CHANNELS / 2));
+
+    let mut group = c.benchmark_group("region");
+    for size in [10, 50, 100, 250, WIDTH] {
+        let region = Region {
+            x: (WIDTH - size) / 2,
+            y: (HEIGHT - size) / 2,
+            width: size,
+            height: size,
+        };
+
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &region, |b, region| {
+            b.iter(|| {
+                acquisition
+                    .channel_image(&identifier, Some(*region))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, convert_dcm, channel_image, region);
+criterion_main!(benches);
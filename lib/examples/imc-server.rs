@@ -0,0 +1,320 @@
+// Minimal REST service exposing the acquisitions of an .mcd file over HTTP.
+//
+// Usage: cargo run --example imc-server -- <file.mcd> [address]
+//
+// Endpoints:
+//
+// * `GET /acquisitions` - JSON list of the acquisitions
+// * `GET /acquisitions/{slide}/{panorama}/{id}` - JSON metadata of the acquisition, including its channels
+// * `GET /acquisitions/{slide}/{panorama}/{id}/channels/{channel}/tile?x=&y=&width=&height=&min=&max=` - PNG of a
+//   region of the channel (by name, e.g. `Ir(191)`), defaulting to the whole acquisition and its intensity range
+//
+// The .dcm file is created (or reused) on start up, so tiles can be served without reading whole spectra. Each
+// connection is handled on its own thread; a production service would use an HTTP framework instead.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+};
+
+use imc_rs::{
+    error::MCDError, render::Colormap, Acquisition, AcquisitionRef, ChannelIdentifier, Region, MCD,
+};
+
+/// Response to a request: status line, content type and body
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: format!("{{\"error\": {}}}", json_string(message)).into_bytes(),
+        }
+    }
+}
+
+impl From<MCDError> for Response {
+    fn from(error: MCDError) -> Self {
+        match error {
+            MCDError::InvalidAcquisition { .. } | MCDError::ChannelNotFound { .. } => {
+                Response::error("404 Not Found", &error.to_string())
+            }
+            _ => Response::error("500 Internal Server Error", &error.to_string()),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or("usage: imc-server <file.mcd> [address]")?;
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+
+    let mcd = Arc::new(MCD::from_path(&path)?.with_dcm()?);
+
+    let listener = TcpListener::bind(&address)?;
+    println!("Serving {} on http://{}", path, address);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("Failed to accept connection: {}", error);
+                continue;
+            }
+        };
+
+        let mcd = mcd.clone();
+        std::thread::spawn(move || {
+            if let Err(error) = handle_connection(&mcd, stream) {
+                eprintln!("Failed to handle request: {}", error);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mcd: &MCD<File>, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, the body of GET requests is ignored
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => route(mcd, target),
+        (Some(_), Some(_)) => Response::error("405 Method Not Allowed", "only GET is supported"),
+        _ => Response::error("400 Bad Request", "malformed request line"),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn route(mcd: &MCD<File>, target: &str) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let result = match segments.as_slice() {
+        ["acquisitions"] => Ok(list_acquisitions(mcd)),
+        ["acquisitions", slide, panorama, id] => {
+            acquisition(mcd, slide, panorama, id).map(metadata)
+        }
+        ["acquisitions", slide, panorama, id, "channels", channel, "tile"] => {
+            acquisition(mcd, slide, panorama, id)
+                .and_then(|acquisition| tile(acquisition, channel, &parse_query(query)))
+        }
+        _ => Err(Response::error("404 Not Found", "unknown endpoint")),
+    };
+
+    result.unwrap_or_else(|response| response)
+}
+
+fn acquisition<'a>(
+    mcd: &'a MCD<File>,
+    slide: &str,
+    panorama: &str,
+    id: &str,
+) -> Result<&'a Acquisition<File>, Response> {
+    let parse = |value: &str| {
+        value
+            .parse::<u16>()
+            .map_err(|_| Response::error("400 Bad Request", "IDs must be integers"))
+    };
+
+    let reference = AcquisitionRef::new(parse(slide)?, parse(panorama)?, parse(id)?);
+
+    Ok(mcd.find_acquisition(reference)?)
+}
+
+fn list_acquisitions(mcd: &MCD<File>) -> Response {
+    let acquisitions: Vec<String> = mcd
+        .acquisitions()
+        .iter()
+        .map(|acquisition| {
+            let reference = acquisition.reference();
+
+            format!(
+                "{{\"slide\": {}, \"panorama\": {}, \"id\": {}, \"description\": {}, \"width\": {}, \"height\": {}}}",
+                reference.slide(),
+                reference.panorama(),
+                reference.id(),
+                json_string(acquisition.description()),
+                acquisition.width(),
+                acquisition.height()
+            )
+        })
+        .collect();
+
+    Response::json(format!("[{}]", acquisitions.join(", ")))
+}
+
+fn metadata(acquisition: &Acquisition<File>) -> Response {
+    let channels: Vec<String> = acquisition
+        .channels()
+        .iter()
+        .map(|channel| {
+            format!(
+                "{{\"name\": {}, \"label\": {}, \"order\": {}}}",
+                json_string(channel.name()),
+                json_string(channel.label()),
+                channel.order_number()
+            )
+        })
+        .collect();
+
+    Response::json(format!(
+        "{{\"id\": {}, \"description\": {}, \"width\": {}, \"height\": {}, \"start\": {}, \"end\": {}, \"ablation_frequency\": {}, \"complete\": {}, \"channels\": [{}]}}",
+        acquisition.id(),
+        json_string(acquisition.description()),
+        acquisition.width(),
+        acquisition.height(),
+        json_string(acquisition.start_timestamp()),
+        json_string(acquisition.end_timestamp()),
+        acquisition.ablation_frequency(),
+        acquisition.is_complete(),
+        channels.join(", ")
+    ))
+}
+
+fn tile(
+    acquisition: &Acquisition<File>,
+    channel: &str,
+    query: &HashMap<String, String>,
+) -> Result<Response, Response> {
+    let value = |key: &str| -> Result<Option<f64>, Response> {
+        match query.get(key) {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                Response::error("400 Bad Request", &format!("{} must be a number", key))
+            }),
+            None => Ok(None),
+        }
+    };
+
+    let region = match (value("x")?, value("y")?, value("width")?, value("height")?) {
+        (Some(x), Some(y), Some(width), Some(height)) => Some(Region {
+            x: x as u32,
+            y: y as u32,
+            width: width as u32,
+            height: height as u32,
+        }),
+        (None, None, None, None) => None,
+        _ => {
+            return Err(Response::error(
+                "400 Bad Request",
+                "x, y, width and height must be specified together",
+            ))
+        }
+    };
+
+    let range = match (value("min")?, value("max")?) {
+        (Some(min), Some(max)) => Some((min as f32, max as f32)),
+        _ => None,
+    };
+
+    let image = acquisition.channel_image(ChannelIdentifier::name(channel), region)?;
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(image.to_rgba(Colormap::Grayscale, range))
+        .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+        .map_err(|error| Response::error("500 Internal Server Error", &error.to_string()))?;
+
+    Ok(Response {
+        status: "200 OK",
+        content_type: "image/png",
+        body: png,
+    })
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+        .collect()
+}
+
+/// Decode %XX escapes in a URL component (invalid escapes are kept as they are)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
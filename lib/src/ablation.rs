@@ -0,0 +1,137 @@
+use std::io::{Read, Seek};
+
+use image::{imageops::FilterType, GrayImage, Rgba, RgbaImage};
+
+use crate::{
+    error::{MCDError, Result},
+    Acquisition, OpticalImage,
+};
+
+/// Colour in which the ablated area is highlighted (magenta, which rarely occurs in images of stained tissue)
+const HIGHLIGHT: [f32; 3] = [255.0, 0.0, 255.0];
+
+impl<R: Read + Seek> Acquisition<R> {
+    /// Returns an image highlighting the area which changed on ablation, for checking that the acquisition
+    /// ablated the expected region (e.g. to detect misfires, or drift of the stage).
+    ///
+    /// The before ablation image is shown in greyscale, with each pixel whose brightness differs from the after
+    /// ablation image by more than `threshold` (0-255) overlaid in magenta, more strongly the larger the
+    /// difference. Both images cover the acquisition region, so if they differ in size the after ablation image
+    /// is resized to match.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCDError::MissingAblationImage`] if either image is not stored in the .mcd file.
+    pub fn ablation_difference_image(&self, threshold: u8) -> Result<RgbaImage> {
+        let before = self.ablation_image(self.before_ablation_image(), "before")?;
+        let mut after = self.ablation_image(self.after_ablation_image(), "after")?;
+
+        if after.dimensions() != before.dimensions() {
+            after = image::imageops::resize(
+                &after,
+                before.width(),
+                before.height(),
+                FilterType::Triangle,
+            );
+        }
+
+        Ok(RgbaImage::from_fn(
+            before.width(),
+            before.height(),
+            |x, y| {
+                let grey = before.get_pixel(x, y).0[0];
+                let difference = grey.abs_diff(after.get_pixel(x, y).0[0]);
+
+                if difference <= threshold {
+                    return Rgba([grey, grey, grey, 255]);
+                }
+
+                // Always clearly visible, and more strongly highlighted the larger the difference
+                let weight = 0.5 + 0.5 * difference as f32 / 255.0;
+                let blend = |highlight: f32| {
+                    (grey as f32 * (1.0 - weight) + highlight * weight).round() as u8
+                };
+
+                Rgba([
+                    blend(HIGHLIGHT[0]),
+                    blend(HIGHLIGHT[1]),
+                    blend(HIGHLIGHT[2]),
+                    255,
+                ])
+            },
+        ))
+    }
+
+    fn ablation_image(&self, image: OpticalImage<R>, which: &'static str) -> Result<GrayImage> {
+        if !image.is_stored() {
+            return Err(MCDError::MissingAblationImage {
+                acquisition_id: self.id(),
+                image: which,
+            });
+        }
+
+        Ok(image.dynamic_image()?.into_luma8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat, Luma};
+
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    fn png(image: GrayImage) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut data, ImageOutputFormat::Png)
+            .expect("Image should encode");
+
+        data.into_inner()
+    }
+
+    #[test]
+    fn highlights_ablated_area() -> Result<()> {
+        let before = GrayImage::from_pixel(4, 4, Luma([200]));
+        // The centre of the region is ablated
+        let after = GrayImage::from_fn(4, 4, |x, y| {
+            if (1..3).contains(&x) && (1..3).contains(&y) {
+                Luma([50])
+            } else {
+                Luma([198])
+            }
+        });
+
+        let mcd = MCD::from_bytes(synthetic_mcd([
+            (
+                AcquisitionSpec::new(1, 1, 1, 1)
+                    .channel("Ir191", "DNA1")
+                    .before_ablation_image(png(before))
+                    .after_ablation_image(png(after)),
+                [1.0],
+            ),
+            (
+                AcquisitionSpec::new(2, 1, 1, 1).channel("Ir191", "DNA1"),
+                [1.0],
+            ),
+        ])?)?;
+
+        let difference = mcd.acquisitions()[0].ablation_difference_image(10)?;
+        assert_eq!(difference.dimensions(), (4, 4));
+        assert_eq!(difference.get_pixel(0, 0).0, [200, 200, 200, 255]);
+        let ablated = difference.get_pixel(1, 2).0;
+        assert!(ablated[1] < ablated[0] && ablated[1] < ablated[2]);
+
+        assert!(matches!(
+            mcd.acquisitions()[1].ablation_difference_image(10),
+            Err(MCDError::MissingAblationImage {
+                acquisition_id: 2,
+                image: "before"
+            })
+        ));
+
+        Ok(())
+    }
+}
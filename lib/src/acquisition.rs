@@ -1,26 +1,107 @@
 use core::fmt;
 use std::{
-    collections::{HashMap, HashSet},
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
-    sync::{Arc, Mutex, MutexGuard},
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    io::{Read, Seek, SeekFrom},
+    ops::DerefMut,
+    str::FromStr,
+    sync::Arc,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
 use image::ImageFormat;
 use nalgebra::Vector2;
+use regex::Regex;
 
 use crate::{
-    channel::{AcquisitionChannel, ChannelIdentifier},
+    cache::ChannelCache,
+    channel::{AcquisitionChannel, ChannelIdentifier, ChannelSummary},
     convert::DCMLocation,
-    error::{MCDError, Result},
+    coords::SlidePoint,
+    correction::ChannelCorrections,
+    describe::{Describe, Description, Value},
+    error::{self, channel_not_found, MCDError, Result},
+    filter::Despeckle,
     mcd::AcquisitionXML,
+    pattern,
+    plume::PlumeWindow,
+    reader::{self, PooledReader, ReaderPool},
+    roi::{polygon_contains, RoiPoint, RoiShape},
+    tiles,
     transform::AffineTransform,
-    BoundingBox, ChannelImage, OnSlide, OpticalImage, Print, Region,
+    BoundingBox, ByteSpan, ChannelImage, OnSlide, OpticalImage, Print, Region, ValidRegion,
 };
 
-#[derive(Debug, Clone)]
+/// Format in which the intensities of each spectrum are stored (`SegmentDataFormat` in the XML metadata). The
+/// number of bytes used for each value is given separately (`ValueBytes`, see `Acquisition::value_bytes`).
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DataFormat {
+    /// Floating point values (4 or 8 bytes)
     Float,
+    /// Unsigned integer counts, usually stored in 2 bytes
+    UInt16,
+    /// Unsigned integer counts, usually stored in 4 bytes
+    UInt32,
+    /// A format which is not recognised. The metadata and images can still be read, but reading the
+    /// intensities returns `MCDError::UnsupportedDataFormat`.
+    Other(String),
+}
+
+impl DataFormat {
+    /// Returns the format with the name used in the XML metadata (e.g. `Float`)
+    pub(crate) fn from_name(name: &str) -> Self {
+        match name {
+            "Float" | "Single" => DataFormat::Float,
+            "UInt16" => DataFormat::UInt16,
+            "UInt32" => DataFormat::UInt32,
+            _ => DataFormat::Other(name.to_string()),
+        }
+    }
+
+    /// Returns the number of bytes usually used to store each value in this format
+    pub(crate) fn default_value_bytes(&self) -> Option<u8> {
+        match self {
+            DataFormat::Float | DataFormat::UInt32 => Some(4),
+            DataFormat::UInt16 => Some(2),
+            DataFormat::Other(_) => None,
+        }
+    }
+
+    /// Returns the function which converts each stored value (of `value_bytes` bytes, little endian) to an
+    /// intensity, or an error if the combination of format and size is not supported
+    pub(crate) fn decoder(&self, value_bytes: u8) -> Result<fn(&[u8]) -> f32> {
+        let decode: fn(&[u8]) -> f32 = match (self, value_bytes) {
+            (DataFormat::Float, 4) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (DataFormat::Float, 8) => {
+                |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+            }
+            (DataFormat::UInt16 | DataFormat::UInt32, 1) => |b| b[0] as f32,
+            (DataFormat::UInt16 | DataFormat::UInt32, 2) => {
+                |b| u16::from_le_bytes([b[0], b[1]]) as f32
+            }
+            (DataFormat::UInt16 | DataFormat::UInt32, 4) => {
+                |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32
+            }
+            _ => {
+                return Err(MCDError::UnsupportedDataFormat {
+                    format: self.to_string(),
+                    value_bytes,
+                })
+            }
+        };
+
+        Ok(decode)
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataFormat::Float => write!(f, "Float"),
+            DataFormat::UInt16 => write!(f, "UInt16"),
+            DataFormat::UInt32 => write!(f, "UInt32"),
+            DataFormat::Other(name) => write!(f, "{}", name),
+        }
+    }
 }
 
 // #[derive(Debug)]
@@ -32,7 +113,7 @@ pub enum DataFormat {
 // }
 
 /// AcquisitionIdentifier is a way of identifying a specific acquisition
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AcquisitionIdentifier {
     /// Identified by unique identifier
     Id(u16),
@@ -40,6 +121,10 @@ pub enum AcquisitionIdentifier {
     Order(i16),
     /// Match the description of the acquistion (specified by the user)
     Description(String),
+    /// Match the description of the acquisition against a pattern
+    Pattern(AcquisitionPattern),
+    /// Identified by slide, panorama and ID, which is unambiguous even if IDs are repeated across slides
+    Ref(AcquisitionRef),
 }
 
 impl AcquisitionIdentifier {
@@ -47,6 +132,178 @@ impl AcquisitionIdentifier {
     pub fn description(description: &str) -> Self {
         AcquisitionIdentifier::Description(description.into())
     }
+
+    /// Create an acquisition identifier matching descriptions against a glob pattern (e.g. `ROI_0*`)
+    pub fn glob(pattern: &str) -> Self {
+        AcquisitionIdentifier::Pattern(AcquisitionPattern::Glob(pattern.into()))
+    }
+
+    /// Create an acquisition identifier matching descriptions against a regular expression. The expression can
+    /// match anywhere within the description (e.g. `tumor` matches `lung tumor 2`).
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Ok(AcquisitionIdentifier::Pattern(AcquisitionPattern::Regex(
+            Regex::new(pattern)?,
+        )))
+    }
+
+    /// Returns true if the acquisition matches this identifier
+    pub fn matches<R>(&self, acquisition: &Acquisition<R>) -> bool {
+        match self {
+            AcquisitionIdentifier::Id(id) => acquisition.id() == *id,
+            AcquisitionIdentifier::Order(order_number) => {
+                acquisition.order_number() == *order_number
+            }
+            AcquisitionIdentifier::Description(description) => {
+                acquisition.description() == description
+            }
+            AcquisitionIdentifier::Pattern(pattern) => pattern.is_match(acquisition.description()),
+            AcquisitionIdentifier::Ref(reference) => acquisition.reference() == *reference,
+        }
+    }
+
+    /// Returns how different the acquisition is from this identifier (lower is closer), or None if it is too
+    /// different to be suggested in place of this identifier
+    pub(crate) fn distance<R>(&self, acquisition: &Acquisition<R>) -> Option<usize> {
+        let number_distance = |a: i64, b: i64| {
+            let distance = a.abs_diff(b) as usize;
+            (distance <= MAX_NUMBER_DISTANCE).then_some(distance)
+        };
+
+        match self {
+            AcquisitionIdentifier::Id(id) => number_distance(acquisition.id().into(), (*id).into()),
+            AcquisitionIdentifier::Order(order_number) => {
+                number_distance(acquisition.order_number().into(), (*order_number).into())
+            }
+            AcquisitionIdentifier::Description(description) => {
+                pattern::text_distance(description, acquisition.description())
+            }
+            AcquisitionIdentifier::Pattern(AcquisitionPattern::Glob(pattern)) => {
+                pattern::text_distance(pattern, acquisition.description())
+            }
+            AcquisitionIdentifier::Pattern(AcquisitionPattern::Regex(regex)) => {
+                pattern::text_distance(regex.as_str(), acquisition.description())
+            }
+            // The same ID on a different slide or panorama
+            AcquisitionIdentifier::Ref(reference) => {
+                (acquisition.id() == reference.id()).then_some(0)
+            }
+        }
+    }
+}
+
+/// Fully-qualified reference to an acquisition. Acquisition IDs are only unique per slide in some .mcd files,
+/// so the slide and panorama are needed to refer to an acquisition unambiguously.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AcquisitionRef {
+    slide: u16,
+    panorama: u16,
+    id: u16,
+}
+
+impl AcquisitionRef {
+    /// Create a reference to the acquisition with the specified ID, on the specified slide and panorama
+    pub fn new(slide: u16, panorama: u16, id: u16) -> Self {
+        AcquisitionRef {
+            slide,
+            panorama,
+            id,
+        }
+    }
+
+    /// Returns the ID of the slide the acquisition was performed on
+    pub fn slide(&self) -> u16 {
+        self.slide
+    }
+
+    /// Returns the ID of the panorama the acquisition belongs to
+    pub fn panorama(&self) -> u16 {
+        self.panorama
+    }
+
+    /// Returns the ID of the acquisition
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+}
+
+impl fmt::Display for AcquisitionRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "slide {}, panorama {}, acquisition {}",
+            self.slide, self.panorama, self.id
+        )
+    }
+}
+
+impl From<AcquisitionRef> for AcquisitionIdentifier {
+    fn from(reference: AcquisitionRef) -> Self {
+        Self::Ref(reference)
+    }
+}
+
+/// Maximum difference between IDs (or order numbers) for an acquisition to be suggested
+pub(crate) const MAX_NUMBER_DISTANCE: usize = 2;
+
+/// Acquisition suggested in place of an identifier which did not match any acquisition (see
+/// `MCDError::InvalidAcquisition`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcquisitionSuggestion {
+    reference: AcquisitionRef,
+    description: String,
+}
+
+impl AcquisitionSuggestion {
+    pub(crate) fn new<R>(acquisition: &Acquisition<R>) -> Self {
+        AcquisitionSuggestion {
+            reference: acquisition.reference(),
+            description: acquisition.description().to_string(),
+        }
+    }
+
+    /// Returns the reference to the suggested acquisition
+    pub fn reference(&self) -> AcquisitionRef {
+        self.reference
+    }
+
+    /// Returns the description of the suggested acquisition
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Display for AcquisitionSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" ({})", self.description, self.reference)
+    }
+}
+
+/// Pattern used to match the description of acquisitions
+#[derive(Debug, Clone)]
+pub enum AcquisitionPattern {
+    /// Glob pattern which must match the whole description. Supports `*`, `?` and character classes (`[a-z]`).
+    Glob(String),
+    /// Regular expression which can match anywhere within the description
+    Regex(Regex),
+}
+
+impl AcquisitionPattern {
+    /// Returns true if the text matches the pattern
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            AcquisitionPattern::Glob(pattern) => pattern::glob_match(pattern, text),
+            AcquisitionPattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+impl fmt::Display for AcquisitionPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AcquisitionPattern::Glob(pattern) => write!(f, "{}", pattern),
+            AcquisitionPattern::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
 }
 
 impl From<&str> for AcquisitionIdentifier {
@@ -55,6 +312,22 @@ impl From<&str> for AcquisitionIdentifier {
     }
 }
 
+impl FromStr for AcquisitionIdentifier {
+    type Err = Infallible;
+
+    /// Interpret the text as an ID if it is a number, a glob pattern if it contains wildcards (`*`, `?` or `[`)
+    /// and otherwise a description, as accepted by the command line tools
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(if let Ok(id) = text.parse() {
+            AcquisitionIdentifier::Id(id)
+        } else if text.contains(['*', '?', '[']) {
+            AcquisitionIdentifier::glob(text)
+        } else {
+            AcquisitionIdentifier::description(text)
+        })
+    }
+}
+
 impl fmt::Display for AcquisitionIdentifier {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -67,6 +340,10 @@ impl fmt::Display for AcquisitionIdentifier {
             AcquisitionIdentifier::Description(description) => {
                 write!(f, "acquisition description: {}", description)
             }
+            AcquisitionIdentifier::Pattern(pattern) => {
+                write!(f, "acquisition pattern: {}", pattern)
+            }
+            AcquisitionIdentifier::Ref(reference) => write!(f, "{}", reference),
         }
     }
 }
@@ -109,8 +386,14 @@ impl<R> Acquisitions for Vec<&Acquisition<R>> {
 /// Acquisition represents a single region analysed by IMC.
 #[derive(Debug)]
 pub struct Acquisition<R> {
-    pub(crate) reader: Option<Arc<Mutex<BufReader<R>>>>,
+    pub(crate) reader: Option<Arc<ReaderPool<R>>>,
     pub(crate) dcm_location: Option<DCMLocation>,
+    pub(crate) cache: Option<Arc<ChannelCache>>,
+    pub(crate) despeckle: Option<Despeckle>,
+
+    // Set when the acquisition is added to its panorama (and the panorama to its slide)
+    pub(crate) slide_id: u16,
+    pub(crate) panorama_id: u16,
 
     id: u16,
     description: String,
@@ -122,14 +405,11 @@ pub struct Acquisition<R> {
     order_number: i16,
     signal_type: String,
     dual_count_start: String,
-    data_start_offset: i64,
-    data_end_offset: i64,
+    data: ByteSpan,
     start_timestamp: String,
     end_timestamp: String,
-    after_ablation_image_start_offset: i64,
-    after_ablation_image_end_offset: i64,
-    before_ablation_image_start_offset: i64,
-    before_ablation_image_end_offset: i64,
+    after_ablation_image: ByteSpan,
+    before_ablation_image: ByteSpan,
     roi_start_x_pos_um: f64,
     roi_start_y_pos_um: f64,
     roi_end_x_pos_um: f64,
@@ -144,8 +424,10 @@ pub struct Acquisition<R> {
     template: String,
 
     profiling_type: Option<ProfilingType>,
+    extras: BTreeMap<String, String>,
 
     channels: Vec<AcquisitionChannel>,
+    roi_points: Vec<RoiPoint>,
 }
 
 impl<R> Clone for Acquisition<R> {
@@ -153,6 +435,10 @@ impl<R> Clone for Acquisition<R> {
         Self {
             reader: self.reader.clone(),
             dcm_location: self.dcm_location.clone(),
+            cache: self.cache.clone(),
+            despeckle: self.despeckle,
+            slide_id: self.slide_id,
+            panorama_id: self.panorama_id,
             id: self.id,
             description: self.description.clone(),
             ablation_power: self.ablation_power,
@@ -163,14 +449,11 @@ impl<R> Clone for Acquisition<R> {
             order_number: self.order_number,
             signal_type: self.signal_type.clone(),
             dual_count_start: self.dual_count_start.clone(),
-            data_start_offset: self.data_start_offset,
-            data_end_offset: self.data_end_offset,
+            data: self.data,
             start_timestamp: self.start_timestamp.clone(),
             end_timestamp: self.end_timestamp.clone(),
-            after_ablation_image_start_offset: self.after_ablation_image_start_offset,
-            after_ablation_image_end_offset: self.after_ablation_image_end_offset,
-            before_ablation_image_start_offset: self.before_ablation_image_start_offset,
-            before_ablation_image_end_offset: self.before_ablation_image_end_offset,
+            after_ablation_image: self.after_ablation_image,
+            before_ablation_image: self.before_ablation_image,
             roi_start_x_pos_um: self.roi_start_x_pos_um,
             roi_start_y_pos_um: self.roi_start_y_pos_um,
             roi_end_x_pos_um: self.roi_end_x_pos_um,
@@ -184,55 +467,69 @@ impl<R> Clone for Acquisition<R> {
             plume_end: self.plume_end,
             template: self.template.clone(),
             profiling_type: self.profiling_type,
+            extras: self.extras.clone(),
             channels: self.channels.clone(),
+            roi_points: self.roi_points.clone(),
         }
     }
 }
 
+/// Iterator over the spectra of an acquisition (see `Acquisition::spectra()`). If a spectrum can't be read, the
+/// error is returned and the iteration ends.
 pub struct SpectrumIterator<'a, R> {
     acquisition: &'a Acquisition<R>,
-    reader: MutexGuard<'a, BufReader<R>>,
+    reader: PooledReader<'a, R>,
     buffer: Vec<u8>,
+    decode: fn(&[u8]) -> f32,
+    remaining: usize,
 }
 
 impl<'a, R: Seek> SpectrumIterator<'a, R> {
-    fn new(acquisition: &'a Acquisition<R>) -> Self {
-        let mut reader = acquisition.reader.as_ref().unwrap().lock().unwrap();
-
-        let offset = acquisition.data_start_offset as u64;
-
-        // TODO: Handle this properly without unwrapping
-        reader.seek(SeekFrom::Start(offset)).unwrap();
+    fn new(acquisition: &'a Acquisition<R>) -> Result<Self> {
+        let decode = acquisition
+            .segment_data_format
+            .decoder(acquisition.value_bytes)?;
+        let mut reader = acquisition
+            .reader
+            .as_ref()
+            .ok_or(MCDError::LocationNotSpecified)?
+            .get()?;
+        reader.seek(SeekFrom::Start(acquisition.data.start()))?;
 
-        SpectrumIterator {
+        Ok(SpectrumIterator {
             acquisition,
             reader,
-            buffer: vec![0u8; 4 * acquisition.channels.len()],
-        }
+            buffer: vec![0u8; acquisition.spectrum_size()],
+            decode,
+            remaining: acquisition.num_spectra(),
+        })
     }
 }
 
 impl<'a, R: Read + Seek> Iterator for SpectrumIterator<'a, R> {
-    type Item = Vec<f32>;
+    type Item = Result<Vec<f32>>;
 
-    fn next(&mut self) -> Option<Vec<f32>> {
-        let cur_pos = self.reader.seek(SeekFrom::Current(0)).unwrap();
-        if cur_pos >= self.acquisition.data_end_offset as u64 {
-            None
-        } else {
-            let mut spectrum = Vec::with_capacity(self.acquisition.channels.len());
-
-            self.reader.read_exact(&mut self.buffer).unwrap();
-            let mut buffer = Cursor::new(&mut self.buffer);
+    fn next(&mut self) -> Option<Result<Vec<f32>>> {
+        if self.remaining == 0 {
+            return None;
+        }
 
-            for _i in 0..self.acquisition.channels.len() {
-                let float = buffer.read_f32::<LittleEndian>().unwrap();
+        if let Err(error) = self.reader.read_exact(&mut self.buffer) {
+            // The remaining spectra can't be read either
+            self.remaining = 0;
+            return Some(Err(error.into()));
+        }
+        self.remaining -= 1;
 
-                spectrum.push(float);
-            }
+        Some(Ok(self
+            .buffer
+            .chunks_exact(self.acquisition.value_bytes as usize)
+            .map(self.decode)
+            .collect()))
+    }
 
-            Some(spectrum)
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
     }
 }
 
@@ -242,6 +539,18 @@ impl<R> Acquisition<R> {
         self.id
     }
 
+    /// Returns the fully-qualified reference (slide, panorama and ID) to the acquisition
+    pub fn reference(&self) -> AcquisitionRef {
+        AcquisitionRef::new(self.slide_id, self.panorama_id, self.id)
+    }
+
+    /// Change the ID of the acquisition, to reproduce files where IDs are only unique per slide (which can't be
+    /// written by `MCDWriter`)
+    #[cfg(test)]
+    pub(crate) fn set_id(&mut self, id: u16) {
+        self.id = id;
+    }
+
     /// Returns a description of the acquisition
     pub fn description(&self) -> &str {
         &self.description
@@ -252,6 +561,16 @@ impl<R> Acquisition<R> {
         self.order_number
     }
 
+    /// Returns the time stamp recorded when the acquisition started
+    pub fn start_timestamp(&self) -> &str {
+        &self.start_timestamp
+    }
+
+    /// Returns the time stamp recorded when the acquisition finished
+    pub fn end_timestamp(&self) -> &str {
+        &self.end_timestamp
+    }
+
     /// Returns the width of the acquired region (in pixels)
     pub fn width(&self) -> i32 {
         self.max_x
@@ -267,6 +586,54 @@ impl<R> Acquisition<R> {
         self.ablation_frequency
     }
 
+    /// Returns the ablation power
+    pub fn ablation_power(&self) -> f64 {
+        self.ablation_power
+    }
+
+    /// Returns the distance between shots (x, y) in μm, i.e. the size of each pixel
+    pub fn ablation_distance_between_shots(&self) -> (f64, f64) {
+        (
+            self.ablation_distance_between_shots_x,
+            self.ablation_distance_between_shots_y,
+        )
+    }
+
+    /// Returns the type of signal recorded (e.g. `Dual`)
+    pub fn signal_type(&self) -> &str {
+        &self.signal_type
+    }
+
+    /// Returns the dual count start recorded for the acquisition
+    pub fn dual_count_start(&self) -> &str {
+        &self.dual_count_start
+    }
+
+    /// Returns the format in which the intensities are stored in the .mcd file
+    pub fn segment_data_format(&self) -> &DataFormat {
+        &self.segment_data_format
+    }
+
+    /// Returns the number of bytes used to store each intensity in the .mcd file
+    pub fn value_bytes(&self) -> u8 {
+        self.value_bytes
+    }
+
+    /// Returns how the stage moved during the acquisition (e.g. `XYLines`)
+    pub fn movement_type(&self) -> &str {
+        &self.movement_type
+    }
+
+    /// Returns the name of the template used for the acquisition
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the window (in pushes) over which the intensities of each plume were integrated by the instrument
+    pub fn plume_window(&self) -> PlumeWindow {
+        PlumeWindow::new(self.plume_start, self.plume_end)
+    }
+
     /// Returns the region ID for the acquisition
     pub fn acquisition_roi_id(&self) -> i16 {
         self.acquisition_roi_id
@@ -277,6 +644,12 @@ impl<R> Acquisition<R> {
         self.profiling_type.as_ref()
     }
 
+    /// Returns the child elements of the `Acquisition` element in the XML metadata which are not modelled here (e.g.
+    /// fields added in newer versions of the schema), with their text, ordered by name
+    pub fn extras(&self) -> &BTreeMap<String, String> {
+        &self.extras
+    }
+
     /*fn image_data(&self, start: i64, end: i64) -> Result<Vec<u8>, std::io::Error> {
         let mutex = self
             .reader
@@ -297,9 +670,9 @@ impl<R> Acquisition<R> {
     pub fn before_ablation_image(&self) -> OpticalImage<R> {
         OpticalImage {
             reader: self.reader.as_ref().unwrap().clone(),
-            start_offset: self.before_ablation_image_start_offset,
-            end_offset: self.before_ablation_image_end_offset,
+            span: self.before_ablation_image,
             image_format: ImageFormat::Png,
+            element: format!("Acquisition {} before ablation image", self.id),
         }
         // match self.dynamic_image(
         //     self.before_ablation_image_start_offset,
@@ -314,9 +687,9 @@ impl<R> Acquisition<R> {
     pub fn after_ablation_image(&self) -> OpticalImage<R> {
         OpticalImage {
             reader: self.reader.as_ref().unwrap().clone(),
-            start_offset: self.after_ablation_image_start_offset,
-            end_offset: self.after_ablation_image_end_offset,
+            span: self.after_ablation_image,
             image_format: ImageFormat::Png,
+            element: format!("Acquisition {} after ablation image", self.id),
         }
     }
 
@@ -325,6 +698,19 @@ impl<R> Acquisition<R> {
         &self.channels
     }
 
+    /// Returns the marker channels acquired within this acquisition, ordered by channel order number. These are
+    /// all channels except those recording the position of each pixel (X, Y and Z).
+    pub fn marker_channels(&self) -> Vec<&AcquisitionChannel> {
+        let mut channels: Vec<_> = self
+            .channels
+            .iter()
+            .filter(|channel| !channel.is_position())
+            .collect();
+        channels.sort_by_key(|channel| channel.order_number());
+
+        channels
+    }
+
     pub(crate) fn channels_mut(&mut self) -> &mut Vec<AcquisitionChannel> {
         &mut self.channels
     }
@@ -332,11 +718,11 @@ impl<R> Acquisition<R> {
     /// Returns whether the acquisition has run to completion (checks the size of the recorded data
     /// compared to the expected data size)
     pub fn is_complete(&self) -> bool {
-        let expected_size: usize = self.channels().len()
-            * self.max_x as usize
-            * self.max_y as usize
-            * self.value_bytes as usize;
-        let measured_size: usize = self.data_end_offset as usize - self.data_start_offset as usize;
+        let expected_size = self.channels().len() as u64
+            * self.max_x as u64
+            * self.max_y as u64
+            * self.value_bytes as u64;
+        let measured_size = self.data.len();
 
         // println!("Expected: {} | Measured: {}", expected_size, measured_size);
 
@@ -345,67 +731,73 @@ impl<R> Acquisition<R> {
 
     /// Returns a `Region` describing the pixel region contained within the specified bounding box
     pub fn pixels_in(&self, region: &BoundingBox<f64>) -> Option<Region> {
-        let transform = self.to_slide_transform();
-
-        let top_left = transform.transform_from_slide(region.min_x, region.min_y)?;
-        let top_right = transform.transform_from_slide(region.max_x(), region.min_y)?;
-
-        let bottom_right = transform.transform_from_slide(region.max_x(), region.max_y())?;
-        let bottom_left = transform.transform_from_slide(region.min_x, region.max_y())?;
-
-        let min_x = top_left
-            .x
-            .min(top_right.x)
-            .min(bottom_right.x)
-            .min(bottom_left.x)
+        let corners = [
+            SlidePoint::new(region.min_x, region.min_y).to_acquisition(self)?,
+            SlidePoint::new(region.max_x(), region.min_y).to_acquisition(self)?,
+            SlidePoint::new(region.max_x(), region.max_y()).to_acquisition(self)?,
+            SlidePoint::new(region.min_x, region.max_y()).to_acquisition(self)?,
+        ];
+
+        let min_x = corners
+            .iter()
+            .map(|corner| corner.x)
+            .fold(f64::MAX, f64::min)
             .max(0.0)
             .floor();
-
-        let max_x = top_left
-            .x
-            .max(top_right.x)
-            .max(bottom_right.x)
-            .max(bottom_left.x)
+        let max_x = corners
+            .iter()
+            .map(|corner| corner.x)
+            .fold(f64::MIN, f64::max)
             .min(self.width() as f64)
             .ceil();
 
-        let min_y = top_left
-            .y
-            .min(top_right.y)
-            .min(bottom_right.y)
-            .min(bottom_left.y)
+        let min_y = corners
+            .iter()
+            .map(|corner| corner.y)
+            .fold(f64::MAX, f64::min)
             .max(0.0)
             .floor();
-
-        let max_y = top_left
-            .y
-            .max(top_right.y)
-            .max(bottom_right.y)
-            .max(bottom_left.y)
+        let max_y = corners
+            .iter()
+            .map(|corner| corner.y)
+            .fold(f64::MIN, f64::max)
             .min(self.height() as f64)
             .ceil();
 
         Some(Region {
             x: min_x as u32,
-            y: (self.height() as u32 - max_y as u32),
+            y: min_y as u32,
             width: (max_x - min_x) as u32,
             height: (max_y - min_y) as u32,
         })
     }
 
     /// Tests whether the acquisition is (at least partially) contained within the specified bounding box (slide coordinates).
+    /// For acquisitions defined by a free-hand ROI, this uses the outline of the ROI (see `roi_shape()`).
     pub fn in_region(&self, region: &BoundingBox<f64>) -> bool {
-        let slide_box = self.slide_bounding_box();
+        self.roi_shape().intersects(region)
+    }
 
-        if slide_box.min_x < region.max_x()
-            && slide_box.max_x() > region.min_x
-            && slide_box.max_y() > region.min_y
-            && slide_box.min_y < region.max_y()
-        {
-            return true;
-        }
+    /// Returns the points outlining the region of interest (ROI) which defined the acquisition, ordered by order
+    /// number. This is empty if no points were recorded.
+    pub fn roi_points(&self) -> &[RoiPoint] {
+        &self.roi_points
+    }
 
-        false
+    /// Returns the shape of the region of the slide covered by the acquisition: the outline of the ROI if it has at
+    /// least 3 points, otherwise the bounding box of the acquisition
+    pub fn roi_shape(&self) -> RoiShape {
+        RoiShape::from_points(&self.roi_points)
+            .unwrap_or_else(|| RoiShape::Rectangle(self.slide_bounding_box()))
+    }
+
+    pub(crate) fn roi_points_mut(&mut self) -> &mut Vec<RoiPoint> {
+        &mut self.roi_points
+    }
+
+    /// Returns the span of the .mcd file containing the spectra of the acquisition
+    pub fn data_span(&self) -> ByteSpan {
+        self.data
     }
 
     /// Returns the size of a single spectrum in bytes
@@ -417,9 +809,55 @@ impl<R> Acquisition<R> {
     /// Returns the number of spectra acquired as part of the acquisition
     #[inline]
     pub fn num_spectra(&self) -> usize {
-        let measured_size: usize = self.data_end_offset as usize - self.data_start_offset as usize;
+        match self.spectrum_size() {
+            0 => 0,
+            spectrum_size => (self.data.len() / spectrum_size as u64) as usize,
+        }
+    }
 
-        measured_size / self.spectrum_size()
+    /// Returns the width and height (in pixels) of the chunks in which the channel images are stored in the .dcm
+    /// file, or None if the acquisition is read from the .mcd file (see `MCD::with_dcm()`). Reading regions
+    /// aligned to the chunks avoids decompressing chunks which are only partially required.
+    pub fn dcm_chunk_size(&self) -> Option<u32> {
+        self.dcm_location
+            .as_ref()
+            .map(|location| location.chunk_size())
+    }
+
+    /// Returns the extent of the acquired pixels. If the acquisition was aborted part way through a row, the
+    /// partial row is described by `ValidRegion::partial_row_width()`.
+    pub fn valid_region(&self) -> ValidRegion {
+        let width = self.width().max(1) as usize;
+        let num_spectra = self
+            .num_spectra()
+            .min(width * self.height().max(0) as usize);
+
+        ValidRegion::new(
+            (num_spectra / width) as u32,
+            (num_spectra % width) as u32,
+            width as u32,
+        )
+    }
+
+    /// Returns the number of acquired pixels within the region. As pixels are acquired row by row, these are
+    /// always the first pixels of the region (in row-major order).
+    pub(crate) fn valid_pixels_in(&self, region: &Region) -> usize {
+        let valid_region = self.valid_region();
+
+        (region.y..(region.y + region.height))
+            .map(|y| {
+                if y < valid_region.full_rows() {
+                    region.width
+                } else if y == valid_region.full_rows() {
+                    valid_region
+                        .partial_row_width()
+                        .saturating_sub(region.x)
+                        .min(region.width)
+                } else {
+                    0
+                }
+            })
+            .sum::<u32>() as usize
     }
 }
 
@@ -438,8 +876,296 @@ impl<R: Read + Seek> Acquisition<R> {
             .expect("A channel image should always be returned, as we always pass one identifier"))
     }
 
+    /// Read the intensities of the channel matching the `ChannelIdentifier` (as for
+    /// `Acquisition::channel_image`) into `buffer`, in row-major order with NaN for pixels which were not
+    /// acquired. The buffer is resized to the number of pixels in the region, reusing its existing allocation,
+    /// so that extracting many channels (e.g. all channels for all ROIs) in a loop doesn't repeatedly allocate
+    /// large images.
+    ///
+    /// Images returned from the channel cache or despeckled (see `MCD::with_channel_cache()` and
+    /// `MCD::with_despeckle()`) are copied into the buffer.
+    pub fn channel_image_into<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        region: Option<Region>,
+        buffer: &mut Vec<f32>,
+    ) -> Result<()> {
+        if self.cache.is_some() || self.despeckle.is_some() {
+            let image = self.channel_image(identifier, region)?;
+            buffer.clear();
+            buffer.extend_from_slice(image.intensities());
+
+            return Ok(());
+        }
+
+        let identifier = identifier.into();
+        let channel = self
+            .channel(&identifier)
+            .ok_or_else(|| channel_not_found(&identifier, self.channels()))?;
+        let region = region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.width() as u32,
+            height: self.height() as u32,
+        });
+
+        self.read_channels_into(&[channel], region, std::slice::from_mut(buffer))
+    }
+
+    /// Read the intensities of the channel matching the `ChannelIdentifier` into `array` (as for
+    /// `Acquisition::channel_image_into`), which is reshaped to (height, width) of the region. The allocation
+    /// of the array is reused. If an error is returned, the array is left empty.
+    #[cfg(feature = "ndarray")]
+    pub fn channel_image_into_array<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        region: Option<Region>,
+        array: &mut ndarray::Array2<f32>,
+    ) -> Result<()> {
+        let (width, height) = match region {
+            Some(region) => (region.width as usize, region.height as usize),
+            None => (self.width() as usize, self.height() as usize),
+        };
+
+        let mut buffer = std::mem::take(array).into_raw_vec();
+        self.channel_image_into(identifier, region, &mut buffer)?;
+
+        let actual = buffer.len();
+        *array = ndarray::Array2::from_shape_vec((height, width), buffer).or(Err(
+            MCDError::InvalidBufferSize {
+                expected: width * height,
+                actual,
+            },
+        ))?;
+
+        Ok(())
+    }
+
+    /// Returns the raw (integer) ion counts of the channel matching the `ChannelIdentifier` within the region (or
+    /// the whole acquisition), in row-major order, with None for pixels which were not acquired. Unlike
+    /// `Acquisition::channel_image`, no despeckling is applied, as statistical models of counts (e.g. Poisson)
+    /// require the values as recorded.
+    ///
+    /// With dual counting (see `Acquisition::signal_type` and `Acquisition::dual_count_start`), the detector
+    /// counts individual ions (pulse counting) at low intensities, and above the dual count start converts the
+    /// analog signal into an equivalent number of counts. The intensities stored in the .mcd file are these
+    /// converted counts, so they are only whole numbers where analog conversion was not needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCDError::RawCountsUnavailable`] if any of the intensities are not whole, non-negative numbers
+    /// (e.g. they were converted from the analog signal), as the original counts can't be recovered.
+    pub fn channel_counts<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        region: Option<Region>,
+    ) -> Result<Vec<Option<u32>>> {
+        let identifier = identifier.into();
+        let channel = self
+            .channel(&identifier)
+            .ok_or_else(|| channel_not_found(&identifier, self.channels()))?;
+        let region = region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.width() as u32,
+            height: self.height() as u32,
+        });
+
+        let image = self
+            .read_channel_images(&[channel], region)?
+            .pop()
+            .expect("A channel image should always be returned, as we always pass one channel");
+
+        image
+            .intensities()
+            .iter()
+            .enumerate()
+            .map(|(index, &intensity)| {
+                if intensity.is_nan() {
+                    Ok(None)
+                } else if intensity >= 0.0
+                    && intensity.fract() == 0.0
+                    && intensity <= u32::MAX as f32
+                {
+                    Ok(Some(intensity as u32))
+                } else {
+                    Err(MCDError::RawCountsUnavailable {
+                        acquisition_id: self.id,
+                        reason: format!(
+                            "{} stored as {} includes the non-integer intensity {} (pixel {}), \
+                             e.g. counts converted from the analog signal",
+                            channel.name(),
+                            self.segment_data_format,
+                            intensity,
+                            index
+                        ),
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the intensities of each channel for the pixels within the polygon, described by its vertices on
+    /// the slide (in μm). A pixel is within the polygon if its centre is (using the even-odd rule), and pixels
+    /// which were not acquired are skipped. The intensities are returned in the order of `identifiers`, with the
+    /// pixels of each channel in row-major order.
+    pub fn intensities_in_polygon<C: AsRef<ChannelIdentifier>>(
+        &self,
+        polygon: &[(f64, f64)],
+        identifiers: &[C],
+    ) -> Result<Vec<Vec<f32>>> {
+        if polygon.len() < 3 {
+            return Err(MCDError::InvalidPolygon {
+                reason: format!("{} vertices (at least 3 are required)", polygon.len()),
+            });
+        }
+
+        let vertices = polygon
+            .iter()
+            .map(|&(x, y)| {
+                SlidePoint::new(x, y)
+                    .to_acquisition(self)
+                    .map(|pixel| (pixel.x, pixel.y))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| MCDError::InvalidPolygon {
+                reason: format!(
+                    "acquisition {} has no valid transform to the slide",
+                    self.id()
+                ),
+            })?;
+
+        let bounds = |values: &mut dyn Iterator<Item = f64>, max: i32| {
+            let (min, max_value) = values.fold((f64::MAX, f64::MIN), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+
+            (
+                min.floor().clamp(0.0, max as f64) as u32,
+                max_value.ceil().clamp(0.0, max as f64) as u32,
+            )
+        };
+        let (min_x, max_x) = bounds(&mut vertices.iter().map(|vertex| vertex.0), self.width());
+        let (min_y, max_y) = bounds(&mut vertices.iter().map(|vertex| vertex.1), self.height());
+
+        if min_x >= max_x || min_y >= max_y {
+            return Ok(vec![Vec::new(); identifiers.len()]);
+        }
+
+        let region = Region {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        };
+
+        let inside: Vec<usize> = (0..region.height)
+            .flat_map(|y| (0..region.width).map(move |x| (x, y)))
+            .enumerate()
+            .filter(|&(_, (x, y))| {
+                polygon_contains(
+                    &vertices,
+                    (region.x + x) as f64 + 0.5,
+                    (region.y + y) as f64 + 0.5,
+                )
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        Ok(self
+            .channel_images(identifiers, Some(region))?
+            .iter()
+            .map(|image| {
+                inside
+                    .iter()
+                    .take_while(|&&index| index < image.valid_pixels)
+                    .filter_map(|&index| image.data.get(index).copied())
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Returns the ChannelImage for the channel matching the `ChannelIdentifier`, with the intensities corrected
+    /// using the calibration data (see `MCD::channel_corrections()`). The raw intensities are available through
+    /// `channel_image()`.
+    pub fn channel_image_corrected<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        region: Option<Region>,
+        corrections: &ChannelCorrections,
+    ) -> Result<ChannelImage> {
+        let identifier = identifier.into();
+        let correction = match self.channel(&identifier) {
+            Some(channel) => corrections.correction(channel),
+            None => return Err(channel_not_found(&identifier, self.channels())),
+        };
+
+        let mut image = self.channel_image(identifier, region)?;
+        for intensity in image.data.iter_mut() {
+            *intensity = correction.apply(*intensity);
+        }
+        image.range = (
+            correction.apply(image.range.0),
+            correction.apply(image.range.1),
+        );
+
+        Ok(image)
+    }
+
+    /// Returns a reduced resolution image of the channel, no larger than `max_dim` pixels in either dimension,
+    /// where each pixel is the mean of the block of pixels it covers (ignoring missing pixels). The acquisition
+    /// is read in bands of rows, so the full resolution image is never held in memory.
+    pub fn channel_thumbnail<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        max_dim: u32,
+    ) -> Result<ChannelImage> {
+        let identifier = identifier.into();
+        let channel = self
+            .channel(&identifier)
+            .ok_or_else(|| channel_not_found(&identifier, self.channels()))?;
+
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        let factor = width.max(height).div_ceil(max_dim.max(1)).max(1);
+
+        // Bands of around 256 rows (the size of a .dcm chunk), covering whole blocks
+        let band_height = factor * (256 / factor).max(1);
+
+        let mut data =
+            Vec::with_capacity(width.div_ceil(factor) as usize * height.div_ceil(factor) as usize);
+        for y in (0..height).step_by(band_height as usize) {
+            let band = Region {
+                x: 0,
+                y,
+                width,
+                height: band_height.min(height - y),
+            };
+            let image = self.channel_image(identifier.clone(), Some(band))?;
+
+            data.extend(tiles::downsample(
+                &image.data,
+                band.width,
+                band.height,
+                factor,
+            ));
+        }
+
+        let region = Region {
+            x: 0,
+            y: 0,
+            width: width.div_ceil(factor),
+            height: height.div_ceil(factor),
+        };
+
+        Ok(self.downsampled_image(channel, factor, region, data))
+    }
+
     /// Returns array of ChannelImages for the channels matching the `ChannelIdentifier`s. This contains the intensities of the channel
     /// for each detected pixel, the number of valid pixels and the width and height of the image.
+    ///
+    /// If a channel cache is enabled (see `MCD::with_channel_cache()`), images are returned from the cache where
+    /// possible and only the remaining channels are read. If despeckling is enabled (see `MCD::with_despeckle()`),
+    /// hot pixels are removed from each image before it is returned.
     pub fn channel_images<C: AsRef<ChannelIdentifier>>(
         &self,
         identifiers: &[C],
@@ -451,17 +1177,11 @@ impl<R: Read + Seek> Acquisition<R> {
         let channels: Vec<_> = identifiers
             .iter()
             .map(|identifier| {
-                self.channel(identifier).ok_or(MCDError::InvalidChannel {
-                    channel: identifier.as_ref().clone(),
-                })
+                self.channel(identifier)
+                    .ok_or_else(|| channel_not_found(identifier.as_ref(), self.channels()))
             })
             .collect::<Result<Vec<_>>>()?;
 
-        let order_numbers: Vec<_> = channels
-            .iter()
-            .map(|channel| channel.order_number() as usize)
-            .collect();
-
         let region = match region {
             Some(region) => region,
             None => crate::Region {
@@ -472,47 +1192,70 @@ impl<R: Read + Seek> Acquisition<R> {
             },
         };
 
-        let last_row = self.num_spectra() / self.width() as usize;
-        let last_col = self.width() as usize - (self.num_spectra() % self.width() as usize);
+        let images = match &self.cache {
+            Some(cache) => self.cached_channel_images(&channels, region, cache)?,
+            None => self.read_channel_images(&channels, region)?,
+        };
 
-        let valid_region_row = (region.y + region.height).min(last_row as u32);
-        let valid_region_col = (region.x + region.width).min(last_col as u32);
+        Ok(match self.despeckle {
+            Some(method) => images.iter().map(|image| image.despeckle(method)).collect(),
+            None => images,
+        })
+    }
 
-        // println!("{} / {}", valid_region_row, region.y);
-        let valid_pixels = if region.y >= valid_region_row {
-            (valid_region_row - 1) * region.width + valid_region_col
-        } else {
-            ((valid_region_row - region.y - 1) * region.width) + (valid_region_col - region.x)
-        };
+    /// Returns the images of the channels from the cache where possible, reading (and caching) the remaining
+    /// channels
+    fn cached_channel_images(
+        &self,
+        channels: &[&AcquisitionChannel],
+        region: Region,
+        cache: &ChannelCache,
+    ) -> Result<Vec<ChannelImage>> {
+        let reference = self.reference();
+        let cached: Vec<_> = channels
+            .iter()
+            .map(|channel| cache.get(reference, channel.name(), &region))
+            .collect();
 
-        let mut data = if let Some(data_location) = &self.dcm_location {
-            data_location.read_channels(&order_numbers, &region)?
-        } else {
-            let mut data: Vec<Vec<f32>> =
-                vec![
-                    Vec::with_capacity((region.width * region.height) as usize);
-                    identifiers.len()
-                ];
-
-            let order_hash: HashSet<usize> = HashSet::from_iter(order_numbers.iter().copied());
-
-            for y in region.y..(region.y + region.height) {
-                for x in region.x..(region.x + region.width) {
-                    for (channel_index, intensity) in self
-                        .spectrum(x, y)?
-                        .iter()
-                        .enumerate()
-                        .filter(|(index, _intensity)| order_hash.contains(index))
-                        .map(|(_, intensity)| *intensity)
-                        .enumerate()
-                    {
-                        data[channel_index].push(intensity);
-                    }
+        let missing: Vec<_> = channels
+            .iter()
+            .zip(&cached)
+            .filter(|(_, image)| image.is_none())
+            .map(|(&channel, _)| channel)
+            .collect();
+        let mut read = self.read_channel_images(&missing, region)?.into_iter();
+
+        cached
+            .into_iter()
+            .map(|image| match image {
+                Some(image) => Ok(image),
+                None => {
+                    let image = read.next().ok_or(MCDError::InvalidBufferSize {
+                        expected: missing.len(),
+                        actual: 0,
+                    })?;
+                    cache.insert(reference, &image);
+
+                    Ok(image)
                 }
-            }
+            })
+            .collect()
+    }
 
-            data
-        };
+    /// Read the images of the channels from the .dcm file (if available) or the .mcd file
+    fn read_channel_images(
+        &self,
+        channels: &[&AcquisitionChannel],
+        region: Region,
+    ) -> Result<Vec<ChannelImage>> {
+        if channels.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let valid_pixels = self.valid_pixels_in(&region);
+
+        let mut data = vec![Vec::new(); channels.len()];
+        self.read_channels_into(channels, region, &mut data)?;
 
         let images: Vec<_> = data
             .drain(..)
@@ -521,6 +1264,7 @@ impl<R: Read + Seek> Acquisition<R> {
                 let mut min_value = f32::MAX;
                 let mut max_value = f32::MIN;
 
+                // NaN (missing) pixels are ignored, as comparisons with NaN are always false
                 for &data_point in data.iter() {
                     if data_point < min_value {
                         min_value = data_point;
@@ -536,13 +1280,69 @@ impl<R: Read + Seek> Acquisition<R> {
                     name: channel.name().to_string(),
                     label: channel.label().to_string(),
                     range: (min_value, max_value),
-                    valid_pixels: valid_pixels as usize,
+                    valid_pixels,
                     data,
                 }
             })
             .collect();
 
         Ok(images)
+    }
+
+    /// Read the intensities of the channels from the .dcm file (if available) or the .mcd file into the supplied
+    /// buffers (one per channel), reusing their existing allocations
+    fn read_channels_into(
+        &self,
+        channels: &[&AcquisitionChannel],
+        region: Region,
+        data: &mut [Vec<f32>],
+    ) -> Result<()> {
+        let order_numbers: Vec<_> = channels
+            .iter()
+            .map(|channel| channel.order_number() as usize)
+            .collect();
+
+        if let Some(data_location) = &self.dcm_location {
+            return data_location.read_channels_into(&order_numbers, &region, data);
+        }
+
+        if data.len() != order_numbers.len() {
+            return Err(MCDError::InvalidBufferSize {
+                expected: order_numbers.len(),
+                actual: data.len(),
+            });
+        }
+
+        for channel_data in data.iter_mut() {
+            channel_data.clear();
+            channel_data.reserve((region.width * region.height) as usize);
+        }
+
+        let mut spectrum = vec![0.0; self.channels.len()];
+        for y in region.y..(region.y + region.height) {
+            for x in region.x..(region.x + region.width) {
+                match self.spectrum_into(x, y, &mut spectrum) {
+                    Ok(()) => {}
+                    // Pixels which were not acquired (aborted acquisition) are missing
+                    Err(MCDError::InvalidIndex { .. }) => {
+                        for channel_data in data.iter_mut() {
+                            channel_data.push(f32::NAN);
+                        }
+
+                        continue;
+                    }
+                    Err(error) => return Err(error),
+                };
+
+                // Each buffer holds the channel requested at the same index, in the requested order (which may
+                // include the same channel more than once)
+                for (channel_data, &order_number) in data.iter_mut().zip(&order_numbers) {
+                    channel_data.push(spectrum.get(order_number).copied().unwrap_or(f32::NAN));
+                }
+            }
+        }
+
+        Ok(())
 
         // Ok(ChannelImage {
         //     region,
@@ -562,6 +1362,20 @@ impl<R: Read + Seek> Acquisition<R> {
             .find(|&channel| channel.is(identifier.as_ref()))
     }
 
+    /// Returns the channels most similar to the identifier, closest first, to suggest in place of an identifier
+    /// which doesn't match any channel (e.g. `Ir191` for the name `Ir193`, or the label `DNA1` for `dna-1`)
+    pub fn channel_suggestions<C: AsRef<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+    ) -> Vec<ChannelSummary> {
+        error::channel_suggestions(identifier.as_ref(), &self.channels)
+    }
+
+    /// Returns [`MCDError::ChannelNotFound`] for the identifier, listing the available channels and suggestions
+    pub fn channel_not_found<C: AsRef<ChannelIdentifier>>(&self, identifier: C) -> MCDError {
+        channel_not_found(identifier.as_ref(), &self.channels)
+    }
+
     // pub fn channel_index(&self, identifier: &ChannelIdentifier) -> Option<usize> {
     //     for (index, channel) in self.channels.iter().enumerate() {
     //         if channel.is(identifier) {
@@ -573,38 +1387,118 @@ impl<R: Read + Seek> Acquisition<R> {
     // }
 
     // There are a number of potential issues with the ROI positions that we attempt to fix here
-    pub(crate) fn fix_roi_positions(&mut self) {
+    /// Use the usual number of bytes for the data format if `ValueBytes` was missing or invalid, returning true
+    /// if it was corrected
+    pub(crate) fn fix_value_bytes(&mut self) -> bool {
+        if self.value_bytes != 0 {
+            return false;
+        }
+
+        match self.segment_data_format.default_value_bytes() {
+            Some(value_bytes) => {
+                self.value_bytes = value_bytes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Correct the ROI positions which are known to be recorded incorrectly, returning the names of the fields
+    /// which were corrected
+    pub(crate) fn fix_roi_positions(&mut self) -> Vec<&'static str> {
+        let mut fixed = Vec::new();
+
         // In version 2 of the schema, it seems like ROIStartXPosUm and ROIStartYPosUm are 1000x what they should be, so try and detect this and correct for it
         if self.roi_start_x_pos_um > 75000.0 {
             self.roi_start_x_pos_um /= 1000.0;
+            fixed.push("ROIStartXPosUm");
         }
 
         // In version 2 of the schema, it seems like ROIStartXPosUm and ROIStartYPosUm are 1000x what they should be, so try and detect this and correct for it
         if self.roi_start_y_pos_um > 75000.0 {
             self.roi_start_y_pos_um /= 1000.0;
+            fixed.push("ROIStartYPosUm");
         }
 
         // There seems to be a bug where the start and end x pos is recorded as the same value
         if (self.roi_start_x_pos_um == self.roi_end_x_pos_um) || self.roi_end_x_pos_um == 0.0 {
             self.roi_end_x_pos_um = self.roi_start_x_pos_um
                 + (self.max_x as f64 * self.ablation_distance_between_shots_x);
+            fixed.push("ROIEndXPosUm");
         }
 
         if self.roi_end_y_pos_um == 0.0 {
             self.roi_end_y_pos_um = self.roi_start_y_pos_um
                 - (self.max_y as f64 * self.ablation_distance_between_shots_y);
+            fixed.push("ROIEndYPosUm");
         }
+
+        fixed
     }
 }
 
 impl<R: Read + Seek> Acquisition<R> {
-    /// Provides an iterator over all spectra (each pixel) within the acquisition
-    pub fn spectra(&self) -> SpectrumIterator<R> {
+    /// Provides an iterator over all spectra (each pixel) within the acquisition, or an error if the intensities
+    /// are stored in an unsupported format
+    pub fn spectra(&self) -> Result<SpectrumIterator<'_, R>> {
         SpectrumIterator::new(self)
     }
 
     /// Returns a spectrum at the specified (x, y) coordinate
     pub fn spectrum(&self, x: u32, y: u32) -> Result<Vec<f32>> {
+        let mut spectrum = vec![0.0; self.channels.len()];
+        self.spectrum_into(x, y, &mut spectrum)?;
+
+        Ok(spectrum)
+    }
+
+    /// Read the spectrum at the specified (x, y) coordinate into `spectrum`, which must hold one value per channel
+    /// (ordered by channel order number, as `Acquisition::spectrum`). This avoids allocating when reading many
+    /// spectra.
+    pub fn spectrum_into(&self, x: u32, y: u32, spectrum: &mut [f32]) -> Result<()> {
+        if spectrum.len() != self.channels.len() {
+            return Err(MCDError::InvalidBufferSize {
+                expected: self.channels.len(),
+                actual: spectrum.len(),
+            });
+        }
+
+        self.read_spectrum(x, y, |index, intensity| spectrum[index] = intensity)
+    }
+
+    /// Read the intensities of the marker channels at the specified (x, y) coordinate into `spectrum`, skipping
+    /// the X, Y and Z position channels. `spectrum` must hold one value per marker channel, and is filled in the
+    /// same order as `Acquisition::marker_channels`.
+    pub fn marker_spectrum_into(&self, x: u32, y: u32, spectrum: &mut [f32]) -> Result<()> {
+        let mut positions = [usize::MAX; 3];
+        let mut num_positions = 0;
+        for (position, channel) in positions
+            .iter_mut()
+            .zip(self.channels.iter().filter(|channel| channel.is_position()))
+        {
+            *position = channel.order_number() as usize;
+            num_positions += 1;
+        }
+
+        if spectrum.len() != self.channels.len() - num_positions {
+            return Err(MCDError::InvalidBufferSize {
+                expected: self.channels.len() - num_positions,
+                actual: spectrum.len(),
+            });
+        }
+
+        let mut marker_index = 0;
+        self.read_spectrum(x, y, |index, intensity| {
+            if !positions.contains(&index) {
+                spectrum[marker_index] = intensity;
+                marker_index += 1;
+            }
+        })
+    }
+
+    /// Read the spectrum at the specified (x, y) coordinate, calling `visit` with the index (order number) and
+    /// intensity of each channel
+    fn read_spectrum<F: FnMut(usize, f32)>(&self, x: u32, y: u32, mut visit: F) -> Result<()> {
         let index = y as usize * self.max_x as usize + x as usize;
 
         if index >= self.num_spectra() {
@@ -614,23 +1508,59 @@ impl<R: Read + Seek> Acquisition<R> {
             });
         }
 
-        let offset = self.data_start_offset as u64
-            + (index * self.channels.len() * self.value_bytes as usize) as u64;
-
-        let mut spectrum = Vec::with_capacity(self.channels.len());
-        let mut reader = self.reader.as_ref().unwrap().lock().unwrap();
+        let offset = self.data.start() + index as u64 * self.spectrum_size() as u64;
 
-        // TODO: Handle this properly without unwrapping
+        let decode = self.segment_data_format.decoder(self.value_bytes)?;
+        let mut reader = self
+            .reader
+            .as_ref()
+            .ok_or(MCDError::LocationNotSpecified)?
+            .get()?;
         reader.seek(SeekFrom::Start(offset))?;
 
-        let mut buffer = [0u8; 4];
-        for _i in 0..self.channels.len() {
+        let mut buffer = [0u8; 8];
+        let buffer = &mut buffer[..self.value_bytes as usize];
+        for channel_index in 0..self.channels.len() {
+            reader.read_exact(buffer)?;
+            visit(channel_index, decode(buffer));
+        }
+
+        Ok(())
+    }
+
+    /// Read `num_spectra` consecutive spectra starting from the spectrum at `first_index`, returning the
+    /// intensities spectrum by spectrum
+    pub(crate) fn read_spectra(&self, first_index: usize, num_spectra: usize) -> Result<Vec<f32>> {
+        if first_index + num_spectra > self.num_spectra() {
+            return Err(MCDError::InvalidIndex {
+                index: first_index + num_spectra,
+                num_spectra: self.num_spectra(),
+            });
+        }
+
+        let decode = self.segment_data_format.decoder(self.value_bytes)?;
+        let mut buffer = vec![0u8; num_spectra * self.spectrum_size()];
+
+        if !buffer.is_empty() {
+            let mut reader = self
+                .reader
+                .as_ref()
+                .ok_or(MCDError::LocationNotSpecified)?
+                .get()?;
+            let start_offset = self.data.start() + first_index as u64 * self.spectrum_size() as u64;
+            reader::check_in_bounds(
+                reader.deref_mut(),
+                || format!("Acquisition {} data", self.id),
+                start_offset + buffer.len() as u64,
+            )?;
+            reader.seek(SeekFrom::Start(start_offset))?;
             reader.read_exact(&mut buffer)?;
-            let float = f32::from_le_bytes(buffer);
-            spectrum.push(float);
         }
 
-        Ok(spectrum)
+        Ok(buffer
+            .chunks_exact(self.value_bytes as usize)
+            .map(decode)
+            .collect())
     }
 }
 
@@ -681,141 +1611,41 @@ impl<R> OnSlide for Acquisition<R> {
     }
 }
 
-#[rustfmt::skip]
-impl<R> Print for Acquisition<R> {
-    fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "Acquisition", 48)?;
-
-        writeln!(writer, "{:indent$}{: <22} | {}", "", "ID",          self.id,          indent = indent)?;
-        writeln!(writer, "{:indent$}{: <22} | {}", "", "Description", self.description, indent = indent)?;
-        writeln!(writer, "{:indent$}{: <22} | {}", "", "Order number", self.order_number, indent = indent)?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {} x {}",
-            "",
-            "Dimensions (pixels)",
-            self.max_x,
-            self.max_y,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {} x {}",
-            "",
-            "Distance between shots",
-            self.ablation_distance_between_shots_x,
-            self.ablation_distance_between_shots_y,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Signal type",
-            self.signal_type,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Ablation power",
-            self.ablation_power,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Dual count start",
-            self.dual_count_start,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Start timestamp",
-            self.start_timestamp,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "End timestamp",
-            self.end_timestamp,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | ({:.4} μm, {:.4} μm)",
-            "",
-            "ROI",
-            self.roi_start_x_pos_um,
-            self.roi_start_y_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | ({:.4} μm, {:.4} μm)",
-            "",
-            "",
-            self.roi_end_x_pos_um,
-            self.roi_end_y_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Movement type",
-            self.movement_type,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {:?}",
-            "",
-            "Segment data format",
-            self.segment_data_format,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Value bytes",
-            self.value_bytes,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Plume start",
-            self.plume_start,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Plume end",
-            self.plume_end,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <22} | {}",
-            "",
-            "Template",
-            self.template,
-            indent = indent
-        )?;
-
-        Ok(())
+impl<R> Describe for Acquisition<R> {
+    fn describe(&self) -> Description {
+        Description::new("Acquisition")
+            .field("ID", self.id)
+            .field("Description", &self.description)
+            .field("Order number", self.order_number)
+            .field(
+                "Dimensions (pixels)",
+                Value::Size(self.max_x as f64, self.max_y as f64),
+            )
+            .field(
+                "Distance between shots",
+                Value::Size(
+                    self.ablation_distance_between_shots_x,
+                    self.ablation_distance_between_shots_y,
+                ),
+            )
+            .field("Signal type", &self.signal_type)
+            .field("Ablation power", self.ablation_power)
+            .field("Dual count start", &self.dual_count_start)
+            .field("Start timestamp", &self.start_timestamp)
+            .field("End timestamp", &self.end_timestamp)
+            .field(
+                "ROI",
+                vec![
+                    Value::Position(self.roi_start_x_pos_um, self.roi_start_y_pos_um),
+                    Value::Position(self.roi_end_x_pos_um, self.roi_end_y_pos_um),
+                ],
+            )
+            .field("Movement type", &self.movement_type)
+            .field("Segment data format", self.segment_data_format.to_string())
+            .field("Value bytes", self.value_bytes)
+            .field("Plume start", self.plume_start)
+            .field("Plume end", self.plume_end)
+            .field("Template", &self.template)
     }
 }
 
@@ -830,6 +1660,11 @@ impl<R> From<AcquisitionXML> for Acquisition<R> {
         Acquisition {
             reader: None,
             dcm_location: None,
+            cache: None,
+            despeckle: None,
+
+            slide_id: 0,
+            panorama_id: 0,
 
             id: acquisition.id.unwrap(),
             description: acquisition.description.unwrap(),
@@ -845,25 +1680,24 @@ impl<R> From<AcquisitionXML> for Acquisition<R> {
             order_number: acquisition.order_number.unwrap(),
             signal_type: acquisition.signal_type.unwrap(),
             dual_count_start: acquisition.dual_count_start.unwrap(),
-            data_start_offset: acquisition.data_start_offset.unwrap(),
-            data_end_offset: acquisition.data_end_offset.unwrap(),
+            data: ByteSpan::from_xml(acquisition.data_start_offset, acquisition.data_end_offset),
             start_timestamp: acquisition.start_timestamp.unwrap(),
             end_timestamp: acquisition.end_timestamp.unwrap(),
-            after_ablation_image_start_offset: acquisition
-                .after_ablation_image_start_offset
-                .unwrap(),
-            after_ablation_image_end_offset: acquisition.after_ablation_image_end_offset.unwrap(),
-            before_ablation_image_start_offset: acquisition
-                .before_ablation_image_start_offset
-                .unwrap(),
-            before_ablation_image_end_offset: acquisition.before_ablation_image_end_offset.unwrap(),
+            after_ablation_image: ByteSpan::from_xml(
+                acquisition.after_ablation_image_start_offset,
+                acquisition.after_ablation_image_end_offset,
+            ),
+            before_ablation_image: ByteSpan::from_xml(
+                acquisition.before_ablation_image_start_offset,
+                acquisition.before_ablation_image_end_offset,
+            ),
             roi_start_x_pos_um: acquisition.roi_start_x_pos_um.unwrap(),
             roi_start_y_pos_um: acquisition.roi_start_y_pos_um.unwrap(),
             roi_end_x_pos_um: acquisition.roi_end_x_pos_um.unwrap(),
             roi_end_y_pos_um: acquisition.roi_end_y_pos_um.unwrap(),
             movement_type: acquisition.movement_type.unwrap(),
             segment_data_format: acquisition.segment_data_format.unwrap(),
-            value_bytes: acquisition.value_bytes.unwrap(),
+            value_bytes: acquisition.value_bytes.unwrap_or(0),
             max_x: acquisition.max_x.unwrap(),
             max_y: acquisition.max_y.unwrap(),
             plume_start: acquisition.plume_start.unwrap(),
@@ -871,8 +1705,266 @@ impl<R> From<AcquisitionXML> for Acquisition<R> {
             template: acquisition.template.unwrap(),
 
             profiling_type: acquisition.profiling_type,
+            extras: acquisition.extras,
 
             channels: Vec::new(),
+            roi_points: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    #[test]
+    fn parse_identifier() {
+        let parse = |text: &str| {
+            text.parse::<AcquisitionIdentifier>()
+                .map(|id| id.to_string())
+        };
+
+        assert_eq!(parse("12"), Ok("acquisition id: 12".to_string()));
+        assert_eq!(
+            parse("ROI_0*"),
+            Ok("acquisition pattern: ROI_0*".to_string())
+        );
+        assert_eq!(
+            parse("ROI 12"),
+            Ok("acquisition description: ROI 12".to_string())
+        );
+    }
+
+    #[test]
+    fn marker_spectrum_skips_positions() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("X", "X")
+                .channel("Y", "Y")
+                .channel("Z", "Z")
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            [0.0, 0.0, 0.0, 1.0, 2.0, 1.0, 0.0, 1.0, 3.0, 4.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        let names: Vec<_> = acquisition
+            .marker_channels()
+            .iter()
+            .map(|channel| channel.name())
+            .collect();
+        assert_eq!(names, ["Ir191", "Er170"]);
+
+        let mut spectrum = [0.0; 5];
+        acquisition.spectrum_into(1, 0, &mut spectrum)?;
+        assert_eq!(spectrum, [1.0, 0.0, 1.0, 3.0, 4.0]);
+
+        let mut markers = [0.0; 2];
+        acquisition.marker_spectrum_into(1, 0, &mut markers)?;
+        assert_eq!(markers, [3.0, 4.0]);
+
+        assert!(matches!(
+            acquisition.marker_spectrum_into(0, 0, &mut spectrum),
+            Err(MCDError::InvalidBufferSize {
+                expected: 2,
+                actual: 5
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spectra_stop_at_read_errors() -> Result<()> {
+        let data = synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "A")
+                .channel("Er170", "B"),
+            [1.0, 10.0, 2.0, 20.0],
+        )])?;
+        let length = data.len() as u64;
+        let mut mcd = MCD::from_bytes(data)?;
+
+        let spectra = mcd.acquisitions()[0]
+            .spectra()?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(spectra, [vec![1.0, 10.0], vec![2.0, 20.0]]);
+
+        // The data extends beyond the end of the file
+        for slide in mcd.slides_mut().values_mut() {
+            for panorama in slide.panoramas_mut().values_mut() {
+                for acquisition in panorama.acquisitions_mut().values_mut() {
+                    acquisition.data = ByteSpan::new(length - 4, length + 12)?;
+                }
+            }
+        }
+
+        let mut spectra = mcd.acquisitions()[0].spectra()?;
+        assert!(matches!(spectra.next(), Some(Err(MCDError::Io { .. }))));
+        assert!(spectra.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn channel_images_in_requested_order() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "A")
+                .channel("Er170", "B"),
+            [1.0, 10.0, 2.0, 20.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+        let intensities = |identifiers: &[ChannelIdentifier]| -> Result<Vec<Vec<f32>>> {
+            Ok(acquisition
+                .channel_images(identifiers, None)?
+                .iter()
+                .map(|image| image.intensities().to_vec())
+                .collect())
+        };
+
+        let (a, b) = (ChannelIdentifier::label("A"), ChannelIdentifier::label("B"));
+        assert_eq!(
+            intensities(&[b.clone(), a.clone()])?,
+            [vec![10.0, 20.0], vec![1.0, 2.0]]
+        );
+        assert_eq!(
+            intensities(&[a.clone(), a])?,
+            [vec![1.0, 2.0], vec![1.0, 2.0]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn intensities_in_polygon_follow_identifiers() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .position(10.0, 11.0)
+                .channel("Ir191", "A")
+                .channel("Er170", "B"),
+            [1.0, 10.0, 2.0, 20.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+        // Covers the whole acquisition
+        let polygon = [(0.0, 0.0), (50.0, 0.0), (50.0, 50.0), (0.0, 50.0)];
+
+        let (a, b) = (ChannelIdentifier::label("A"), ChannelIdentifier::label("B"));
+        assert_eq!(
+            acquisition.intensities_in_polygon(&polygon, &[b, a.clone()])?,
+            [vec![10.0, 20.0], vec![1.0, 2.0]]
+        );
+        assert_eq!(
+            acquisition.intensities_in_polygon(&polygon, &[a.clone(), a])?,
+            [vec![1.0, 2.0], vec![1.0, 2.0]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_counts() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 2)
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            [3.0, 0.5, 0.0, 12.25, 41.0, 2.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        // The last row is missing, as only 3 of the 4 spectra were acquired
+        let counts = acquisition.channel_counts(ChannelIdentifier::name("Ir191"), None)?;
+        assert_eq!(counts, [Some(3), Some(0), Some(41), None]);
+
+        assert!(matches!(
+            acquisition.channel_counts(ChannelIdentifier::name("Er170"), None),
+            Err(MCDError::RawCountsUnavailable {
+                acquisition_id: 1,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn integer_data_formats() -> Result<()> {
+        let mut data = synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3")
+                .segment_data_format(DataFormat::UInt16),
+            [3.0, 70000.0, 41.0, 2.0],
+        )])?;
+
+        let mcd = MCD::from_bytes(data.clone())?;
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(acquisition.segment_data_format(), &DataFormat::UInt16);
+        assert_eq!(acquisition.value_bytes(), 2);
+        assert_eq!(acquisition.spectrum(0, 0)?, [3.0, 65535.0]);
+        assert_eq!(acquisition.read_spectra(0, 2)?, [3.0, 65535.0, 41.0, 2.0]);
+        assert_eq!(
+            acquisition.channel_counts(ChannelIdentifier::name("Ir191"), None)?,
+            [Some(3), Some(41)]
+        );
+
+        // Files with an unknown format still load, but the intensities can't be read
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let (from, to) = (utf16(">UInt16<"), utf16(">Int24X<"));
+        let start = data
+            .windows(from.len())
+            .position(|window| window == from)
+            .expect("SegmentDataFormat is in the XML");
+        data[start..start + to.len()].copy_from_slice(&to);
+
+        let mcd = MCD::from_bytes(data)?;
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(
+            acquisition.segment_data_format(),
+            &DataFormat::Other("Int24X".to_string())
+        );
+        assert!(matches!(
+            acquisition.spectrum(0, 0),
+            Err(MCDError::UnsupportedDataFormat { value_bytes: 2, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn channel_image_into_reuses_buffer() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 2)
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        let mut buffer = Vec::with_capacity(16);
+        let allocation = buffer.as_ptr();
+        acquisition.channel_image_into(ChannelIdentifier::name("Er170"), None, &mut buffer)?;
+        assert_eq!(buffer[..3], [2.0, 4.0, 6.0]);
+        assert!(buffer[3].is_nan());
+
+        let region = Region {
+            x: 0,
+            y: 1,
+            width: 2,
+            height: 1,
+        };
+        acquisition.channel_image_into(
+            ChannelIdentifier::name("Ir191"),
+            Some(region),
+            &mut buffer,
+        )?;
+        assert_eq!(buffer[0], 5.0);
+        assert!(buffer[1].is_nan());
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.as_ptr(), allocation);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::io::{Read, Seek, Write};
+
+use crate::{
+    error::Result, AcquisitionRef, AcquisitionSpec, MCDWriter, OnSlide, PanoramaSpec, SlideSpec,
+    MCD,
+};
+
+/// Options controlling what is kept when copying an .mcd file with `MCD::anonymize_to()`. By default,
+/// descriptions are replaced (e.g. with `Acquisition 3`), filenames, UIDs, template names and timestamps are
+/// removed, and the optical images are kept.
+#[derive(Debug, Clone)]
+pub struct AnonymizeOptions {
+    keep_descriptions: bool,
+    keep_filenames: bool,
+    keep_timestamps: bool,
+    keep_optical_images: bool,
+}
+
+impl Default for AnonymizeOptions {
+    fn default() -> Self {
+        AnonymizeOptions {
+            keep_descriptions: false,
+            keep_filenames: false,
+            keep_timestamps: false,
+            keep_optical_images: true,
+        }
+    }
+}
+
+impl AnonymizeOptions {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options keeping everything, used when copying without anonymizing
+    pub(crate) fn keep_all() -> Self {
+        AnonymizeOptions {
+            keep_descriptions: true,
+            keep_filenames: true,
+            keep_timestamps: true,
+            keep_optical_images: true,
+        }
+    }
+
+    /// Whether to keep the descriptions of slides, panoramas and acquisitions
+    pub fn keep_descriptions(mut self, keep_descriptions: bool) -> Self {
+        self.keep_descriptions = keep_descriptions;
+        self
+    }
+
+    /// Whether to keep the filenames (of the .mcd file and slide image), slide UIDs and template names
+    pub fn keep_filenames(mut self, keep_filenames: bool) -> Self {
+        self.keep_filenames = keep_filenames;
+        self
+    }
+
+    /// Whether to keep the start and end timestamps of acquisitions
+    pub fn keep_timestamps(mut self, keep_timestamps: bool) -> Self {
+        self.keep_timestamps = keep_timestamps;
+        self
+    }
+
+    /// Whether to keep the optical images (slide, panorama and before/after ablation images), which may show
+    /// labels written on the slide
+    pub fn keep_optical_images(mut self, keep_optical_images: bool) -> Self {
+        self.keep_optical_images = keep_optical_images;
+        self
+    }
+
+    fn description(&self, description: &str, replacement: String) -> String {
+        if self.keep_descriptions {
+            description.to_string()
+        } else {
+            replacement
+        }
+    }
+
+    fn filename<'a>(&self, filename: &'a str) -> &'a str {
+        if self.keep_filenames {
+            filename
+        } else {
+            ""
+        }
+    }
+}
+
+impl<R: Read + Seek> MCD<R> {
+    /// Write a copy of the .mcd file to `writer` from which identifying information has been stripped (see
+    /// `AnonymizeOptions`), returning the writer. The slides, panoramas and acquisitions (with their spectra)
+    /// are copied. Everything else recorded in the XML (e.g. calibrations and operator names) is dropped.
+    pub fn anonymize_to<W: Write>(&self, writer: W, options: &AnonymizeOptions) -> Result<W> {
+        self.copy_to(writer, options, None)
+    }
+
+    /// Write a copy of the .mcd file to `writer`, keeping only the `selected` acquisitions (and the slides and
+    /// panoramas containing them) if specified
+    pub(crate) fn copy_to<W: Write>(
+        &self,
+        writer: W,
+        options: &AnonymizeOptions,
+        selected: Option<&[AcquisitionRef]>,
+    ) -> Result<W> {
+        let mut writer = MCDWriter::new(writer);
+        let is_selected = |reference: AcquisitionRef| {
+            selected.is_none_or(|selected| selected.contains(&reference))
+        };
+
+        for slide in self.slides() {
+            if selected.is_some()
+                && !slide
+                    .panoramas()
+                    .iter()
+                    .flat_map(|panorama| panorama.acquisitions())
+                    .any(|acquisition| is_selected(acquisition.reference()))
+            {
+                continue;
+            }
+
+            let mut spec = SlideSpec::new(slide.id())
+                .description(
+                    &options.description(slide.description(), format!("Slide {}", slide.id())),
+                )
+                .filename(options.filename(slide.filename()))
+                .slide_type(slide.slide_type())
+                .dimensions(slide.width_in_um(), slide.height_in_um())
+                .software_version(slide.software_version());
+
+            if let Some(uid) = slide.uid().filter(|_| options.keep_filenames) {
+                spec = spec.uid(uid);
+            }
+
+            let image = slide.image();
+            if options.keep_optical_images && image.is_stored() {
+                spec = spec.image(options.filename(slide.image_file()), image.image_data()?);
+            }
+
+            writer.add_slide(spec)?;
+
+            for panorama in slide.panoramas() {
+                let acquisitions: Vec<_> = panorama
+                    .acquisitions()
+                    .into_iter()
+                    .filter(|acquisition| is_selected(acquisition.reference()))
+                    .collect();
+
+                if selected.is_some() && acquisitions.is_empty() {
+                    continue;
+                }
+
+                let (pixel_width, pixel_height) = panorama.dimensions();
+
+                let mut spec = PanoramaSpec::new(panorama.id(), slide.id())
+                    .description(&options.description(
+                        panorama.description(),
+                        format!("Panorama {}", panorama.id()),
+                    ))
+                    .corners(panorama.slide_corners())
+                    .dimensions(pixel_width, pixel_height)
+                    .pixel_scale_coef(panorama.pixel_scale_coef());
+
+                if let Some(image) = panorama.image().filter(|_| options.keep_optical_images) {
+                    spec = spec.image(image.image_data()?);
+                }
+
+                writer.add_panorama(spec)?;
+
+                for acquisition in acquisitions {
+                    let (distance_x, distance_y) = acquisition.ablation_distance_between_shots();
+                    let bounds = acquisition.slide_bounding_box();
+                    let window = acquisition.plume_window();
+
+                    let roi_points: Vec<_> = acquisition
+                        .roi_points()
+                        .iter()
+                        .map(|point| {
+                            let position = point.slide_position();
+                            (position.x, position.y)
+                        })
+                        .collect();
+
+                    let mut spec = AcquisitionSpec::new(
+                        acquisition.id(),
+                        panorama.id(),
+                        acquisition.width(),
+                        acquisition.height(),
+                    )
+                    .description(&options.description(
+                        acquisition.description(),
+                        format!("Acquisition {}", acquisition.id()),
+                    ))
+                    .order_number(acquisition.order_number())
+                    .position(bounds.min_x, bounds.min_y + bounds.height)
+                    .distance_between_shots(distance_x, distance_y)
+                    .ablation(
+                        acquisition.ablation_power(),
+                        acquisition.ablation_frequency(),
+                    )
+                    .signal_type(acquisition.signal_type())
+                    .dual_count_start(acquisition.dual_count_start())
+                    .movement_type(acquisition.movement_type())
+                    .plume(window.start(), window.end())
+                    .roi_points(&roi_points)
+                    .template(options.filename(acquisition.template()));
+
+                    if options.keep_timestamps {
+                        spec = spec
+                            .timestamps(acquisition.start_timestamp(), acquisition.end_timestamp());
+                    }
+
+                    // Intensities are stored in order of the channel order numbers
+                    let mut channels: Vec<_> = acquisition.channels().iter().collect();
+                    channels.sort_by_key(|channel| channel.order_number());
+                    for channel in channels {
+                        spec = spec.channel(channel.name(), channel.label());
+                    }
+
+                    if options.keep_optical_images {
+                        let before = acquisition.before_ablation_image();
+                        if before.is_stored() {
+                            spec = spec.before_ablation_image(before.image_data()?);
+                        }
+
+                        let after = acquisition.after_ablation_image();
+                        if after.is_stored() {
+                            spec = spec.after_ablation_image(after.image_data()?);
+                        }
+                    }
+
+                    writer.add_acquisition_with(
+                        spec,
+                        acquisition.num_spectra(),
+                        |first_index, num_spectra| {
+                            acquisition.read_spectra(first_index, num_spectra)
+                        },
+                    )?;
+                }
+            }
+        }
+
+        writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn strips_descriptions_and_timestamps() -> Result<()> {
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(
+            SlideSpec::new(1)
+                .description("Patient 1234")
+                .filename("p1234.mcd"),
+        )?;
+        writer.add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 100.0, 100.0))?;
+        writer.add_acquisition(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .description("Biopsy of patient 1234")
+                .timestamps("2023-01-01T10:00:00", "2023-01-01T10:05:00")
+                .position(10.0, 50.0)
+                .channel("X", "X")
+                .channel("Ir191", "DNA1"),
+            &[0.0, 4.0, 1.0, 8.0],
+        )?;
+
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+        let anonymized = mcd.anonymize_to(Cursor::new(Vec::new()), &AnonymizeOptions::new())?;
+        let anonymized = MCD::parse(Cursor::new(anonymized.into_inner()))?;
+
+        let slide = anonymized.slide(1).expect("slide is copied");
+        assert_eq!(slide.description(), "Slide 1");
+        assert_eq!(slide.filename(), "");
+
+        let acquisition = anonymized.acquisitions()[0];
+        assert_eq!(acquisition.description(), "Acquisition 1");
+        assert_eq!(acquisition.start_timestamp(), "");
+        assert_eq!(acquisition.read_spectra(0, 2)?, [0.0, 4.0, 1.0, 8.0]);
+        assert_eq!(
+            acquisition.slide_bounding_box().min_x,
+            mcd.acquisitions()[0].slide_bounding_box().min_x
+        );
+
+        Ok(())
+    }
+}
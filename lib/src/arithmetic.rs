@@ -0,0 +1,191 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::{
+    error::{MCDError, Result},
+    ChannelImage,
+};
+
+impl ChannelImage {
+    /// Combine the intensities of this image with those of another image of the same size, pixel by pixel
+    /// (e.g. `|a, b| a / b` for a ratio). Missing pixels in either image are passed to `op` as NaN, so remain
+    /// missing for any arithmetic operation. The result describes the channel of this image.
+    pub fn combine<F: Fn(f32, f32) -> f32>(
+        &self,
+        other: &ChannelImage,
+        op: F,
+    ) -> Result<ChannelImage> {
+        if (self.width(), self.height()) != (other.width(), other.height()) {
+            return Err(MCDError::ImageSizeMismatch {
+                expected: (self.width(), self.height()),
+                actual: (other.width(), other.height()),
+            });
+        }
+
+        let data = (0..self.num_pixels())
+            .map(|index| op(self.pixel(index), other.pixel(index)))
+            .collect();
+
+        Ok(self.with_data(data, self.valid_pixels.min(other.valid_pixels)))
+    }
+
+    /// Apply `op` to each intensity of the image. Missing pixels are passed to `op` as NaN.
+    pub fn map<F: Fn(f32) -> f32>(&self, op: F) -> ChannelImage {
+        let data = (0..self.num_pixels())
+            .map(|index| op(self.pixel(index)))
+            .collect();
+
+        self.with_data(data, self.valid_pixels)
+    }
+
+    fn num_pixels(&self) -> usize {
+        (self.region.width * self.region.height) as usize
+    }
+
+    /// Returns the intensity of the pixel, or NaN if it was not acquired
+    pub(crate) fn pixel(&self, index: usize) -> f32 {
+        self.data.get(index).copied().unwrap_or(f32::NAN)
+    }
+
+    pub(crate) fn with_data(&self, data: Vec<f32>, valid_pixels: usize) -> ChannelImage {
+        // Missing pixels (and infinities, e.g. from division by zero) are excluded from the range
+        let range = data
+            .iter()
+            .filter(|value| value.is_finite())
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+
+        ChannelImage {
+            region: self.region,
+            acquisition_id: self.acquisition_id,
+            name: self.name.clone(),
+            label: self.label.clone(),
+            range,
+            valid_pixels,
+            data,
+        }
+    }
+}
+
+/// Implement the operator for pairs of images (by reference or value) and for images with a scalar (on either
+/// side). Pairs of images of different sizes panic, use `ChannelImage::combine` to handle this as an error.
+macro_rules! impl_operator {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait<&ChannelImage> for &ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, other: &ChannelImage) -> ChannelImage {
+                match self.combine(other, |a, b| a $op b) {
+                    Ok(image) => image,
+                    Err(error) => panic!("{}", error),
+                }
+            }
+        }
+
+        impl $trait<ChannelImage> for ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, other: ChannelImage) -> ChannelImage {
+                (&self).$method(&other)
+            }
+        }
+
+        impl $trait<&ChannelImage> for ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, other: &ChannelImage) -> ChannelImage {
+                (&self).$method(other)
+            }
+        }
+
+        impl $trait<ChannelImage> for &ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, other: ChannelImage) -> ChannelImage {
+                self.$method(&other)
+            }
+        }
+
+        impl $trait<f32> for &ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, scalar: f32) -> ChannelImage {
+                self.map(|value| value $op scalar)
+            }
+        }
+
+        impl $trait<f32> for ChannelImage {
+            type Output = ChannelImage;
+
+            fn $method(self, scalar: f32) -> ChannelImage {
+                (&self).$method(scalar)
+            }
+        }
+
+        impl $trait<&ChannelImage> for f32 {
+            type Output = ChannelImage;
+
+            fn $method(self, image: &ChannelImage) -> ChannelImage {
+                image.map(|value| self $op value)
+            }
+        }
+
+        impl $trait<ChannelImage> for f32 {
+            type Output = ChannelImage;
+
+            fn $method(self, image: ChannelImage) -> ChannelImage {
+                self.$method(&image)
+            }
+        }
+    };
+}
+
+impl_operator!(Add, add, +);
+impl_operator!(Sub, sub, -);
+impl_operator!(Mul, mul, *);
+impl_operator!(Div, div, /);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Region;
+
+    fn image(width: u32, data: Vec<f32>) -> ChannelImage {
+        ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir(191)".to_string(),
+            label: "DNA1".to_string(),
+            range: (0.0, 0.0),
+            valid_pixels: data.len(),
+            data,
+        }
+    }
+
+    #[test]
+    fn ratio_and_background() {
+        // The last pixel of `a` was not acquired
+        let a = image(2, vec![2.0, 4.0, f32::NAN]);
+        let b = image(2, vec![1.0, 2.0, 4.0, 0.0]);
+
+        let ratio = &a / &b;
+        assert_eq!(&ratio.intensities()[..2], [2.0, 2.0]);
+        assert!(ratio.intensities()[2].is_nan() && ratio.intensities()[3].is_nan());
+        assert_eq!(ratio.num_valid_pixels(), 3);
+        assert_eq!(ratio.intensity_range(), (2.0, 2.0));
+
+        let subtracted = (&b - 1.0) * 2.0;
+        assert_eq!(subtracted.intensities(), [0.0, 2.0, 6.0, -2.0]);
+        assert_eq!((1.0 / &b).intensity_range(), (0.25, 1.0));
+
+        assert!(matches!(
+            a.combine(&image(1, vec![0.0; 2]), |a, b| a + b),
+            Err(MCDError::ImageSizeMismatch { .. })
+        ));
+    }
+}
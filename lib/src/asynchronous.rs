@@ -0,0 +1,353 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use crate::{
+    error::{MCDError, Result},
+    AcquisitionRef, ChannelIdentifier, ChannelImage, Region, MCD,
+};
+
+/// Maximum number of threads running blocking operations at once
+const MAX_BLOCKING_THREADS: usize = 32;
+
+/// Time after which a thread with no blocking operation to run exits
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads dedicated to blocking operations, which are started as needed (up to `MAX_BLOCKING_THREADS`) and
+/// exit once idle. Blocking file reads therefore neither stall the async runtime nor occupy the threads of the
+/// compute pool (see [`crate::config`]) used for decompression.
+struct BlockingPool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+impl BlockingPool {
+    fn global() -> &'static BlockingPool {
+        static POOL: OnceLock<BlockingPool> = OnceLock::new();
+
+        POOL.get_or_init(|| BlockingPool {
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        // Jobs run without the lock held, so it can't be poisoned by a panicking operation
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn execute(&'static self, job: Job) {
+        let mut state = self.lock();
+        state.queue.push_back(job);
+
+        if state.queue.len() <= state.idle || state.threads >= MAX_BLOCKING_THREADS {
+            self.available.notify_one();
+            return;
+        }
+
+        state.threads += 1;
+        let spawned = std::thread::Builder::new()
+            .name("imc-rs-blocking".to_string())
+            .spawn(move || self.work());
+
+        if spawned.is_err() {
+            state.threads -= 1;
+
+            if state.threads > 0 {
+                self.available.notify_one();
+                return;
+            }
+
+            // Without any thread to run it, the operation is run on the calling thread instead
+            let job = state.queue.pop_back();
+            drop(state);
+
+            if let Some(job) = job {
+                job();
+            }
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.lock();
+
+        loop {
+            match state.queue.pop_front() {
+                Some(job) => {
+                    drop(state);
+                    job();
+                    state = self.lock();
+                }
+                None => {
+                    state.idle += 1;
+                    let (guard, timeout) = self
+                        .available
+                        .wait_timeout(state, IDLE_TIMEOUT)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    state = guard;
+                    state.idle -= 1;
+
+                    if timeout.timed_out() && state.queue.is_empty() {
+                        state.threads -= 1;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A future which resolves once a blocking operation, run on a thread dedicated to blocking operations, has
+/// completed. This keeps file reads and decompression off the threads of the async runtime, and works with any
+/// runtime (e.g. tokio).
+pub struct Blocking<T> {
+    shared: Arc<Mutex<BlockingState<T>>>,
+}
+
+struct BlockingState<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Run `operation` on a thread dedicated to blocking operations, returning a future which resolves to its result
+pub fn spawn_blocking<T, F>(operation: F) -> Blocking<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(BlockingState {
+        result: None,
+        waker: None,
+    }));
+    let thread_shared = shared.clone();
+
+    BlockingPool::global().execute(Box::new(move || {
+        // Panics are passed on to the task awaiting the result
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(operation));
+
+        let mut state = thread_shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.result = Some(result);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }));
+
+    Blocking { shared }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self
+            .shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl MCD<File> {
+    /// Open an .mcd file from the specified path without blocking the async runtime (see [`MCD::from_path`])
+    pub async fn parse_async<P: AsRef<Path>>(path: P) -> Result<MCD<File>> {
+        let path = path.as_ref().to_path_buf();
+
+        spawn_blocking(move || MCD::from_path(path)).await
+    }
+
+    /// Share the .mcd file between async tasks, for reading images without blocking the async runtime
+    pub fn into_async(self) -> AsyncMCD {
+        AsyncMCD {
+            mcd: Arc::new(self),
+        }
+    }
+}
+
+/// An .mcd file which can be shared between async tasks (e.g. the handlers of a web service). Metadata is held
+/// in memory, so is accessed directly through [`AsyncMCD::mcd`]. Reading images requires reading (and
+/// potentially decompressing) data from the file, so is performed on threads dedicated to blocking operations.
+#[derive(Debug, Clone)]
+pub struct AsyncMCD {
+    mcd: Arc<MCD<File>>,
+}
+
+impl AsyncMCD {
+    /// Open an .mcd file from the specified path, optionally creating (or opening) the .dcm file for faster
+    /// access to channel images (see [`MCD::with_dcm`])
+    pub async fn open<P: AsRef<Path>>(path: P, with_dcm: bool) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let mcd = spawn_blocking(move || {
+            let mcd = MCD::from_path(path)?;
+
+            if with_dcm {
+                mcd.with_dcm()
+            } else {
+                Ok(mcd)
+            }
+        })
+        .await?;
+
+        Ok(mcd.into_async())
+    }
+
+    /// Returns the underlying .mcd file, for accessing metadata
+    pub fn mcd(&self) -> &MCD<File> {
+        &self.mcd
+    }
+
+    /// Returns the image of the channel of the acquisition (see [`crate::Acquisition::channel_image`])
+    pub async fn channel_image(
+        &self,
+        acquisition: AcquisitionRef,
+        identifier: ChannelIdentifier,
+        region: Option<Region>,
+    ) -> Result<ChannelImage> {
+        let mut images = self
+            .channel_images(acquisition, vec![identifier], region)
+            .await?;
+
+        images.pop().ok_or(MCDError::InvalidBufferSize {
+            expected: 1,
+            actual: 0,
+        })
+    }
+
+    /// Returns the images of the channels of the acquisition (see [`crate::Acquisition::channel_images`])
+    pub async fn channel_images(
+        &self,
+        acquisition: AcquisitionRef,
+        identifiers: Vec<ChannelIdentifier>,
+        region: Option<Region>,
+    ) -> Result<Vec<ChannelImage>> {
+        let mcd = self.mcd.clone();
+
+        spawn_blocking(move || {
+            mcd.find_acquisition(acquisition)?
+                .channel_images(&identifiers, region)
+        })
+        .await
+    }
+
+    /// Returns the encoded image of the slide (see [`crate::OpticalImage::image_data`])
+    pub async fn slide_image(&self, slide_id: u16) -> Result<Vec<u8>> {
+        let mcd = self.mcd.clone();
+
+        spawn_blocking(move || match mcd.slide(slide_id) {
+            Some(slide) => slide.image().image_data(),
+            None => Err(MCDError::NoSlidePresent),
+        })
+        .await
+    }
+
+    /// Returns the encoded image of the panorama, or None if the panorama has no image (see
+    /// [`crate::OpticalImage::image_data`])
+    pub async fn panorama_image(&self, slide_id: u16, panorama_id: u16) -> Result<Option<Vec<u8>>> {
+        let mcd = self.mcd.clone();
+
+        spawn_blocking(move || {
+            match mcd
+                .slide(slide_id)
+                .and_then(|slide| slide.panorama(panorama_id))
+                .and_then(|panorama| panorama.image())
+            {
+                Some(image) => image.image_data().map(Some),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        task::{Context, Poll, Wake},
+        thread::Thread,
+    };
+
+    use super::*;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor, so that the futures can be tested without an async runtime
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn blocking_resolves() {
+        assert_eq!(block_on(spawn_blocking(|| 6 * 7)), 42);
+        assert!(block_on(MCD::parse_async("does_not_exist.mcd")).is_err());
+    }
+
+    #[test]
+    fn blocking_runs_on_dedicated_threads() {
+        // More operations than threads, so that some are queued
+        let futures: Vec<_> = (0..2 * MAX_BLOCKING_THREADS)
+            .map(|_| {
+                spawn_blocking(|| {
+                    std::thread::sleep(Duration::from_millis(5));
+
+                    (
+                        std::thread::current().name().map(str::to_string),
+                        rayon::current_thread_index(),
+                    )
+                })
+            })
+            .collect();
+
+        for future in futures {
+            assert_eq!(
+                block_on(future),
+                (Some("imc-rs-blocking".to_string()), None)
+            );
+        }
+        assert!(BlockingPool::global().lock().threads <= MAX_BLOCKING_THREADS);
+    }
+}
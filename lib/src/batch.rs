@@ -0,0 +1,262 @@
+use std::{
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    config,
+    error::{MCDError, Result},
+    statistics::ChannelStatistics,
+    Acquisition, AcquisitionRef, ChannelIdentifier, Region, MCD,
+};
+
+/// Number of rows read at a time, matching the chunk size of .dcm files so that each band is read from a single
+/// row of chunks
+const BAND_HEIGHT: u32 = 256;
+
+/// Statistics of the requested channels in a single acquisition
+#[derive(Debug, Clone)]
+pub struct AcquisitionStats {
+    reference: AcquisitionRef,
+    description: String,
+    channels: Vec<Option<ChannelStatistics>>,
+}
+
+impl AcquisitionStats {
+    /// Returns the reference of the acquisition
+    pub fn reference(&self) -> AcquisitionRef {
+        self.reference
+    }
+
+    /// Returns the description of the acquisition
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the statistics of the requested channel (by index in the requested channels), or None if the
+    /// channel is not present in the acquisition
+    pub fn channel(&self, index: usize) -> Option<&ChannelStatistics> {
+        self.channels.get(index)?.as_ref()
+    }
+}
+
+/// Statistics of the requested channels in each acquisition of a single .mcd file
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    path: PathBuf,
+    acquisitions: Vec<AcquisitionStats>,
+}
+
+impl FileStats {
+    /// Returns the path of the .mcd file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the statistics of each acquisition in the file
+    pub fn acquisitions(&self) -> &[AcquisitionStats] {
+        &self.acquisitions
+    }
+
+    /// Returns the combined statistics of the requested channel (by index) across all acquisitions in the file
+    pub fn channel(&self, index: usize) -> ChannelStatistics {
+        combine(self.acquisitions.iter(), index)
+    }
+}
+
+/// Distributions of channel intensities across a cohort of .mcd files, per file and per acquisition (see
+/// [`channel_statistics`])
+#[derive(Debug, Clone)]
+pub struct CohortStats {
+    channels: Vec<ChannelIdentifier>,
+    files: Vec<FileStats>,
+}
+
+impl CohortStats {
+    /// Returns the requested channels, in the order used to index the statistics
+    pub fn channels(&self) -> &[ChannelIdentifier] {
+        &self.channels
+    }
+
+    /// Returns the statistics of each file, in the order the paths were specified
+    pub fn files(&self) -> &[FileStats] {
+        &self.files
+    }
+
+    /// Returns the combined statistics of the requested channel (by index) across the whole cohort
+    pub fn channel(&self, index: usize) -> ChannelStatistics {
+        combine(
+            self.files.iter().flat_map(|file| file.acquisitions.iter()),
+            index,
+        )
+    }
+}
+
+fn combine<'a, I: Iterator<Item = &'a AcquisitionStats>>(
+    acquisitions: I,
+    index: usize,
+) -> ChannelStatistics {
+    let mut statistics = ChannelStatistics::new();
+
+    for channel in acquisitions.filter_map(|acquisition| acquisition.channel(index)) {
+        statistics.merge(channel);
+    }
+
+    statistics
+}
+
+/// Calculate statistics of the channels in every acquisition of each .mcd file, as the basis for deciding how
+/// to normalise intensities across samples. Channels which are not present in an acquisition are skipped.
+///
+/// Files are processed in parallel (see [`crate::config`]), reading from the .dcm file of each (which is
+/// created if needed, see [`MCD::with_dcm`]). Acquisitions are read in bands of rows matching the .dcm chunks,
+/// so memory use is bounded by the number of threads rather than the size of the acquisitions. Errors are
+/// returned as `MCDError::File`, identifying the file which could not be processed.
+///
+/// ```no_run
+/// use imc_rs::{batch, ChannelIdentifier};
+///
+/// let paths = ["sample_1.mcd", "sample_2.mcd"];
+/// let cohort = batch::channel_statistics(&paths, &[ChannelIdentifier::label("CD3")]).unwrap();
+///
+/// for file in cohort.files() {
+///     println!("{}: {:?}", file.path().display(), file.channel(0).quantile(0.99));
+/// }
+/// ```
+pub fn channel_statistics<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    channels: &[ChannelIdentifier],
+) -> Result<CohortStats> {
+    let files = config::install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let path = path.as_ref();
+
+                file_statistics(path, channels).map_err(|error| MCDError::File {
+                    path: path.to_path_buf(),
+                    source: Box::new(error),
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    Ok(CohortStats {
+        channels: channels.to_vec(),
+        files,
+    })
+}
+
+fn file_statistics(path: &Path, channels: &[ChannelIdentifier]) -> Result<FileStats> {
+    let mcd = MCD::from_path(path)?.with_dcm()?;
+
+    let acquisitions = mcd
+        .acquisitions()
+        .into_iter()
+        .map(|acquisition| acquisition_statistics(acquisition, channels))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(FileStats {
+        path: path.to_path_buf(),
+        acquisitions,
+    })
+}
+
+/// Calculate statistics of the channels present in the acquisition, one band of rows at a time
+fn acquisition_statistics<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    channels: &[ChannelIdentifier],
+) -> Result<AcquisitionStats> {
+    let present: Vec<_> = channels
+        .iter()
+        .filter(|&identifier| acquisition.channel(identifier).is_some())
+        .collect();
+    let mut statistics = vec![ChannelStatistics::new(); present.len()];
+
+    let width = acquisition.width().max(0) as u32;
+    let height = acquisition.height().max(0) as u32;
+
+    for y in (0..height).step_by(BAND_HEIGHT as usize) {
+        let band = Region {
+            x: 0,
+            y,
+            width,
+            height: BAND_HEIGHT.min(height - y),
+        };
+
+        for (statistics, image) in statistics
+            .iter_mut()
+            .zip(acquisition.channel_images(&present, Some(band))?)
+        {
+            statistics.merge(&ChannelStatistics::from_image(&image));
+        }
+    }
+
+    let mut statistics = statistics.into_iter();
+
+    Ok(AcquisitionStats {
+        reference: acquisition.reference(),
+        description: acquisition.description().to_string(),
+        channels: channels
+            .iter()
+            .map(|identifier| {
+                acquisition
+                    .channel(identifier)
+                    .and_then(|_| statistics.next())
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn statistics_in_bands() -> Result<()> {
+        // Taller than a single band, so that statistics are merged across bands
+        let height = BAND_HEIGHT + 10;
+        let data: Vec<f32> = (0..height).map(|y| y as f32).collect();
+
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 1, height as i32).channel("Ir191", "DNA1"),
+            &data,
+        )])?)?;
+        let channels = [
+            ChannelIdentifier::label("DNA1"),
+            ChannelIdentifier::label("CD3"),
+        ];
+        let statistics = acquisition_statistics(mcd.acquisitions()[0], &channels)?;
+
+        let dna = statistics.channel(0).expect("DNA1 is present");
+        assert_eq!(dna.count(), height as u64);
+        assert_eq!(dna.max(), Some((height - 1) as f32));
+        assert!(statistics.channel(1).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn statistics_follow_channel_order() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            [1.0, 10.0, 2.0, 20.0],
+        )])?)?;
+        // Requested in the reverse of the order the channels are stored in
+        let channels = [
+            ChannelIdentifier::label("CD3"),
+            ChannelIdentifier::label("DNA1"),
+        ];
+        let statistics = acquisition_statistics(mcd.acquisitions()[0], &channels)?;
+
+        assert_eq!(statistics.channel(0).and_then(|cd3| cd3.max()), Some(20.0));
+        assert_eq!(statistics.channel(1).and_then(|dna| dna.max()), Some(2.0));
+
+        Ok(())
+    }
+}
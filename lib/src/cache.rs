@@ -0,0 +1,220 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{AcquisitionRef, ChannelImage, Region};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    acquisition: AcquisitionRef,
+    channel: String,
+    region: Region,
+}
+
+/// In-memory cache of recently read `ChannelImage`s, keyed by acquisition, channel and region. Once the total
+/// size of the cached images exceeds the memory budget, the least recently used images are evicted. A cache is
+/// shared by all acquisitions of an `MCD` (see `MCD::with_channel_cache()`).
+#[derive(Debug)]
+pub struct ChannelCache {
+    budget: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    images: HashMap<CacheKey, (u64, ChannelImage)>,
+    // Keys ordered by when they were last used, oldest first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    used: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl ChannelCache {
+    /// Create an empty cache which holds at most `budget_bytes` of images
+    pub fn new(budget_bytes: usize) -> Self {
+        ChannelCache {
+            budget: budget_bytes,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns the memory budget of the cache (in bytes)
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Returns the approximate memory used by the cached images (in bytes)
+    pub fn used_bytes(&self) -> usize {
+        self.state().used
+    }
+
+    /// Returns the number of cached images
+    pub fn len(&self) -> usize {
+        self.state().images.len()
+    }
+
+    /// Returns true if no images are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of requests for a channel image which were served from the cache
+    pub fn hits(&self) -> u64 {
+        self.state().hits
+    }
+
+    /// Returns the number of requests for a channel image which had to be read from the file
+    pub fn misses(&self) -> u64 {
+        self.state().misses
+    }
+
+    /// Remove all images from the cache
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.images.clear();
+        state.recency.clear();
+        state.used = 0;
+    }
+
+    /// Returns a copy of the cached image, marking it as recently used
+    pub(crate) fn get(
+        &self,
+        acquisition: AcquisitionRef,
+        channel: &str,
+        region: &Region,
+    ) -> Option<ChannelImage> {
+        let key = CacheKey {
+            acquisition,
+            channel: channel.to_string(),
+            region: *region,
+        };
+
+        let mut state = self.state();
+        state.tick += 1;
+        let tick = state.tick;
+
+        let last_used = match state.images.get_mut(&key) {
+            Some((last_used, _)) => std::mem::replace(last_used, tick),
+            None => {
+                state.misses += 1;
+                return None;
+            }
+        };
+
+        state.hits += 1;
+        state.recency.remove(&last_used);
+        state.recency.insert(tick, key.clone());
+
+        state.images.get(&key).map(|(_, image)| image.clone())
+    }
+
+    /// Add a copy of the image to the cache, evicting the least recently used images to stay within the budget.
+    /// Images larger than the whole budget are not cached.
+    pub(crate) fn insert(&self, acquisition: AcquisitionRef, image: &ChannelImage) {
+        let size = image_size(image);
+        if size > self.budget {
+            return;
+        }
+
+        let key = CacheKey {
+            acquisition,
+            channel: image.name().to_string(),
+            region: image.region,
+        };
+
+        let mut state = self.state();
+        state.tick += 1;
+        let tick = state.tick;
+
+        if let Some((last_used, previous)) = state.images.remove(&key) {
+            state.recency.remove(&last_used);
+            state.used -= image_size(&previous);
+        }
+
+        while state.used + size > self.budget {
+            let oldest = match state.recency.pop_first() {
+                Some((_, oldest)) => oldest,
+                None => break,
+            };
+
+            if let Some((_, evicted)) = state.images.remove(&oldest) {
+                state.used -= image_size(&evicted);
+            }
+        }
+
+        state.used += size;
+        state.recency.insert(tick, key.clone());
+        state.images.insert(key, (tick, image.clone()));
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        // The state is always left consistent, so it can still be used if another thread panicked
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Approximate memory used by the image (in bytes)
+fn image_size(image: &ChannelImage) -> usize {
+    std::mem::size_of::<ChannelImage>()
+        + image.data.len() * std::mem::size_of::<f32>()
+        + image.name.len()
+        + image.label.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(name: &str, num_pixels: usize) -> ChannelImage {
+        ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: num_pixels as u32,
+                height: 1,
+            },
+            acquisition_id: 1,
+            name: name.to_string(),
+            label: String::new(),
+            range: (0.0, 0.0),
+            valid_pixels: num_pixels,
+            data: vec![0.0; num_pixels],
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let reference = AcquisitionRef::new(1, 1, 1);
+        let budget = image_size(&image("Ir191", 100)) * 2;
+        let cache = ChannelCache::new(budget);
+
+        let (first, second, third) = (
+            image("Ir191", 100),
+            image("Ir193", 100),
+            image("Pt195", 100),
+        );
+        cache.insert(reference, &first);
+        cache.insert(reference, &second);
+
+        // Use the first image, so the second is the least recently used
+        assert!(cache.get(reference, "Ir191", &first.region).is_some());
+        cache.insert(reference, &third);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(reference, "Ir193", &second.region).is_none());
+        assert!(cache.get(reference, "Ir191", &first.region).is_some());
+        assert!(cache.used_bytes() <= budget);
+        assert_eq!((cache.hits(), cache.misses()), (2, 1));
+
+        // Too large to ever fit
+        cache.insert(reference, &image("Xe131", 1000));
+        assert!(cache
+            .get(reference, "Xe131", &image("Xe131", 1000).region)
+            .is_none());
+    }
+}
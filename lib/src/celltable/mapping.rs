@@ -0,0 +1,184 @@
+use super::{Column, ColumnType};
+
+/// Columns holding the intensity of each marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerColumns {
+    /// The columns with the specified names, with each marker named as its column
+    Named(Vec<String>),
+    /// The columns whose names start with the prefix (e.g. `Intensity_MeanIntensity_` in CellProfiler), with each
+    /// marker named without the prefix
+    Prefix(String),
+    /// The columns whose names end with the suffix (e.g. ` Cell Intensity` in HALO), with each marker named
+    /// without the suffix
+    Suffix(String),
+    /// All numeric columns which are not mapped to the id, position, area or bounds of the cells (e.g. the
+    /// channels exported by steinbock), with each marker named as its column
+    Remaining,
+}
+
+/// Marker (e.g. `CD3`) and the column holding its intensity in each cell (see [`MarkerColumns`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    name: String,
+    column: String,
+}
+
+impl Marker {
+    /// Returns the name of the marker
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the column holding the intensity of the marker
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+}
+
+/// Describes which columns of a cell table hold the id, position, area, bounds and marker intensities of each
+/// cell, so that tables written by different pipelines (HALO, CellProfiler, steinbock or custom scripts) can be
+/// accessed in the same way through `CellData`. Presets are provided for the common pipelines, and any column can
+/// be remapped with the builder methods:
+///
+/// ```
+/// use imc_rs::celltable::{ColumnMapping, MarkerColumns};
+///
+/// let mapping = ColumnMapping::new()
+///     .id("CellID")
+///     .position("X_centroid", "Y_centroid")
+///     .area("Area")
+///     .markers(MarkerColumns::Prefix("mean_".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColumnMapping {
+    pub(super) id: Option<String>,
+    pub(super) x: Option<String>,
+    pub(super) y: Option<String>,
+    pub(super) area: Option<String>,
+    /// XMin, XMax, YMin and YMax columns
+    pub(super) bounds: Option<[String; 4]>,
+    pub(super) markers: Option<MarkerColumns>,
+    pub(super) text_columns: Vec<String>,
+    pub(super) positive_columns: Option<String>,
+}
+
+impl ColumnMapping {
+    /// Create a mapping with no columns mapped, where the type of each column is inferred from its values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mapping for the output of HALO cell detection. Cells are positioned by their bounds (`XMin`, `XMax`, `YMin`
+    /// and `YMax`), markers are the ` Cell Intensity` columns, and columns containing `Positive` record whether
+    /// each cell is positive for a marker as 0 or 1.
+    pub fn halo() -> Self {
+        Self::new()
+            .id("Object Id")
+            .area("Cell Area (µm²)")
+            .bounds("XMin", "XMax", "YMin", "YMax")
+            .markers(MarkerColumns::Suffix(" Cell Intensity".to_string()))
+            .text_column("Image Location")
+            .text_column("Analysis Region")
+            .text_column("Analysis Inputs")
+            .text_column("Classifier Label")
+            .positive_columns("Positive")
+    }
+
+    /// Mapping for the object table exported by CellProfiler (`ExportToSpreadsheet`), where markers are the
+    /// `Intensity_MeanIntensity_` columns
+    pub fn cellprofiler() -> Self {
+        Self::new()
+            .id("ObjectNumber")
+            .position("AreaShape_Center_X", "AreaShape_Center_Y")
+            .area("AreaShape_Area")
+            .markers(MarkerColumns::Prefix(
+                "Intensity_MeanIntensity_".to_string(),
+            ))
+    }
+
+    /// Mapping for the single cell data exported by steinbock, with the intensities and region properties of each
+    /// cell in the same table. Every other numeric column is treated as a marker, named by its channel.
+    pub fn steinbock() -> Self {
+        Self::new()
+            .id("Object")
+            .position("centroid-1", "centroid-0")
+            .area("area")
+            .markers(MarkerColumns::Remaining)
+            .text_column("Image")
+    }
+
+    /// Set the column holding the id of each cell
+    pub fn id(mut self, column: &str) -> Self {
+        self.id = Some(column.to_string());
+        self
+    }
+
+    /// Set the columns holding the x and y coordinates of the centroid of each cell
+    pub fn position(mut self, x: &str, y: &str) -> Self {
+        self.x = Some(x.to_string());
+        self.y = Some(y.to_string());
+        self
+    }
+
+    /// Set the column holding the area of each cell
+    pub fn area(mut self, column: &str) -> Self {
+        self.area = Some(column.to_string());
+        self
+    }
+
+    /// Set the columns holding the bounding box of each cell (see `CellData::boundaries`)
+    pub fn bounds(mut self, x_min: &str, x_max: &str, y_min: &str, y_max: &str) -> Self {
+        self.bounds = Some([x_min, x_max, y_min, y_max].map(str::to_string));
+        self
+    }
+
+    /// Set the columns holding the intensity of each marker
+    pub fn markers(mut self, markers: MarkerColumns) -> Self {
+        self.markers = Some(markers);
+        self
+    }
+
+    /// Read the column as text, even if its values look like numbers (e.g. an analysis region named `1`)
+    pub fn text_column(mut self, column: &str) -> Self {
+        self.text_columns.push(column.to_string());
+        self
+    }
+
+    /// Read columns whose names contain `pattern` and hold only 0 or 1 (or true and false) as binary, recording
+    /// whether each cell is positive for a marker
+    pub fn positive_columns(mut self, pattern: &str) -> Self {
+        self.positive_columns = Some(pattern.to_string());
+        self
+    }
+
+    /// Returns true if the column is mapped to the id, position, area or bounds of the cells
+    fn is_mapped(&self, column: &str) -> bool {
+        [&self.id, &self.x, &self.y, &self.area]
+            .into_iter()
+            .flatten()
+            .chain(self.bounds.iter().flatten())
+            .any(|mapped| mapped == column)
+    }
+
+    /// Returns the marker held in the column, if any
+    pub(super) fn marker(&self, column: &Column) -> Option<Marker> {
+        let name = match self.markers.as_ref()? {
+            MarkerColumns::Named(columns) => columns
+                .iter()
+                .any(|name| name == column.name())
+                .then_some(column.name()),
+            MarkerColumns::Prefix(prefix) => column.name().strip_prefix(prefix.as_str()),
+            MarkerColumns::Suffix(suffix) => column.name().strip_suffix(suffix.as_str()),
+            MarkerColumns::Remaining => (matches!(
+                column.column_type(),
+                ColumnType::Integer | ColumnType::Float
+            ) && !self.is_mapped(column.name()))
+            .then_some(column.name()),
+        }?;
+
+        Some(Marker {
+            name: name.trim().to_string(),
+            column: column.name().to_string(),
+        })
+    }
+}
@@ -0,0 +1,664 @@
+use std::{
+    collections::HashMap,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use csv::StringRecord;
+
+use crate::{
+    error::{MCDError, Result},
+    BoundingBox,
+};
+
+mod mapping;
+mod query;
+
+pub use mapping::{ColumnMapping, Marker, MarkerColumns};
+pub use query::Cell;
+
+/// Parse a table of cells stored as a delimited (e.g. .csv) file, returning a representation of the cell data
+pub fn parse_from_path<P: AsRef<Path>>(path: P, options: &CellTableOptions) -> Result<CellData> {
+    let file = std::fs::File::open(path)?;
+    let reader = BufReader::new(file);
+
+    parse(reader, options)
+}
+
+/// Type of the data stored in a column. Unless specified (see [`CellTableOptions::column_type`]), the type of
+/// each column is inferred from its values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Text, such as the name of the image or the classification of the cell
+    Text,
+    /// Whether the cell is positive for a marker, stored as 0 or 1
+    Binary,
+    /// Whole numbers, such as the object ID or the bounds of the cell
+    Integer,
+    /// Floating point numbers, such as intensities and areas
+    Float,
+}
+
+/// Represents the data stored in a column. Assumes that all data is of the same type.
+/// Strings are stored as a DictionaryID as most strings appear multiple times.
+///
+/// Missing values (empty, `NA`, `N/A` or `NaN`) are None in binary and integer columns, and NaN in floating point
+/// columns.
+#[derive(Debug, Clone)]
+pub enum ColumnData {
+    /// Text data, stored as a DictionaryID
+    Text(Vec<DictionaryID>),
+    /// Binary column data
+    Binary(Vec<Option<bool>>),
+    /// Integer column data
+    Integer(Vec<Option<i64>>),
+    /// Floating point column data
+    Float(Vec<f64>),
+}
+
+impl ColumnData {
+    fn new(column_type: ColumnType, capacity: usize) -> Self {
+        match column_type {
+            ColumnType::Text => ColumnData::Text(Vec::with_capacity(capacity)),
+            ColumnType::Binary => ColumnData::Binary(Vec::with_capacity(capacity)),
+            ColumnType::Integer => ColumnData::Integer(Vec::with_capacity(capacity)),
+            ColumnType::Float => ColumnData::Float(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns the number of values in the column
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Text(data) => data.len(),
+            ColumnData::Binary(data) => data.len(),
+            ColumnData::Integer(data) => data.len(),
+            ColumnData::Float(data) => data.len(),
+        }
+    }
+
+    /// Returns true if the column contains no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Describes a column in the table
+#[derive(Debug, Clone)]
+pub struct Column {
+    name: String,
+    column_type: ColumnType,
+    number: usize,
+}
+
+impl Column {
+    /// Returns the name (title) of the column
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the data stored in the column
+    pub fn column_type(&self) -> ColumnType {
+        self.column_type
+    }
+
+    /// Returns the index of the column (number representing the order in which the column appears)
+    pub fn column_number(&self) -> usize {
+        self.number
+    }
+}
+
+/// Options for parsing a table of cells
+#[derive(Debug, Clone)]
+pub struct CellTableOptions {
+    mapping: ColumnMapping,
+    column_types: HashMap<String, ColumnType>,
+    delimiter: u8,
+}
+
+impl Default for CellTableOptions {
+    fn default() -> Self {
+        CellTableOptions {
+            mapping: ColumnMapping::default(),
+            column_types: HashMap::new(),
+            delimiter: b',',
+        }
+    }
+}
+
+impl CellTableOptions {
+    /// Create the default options for a comma separated table with no columns mapped, where the type of each
+    /// column is inferred from its values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options for the output of HALO cell detection (see [`ColumnMapping::halo`])
+    pub fn halo() -> Self {
+        Self::new().mapping(ColumnMapping::halo())
+    }
+
+    /// Options for the object table exported by CellProfiler (see [`ColumnMapping::cellprofiler`])
+    pub fn cellprofiler() -> Self {
+        Self::new().mapping(ColumnMapping::cellprofiler())
+    }
+
+    /// Options for the single cell data exported by steinbock (see [`ColumnMapping::steinbock`])
+    pub fn steinbock() -> Self {
+        Self::new().mapping(ColumnMapping::steinbock())
+    }
+
+    /// Set which columns hold the id, position, area, bounds and marker intensities of each cell
+    pub fn mapping(mut self, mapping: ColumnMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Set the character separating the fields of each row (e.g. `b'\t'` for tab separated tables)
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set the type of the column with the specified name, rather than inferring it from the values. Values which
+    /// can't be parsed as this type are reported as `MCDError::InvalidCellData`.
+    pub fn column_type(mut self, name: &str, column_type: ColumnType) -> Self {
+        self.column_types.insert(name.to_string(), column_type);
+        self
+    }
+}
+
+type DictionaryID = usize;
+
+#[derive(Debug, Clone)]
+struct Dictionary {
+    by_value: HashMap<String, DictionaryID>,
+    by_id: HashMap<DictionaryID, String>,
+
+    next_id: DictionaryID,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Dictionary {
+            by_value: HashMap::new(),
+            by_id: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn get_or_insert(&mut self, entry: &str) -> DictionaryID {
+        match self.by_value.get(entry) {
+            Some(id) => *id,
+            None => {
+                let id = self.next_id;
+                self.by_value.insert(entry.to_string(), id);
+                self.by_id.insert(id, entry.to_string());
+
+                self.next_id += 1;
+
+                id
+            }
+        }
+    }
+}
+
+/// Represents cell segmentation and analysis data parsed from a table of cells (e.g. a .csv file)
+#[derive(Clone)]
+pub struct CellData {
+    headers: Vec<Column>,
+    data: Vec<ColumnData>,
+    dictionary: Dictionary,
+    num_cells: usize,
+    mapping: ColumnMapping,
+}
+
+// TODO: Allow selecting column based on Header, Index, HeaderContains,...
+
+impl CellData {
+    /// Returns the columns of the table, in the order in which they appear
+    pub fn columns(&self) -> &[Column] {
+        &self.headers
+    }
+
+    /// Returns the mapping of the columns to the id, position, area, bounds and marker intensities of each cell
+    pub fn mapping(&self) -> &ColumnMapping {
+        &self.mapping
+    }
+
+    /// Returns the number of cells (rows) in the table
+    pub fn num_cells(&self) -> usize {
+        self.num_cells
+    }
+
+    /// Returns the text with the given `DictionaryID` (as stored in `ColumnData::Text`), or None if there is no
+    /// such entry
+    pub fn text(&self, id: usize) -> Option<&str> {
+        self.dictionary.by_id.get(&id).map(|text| text.as_str())
+    }
+
+    /// Returns a header `Column` with the specified name
+    pub fn header(&self, name: &str) -> Option<&Column> {
+        self.headers.iter().find(|&header| header.name == name)
+    }
+
+    /// Returns the data in the column at the ith position in the file
+    pub fn column_data(&self, index: usize) -> Option<&ColumnData> {
+        self.data.get(index)
+    }
+
+    /// Returns the integer data in the column with the specified name, with an error if the column is not
+    /// present, is not an integer column or has missing values
+    fn integer_column(&self, name: &str) -> Result<&[Option<i64>]> {
+        let data = self.integers(name)?;
+
+        match data.iter().position(Option::is_none) {
+            Some(row) => Err(MCDError::InvalidCellData {
+                column: name.to_string(),
+                line: None,
+                reason: format!("cell {} has no value", row),
+            }),
+            None => Ok(data),
+        }
+    }
+
+    /// Returns the column mapped (by `column`) to the `field` of each cell (e.g. the area), with an error if no
+    /// column is mapped
+    fn mapped<'a>(&self, field: &str, column: &'a Option<String>) -> Result<&'a str> {
+        column.as_deref().ok_or_else(|| MCDError::InvalidCellData {
+            column: field.to_string(),
+            line: None,
+            reason: format!("no column is mapped to the {} of each cell", field),
+        })
+    }
+
+    /// Returns the id of each cell, with None for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if no column is mapped to the id (see [`ColumnMapping::id`]), or the
+    /// column is not present or is not an integer column.
+    pub fn ids(&self) -> Result<&[Option<i64>]> {
+        self.integers(self.mapped("id", &self.mapping.id)?)
+    }
+
+    /// Returns the area of each cell, with NaN for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if no column is mapped to the area (see [`ColumnMapping::area`]), or
+    /// the column is not present or contains text.
+    pub fn areas(&self) -> Result<Vec<f64>> {
+        self.floats(self.mapped("area", &self.mapping.area)?)
+    }
+
+    /// Returns the markers whose intensities are recorded in the table (see [`ColumnMapping::markers`]), in the
+    /// order in which their columns appear
+    pub fn markers(&self) -> Vec<Marker> {
+        self.headers
+            .iter()
+            .filter_map(|column| self.mapping.marker(column))
+            .collect()
+    }
+
+    /// Returns the intensity of each marker (see [`CellData::markers`]) in each cell as a cells by markers matrix,
+    /// in row-major order, with NaN for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if any of the marker columns contains text.
+    pub fn marker_intensities(&self) -> Result<Vec<f64>> {
+        let markers = self.markers();
+        let columns: Vec<_> = markers.iter().map(Marker::column).collect();
+
+        self.marker_matrix(&columns)
+    }
+
+    /// Returns an iterator over each cell, providing the detected boundaries for each cell
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if no columns are mapped to the bounds (see [`ColumnMapping::bounds`]),
+    /// or any of the columns (e.g. XMin, XMax, YMin and YMax for HALO) is missing, is not an integer column or has
+    /// missing values.
+    pub fn boundaries(&self) -> Result<BoundariesIterator<'_>> {
+        let [x_min, x_max, y_min, y_max] =
+            self.mapping
+                .bounds
+                .as_ref()
+                .ok_or_else(|| MCDError::InvalidCellData {
+                    column: "bounds".to_string(),
+                    line: None,
+                    reason: "no columns are mapped to the bounds of each cell".to_string(),
+                })?;
+
+        Ok(BoundariesIterator {
+            x_min_data: self.integer_column(x_min)?,
+            x_max_data: self.integer_column(x_max)?,
+            y_min_data: self.integer_column(y_min)?,
+            y_max_data: self.integer_column(y_max)?,
+
+            index: 0,
+        })
+    }
+}
+
+/// Iterator over each cell, providing the detected boundaries for each cell
+pub struct BoundariesIterator<'a> {
+    x_min_data: &'a [Option<i64>],
+    x_max_data: &'a [Option<i64>],
+    y_min_data: &'a [Option<i64>],
+    y_max_data: &'a [Option<i64>],
+
+    index: usize,
+}
+
+impl<'a> Iterator for BoundariesIterator<'a> {
+    type Item = BoundingBox<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+
+        // Missing values are rejected when the iterator is created
+        let x_min = (*self.x_min_data.get(index)?)?;
+        let x_max = (*self.x_max_data.get(index)?)?;
+        let y_min = (*self.y_min_data.get(index)?)?;
+        let y_max = (*self.y_max_data.get(index)?)?;
+
+        self.index += 1;
+
+        Some(BoundingBox {
+            min_x: x_min,
+            min_y: y_min,
+            width: x_max - x_min,
+            height: y_max - y_min,
+        })
+    }
+}
+
+/// Returns true if the value is missing (e.g. a measurement which could not be made for the cell)
+fn is_missing(value: &str) -> bool {
+    matches!(value, "" | "NA" | "N/A" | "NaN" | "nan")
+}
+
+fn parse_binary(value: &str) -> Option<bool> {
+    match value {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ if value.eq_ignore_ascii_case("true") => Some(true),
+        _ if value.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+/// Infer the type of the column from its name and (present) values. Columns which the mapping reads as text or
+/// as positive classifications (e.g. those containing `Positive` in HALO, as 0 or 1) are checked first.
+fn infer_column_type<'a, I: Iterator<Item = &'a str> + Clone>(
+    mapping: &ColumnMapping,
+    name: &str,
+    values: I,
+) -> ColumnType {
+    let mut values = values.filter(|value| !is_missing(value));
+    let positive = mapping
+        .positive_columns
+        .as_ref()
+        .is_some_and(|pattern| name.contains(pattern.as_str()));
+
+    if mapping.text_columns.iter().any(|column| column == name) {
+        ColumnType::Text
+    } else if positive && values.clone().all(|value| parse_binary(value).is_some()) {
+        ColumnType::Binary
+    } else if values.clone().all(|value| value.parse::<i64>().is_ok()) && values.next().is_some() {
+        ColumnType::Integer
+    } else if values.all(|value| value.parse::<f64>().is_ok()) {
+        ColumnType::Float
+    } else {
+        ColumnType::Text
+    }
+}
+
+/// Parse a table of cells stored in a delimited (e.g. .csv) format, with the first row naming the columns. The
+/// options describe which columns hold the id, position, area, bounds and marker intensities of each cell, so that
+/// tables from HALO, CellProfiler, steinbock or custom pipelines can be accessed through the same `CellData`.
+///
+/// ```no_run
+/// use imc_rs::celltable::{self, CellTableOptions};
+///
+/// let cells = celltable::parse_from_path("cells.csv", &CellTableOptions::steinbock()).unwrap();
+///
+/// for marker in cells.markers() {
+///     println!("{}", marker.name());
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns `MCDError::Csv` if the file is not valid CSV (e.g. a row has the wrong number of fields), and
+/// `MCDError::InvalidCellData` (with the line number) if a value can't be parsed as the type of its column.
+pub fn parse<R: Read>(reader: R, options: &CellTableOptions) -> Result<CellData> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .from_reader(reader);
+
+    let names: Vec<String> = rdr.headers()?.iter().map(str::to_string).collect();
+    let records = rdr.records().collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut dictionary = Dictionary::new();
+    let mut headers = Vec::with_capacity(names.len());
+    let mut column_data = Vec::with_capacity(names.len());
+
+    for (index, name) in names.into_iter().enumerate() {
+        let values = records
+            .iter()
+            .map(move |record| record.get(index).unwrap_or_default().trim());
+        let column_type = options
+            .column_types
+            .get(&name)
+            .copied()
+            .unwrap_or_else(|| infer_column_type(&options.mapping, &name, values.clone()));
+
+        let mut data = ColumnData::new(column_type, records.len());
+        for (record, value) in records.iter().zip(values) {
+            push_value(&mut data, &mut dictionary, value).map_err(|reason| {
+                MCDError::InvalidCellData {
+                    column: name.clone(),
+                    line: line(record),
+                    reason,
+                }
+            })?;
+        }
+
+        headers.push(Column {
+            name,
+            column_type,
+            number: index,
+        });
+        column_data.push(data);
+    }
+
+    Ok(CellData {
+        headers,
+        data: column_data,
+        dictionary,
+        num_cells: records.len(),
+        mapping: options.mapping.clone(),
+    })
+}
+
+fn line(record: &StringRecord) -> Option<u64> {
+    record.position().map(|position| position.line())
+}
+
+/// Parse the value as the type of the column, returning a description of the problem if it can't be parsed
+fn push_value(
+    data: &mut ColumnData,
+    dictionary: &mut Dictionary,
+    value: &str,
+) -> std::result::Result<(), String> {
+    let missing = is_missing(value);
+
+    match data {
+        ColumnData::Text(data) => data.push(dictionary.get_or_insert(value)),
+        ColumnData::Binary(data) if missing => data.push(None),
+        ColumnData::Binary(data) => data.push(Some(
+            parse_binary(value).ok_or_else(|| format!("'{}' is not 0 or 1", value))?,
+        )),
+        ColumnData::Integer(data) if missing => data.push(None),
+        ColumnData::Integer(data) => {
+            data.push(Some(value.parse().map_err(|error| {
+                format!("'{}' is not an integer ({})", value, error)
+            })?))
+        }
+        ColumnData::Float(data) if missing => data.push(f64::NAN),
+        ColumnData::Float(data) => data.push(
+            value
+                .parse()
+                .map_err(|error| format!("'{}' is not a number ({})", value, error))?,
+        ),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const CSV: &str = "\
+Image Location,Object Id,XMin,XMax,YMin,YMax,Cell Area (µm²),CD3 Positive,CD3 Intensity,Analysis Region
+slide.svs,1,0,10,5,15,45.5,1,0.25,1
+slide.svs,2,20,30,25,35,,0,NA,1
+";
+
+    #[test]
+    fn infers_column_types() -> Result<()> {
+        let cells = parse(Cursor::new(CSV), &CellTableOptions::halo())?;
+        assert_eq!(cells.num_cells(), 2);
+
+        let types: Vec<_> = cells
+            .columns()
+            .iter()
+            .map(|column| column.column_type())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Text,
+                ColumnType::Integer,
+                ColumnType::Integer,
+                ColumnType::Integer,
+                ColumnType::Integer,
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::Binary,
+                ColumnType::Float,
+                ColumnType::Text,
+            ]
+        );
+
+        match cells.column_data(6) {
+            Some(ColumnData::Float(area)) => assert!(area[0] == 45.5 && area[1].is_nan()),
+            other => panic!("Unexpected area column {:?}", other),
+        }
+        assert!(matches!(
+            cells.column_data(7),
+            Some(ColumnData::Binary(positive)) if positive == &[Some(true), Some(false)]
+        ));
+
+        let boundaries: Vec<_> = cells.boundaries()?.map(|b| (b.min_x, b.width)).collect();
+        assert_eq!(boundaries, [(0, 10), (20, 10)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_invalid_values() -> Result<()> {
+        let options = CellTableOptions::halo().column_type("Image Location", ColumnType::Integer);
+        match parse(Cursor::new(CSV), &options) {
+            Err(error @ MCDError::InvalidCellData { line: Some(2), .. }) => assert_eq!(
+                error.to_string(),
+                "Invalid cell data in column 'Image Location' on line 2: 'slide.svs' is not an integer \
+                 (invalid digit found in string)"
+            ),
+            other => panic!("Unexpected result {:?}", other.err()),
+        }
+
+        // Bounds which are missing for a cell
+        let cells = parse(
+            Cursor::new("XMin,XMax,YMin,YMax\n0,10,5,15\n20,,25,35\n"),
+            &CellTableOptions::halo(),
+        )?;
+        assert!(matches!(
+            cells.boundaries(),
+            Err(MCDError::InvalidCellData { column, line: None, .. }) if column == "XMax"
+        ));
+
+        // Bounds which are not mapped
+        let cells = parse(
+            Cursor::new("XMin,XMax,YMin,YMax\n0,10,5,15\n"),
+            &CellTableOptions::new(),
+        )?;
+        assert!(matches!(
+            cells.boundaries(),
+            Err(MCDError::InvalidCellData { column, .. }) if column == "bounds"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_pipeline_columns() -> Result<()> {
+        let cellprofiler = parse(
+            Cursor::new(
+                "ImageNumber\tObjectNumber\tAreaShape_Area\tAreaShape_Center_X\tAreaShape_Center_Y\t\
+                 Intensity_MeanIntensity_CD3\tIntensity_MeanIntensity_DNA1\n\
+                 1\t1\t120\t10.5\t20.5\t0.25\t1.5\n\
+                 1\t2\t80\t30.5\t40.5\t0.75\t2.5\n",
+            ),
+            &CellTableOptions::cellprofiler().delimiter(b'\t'),
+        )?;
+        let markers: Vec<_> = cellprofiler
+            .markers()
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect();
+        assert_eq!(markers, ["CD3", "DNA1"]);
+        assert_eq!(cellprofiler.ids()?, [Some(1), Some(2)]);
+        assert_eq!(cellprofiler.areas()?, [120.0, 80.0]);
+        assert_eq!(cellprofiler.centroids()?, [(10.5, 20.5), (30.5, 40.5)]);
+        assert_eq!(cellprofiler.marker_intensities()?, [0.25, 1.5, 0.75, 2.5]);
+
+        let steinbock = parse(
+            Cursor::new(
+                "Image,Object,CD3,DNA1,area,centroid-0,centroid-1\n\
+                 img.tiff,1,0.5,2,14,5.5,7.5\n",
+            ),
+            &CellTableOptions::steinbock(),
+        )?;
+        let markers: Vec<_> = steinbock
+            .markers()
+            .iter()
+            .map(|m| m.column().to_string())
+            .collect();
+        assert_eq!(markers, ["CD3", "DNA1"]);
+        assert_eq!(steinbock.centroids()?, [(7.5, 5.5)]);
+
+        // Custom pipelines map their own columns
+        let mapping = ColumnMapping::new()
+            .id("CellID")
+            .markers(MarkerColumns::Named(vec!["CD8".to_string()]));
+        let custom = parse(
+            Cursor::new("CellID,CD8,Notes\n7,3.5,x\n"),
+            &CellTableOptions::new().mapping(mapping),
+        )?;
+        assert_eq!(custom.ids()?, [Some(7)]);
+        assert_eq!(custom.marker_intensities()?, [3.5]);
+        assert!(matches!(
+            custom.areas(),
+            Err(MCDError::InvalidCellData { column, .. }) if column == "area"
+        ));
+
+        Ok(())
+    }
+}
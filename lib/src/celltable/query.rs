@@ -0,0 +1,360 @@
+use crate::{
+    coords::SlidePoint,
+    error::{MCDError, Result},
+    transform::Transform2D,
+};
+
+use super::{CellData, Column, ColumnData};
+
+/// A single cell (row) of `CellData`, providing access to its values by column name
+#[derive(Clone, Copy)]
+pub struct Cell<'a> {
+    data: &'a CellData,
+    index: usize,
+}
+
+impl<'a> Cell<'a> {
+    /// Returns the position of the cell (row) within the `CellData`
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    fn value(&self, name: &str) -> Option<&'a ColumnData> {
+        let header = self.data.header(name)?;
+
+        self.data.column_data(header.column_number())
+    }
+
+    /// Returns the value of the numeric (floating point, integer or binary) column with the specified name as a
+    /// float, or None if there is no such column or the value is missing
+    pub fn float(&self, name: &str) -> Option<f64> {
+        let value = as_float(self.value(name)?, self.index)?;
+
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// Returns the value of the integer column with the specified name, or None if there is no such column or the
+    /// value is missing
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.value(name)? {
+            ColumnData::Integer(data) => data.get(self.index).copied().flatten(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the binary column with the specified name (e.g. whether the cell is positive for a
+    /// marker), or None if there is no such column or the value is missing
+    pub fn binary(&self, name: &str) -> Option<bool> {
+        match self.value(name)? {
+            ColumnData::Binary(data) => data.get(self.index).copied().flatten(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the text column with the specified name, or None if there is no such column
+    pub fn text(&self, name: &str) -> Option<&'a str> {
+        match self.value(name)? {
+            ColumnData::Text(data) => self.data.text(*data.get(self.index)?),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the value of a numeric column as a float (NaN if missing), or None for text columns
+fn as_float(data: &ColumnData, index: usize) -> Option<f64> {
+    match data {
+        ColumnData::Float(data) => data.get(index).copied(),
+        ColumnData::Integer(data) => Some(data.get(index)?.map_or(f64::NAN, |value| value as f64)),
+        ColumnData::Binary(data) => Some(
+            data.get(index)?
+                .map_or(f64::NAN, |value| value as u8 as f64),
+        ),
+        ColumnData::Text(_) => None,
+    }
+}
+
+/// Returns the data at the specified rows of the column
+fn take_rows(data: &ColumnData, rows: &[usize]) -> ColumnData {
+    match data {
+        ColumnData::Text(data) => ColumnData::Text(rows.iter().map(|&row| data[row]).collect()),
+        ColumnData::Binary(data) => ColumnData::Binary(rows.iter().map(|&row| data[row]).collect()),
+        ColumnData::Integer(data) => {
+            ColumnData::Integer(rows.iter().map(|&row| data[row]).collect())
+        }
+        ColumnData::Float(data) => ColumnData::Float(rows.iter().map(|&row| data[row]).collect()),
+    }
+}
+
+fn invalid_column(name: &str, reason: String) -> MCDError {
+    MCDError::InvalidCellData {
+        column: name.to_string(),
+        line: None,
+        reason,
+    }
+}
+
+impl CellData {
+    /// Returns the column with the specified name and its data, or `MCDError::InvalidCellData` if there is no such
+    /// column
+    fn named_column(&self, name: &str) -> Result<(&Column, &ColumnData)> {
+        self.header(name)
+            .and_then(|header| Some((header, self.column_data(header.column_number())?)))
+            .ok_or_else(|| invalid_column(name, "no such column".to_string()))
+    }
+
+    /// Returns the values of the numeric (floating point, integer or binary) column with the specified name (e.g.
+    /// `floats("Dapi Intensity")`) as floats, with NaN for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if there is no such column, or it contains text.
+    pub fn floats(&self, name: &str) -> Result<Vec<f64>> {
+        let (header, data) = self.named_column(name)?;
+
+        (0..self.num_cells())
+            .map(|index| as_float(data, index))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                invalid_column(
+                    name,
+                    format!(
+                        "expected numbers, but the column contains {:?} data",
+                        header.column_type()
+                    ),
+                )
+            })
+    }
+
+    /// Returns the values of the integer column with the specified name, with None for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if there is no such column, or it is not an integer column.
+    pub fn integers(&self, name: &str) -> Result<&[Option<i64>]> {
+        match self.named_column(name)? {
+            (_, ColumnData::Integer(data)) => Ok(data),
+            (header, _) => Err(invalid_column(
+                name,
+                format!(
+                    "expected integers, but the column contains {:?} data",
+                    header.column_type()
+                ),
+            )),
+        }
+    }
+
+    /// Returns the values of the binary column with the specified name (e.g. `binary("CD3 Positive")`), with None
+    /// for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if there is no such column, or it is not a binary column.
+    pub fn binary(&self, name: &str) -> Result<&[Option<bool>]> {
+        match self.named_column(name)? {
+            (_, ColumnData::Binary(data)) => Ok(data),
+            (header, _) => Err(invalid_column(
+                name,
+                format!(
+                    "expected 0 or 1, but the column contains {:?} data",
+                    header.column_type()
+                ),
+            )),
+        }
+    }
+
+    /// Returns the values of the text column with the specified name (e.g. `texts("Classifier Label")`)
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if there is no such column, or it is not a text column.
+    pub fn texts(&self, name: &str) -> Result<Vec<&str>> {
+        match self.named_column(name)? {
+            (_, ColumnData::Text(data)) => Ok(data
+                .iter()
+                .map(|&id| self.text(id).unwrap_or_default())
+                .collect()),
+            (header, _) => Err(invalid_column(
+                name,
+                format!(
+                    "expected text, but the column contains {:?} data",
+                    header.column_type()
+                ),
+            )),
+        }
+    }
+
+    /// Returns the cell (row) at the specified position, or None if there are fewer cells
+    pub fn cell(&self, index: usize) -> Option<Cell<'_>> {
+        (index < self.num_cells()).then_some(Cell { data: self, index })
+    }
+
+    /// Returns an iterator over the cells (rows), in the order in which they appear in the file
+    pub fn cells(&self) -> impl Iterator<Item = Cell<'_>> + '_ {
+        (0..self.num_cells()).map(move |index| Cell { data: self, index })
+    }
+
+    /// Returns a copy containing only the cells for which `predicate` returns true, e.g. the CD3 positive
+    /// cells with `filter(|cell| cell.binary("CD3 Positive") == Some(true))`
+    pub fn filter<F: FnMut(&Cell) -> bool>(&self, mut predicate: F) -> CellData {
+        let rows: Vec<_> = self
+            .cells()
+            .filter(|cell| predicate(cell))
+            .map(|cell| cell.index)
+            .collect();
+
+        CellData {
+            headers: self.headers.clone(),
+            data: self
+                .data
+                .iter()
+                .map(|data| take_rows(data, &rows))
+                .collect(),
+            dictionary: self.dictionary.clone(),
+            num_cells: rows.len(),
+            mapping: self.mapping.clone(),
+        }
+    }
+
+    /// Returns a copy containing only the columns with the specified names, in the order given
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if any of the columns is not present.
+    pub fn select(&self, names: &[&str]) -> Result<CellData> {
+        let mut headers = Vec::with_capacity(names.len());
+        let mut data = Vec::with_capacity(names.len());
+
+        for (number, name) in names.iter().enumerate() {
+            let (header, column_data) = self.named_column(name)?;
+
+            headers.push(Column {
+                name: header.name.clone(),
+                column_type: header.column_type,
+                number,
+            });
+            data.push(column_data.clone());
+        }
+
+        Ok(CellData {
+            headers,
+            data,
+            dictionary: self.dictionary.clone(),
+            num_cells: self.num_cells,
+            mapping: self.mapping.clone(),
+        })
+    }
+
+    /// Returns the values of the specified numeric columns (e.g. the intensity of each marker) as a cells by
+    /// columns matrix, in row-major order (`num_cells() * names.len()` in length), with NaN for missing values
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCDError::InvalidCellData` if any of the columns is not present, or contains text.
+    pub fn marker_matrix(&self, names: &[&str]) -> Result<Vec<f64>> {
+        let columns = names
+            .iter()
+            .map(|name| self.floats(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((0..self.num_cells())
+            .flat_map(|index| columns.iter().map(move |column| column[index]))
+            .collect())
+    }
+
+    /// Returns the centroid of each cell, in the pixel coordinates recorded in the table. This is read from the
+    /// position columns if mapped (see `ColumnMapping::position`), and otherwise is the centre of the bounding
+    /// box (see [`CellData::boundaries`]). Missing coordinates are NaN.
+    pub fn centroids(&self) -> Result<Vec<(f64, f64)>> {
+        if let (Some(x), Some(y)) = (&self.mapping.x, &self.mapping.y) {
+            return Ok(self.floats(x)?.into_iter().zip(self.floats(y)?).collect());
+        }
+
+        Ok(self
+            .boundaries()?
+            .map(|bounds| {
+                (
+                    bounds.min_x as f64 + bounds.width as f64 / 2.0,
+                    bounds.min_y as f64 + bounds.height as f64 / 2.0,
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the centroid of each cell (see [`CellData::centroids`]) on the slide (in μm), mapping the pixel
+    /// coordinates with `transform` (e.g. an `AffineTransform` fitted with `AffineTransform::from_points` to
+    /// register the analysed image to the slide). The position is None for cells which have no centroid or can't
+    /// be mapped.
+    pub fn slide_centroids<T: Transform2D<f64> + ?Sized>(
+        &self,
+        transform: &T,
+    ) -> Result<Vec<Option<SlidePoint>>> {
+        Ok(self
+            .centroids()?
+            .into_iter()
+            .map(|(x, y)| {
+                if x.is_nan() || y.is_nan() {
+                    return None;
+                }
+
+                transform.to_slide(x, y).map(|(x, y)| SlidePoint::new(x, y))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        celltable::{parse, CellTableOptions},
+        transform::AffineTransform,
+    };
+
+    #[test]
+    fn queries_cells() -> Result<()> {
+        let cells = parse(Cursor::new(
+            "Object Id,XMin,XMax,YMin,YMax,CD3 Positive,Dapi Intensity,CD3 Intensity,Classifier Label\n\
+             1,0,10,0,20,1,0.5,2.5,Tumour\n\
+             2,10,20,0,20,0,1.5,,Stroma\n\
+             3,20,30,0,20,1,2.5,4.5,Tumour\n",
+        ), &CellTableOptions::halo())?;
+
+        assert_eq!(cells.floats("Dapi Intensity")?, [0.5, 1.5, 2.5]);
+        assert_eq!(cells.floats("Object Id")?, [1.0, 2.0, 3.0]);
+        assert_eq!(
+            cells.texts("Classifier Label")?,
+            ["Tumour", "Stroma", "Tumour"]
+        );
+        assert!(matches!(
+            cells.floats("Classifier Label"),
+            Err(MCDError::InvalidCellData { .. })
+        ));
+        assert!(cells.binary("Missing").is_err());
+
+        let positive = cells.filter(|cell| cell.binary("CD3 Positive") == Some(true));
+        assert_eq!(positive.num_cells(), 2);
+        assert_eq!(positive.integers("Object Id")?, [Some(1), Some(3)]);
+        assert_eq!(
+            positive
+                .cell(1)
+                .and_then(|cell| cell.text("Classifier Label")),
+            Some("Tumour")
+        );
+
+        let markers = cells.select(&["CD3 Intensity", "Dapi Intensity"])?;
+        assert_eq!(markers.columns().len(), 2);
+        assert_eq!(markers.columns()[1].column_number(), 1);
+        let matrix = markers.marker_matrix(&["CD3 Intensity", "Dapi Intensity"])?;
+        assert_eq!(matrix.len(), 6);
+        assert_eq!(matrix[..2], [2.5, 0.5]);
+        assert!(matrix[2].is_nan());
+
+        let transform = AffineTransform::scaling(0.5, 0.5);
+        let centroids = cells.slide_centroids(&transform)?;
+        assert_eq!(centroids[1], Some(SlidePoint::new(7.5, 5.0)));
+
+        Ok(())
+    }
+}
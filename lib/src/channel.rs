@@ -1,5 +1,170 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{
+    acquisition::MAX_NUMBER_DISTANCE,
+    error::{MCDError, Result},
+    pattern,
+};
+
+/// Chemical symbols of the elements, used when parsing isotopes from channel names and labels
+const ELEMENTS: [&str; 118] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb",
+    "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl",
+    "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+    "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh",
+    "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// Range of mass numbers considered when parsing isotopes. Restricting to the mass range measured by imaging
+/// mass cytometry avoids interpreting marker names such as `H3` or `CD45` as isotopes.
+const MASS_RANGE: std::ops::RangeInclusive<u16> = 70..=260;
+
+/// Names of the channels recording the position of each pixel, which are not part of the antibody panel
+const POSITION_CHANNELS: [&str; 3] = ["X", "Y", "Z"];
+
+/// Characters allowed between the element and mass of an isotope (e.g. `Ir(191)`, `152-Sm`)
+const SEPARATORS: [char; 5] = ['(', ')', '-', '_', ' '];
+
+/// An isotope, described by the chemical element and mass number (e.g. Ir191)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Isotope {
+    element: String,
+    mass: u16,
+}
+
+impl Isotope {
+    /// Create an isotope from an element symbol (e.g. `Ir`) and mass number
+    pub fn new(element: &str, mass: u16) -> Self {
+        Isotope {
+            element: element.to_string(),
+            mass,
+        }
+    }
+
+    /// Returns the chemical symbol of the element
+    pub fn element(&self) -> &str {
+        &self.element
+    }
+
+    /// Returns the mass number
+    pub fn mass(&self) -> u16 {
+        self.mass
+    }
+
+    /// Find an isotope within `text`, regardless of formatting. For example, `Ir191`, `Ir(191)`, `191Ir`,
+    /// `Ir191Di` and `DNA1(Ir191)` are all parsed as Ir191. Returns None if no isotope is found.
+    pub fn parse(text: &str) -> Option<Self> {
+        let chars: Vec<char> = text.chars().collect();
+
+        let mut index = 0;
+        while index < chars.len() {
+            if !chars[index].is_ascii_digit() {
+                index += 1;
+                continue;
+            }
+
+            let start = index;
+            while index < chars.len() && chars[index].is_ascii_digit() {
+                index += 1;
+            }
+
+            let mass = match chars[start..index].iter().collect::<String>().parse() {
+                Ok(mass) if MASS_RANGE.contains(&mass) => mass,
+                _ => continue,
+            };
+
+            if let Some(element) = element_before(&chars[..start]) {
+                return Some(Isotope::new(element, mass));
+            }
+            if let Some(element) = element_after(&chars[index..]) {
+                return Some(Isotope::new(element, mass));
+            }
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for Isotope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.element, self.mass)
+    }
+}
+
+impl FromStr for Isotope {
+    type Err = MCDError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Isotope::parse(text).ok_or_else(|| MCDError::InvalidIsotope {
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Returns the canonical symbol if `symbol` is an element. Only symbols which are capitalised correctly are
+/// accepted unless `ignore_case`.
+fn lookup_element(symbol: &[char], ignore_case: bool) -> Option<&'static str> {
+    let symbol: String = symbol.iter().collect();
+
+    ELEMENTS.iter().copied().find(|element| {
+        if ignore_case {
+            element.eq_ignore_ascii_case(&symbol)
+        } else {
+            *element == symbol
+        }
+    })
+}
+
+/// Find the element symbol at the end of `chars` (e.g. `Ir` in `DNA1(Ir`)
+fn element_before(chars: &[char]) -> Option<&'static str> {
+    let chars = match chars.last() {
+        Some(c) if SEPARATORS.contains(c) => &chars[..chars.len() - 1],
+        _ => chars,
+    };
+
+    let start = chars
+        .iter()
+        .rposition(|c| !c.is_ascii_alphabetic())
+        .map_or(0, |position| position + 1);
+    let letters = &chars[start..];
+
+    if letters.is_empty() {
+        None
+    } else if letters.len() <= 2 {
+        lookup_element(letters, true)
+    } else {
+        lookup_element(&letters[letters.len() - 2..], false)
+            .or_else(|| lookup_element(&letters[letters.len() - 1..], false))
+    }
+}
+
+/// Find the element symbol at the start of `chars` (e.g. `Sm` in `Sm_CD45`)
+fn element_after(chars: &[char]) -> Option<&'static str> {
+    let chars = match chars.first() {
+        Some(c) if SEPARATORS.contains(c) => &chars[1..],
+        _ => chars,
+    };
+
+    let end = chars
+        .iter()
+        .position(|c| !c.is_ascii_alphabetic())
+        .unwrap_or(chars.len());
+    let letters = &chars[..end];
+
+    if letters.is_empty() {
+        None
+    } else if letters.len() <= 2 {
+        lookup_element(letters, true)
+    } else {
+        lookup_element(&letters[..2], false).or_else(|| lookup_element(&letters[..1], false))
+    }
+}
+
 /// ChannelIdentifier describes how a channel can be identified
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChannelIdentifier {
     // Unique identifier for the channel
     //Id(u16),
@@ -9,6 +174,11 @@ pub enum ChannelIdentifier {
     Name(String),
     /// Label given to the channel
     Label(String),
+    /// Isotope measured in the channel, matched against the isotope parsed from the channel name or label
+    /// regardless of formatting (e.g. `Ir191`, `Ir(191)` and `191Ir` all match)
+    Isotope(Isotope),
+    /// Case-insensitive substring of the channel name or label
+    Fuzzy(String),
 }
 
 impl ChannelIdentifier {
@@ -26,6 +196,38 @@ impl ChannelIdentifier {
     pub fn order(order: i16) -> Self {
         Self::Order(order)
     }
+
+    /// Create a channel identifier based on the isotope (e.g. `Ir191`, `Ir(191)` or `191Ir`).
+    pub fn isotope(isotope: &str) -> Result<Self> {
+        Ok(Self::Isotope(isotope.parse()?))
+    }
+
+    /// Create a channel identifier matching any channel with a name or label containing `text` (ignoring case).
+    pub fn fuzzy(text: &str) -> Self {
+        Self::Fuzzy(text.into())
+    }
+
+    /// Returns how different the channel is from this identifier (lower is closer), or None if it is too
+    /// different to be suggested in place of this identifier
+    pub(crate) fn distance(&self, channel: &AcquisitionChannel) -> Option<usize> {
+        let text_distance = |text: &str| {
+            let name = pattern::text_distance(text, channel.name());
+            let label = pattern::text_distance(text, channel.label());
+
+            name.into_iter().chain(label).min()
+        };
+
+        match self {
+            ChannelIdentifier::Order(order_number) => {
+                let distance = channel.order_number().abs_diff(*order_number) as usize;
+                (distance <= MAX_NUMBER_DISTANCE).then_some(distance)
+            }
+            ChannelIdentifier::Name(text)
+            | ChannelIdentifier::Label(text)
+            | ChannelIdentifier::Fuzzy(text) => text_distance(text),
+            ChannelIdentifier::Isotope(isotope) => text_distance(&isotope.to_string()),
+        }
+    }
 }
 
 impl AsRef<ChannelIdentifier> for ChannelIdentifier {
@@ -46,6 +248,45 @@ impl From<&AcquisitionChannel> for ChannelIdentifier {
     }
 }
 
+/// Name and label of a channel, listed in `MCDError::ChannelNotFound` as the available channels and those
+/// suggested in place of an unknown `ChannelIdentifier`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelSummary {
+    name: String,
+    label: String,
+}
+
+impl ChannelSummary {
+    /// Returns the name of the channel (e.g. Ir191)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the label of the channel (e.g. DNA1)
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl From<&AcquisitionChannel> for ChannelSummary {
+    fn from(channel: &AcquisitionChannel) -> Self {
+        ChannelSummary {
+            name: channel.name().to_string(),
+            label: channel.label().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ChannelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.label.is_empty() || self.label == self.name {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.label)
+        }
+    }
+}
+
 /// AcquisitionChannel represents a single channel acquired, forming part of an acquisition
 #[derive(Debug, Clone)]
 pub struct AcquisitionChannel {
@@ -54,6 +295,7 @@ pub struct AcquisitionChannel {
     order_number: i16,
     acquisition_id: u16,
     channel_label: String,
+    extras: BTreeMap<String, String>,
 }
 
 impl AcquisitionChannel {
@@ -70,9 +312,15 @@ impl AcquisitionChannel {
             order_number,
             channel_name: name.to_string(),
             channel_label: label.to_string(),
+            extras: BTreeMap::new(),
         }
     }
 
+    pub(crate) fn with_extras(mut self, extras: BTreeMap<String, String>) -> Self {
+        self.extras = extras;
+        self
+    }
+
     /// Returns whether the specified channel identifier matches this channel
     pub fn is(&self, identifier: &ChannelIdentifier) -> bool {
         match identifier {
@@ -91,11 +339,41 @@ impl AcquisitionChannel {
                     return true;
                 }
             }
+            ChannelIdentifier::Isotope(isotope) => {
+                if self.isotope().as_ref() == Some(isotope) {
+                    return true;
+                }
+            }
+            ChannelIdentifier::Fuzzy(text) => {
+                let text = text.to_lowercase();
+
+                if self.name().to_lowercase().contains(&text)
+                    || self.label().to_lowercase().contains(&text)
+                {
+                    return true;
+                }
+            }
         }
 
         false
     }
 
+    /// Returns the isotope measured in the channel, parsed from the channel name (or the label, if the name
+    /// does not contain an isotope)
+    pub fn isotope(&self) -> Option<Isotope> {
+        Isotope::parse(self.name()).or_else(|| Isotope::parse(self.label()))
+    }
+
+    /// Returns the metal (element symbol, e.g. `Ir`) measured in the channel, if the isotope is known
+    pub fn metal(&self) -> Option<String> {
+        self.isotope().map(|isotope| isotope.element().to_string())
+    }
+
+    /// Returns the mass of the isotope measured in the channel, if known
+    pub fn mass(&self) -> Option<u16> {
+        self.isotope().map(|isotope| isotope.mass())
+    }
+
     /// Returns the ID associated with the channel
     #[inline]
     pub fn id(&self) -> u16 {
@@ -125,4 +403,49 @@ impl AcquisitionChannel {
     pub fn label(&self) -> &str {
         &self.channel_label
     }
+
+    /// Returns the child elements of the `AcquisitionChannel` element in the XML metadata which are not modelled here (e.g.
+    /// fields added in newer versions of the schema), with their text, ordered by name
+    pub fn extras(&self) -> &BTreeMap<String, String> {
+        &self.extras
+    }
+
+    /// Returns true if the channel records the position of each pixel (X, Y or Z) rather than the intensity of
+    /// a marker
+    pub fn is_position(&self) -> bool {
+        POSITION_CHANNELS.contains(&self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_isotopes() {
+        let ir191 = Some(Isotope::new("Ir", 191));
+
+        assert_eq!(Isotope::parse("Ir191"), ir191);
+        assert_eq!(Isotope::parse("Ir(191)"), ir191);
+        assert_eq!(Isotope::parse("191Ir"), ir191);
+        assert_eq!(Isotope::parse("ir191Di"), ir191);
+        assert_eq!(Isotope::parse("DNA1(Ir191)"), ir191);
+        assert_eq!(Isotope::parse("152Sm_CD45"), Some(Isotope::new("Sm", 152)));
+        assert_eq!(Isotope::parse("Histone H3"), None);
+        assert_eq!(Isotope::parse("CD45RA"), None);
+        assert_eq!(Isotope::parse("BCKG190"), None);
+    }
+
+    #[test]
+    fn isotope_and_fuzzy_matching() -> Result<()> {
+        let channel = AcquisitionChannel::new(1, 1, 1, "Ir(191)", "191Ir_DNA1");
+
+        assert!(channel.is(&ChannelIdentifier::isotope("Ir191")?));
+        assert!(!channel.is(&ChannelIdentifier::isotope("Ir193")?));
+        assert!(channel.is(&ChannelIdentifier::fuzzy("dna")));
+        assert!(!channel.is(&ChannelIdentifier::fuzzy("cd3")));
+        assert!(ChannelIdentifier::isotope("CD3").is_err());
+
+        Ok(())
+    }
 }
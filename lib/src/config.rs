@@ -0,0 +1,86 @@
+use std::sync::{Arc, RwLock};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::{MCDError, Result};
+
+static THREAD_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+/// Global configuration for the library.
+///
+/// Settings are only applied once [`Config::apply`] is called, for example:
+///
+/// ```
+/// use imc_rs::config::Config;
+///
+/// Config::new().threads(2).apply().unwrap();
+///
+/// assert_eq!(imc_rs::config::threads(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    threads: Option<usize>,
+}
+
+impl Config {
+    /// Create a new configuration using the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of threads used for parallel work (conversion, rendering, exporting and batch processing).
+    /// A value of 0 uses the default number of threads (one per logical CPU).
+    pub fn threads(mut self, num_threads: usize) -> Self {
+        self.threads = Some(num_threads);
+        self
+    }
+
+    /// Apply the configuration, replacing any previously applied configuration
+    pub fn apply(self) -> Result<()> {
+        if let Some(num_threads) = self.threads {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|index| format!("imc-rs-{}", index))
+                .build()?;
+
+            let mut thread_pool = THREAD_POOL.write().or(Err(MCDError::PoisonMutex))?;
+            *thread_pool = Some(Arc::new(pool));
+        }
+
+        Ok(())
+    }
+}
+
+/// Set the number of threads used for parallel work within the library. A value of 0 uses the default number
+/// of threads (one per logical CPU). This is equivalent to `Config::new().threads(num_threads).apply()`.
+pub fn set_threads(num_threads: usize) -> Result<()> {
+    Config::new().threads(num_threads).apply()
+}
+
+/// Returns the number of threads that will be used for parallel work within the library
+pub fn threads() -> usize {
+    match thread_pool() {
+        Some(pool) => pool.current_num_threads(),
+        None => rayon::current_num_threads(),
+    }
+}
+
+fn thread_pool() -> Option<Arc<ThreadPool>> {
+    match THREAD_POOL.read() {
+        Ok(pool) => pool.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Run `op` within the configured thread pool, so that any rayon parallel iterators used by `op` respect the
+/// configured number of threads. If no thread pool has been configured, the global rayon pool is used.
+pub(crate) fn install<OP, T>(op: OP) -> T
+where
+    OP: FnOnce() -> T + Send,
+    T: Send,
+{
+    match thread_pool() {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
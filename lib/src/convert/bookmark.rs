@@ -0,0 +1,373 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{error::MCDError, render::Colormap, AcquisitionRef, Region};
+
+use super::{ReadDCM, WriteDCM};
+
+/// Marks the end of the bookmarks stored at the end of a .dcm file
+const BOOKMARK_MAGIC: &[u8; 8] = b"IMCBKMK2";
+
+/// Size of the trailer following the bookmarks (length of the bookmarks followed by the magic)
+const TRAILER_SIZE: u64 = 16;
+
+/// Display settings for a channel of a `Bookmark`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkChannel {
+    name: String,
+    range: Option<(f32, f32)>,
+    colormap: Option<Colormap>,
+}
+
+impl BookmarkChannel {
+    /// Create display settings for the channel with the specified name (e.g. `Ir(191)`)
+    pub fn new(name: &str) -> Self {
+        BookmarkChannel {
+            name: name.to_string(),
+            range: None,
+            colormap: None,
+        }
+    }
+
+    /// Set the (min, max) intensity range used for display
+    pub fn range(mut self, min: f32, max: f32) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// Set the colormap used for display
+    pub fn colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = Some(colormap);
+        self
+    }
+
+    /// Returns the name of the channel
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the (min, max) intensity range used for display, if set
+    pub fn display_range(&self) -> Option<(f32, f32)> {
+        self.range
+    }
+
+    /// Returns the colormap used for display, if set
+    pub fn display_colormap(&self) -> Option<Colormap> {
+        self.colormap
+    }
+}
+
+/// A named region of interest within an acquisition, along with the channels and display settings used to view
+/// it. Bookmarks are stored in the .dcm file (see `MCD::bookmarks()` and `MCD::add_bookmark()`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    name: String,
+    acquisition: AcquisitionRef,
+    region: Option<Region>,
+    channels: Vec<BookmarkChannel>,
+}
+
+impl Bookmark {
+    /// Create a bookmark of the whole of the acquisition (e.g. from `Acquisition::reference()`)
+    pub fn new(name: &str, acquisition: AcquisitionRef) -> Self {
+        Bookmark {
+            name: name.to_string(),
+            acquisition,
+            region: None,
+            channels: Vec::new(),
+        }
+    }
+
+    /// Restrict the bookmark to a region (in pixels) of the acquisition
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Add a channel to display
+    pub fn channel(mut self, channel: BookmarkChannel) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    /// Returns the name of the bookmark
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the acquisition the bookmark belongs to
+    pub fn acquisition(&self) -> AcquisitionRef {
+        self.acquisition
+    }
+
+    /// Returns the ID of the acquisition the bookmark belongs to
+    pub fn acquisition_id(&self) -> u16 {
+        self.acquisition.id()
+    }
+
+    /// Returns the region (in pixels) of the acquisition, or None if the bookmark covers the whole acquisition
+    pub fn pixel_region(&self) -> Option<&Region> {
+        self.region.as_ref()
+    }
+
+    /// Returns the channels to display
+    pub fn channels(&self) -> &[BookmarkChannel] {
+        &self.channels
+    }
+}
+
+// Format (appended to the end of the .dcm file)
+// -----------
+// number of bookmarks (u16)
+// bookmarks (name, slide ID, panorama ID, acquisition ID, optional region, channels)
+// length of the bookmarks in bytes (u64)
+// magic (8 bytes)
+
+/// Read the bookmarks stored at the end of the .dcm file, returning an empty list if there are none
+pub(crate) fn read_bookmarks<T: Read + Seek>(dcm_file: &mut T) -> Result<Vec<Bookmark>, MCDError> {
+    let start = match bookmarks_start(dcm_file)? {
+        Some(start) => start,
+        None => return Ok(Vec::new()),
+    };
+
+    dcm_file.seek(SeekFrom::Start(start))?;
+
+    let num_bookmarks = dcm_file.read_u16::<LittleEndian>()?;
+    let mut bookmarks = Vec::with_capacity(num_bookmarks as usize);
+
+    for _ in 0..num_bookmarks {
+        let name = read_string(dcm_file)?;
+        let acquisition = dcm_file.read_acquisition_ref()?;
+
+        let region = if dcm_file.read_u8()? > 0 {
+            Some(Region {
+                x: dcm_file.read_u32::<LittleEndian>()?,
+                y: dcm_file.read_u32::<LittleEndian>()?,
+                width: dcm_file.read_u32::<LittleEndian>()?,
+                height: dcm_file.read_u32::<LittleEndian>()?,
+            })
+        } else {
+            None
+        };
+
+        let num_channels = dcm_file.read_u16::<LittleEndian>()?;
+        let mut channels = Vec::with_capacity(num_channels as usize);
+
+        for _ in 0..num_channels {
+            let name = read_string(dcm_file)?;
+
+            let range = if dcm_file.read_u8()? > 0 {
+                Some((
+                    dcm_file.read_f32::<LittleEndian>()?,
+                    dcm_file.read_f32::<LittleEndian>()?,
+                ))
+            } else {
+                None
+            };
+
+            let colormap = match dcm_file.read_u8()? {
+                1 => Some(Colormap::Viridis),
+                2 => Some(Colormap::Magma),
+                3 => Some(Colormap::Grayscale),
+                4 => {
+                    let mut color = [0; 3];
+                    dcm_file.read_exact(&mut color)?;
+                    Some(Colormap::SingleHue(color))
+                }
+                _ => None,
+            };
+
+            channels.push(BookmarkChannel {
+                name,
+                range,
+                colormap,
+            });
+        }
+
+        bookmarks.push(Bookmark {
+            name,
+            acquisition,
+            region,
+            channels,
+        });
+    }
+
+    Ok(bookmarks)
+}
+
+/// Write the bookmarks to the end of the .dcm file, replacing any bookmarks already stored there
+pub(crate) fn write_bookmarks<T: Read + Write + Seek>(
+    dcm_file: &mut T,
+    bookmarks: &[Bookmark],
+) -> Result<u64, MCDError> {
+    let mut buffer = Vec::new();
+    let num_bookmarks = u16::try_from(bookmarks.len()).map_err(|_| MCDError::InvalidBookmark {
+        name: bookmarks
+            .last()
+            .map(|bookmark| bookmark.name.clone())
+            .unwrap_or_default(),
+        reason: format!("at most {} bookmarks can be stored", u16::MAX),
+    })?;
+    buffer.write_u16::<LittleEndian>(num_bookmarks)?;
+
+    for bookmark in bookmarks {
+        write_string(&mut buffer, bookmark, &bookmark.name)?;
+        buffer.write_acquisition_ref(bookmark.acquisition)?;
+
+        match &bookmark.region {
+            Some(region) => {
+                buffer.write_u8(1)?;
+                buffer.write_u32::<LittleEndian>(region.x)?;
+                buffer.write_u32::<LittleEndian>(region.y)?;
+                buffer.write_u32::<LittleEndian>(region.width)?;
+                buffer.write_u32::<LittleEndian>(region.height)?;
+            }
+            None => buffer.write_u8(0)?,
+        }
+
+        let num_channels =
+            u16::try_from(bookmark.channels.len()).map_err(|_| MCDError::InvalidBookmark {
+                name: bookmark.name.clone(),
+                reason: format!("at most {} channels can be stored", u16::MAX),
+            })?;
+        buffer.write_u16::<LittleEndian>(num_channels)?;
+
+        for channel in &bookmark.channels {
+            write_string(&mut buffer, bookmark, &channel.name)?;
+
+            match channel.range {
+                Some((min, max)) => {
+                    buffer.write_u8(1)?;
+                    buffer.write_f32::<LittleEndian>(min)?;
+                    buffer.write_f32::<LittleEndian>(max)?;
+                }
+                None => buffer.write_u8(0)?,
+            }
+
+            match channel.colormap {
+                None => buffer.write_u8(0)?,
+                Some(Colormap::Viridis) => buffer.write_u8(1)?,
+                Some(Colormap::Magma) => buffer.write_u8(2)?,
+                Some(Colormap::Grayscale) => buffer.write_u8(3)?,
+                Some(Colormap::SingleHue(color)) => {
+                    buffer.write_u8(4)?;
+                    buffer.write_all(&color)?;
+                }
+            }
+        }
+    }
+
+    let start = match bookmarks_start(dcm_file)? {
+        Some(start) => start,
+        None => dcm_file.seek(SeekFrom::End(0))?,
+    };
+
+    dcm_file.seek(SeekFrom::Start(start))?;
+    dcm_file.write_all(&buffer)?;
+    dcm_file.write_u64::<LittleEndian>(buffer.len() as u64)?;
+    dcm_file.write_all(BOOKMARK_MAGIC)?;
+    dcm_file.flush()?;
+
+    // Returns the new length of the file, so that any remains of previous (longer) bookmarks can be truncated
+    Ok(start + buffer.len() as u64 + TRAILER_SIZE)
+}
+
+/// Returns the offset of the bookmarks if the file ends with the bookmark trailer
+pub(crate) fn bookmarks_start<T: Read + Seek>(dcm_file: &mut T) -> io::Result<Option<u64>> {
+    let length = dcm_file.seek(SeekFrom::End(0))?;
+    if length < TRAILER_SIZE {
+        return Ok(None);
+    }
+
+    dcm_file.seek(SeekFrom::Start(length - TRAILER_SIZE))?;
+    let bookmarks_length = dcm_file.read_u64::<LittleEndian>()?;
+    let mut magic = [0; 8];
+    dcm_file.read_exact(&mut magic)?;
+
+    if &magic != BOOKMARK_MAGIC || bookmarks_length > length - TRAILER_SIZE {
+        return Ok(None);
+    }
+
+    Ok(Some(length - TRAILER_SIZE - bookmarks_length))
+}
+
+fn read_string<T: Read>(reader: &mut T) -> Result<String, MCDError> {
+    let length = reader.read_u16::<LittleEndian>()?;
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|error| MCDError::InvalidUtf8 {
+        source: error.utf8_error(),
+    })
+}
+
+/// Write the text (the name of the bookmark or one of its channels), which must be at most `u16::MAX` bytes long
+fn write_string<T: Write>(writer: &mut T, bookmark: &Bookmark, text: &str) -> Result<(), MCDError> {
+    let length = u16::try_from(text.len()).map_err(|_| MCDError::InvalidBookmark {
+        name: bookmark.name.clone(),
+        reason: format!(
+            "names must be at most {} bytes long, but found {} bytes",
+            u16::MAX,
+            text.len()
+        ),
+    })?;
+
+    writer.write_u16::<LittleEndian>(length)?;
+    writer.write_all(text.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn bookmarks_round_trip() -> Result<(), MCDError> {
+        let mut dcm_file = Cursor::new(vec![1, 2, 3]);
+        assert!(read_bookmarks(&mut dcm_file)?.is_empty());
+
+        let bookmark = Bookmark::new("Tumour border", AcquisitionRef::new(1, 3, 2))
+            .region(Region {
+                x: 10,
+                y: 20,
+                width: 100,
+                height: 50,
+            })
+            .channel(
+                BookmarkChannel::new("Ir(191)")
+                    .range(0.0, 20.0)
+                    .colormap(Colormap::SingleHue([0, 0, 255])),
+            );
+
+        write_bookmarks(
+            &mut dcm_file,
+            &[
+                bookmark.clone(),
+                Bookmark::new("All", AcquisitionRef::new(2, 1, 1)),
+            ],
+        )?;
+        let length = write_bookmarks(&mut dcm_file, std::slice::from_ref(&bookmark))?;
+        dcm_file.get_mut().truncate(length as usize);
+
+        assert_eq!(read_bookmarks(&mut dcm_file)?, vec![bookmark.clone()]);
+        assert_eq!(&dcm_file.get_ref()[..3], &[1, 2, 3]);
+
+        // Names which don't fit are rejected, leaving the stored bookmarks unchanged
+        let long_name = "a".repeat(u16::MAX as usize + 1);
+        let result = write_bookmarks(
+            &mut dcm_file,
+            &[Bookmark::new(&long_name, AcquisitionRef::new(1, 1, 1))],
+        );
+        assert!(matches!(result, Err(MCDError::InvalidBookmark { .. })));
+        let channel = Bookmark::new("Long channel", AcquisitionRef::new(1, 1, 1))
+            .channel(BookmarkChannel::new(&long_name));
+        assert!(write_bookmarks(&mut dcm_file, &[channel]).is_err());
+        assert_eq!(read_bookmarks(&mut dcm_file)?, vec![bookmark]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::io::{self, Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::error::MCDError;
+
+/// Codec used to compress each channel chunk of a .dcm file. The codec is stored with every chunk, so .dcm
+/// files can be read regardless of the options used to create them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// Chunks are stored uncompressed, giving the fastest reads at the cost of the largest file
+    None,
+    /// LZ4 compression, which is very fast to decompress (default)
+    #[default]
+    Lz4,
+    /// Deflate compression, which is slower than LZ4 but gives smaller files (especially at higher levels)
+    Deflate,
+}
+
+impl Codec {
+    /// Identifier of the codec stored in the .dcm file
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Lz4 => 1,
+            Codec::Deflate => 2,
+        }
+    }
+
+    /// Returns the codec with the specified identifier, if it is known
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Codec::None),
+            1 => Some(Codec::Lz4),
+            2 => Some(Codec::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Compress the data using the level (where supported by the codec)
+    pub(crate) fn compress(&self, data: &[u8], level: u32) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Lz4 => Ok(lz4_flex::compress(data)),
+            Codec::Deflate => {
+                let mut encoder = DeflateEncoder::new(
+                    Vec::with_capacity(data.len() / 2),
+                    Compression::new(level.min(9)),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decompress the data, which is expected to decompress to `length` bytes
+    pub(crate) fn decompress(&self, data: &[u8], length: usize) -> Result<Vec<u8>, MCDError> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Lz4 => Ok(lz4_flex::decompress(data, length)?),
+            Codec::Deflate => {
+                let mut decompressed = Vec::with_capacity(length);
+                DeflateDecoder::new(data).read_to_end(&mut decompressed)?;
+
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+/// How the intensities of a channel chunk are laid out before compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    /// Little endian f32 intensities
+    Float,
+    /// Little endian i32 differences between consecutive intensities, which must all be integers. Used for the
+    /// position (X, Y, Z) channels, which are ramps of integers that compress poorly as f32.
+    Delta,
+}
+
+impl Encoding {
+    /// Identifier of the encoding stored in the .dcm file
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Encoding::Float => 0,
+            Encoding::Delta => 1,
+        }
+    }
+
+    /// Returns the encoding with the specified identifier, if it is known
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Encoding::Float),
+            1 => Some(Encoding::Delta),
+            _ => None,
+        }
+    }
+}
+
+/// Delta encode the intensities, or None if any intensity can't be stored exactly as an integer
+pub(crate) fn delta_encode(intensities: &[f32]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(intensities.len() * 4);
+    let mut previous = 0i32;
+
+    for &intensity in intensities {
+        let value = intensity as i32;
+
+        // Checked bitwise, so that e.g. -0.0 and NaN are stored as floats and read back unchanged
+        if (value as f32).to_bits() != intensity.to_bits() {
+            return None;
+        }
+
+        data.extend_from_slice(&value.wrapping_sub(previous).to_le_bytes());
+        previous = value;
+    }
+
+    Some(data)
+}
+
+/// Convert delta encoded data back to little endian f32 intensities
+pub(crate) fn delta_decode(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut value = 0i32;
+
+    for delta in data.chunks_exact(4) {
+        value = value.wrapping_add(i32::from_le_bytes([delta[0], delta[1], delta[2], delta[3]]));
+        decoded.extend_from_slice(&(value as f32).to_le_bytes());
+    }
+
+    decoded
+}
+
+/// Options controlling how a .dcm file is created (see `convert_with_options()`)
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    codec: Codec,
+    level: Option<u32>,
+    delta_coordinates: bool,
+    chunk_size: u32,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            codec: Codec::default(),
+            level: None,
+            delta_coordinates: true,
+            chunk_size: 256,
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Create options using the default codec (LZ4) and chunks of 256 x 256 pixels, with delta encoded position
+    /// channels
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the codec used to compress each chunk
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Set the compression level, from 0 (fastest) to 9 (smallest). Only `Codec::Deflate` supports levels
+    /// (defaulting to 6); the level is ignored by the other codecs.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Set whether the position (X, Y, Z) channels are delta encoded as integers before compression (default
+    /// true), which makes them considerably smaller and faster to read
+    pub fn delta_coordinates(mut self, enabled: bool) -> Self {
+        self.delta_coordinates = enabled;
+        self
+    }
+
+    /// Set the width and height (in pixels) of each chunk (default 256, minimum 1). Smaller chunks make reading
+    /// small regions faster, while larger chunks compress better and make reading whole images faster.
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the codec used to compress each chunk
+    pub fn chunk_codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Returns the compression level, or the default level of the codec if none was set
+    pub fn compression_level(&self) -> u32 {
+        self.level.unwrap_or(6)
+    }
+
+    /// Returns whether the position (X, Y, Z) channels are delta encoded
+    pub fn coordinates_delta_encoded(&self) -> bool {
+        self.delta_coordinates
+    }
+
+    /// Returns the width and height (in pixels) of each chunk
+    pub fn pixel_chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_round_trip() -> Result<(), MCDError> {
+        let data: Vec<u8> = (0..1024).map(|value| (value % 7) as u8).collect();
+
+        for codec in [Codec::None, Codec::Lz4, Codec::Deflate] {
+            assert_eq!(Codec::from_id(codec.id()), Some(codec));
+
+            let compressed = codec.compress(&data, 9)?;
+            assert_eq!(codec.decompress(&compressed, data.len())?, data);
+        }
+
+        assert_eq!(Codec::from_id(3), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn delta_encoding_round_trip() -> Result<(), MCDError> {
+        // X channel of a 256x256 chunk
+        let ramp: Vec<f32> = (0..256 * 256).map(|index| (index % 256) as f32).collect();
+        let float: Vec<u8> = ramp.iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        let delta = delta_encode(&ramp).expect("ramp is integers");
+        assert_eq!(delta_decode(&delta), float);
+
+        // The deltas compress to around half the size of the f32 ramp
+        let delta_size = Codec::Lz4.compress(&delta, 6)?.len();
+        let float_size = Codec::Lz4.compress(&float, 6)?.len();
+        assert!(delta_size * 3 < float_size * 2);
+
+        assert!(delta_encode(&[1.0, 2.5]).is_none());
+        assert!(delta_encode(&[-0.0]).is_none());
+        assert!(delta_encode(&[f32::NAN]).is_none());
+
+        Ok(())
+    }
+}
@@ -1,14 +1,38 @@
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
-    sync::{Arc, Mutex},
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+    time::UNIX_EPOCH,
 };
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use rayon::prelude::{ParallelDrainRange, ParallelIterator};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
-use crate::{error::MCDError, Acquisition, Region, MCD};
+use crate::{
+    config, error::MCDError, reader::ReaderPool, trace, Acquisition, AcquisitionRef, Region, MCD,
+};
+
+mod bookmark;
+mod codec;
+mod progress;
+mod pyramid;
+mod verify;
+
+pub(crate) use self::bookmark::{read_bookmarks, write_bookmarks};
+pub use self::bookmark::{Bookmark, BookmarkChannel};
+use self::codec::Encoding;
+pub use self::codec::{Codec, ConvertOptions};
+pub use self::progress::{CancellationToken, Progress};
+#[cfg(feature = "fs")]
+use self::pyramid::read_pyramid;
+#[cfg(feature = "fs")]
+pub(crate) use self::pyramid::write_pyramid;
+use self::pyramid::{StoredTile, TileKey};
+pub use self::verify::{
+    repair, verify, verify_with, BadChunk, ChunkProblem, VerifyOptions, VerifyReport,
+};
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -20,9 +44,17 @@ struct AcquisitionOffset {
 
 // Format
 // -----------
-// chunk size (u8)
-// number of acquisitions (u8)
-// offsets for each acquisition ((u16, u64, u8))
+// magic (8 bytes)
+// format version (u16)
+// fingerprint of the .mcd file (u64)
+// number of acquisitions (u32)
+// offsets for each acquisition ((slide ID, panorama ID, acquisition ID, offset) as (u16, u16, u16, u64))
+
+/// Identifies a .dcm file
+const DCM_MAGIC: &[u8; 8] = b"IMCRSDCM";
+
+/// Version of the .dcm format, incremented whenever the layout changes so that older files are regenerated
+const DCM_VERSION: u16 = 6;
 
 #[derive(Debug, Clone)]
 struct AcquisitionDetails {
@@ -72,6 +104,24 @@ struct ChannelChunk {
     num_intensities: u64,
     offset: u64,
     length: u64,
+    /// CRC32 of the compressed data
+    checksum: u32,
+    codec: Codec,
+    encoding: Encoding,
+}
+
+impl ChannelChunk {
+    /// Decompress and decode the (compressed) data of the chunk into little endian f32 intensities
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, MCDError> {
+        let decompressed = self
+            .codec
+            .decompress(data, self.num_intensities as usize * 4)?;
+
+        Ok(match self.encoding {
+            Encoding::Float => decompressed,
+            Encoding::Delta => codec::delta_decode(&decompressed),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -88,12 +138,40 @@ impl PixelChunk {
 }
 
 /// Function to convert an .mcd file to a .dcm file.
-pub fn convert<R: Read + Seek, W: Write + Seek>(
+pub fn convert<R: Read + Seek, W: Write + Seek>(mcd: &MCD<R>, dcm_file: W) -> Result<(), MCDError> {
+    convert_with_progress(mcd, dcm_file, &(), &CancellationToken::new())
+}
+
+/// Convert an .mcd file to a .dcm file using the default options, reporting progress (see
+/// `convert_with_options()`)
+pub fn convert_with_progress<R: Read + Seek, W: Write + Seek>(
+    mcd: &MCD<R>,
+    dcm_file: W,
+    progress: &dyn Progress,
+    cancel: &CancellationToken,
+) -> Result<(), MCDError> {
+    convert_with_options(mcd, dcm_file, &ConvertOptions::default(), progress, cancel)
+}
+
+/// Convert an .mcd file to a .dcm file, compressing chunks as specified by `options` and reporting progress
+/// per acquisition and per chunk. If `cancel` is cancelled then the conversion stops before the next row of
+/// chunks and `MCDError::Cancelled` is returned, leaving an incomplete .dcm file which should be discarded.
+///
+/// Each row of chunks is read from the .mcd in a single read, then transposed and compressed in parallel (see
+/// `config` for setting the number of threads).
+pub fn convert_with_options<R: Read + Seek, W: Write + Seek>(
     mcd: &MCD<R>,
     mut dcm_file: W,
+    options: &ConvertOptions,
+    progress: &dyn Progress,
+    cancel: &CancellationToken,
 ) -> Result<(), MCDError> {
-    //let mut acquisition_offsets = HashMap::new();
-    //println!("Opening {:?} for writing", mcd.dcm_file());
+    let mut span = trace::span(trace::Operation::ConvertDcm, || {
+        mcd.location
+            .as_ref()
+            .map(|location| location.display().to_string())
+            .unwrap_or_default()
+    });
 
     let mut num_acquisitions = 0;
 
@@ -103,130 +181,101 @@ pub fn convert<R: Read + Seek, W: Write + Seek>(
         }
     }
 
-    //println!("Writing {} acquisitions.", num_acquisitions);
+    span.record("acquisitions", num_acquisitions);
 
-    let chunk_size = 256;
+    let chunk_size = options.pixel_chunk_size();
 
     //dcm_file.write_u8(chunk_size as u8)?;
 
-    dcm_file.write_u8(num_acquisitions as u8)?;
+    dcm_file.write_all(DCM_MAGIC)?;
+    dcm_file.write_u16::<LittleEndian>(DCM_VERSION)?;
+    dcm_file.write_u64::<LittleEndian>(source_fingerprint(mcd.location.as_deref()))?;
+
+    let count = u32::try_from(num_acquisitions).map_err(|_| MCDError::TooManyAcquisitions {
+        count: num_acquisitions,
+    })?;
+    dcm_file.write_u32::<LittleEndian>(count)?;
     let index_location = dcm_file.seek(SeekFrom::Current(0))?;
-    dcm_file.write_all(&vec![0; num_acquisitions * 10])?;
+    dcm_file.write_all(&vec![0; num_acquisitions * 14])?;
 
-    let mut acquisition_index: Vec<(u16, u64)> = Vec::new();
+    let mut acquisition_index: Vec<(AcquisitionRef, u64)> = Vec::new();
 
     for slide in mcd.slides() {
         for panorama in slide.panoramas() {
             for acquisition in panorama.acquisitions() {
                 let mut acq_details = AcquisitionDetails::from(acquisition, chunk_size);
-
-                // println!(
-                //     "[{}] Total # chunks: ({}, {})",
-                //     acquisition.description(),
-                //     acq_details.num_chunks_x(),
-                //     acq_details.num_chunks_y()
-                // );
+                let num_chunks =
+                    acq_details.num_chunks_x() as usize * acq_details.num_chunks_y() as usize;
+
+                progress.acquisition_started(
+                    acquisition.id(),
+                    acquisition_index.len(),
+                    num_acquisitions,
+                );
+
+                let num_channels = acquisition.channels().len();
+                let delta_channels: Vec<bool> = acquisition
+                    .channels()
+                    .iter()
+                    .map(|channel| options.coordinates_delta_encoded() && channel.is_position())
+                    .collect();
 
                 for y_chunk in 0..acq_details.num_chunks_y() {
-                    for x_chunk in 0..acq_details.num_chunks_x() {
-                        let x_start = x_chunk * chunk_size;
-                        let x_stop = (x_start + chunk_size).min(acq_details.acquired_width());
-
-                        let y_start = y_chunk * chunk_size;
-                        let y_stop = (y_start + chunk_size).min(acq_details.acquired_height());
-
-                        let chunk_width = x_stop - x_start;
-                        let chunk_height = y_stop - y_start;
-
-                        //println!("[{}] ({}, {})", acquisition.description(), x_chunk, y_chunk);
-
-                        let mut channel_chunks = Vec::with_capacity(acquisition.channels().len());
-
-                        for _ in 0..acquisition.channels().len() {
-                            channel_chunks.push(Vec::with_capacity(
-                                chunk_width as usize * chunk_height as usize,
-                            ));
-                        }
-
-                        for y in y_start..y_stop {
-                            for x in x_start..x_stop {
-                                let spectrum = match acquisition.spectrum(x, y) {
-                                    Ok(spectrum) => spectrum,
-                                    Err(MCDError::InvalidIndex {
-                                        index: _,
-                                        num_spectra: _,
-                                    }) => {
-                                        break;
-                                    }
-                                    Err(error) => {
-                                        return Err(error);
-                                    }
-                                };
-
-                                for (channel_chunk, intensity) in
-                                    channel_chunks.iter_mut().zip(spectrum.iter())
-                                {
-                                    channel_chunk.push(*intensity);
-                                }
-                            }
-                        }
-
-                        let mut pixel_chunk = PixelChunk::new();
-
-                        let compressed_chunks = channel_chunks
-                            .par_drain(..)
-                            .map(|channel_chunk| {
-                                let num_intensities = channel_chunk.len();
-
-                                let mut buf: Vec<u8> = Vec::with_capacity(channel_chunk.len() * 4);
-
-                                for intensity in channel_chunk {
-                                    buf.write_f32::<LittleEndian>(intensity)?;
-                                }
+                    if cancel.is_cancelled() {
+                        return Err(MCDError::Cancelled);
+                    }
 
-                                Ok((num_intensities, lz4_flex::compress(&buf)))
+                    // Read the spectra for the whole row of chunks at once, then transpose and compress each
+                    // channel of each chunk in parallel
+                    let band = read_band(acquisition, &acq_details, y_chunk)?;
+
+                    let compressed_chunks = config::install(|| {
+                        (0..acq_details.num_chunks_x())
+                            .into_par_iter()
+                            .flat_map_iter(|x_chunk| {
+                                chunk_from_band(&band, &acq_details, num_channels, x_chunk, y_chunk)
+                                    .into_iter()
+                                    .enumerate()
                             })
-                            .collect::<Result<Vec<_>, MCDError>>()?;
-
-                        // for channel_chunk in channel_chunks {
-                        //     let num_intensities = channel_chunk.len();
-
-                        //     let mut buf: Vec<u8> = Vec::with_capacity(channel_chunk.len() * 4);
-
-                        //     for intensity in channel_chunk {
-                        //         buf.write_f32::<LittleEndian>(intensity)?;
-                        //     }
-
-                        //     let compressed = lz4_flex::compress(&buf);
+                            .map(|(channel, channel_chunk)| {
+                                compress_chunk(channel_chunk, options, delta_channels[channel])
+                            })
+                            .collect::<Result<Vec<_>, MCDError>>()
+                    })?;
 
-                        //     let cur_location = dcm_file.seek(SeekFrom::Current(0))?;
-                        //     dcm_file.write_all(&compressed)?;
-                        //     let new_location = dcm_file.seek(SeekFrom::Current(0))?;
+                    for x_chunk in 0..acq_details.num_chunks_x() as usize {
+                        let mut pixel_chunk = PixelChunk::new();
 
-                        //     pixel_chunk.channels.push(ChannelChunk {
-                        //         num_intensities: num_intensities as u64,
-                        //         offset: cur_location,
-                        //         length: new_location - cur_location,
-                        //     });
-                        // }
-                        for (num_intensities, compressed) in compressed_chunks {
+                        for (num_intensities, encoding, compressed) in compressed_chunks
+                            [x_chunk * num_channels..(x_chunk + 1) * num_channels]
+                            .iter()
+                        {
                             let cur_location = dcm_file.seek(SeekFrom::Current(0))?;
-                            dcm_file.write_all(&compressed)?;
+                            dcm_file.write_all(compressed)?;
                             let new_location = dcm_file.seek(SeekFrom::Current(0))?;
 
                             pixel_chunk.channels.push(ChannelChunk {
-                                num_intensities: num_intensities as u64,
+                                num_intensities: *num_intensities as u64,
                                 offset: cur_location,
                                 length: new_location - cur_location,
+                                checksum: crc32fast::hash(compressed),
+                                codec: options.chunk_codec(),
+                                encoding: *encoding,
                             });
                         }
 
                         acq_details.chunks.push(pixel_chunk);
+
+                        progress.chunk_completed(
+                            acquisition.id(),
+                            acq_details.chunks.len() - 1,
+                            num_chunks,
+                        );
                     }
                 }
 
                 let acquisition_index_location = dcm_file.seek(SeekFrom::Current(0))?;
-                acquisition_index.push((acquisition.id(), acquisition_index_location));
+                acquisition_index.push((acquisition.reference(), acquisition_index_location));
 
                 dcm_file.write_acquisition_details(&acq_details)?;
             }
@@ -238,31 +287,227 @@ pub fn convert<R: Read + Seek, W: Write + Seek>(
     // Go to location to write the index now we know where the data is stored
     dcm_file.seek(SeekFrom::Start(index_location))?;
 
-    for &(acquisition_id, offset) in &acquisition_index {
-        dcm_file.write_u16::<LittleEndian>(acquisition_id)?;
+    for &(reference, offset) in &acquisition_index {
+        dcm_file.write_acquisition_ref(reference)?;
         dcm_file.write_u64::<LittleEndian>(offset)?;
-
-        //  println!("Written: {}, {}", acquisition_id, offset);
     }
 
     dcm_file.flush()?;
+    span.record("bytes", dcm_file.seek(SeekFrom::End(0))?);
+
+    progress.finished();
 
     Ok(())
 }
 
+/// The spectra of a row of chunks, stored as read from the .mcd (spectrum by spectrum)
+struct Band {
+    first_spectrum: usize,
+    intensities: Vec<f32>,
+}
+
+/// Read the spectra covering the row of chunks `y_chunk` of the acquisition from the .mcd, in a single read
+fn read_band<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    details: &AcquisitionDetails,
+    y_chunk: u32,
+) -> Result<Band, MCDError> {
+    let y_start = y_chunk * details.chunk_size;
+    let y_stop = (y_start + details.chunk_size).min(details.acquired_height());
+
+    let first_spectrum =
+        (y_start as usize * details.width as usize).min(details.num_spectra as usize);
+    let last_spectrum =
+        (y_stop as usize * details.width as usize).min(details.num_spectra as usize);
+
+    Ok(Band {
+        first_spectrum,
+        intensities: acquisition.read_spectra(first_spectrum, last_spectrum - first_spectrum)?,
+    })
+}
+
+/// Transpose the spectra of the band into the intensities of each channel for the chunk (`x_chunk`, `y_chunk`)
+fn chunk_from_band(
+    band: &Band,
+    details: &AcquisitionDetails,
+    num_channels: usize,
+    x_chunk: u32,
+    y_chunk: u32,
+) -> Vec<Vec<f32>> {
+    let chunk_size = details.chunk_size;
+
+    let x_start = x_chunk * chunk_size;
+    let x_stop = (x_start + chunk_size).min(details.acquired_width());
+
+    let y_start = y_chunk * chunk_size;
+    let y_stop = (y_start + chunk_size).min(details.acquired_height());
+
+    let chunk_width = x_stop.saturating_sub(x_start);
+    let chunk_height = y_stop.saturating_sub(y_start);
+
+    let mut channel_chunks =
+        vec![Vec::with_capacity(chunk_width as usize * chunk_height as usize); num_channels];
+
+    for y in y_start..y_stop {
+        for x in x_start..x_stop {
+            let index = y as usize * details.width as usize + x as usize;
+
+            // Pixels beyond the last acquired spectrum (aborted acquisition) are not stored
+            if index >= details.num_spectra as usize {
+                break;
+            }
+
+            let offset = (index - band.first_spectrum) * num_channels;
+            let spectrum = &band.intensities[offset..offset + num_channels];
+
+            for (channel_chunk, intensity) in channel_chunks.iter_mut().zip(spectrum) {
+                channel_chunk.push(*intensity);
+            }
+        }
+    }
+
+    channel_chunks
+}
+
+/// Read the intensities of each channel for the chunk (`x_chunk`, `y_chunk`) of the acquisition from the .mcd
+fn read_chunk<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    details: &AcquisitionDetails,
+    x_chunk: u32,
+    y_chunk: u32,
+) -> Result<Vec<Vec<f32>>, MCDError> {
+    let band = read_band(acquisition, details, y_chunk)?;
+
+    Ok(chunk_from_band(
+        &band,
+        details,
+        acquisition.channels().len(),
+        x_chunk,
+        y_chunk,
+    ))
+}
+
+/// Compress the intensities of a single channel chunk, returning the number of intensities, how they were
+/// encoded and the compressed data. If `delta` is true then the intensities are delta encoded, unless any of
+/// them is not an integer.
+fn compress_chunk(
+    channel_chunk: Vec<f32>,
+    options: &ConvertOptions,
+    delta: bool,
+) -> Result<(usize, Encoding, Vec<u8>), MCDError> {
+    let num_intensities = channel_chunk.len();
+
+    let (encoding, buf) = match codec::delta_encode(&channel_chunk).filter(|_| delta) {
+        Some(buf) => (Encoding::Delta, buf),
+        None => {
+            let mut buf: Vec<u8> = Vec::with_capacity(channel_chunk.len() * 4);
+
+            for intensity in channel_chunk {
+                buf.write_f32::<LittleEndian>(intensity)?;
+            }
+
+            (Encoding::Float, buf)
+        }
+    };
+
+    Ok((
+        num_intensities,
+        encoding,
+        options
+            .chunk_codec()
+            .compress(&buf, options.compression_level())?,
+    ))
+}
+
+/// Fingerprint of the .mcd file, derived from its size and modification time, which is stored in the .dcm file
+/// so that the .dcm file is regenerated if the .mcd file changes. Returns 0 if the .mcd file has no location.
+fn source_fingerprint(mcd_path: Option<&Path>) -> u64 {
+    let metadata = match mcd_path.and_then(|path| std::fs::metadata(path).ok()) {
+        Some(metadata) => metadata,
+        None => return 0,
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    // FNV-1a, which (unlike `DefaultHasher`) is stable between Rust versions
+    let mut bytes = Vec::with_capacity(20);
+    bytes.extend_from_slice(&metadata.len().to_le_bytes());
+    bytes.extend_from_slice(&modified.as_secs().to_le_bytes());
+    bytes.extend_from_slice(&modified.subsec_nanos().to_le_bytes());
+
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Read the header from the start of a .dcm file, checking the magic and format version, and returning the
+/// fingerprint of the .mcd file it was generated from
+fn read_header<T: Read>(dcm_file: &mut T) -> Result<u64, MCDError> {
+    let mut magic = [0; 8];
+    dcm_file.read_exact(&mut magic)?;
+
+    if &magic != DCM_MAGIC {
+        return Err(MCDError::InvalidDCM {
+            reason: "missing header".to_string(),
+        });
+    }
+
+    let version = dcm_file.read_u16::<LittleEndian>()?;
+    if version != DCM_VERSION {
+        return Err(MCDError::DcmVersionMismatch {
+            found: version,
+            expected: DCM_VERSION,
+        });
+    }
+
+    Ok(dcm_file.read_u64::<LittleEndian>()?)
+}
+
+/// Read the index of acquisitions (reference, offset of the `AcquisitionDetails`) following the header of a .dcm
+/// file. Acquisitions are identified by their slide and panorama as well as their ID, as IDs are only unique per
+/// slide in some .mcd files.
+fn read_index<T: Read>(dcm_file: &mut T) -> std::io::Result<HashMap<AcquisitionRef, u64>> {
+    let num_acquisitions = dcm_file.read_u32::<LittleEndian>()?;
+
+    let mut acquisition_offsets = HashMap::with_capacity(num_acquisitions as usize);
+
+    for _i in 0..num_acquisitions {
+        let reference = dcm_file.read_acquisition_ref()?;
+        let offset = dcm_file.read_u64::<LittleEndian>()?;
+
+        acquisition_offsets.insert(reference, offset);
+    }
+
+    Ok(acquisition_offsets)
+}
+
 trait ReadDCM {
+    fn read_acquisition_ref(&mut self) -> std::io::Result<AcquisitionRef>;
     fn read_acquisition_details(&mut self) -> std::io::Result<AcquisitionDetails>;
     fn read_pixel_chunk(&mut self) -> std::io::Result<PixelChunk>;
     fn read_channel_chunk(&mut self) -> std::io::Result<ChannelChunk>;
 }
 
 trait WriteDCM {
+    fn write_acquisition_ref(&mut self, reference: AcquisitionRef) -> std::io::Result<()>;
     fn write_acquisition_details(&mut self, details: &AcquisitionDetails) -> std::io::Result<()>;
     fn write_pixel_chunk(&mut self, chunk: &PixelChunk) -> std::io::Result<()>;
     fn write_channel_chunk(&mut self, chunk: &ChannelChunk) -> std::io::Result<()>;
 }
 
 impl<T: ReadBytesExt> ReadDCM for T {
+    fn read_acquisition_ref(&mut self) -> std::io::Result<AcquisitionRef> {
+        let slide = self.read_u16::<LittleEndian>()?;
+        let panorama = self.read_u16::<LittleEndian>()?;
+        let id = self.read_u16::<LittleEndian>()?;
+
+        Ok(AcquisitionRef::new(slide, panorama, id))
+    }
+
     fn read_acquisition_details(&mut self) -> std::io::Result<AcquisitionDetails> {
         let width = self.read_u32::<LittleEndian>()?;
         let height = self.read_u32::<LittleEndian>()?;
@@ -301,16 +546,40 @@ impl<T: ReadBytesExt> ReadDCM for T {
         let num_intensities = self.read_u64::<LittleEndian>()?;
         let offset = self.read_u64::<LittleEndian>()?;
         let length = self.read_u64::<LittleEndian>()?;
+        let checksum = self.read_u32::<LittleEndian>()?;
+        let codec_id = self.read_u8()?;
+        let codec = Codec::from_id(codec_id).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown codec {}", codec_id),
+            )
+        })?;
+        let encoding_id = self.read_u8()?;
+        let encoding = Encoding::from_id(encoding_id).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown encoding {}", encoding_id),
+            )
+        })?;
 
         Ok(ChannelChunk {
             num_intensities,
             offset,
             length,
+            checksum,
+            codec,
+            encoding,
         })
     }
 }
 
 impl<T: WriteBytesExt> WriteDCM for T {
+    fn write_acquisition_ref(&mut self, reference: AcquisitionRef) -> std::io::Result<()> {
+        self.write_u16::<LittleEndian>(reference.slide())?;
+        self.write_u16::<LittleEndian>(reference.panorama())?;
+        self.write_u16::<LittleEndian>(reference.id())
+    }
+
     fn write_acquisition_details(&mut self, details: &AcquisitionDetails) -> std::io::Result<()> {
         self.write_u32::<LittleEndian>(details.width)?;
         self.write_u32::<LittleEndian>(details.height)?;
@@ -339,47 +608,79 @@ impl<T: WriteBytesExt> WriteDCM for T {
         self.write_u64::<LittleEndian>(chunk.num_intensities)?;
         self.write_u64::<LittleEndian>(chunk.offset)?;
         self.write_u64::<LittleEndian>(chunk.length)?;
+        self.write_u32::<LittleEndian>(chunk.checksum)?;
+        self.write_u8(chunk.codec.id())?;
+        self.write_u8(chunk.encoding.id())?;
 
         Ok(())
     }
 }
 
-/// Open an .mcd file
+/// Open the .dcm file of an .mcd file (see `open_with_options()`)
+#[cfg(feature = "fs")]
 pub fn open(mcd: &mut MCD<File>) -> Result<(), MCDError> {
-    //println!("Opening {:?} for reading", mcd.dcm_file());
-    let dcm_file = std::fs::File::open(mcd.dcm_file().ok_or(MCDError::LocationNotSpecified)?)?;
-    let dcm_file_arc = Arc::new(Mutex::new(BufReader::new(dcm_file)));
-    let mut dcm_file = dcm_file_arc.lock().unwrap();
-
-    let num_acquisitions = dcm_file.read_u8()?;
+    open_with_progress(mcd, &(), &CancellationToken::new())
+}
 
-    let mut acquisition_offsets = HashMap::with_capacity(num_acquisitions as usize);
+/// Open the .dcm file of an .mcd file, generating it with the default options if needed (see
+/// `open_with_options()`)
+#[cfg(feature = "fs")]
+pub fn open_with_progress(
+    mcd: &mut MCD<File>,
+    progress: &dyn Progress,
+    cancel: &CancellationToken,
+) -> Result<(), MCDError> {
+    open_with_options(mcd, &ConvertOptions::default(), progress, cancel)
+}
 
-    for _i in 0..num_acquisitions {
-        let id = dcm_file.read_u16::<LittleEndian>().expect("read id failed");
-        let offset = dcm_file
-            .read_u64::<LittleEndian>()
-            .expect("read offset failed");
-        //let num_channels = dcm_file.read_u8().unwrap();
+/// Open the .dcm file of an .mcd file, so that channel images are read from it. The .dcm file is (re)generated
+/// using `options` if it is missing or fails validation: the header is from a different format version, the .mcd file has
+/// changed since it was created (size or modification time), acquisitions are missing, or chunks lie beyond
+/// the end of the file. Any other error reading the .dcm file (e.g. permission denied) is returned rather than
+/// regenerating the file. Bookmarks are kept when the file is regenerated. Progress of the conversion is
+/// reported to `progress`, and if it is cancelled the incomplete .dcm file is removed.
+///
+/// Chunk checksums are not checked when opening, as this would require reading the whole file, but each
+/// chunk is checked when it is read (returning `MCDError::ChecksumMismatch`) and by `verify()`. An existing
+/// file created with a different codec is not regenerated, as the codec is stored with each chunk.
+#[cfg(feature = "fs")]
+pub fn open_with_options(
+    mcd: &mut MCD<File>,
+    options: &ConvertOptions,
+    progress: &dyn Progress,
+    cancel: &CancellationToken,
+) -> Result<(), MCDError> {
+    //println!("Opening {:?} for reading", mcd.dcm_file());
+    let dcm_path = mcd.dcm_file().ok_or(MCDError::LocationNotSpecified)?;
+
+    let mut acquisition_details = match read_all_details(&dcm_path, mcd) {
+        Ok(acquisition_details) => acquisition_details,
+        Err(error) if needs_regeneration(&error) => {
+            mcd.check_writable("generating the .dcm file")?;
+            regenerate(&dcm_path, mcd, options, progress, cancel)?;
+            read_all_details(&dcm_path, mcd)?
+        }
+        Err(error) => return Err(error),
+    };
 
-        acquisition_offsets.insert(id, offset);
-    }
+    let mut dcm_file = std::fs::File::open(&dcm_path)?;
+    // A pyramid which can't be read is ignored, as tiles can still be computed from the chunks
+    let mut pyramid = read_pyramid(&mut BufReader::new(&mut dcm_file)).unwrap_or_default();
 
-    // println!("Offsets: {:?}", acquisition_offsets);
+    let dcm_file_arc = Arc::new(ReaderPool::with_opener(dcm_file, move || {
+        std::fs::File::open(&dcm_path)
+    }));
 
     for slide in mcd.slides_mut().values_mut() {
         for panorama in slide.panoramas_mut().values_mut() {
             for acquisition in panorama.acquisitions_mut().values_mut() {
-                let offset = acquisition_offsets.get(&acquisition.id());
-
-                if let Some(&offset) = offset {
-                    dcm_file.seek(SeekFrom::Start(offset))?;
-
-                    let acquisition_details = dcm_file.read_acquisition_details()?;
+                let reference = acquisition.reference();
 
+                if let Some(details) = acquisition_details.remove(&reference) {
                     acquisition.dcm_location = Some(DCMLocation {
                         reader: dcm_file_arc.clone(),
-                        details: acquisition_details,
+                        details,
+                        pyramid: pyramid.remove(&reference).unwrap_or_default(),
                     });
                 }
             }
@@ -389,29 +690,225 @@ pub fn open(mcd: &mut MCD<File>) -> Result<(), MCDError> {
     Ok(())
 }
 
+/// Returns true if the error from reading the .dcm file shows that it is missing, incomplete or invalid, and so
+/// should be regenerated. Other errors (e.g. permission denied, or a transient failure of a network drive) are
+/// returned to the caller, so that a valid .dcm file (and its bookmarks) is not discarded.
+#[cfg(feature = "fs")]
+fn needs_regeneration(error: &MCDError) -> bool {
+    match error {
+        MCDError::InvalidDCM { .. }
+        | MCDError::DcmVersionMismatch { .. }
+        | MCDError::ChecksumMismatch { .. } => true,
+        MCDError::Io { source } => matches!(
+            source.kind(),
+            std::io::ErrorKind::NotFound
+                | std::io::ErrorKind::UnexpectedEof
+                | std::io::ErrorKind::InvalidData
+        ),
+        _ => false,
+    }
+}
+
+/// Read the details of every acquisition from the .dcm file, validating it against the .mcd file
+#[cfg(feature = "fs")]
+fn read_all_details<R: Read + Seek>(
+    dcm_path: &Path,
+    mcd: &MCD<R>,
+) -> Result<HashMap<AcquisitionRef, AcquisitionDetails>, MCDError> {
+    let mut dcm_file = BufReader::new(File::open(dcm_path)?);
+    let file_length = dcm_file.seek(SeekFrom::End(0))?;
+    dcm_file.seek(SeekFrom::Start(0))?;
+
+    if read_header(&mut dcm_file)? != source_fingerprint(mcd.location.as_deref()) {
+        return Err(MCDError::InvalidDCM {
+            reason: "the .mcd file has changed".to_string(),
+        });
+    }
+
+    let acquisition_offsets = read_index(&mut dcm_file)?;
+    let mut acquisition_details = HashMap::with_capacity(acquisition_offsets.len());
+
+    // println!("Offsets: {:?}", acquisition_offsets);
+
+    for acquisition in mcd.acquisitions() {
+        let reference = acquisition.reference();
+        let offset = acquisition_offsets
+            .get(&reference)
+            .ok_or_else(|| MCDError::InvalidDCM {
+                reason: format!("acquisition ({}) is missing", reference),
+            })?;
+
+        dcm_file.seek(SeekFrom::Start(*offset))?;
+        let details = dcm_file.read_acquisition_details()?;
+
+        let truncated = details
+            .chunks
+            .iter()
+            .flat_map(|chunk| chunk.channels.iter())
+            .any(|channel| channel.offset + channel.length > file_length);
+        if truncated {
+            return Err(MCDError::InvalidDCM {
+                reason: format!("acquisition ({}) is truncated", reference),
+            });
+        }
+
+        acquisition_details.insert(reference, details);
+    }
+
+    Ok(acquisition_details)
+}
+
+/// Regenerate the .dcm file from the .mcd file, keeping any bookmarks which can still be read. The incomplete
+/// file is removed if the conversion fails or is cancelled.
+fn regenerate<R: Read + Seek>(
+    dcm_path: &Path,
+    mcd: &MCD<R>,
+    options: &ConvertOptions,
+    progress: &dyn Progress,
+    cancel: &CancellationToken,
+) -> Result<(), MCDError> {
+    let bookmarks = File::open(dcm_path)
+        .map_err(MCDError::from)
+        .and_then(|dcm_file| read_bookmarks(&mut BufReader::new(dcm_file)))
+        .unwrap_or_default();
+
+    let result = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dcm_path)
+        .map_err(MCDError::from)
+        .and_then(|mut dcm_file| {
+            convert_with_options(
+                mcd,
+                BufWriter::new(&mut dcm_file),
+                options,
+                progress,
+                cancel,
+            )?;
+
+            if !bookmarks.is_empty() {
+                write_bookmarks(&mut dcm_file, &bookmarks)?;
+            }
+
+            dcm_file.sync_all()?;
+
+            Ok(())
+        });
+
+    if result.is_err() {
+        // Don't leave an incomplete file behind, as it would otherwise be opened next time
+        let _ = std::fs::remove_file(dcm_path);
+    }
+
+    result
+}
+
 /// DCMLocation describes where the acquisition is stored.
 #[derive(Debug, Clone)]
 pub struct DCMLocation {
-    reader: Arc<Mutex<BufReader<File>>>,
+    reader: Arc<ReaderPool<File>>,
     details: AcquisitionDetails,
+    pyramid: HashMap<TileKey, StoredTile>,
 }
 
 impl DCMLocation {
+    /// Returns the width and height (in pixels) of each chunk
+    pub(crate) fn chunk_size(&self) -> u32 {
+        self.details.chunk_size
+    }
+
     // pub fn read_channel(&self, channel: usize, region: &Region) -> Result<Vec<f32>, MCDError> {
     //     self.read_channels(&[channel], region)
     //         .map(|mut data| data.drain(..).last().unwrap())
     // }
 
+    /// Read the tile of the downsampled level of the channel from the pyramid, or None if the pyramid has not
+    /// been precomputed (see `MCD::precompute_pyramid()`)
+    pub(crate) fn read_tile(
+        &self,
+        channel: u16,
+        level: u32,
+        x: u32,
+        y: u32,
+    ) -> Result<Option<Vec<f32>>, MCDError> {
+        let key = TileKey {
+            channel,
+            level: level as u8,
+            x,
+            y,
+        };
+        let tile = match self.pyramid.get(&key) {
+            Some(tile) => tile,
+            None => return Ok(None),
+        };
+
+        let mut buf = vec![0; tile.length as usize];
+        let mut reader = self.reader.get()?;
+        reader.seek(SeekFrom::Start(tile.offset))?;
+        reader.read_exact(&mut buf)?;
+
+        if crc32fast::hash(&buf) != tile.checksum {
+            return Err(MCDError::ChecksumMismatch {
+                offset: tile.offset,
+            });
+        }
+
+        let decompressed = Codec::Lz4.decompress(&buf, tile.num_intensities as usize * 4)?;
+
+        Ok(Some(
+            decompressed
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect(),
+        ))
+    }
+
     /// Read in multiple channels at once. This can be faster than reading in single channels.
     pub fn read_channels(
         &self,
         channels: &[usize],
         region: &Region,
     ) -> Result<Vec<Vec<f32>>, MCDError> {
-        let mut data =
-            vec![vec![0.0; region.width as usize * region.height as usize]; channels.len()];
+        let mut data = vec![Vec::new(); channels.len()];
+        self.read_channels_into(channels, region, &mut data)?;
 
-        let mut reader = self.reader.lock().unwrap();
+        Ok(data)
+    }
+
+    /// Read in multiple channels at once (see [`DCMLocation::read_channels`]), into the supplied buffers (one per
+    /// channel). Each buffer is resized to the number of pixels in the region, reusing its existing allocation
+    /// where possible.
+    pub fn read_channels_into(
+        &self,
+        channels: &[usize],
+        region: &Region,
+        data: &mut [Vec<f32>],
+    ) -> Result<(), MCDError> {
+        if data.len() != channels.len() {
+            return Err(MCDError::InvalidBufferSize {
+                expected: channels.len(),
+                actual: data.len(),
+            });
+        }
+
+        let mut span = trace::span(trace::Operation::ReadChunks, || {
+            format!(
+                "{}x{} region at ({}, {})",
+                region.width, region.height, region.x, region.y
+            )
+        });
+        let mut num_chunks = 0;
+        let mut num_bytes = 0;
+
+        // Pixels which are not present in any chunk (aborted acquisition) are missing
+        for channel_data in data.iter_mut() {
+            channel_data.clear();
+            channel_data.resize(region.width as usize * region.height as usize, f32::NAN);
+        }
+
+        let mut reader = self.reader.get()?;
 
         let start_chunk_x = region.x / self.details.chunk_size;
         let end_chunk_x = ((region.x + region.width) / self.details.chunk_size + 1)
@@ -445,9 +942,16 @@ impl DCMLocation {
 
                     reader.seek(SeekFrom::Start(channel_chunk.offset))?;
                     reader.read_exact(&mut buf)?;
+                    num_chunks += 1;
+                    num_bytes += buf.len();
+
+                    if crc32fast::hash(&buf) != channel_chunk.checksum {
+                        return Err(MCDError::ChecksumMismatch {
+                            offset: channel_chunk.offset,
+                        });
+                    }
 
-                    let decompressed_data =
-                        lz4_flex::decompress(&buf, channel_chunk.num_intensities as usize * 4)?;
+                    let decompressed_data = channel_chunk.decode(&buf)?;
 
                     let mut decompressed_data = Cursor::new(decompressed_data);
 
@@ -495,6 +999,174 @@ impl DCMLocation {
             }
         }
 
-        Ok(data)
+        span.record("channels", channels.len());
+        span.record("chunks", num_chunks);
+        span.record("bytes", num_bytes);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transpose_band() {
+        // 3x2 acquisition with 2 channels, aborted after 5 spectra, in chunks of 2x2 pixels
+        let details = AcquisitionDetails {
+            width: 3,
+            height: 2,
+            num_spectra: 5,
+            chunk_size: 2,
+            chunks: Vec::new(),
+        };
+        let band = Band {
+            first_spectrum: 0,
+            intensities: (0..10).map(|intensity| intensity as f32).collect(),
+        };
+
+        let chunk = chunk_from_band(&band, &details, 2, 0, 0);
+        assert_eq!(chunk[0], vec![0.0, 2.0, 6.0, 8.0]);
+        assert_eq!(chunk[1], vec![1.0, 3.0, 7.0, 9.0]);
+
+        // The last spectrum of the second row was not acquired
+        let chunk = chunk_from_band(&band, &details, 2, 1, 0);
+        assert_eq!(chunk[0], vec![4.0]);
+    }
+
+    #[test]
+    fn index_beyond_u8() -> Result<(), MCDError> {
+        let mut index = Vec::new();
+        index.write_u32::<LittleEndian>(300)?;
+        for id in 1..=300 {
+            index.write_acquisition_ref(AcquisitionRef::new(1, 1, id))?;
+            index.write_u64::<LittleEndian>(id as u64)?;
+        }
+
+        let offsets = read_index(&mut Cursor::new(index))?;
+        assert_eq!(offsets.len(), 300);
+        assert_eq!(offsets[&AcquisitionRef::new(1, 1, 300)], 300);
+
+        Ok(())
+    }
+
+    #[test]
+    fn header_validation() -> Result<(), MCDError> {
+        let mut header = DCM_MAGIC.to_vec();
+        header.write_u16::<LittleEndian>(DCM_VERSION)?;
+        header.write_u64::<LittleEndian>(42)?;
+
+        assert_eq!(read_header(&mut Cursor::new(&header))?, 42);
+
+        // Written by a different version of the format
+        header[8] += 1;
+        assert!(matches!(
+            read_header(&mut Cursor::new(&header)),
+            Err(MCDError::DcmVersionMismatch { found, expected }) if found == DCM_VERSION + 1 && expected == DCM_VERSION
+        ));
+
+        // Written before the header was introduced, starting with the number of acquisitions
+        assert!(read_header(&mut Cursor::new(vec![1; 18])).is_err());
+
+        assert_eq!(source_fingerprint(None), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn keys_acquisitions_by_slide() -> Result<(), MCDError> {
+        use crate::{AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+
+        let dir = std::env::temp_dir().join(format!("imc-rs-duplicate-ids-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("duplicate.mcd");
+
+        // Acquisitions of different sizes on two slides, the second of which is renumbered to share the ID
+        let mut writer = MCDWriter::new(File::create(&path)?);
+        for id in 1..=2 {
+            writer.add_slide(SlideSpec::new(id))?;
+            writer.add_panorama(PanoramaSpec::new(id, id).bounds(0.0, 0.0, 100.0, 100.0))?;
+        }
+        writer.add_acquisition(
+            AcquisitionSpec::new(1, 1, 2, 1).channel("Ir(191)", "DNA1"),
+            &[1.0, 2.0],
+        )?;
+        writer.add_acquisition(
+            AcquisitionSpec::new(2, 2, 3, 1).channel("Ir(191)", "DNA1"),
+            &[10.0, 20.0, 30.0],
+        )?;
+        writer.finish()?;
+
+        let mut mcd = MCD::from_path(&path)?;
+        for slide in mcd.slides_mut().values_mut() {
+            for panorama in slide.panoramas_mut().values_mut() {
+                let acquisitions = panorama.acquisitions_mut();
+                *acquisitions = acquisitions
+                    .drain()
+                    .map(|(_, mut acquisition)| {
+                        acquisition.set_id(1);
+                        (1, acquisition)
+                    })
+                    .collect();
+            }
+        }
+        mcd.acquisition_order = Default::default();
+
+        // Opened twice, to read both the newly generated and the existing .dcm file
+        for _ in 0..2 {
+            open_with_options(
+                &mut mcd,
+                &ConvertOptions::default(),
+                &(),
+                &CancellationToken::new(),
+            )?;
+
+            for (reference, expected) in [
+                (AcquisitionRef::new(1, 1, 1), vec![1.0, 2.0]),
+                (AcquisitionRef::new(2, 2, 1), vec![10.0, 20.0, 30.0]),
+            ] {
+                let acquisition = mcd.find_acquisition(reference)?;
+                assert!(acquisition.dcm_location.is_some(), "{}", reference);
+                assert_eq!(
+                    acquisition
+                        .channel_image(crate::ChannelIdentifier::name("Ir(191)"), None)?
+                        .data,
+                    expected
+                );
+            }
+        }
+
+        let reference = AcquisitionRef::new(2, 2, 1);
+        mcd.add_bookmark(Bookmark::new("Second slide", reference))?;
+        assert_eq!(mcd.bookmarks()?[0].acquisition(), reference);
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn regenerates_only_invalid_files() {
+        let io_error = |kind| MCDError::from(std::io::Error::from(kind));
+
+        assert!(needs_regeneration(&io_error(std::io::ErrorKind::NotFound)));
+        assert!(needs_regeneration(&io_error(
+            std::io::ErrorKind::UnexpectedEof
+        )));
+        assert!(needs_regeneration(&MCDError::DcmVersionMismatch {
+            found: DCM_VERSION - 1,
+            expected: DCM_VERSION
+        }));
+
+        assert!(!needs_regeneration(&io_error(
+            std::io::ErrorKind::PermissionDenied
+        )));
+        assert!(!needs_regeneration(&io_error(
+            std::io::ErrorKind::Interrupted
+        )));
+        assert!(!needs_regeneration(&MCDError::PoisonMutex));
     }
 }
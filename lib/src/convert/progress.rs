@@ -0,0 +1,46 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Receives progress updates while converting an .mcd file to a .dcm file (see `convert_with_progress()`).
+/// All methods have empty default implementations, so only the updates of interest need implementing.
+pub trait Progress {
+    /// Called before converting the acquisition with the specified ID, which is number `index` (starting
+    /// from 0) of `num_acquisitions`
+    fn acquisition_started(&self, _acquisition_id: u16, _index: usize, _num_acquisitions: usize) {}
+
+    /// Called after converting chunk number `chunk` (starting from 0) of `num_chunks` of the acquisition
+    fn chunk_completed(&self, _acquisition_id: u16, _chunk: usize, _num_chunks: usize) {}
+
+    /// Called once all acquisitions have been converted
+    fn finished(&self) {}
+}
+
+/// Ignore all progress updates
+impl Progress for () {}
+
+/// A token which can be used to cancel a conversion from another thread (e.g. in response to a user clicking
+/// cancel in a GUI). Clones of the token share the same state, so cancelling one cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token which has not been cancelled
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Request cancellation. The conversion stops before the next row of chunks, returning
+    /// `MCDError::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,317 @@
+#[cfg(feature = "fs")]
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+#[cfg(feature = "fs")]
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+#[cfg(feature = "fs")]
+use crate::{
+    error::MCDError,
+    tiles::{downsample, TileGrid},
+    AcquisitionRef, ChannelIdentifier, MCD,
+};
+
+#[cfg(feature = "fs")]
+use super::{bookmark::bookmarks_start, read_bookmarks, write_bookmarks, Codec, ReadDCM, WriteDCM};
+
+/// Marks the end of the pyramid stored before the bookmarks at the end of a .dcm file
+#[cfg(feature = "fs")]
+const PYRAMID_MAGIC: &[u8; 8] = b"IMCPYRM1";
+
+/// Size of the trailer following the pyramid index (length of the index followed by the magic)
+#[cfg(feature = "fs")]
+const TRAILER_SIZE: u64 = 16;
+
+/// Identifies a tile of the pyramid of an acquisition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct TileKey {
+    pub(crate) channel: u16,
+    pub(crate) level: u8,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+}
+
+/// Location of a (LZ4 compressed) tile in the .dcm file
+#[derive(Debug, Clone)]
+pub(crate) struct StoredTile {
+    pub(crate) num_intensities: u32,
+    pub(crate) offset: u64,
+    pub(crate) length: u64,
+    /// CRC32 of the compressed data
+    pub(crate) checksum: u32,
+}
+
+// Format (written before the bookmarks at the end of the .dcm file)
+// -----------
+// tiles (compressed f32 intensities)
+// offset of the first tile (u64)
+// number of tiles (u64)
+// tiles (slide ID, panorama ID, acquisition ID, channel, level, x, y, number of intensities, offset, length,
+//   checksum)
+// length of the index in bytes (u64)
+// magic (8 bytes)
+
+/// Read the index of the pyramid stored in the .dcm file, for each acquisition (identified by its slide,
+/// panorama and ID). Returns an empty index if no pyramid has been stored.
+#[cfg(feature = "fs")]
+pub(crate) fn read_pyramid<T: Read + Seek>(
+    dcm_file: &mut T,
+) -> Result<HashMap<AcquisitionRef, HashMap<TileKey, StoredTile>>, MCDError> {
+    let mut pyramid: HashMap<AcquisitionRef, HashMap<TileKey, StoredTile>> = HashMap::new();
+
+    let index_start = match pyramid_bounds(dcm_file)? {
+        Some((_, index_start)) => index_start,
+        None => return Ok(pyramid),
+    };
+
+    dcm_file.seek(SeekFrom::Start(index_start + 8))?;
+    let num_tiles = dcm_file.read_u64::<LittleEndian>()?;
+
+    for _ in 0..num_tiles {
+        let reference = dcm_file.read_acquisition_ref()?;
+        let key = TileKey {
+            channel: dcm_file.read_u16::<LittleEndian>()?,
+            level: dcm_file.read_u8()?,
+            x: dcm_file.read_u32::<LittleEndian>()?,
+            y: dcm_file.read_u32::<LittleEndian>()?,
+        };
+        let tile = StoredTile {
+            num_intensities: dcm_file.read_u32::<LittleEndian>()?,
+            offset: dcm_file.read_u64::<LittleEndian>()?,
+            length: dcm_file.read_u64::<LittleEndian>()?,
+            checksum: dcm_file.read_u32::<LittleEndian>()?,
+        };
+
+        pyramid.entry(reference).or_default().insert(key, tile);
+    }
+
+    Ok(pyramid)
+}
+
+/// Compute the downsampled levels of every channel of every acquisition and store them in the .dcm file,
+/// replacing any pyramid already stored. The bookmarks are kept.
+#[cfg(feature = "fs")]
+pub(crate) fn write_pyramid(dcm_path: &Path, mcd: &MCD<File>) -> Result<(), MCDError> {
+    // Every tile is built before the file is modified, so that a failure leaves the .dcm file as it was
+    let (tiles, index) = build_pyramid(mcd)?;
+
+    let mut dcm_file = OpenOptions::new().read(true).write(true).open(dcm_path)?;
+    let bookmarks = read_bookmarks(&mut dcm_file)?;
+
+    let start = match pyramid_bounds(&mut dcm_file)? {
+        Some((start, _)) => start,
+        None => match bookmarks_start(&mut dcm_file)? {
+            Some(start) => start,
+            None => dcm_file.seek(SeekFrom::End(0))?,
+        },
+    };
+
+    let written = write_tiles(&mut dcm_file, start, &tiles, &index).and_then(|_| {
+        if !bookmarks.is_empty() {
+            write_bookmarks(&mut dcm_file, &bookmarks)?;
+        }
+
+        Ok(())
+    });
+
+    if let Err(error) = written {
+        // Drop the partially written pyramid, but don't lose the bookmarks
+        dcm_file.set_len(start)?;
+        if !bookmarks.is_empty() {
+            write_bookmarks(&mut dcm_file, &bookmarks)?;
+        }
+
+        return Err(error);
+    }
+
+    dcm_file.sync_all()?;
+
+    Ok(())
+}
+
+/// A tile of the pyramid of an acquisition, with the offset relative to the first tile
+#[cfg(feature = "fs")]
+type PyramidEntry = (AcquisitionRef, TileKey, StoredTile);
+
+/// Compute the compressed tiles of the downsampled levels of every channel of every acquisition, along with
+/// the index of the tiles
+#[cfg(feature = "fs")]
+fn build_pyramid(mcd: &MCD<File>) -> Result<(Vec<u8>, Vec<PyramidEntry>), MCDError> {
+    let mut tiles = Vec::new();
+    let mut index = Vec::new();
+
+    for acquisition in mcd.acquisitions() {
+        let grid = acquisition.tile_grid();
+
+        for channel in acquisition.channels() {
+            let image = acquisition.channel_image(ChannelIdentifier::name(channel.name()), None)?;
+
+            for level in 1..grid.num_levels() {
+                let (level_width, _) = grid.level_size(level).unwrap_or_default();
+                let (num_x, num_y) = grid.num_tiles(level).unwrap_or_default();
+
+                let downsampled = downsample(
+                    &image.data,
+                    image.width(),
+                    image.height(),
+                    TileGrid::downsampling(level),
+                );
+
+                for y in 0..num_y {
+                    for x in 0..num_x {
+                        let region = match grid.tile_region(level, x, y) {
+                            Some(region) => region,
+                            None => continue,
+                        };
+
+                        let mut buffer =
+                            Vec::with_capacity(region.width as usize * region.height as usize * 4);
+                        for row in region.y..region.y + region.height {
+                            let row_start = (row * level_width + region.x) as usize;
+
+                            for value in &downsampled[row_start..row_start + region.width as usize]
+                            {
+                                buffer.write_f32::<LittleEndian>(*value)?;
+                            }
+                        }
+
+                        let compressed = Codec::Lz4.compress(&buffer, 0)?;
+
+                        index.push((
+                            acquisition.reference(),
+                            TileKey {
+                                channel: channel.order_number() as u16,
+                                level: level as u8,
+                                x,
+                                y,
+                            },
+                            StoredTile {
+                                num_intensities: region.width * region.height,
+                                offset: tiles.len() as u64,
+                                length: compressed.len() as u64,
+                                checksum: crc32fast::hash(&compressed),
+                            },
+                        ));
+
+                        tiles.extend_from_slice(&compressed);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((tiles, index))
+}
+
+/// Replace everything from `start` onwards with the tiles and their index
+#[cfg(feature = "fs")]
+fn write_tiles(
+    dcm_file: &mut File,
+    start: u64,
+    tiles: &[u8],
+    index: &[PyramidEntry],
+) -> Result<(), MCDError> {
+    let mut index_buffer = Vec::new();
+    index_buffer.write_u64::<LittleEndian>(start)?;
+    index_buffer.write_u64::<LittleEndian>(index.len() as u64)?;
+
+    for (reference, key, tile) in index {
+        index_buffer.write_acquisition_ref(*reference)?;
+        index_buffer.write_u16::<LittleEndian>(key.channel)?;
+        index_buffer.write_u8(key.level)?;
+        index_buffer.write_u32::<LittleEndian>(key.x)?;
+        index_buffer.write_u32::<LittleEndian>(key.y)?;
+        index_buffer.write_u32::<LittleEndian>(tile.num_intensities)?;
+        index_buffer.write_u64::<LittleEndian>(start + tile.offset)?;
+        index_buffer.write_u64::<LittleEndian>(tile.length)?;
+        index_buffer.write_u32::<LittleEndian>(tile.checksum)?;
+    }
+
+    dcm_file.set_len(start)?;
+    dcm_file.seek(SeekFrom::Start(start))?;
+
+    let mut writer = BufWriter::new(dcm_file);
+    writer.write_all(tiles)?;
+    writer.write_all(&index_buffer)?;
+    writer.write_u64::<LittleEndian>(index_buffer.len() as u64)?;
+    writer.write_all(PYRAMID_MAGIC)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the offset of the first tile and of the index if a pyramid is stored in the .dcm file. The pyramid
+/// ends where the bookmarks start (or at the end of the file if there are none).
+#[cfg(feature = "fs")]
+fn pyramid_bounds<T: Read + Seek>(dcm_file: &mut T) -> std::io::Result<Option<(u64, u64)>> {
+    let end = match bookmarks_start(dcm_file)? {
+        Some(end) => end,
+        None => dcm_file.seek(SeekFrom::End(0))?,
+    };
+    if end < TRAILER_SIZE {
+        return Ok(None);
+    }
+
+    dcm_file.seek(SeekFrom::Start(end - TRAILER_SIZE))?;
+    let index_length = dcm_file.read_u64::<LittleEndian>()?;
+    let mut magic = [0; 8];
+    dcm_file.read_exact(&mut magic)?;
+
+    if &magic != PYRAMID_MAGIC || index_length > end - TRAILER_SIZE {
+        return Ok(None);
+    }
+
+    let index_start = end - TRAILER_SIZE - index_length;
+    dcm_file.seek(SeekFrom::Start(index_start))?;
+    let start = dcm_file.read_u64::<LittleEndian>()?;
+
+    Ok(Some((start.min(index_start), index_start)))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(feature = "fs")]
+    fn failed_tiles_keep_dcm_file() -> crate::error::Result<()> {
+        use super::*;
+        use crate::{mcd::synthetic_mcd, AcquisitionSpec, Bookmark};
+
+        let dir = std::env::temp_dir().join(format!("imc-rs-pyramid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("pyramid.mcd");
+
+        std::fs::write(
+            &path,
+            synthetic_mcd([(
+                AcquisitionSpec::new(1, 1, 2, 1).channel("Ir(191)", "DNA1"),
+                [1.0, 2.0],
+            )])?,
+        )?;
+
+        let reference = AcquisitionRef::new(1, 1, 1);
+        MCD::from_path(&path)?
+            .with_dcm()?
+            .add_bookmark(Bookmark::new("All", reference))?;
+        let dcm_path = path.with_extension("dcm");
+        let before = std::fs::read(&dcm_path)?;
+
+        // Without the spectra, no tile can be computed
+        let mcd = MCD::from_path(&path)?;
+        File::options().write(true).open(&path)?.set_len(0)?;
+        assert!(write_pyramid(&dcm_path, &mcd).is_err());
+
+        assert_eq!(std::fs::read(&dcm_path)?, before);
+        let bookmarks = read_bookmarks(&mut File::open(&dcm_path)?)?;
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].acquisition(), reference);
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{error::MCDError, AcquisitionRef, MCD};
+
+use super::{
+    compress_chunk, read_bookmarks, read_chunk, read_header, read_index, regenerate,
+    write_bookmarks, AcquisitionDetails, CancellationToken, ConvertOptions, Encoding, ReadDCM,
+    WriteDCM,
+};
+
+/// Options controlling how thoroughly a .dcm file is verified
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    sample_every: usize,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions { sample_every: 16 }
+    }
+}
+
+impl VerifyOptions {
+    /// Create options using the default sampling (every 16th chunk is compared against the .mcd)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare every `n`th chunk (as well as the first and last chunk of each acquisition) against the data
+    /// re-derived from the .mcd file. A value of 0 disables comparison, so only the structure of each chunk
+    /// is checked.
+    pub fn sample_every(mut self, n: usize) -> Self {
+        self.sample_every = n;
+        self
+    }
+
+    /// Compare every chunk against the .mcd file. This is as slow as regenerating the .dcm file.
+    pub fn compare_all(self) -> Self {
+        self.sample_every(1)
+    }
+
+    fn is_sampled(&self, chunk: usize, num_chunks: usize) -> bool {
+        match self.sample_every {
+            0 => false,
+            n => chunk.is_multiple_of(n) || chunk + 1 == num_chunks,
+        }
+    }
+}
+
+/// Problem detected with a chunk of a .dcm file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkProblem {
+    /// The chunk could not be read (e.g. the file is truncated)
+    Unreadable,
+    /// The checksum of the chunk does not match, the chunk could not be decompressed, or it decompressed to the
+    /// wrong number of intensities
+    Corrupt,
+    /// The chunk decompressed, but the intensities differ from those in the .mcd file
+    Mismatch,
+}
+
+/// A single channel chunk of a .dcm file which failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadChunk {
+    acquisition: AcquisitionRef,
+    chunk: usize,
+    channel: usize,
+    problem: ChunkProblem,
+}
+
+impl BadChunk {
+    /// Returns the acquisition the chunk belongs to
+    pub fn acquisition(&self) -> AcquisitionRef {
+        self.acquisition
+    }
+
+    /// Returns the ID of the acquisition the chunk belongs to
+    pub fn acquisition_id(&self) -> u16 {
+        self.acquisition.id()
+    }
+
+    /// Returns the index of the chunk within the acquisition
+    pub fn chunk(&self) -> usize {
+        self.chunk
+    }
+
+    /// Returns the index of the channel within the chunk
+    pub fn channel(&self) -> usize {
+        self.channel
+    }
+
+    /// Returns the problem detected with the chunk
+    pub fn problem(&self) -> ChunkProblem {
+        self.problem
+    }
+}
+
+/// Result of verifying a .dcm file against the .mcd file it was generated from
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    num_checked: usize,
+    num_compared: usize,
+    bad_chunks: Vec<BadChunk>,
+    missing_acquisitions: Vec<AcquisitionRef>,
+}
+
+impl VerifyReport {
+    /// Returns true if no problems were detected
+    pub fn is_ok(&self) -> bool {
+        self.bad_chunks.is_empty() && self.missing_acquisitions.is_empty()
+    }
+
+    /// Returns the number of channel chunks which were checked (read and decompressed)
+    pub fn num_checked(&self) -> usize {
+        self.num_checked
+    }
+
+    /// Returns the number of channel chunks which were compared against the .mcd file
+    pub fn num_compared(&self) -> usize {
+        self.num_compared
+    }
+
+    /// Returns the channel chunks which failed verification
+    pub fn bad_chunks(&self) -> &[BadChunk] {
+        &self.bad_chunks
+    }
+
+    /// Returns the acquisitions in the .mcd file which are missing from the .dcm file, or whose description in
+    /// the .dcm file could not be read
+    pub fn missing_acquisitions(&self) -> &[AcquisitionRef] {
+        &self.missing_acquisitions
+    }
+}
+
+/// Verify the .dcm file with the default options (see `verify_with`)
+pub fn verify<P: AsRef<Path>, R: Read + Seek>(
+    dcm_path: P,
+    mcd: &MCD<R>,
+) -> Result<VerifyReport, MCDError> {
+    verify_with(dcm_path, mcd, &VerifyOptions::default())
+}
+
+/// Verify the .dcm file generated from `mcd`. Every channel chunk is read and decompressed, and a sample of
+/// chunks (see `VerifyOptions::sample_every`) are re-derived from the .mcd file and compared against the
+/// cached intensities. Any problems found can be fixed with `repair`.
+///
+/// An error is returned only if the header or index at the start of the .dcm file can't be read (e.g. it was
+/// written by a different version of the format), in which case the .dcm file should be regenerated with
+/// `convert`.
+pub fn verify_with<P: AsRef<Path>, R: Read + Seek>(
+    dcm_path: P,
+    mcd: &MCD<R>,
+    options: &VerifyOptions,
+) -> Result<VerifyReport, MCDError> {
+    let mut dcm_file = BufReader::new(File::open(dcm_path)?);
+    read_header(&mut dcm_file)?;
+    let acquisition_offsets = read_index(&mut dcm_file)?;
+
+    let mut report = VerifyReport::default();
+
+    for acquisition in mcd.acquisitions() {
+        let details = match acquisition_offsets.get(&acquisition.reference()) {
+            Some(&offset) => read_details(&mut dcm_file, offset).ok(),
+            None => None,
+        };
+
+        let details = match details {
+            Some(details) => details,
+            None => {
+                report.missing_acquisitions.push(acquisition.reference());
+                continue;
+            }
+        };
+
+        let num_chunks = details.chunks.len();
+
+        for (chunk_index, pixel_chunk) in details.chunks.iter().enumerate() {
+            let expected = if options.is_sampled(chunk_index, num_chunks) {
+                let num_chunks_x = details.num_chunks_x() as usize;
+                let x_chunk = (chunk_index % num_chunks_x) as u32;
+                let y_chunk = (chunk_index / num_chunks_x) as u32;
+
+                Some(read_chunk(acquisition, &details, x_chunk, y_chunk)?)
+            } else {
+                None
+            };
+
+            for (channel_index, channel_chunk) in pixel_chunk.channels.iter().enumerate() {
+                report.num_checked += 1;
+
+                let mut buf = vec![0; channel_chunk.length as usize];
+                let read = dcm_file
+                    .seek(SeekFrom::Start(channel_chunk.offset))
+                    .and_then(|_| dcm_file.read_exact(&mut buf));
+
+                let problem = if read.is_err() {
+                    Some(ChunkProblem::Unreadable)
+                } else if crc32fast::hash(&buf) != channel_chunk.checksum {
+                    Some(ChunkProblem::Corrupt)
+                } else {
+                    match channel_chunk.decode(&buf) {
+                        Ok(data) if data.len() == channel_chunk.num_intensities as usize * 4 => {
+                            match expected
+                                .as_ref()
+                                .map(|expected| expected.get(channel_index))
+                            {
+                                Some(expected) => {
+                                    report.num_compared += 1;
+
+                                    if matches(&data, expected.map(|e| e.as_slice())) {
+                                        None
+                                    } else {
+                                        Some(ChunkProblem::Mismatch)
+                                    }
+                                }
+                                None => None,
+                            }
+                        }
+                        _ => Some(ChunkProblem::Corrupt),
+                    }
+                };
+
+                if let Some(problem) = problem {
+                    report.bad_chunks.push(BadChunk {
+                        acquisition: acquisition.reference(),
+                        chunk: chunk_index,
+                        channel: channel_index,
+                        problem,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Repair the problems in the .dcm file found by `verify`. Only the bad chunks are rewritten, by re-deriving
+/// them from the .mcd file. Where the recompressed chunk no longer fits in its original location, it is
+/// appended to the end of the file.
+///
+/// If any acquisitions are missing from the .dcm file, the whole file is regenerated with `convert`.
+pub fn repair<P: AsRef<Path>, R: Read + Seek>(
+    dcm_path: P,
+    mcd: &MCD<R>,
+    report: &VerifyReport,
+) -> Result<(), MCDError> {
+    mcd.check_writable("repairing the .dcm file")?;
+
+    if !report.missing_acquisitions.is_empty() {
+        // Bookmarks are kept, if they can still be read
+        return regenerate(
+            dcm_path.as_ref(),
+            mcd,
+            &ConvertOptions::default(),
+            &(),
+            &CancellationToken::new(),
+        );
+    }
+
+    if report.bad_chunks.is_empty() {
+        return Ok(());
+    }
+
+    let mut dcm_file = OpenOptions::new().read(true).write(true).open(dcm_path)?;
+    let bookmarks = read_bookmarks(&mut dcm_file)?;
+
+    dcm_file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&mut dcm_file);
+    read_header(&mut reader)?;
+    let acquisition_offsets = read_index(&mut reader)?;
+
+    let mut bad_chunks: BTreeMap<AcquisitionRef, BTreeMap<usize, Vec<usize>>> = BTreeMap::new();
+    for bad_chunk in &report.bad_chunks {
+        bad_chunks
+            .entry(bad_chunk.acquisition)
+            .or_default()
+            .entry(bad_chunk.chunk)
+            .or_default()
+            .push(bad_chunk.channel);
+    }
+
+    for (reference, chunks) in bad_chunks {
+        let acquisition = mcd.find_acquisition(reference)?;
+        let details_offset = *acquisition_offsets
+            .get(&reference)
+            .ok_or_else(|| mcd.unknown_acquisition(reference.into()))?;
+
+        let mut details = read_details(&mut BufReader::new(&mut dcm_file), details_offset)?;
+        let num_chunks_x = details.num_chunks_x() as usize;
+
+        for (chunk_index, channels) in chunks {
+            let x_chunk = (chunk_index % num_chunks_x) as u32;
+            let y_chunk = (chunk_index / num_chunks_x) as u32;
+            let mut channel_chunks = read_chunk(acquisition, &details, x_chunk, y_chunk)?;
+
+            for channel_index in channels {
+                let channel_chunk = match details
+                    .chunks
+                    .get_mut(chunk_index)
+                    .and_then(|chunk| chunk.channels.get_mut(channel_index))
+                {
+                    Some(channel_chunk) => channel_chunk,
+                    None => continue,
+                };
+                let intensities = match channel_chunks.get_mut(channel_index) {
+                    Some(intensities) => std::mem::take(intensities),
+                    None => continue,
+                };
+
+                // Chunks are recompressed with the codec and encoding they were originally written with
+                let (num_intensities, encoding, compressed) = compress_chunk(
+                    intensities,
+                    &ConvertOptions::new().codec(channel_chunk.codec),
+                    channel_chunk.encoding == Encoding::Delta,
+                )?;
+
+                let offset = if compressed.len() as u64 <= channel_chunk.length {
+                    dcm_file.seek(SeekFrom::Start(channel_chunk.offset))?
+                } else {
+                    dcm_file.seek(SeekFrom::End(0))?
+                };
+                dcm_file.write_all(&compressed)?;
+
+                channel_chunk.num_intensities = num_intensities as u64;
+                channel_chunk.offset = offset;
+                channel_chunk.length = compressed.len() as u64;
+                channel_chunk.checksum = crc32fast::hash(&compressed);
+                channel_chunk.encoding = encoding;
+            }
+        }
+
+        // The number of chunks is unchanged, so the details occupy the same space as before
+        dcm_file.seek(SeekFrom::Start(details_offset))?;
+        let mut writer = BufWriter::new(&mut dcm_file);
+        writer.write_acquisition_details(&details)?;
+        writer.flush()?;
+    }
+
+    // Chunks appended to the end of the file are written over the bookmarks, so they are rewritten at the end
+    if !bookmarks.is_empty() {
+        write_bookmarks(&mut dcm_file, &bookmarks)?;
+    }
+
+    dcm_file.sync_all()?;
+
+    Ok(())
+}
+
+fn read_details<T: Read + Seek>(
+    dcm_file: &mut T,
+    offset: u64,
+) -> std::io::Result<AcquisitionDetails> {
+    dcm_file.seek(SeekFrom::Start(offset))?;
+    dcm_file.read_acquisition_details()
+}
+
+/// Compare the decompressed little endian intensities against those expected (bitwise, so NaNs compare equal)
+fn matches(data: &[u8], expected: Option<&[f32]>) -> bool {
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return false,
+    };
+
+    data.len() == expected.len() * 4
+        && data
+            .chunks_exact(4)
+            .zip(expected)
+            .all(|(bytes, expected)| LittleEndian::read_f32(bytes).to_bits() == expected.to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_includes_first_and_last_chunks() {
+        let options = VerifyOptions::new().sample_every(4);
+        let sampled: Vec<_> = (0..10).filter(|&c| options.is_sampled(c, 10)).collect();
+        assert_eq!(sampled, vec![0, 4, 8, 9]);
+
+        let options = VerifyOptions::new().sample_every(0);
+        assert!(!(0..10).any(|c| options.is_sampled(c, 10)));
+    }
+
+    #[test]
+    fn compare_decompressed() {
+        let expected = [1.0f32, f32::NAN, 3.5];
+        let mut data = vec![0; 12];
+        LittleEndian::write_f32_into(&expected, &mut data);
+
+        assert!(matches(&data, Some(&expected)));
+        assert!(!matches(&data, Some(&expected[..2])));
+        assert!(!matches(&data, None));
+    }
+}
@@ -0,0 +1,216 @@
+// There are four frames, each with its own point type so that they can't be mixed up:
+//
+// * `AcquisitionPixel` - column and row within an acquisition, with row 0 being the first row stored
+//   (as in `ChannelImage`)
+// * `PanoramaPixel` - column and row within a panorama image, with row 0 being the top row of the image
+// * `SlidePoint` - position on the slide in μm, with the y-axis pointing in the opposite direction to the
+//   rows of the images above
+// * `OverviewPixel` - column and row within an overview image of the slide (see
+//   `Slide::create_overview_image`), described by an `OverviewFrame`
+//
+// Acquisitions and panoramas are converted via the slide, using the transforms from `OnSlide`. These
+// transforms map into a pixel frame whose y-axis points in the same direction as the slide, so the rows are
+// flipped when converting to and from `AcquisitionPixel` and `PanoramaPixel`. The `_with` conversions take any
+// `Transform2D` in place of the transform from `OnSlide` (e.g. a `ThinPlateSpline` fitted to landmarks), which
+// maps into the same flipped pixel frame.
+
+use crate::{transform::Transform2D, Acquisition, OnSlide, Panorama};
+
+/// Position (column, row) within an acquisition, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AcquisitionPixel {
+    /// Column (0 being the first column)
+    pub x: f64,
+    /// Row (0 being the first row stored)
+    pub y: f64,
+}
+
+/// Position (column, row) within a panorama image, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanoramaPixel {
+    /// Column (0 being the left of the image)
+    pub x: f64,
+    /// Row (0 being the top of the image)
+    pub y: f64,
+}
+
+/// Position on the slide, in μm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlidePoint {
+    /// x-position (μm)
+    pub x: f64,
+    /// y-position (μm)
+    pub y: f64,
+}
+
+/// Position (column, row) within an overview image of the slide, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverviewPixel {
+    /// Column (0 being the left of the image)
+    pub x: f64,
+    /// Row (0 being the top of the image)
+    pub y: f64,
+}
+
+/// Describes how an overview image covers the slide: the slide origin is at the bottom left of the image, and
+/// each pixel covers `um_per_pixel` μm in both directions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverviewFrame {
+    um_per_pixel: f64,
+    height: u32,
+}
+
+impl OverviewFrame {
+    /// Create a frame for an overview image with the specified height (in pixels) and scale
+    pub fn new(um_per_pixel: f64, height: u32) -> Self {
+        OverviewFrame {
+            um_per_pixel,
+            height,
+        }
+    }
+
+    /// Returns the size of each pixel (in μm)
+    pub fn um_per_pixel(&self) -> f64 {
+        self.um_per_pixel
+    }
+
+    /// Returns the height of the overview image (in pixels)
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl AcquisitionPixel {
+    /// Create a point at the specified column and row
+    pub fn new(x: f64, y: f64) -> Self {
+        AcquisitionPixel { x, y }
+    }
+
+    /// Convert to a position on the slide, or None if the acquisition has no valid transform
+    pub fn to_slide<R>(self, acquisition: &Acquisition<R>) -> Option<SlidePoint> {
+        self.to_slide_with(acquisition, &acquisition.to_slide_transform())
+    }
+
+    /// Convert to a position on the slide using the supplied transform in place of the acquisition's own
+    pub fn to_slide_with<R, T: Transform2D<f64> + ?Sized>(
+        self,
+        acquisition: &Acquisition<R>,
+        transform: &T,
+    ) -> Option<SlidePoint> {
+        let height = acquisition.height() as f64;
+        let (x, y) = transform.to_slide(self.x, height - self.y)?;
+
+        Some(SlidePoint::new(x, y))
+    }
+}
+
+impl PanoramaPixel {
+    /// Create a point at the specified column and row
+    pub fn new(x: f64, y: f64) -> Self {
+        PanoramaPixel { x, y }
+    }
+
+    /// Convert to a position on the slide, or None if the panorama has no valid transform
+    pub fn to_slide<R>(self, panorama: &Panorama<R>) -> Option<SlidePoint> {
+        self.to_slide_with(panorama, &panorama.to_slide_transform())
+    }
+
+    /// Convert to a position on the slide using the supplied transform in place of the panorama's own
+    pub fn to_slide_with<R, T: Transform2D<f64> + ?Sized>(
+        self,
+        panorama: &Panorama<R>,
+        transform: &T,
+    ) -> Option<SlidePoint> {
+        let height = panorama.dimensions().1 as f64;
+        let (x, y) = transform.to_slide(self.x, height - self.y)?;
+
+        Some(SlidePoint::new(x, y))
+    }
+}
+
+impl SlidePoint {
+    /// Create a point at the specified position (in μm)
+    pub fn new(x: f64, y: f64) -> Self {
+        SlidePoint { x, y }
+    }
+
+    /// Convert to a pixel within the acquisition, or None if the acquisition has no valid transform. The
+    /// pixel may lie outside of the acquisition.
+    pub fn to_acquisition<R>(self, acquisition: &Acquisition<R>) -> Option<AcquisitionPixel> {
+        self.to_acquisition_with(acquisition, &acquisition.to_slide_transform())
+    }
+
+    /// Convert to a pixel within the acquisition using the supplied transform in place of the acquisition's own
+    pub fn to_acquisition_with<R, T: Transform2D<f64> + ?Sized>(
+        self,
+        acquisition: &Acquisition<R>,
+        transform: &T,
+    ) -> Option<AcquisitionPixel> {
+        let height = acquisition.height() as f64;
+        let (x, y) = transform.from_slide(self.x, self.y)?;
+
+        Some(AcquisitionPixel::new(x, height - y))
+    }
+
+    /// Convert to a pixel within the panorama image, or None if the panorama has no valid transform. The
+    /// pixel may lie outside of the panorama.
+    pub fn to_panorama<R>(self, panorama: &Panorama<R>) -> Option<PanoramaPixel> {
+        self.to_panorama_with(panorama, &panorama.to_slide_transform())
+    }
+
+    /// Convert to a pixel within the panorama image using the supplied transform in place of the panorama's own
+    pub fn to_panorama_with<R, T: Transform2D<f64> + ?Sized>(
+        self,
+        panorama: &Panorama<R>,
+        transform: &T,
+    ) -> Option<PanoramaPixel> {
+        let height = panorama.dimensions().1 as f64;
+        let (x, y) = transform.from_slide(self.x, self.y)?;
+
+        Some(PanoramaPixel::new(x, height - y))
+    }
+
+    /// Convert to a pixel within the overview image
+    pub fn to_overview(self, frame: &OverviewFrame) -> OverviewPixel {
+        OverviewPixel::new(
+            self.x / frame.um_per_pixel,
+            frame.height as f64 - self.y / frame.um_per_pixel,
+        )
+    }
+}
+
+impl OverviewPixel {
+    /// Create a point at the specified column and row
+    pub fn new(x: f64, y: f64) -> Self {
+        OverviewPixel { x, y }
+    }
+
+    /// Convert to a position on the slide
+    pub fn to_slide(self, frame: &OverviewFrame) -> SlidePoint {
+        SlidePoint::new(
+            self.x * frame.um_per_pixel,
+            (frame.height as f64 - self.y) * frame.um_per_pixel,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overview_round_trip() {
+        let frame = OverviewFrame::new(25.0, 1000);
+
+        // The slide origin is at the bottom left of the overview
+        assert_eq!(
+            SlidePoint::new(0.0, 0.0).to_overview(&frame),
+            OverviewPixel::new(0.0, 1000.0)
+        );
+
+        let point = SlidePoint::new(1250.0, 5000.0);
+        let pixel = point.to_overview(&frame);
+        assert_eq!(pixel, OverviewPixel::new(50.0, 800.0));
+        assert_eq!(pixel.to_slide(&frame), point);
+    }
+}
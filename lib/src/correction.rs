@@ -0,0 +1,199 @@
+use crate::{
+    calibration::{Calibration, CalibrationChannel},
+    channel::{AcquisitionChannel, Isotope},
+};
+
+/// A linear correction (`intensity * gain + offset`) applied to the intensities of a channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelCorrection {
+    gain: f64,
+    offset: f64,
+}
+
+impl ChannelCorrection {
+    /// Create a correction with the specified gain and offset
+    pub fn new(gain: f64, offset: f64) -> Self {
+        ChannelCorrection { gain, offset }
+    }
+
+    /// The identity correction, which leaves intensities unchanged
+    pub fn identity() -> Self {
+        ChannelCorrection::new(1.0, 0.0)
+    }
+
+    /// Returns the gain (multiplicative factor) of the correction
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Returns the offset added after applying the gain
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Apply the correction to a single intensity. Missing (NaN) intensities remain NaN.
+    pub fn apply(&self, intensity: f32) -> f32 {
+        (intensity as f64 * self.gain + self.offset) as f32
+    }
+}
+
+impl Default for ChannelCorrection {
+    fn default() -> Self {
+        ChannelCorrection::identity()
+    }
+}
+
+/// Per-channel corrections for an acquisition, derived from the calibration performed before it (see
+/// `MCD::channel_corrections()`).
+///
+/// The gain for each calibration channel is the ratio of the mean dual counts averaged over all calibrations
+/// in the .mcd file to the mean dual counts measured in the calibration used for the acquisition, so that
+/// correcting each acquisition brings them to a common detector response. Channels which were not part of the
+/// calibration are corrected by interpolating the gain linearly by mass between the nearest calibration
+/// channels.
+#[derive(Debug, Clone)]
+pub struct ChannelCorrections {
+    acquisition_id: u16,
+    calibration_id: u16,
+    channels: Vec<CalibratedChannel>,
+}
+
+#[derive(Debug, Clone)]
+struct CalibratedChannel {
+    name: String,
+    isotope: Option<Isotope>,
+    correction: ChannelCorrection,
+}
+
+impl ChannelCorrections {
+    /// Determine the corrections for the acquisition from `calibration`, using the calibration channels of
+    /// all calibrations (`all_channels`) as the reference. Returns None if no channel could be corrected.
+    pub(crate) fn new(
+        acquisition_id: u16,
+        calibration: &Calibration,
+        all_channels: &[&CalibrationChannel],
+    ) -> Option<Self> {
+        let mut channels: Vec<_> = all_channels
+            .iter()
+            .filter(|channel| {
+                channel.calibration_id() == calibration.id() && channel.mean_duals() > 0.0
+            })
+            .filter_map(|channel| {
+                let reference: Vec<_> = all_channels
+                    .iter()
+                    .filter(|other| other.name() == channel.name() && other.mean_duals() > 0.0)
+                    .map(|other| other.mean_duals())
+                    .collect();
+                let reference = reference.iter().sum::<f64>() / reference.len() as f64;
+
+                reference.is_finite().then(|| CalibratedChannel {
+                    name: channel.name().to_string(),
+                    isotope: Isotope::parse(channel.name()),
+                    correction: ChannelCorrection::new(reference / channel.mean_duals(), 0.0),
+                })
+            })
+            .collect();
+
+        if channels.is_empty() {
+            return None;
+        }
+
+        channels.sort_by_key(|channel| channel.isotope.as_ref().map(|isotope| isotope.mass()));
+
+        Some(ChannelCorrections {
+            acquisition_id,
+            calibration_id: calibration.id(),
+            channels,
+        })
+    }
+
+    /// Returns the ID of the acquisition to which the corrections apply
+    pub fn acquisition_id(&self) -> u16 {
+        self.acquisition_id
+    }
+
+    /// Returns the ID of the calibration the corrections were derived from
+    pub fn calibration_id(&self) -> u16 {
+        self.calibration_id
+    }
+
+    /// Returns the correction for the channel. Calibration channels are matched by name or isotope, other
+    /// channels are interpolated by mass, and the identity is returned if the mass of the channel is unknown.
+    pub fn correction(&self, channel: &AcquisitionChannel) -> ChannelCorrection {
+        let isotope = channel.isotope();
+
+        if let Some(calibrated) = self.channels.iter().find(|calibrated| {
+            calibrated.name == channel.name()
+                || (calibrated.isotope.is_some() && calibrated.isotope == isotope)
+        }) {
+            return calibrated.correction;
+        }
+
+        match isotope {
+            Some(isotope) => self.interpolate(isotope.mass()),
+            None => ChannelCorrection::identity(),
+        }
+    }
+
+    fn interpolate(&self, mass: u16) -> ChannelCorrection {
+        let masses: Vec<_> = self
+            .channels
+            .iter()
+            .filter_map(|channel| {
+                channel
+                    .isotope
+                    .as_ref()
+                    .map(|isotope| (isotope.mass() as f64, channel.correction.gain))
+            })
+            .collect();
+
+        let mass = mass as f64;
+        let gain = match masses.iter().position(|&(m, _)| m >= mass) {
+            None => match masses.last() {
+                Some(&(_, gain)) => gain,
+                None => return ChannelCorrection::identity(),
+            },
+            Some(0) => masses[0].1,
+            Some(index) => {
+                let (lower_mass, lower_gain) = masses[index - 1];
+                let (upper_mass, upper_gain) = masses[index];
+
+                lower_gain
+                    + (upper_gain - lower_gain) * (mass - lower_mass) / (upper_mass - lower_mass)
+            }
+        };
+
+        ChannelCorrection::new(gain, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolated_corrections() {
+        let corrections = ChannelCorrections {
+            acquisition_id: 1,
+            calibration_id: 1,
+            channels: vec![
+                CalibratedChannel {
+                    name: "Y89".to_string(),
+                    isotope: Isotope::parse("Y89"),
+                    correction: ChannelCorrection::new(2.0, 0.0),
+                },
+                CalibratedChannel {
+                    name: "Lu175".to_string(),
+                    isotope: Isotope::parse("Lu175"),
+                    correction: ChannelCorrection::new(1.0, 0.0),
+                },
+            ],
+        };
+
+        assert_eq!(corrections.interpolate(89).gain(), 2.0);
+        assert_eq!(corrections.interpolate(132).gain(), 1.5);
+        assert_eq!(corrections.interpolate(209).gain(), 1.0);
+        assert_eq!(ChannelCorrection::new(2.0, 1.0).apply(3.0), 7.0);
+        assert!(ChannelCorrection::new(2.0, 1.0).apply(f32::NAN).is_nan());
+    }
+}
@@ -0,0 +1,580 @@
+use std::fmt;
+
+/// Produces a structured description of an item (e.g. an acquisition), which can be rendered for people
+/// ([`Description::write_text`], as used by `Display`) or for machines ([`Description::to_json`] and
+/// [`Description::to_yaml`])
+pub trait Describe {
+    /// Returns the description of the item
+    fn describe(&self) -> Description;
+}
+
+/// Value of a field of a `Description`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Text
+    Text(String),
+    /// Whole number
+    Integer(i64),
+    /// Real number
+    Float(f64),
+    /// True or false
+    Boolean(bool),
+    /// Width and height
+    Size(f64, f64),
+    /// Position on the slide (in μm)
+    Position(f64, f64),
+    /// Sequence of values
+    List(Vec<Value>),
+}
+
+/// Named value within a `Description`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    name: String,
+    value: Value,
+}
+
+impl Field {
+    /// Returns the name of the field, as shown to people (e.g. `Dimensions (pixels)`)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the field as used in JSON (e.g. `dimensions_pixels`)
+    pub fn key(&self) -> String {
+        key(&self.name)
+    }
+
+    /// Returns the value of the field
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+/// Table of values within a `Description` (e.g. the channels of an .mcd file)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    title: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    /// Create an empty table with the specified columns
+    pub fn new(title: &str, columns: &[&str]) -> Self {
+        Table {
+            title: title.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, with a value for each column
+    pub fn row(mut self, row: Vec<Value>) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    /// Returns the title of the table
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the names of the columns
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Returns the rows of the table
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+}
+
+/// Tree of typed fields describing an item, along with descriptions of the items it contains (e.g. the
+/// panoramas of a slide) and any tables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Description {
+    title: String,
+    fields: Vec<Field>,
+    children: Vec<Description>,
+    tables: Vec<Table>,
+}
+
+impl Description {
+    /// Create an empty description with the specified title (e.g. `Acquisition`)
+    pub fn new(title: &str) -> Self {
+        Description {
+            title: title.to_string(),
+            fields: Vec::new(),
+            children: Vec::new(),
+            tables: Vec::new(),
+        }
+    }
+
+    /// Add a field
+    pub fn field<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.fields.push(Field {
+            name: name.to_string(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Add the description of a contained item
+    pub fn child(mut self, child: Description) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Add a table
+    pub fn table(mut self, table: Table) -> Self {
+        self.tables.push(table);
+        self
+    }
+
+    /// Returns the title of the description
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the fields of the description
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the specified name (either as shown to people, or as used in JSON)
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name || field.key() == name)
+            .map(|field| &field.value)
+    }
+
+    /// Returns the descriptions of the contained items
+    pub fn children(&self) -> &[Description] {
+        &self.children
+    }
+
+    /// Returns the tables of the description
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    /// Write the description for people, as a block of `name | value` lines headed by the title, indented by
+    /// `indent` spaces. Contained items are indented by a further space.
+    pub fn write_text<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
+        let name_width = self
+            .fields
+            .iter()
+            .map(|field| field.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = (name_width + 26).max(25);
+
+        writeln!(writer, "{:indent$}{:-^width$}", "", self.title)?;
+
+        for field in &self.fields {
+            match &field.value {
+                // Each position is written on its own line (e.g. the corners of a panorama)
+                Value::List(values)
+                    if !values.is_empty()
+                        && values
+                            .iter()
+                            .all(|value| matches!(value, Value::Position(..))) =>
+                {
+                    for (index, value) in values.iter().enumerate() {
+                        let name = if index == 0 { field.name.as_str() } else { "" };
+                        writeln!(writer, "{:indent$}{: <name_width$} | {}", "", name, value)?;
+                    }
+                }
+                value => writeln!(
+                    writer,
+                    "{:indent$}{: <name_width$} | {}",
+                    "", field.name, value
+                )?,
+            }
+        }
+
+        for child in &self.children {
+            child.write_text(writer, indent + 1)?;
+        }
+
+        for table in &self.tables {
+            writeln!(writer, "{:indent$}{:-^width$}", "", table.title)?;
+
+            let cells: Vec<Vec<String>> = std::iter::once(table.columns.clone())
+                .chain(
+                    table
+                        .rows
+                        .iter()
+                        .map(|row| row.iter().map(|value| value.to_string()).collect()),
+                )
+                .collect();
+            let column_widths: Vec<usize> = (0..table.columns.len())
+                .map(|column| {
+                    cells
+                        .iter()
+                        .filter_map(|row| row.get(column))
+                        .map(|cell| cell.chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            for row in cells {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(&column_widths)
+                    .map(|(cell, &column_width)| format!("{: <column_width$}", cell))
+                    .collect();
+
+                writeln!(writer, "{:indent$}{}", "", line.join(" | ").trim_end())?;
+            }
+        }
+
+        writeln!(writer, "{:indent$}{:-^width$}", "", "")
+    }
+
+    /// Write the description as a JSON object. Fields are keyed by their JSON names (see [`Field::key`]),
+    /// along with `type` (the title), `children` and an array of objects for each table.
+    pub fn write_json<W: fmt::Write + ?Sized>(&self, writer: &mut W) -> fmt::Result {
+        write!(writer, "{{\"type\": ")?;
+        write_json_string(writer, &key(&self.title))?;
+
+        for field in &self.fields {
+            write!(writer, ", ")?;
+            write_json_string(writer, &field.key())?;
+            write!(writer, ": ")?;
+            field.value.write_json(writer)?;
+        }
+
+        if !self.children.is_empty() {
+            write!(writer, ", \"children\": [")?;
+
+            for (index, child) in self.children.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ", ")?;
+                }
+                child.write_json(writer)?;
+            }
+
+            write!(writer, "]")?;
+        }
+
+        for table in &self.tables {
+            write!(writer, ", ")?;
+            write_json_string(writer, &key(&table.title))?;
+            write!(writer, ": [")?;
+
+            for (index, row) in table.rows.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ", ")?;
+                }
+                write!(writer, "{{")?;
+
+                for (column, (name, value)) in table.columns.iter().zip(row).enumerate() {
+                    if column > 0 {
+                        write!(writer, ", ")?;
+                    }
+                    write_json_string(writer, &key(name))?;
+                    write!(writer, ": ")?;
+                    value.write_json(writer)?;
+                }
+
+                write!(writer, "}}")?;
+            }
+
+            write!(writer, "]")?;
+        }
+
+        write!(writer, "}}")
+    }
+
+    /// Returns the description as a JSON object (see [`Description::write_json`])
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        // Writing to a String never fails
+        let _ = self.write_json(&mut json);
+
+        json
+    }
+
+    /// Write the description as a YAML document, with the same structure as the JSON object (see
+    /// [`Description::write_json`]). Values are written in flow style (as in JSON).
+    pub fn write_yaml<W: fmt::Write + ?Sized>(&self, writer: &mut W) -> fmt::Result {
+        for line in self.yaml_lines() {
+            writeln!(writer, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the description as a YAML document (see [`Description::write_yaml`])
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::new();
+        // Writing to a String never fails
+        let _ = self.write_yaml(&mut yaml);
+
+        yaml
+    }
+
+    fn yaml_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("type: {}", json(&Value::Text(key(&self.title))))];
+
+        for field in &self.fields {
+            lines.push(format!("{}: {}", field.key(), json(&field.value)));
+        }
+
+        if !self.children.is_empty() {
+            lines.push("children:".to_string());
+
+            for child in &self.children {
+                push_yaml_item(&mut lines, child.yaml_lines());
+            }
+        }
+
+        for table in &self.tables {
+            if table.rows.is_empty() {
+                lines.push(format!("{}: []", key(&table.title)));
+                continue;
+            }
+
+            lines.push(format!("{}:", key(&table.title)));
+
+            for row in &table.rows {
+                let item = table
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(name, value)| format!("{}: {}", key(name), json(value)))
+                    .collect();
+
+                push_yaml_item(&mut lines, item);
+            }
+        }
+
+        lines
+    }
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_text(f, 0)
+    }
+}
+
+impl Value {
+    fn write_json<W: fmt::Write + ?Sized>(&self, writer: &mut W) -> fmt::Result {
+        match self {
+            Value::Text(text) => write_json_string(writer, text),
+            Value::Integer(value) => write!(writer, "{}", value),
+            Value::Float(value) => write_json_number(writer, *value),
+            Value::Boolean(value) => write!(writer, "{}", value),
+            Value::Size(first, second) | Value::Position(first, second) => {
+                write!(writer, "[")?;
+                write_json_number(writer, *first)?;
+                write!(writer, ", ")?;
+                write_json_number(writer, *second)?;
+                write!(writer, "]")
+            }
+            Value::List(values) => {
+                write!(writer, "[")?;
+
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(writer, ", ")?;
+                    }
+                    value.write_json(writer)?;
+                }
+
+                write!(writer, "]")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Size(width, height) => write!(f, "{} x {}", width, height),
+            Value::Position(x, y) => write!(f, "({:.4} μm, {:.4} μm)", x, y),
+            Value::List(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<&String> for Value {
+    fn from(text: &String) -> Self {
+        Value::Text(text.clone())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+macro_rules! integer_value {
+    ($($integer:ty),*) => {
+        $(
+            impl From<$integer> for Value {
+                fn from(value: $integer) -> Self {
+                    Value::Integer(value as i64)
+                }
+            }
+        )*
+    };
+}
+
+integer_value!(u8, u16, u32, u64, usize, i16, i32, i64);
+
+impl<V: Into<Value>> From<Vec<V>> for Value {
+    fn from(values: Vec<V>) -> Self {
+        Value::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Convert a name for people into a JSON key (lowercase words separated by underscores)
+fn key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+
+    for character in name.chars() {
+        if character.is_alphanumeric() {
+            key.extend(character.to_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+
+    key.trim_end_matches('_').to_string()
+}
+
+/// Returns the value as JSON, which is also valid (flow style) YAML
+fn json(value: &Value) -> String {
+    let mut json = String::new();
+    // Writing to a String never fails
+    let _ = value.write_json(&mut json);
+
+    json
+}
+
+/// Add the lines of a mapping as an item of a YAML sequence
+fn push_yaml_item(lines: &mut Vec<String>, item: Vec<String>) {
+    for (index, line) in item.into_iter().enumerate() {
+        let prefix = if index == 0 { "  - " } else { "    " };
+        lines.push(format!("{}{}", prefix, line));
+    }
+}
+
+pub(crate) fn write_json_number<W: fmt::Write + ?Sized>(writer: &mut W, value: f64) -> fmt::Result {
+    if value.is_finite() {
+        write!(writer, "{}", value)
+    } else {
+        write!(writer, "null")
+    }
+}
+
+pub(crate) fn write_json_string<W: fmt::Write + ?Sized>(writer: &mut W, text: &str) -> fmt::Result {
+    writer.write_char('"')?;
+
+    for character in text.chars() {
+        match character {
+            '"' => writer.write_str("\\\"")?,
+            '\\' => writer.write_str("\\\\")?,
+            '\n' => writer.write_str("\\n")?,
+            '\r' => writer.write_str("\\r")?,
+            '\t' => writer.write_str("\\t")?,
+            character if (character as u32) < 0x20 => {
+                write!(writer, "\\u{:04x}", character as u32)?
+            }
+            character => writer.write_char(character)?,
+        }
+    }
+
+    writer.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_text_and_json() {
+        let description = Description::new("Panorama")
+            .field("ID", 2u16)
+            .field("Description", "Tumour \"core\"")
+            .field("Dimensions (pixels)", Value::Size(100.0, 50.0))
+            .field(
+                "Slide coordinates",
+                vec![Value::Position(1.0, 2.0), Value::Position(3.0, 4.0)],
+            )
+            .table(
+                Table::new("Channels", &["Order", "Name"])
+                    .row(vec![0.into(), "X".into()])
+                    .row(vec![1.into(), "Ir(191)".into()]),
+            );
+
+        let text = description.to_string();
+        assert!(text.contains("Dimensions (pixels) | 100 x 50\n"));
+        assert!(text.contains("                    | (3.0000 μm, 4.0000 μm)\n"));
+        assert!(text.contains("1     | Ir(191)\n"));
+
+        assert_eq!(
+            description.to_json(),
+            "{\"type\": \"panorama\", \"id\": 2, \"description\": \"Tumour \\\"core\\\"\", \
+             \"dimensions_pixels\": [100, 50], \"slide_coordinates\": [[1, 2], [3, 4]], \
+             \"channels\": [{\"order\": 0, \"name\": \"X\"}, {\"order\": 1, \"name\": \"Ir(191)\"}]}"
+        );
+
+        assert_eq!(
+            description.to_yaml(),
+            "type: \"panorama\"\nid: 2\ndescription: \"Tumour \\\"core\\\"\"\n\
+             dimensions_pixels: [100, 50]\nslide_coordinates: [[1, 2], [3, 4]]\nchannels:\n  \
+             - order: 0\n    name: \"X\"\n  - order: 1\n    name: \"Ir(191)\"\n"
+        );
+
+        assert_eq!(
+            description.value("dimensions_pixels"),
+            Some(&Value::Size(100.0, 50.0))
+        );
+    }
+}
@@ -1,15 +1,24 @@
-use std::{io, num::TryFromIntError, result, str::Utf8Error, string::FromUtf16Error};
+use std::{
+    fmt, io, num::TryFromIntError, path::PathBuf, result, str::Utf8Error, string::FromUtf16Error,
+};
 
 use lz4_flex::block::DecompressError;
 use thiserror::Error;
 
-use crate::ChannelIdentifier;
+use crate::{
+    AcquisitionChannel, AcquisitionIdentifier, AcquisitionRef, AcquisitionSuggestion,
+    ChannelIdentifier, ChannelSummary, Region,
+};
 
 /// A type alias for `Result<T, imc_rs::MCDError>`.
 pub type Result<T> = result::Result<T, MCDError>;
 
-/// Describes what has gone wrong with reading an .mcd file
+/// Describes what has gone wrong with reading an .mcd file. Errors caused by another error (e.g. an I/O error)
+/// return it from `std::error::Error::source`, so that the full chain of causes can be reported.
+///
+/// New variants may be added in future releases, so matches on `MCDError` must include a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum MCDError {
     /// An I/O error occurred
     #[error("An I/O error occured")]
@@ -35,10 +44,30 @@ pub enum MCDError {
         source: DecompressError,
     },
     /// No channel exists which matches the specified `ChannelIdentifier`
-    #[error("No such channel exists")]
-    InvalidChannel {
+    #[error("No such channel exists ({channel:?}), available channels are: {}{}", join(.available), did_you_mean(.suggestions))]
+    ChannelNotFound {
         /// Channel identifier of the unknown channel.
         channel: ChannelIdentifier,
+        /// The channels which are available (e.g. in the acquisition).
+        available: Vec<ChannelSummary>,
+        /// The channels most similar to the identifier, closest first (see `Acquisition::channel_suggestions`).
+        suggestions: Vec<ChannelSummary>,
+    },
+    /// No acquisition exists which matches the specified `AcquisitionIdentifier`
+    #[error("No such acquisition exists ({acquisition}){}", did_you_mean(.suggestions))]
+    InvalidAcquisition {
+        /// Identifier of the unknown acquisition.
+        acquisition: AcquisitionIdentifier,
+        /// The acquisitions most similar to the identifier, closest first (see `MCD::acquisition_suggestions`).
+        suggestions: Vec<AcquisitionSuggestion>,
+    },
+    /// More than one acquisition matches the specified `AcquisitionIdentifier`
+    #[error("More than one acquisition matches ({acquisition}): {}", .matches.iter().map(|m| format!("[{}]", m)).collect::<Vec<_>>().join(", "))]
+    AmbiguousAcquisition {
+        /// Identifier which matched more than one acquisition.
+        acquisition: AcquisitionIdentifier,
+        /// References to the matching acquisitions, any of which can be used to select one unambiguously.
+        matches: Vec<AcquisitionRef>,
     },
     /// No slide present in MCD file, so likely this is not a valid .mcd file.
     #[error("No slide found in MCD file - is this a valid .mcd file?")]
@@ -80,6 +109,39 @@ pub enum MCDError {
         source: quick_xml::Error,
     },
 
+    /// The XML metadata could not be found at the end of the .mcd file (e.g. the file was truncated, or is not an
+    /// .mcd file).
+    #[error("Unable to find the XML metadata at the end of the file: {found}")]
+    XmlNotFound {
+        /// Description of what was found instead.
+        found: String,
+    },
+
+    /// The XML metadata of the .mcd file is malformed.
+    #[error("The XML metadata is malformed at line {line}: {source}")]
+    XmlSyntax {
+        /// Line of the XML metadata (starting from 1) at which the error occurred.
+        line: usize,
+        /// The original error that was raised.
+        source: quick_xml::Error,
+    },
+
+    /// Parsing the XML metadata failed without the cause being recorded.
+    #[error("Parsing the XML metadata failed at line {line} (in {tag})")]
+    ParseFailed {
+        /// Line of the XML metadata (starting from 1) at which parsing failed.
+        line: usize,
+        /// Name of the element being parsed.
+        tag: String,
+    },
+
+    /// An element which is referred to in the XML metadata (e.g. the panorama of an acquisition) is missing.
+    #[error("The XML metadata has no element {path}")]
+    MissingElement {
+        /// Path of the missing element (e.g. `MCDSchema/Panorama[ID=2]`).
+        path: String,
+    },
+
     /// An error occured when parsing an image.
     #[error("An error occured when parsing an image: {source}")]
     ImageError {
@@ -92,11 +154,25 @@ pub enum MCDError {
     #[error("An error occured when locking the reader.")]
     PoisonMutex,
 
-    /// Invalid offset in file.
-    #[error("Invalid offset in file: {offset}")]
-    InvalidOffset {
-        /// Offset not reachable in the file.
-        offset: i64,
+    /// Range of bytes in the file which ends before it starts, or which is too large to be read on this platform.
+    #[error("Invalid range of bytes in file: {start}..{end}")]
+    InvalidByteSpan {
+        /// Offset of the start of the range.
+        start: u64,
+        /// Offset of the end of the range.
+        end: u64,
+    },
+
+    /// Data recorded in the XML metadata lies beyond the end of the file (e.g. the file was truncated when it was
+    /// copied).
+    #[error("{element} ends at offset {offset}, beyond the end of the file ({file_len} bytes)")]
+    OffsetOutOfBounds {
+        /// Description of the data (e.g. `Panorama 2 image`).
+        element: String,
+        /// Offset at which the data ends.
+        offset: u64,
+        /// Length of the file in bytes.
+        file_len: u64,
     },
 
     /// An error occured when trying to convert from an integer.
@@ -106,4 +182,394 @@ pub enum MCDError {
         /// The original error that was raised.
         source: TryFromIntError,
     },
+
+    /// An error occured when building the thread pool.
+    #[error("Could not build the thread pool: {source}")]
+    ThreadPool {
+        /// The original error that was raised.
+        #[from]
+        source: rayon::ThreadPoolBuildError,
+    },
+
+    /// The supplied buffer is not the expected size.
+    #[error("Buffer has length {actual}, but expected {expected}")]
+    InvalidBufferSize {
+        /// Expected length of the buffer.
+        expected: usize,
+        /// Actual length of the supplied buffer.
+        actual: usize,
+    },
+
+    /// An error occured when reading or writing a TIFF file.
+    #[error("An error occured when reading or writing a TIFF file: {source}")]
+    Tiff {
+        #[from]
+        /// The original error that was raised.
+        source: tiff::TiffError,
+    },
+
+    /// The specified colormap is not known.
+    #[error("Unknown colormap: {name}")]
+    InvalidColormap {
+        /// Name of the colormap that was requested.
+        name: String,
+    },
+
+    /// The specified collision policy is not known.
+    #[error("Unknown collision policy: {name} (expected number, isotope, error or skip)")]
+    InvalidCollisionPolicy {
+        /// Name of the collision policy that was requested.
+        name: String,
+    },
+
+    /// An error occured when reading or writing a .csv file.
+    #[error("An error occured when reading or writing a .csv file: {source}")]
+    Csv {
+        #[from]
+        /// The original error that was raised.
+        source: csv::Error,
+    },
+
+    /// An error occured when writing an HDF5 file.
+    #[cfg(feature = "hdf5")]
+    #[error("An error occured when writing an HDF5 file: {source}")]
+    Hdf5 {
+        #[from]
+        /// The original error that was raised.
+        source: hdf5::Error,
+    },
+
+    /// The supplied regular expression is invalid.
+    #[error("Invalid regular expression: {source}")]
+    InvalidRegex {
+        #[from]
+        /// The original error that was raised.
+        source: regex::Error,
+    },
+
+    /// No isotope could be parsed from the supplied text.
+    #[error("Could not parse an isotope from: {text}")]
+    InvalidIsotope {
+        /// Text which was parsed.
+        text: String,
+    },
+
+    /// The tile size and overlap do not describe a valid tiling.
+    #[error("Invalid tiling: tiles of {tile_size} μm with {overlap} μm overlap")]
+    InvalidTiling {
+        /// Requested size of each tile (in μm).
+        tile_size: f64,
+        /// Requested overlap between tiles (in μm).
+        overlap: f64,
+    },
+
+    /// The cell mask could not be read or does not match the acquisition.
+    #[error("Invalid cell mask: {reason}")]
+    InvalidMask {
+        /// Description of the problem with the mask.
+        reason: String,
+    },
+
+    /// The cell data (e.g. a .csv file from HALO) is missing a column, or a value can't be parsed as the type of
+    /// its column.
+    #[error("Invalid cell data in column '{column}'{}: {reason}", .line.map(|line| format!(" on line {}", line)).unwrap_or_default())]
+    InvalidCellData {
+        /// Name of the column.
+        column: String,
+        /// Line of the file containing the value, or None if the problem is with the column as a whole.
+        line: Option<u64>,
+        /// Description of the problem.
+        reason: String,
+    },
+
+    /// The gating rule could not be parsed.
+    #[error("Invalid rule '{rule}': {reason}")]
+    InvalidRule {
+        /// Rule which was parsed.
+        rule: String,
+        /// Description of the problem with the rule.
+        reason: String,
+    },
+
+    /// The operation was cancelled using a `CancellationToken`.
+    #[error("The operation was cancelled")]
+    Cancelled,
+
+    /// The .dcm file is not valid for the .mcd file (e.g. the .mcd file has changed since it was created).
+    #[error("Invalid .dcm file: {reason}")]
+    InvalidDCM {
+        /// Description of the problem with the .dcm file.
+        reason: String,
+    },
+
+    /// The .mcd file contains more acquisitions than can be stored in a .dcm file.
+    #[error(
+        "Too many acquisitions to convert: {count} (at most {} are supported)",
+        u32::MAX
+    )]
+    TooManyAcquisitions {
+        /// Number of acquisitions in the .mcd file.
+        count: usize,
+    },
+
+    /// The .dcm file was written in a different version of the format than is supported, so must be regenerated.
+    #[error(".dcm file has format version {found}, expected {expected}")]
+    DcmVersionMismatch {
+        /// Format version of the .dcm file.
+        found: u16,
+        /// Format version supported by this version of the library.
+        expected: u16,
+    },
+
+    /// The bookmark can't be stored in the .dcm file (e.g. its name is too long).
+    #[error("Invalid bookmark '{name}': {reason}")]
+    InvalidBookmark {
+        /// Name of the bookmark.
+        name: String,
+        /// Description of the problem with the bookmark.
+        reason: String,
+    },
+
+    /// The checksum of a chunk of the .dcm file does not match the stored checksum.
+    #[error("Checksum mismatch for chunk at offset {offset} of the .dcm file")]
+    ChecksumMismatch {
+        /// Offset of the chunk in the .dcm file.
+        offset: u64,
+    },
+
+    /// There is no tile at the position in the level of the pyramid.
+    #[error("Invalid tile ({x}, {y}) of level {level}")]
+    InvalidTile {
+        /// Level of the pyramid.
+        level: u32,
+        /// Column of the tile.
+        x: u32,
+        /// Row of the tile.
+        y: u32,
+    },
+
+    /// The polygon can't be mapped onto the acquisition (e.g. it has fewer than three vertices).
+    #[error("Invalid polygon: {reason}")]
+    InvalidPolygon {
+        /// Description of the problem with the polygon.
+        reason: String,
+    },
+
+    /// The intensities can't be integrated over the requested plume window.
+    #[error("Can't integrate over pushes {start}..{end}: {reason}")]
+    InvalidPlumeWindow {
+        /// First push of the requested window.
+        start: i32,
+        /// End (exclusive) of the requested window.
+        end: i32,
+        /// Description of why the window can't be used.
+        reason: String,
+    },
+
+    /// The .mcd file being written is inconsistent (e.g. a panorama refers to a slide which hasn't been added).
+    #[error("Invalid .mcd file structure: {reason}")]
+    InvalidStructure {
+        /// Description of the inconsistency.
+        reason: String,
+    },
+
+    /// The region does not lie within the acquisition.
+    #[error("Region ({}, {}) {}x{} lies outside of {acquisition}", .region.x, .region.y, .region.width, .region.height)]
+    InvalidRegion {
+        /// The requested region (in pixels).
+        region: Region,
+        /// The acquisition the region was requested from.
+        acquisition: AcquisitionRef,
+    },
+
+    /// The .mcd file was opened read-only (see `MCD::open_read_only`), so the operation, which would create or
+    /// modify a sidecar (.dcm) file, is not permitted.
+    #[error("The .mcd file was opened read-only, so {operation} is not permitted")]
+    ReadOnly {
+        /// Description of the operation which was refused.
+        operation: String,
+    },
+
+    /// An error occurred while processing one of several files (e.g. in `batch::channel_statistics`).
+    #[error("Error processing {}: {source}", .path.display())]
+    File {
+        /// Path of the file which could not be processed.
+        path: PathBuf,
+        /// The original error that was raised.
+        source: Box<MCDError>,
+    },
+
+    /// Two images combined pixel by pixel (e.g. `ChannelImage::combine`) have different sizes.
+    #[error("Image is {}x{} pixels, but expected {}x{}", .actual.0, .actual.1, .expected.0, .expected.1)]
+    ImageSizeMismatch {
+        /// Size (width, height) of the first image.
+        expected: (u32, u32),
+        /// Size (width, height) of the second image.
+        actual: (u32, u32),
+    },
+
+    /// The pixel size is not a positive, finite number.
+    #[error("Invalid pixel size {pixel_size} μm, expected a positive number")]
+    InvalidPixelSize {
+        /// The requested pixel size (in μm).
+        pixel_size: f64,
+    },
+
+    /// Multiple exported channels have the same name (after sanitising), with `CollisionPolicy::Error`.
+    #[error("Multiple channels are named '{name}'")]
+    ChannelNameCollision {
+        /// Name (after sanitising) shared by the channels.
+        name: String,
+    },
+
+    /// No transform could be estimated from the pairs of points (see `register::estimate_affine_ransac`).
+    #[error("Registration failed: {reason}")]
+    InvalidRegistration {
+        /// Description of why the points couldn't be registered.
+        reason: String,
+    },
+
+    /// The ITK or elastix transform parameters can't be parsed, or don't describe a 2D affine transform.
+    #[error("Invalid transform parameters: {reason}")]
+    InvalidTransformParameters {
+        /// Description of the problem with the parameters.
+        reason: String,
+    },
+
+    /// The acquisition has no background channel (e.g. `190BCKG` or Xe131) to estimate the background from.
+    #[error("Acquisition {acquisition_id} has no background channel")]
+    NoBackgroundChannel {
+        /// ID of the acquisition.
+        acquisition_id: u16,
+    },
+
+    /// The before or after ablation image of the acquisition is not stored in the .mcd file.
+    #[error("Acquisition {acquisition_id} has no {image} ablation image")]
+    MissingAblationImage {
+        /// ID of the acquisition.
+        acquisition_id: u16,
+        /// Which image is missing (`before` or `after`).
+        image: &'static str,
+    },
+
+    /// The intensities are stored in a format (`SegmentDataFormat` and `ValueBytes`) which can't be decoded.
+    #[error("Unsupported segment data format {format} with {value_bytes} bytes per value")]
+    UnsupportedDataFormat {
+        /// Name of the format.
+        format: String,
+        /// Number of bytes used to store each value.
+        value_bytes: u8,
+    },
+
+    /// The intensities of the acquisition can't be returned as raw (integer) counts.
+    #[error("Raw counts are not available for acquisition {acquisition_id}: {reason}")]
+    RawCountsUnavailable {
+        /// ID of the acquisition.
+        acquisition_id: u16,
+        /// Why the counts are not available.
+        reason: String,
+    },
+}
+
+/// Error for an identifier which matches none of the channels
+pub(crate) fn channel_not_found(
+    channel: &ChannelIdentifier,
+    channels: &[AcquisitionChannel],
+) -> MCDError {
+    MCDError::ChannelNotFound {
+        channel: channel.clone(),
+        available: channels.iter().map(ChannelSummary::from).collect(),
+        suggestions: channel_suggestions(channel, channels),
+    }
+}
+
+/// Returns the channels most similar to the identifier, closest first
+pub(crate) fn channel_suggestions(
+    channel: &ChannelIdentifier,
+    channels: &[AcquisitionChannel],
+) -> Vec<ChannelSummary> {
+    let mut candidates: Vec<_> = channels
+        .iter()
+        .filter_map(|candidate| {
+            channel
+                .distance(candidate)
+                .map(|distance| (distance, candidate.order_number(), candidate))
+        })
+        .collect();
+    candidates.sort_by_key(|&(distance, order_number, _)| (distance, order_number));
+
+    candidates
+        .into_iter()
+        .take(crate::MAX_SUGGESTIONS)
+        .map(|(_, _, candidate)| ChannelSummary::from(candidate))
+        .collect()
+}
+
+fn did_you_mean<T: fmt::Display>(suggestions: &[T]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+
+    let suggestions: Vec<_> = suggestions
+        .iter()
+        .map(|suggestion| suggestion.to_string())
+        .collect();
+
+    format!(". Did you mean {}?", suggestions.join(" or "))
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    let items: Vec<_> = items.iter().map(|item| item.to_string()).collect();
+
+    items.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    #[test]
+    fn channel_not_found_suggests_closest_channels() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 1, 1)
+                .channel("Ir191", "DNA1")
+                .channel("Er170", "CD3"),
+            [1.0, 2.0],
+        )])?)?;
+
+        let acquisition = mcd.acquisitions()[0];
+        match acquisition.channel_image(ChannelIdentifier::name("Pt195"), None) {
+            Err(error @ MCDError::ChannelNotFound { .. }) => {
+                assert!(error
+                    .to_string()
+                    .ends_with("available channels are: Ir191 (DNA1), Er170 (CD3)"));
+            }
+            other => panic!("expected ChannelNotFound, got {:?}", other.err()),
+        }
+
+        // Closest matches by name or label, and by order number
+        let suggestions = acquisition.channel_suggestions(ChannelIdentifier::name("Ir193"));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name(), "Ir191");
+        let suggestions = acquisition.channel_suggestions(ChannelIdentifier::label("cd-3"));
+        assert_eq!(suggestions[0].label(), "CD3");
+        assert_eq!(
+            acquisition
+                .channel_not_found(ChannelIdentifier::order(3))
+                .to_string(),
+            "No such channel exists (Order(3)), available channels are: Ir191 (DNA1), Er170 (CD3). \
+             Did you mean Er170 (CD3)?"
+        );
+
+        let error = MCDError::from(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated"));
+        assert_eq!(
+            error.source().map(|source| source.to_string()).as_deref(),
+            Some("truncated")
+        );
+
+        Ok(())
+    }
 }
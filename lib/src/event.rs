@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// Category of an event recorded by the instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstrumentEventCategory {
+    /// The instrument was calibrated (`Calibration`)
+    Calibration,
+    /// The final tuning parameters (detector voltage, helium flow etc.) were determined after calibration
+    /// (`CalibrationFinal`)
+    Tuning,
+    /// An acquisition was started
+    AcquisitionStart,
+    /// An acquisition finished
+    AcquisitionEnd,
+}
+
+impl fmt::Display for InstrumentEventCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstrumentEventCategory::Calibration => write!(f, "Calibration"),
+            InstrumentEventCategory::Tuning => write!(f, "Tuning"),
+            InstrumentEventCategory::AcquisitionStart => write!(f, "Acquisition start"),
+            InstrumentEventCategory::AcquisitionEnd => write!(f, "Acquisition end"),
+        }
+    }
+}
+
+/// A timestamped record of the state of the instrument, extracted from the metadata in the .mcd file
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstrumentEvent {
+    timestamp: String,
+    category: InstrumentEventCategory,
+    acquisition_id: Option<u16>,
+    source_id: u16,
+    description: String,
+}
+
+impl InstrumentEvent {
+    pub(crate) fn new(
+        timestamp: &str,
+        category: InstrumentEventCategory,
+        acquisition_id: Option<u16>,
+        source_id: u16,
+        description: String,
+    ) -> Self {
+        InstrumentEvent {
+            timestamp: timestamp.to_string(),
+            category,
+            acquisition_id,
+            source_id,
+            description,
+        }
+    }
+
+    /// Returns the timestamp of the event, as recorded by the instrument (ISO 8601)
+    pub fn timestamp(&self) -> &str {
+        &self.timestamp
+    }
+
+    /// Returns the category of the event
+    pub fn category(&self) -> InstrumentEventCategory {
+        self.category
+    }
+
+    /// Returns the ID of the acquisition the event is associated with, if any
+    pub fn acquisition_id(&self) -> Option<u16> {
+        self.acquisition_id
+    }
+
+    /// Returns the ID of the record the event was extracted from (e.g. the ID of the `Calibration`)
+    pub fn source_id(&self) -> u16 {
+        self.source_id
+    }
+
+    /// Returns a human readable description of the instrument state recorded with the event
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Display for InstrumentEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [{}]", self.timestamp, self.category)?;
+
+        if let Some(acquisition_id) = self.acquisition_id {
+            write!(f, " acquisition {}", acquisition_id)?;
+        }
+
+        if !self.description.is_empty() {
+            write!(f, ": {}", self.description)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{error::Result, segmentation::CellSummary, Acquisition, ChannelIdentifier};
+
+/// Length of the HEADER segment, after which the TEXT segment starts
+const HEADER_SIZE: usize = 58;
+/// Largest offset which can be written in the HEADER (larger offsets are only recorded in the TEXT segment)
+const MAX_HEADER_OFFSET: usize = 99_999_999;
+/// Delimiter between the keywords and values of the TEXT segment
+const DELIMITER: char = '|';
+
+/// Write the cells of one or more acquisitions (e.g. from `CellMask::summarise`) as an FCS 3.1 file, with one
+/// event per cell, for analysis in flow cytometry software (e.g. FlowJo, CellEngine).
+///
+/// Each channel (other than X, Y and Z) is a parameter holding the mean intensity within the cell, named
+/// (`$PnN`) by the channel name (e.g. `Ir191`) and described (`$PnS`) by the channel label (e.g. `DNA1`). The
+/// channels are those of the first acquisition, matched by name in the others (with NaN where an acquisition
+/// lacks a channel). The cell ID, acquisition ID, area (in pixels) and centroid (in pixels, within the
+/// acquisition) of each cell are included as additional parameters.
+pub fn write_fcs<R, W: Write>(
+    mut writer: W,
+    acquisitions: &[(&Acquisition<R>, &[CellSummary])],
+) -> Result<()> {
+    let channels: Vec<_> = acquisitions
+        .first()
+        .map(|(acquisition, _)| acquisition.marker_channels())
+        .unwrap_or_default();
+
+    // Name and description of each parameter
+    let mut parameters: Vec<(&str, &str)> = channels
+        .iter()
+        .map(|channel| (channel.name(), channel.label()))
+        .collect();
+    parameters.extend([
+        ("CellId", "Cell ID"),
+        ("AcquisitionId", "Acquisition ID"),
+        ("Area", "Area (pixels)"),
+        ("X", "Centroid x (pixels)"),
+        ("Y", "Centroid y (pixels)"),
+    ]);
+
+    let mut events = Vec::new();
+    for (acquisition, cells) in acquisitions {
+        let positions: Vec<_> = channels
+            .iter()
+            .map(|channel| {
+                let identifier = ChannelIdentifier::name(channel.name());
+                acquisition
+                    .channels()
+                    .iter()
+                    .position(|other| other.is(&identifier))
+            })
+            .collect();
+
+        for cell in cells.iter() {
+            events.extend(positions.iter().map(|position| {
+                position
+                    .and_then(|position| cell.markers().get(position))
+                    .map_or(f32::NAN, |summary| summary.mean())
+            }));
+
+            let (x, y) = cell.centroid();
+            events.extend([
+                cell.id() as f32,
+                acquisition.id() as f32,
+                cell.num_pixels() as f32,
+                x as f32,
+                y as f32,
+            ]);
+        }
+    }
+
+    let num_events = acquisitions
+        .iter()
+        .map(|(_, cells)| cells.len())
+        .sum::<usize>();
+
+    // Range of each parameter, the maximum value (rounded up) as recommended for floating point data
+    let ranges: Vec<_> = (0..parameters.len())
+        .map(|parameter| {
+            events
+                .iter()
+                .skip(parameter)
+                .step_by(parameters.len())
+                .filter(|value| value.is_finite())
+                .fold(1.0f32, |max, &value| max.max(value))
+                .ceil()
+        })
+        .collect();
+
+    let mut keywords = vec![
+        ("$BYTEORD".to_string(), "1,2,3,4".to_string()),
+        ("$DATATYPE".to_string(), "F".to_string()),
+        ("$MODE".to_string(), "L".to_string()),
+        ("$NEXTDATA".to_string(), "0".to_string()),
+        ("$PAR".to_string(), parameters.len().to_string()),
+        ("$TOT".to_string(), num_events.to_string()),
+        ("$BEGINANALYSIS".to_string(), "0".to_string()),
+        ("$ENDANALYSIS".to_string(), "0".to_string()),
+        ("$BEGINSTEXT".to_string(), "0".to_string()),
+        ("$ENDSTEXT".to_string(), "0".to_string()),
+        ("$CYT".to_string(), "Imaging mass cytometry".to_string()),
+    ];
+    for (index, ((name, description), range)) in parameters.iter().zip(&ranges).enumerate() {
+        let n = index + 1;
+        keywords.push((format!("$P{}N", n), name.to_string()));
+        if !description.is_empty() {
+            keywords.push((format!("$P{}S", n), description.to_string()));
+        }
+        keywords.push((format!("$P{}B", n), "32".to_string()));
+        keywords.push((format!("$P{}E", n), "0,0".to_string()));
+        keywords.push((format!("$P{}R", n), range.to_string()));
+    }
+
+    // The TEXT segment records the position of the DATA segment, which follows it, so repeat until the length
+    // of the TEXT segment no longer changes
+    let data_size = events.len() * 4;
+    let data_offsets = |text: &str| {
+        if data_size > 0 {
+            let data_start = HEADER_SIZE + text.len();
+            (data_start, data_start + data_size - 1)
+        } else {
+            (0, 0)
+        }
+    };
+
+    let mut text = text_segment(&keywords, (0, 0));
+    loop {
+        let updated = text_segment(&keywords, data_offsets(&text));
+        let unchanged = updated.len() == text.len();
+
+        text = updated;
+        if unchanged {
+            break;
+        }
+    }
+
+    let text_end = HEADER_SIZE + text.len() - 1;
+    let (data_start, data_end) = data_offsets(&text);
+
+    let header_offset = |offset: usize| {
+        if offset > MAX_HEADER_OFFSET {
+            0
+        } else {
+            offset
+        }
+    };
+
+    write!(writer, "FCS3.1    ")?;
+    for offset in [HEADER_SIZE, text_end, data_start, data_end, 0, 0] {
+        write!(writer, "{:>8}", header_offset(offset))?;
+    }
+    writer.write_all(text.as_bytes())?;
+
+    for value in events {
+        writer.write_f32::<LittleEndian>(value)?;
+    }
+
+    Ok(())
+}
+
+/// Generate the TEXT segment, with the delimiter doubled wherever it appears in a value
+fn text_segment(keywords: &[(String, String)], (data_start, data_end): (usize, usize)) -> String {
+    let mut text = String::new();
+    text.push(DELIMITER);
+
+    let data = [
+        ("$BEGINDATA".to_string(), data_start.to_string()),
+        ("$ENDDATA".to_string(), data_end.to_string()),
+    ];
+    for (keyword, value) in data.iter().chain(keywords) {
+        let escaped = value.replace(DELIMITER, "||");
+        text.push_str(&format!("{}{}{}{}", keyword, DELIMITER, escaped, DELIMITER));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, segmentation::CellMask, AcquisitionSpec, MCD};
+
+    #[test]
+    fn fcs_segments() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "DNA|1")
+                .channel("Er170", "CD3"),
+            [1.0, 2.0, 3.0, 4.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+        let cells = CellMask::new(2, 1, vec![1, 2])?.summarise(acquisition)?;
+
+        let mut fcs = Vec::new();
+        write_fcs(&mut fcs, &[(acquisition, cells.as_slice())])?;
+
+        assert!(fcs.starts_with(b"FCS3.1    "));
+        let offset = |index: usize| -> usize {
+            let start = 10 + index * 8;
+            std::str::from_utf8(&fcs[start..start + 8])
+                .expect("Offsets should be ASCII")
+                .trim()
+                .parse()
+                .expect("Offsets should be numbers")
+        };
+        let (text_start, text_end, data_start, data_end) =
+            (offset(0), offset(1), offset(2), offset(3));
+
+        let text = std::str::from_utf8(&fcs[text_start..=text_end]).expect("TEXT should be ASCII");
+        assert!(text.contains(&format!("|$BEGINDATA|{}|", data_start)));
+        assert!(text.contains("|$PAR|7|$TOT|2|"));
+        assert!(text.contains("|$P1N|Ir191|$P1S|DNA||1|"));
+        assert!(text.contains("|$P2R|4|"));
+
+        // Two events of seven parameters
+        assert_eq!(data_end - data_start + 1, 2 * 7 * 4);
+        assert_eq!(fcs.len(), data_end + 1);
+        let value = |event: usize, parameter: usize| {
+            let start = data_start + (event * 7 + parameter) * 4;
+            f32::from_le_bytes(fcs[start..start + 4].try_into().expect("4 bytes"))
+        };
+        assert_eq!((value(1, 0), value(1, 1), value(1, 2)), (3.0, 4.0, 2.0));
+
+        Ok(())
+    }
+}
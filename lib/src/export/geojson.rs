@@ -0,0 +1,266 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{Read, Seek, Write},
+};
+
+use crate::{
+    celltable::CellData,
+    coords::AcquisitionPixel,
+    describe::{write_json_number, write_json_string},
+    error::{MCDError, Result},
+    segmentation::CellMask,
+    Acquisition, RoiShape,
+};
+
+/// Feature of a GeoJSON FeatureCollection, outlined by a single polygon
+struct Feature {
+    /// QuPath object type (`annotation` or `detection`)
+    object_type: &'static str,
+    name: String,
+    /// Additional properties, written as numbers
+    properties: Vec<(&'static str, f64)>,
+    vertices: Vec<(f64, f64)>,
+}
+
+impl Feature {
+    fn write_json<W: fmt::Write>(&self, writer: &mut W) -> fmt::Result {
+        write!(
+            writer,
+            r#"{{"type":"Feature","geometry":{{"type":"Polygon","coordinates":[["#
+        )?;
+
+        // GeoJSON polygons are closed, with the first vertex repeated at the end
+        let first = self.vertices.first().copied();
+        for (index, (x, y)) in self.vertices.iter().copied().chain(first).enumerate() {
+            if index > 0 {
+                writer.write_char(',')?;
+            }
+            writer.write_char('[')?;
+            write_json_number(writer, x)?;
+            writer.write_char(',')?;
+            write_json_number(writer, y)?;
+            writer.write_char(']')?;
+        }
+
+        write!(writer, r#"]]}},"properties":{{"objectType":"#)?;
+        write_json_string(writer, self.object_type)?;
+        writer.write_str(r#","name":"#)?;
+        write_json_string(writer, &self.name)?;
+        for (name, value) in &self.properties {
+            writer.write_char(',')?;
+            write_json_string(writer, name)?;
+            writer.write_char(':')?;
+            write_json_number(writer, *value)?;
+        }
+
+        writer.write_str("}}")
+    }
+}
+
+/// Write the features as a GeoJSON FeatureCollection
+fn write_features<W: Write>(mut writer: W, features: &[Feature]) -> Result<()> {
+    let mut json = String::from(r#"{"type":"FeatureCollection","features":["#);
+
+    for (index, feature) in features.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        // Writing to a String can't fail
+        let _ = feature.write_json(&mut json);
+    }
+    json.push_str("]}\n");
+
+    writer.write_all(json.as_bytes())?;
+
+    Ok(())
+}
+
+/// Write the outline of each acquisition (see `Acquisition::roi_shape`) as a GeoJSON FeatureCollection of
+/// QuPath annotations, in slide coordinates (μm). Each annotation is named by the description of the
+/// acquisition, with its slide, panorama and acquisition IDs as properties, so that the regions can be
+/// overlaid on a whole-slide image of the same slide (e.g. in QuPath, after scaling to the pixel size of the
+/// image).
+pub fn write_roi_geojson<R, W: Write>(writer: W, acquisitions: &[&Acquisition<R>]) -> Result<()> {
+    let features: Vec<_> = acquisitions
+        .iter()
+        .map(|acquisition| {
+            let vertices = match acquisition.roi_shape() {
+                RoiShape::Rectangle(bounds) => vec![
+                    (bounds.min_x, bounds.min_y),
+                    (bounds.max_x(), bounds.min_y),
+                    (bounds.max_x(), bounds.max_y()),
+                    (bounds.min_x, bounds.max_y()),
+                ],
+                RoiShape::Polygon(vertices) => vertices,
+            };
+            let reference = acquisition.reference();
+
+            Feature {
+                object_type: "annotation",
+                name: acquisition.description().to_string(),
+                properties: vec![
+                    ("slideId", reference.slide() as f64),
+                    ("panoramaId", reference.panorama() as f64),
+                    ("acquisitionId", reference.id() as f64),
+                ],
+                vertices,
+            }
+        })
+        .collect();
+
+    write_features(writer, &features)
+}
+
+/// Write the bounding box of each cell in the mask as a GeoJSON FeatureCollection of QuPath detections, in
+/// slide coordinates (μm) as for [`write_roi_geojson`]. Each detection is named by the cell ID, with the area of
+/// the cell (in pixels) as a property. The mask must have the same dimensions as the acquisition.
+pub fn write_mask_geojson<R: Read + Seek, W: Write>(
+    writer: W,
+    acquisition: &Acquisition<R>,
+    mask: &CellMask,
+) -> Result<()> {
+    if mask.width() as i32 != acquisition.width() || mask.height() as i32 != acquisition.height() {
+        return Err(MCDError::InvalidMask {
+            reason: format!(
+                "mask is {}x{} pixels but acquisition {} is {}x{}",
+                mask.width(),
+                mask.height(),
+                acquisition.id(),
+                acquisition.width(),
+                acquisition.height()
+            ),
+        });
+    }
+
+    // Pixel bounds (min x, min y, max x, max y, inclusive) and area of each cell
+    let mut cells: BTreeMap<u32, (u32, u32, u32, u32, usize)> = BTreeMap::new();
+    for (index, &label) in mask.labels().iter().enumerate() {
+        if label == 0 {
+            continue;
+        }
+
+        let x = index as u32 % mask.width();
+        let y = index as u32 / mask.width();
+        let bounds = cells.entry(label).or_insert((x, y, x, y, 0));
+        *bounds = (
+            bounds.0.min(x),
+            bounds.1.min(y),
+            bounds.2.max(x),
+            bounds.3.max(y),
+            bounds.4 + 1,
+        );
+    }
+
+    let mut features = Vec::with_capacity(cells.len());
+    for (id, (min_x, min_y, max_x, max_y, area)) in cells {
+        // The outer edges of the pixels, which map to a (possibly rotated) rectangle on the slide
+        let (left, top, right, bottom) = (
+            min_x as f64,
+            min_y as f64,
+            max_x as f64 + 1.0,
+            max_y as f64 + 1.0,
+        );
+        let vertices = [(left, top), (right, top), (right, bottom), (left, bottom)]
+            .into_iter()
+            .map(|(x, y)| {
+                AcquisitionPixel::new(x, y)
+                    .to_slide(acquisition)
+                    .map(|point| (point.x, point.y))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| MCDError::InvalidMask {
+                reason: format!(
+                    "acquisition {} has no transform to slide coordinates",
+                    acquisition.id()
+                ),
+            })?;
+
+        features.push(Feature {
+            object_type: "detection",
+            name: id.to_string(),
+            properties: vec![("area", area as f64)],
+            vertices,
+        });
+    }
+
+    write_features(writer, &features)
+}
+
+/// Write the bounding box of each cell detected by HALO as a GeoJSON FeatureCollection of QuPath detections, in
+/// the coordinates recorded by HALO (the `XMin`, `XMax`, `YMin` and `YMax` columns, i.e. pixels of the
+/// whole-slide image analysed). Each detection is named by its row in the .csv file.
+///
+/// Returns `MCDError::InvalidCellData` if the bounds of any cell are missing (see `CellData::boundaries`).
+pub fn write_halo_geojson<W: Write>(writer: W, cells: &CellData) -> Result<()> {
+    let features: Vec<_> = cells
+        .boundaries()?
+        .enumerate()
+        .map(|(index, bounds)| {
+            let (min_x, min_y) = (bounds.min_x as f64, bounds.min_y as f64);
+            let (max_x, max_y) = (bounds.max_x() as f64, bounds.max_y() as f64);
+
+            Feature {
+                object_type: "detection",
+                name: index.to_string(),
+                properties: Vec::new(),
+                vertices: vec![
+                    (min_x, min_y),
+                    (max_x, min_y),
+                    (max_x, max_y),
+                    (min_x, max_y),
+                ],
+            }
+        })
+        .collect();
+
+    write_features(writer, &features)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    #[test]
+    fn writes_qupath_features() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 2)
+                .description("ROI \"1\"")
+                .position(10.0, 20.0)
+                .channel("Ir191", "DNA1"),
+            [1.0, 2.0, 3.0, 4.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        let mut json = Vec::new();
+        write_roi_geojson(&mut json, &[acquisition])?;
+        let json = String::from_utf8_lossy(&json);
+        assert!(json.starts_with(r#"{"type":"FeatureCollection","features":[{"type":"Feature""#));
+        assert!(json.contains(r#""objectType":"annotation","name":"ROI \"1\"","slideId":1,"#));
+        assert_eq!(json.matches("],[").count(), 4);
+
+        let mask = CellMask::new(2, 2, vec![1, 1, 0, 2])?;
+        let mut json = Vec::new();
+        write_mask_geojson(&mut json, acquisition, &mask)?;
+        let json = String::from_utf8_lossy(&json);
+        assert_eq!(json.matches(r#""objectType":"detection""#).count(), 2);
+        assert!(json.contains(r#""name":"1","area":2"#));
+
+        let wrong_size = CellMask::new(1, 1, vec![1])?;
+        assert!(matches!(
+            write_mask_geojson(Vec::new(), acquisition, &wrong_size),
+            Err(MCDError::InvalidMask { .. })
+        ));
+
+        let cells = crate::halo::parse(Cursor::new("XMin,XMax,YMin,YMax\n0,10,5,15\n"))?;
+        let mut json = Vec::new();
+        write_halo_geojson(&mut json, &cells)?;
+        let json = String::from_utf8_lossy(&json);
+        assert!(json.contains("[[0,5],[10,5],[10,15],[0,15],[0,5]]"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    error::{MCDError, Result},
+    AcquisitionRef,
+};
+
+/// Name of the checkpoint manifest written to the output directory during an export
+pub const MANIFEST_FILE_NAME: &str = "imc-export-manifest.csv";
+
+/// Checkpoint manifest recording which files of an export have been completely written, allowing an
+/// interrupted export to be resumed.
+///
+/// The manifest is stored as a .csv file (see [`MANIFEST_FILE_NAME`]) alongside the exported files, with one
+/// row (slide ID, panorama ID, acquisition ID, file name) per completed file. Rows are only appended once the file has been fully
+/// written and moved to its final location, so any file listed in the manifest is complete.
+#[derive(Debug)]
+pub struct ExportManifest {
+    path: PathBuf,
+    completed: HashSet<String>,
+    writer: Mutex<csv::Writer<File>>,
+}
+
+impl ExportManifest {
+    /// Create a new, empty manifest in `output_dir`, replacing any existing manifest
+    pub fn create<P: AsRef<Path>>(output_dir: P) -> Result<Self> {
+        let path = output_dir.as_ref().join(MANIFEST_FILE_NAME);
+
+        let mut writer = csv::Writer::from_path(&path)?;
+        writer.write_record(["slide_id", "panorama_id", "acquisition_id", "file"])?;
+        writer.flush()?;
+
+        Ok(ExportManifest {
+            path,
+            completed: HashSet::new(),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Open the manifest in `output_dir` to resume an export. If no manifest exists, a new one is created.
+    pub fn resume<P: AsRef<Path>>(output_dir: P) -> Result<Self> {
+        let output_dir = output_dir.as_ref();
+        let path = output_dir.join(MANIFEST_FILE_NAME);
+
+        if !path.exists() {
+            return ExportManifest::create(output_dir);
+        }
+
+        let mut completed = HashSet::new();
+        let mut reader = csv::Reader::from_path(&path)?;
+
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                // The final row may be incomplete if the export was interrupted while writing it
+                Err(_) => break,
+            };
+
+            if let Some(file) = record.get(3) {
+                // Only trust entries where the file is still present
+                if output_dir.join(file).is_file() {
+                    completed.insert(file.to_string());
+                }
+            }
+        }
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        let writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+
+        Ok(ExportManifest {
+            path,
+            completed,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Returns the location of the manifest
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the file (relative to the output directory) was completed in a previous export
+    pub fn is_completed(&self, file: &str) -> bool {
+        self.completed.contains(file)
+    }
+
+    /// Returns the number of files completed in a previous export
+    pub fn num_completed(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// Record that the file (relative to the output directory) has been completely written
+    pub(crate) fn complete(&self, acquisition: AcquisitionRef, file: &str) -> Result<()> {
+        let mut writer = self.writer.lock().or(Err(MCDError::PoisonMutex))?;
+
+        writer.write_record([
+            acquisition.slide().to_string().as_str(),
+            &acquisition.panorama().to_string(),
+            &acquisition.id().to_string(),
+            file,
+        ])?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_skips_missing_files() -> Result<()> {
+        let output_dir =
+            std::env::temp_dir().join(format!("imc-rs-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir)?;
+
+        let manifest = ExportManifest::create(&output_dir)?;
+        std::fs::write(output_dir.join("1.ome.tiff"), [0u8])?;
+        manifest.complete(AcquisitionRef::new(1, 1, 1), "1.ome.tiff")?;
+        manifest.complete(AcquisitionRef::new(1, 1, 2), "2.ome.tiff")?;
+        drop(manifest);
+
+        let rows = std::fs::read_to_string(output_dir.join(MANIFEST_FILE_NAME))?;
+        assert!(rows.contains("1,1,2,2.ome.tiff"));
+
+        let manifest = ExportManifest::resume(&output_dir)?;
+        assert!(manifest.is_completed("1.ome.tiff"));
+        assert!(!manifest.is_completed("2.ome.tiff"));
+        assert_eq!(manifest.num_completed(), 1);
+
+        std::fs::remove_dir_all(&output_dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,782 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use image::ImageOutputFormat;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    config,
+    error::{MCDError, Result},
+    render::Colormap,
+    Acquisition, AcquisitionChannel, AcquisitionIdentifier, ChannelIdentifier, ChannelImage,
+    ChannelPresence, ReadPlan, Region, MCD,
+};
+
+mod fcs;
+mod geojson;
+mod manifest;
+mod npy;
+mod ome;
+mod sanitize;
+mod tiff;
+mod world;
+
+pub use fcs::write_fcs;
+pub use geojson::{write_halo_geojson, write_mask_geojson, write_roi_geojson};
+pub use manifest::{ExportManifest, MANIFEST_FILE_NAME};
+pub use ome::ome_companion;
+pub use sanitize::{CollisionPolicy, NameSanitizer};
+
+use ome::{OmeImage, PixelStorage};
+use world::PixelToSlide;
+
+/// Approximate size (in bytes) of the TIFF header and image file directory written for each page
+const TIFF_PAGE_OVERHEAD: u64 = 256;
+
+/// Maximum width and height (in pixels) of the region read when benchmarking the reading of an acquisition
+const BENCHMARK_REGION_SIZE: u32 = 16;
+
+/// Format used when exporting channel images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One 32-bit floating point TIFF per channel of each acquisition
+    Tiff,
+    /// One multi-page OME-TIFF per acquisition, containing all selected channels
+    OmeTiff,
+    /// One 8-bit PNG per channel of each acquisition, rendered with a colormap (see
+    /// `ExportOptions::colormap()`) scaled to the intensity range of the image
+    Png,
+    /// One NumPy (.npy) array of 32-bit floats per channel of each acquisition
+    Npy,
+}
+
+impl ExportFormat {
+    /// Returns the file extension used for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Tiff => "tiff",
+            ExportFormat::OmeTiff => "ome.tiff",
+            ExportFormat::Png => "png",
+            ExportFormat::Npy => "npy",
+        }
+    }
+
+    /// Returns whether one file is written per channel (rather than per acquisition)
+    pub fn is_per_channel(&self) -> bool {
+        !matches!(self, ExportFormat::OmeTiff)
+    }
+
+    /// Returns the file name template used if none is specified (see `ExportOptions::file_name_template()`),
+    /// which includes the slide and panorama so that acquisitions sharing an ID don't overwrite each other
+    pub fn default_template(&self) -> &'static str {
+        if self.is_per_channel() {
+            "{slide}_{panorama}_{acquisition}_{channel}.{ext}"
+        } else {
+            "{slide}_{panorama}_{acquisition}.{ext}"
+        }
+    }
+}
+
+/// Which channels are exported when the panel differs between the exported acquisitions (see
+/// `ChannelPresence`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelSet {
+    /// Export every channel measured in each acquisition, so the union of channels is exported but not every
+    /// acquisition has every channel
+    #[default]
+    Union,
+    /// Only export the channels measured in every exported acquisition (plus the position channels), so that
+    /// all acquisitions have the same channels
+    Intersection,
+}
+
+/// How exported images are located on the slide, so that GIS-style viewers and registration tools can place
+/// them automatically. Only TIFF and PNG images are georeferenced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Georeference {
+    /// Images are not georeferenced
+    #[default]
+    None,
+    /// A world file (.tfw for TIFF, .pgw for PNG) is written alongside each image, giving the affine
+    /// transformation from pixels to slide coordinates (μm)
+    WorldFile,
+    /// The transformation is embedded in each TIFF as GeoTIFF tags (ModelTransformationTag). PNG images are
+    /// given a world file instead.
+    GeoTiff,
+}
+
+/// Options describing what should be exported and how
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    format: ExportFormat,
+    acquisitions: Option<Vec<AcquisitionIdentifier>>,
+    channels: Option<Vec<ChannelIdentifier>>,
+    channel_set: ChannelSet,
+    region: Option<Region>,
+    template: Option<String>,
+    colormap: Colormap,
+    georeference: Georeference,
+    resume: bool,
+    sanitizer: NameSanitizer,
+    missing_value: f32,
+}
+
+impl ExportOptions {
+    /// Create export options for the specified format, exporting all channels of all acquisitions
+    pub fn new(format: ExportFormat) -> Self {
+        ExportOptions {
+            format,
+            acquisitions: None,
+            channels: None,
+            channel_set: ChannelSet::Union,
+            region: None,
+            template: None,
+            colormap: Colormap::Grayscale,
+            georeference: Georeference::None,
+            resume: false,
+            sanitizer: NameSanitizer::new(),
+            missing_value: f32::NAN,
+        }
+    }
+
+    /// Only export the specified acquisitions (patterns select all matching acquisitions)
+    pub fn acquisitions(mut self, acquisitions: Vec<AcquisitionIdentifier>) -> Self {
+        self.acquisitions = Some(acquisitions);
+        self
+    }
+
+    /// Only export the specified channels. Channels which are not present in an acquisition are skipped.
+    pub fn channels(mut self, channels: Vec<ChannelIdentifier>) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Set which channels are exported when the panel differs between acquisitions. This is applied after the
+    /// selection of channels (see `channels()`).
+    pub fn channel_set(mut self, channel_set: ChannelSet) -> Self {
+        self.channel_set = channel_set;
+        self
+    }
+
+    /// Only export the region (in pixels) of each acquisition, which must lie within every exported acquisition
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Set the template used to name the exported files, relative to the output directory (which can include
+    /// subdirectories). The placeholders `{slide}`, `{panorama}` and `{acquisition}` are replaced with IDs,
+    /// `{description}` with the description of the acquisition, `{channel}` with the label of the channel
+    /// (or its name if it has no label) and `{ext}` with the extension of the format. The default template is
+    /// `ExportFormat::default_template()`.
+    pub fn file_name_template(mut self, template: &str) -> Self {
+        self.template = Some(template.to_string());
+        self
+    }
+
+    /// Set the colormap used to render PNG images (grayscale by default)
+    pub fn colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    /// Set how exported images are located on the slide (not georeferenced by default)
+    pub fn georeference(mut self, georeference: Georeference) -> Self {
+        self.georeference = georeference;
+        self
+    }
+
+    /// Resume a previous export to the same directory, skipping files recorded as completed in the checkpoint
+    /// manifest
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Set the policy used to convert channel labels into file names
+    pub fn name_sanitizer(mut self, sanitizer: NameSanitizer) -> Self {
+        self.sanitizer = sanitizer;
+        self
+    }
+
+    /// Set the value written for missing pixels (e.g. those not acquired as the acquisition was aborted). By
+    /// default missing pixels are written as NaN, which can be replaced (e.g. with 0) for software which does not
+    /// support NaN.
+    pub fn missing_value(mut self, value: f32) -> Self {
+        self.missing_value = value;
+        self
+    }
+
+    /// Returns the format used for exporting
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    fn selected_acquisitions<'a, R>(&self, mcd: &'a MCD<R>) -> Result<Vec<&'a Acquisition<R>>> {
+        match &self.acquisitions {
+            Some(identifiers) => {
+                let mut acquisitions: Vec<&Acquisition<R>> = Vec::new();
+
+                for identifier in identifiers {
+                    let matching = mcd.acquisitions_matching(identifier.clone());
+
+                    if matching.is_empty() {
+                        return Err(mcd.unknown_acquisition(identifier.clone()));
+                    }
+
+                    // Patterns can match the same acquisition as other identifiers
+                    for acquisition in matching {
+                        if !acquisitions
+                            .iter()
+                            .any(|a| a.reference() == acquisition.reference())
+                        {
+                            acquisitions.push(acquisition);
+                        }
+                    }
+                }
+
+                Ok(acquisitions)
+            }
+            None => Ok(mcd.acquisitions()),
+        }
+    }
+
+    /// Returns the name of the file for the acquisition (and channel, for formats with one file per channel), with
+    /// the description sanitised for use in file names
+    fn file_name<R>(&self, acquisition: &Acquisition<R>, channel_name: &str) -> String {
+        let reference = acquisition.reference();
+
+        self.template
+            .as_deref()
+            .unwrap_or(self.format.default_template())
+            .replace("{slide}", &reference.slide().to_string())
+            .replace("{panorama}", &reference.panorama().to_string())
+            .replace("{acquisition}", &reference.id().to_string())
+            .replace(
+                "{description}",
+                &self.sanitizer.sanitize(acquisition.description()),
+            )
+            .replace("{channel}", channel_name)
+            .replace("{ext}", self.format.extension())
+    }
+
+    /// Returns the region of the acquisition to export, checking that it lies within the acquisition
+    fn selected_region<R>(&self, acquisition: &Acquisition<R>) -> Result<Option<Region>> {
+        match self.region {
+            Some(region)
+                if region.x + region.width > acquisition.width().max(0) as u32
+                    || region.y + region.height > acquisition.height().max(0) as u32 =>
+            {
+                Err(MCDError::InvalidRegion {
+                    region,
+                    acquisition: acquisition.reference(),
+                })
+            }
+            region => Ok(region),
+        }
+    }
+
+    /// Returns whether a world file is written alongside each image
+    fn writes_world_files(&self) -> bool {
+        match self.georeference {
+            Georeference::None => false,
+            Georeference::WorldFile => {
+                matches!(self.format, ExportFormat::Tiff | ExportFormat::Png)
+            }
+            Georeference::GeoTiff => self.format == ExportFormat::Png,
+        }
+    }
+
+    fn fill_missing(&self, images: Vec<ChannelImage>) -> Vec<ChannelImage> {
+        if self.missing_value.is_nan() {
+            images
+        } else {
+            images
+                .iter()
+                .map(|image| image.fill_missing(self.missing_value))
+                .collect()
+        }
+    }
+
+    /// Returns the names of the channels which every exported acquisition is restricted to, or None if there
+    /// is no restriction
+    fn common_channels<R>(&self, acquisitions: &[&Acquisition<R>]) -> Option<Vec<String>> {
+        match self.channel_set {
+            ChannelSet::Union => None,
+            ChannelSet::Intersection => Some(
+                ChannelPresence::from_acquisitions(acquisitions)
+                    .intersection()
+                    .into_iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the selected channels with their (sanitised) names, skipping channels according to the collision
+    /// policy of the name sanitizer
+    fn exported_channels<'a, R: Read + Seek>(
+        &self,
+        acquisition: &'a Acquisition<R>,
+        common: Option<&[String]>,
+    ) -> Result<Vec<(&'a AcquisitionChannel, String)>> {
+        let channels = self.selected_channels(acquisition, common);
+        let names = self.sanitizer.resolve_channels(&channels)?;
+
+        Ok(channels
+            .into_iter()
+            .zip(names)
+            .filter_map(|(channel, name)| Some((channel, name?)))
+            .collect())
+    }
+
+    fn selected_channels<'a, R: Read + Seek>(
+        &self,
+        acquisition: &'a Acquisition<R>,
+        common: Option<&[String]>,
+    ) -> Vec<&'a AcquisitionChannel> {
+        let channels: Vec<_> = match &self.channels {
+            Some(identifiers) => identifiers
+                .iter()
+                .filter_map(|identifier| acquisition.channel(identifier))
+                .collect(),
+            None => acquisition.channels().iter().collect(),
+        };
+
+        match common {
+            Some(common) => channels
+                .into_iter()
+                .filter(|channel| {
+                    channel.is_position() || common.iter().any(|name| name == channel.name())
+                })
+                .collect(),
+            None => channels,
+        }
+    }
+}
+
+/// Estimate of the resources required to perform an export
+#[derive(Debug, Clone)]
+pub struct ExportEstimate {
+    output_size: u64,
+    num_files: usize,
+    estimated_duration: Duration,
+}
+
+impl ExportEstimate {
+    /// Returns the expected total size (in bytes) of all exported files
+    pub fn output_size(&self) -> u64 {
+        self.output_size
+    }
+
+    /// Returns the number of files that will be written
+    pub fn num_files(&self) -> usize {
+        self.num_files
+    }
+
+    /// Returns the expected time taken to perform the export, based on a short benchmark of this machine
+    pub fn estimated_duration(&self) -> Duration {
+        self.estimated_duration
+    }
+}
+
+impl fmt::Display for ExportEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Number of files: {}", self.num_files)?;
+        writeln!(
+            f,
+            "Output size: {:.2} MiB",
+            self.output_size as f64 / (1024.0 * 1024.0)
+        )?;
+        write!(
+            f,
+            "Estimated time: {:.1} s",
+            self.estimated_duration.as_secs_f64()
+        )
+    }
+}
+
+/// Exports channel images from an .mcd file
+#[derive(Debug)]
+pub struct Exporter;
+
+impl Exporter {
+    /// Export the channel images selected in `options` to the directory `output_dir`, returning the paths of
+    /// the files written. Acquisitions are exported in parallel (see [`crate::config`]).
+    ///
+    /// Completed files are recorded in a checkpoint manifest ([`ExportManifest`]) in `output_dir`. If
+    /// [`ExportOptions::resume`] is set, files recorded as completed by a previous (interrupted) export are
+    /// skipped.
+    pub fn export<R: Read + Seek + Send, P: AsRef<Path>>(
+        mcd: &MCD<R>,
+        options: &ExportOptions,
+        output_dir: P,
+    ) -> Result<Vec<PathBuf>> {
+        let output_dir = output_dir.as_ref();
+        std::fs::create_dir_all(output_dir)?;
+
+        let acquisitions = options.selected_acquisitions(mcd)?;
+        let common = options.common_channels(&acquisitions);
+
+        let manifest = if options.resume {
+            ExportManifest::resume(output_dir)?
+        } else {
+            ExportManifest::create(output_dir)?
+        };
+
+        let files = config::install(|| {
+            acquisitions
+                .into_par_iter()
+                .map(|acquisition| {
+                    export_acquisition(
+                        acquisition,
+                        options,
+                        common.as_deref(),
+                        output_dir,
+                        &manifest,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        Ok(files.into_iter().flatten().collect())
+    }
+
+    /// Estimate the output size, number of files and time taken to export using the specified options, without
+    /// writing anything. The time estimate is derived from a short benchmark reading from `mcd` and encoding
+    /// images on this machine, so is only approximate.
+    pub fn estimate<R: Read + Seek>(
+        mcd: &MCD<R>,
+        options: &ExportOptions,
+    ) -> Result<ExportEstimate> {
+        let acquisitions = options.selected_acquisitions(mcd)?;
+        let common = options.common_channels(&acquisitions);
+
+        let mut output_size = 0;
+        let mut num_files = 0;
+        let mut num_pixels = 0;
+        let mut num_read_pixels = 0;
+        let mut read_time_per_pixel = None;
+
+        for acquisition in acquisitions {
+            let channels: Vec<_> = options
+                .exported_channels(acquisition, common.as_deref())?
+                .into_iter()
+                .map(|(channel, _)| channel)
+                .collect();
+            if channels.is_empty() {
+                continue;
+            }
+
+            let (width, height) = match options.selected_region(acquisition)? {
+                Some(region) => (region.width, region.height),
+                None => (
+                    acquisition.width().max(0) as u32,
+                    acquisition.height().max(0) as u32,
+                ),
+            };
+            // PNG (RGBA) images also have 4 bytes per pixel before compression
+            let image_size = width as u64 * height as u64 * std::mem::size_of::<f32>() as u64;
+
+            if options.format.is_per_channel() {
+                num_files += channels.len();
+                output_size += channels.len() as u64 * (image_size + TIFF_PAGE_OVERHEAD);
+
+                if options.writes_world_files() {
+                    num_files += channels.len();
+                }
+            } else {
+                let channel_names: Vec<_> = channels
+                    .iter()
+                    .map(|channel| (channel.label(), channel.name()))
+                    .collect();
+
+                num_files += 1;
+                output_size += channels.len() as u64 * (image_size + TIFF_PAGE_OVERHEAD)
+                    + OmeImage::new(acquisition.description(), width, height, &channel_names)
+                        .to_xml(PixelStorage::Tiff)
+                        .len() as u64;
+            }
+
+            let acquisition_pixels =
+                (acquisition.num_spectra() as u64).min(width as u64 * height as u64);
+            num_pixels += acquisition_pixels * channels.len() as u64;
+            num_read_pixels += acquisition_pixels;
+
+            if read_time_per_pixel.is_none() && acquisition_pixels > 0 {
+                read_time_per_pixel = Some(benchmark_read(acquisition, &channels)?);
+            }
+        }
+
+        let read_time = read_time_per_pixel.unwrap_or(0.0) * num_read_pixels as f64;
+        let encode_time = benchmark_encode()? * num_pixels as f64;
+
+        Ok(ExportEstimate {
+            output_size,
+            num_files,
+            estimated_duration: Duration::from_secs_f64(read_time + encode_time),
+        })
+    }
+}
+
+fn export_acquisition<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    options: &ExportOptions,
+    common: Option<&[String]>,
+    output_dir: &Path,
+    manifest: &ExportManifest,
+) -> Result<Vec<PathBuf>> {
+    let channels = options.exported_channels(acquisition, common)?;
+    if channels.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let region = options.selected_region(acquisition)?;
+    let mut files = Vec::new();
+
+    if options.format.is_per_channel() {
+        let mut remaining = Vec::with_capacity(channels.len());
+        for (channel, channel_name) in channels {
+            let file_name = options.file_name(acquisition, &channel_name);
+
+            if manifest.is_completed(&file_name) {
+                files.push(output_dir.join(file_name));
+            } else {
+                remaining.push((channel, file_name));
+            }
+        }
+
+        if remaining.is_empty() {
+            return Ok(files);
+        }
+
+        let remaining_channels: Vec<_> = remaining.iter().map(|(channel, _)| *channel).collect();
+        let images = options.fill_missing(read_channels(acquisition, &remaining_channels, region)?);
+
+        for (image, (_, file_name)) in images.iter().zip(remaining) {
+            let geotiff = options.georeference == Georeference::GeoTiff
+                && options.format == ExportFormat::Tiff;
+            let transform = (geotiff || options.writes_world_files())
+                .then(|| PixelToSlide::new(acquisition, &image.region))
+                .flatten();
+
+            // The world file is written first, so that the image is only recorded as complete once both exist
+            if let Some(transform) = transform.filter(|_| options.writes_world_files()) {
+                let world_file_name = Path::new(&file_name)
+                    .with_extension(world::world_file_extension(options.format.extension()))
+                    .to_string_lossy()
+                    .into_owned();
+
+                let path = write_checkpointed(
+                    acquisition,
+                    output_dir,
+                    &world_file_name,
+                    manifest,
+                    |writer| Ok(writer.write_all(transform.world_file().as_bytes())?),
+                )?;
+                files.push(path);
+            }
+
+            let path =
+                write_checkpointed(acquisition, output_dir, &file_name, manifest, |writer| {
+                    match options.format {
+                        ExportFormat::Png => Ok(image
+                            .to_rgba(options.colormap, None)
+                            .write_to(writer, ImageOutputFormat::Png)?),
+                        ExportFormat::Npy => npy::write_npy(writer, image),
+                        _ => {
+                            tiff::write_tiff(writer, image, transform.as_ref().filter(|_| geotiff))
+                        }
+                    }
+                })?;
+            files.push(path);
+        }
+    } else {
+        let file_name = options.file_name(acquisition, "");
+
+        if manifest.is_completed(&file_name) {
+            files.push(output_dir.join(file_name));
+            return Ok(files);
+        }
+
+        let channels: Vec<_> = channels.into_iter().map(|(channel, _)| channel).collect();
+        let images = options.fill_missing(read_channels(acquisition, &channels, region)?);
+
+        let path = write_checkpointed(acquisition, output_dir, &file_name, manifest, |writer| {
+            tiff::write_ome_tiff(writer, acquisition.description(), &images)
+        })?;
+        files.push(path);
+    }
+
+    Ok(files)
+}
+
+/// Write to a temporary (.partial) file, which is moved into place and recorded in the manifest once complete
+fn write_checkpointed<R, F>(
+    acquisition: &Acquisition<R>,
+    output_dir: &Path,
+    file_name: &str,
+    manifest: &ExportManifest,
+    write: F,
+) -> Result<PathBuf>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let path = output_dir.join(file_name);
+    let partial_path = output_dir.join(format!("{}.partial", file_name));
+
+    // The file name template can place files in subdirectories
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(&partial_path)?);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(|error| error.into_error())?
+        .sync_all()?;
+
+    std::fs::rename(&partial_path, &path)?;
+    manifest.complete(acquisition.reference(), file_name)?;
+
+    Ok(path)
+}
+
+/// Read the images of the channels (optionally restricted to a region), as a single coalesced read (see
+/// [`ReadPlan`])
+fn read_channels<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    channels: &[&AcquisitionChannel],
+    region: Option<Region>,
+) -> Result<Vec<ChannelImage>> {
+    channels
+        .iter()
+        .fold(ReadPlan::new(), |plan, &channel| {
+            plan.read(acquisition.reference(), channel, region)
+        })
+        .execute_with(|_| Ok(acquisition))
+}
+
+fn channels_to_identifiers(channels: &[&AcquisitionChannel]) -> Vec<ChannelIdentifier> {
+    channels
+        .iter()
+        .map(|&channel| ChannelIdentifier::from(channel))
+        .collect()
+}
+
+/// Returns the time taken (in seconds) per pixel to read the specified channels from the acquisition
+fn benchmark_read<R: Read + Seek>(
+    acquisition: &Acquisition<R>,
+    channels: &[&AcquisitionChannel],
+) -> Result<f64> {
+    let width = acquisition.width().max(0) as u32;
+    let valid_rows = (acquisition.num_spectra() / width.max(1) as usize) as u32;
+    if valid_rows == 0 {
+        return Ok(0.0);
+    }
+
+    let region = Region {
+        x: 0,
+        y: 0,
+        width: width.min(BENCHMARK_REGION_SIZE),
+        height: valid_rows.min(BENCHMARK_REGION_SIZE),
+    };
+
+    let start = Instant::now();
+    acquisition.channel_images(&channels_to_identifiers(channels), Some(region))?;
+
+    Ok(start.elapsed().as_secs_f64() / (region.width * region.height) as f64)
+}
+
+/// Returns the time taken (in seconds) per pixel to encode a channel image as TIFF
+fn benchmark_encode() -> Result<f64> {
+    let size = 256;
+    let image = ChannelImage {
+        region: Region {
+            x: 0,
+            y: 0,
+            width: size,
+            height: size,
+        },
+        acquisition_id: 0,
+        name: String::new(),
+        label: String::new(),
+        range: (0.0, 1.0),
+        valid_pixels: (size * size) as usize,
+        data: (0..size * size).map(|value| value as f32).collect(),
+    };
+
+    let start = Instant::now();
+    tiff::write_tiff(Cursor::new(Vec::new()), &image, None)?;
+
+    Ok(start.elapsed().as_secs_f64() / (size * size) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{AcquisitionRef, AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+
+    #[test]
+    fn selects_acquisitions_sharing_ids() -> Result<()> {
+        // An acquisition on each of two slides, the second of which is renumbered to share the ID
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        for id in 1..=2 {
+            writer.add_slide(SlideSpec::new(id))?;
+            writer.add_panorama(PanoramaSpec::new(id, id).bounds(0.0, 0.0, 100.0, 100.0))?;
+            writer.add_acquisition(
+                AcquisitionSpec::new(id, id, 1, 1)
+                    .description("ROI")
+                    .channel("Ir(191)", "DNA1"),
+                &[1.0],
+            )?;
+        }
+
+        let mut mcd = MCD::from_bytes(writer.finish()?.into_inner())?;
+        for slide in mcd.slides_mut().values_mut() {
+            for panorama in slide.panoramas_mut().values_mut() {
+                let acquisitions = panorama.acquisitions_mut();
+                *acquisitions = acquisitions
+                    .drain()
+                    .map(|(_, mut acquisition)| {
+                        acquisition.set_id(1);
+                        (1, acquisition)
+                    })
+                    .collect();
+            }
+        }
+        mcd.acquisition_order = Default::default();
+
+        let options = ExportOptions::new(ExportFormat::Tiff).acquisitions(vec![
+            AcquisitionIdentifier::Id(1),
+            AcquisitionIdentifier::Description("ROI".to_string()),
+        ]);
+        let acquisitions = options.selected_acquisitions(&mcd)?;
+        let references: Vec<_> = acquisitions
+            .iter()
+            .map(|acquisition| acquisition.reference())
+            .collect();
+
+        assert_eq!(
+            references,
+            [AcquisitionRef::new(1, 1, 1), AcquisitionRef::new(2, 2, 1)]
+        );
+
+        // The default file names are distinct
+        let file_names: Vec<_> = acquisitions
+            .iter()
+            .map(|acquisition| options.file_name(acquisition, "DNA1"))
+            .collect();
+        assert_eq!(file_names, ["1_1_1_DNA1.tiff", "2_2_1_DNA1.tiff"]);
+
+        Ok(())
+    }
+}
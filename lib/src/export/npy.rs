@@ -0,0 +1,72 @@
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{error::Result, ChannelImage};
+
+use super::tiff::image_data;
+
+/// Magic string and format version (1.0) at the start of every .npy file
+const NPY_MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+/// Write a single channel image as a NumPy (.npy) array of 32-bit floats with shape (height, width), which can
+/// be loaded with `numpy.load()`
+pub(crate) fn write_npy<W: Write>(mut writer: W, image: &ChannelImage) -> Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        image.height(),
+        image.width()
+    );
+
+    // The header is padded with spaces (and terminated with a newline) so that the data is 64-byte aligned
+    let unpadded = NPY_MAGIC.len() + 2 + header.len() + 1;
+    header.extend(std::iter::repeat_n(' ', (64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_u16::<LittleEndian>(header.len() as u16)?;
+    writer.write_all(header.as_bytes())?;
+
+    for value in image_data(image) {
+        writer.write_f32::<LittleEndian>(value)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Region;
+
+    #[test]
+    fn npy_header() -> Result<()> {
+        let image = ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir(191)".to_string(),
+            label: "DNA1".to_string(),
+            range: (0.0, 5.0),
+            valid_pixels: 6,
+            data: vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0],
+        };
+
+        let mut npy = Vec::new();
+        write_npy(&mut npy, &image)?;
+
+        let header_length = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        let header = String::from_utf8_lossy(&npy[10..10 + header_length]);
+
+        assert_eq!((10 + header_length) % 64, 0);
+        assert!(header.contains("'shape': (2, 3)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(npy.len(), 10 + header_length + 6 * 4);
+
+        Ok(())
+    }
+}
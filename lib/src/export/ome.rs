@@ -0,0 +1,271 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use quick_xml::escape::escape;
+
+use crate::{error::Result, Isotope, OnSlide, MCD};
+
+/// How the pixel data described by the OME-XML is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PixelStorage {
+    /// One page (IFD) per channel of the TIFF containing the OME-XML
+    Tiff,
+    /// The pixel data is stored elsewhere, so only the metadata is described
+    MetadataOnly,
+}
+
+/// An image (with one plane per channel) described as OME-XML
+#[derive(Debug, Clone)]
+pub(crate) struct OmeImage<'a> {
+    name: &'a str,
+    width: u32,
+    height: u32,
+    /// Label and name of each channel
+    channels: &'a [(&'a str, &'a str)],
+    acquisition_date: Option<&'a str>,
+    physical_size: Option<(f64, f64)>,
+    /// Key-value pairs recorded in a `MapAnnotation` referenced by the image
+    annotations: Vec<(&'static str, String)>,
+}
+
+impl<'a> OmeImage<'a> {
+    /// Describe an image with the supplied channels (label, name)
+    pub(crate) fn new(
+        name: &'a str,
+        width: u32,
+        height: u32,
+        channels: &'a [(&'a str, &'a str)],
+    ) -> Self {
+        OmeImage {
+            name,
+            width,
+            height,
+            channels,
+            acquisition_date: None,
+            physical_size: None,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Set when the image was acquired (an ISO 8601 date and time)
+    pub(crate) fn acquisition_date(mut self, acquisition_date: &'a str) -> Self {
+        self.acquisition_date = Some(acquisition_date);
+        self
+    }
+
+    /// Set the size of each pixel (in μm)
+    pub(crate) fn physical_size(mut self, x: f64, y: f64) -> Self {
+        self.physical_size = Some((x, y));
+        self
+    }
+
+    /// Add a key-value pair to the annotation of the image
+    pub(crate) fn annotation<V: ToString>(mut self, key: &'static str, value: V) -> Self {
+        self.annotations.push((key, value.to_string()));
+        self
+    }
+
+    /// Generate the OME-XML describing the image. The metal and mass of each channel (where they can be parsed
+    /// from the name or label) are recorded in a `MapAnnotation` referenced by the channel.
+    pub(crate) fn to_xml(&self, storage: PixelStorage) -> String {
+        let mut xml = String::new();
+        xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        xml.push_str(r#"<OME xmlns="http://www.openmicroscopy.org/Schemas/OME/2016-06">"#);
+        xml.push_str(&format!(
+            r#"<Image ID="Image:0" Name="{}">"#,
+            escape(self.name)
+        ));
+
+        if let Some(acquisition_date) = self.acquisition_date {
+            xml.push_str(&format!(
+                "<AcquisitionDate>{}</AcquisitionDate>",
+                escape(acquisition_date)
+            ));
+        }
+
+        let physical_size = match self.physical_size {
+            Some((x, y)) => format!(r#" PhysicalSizeX="{}" PhysicalSizeY="{}""#, x, y),
+            None => String::new(),
+        };
+        xml.push_str(&format!(
+            r#"<Pixels ID="Pixels:0" DimensionOrder="XYCZT" Type="float" SizeX="{}" SizeY="{}" SizeC="{}" SizeZ="1" SizeT="1"{}>"#,
+            self.width,
+            self.height,
+            self.channels.len(),
+            physical_size
+        ));
+
+        let isotopes: Vec<_> = self
+            .channels
+            .iter()
+            .map(|(label, name)| Isotope::parse(name).or_else(|| Isotope::parse(label)))
+            .collect();
+
+        for (index, ((label, name), isotope)) in self.channels.iter().zip(&isotopes).enumerate() {
+            xml.push_str(&format!(
+                r#"<Channel ID="Channel:0:{}" Name="{}" Fluor="{}" SamplesPerPixel="1""#,
+                index,
+                escape(label),
+                escape(name)
+            ));
+
+            if isotope.is_some() {
+                xml.push_str(&format!(
+                    r#"><AnnotationRef ID="Annotation:Channel:0:{}"/></Channel>"#,
+                    index
+                ));
+            } else {
+                xml.push_str("/>");
+            }
+        }
+
+        match storage {
+            PixelStorage::Tiff => {
+                for index in 0..self.channels.len() {
+                    xml.push_str(&format!(
+                        r#"<TiffData FirstC="{}" FirstZ="0" FirstT="0" IFD="{}" PlaneCount="1"/>"#,
+                        index, index
+                    ));
+                }
+            }
+            PixelStorage::MetadataOnly => xml.push_str("<MetadataOnly/>"),
+        }
+
+        xml.push_str("</Pixels>");
+        if !self.annotations.is_empty() {
+            xml.push_str(r#"<AnnotationRef ID="Annotation:Image:0"/>"#);
+        }
+        xml.push_str("</Image>");
+
+        if !self.annotations.is_empty() || isotopes.iter().any(|isotope| isotope.is_some()) {
+            xml.push_str("<StructuredAnnotations>");
+
+            if !self.annotations.is_empty() {
+                xml.push_str(r#"<MapAnnotation ID="Annotation:Image:0"><Value>"#);
+                for (key, value) in &self.annotations {
+                    xml.push_str(&format!(r#"<M K="{}">{}</M>"#, key, escape(value)));
+                }
+                xml.push_str("</Value></MapAnnotation>");
+            }
+
+            for (index, isotope) in isotopes.iter().enumerate() {
+                if let Some(isotope) = isotope {
+                    xml.push_str(&format!(
+                        r#"<MapAnnotation ID="Annotation:Channel:0:{}"><Value><M K="Metal">{}</M><M K="Mass">{}</M></Value></MapAnnotation>"#,
+                        index,
+                        isotope.element(),
+                        isotope.mass()
+                    ));
+                }
+            }
+
+            xml.push_str("</StructuredAnnotations>");
+        }
+
+        xml.push_str("</OME>");
+
+        xml
+    }
+}
+
+/// Write an OME-XML companion file (`<acquisition ID>.companion.ome`) for each acquisition to `output_dir`,
+/// describing the acquisition and its channels without any pixel data (using `MetadataOnly`), for archival
+/// systems which ingest metadata separately from the pixel exports. Returns the paths of the files written.
+///
+/// The acquisition date, pixel size and channels are recorded in the standard OME-XML elements, and the
+/// remaining acquisition metadata (e.g. ablation settings and position on the slide) in a `MapAnnotation`.
+///
+/// ```no_run
+/// use imc_rs::{export, MCD};
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let files = export::ome_companion(&mcd, "companion").unwrap();
+/// ```
+pub fn ome_companion<R, P: AsRef<Path>>(mcd: &MCD<R>, output_dir: P) -> Result<Vec<PathBuf>> {
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut files = Vec::new();
+
+    for acquisition in mcd.acquisitions() {
+        let reference = acquisition.reference();
+        let channels: Vec<_> = acquisition
+            .channels()
+            .iter()
+            .map(|channel| (channel.label(), channel.name()))
+            .collect();
+        let (distance_x, distance_y) = acquisition.ablation_distance_between_shots();
+        let bounds = acquisition.slide_bounding_box();
+
+        let mut image = OmeImage::new(
+            acquisition.description(),
+            acquisition.width().max(0) as u32,
+            acquisition.height().max(0) as u32,
+            &channels,
+        )
+        .physical_size(distance_x, distance_y)
+        .annotation("Slide ID", reference.slide())
+        .annotation("Panorama ID", reference.panorama())
+        .annotation("Acquisition ID", reference.id())
+        .annotation("Order number", acquisition.order_number())
+        .annotation("Ablation power", acquisition.ablation_power())
+        .annotation("Ablation frequency (Hz)", acquisition.ablation_frequency())
+        .annotation("Signal type", acquisition.signal_type())
+        .annotation("Dual count start", acquisition.dual_count_start())
+        .annotation("Movement type", acquisition.movement_type())
+        .annotation("Slide position x (μm)", bounds.min_x)
+        .annotation("Slide position y (μm)", bounds.min_y)
+        .annotation("Slide width (μm)", bounds.width)
+        .annotation("Slide height (μm)", bounds.height);
+
+        if !acquisition.start_timestamp().is_empty() {
+            image = image.acquisition_date(acquisition.start_timestamp());
+        }
+        if !acquisition.end_timestamp().is_empty() {
+            image = image.annotation("End timestamp", acquisition.end_timestamp());
+        }
+
+        let path = output_dir.join(format!("{}.companion.ome", reference.id()));
+        File::create(&path)?.write_all(image.to_xml(PixelStorage::MetadataOnly).as_bytes())?;
+
+        files.push(path);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn companion_without_pixels() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(3, 1, 2, 1)
+                .description("ROI <1>")
+                .timestamps("2023-01-01T10:00:00", "2023-01-01T10:05:00")
+                .channel("Ir191", "DNA1"),
+            [1.0, 2.0],
+        )])?)?;
+
+        let output_dir =
+            std::env::temp_dir().join(format!("imc-rs-companion-{}", std::process::id()));
+        let files = ome_companion(&mcd, &output_dir)?;
+        assert_eq!(files, [output_dir.join("3.companion.ome")]);
+
+        let xml = std::fs::read_to_string(&files[0])?;
+        std::fs::remove_dir_all(&output_dir)?;
+
+        assert!(xml.contains(r#"Name="ROI &lt;1&gt;""#));
+        assert!(xml.contains("<AcquisitionDate>2023-01-01T10:00:00</AcquisitionDate>"));
+        assert!(xml.contains("<MetadataOnly/>"));
+        assert!(!xml.contains("TiffData"));
+        assert!(xml.contains(r#"<M K="Acquisition ID">3</M>"#));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::{
+    error::{MCDError, Result},
+    AcquisitionChannel, Isotope,
+};
+
+/// Names which are reserved on Windows and so can't be used as file names (regardless of extension)
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Name used when sanitising results in an empty name
+const EMPTY_NAME: &str = "unnamed";
+
+/// How channels whose names are identical after sanitising (ignoring case) are handled, e.g. where the same
+/// label is given to several metals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Append a numeric suffix to all but the first channel (e.g. `CD3`, `CD3_2`)
+    #[default]
+    Number,
+    /// Append the metal and mass of each of the channels (e.g. `CD3_Er170`, `CD3_Sm152`), falling back to a
+    /// numeric suffix if the metal and mass can't be determined or don't make the name unique
+    Isotope,
+    /// Fail with `MCDError::ChannelNameCollision`
+    Error,
+    /// Keep the first channel and skip the others
+    Skip,
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = MCDError;
+
+    /// Parse a collision policy from its name (`number`, `isotope`, `error` or `skip`)
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "number" => Ok(CollisionPolicy::Number),
+            "isotope" => Ok(CollisionPolicy::Isotope),
+            "error" => Ok(CollisionPolicy::Error),
+            "skip" => Ok(CollisionPolicy::Skip),
+            _ => Err(MCDError::InvalidCollisionPolicy {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// Policy for converting channel labels (or other names) into names which are safe to use as file names,
+/// HDF5/Zarr dataset names and so on, across all filesystems.
+///
+/// By default, any character other than ASCII letters, digits, `-`, `_` and `.` is replaced by `_`, repeated
+/// replacements are collapsed, leading/trailing separators are removed and empty names become `unnamed`.
+/// Names which are identical after sanitising (ignoring case, for case-insensitive filesystems) are made unique
+/// by appending a numeric suffix (e.g. `CD3`, `CD3_2`).
+///
+/// ```
+/// use imc_rs::export::NameSanitizer;
+///
+/// let sanitizer = NameSanitizer::new().map("Ir(191)", "DNA1");
+///
+/// assert_eq!(sanitizer.sanitize("CD45RA / CD45RO"), "CD45RA_CD45RO");
+/// assert_eq!(sanitizer.sanitize("Ir(191)"), "DNA1");
+/// assert_eq!(sanitizer.unique(&["CD3", "cd3", ""]), vec!["CD3", "cd3_2", "unnamed"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NameSanitizer {
+    replacement: char,
+    lowercase: bool,
+    ascii_only: bool,
+    max_length: Option<usize>,
+    collision_separator: String,
+    collision_policy: CollisionPolicy,
+    mapping: HashMap<String, String>,
+}
+
+impl Default for NameSanitizer {
+    fn default() -> Self {
+        NameSanitizer {
+            replacement: '_',
+            lowercase: false,
+            ascii_only: true,
+            max_length: None,
+            collision_separator: "_".to_string(),
+            collision_policy: CollisionPolicy::default(),
+            mapping: HashMap::new(),
+        }
+    }
+}
+
+impl NameSanitizer {
+    /// Create a sanitizer using the default rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the character used to replace disallowed characters (default `_`)
+    pub fn replacement(mut self, replacement: char) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    /// Convert names to lower case (default false)
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Only allow ASCII letters and digits (default true). If false, unicode letters and digits are kept.
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Limit the length (in characters) of sanitised names, excluding any collision suffix
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Set the separator placed between a name and the numeric suffix used to resolve collisions (default `_`)
+    pub fn collision_separator(mut self, separator: &str) -> Self {
+        self.collision_separator = separator.to_string();
+        self
+    }
+
+    /// Set how channels with the same name are handled by `resolve_channels` (default
+    /// `CollisionPolicy::Number`)
+    pub fn collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Always use `output` for the name `input`. Custom mappings are used as-is, without applying the other rules.
+    pub fn map(mut self, input: &str, output: &str) -> Self {
+        self.mapping.insert(input.to_string(), output.to_string());
+        self
+    }
+
+    /// Sanitise a single name
+    pub fn sanitize(&self, name: &str) -> String {
+        if let Some(mapped) = self.mapping.get(name) {
+            return mapped.clone();
+        }
+
+        let mut sanitized = String::with_capacity(name.len());
+        let mut last_replaced = false;
+
+        for c in name.trim().chars() {
+            let allowed = if self.ascii_only {
+                c.is_ascii_alphanumeric()
+            } else {
+                c.is_alphanumeric()
+            } || c == '-'
+                || c == '_'
+                || c == '.';
+
+            if allowed {
+                sanitized.push(c);
+                last_replaced = false;
+            } else if !last_replaced {
+                sanitized.push(self.replacement);
+                last_replaced = true;
+            }
+        }
+
+        if self.lowercase {
+            sanitized = sanitized.to_lowercase();
+        }
+
+        if let Some(max_length) = self.max_length {
+            sanitized = sanitized.chars().take(max_length).collect();
+        }
+
+        let replacement = self.replacement;
+        let sanitized = sanitized.trim_matches(|c| c == replacement || c == '.');
+
+        if sanitized.is_empty() {
+            return EMPTY_NAME.to_string();
+        }
+
+        let stem = sanitized.split('.').next().unwrap_or(sanitized);
+        if RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+        {
+            return format!("{}{}", sanitized, self.replacement);
+        }
+
+        sanitized.to_string()
+    }
+
+    /// Sanitise the channel label, falling back to the channel name if the label is empty. Custom mappings can
+    /// be specified for either the label or the name.
+    pub fn sanitize_channel(&self, channel: &AcquisitionChannel) -> String {
+        if !self.mapping.contains_key(channel.label()) {
+            if let Some(mapped) = self.mapping.get(channel.name()) {
+                return mapped.clone();
+            }
+        }
+
+        if channel.label().trim().is_empty() {
+            self.sanitize(channel.name())
+        } else {
+            self.sanitize(channel.label())
+        }
+    }
+
+    /// Sanitise all names, appending numeric suffixes where necessary so that the resulting names are unique
+    /// (ignoring case)
+    pub fn unique<S: AsRef<str>>(&self, names: &[S]) -> Vec<String> {
+        let sanitized: Vec<_> = names
+            .iter()
+            .map(|name| self.sanitize(name.as_ref()))
+            .collect();
+
+        self.make_unique(sanitized)
+    }
+
+    /// Sanitise the label (or name) of all channels, appending numeric suffixes where necessary so that the
+    /// resulting names are unique (ignoring case)
+    pub fn unique_channels(&self, channels: &[&AcquisitionChannel]) -> Vec<String> {
+        let sanitized: Vec<_> = channels
+            .iter()
+            .map(|channel| self.sanitize_channel(channel))
+            .collect();
+
+        self.make_unique(sanitized)
+    }
+
+    /// Sanitise the label (or name) of all channels, handling channels with the same name according to the
+    /// collision policy. Channels which are skipped are given no name.
+    pub fn resolve_channels(
+        &self,
+        channels: &[&AcquisitionChannel],
+    ) -> Result<Vec<Option<String>>> {
+        let sanitized: Vec<_> = channels
+            .iter()
+            .map(|channel| self.sanitize_channel(channel))
+            .collect();
+
+        let mut counts = HashMap::with_capacity(sanitized.len());
+        for name in &sanitized {
+            *counts.entry(name.to_lowercase()).or_insert(0) += 1;
+        }
+        let collides = |name: &String| counts[&name.to_lowercase()] > 1;
+
+        match self.collision_policy {
+            CollisionPolicy::Number => {
+                Ok(self.make_unique(sanitized).into_iter().map(Some).collect())
+            }
+            CollisionPolicy::Error => match sanitized.iter().find(|name| collides(name)) {
+                Some(name) => Err(MCDError::ChannelNameCollision { name: name.clone() }),
+                None => Ok(sanitized.into_iter().map(Some).collect()),
+            },
+            CollisionPolicy::Skip => {
+                let mut used = HashSet::with_capacity(sanitized.len());
+
+                Ok(sanitized
+                    .into_iter()
+                    .map(|name| used.insert(name.to_lowercase()).then_some(name))
+                    .collect())
+            }
+            CollisionPolicy::Isotope => {
+                let suffixed = sanitized
+                    .iter()
+                    .zip(channels)
+                    .map(|(name, channel)| {
+                        let isotope = Isotope::parse(channel.name())
+                            .or_else(|| Isotope::parse(channel.label()));
+
+                        match isotope {
+                            Some(isotope) if collides(name) => {
+                                format!("{}{}{}", name, self.collision_separator, isotope)
+                            }
+                            _ => name.clone(),
+                        }
+                    })
+                    .collect();
+
+                Ok(self.make_unique(suffixed).into_iter().map(Some).collect())
+            }
+        }
+    }
+
+    fn make_unique(&self, names: Vec<String>) -> Vec<String> {
+        let mut used = HashSet::with_capacity(names.len());
+
+        names
+            .into_iter()
+            .map(|name| {
+                let mut unique_name = name.clone();
+                let mut suffix = 2;
+
+                while !used.insert(unique_name.to_lowercase()) {
+                    unique_name = format!("{}{}{}", name, self.collision_separator, suffix);
+                    suffix += 1;
+                }
+
+                unique_name
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_collision_policy() {
+        assert_eq!(
+            "Isotope".parse::<CollisionPolicy>().ok(),
+            Some(CollisionPolicy::Isotope)
+        );
+        assert_eq!(
+            "skip".parse::<CollisionPolicy>().ok(),
+            Some(CollisionPolicy::Skip)
+        );
+        assert!(matches!(
+            "rename".parse::<CollisionPolicy>(),
+            Err(MCDError::InvalidCollisionPolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn sanitize_rules() {
+        let sanitizer = NameSanitizer::new();
+
+        assert_eq!(sanitizer.sanitize("  CD8a/b  "), "CD8a_b");
+        assert_eq!(sanitizer.sanitize("IFN-γ"), "IFN-");
+        assert_eq!(sanitizer.sanitize("///"), "unnamed");
+        assert_eq!(sanitizer.sanitize("con"), "con_");
+
+        let sanitizer = NameSanitizer::new().ascii_only(false).lowercase(true);
+        assert_eq!(sanitizer.sanitize("IFN-γ"), "ifn-γ");
+
+        let sanitizer = NameSanitizer::new().max_length(4).collision_separator("-");
+        assert_eq!(
+            sanitizer.unique(&["Collagen", "CollagenI"]),
+            vec!["Coll", "Coll-2"]
+        );
+    }
+
+    #[test]
+    fn collision_policies() -> Result<()> {
+        let channels = [
+            AcquisitionChannel::new(1, 1, 0, "Er(170)", "CD3"),
+            AcquisitionChannel::new(2, 1, 1, "Sm(152)", "CD3"),
+            AcquisitionChannel::new(3, 1, 2, "Ir(191)", "DNA1"),
+        ];
+        let channels: Vec<_> = channels.iter().collect();
+
+        let resolve = |policy| {
+            NameSanitizer::new()
+                .collision_policy(policy)
+                .resolve_channels(&channels)
+        };
+
+        assert_eq!(
+            resolve(CollisionPolicy::Isotope)?,
+            [
+                Some("CD3_Er170".to_string()),
+                Some("CD3_Sm152".to_string()),
+                Some("DNA1".to_string())
+            ]
+        );
+        assert_eq!(
+            resolve(CollisionPolicy::Skip)?,
+            [Some("CD3".to_string()), None, Some("DNA1".to_string())]
+        );
+        assert!(matches!(
+            resolve(CollisionPolicy::Error),
+            Err(MCDError::ChannelNameCollision { name }) if name == "CD3"
+        ));
+
+        Ok(())
+    }
+}
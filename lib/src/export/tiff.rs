@@ -0,0 +1,134 @@
+use std::io::{Seek, Write};
+
+use tiff::{
+    encoder::{colortype::Gray32Float, TiffEncoder},
+    tags::Tag,
+};
+
+use crate::{error::Result, ChannelImage};
+
+use super::{
+    ome::{OmeImage, PixelStorage},
+    world::PixelToSlide,
+};
+
+/// Returns the pixel data of the channel image, padded with NaN (missing) to the full size of the image for
+/// acquisitions which were aborted part way through
+pub(crate) fn image_data(image: &ChannelImage) -> Vec<f32> {
+    let mut data = image.intensities().to_vec();
+    data.resize((image.width() * image.height()) as usize, f32::NAN);
+
+    data
+}
+
+/// GeoTIFF tag holding the affine transformation from raster to model space
+const MODEL_TRANSFORMATION_TAG: u16 = 34264;
+/// GeoTIFF tag holding the GeoKeys
+const GEO_KEY_DIRECTORY_TAG: u16 = 34735;
+/// GeoKey directory (version 1.1.0, 2 keys) describing a user-defined model (the slide) with pixels covering an
+/// area
+const GEO_KEYS: [u16; 12] = [1, 1, 0, 2, 1024, 0, 1, 32767, 1025, 0, 1, 1];
+
+/// Write a single channel image as a 32-bit floating point TIFF, optionally with GeoTIFF tags locating the image
+/// on the slide
+pub(crate) fn write_tiff<W: Write + Seek>(
+    writer: W,
+    image: &ChannelImage,
+    transform: Option<&PixelToSlide>,
+) -> Result<()> {
+    let mut encoder = TiffEncoder::new(writer)?;
+    let mut page = encoder.new_image::<Gray32Float>(image.width(), image.height())?;
+
+    if let Some(transform) = transform {
+        page.encoder().write_tag(
+            Tag::Unknown(MODEL_TRANSFORMATION_TAG),
+            &transform.model_transformation()[..],
+        )?;
+        page.encoder()
+            .write_tag(Tag::Unknown(GEO_KEY_DIRECTORY_TAG), &GEO_KEYS[..])?;
+    }
+
+    page.write_data(&image_data(image))?;
+
+    Ok(())
+}
+
+/// Write all channel images as pages of a single OME-TIFF, with the OME-XML stored in the ImageDescription of
+/// the first page
+pub(crate) fn write_ome_tiff<W: Write + Seek>(
+    writer: W,
+    name: &str,
+    images: &[ChannelImage],
+) -> Result<()> {
+    let mut encoder = TiffEncoder::new(writer)?;
+
+    for (index, image) in images.iter().enumerate() {
+        let mut page = encoder.new_image::<Gray32Float>(image.width(), image.height())?;
+
+        if index == 0 {
+            let channels: Vec<_> = images
+                .iter()
+                .map(|image| (image.label(), image.name()))
+                .collect();
+            let xml = OmeImage::new(name, image.width(), image.height(), &channels)
+                .to_xml(PixelStorage::Tiff);
+
+            page.encoder()
+                .write_tag(Tag::ImageDescription, xml.as_str())?;
+        }
+
+        page.write_data(&image_data(image))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ::tiff::decoder::Decoder;
+
+    use super::*;
+    use crate::Region;
+
+    fn channel_image(label: &str) -> ChannelImage {
+        ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir191".to_string(),
+            label: label.to_string(),
+            range: (0.0, 3.0),
+            valid_pixels: 4,
+            data: vec![0.0, 1.0, 2.0, 3.0],
+        }
+    }
+
+    #[test]
+    fn ome_tiff_pages() {
+        let images = [channel_image("DNA1"), channel_image("CD3 <T cells>")];
+
+        let mut buffer = Cursor::new(Vec::new());
+        write_ome_tiff(&mut buffer, "ROI 1", &images).expect("write OME-TIFF");
+        buffer.set_position(0);
+
+        let mut decoder = Decoder::new(buffer).expect("decode OME-TIFF");
+        assert_eq!(decoder.dimensions().expect("dimensions"), (3, 2));
+
+        let description = decoder
+            .get_tag_ascii_string(Tag::ImageDescription)
+            .expect("OME-XML");
+        assert!(description.contains(r#"SizeC="2""#));
+        assert!(description.contains("CD3 &lt;T cells&gt;"));
+        assert!(description.contains(r#"<M K="Metal">Ir</M><M K="Mass">191</M>"#));
+
+        assert!(decoder.more_images());
+        decoder.next_image().expect("second page");
+        assert!(!decoder.more_images());
+    }
+}
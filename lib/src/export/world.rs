@@ -0,0 +1,105 @@
+use crate::{coords::AcquisitionPixel, Acquisition, Region};
+
+/// Affine transformation from the pixel (column, row) of an exported image to slide coordinates (μm), where
+/// `slide_x = x_scale * column + x_skew * row + x_origin` and `slide_y = y_skew * column + y_scale * row +
+/// y_origin`. The origin is the top left corner of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PixelToSlide {
+    x_scale: f64,
+    y_skew: f64,
+    x_skew: f64,
+    y_scale: f64,
+    x_origin: f64,
+    y_origin: f64,
+}
+
+impl PixelToSlide {
+    /// Returns the transformation for the region of the acquisition, or None if the acquisition has no valid
+    /// transform
+    pub(crate) fn new<R>(acquisition: &Acquisition<R>, region: &Region) -> Option<Self> {
+        let to_slide = |column: f64, row: f64| {
+            AcquisitionPixel::new(region.x as f64 + column, region.y as f64 + row)
+                .to_slide(acquisition)
+        };
+
+        let origin = to_slide(0.0, 0.0)?;
+        let right = to_slide(1.0, 0.0)?;
+        let down = to_slide(0.0, 1.0)?;
+
+        Some(PixelToSlide {
+            x_scale: right.x - origin.x,
+            y_skew: right.y - origin.y,
+            x_skew: down.x - origin.x,
+            y_scale: down.y - origin.y,
+            x_origin: origin.x,
+            y_origin: origin.y,
+        })
+    }
+
+    /// Returns the contents of a world file (e.g. .tfw or .pgw), which locates the centre of the top left pixel
+    pub(crate) fn world_file(&self) -> String {
+        let x_centre = self.x_origin + (self.x_scale + self.x_skew) / 2.0;
+        let y_centre = self.y_origin + (self.y_skew + self.y_scale) / 2.0;
+
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n",
+            self.x_scale, self.y_skew, self.x_skew, self.y_scale, x_centre, y_centre
+        )
+    }
+
+    /// Returns the GeoTIFF ModelTransformationTag (a row-major 4x4 matrix), which maps the top left corner of
+    /// the raster to the slide
+    pub(crate) fn model_transformation(&self) -> [f64; 16] {
+        [
+            self.x_scale,
+            self.x_skew,
+            0.0,
+            self.x_origin,
+            self.y_skew,
+            self.y_scale,
+            0.0,
+            self.y_origin,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ]
+    }
+}
+
+/// Returns the extension of the world file accompanying an image with the specified extension (e.g. `tfw` for
+/// `tiff`), formed from the first and last letters of the extension followed by `w`
+pub(crate) fn world_file_extension(extension: &str) -> String {
+    let mut chars = extension.chars();
+
+    match (chars.next(), chars.last()) {
+        (Some(first), Some(last)) => format!("{}{}w", first, last),
+        _ => format!("{}w", extension),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_file_lines() {
+        let transform = PixelToSlide {
+            x_scale: 1.0,
+            y_skew: 0.0,
+            x_skew: 0.0,
+            y_scale: -1.0,
+            x_origin: 100.0,
+            y_origin: 200.0,
+        };
+
+        assert_eq!(transform.world_file(), "1\n0\n0\n-1\n100.5\n199.5\n");
+        assert_eq!(transform.model_transformation()[3], 100.0);
+        assert_eq!(world_file_extension("tiff"), "tfw");
+        assert_eq!(world_file_extension("png"), "pgw");
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::{Read, Seek, Write};
+
+use crate::{
+    anonymize::AnonymizeOptions, error::Result, AcquisitionIdentifier, AcquisitionRef, MCD,
+};
+
+impl<R: Read + Seek> MCD<R> {
+    /// Write a new .mcd file to `writer` containing only the acquisitions matching `identifiers` (patterns select
+    /// all matching acquisitions), along with the slides and panoramas they belong to, returning the writer.
+    /// Acquisitions are copied with their spectra, channels and optical images, and the data offsets are
+    /// rewritten for the new file. As with `MCD::anonymize_to()`, anything else recorded in the XML is dropped.
+    ///
+    /// ```no_run
+    /// use imc_rs::{AcquisitionIdentifier, MCD};
+    ///
+    /// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+    /// let file = std::fs::File::create("roi_3.mcd").unwrap();
+    ///
+    /// mcd.extract_acquisitions(&[AcquisitionIdentifier::Id(3)], file).unwrap();
+    /// ```
+    pub fn extract_acquisitions<W: Write>(
+        &self,
+        identifiers: &[AcquisitionIdentifier],
+        writer: W,
+    ) -> Result<W> {
+        let mut selected: Vec<AcquisitionRef> = Vec::new();
+
+        for identifier in identifiers {
+            let matching = self.acquisitions_matching(identifier.clone());
+
+            if matching.is_empty() {
+                return Err(self.unknown_acquisition(identifier.clone()));
+            }
+
+            selected.extend(matching.iter().map(|acquisition| acquisition.reference()));
+        }
+
+        self.copy_to(writer, &AnonymizeOptions::keep_all(), Some(&selected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+
+    #[test]
+    fn extracts_single_acquisition() -> Result<()> {
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(SlideSpec::new(1))?;
+
+        for id in 1..=2 {
+            writer.add_panorama(PanoramaSpec::new(id, 1).bounds(0.0, 0.0, 100.0, 100.0))?;
+            writer.add_acquisition(
+                AcquisitionSpec::new(id, id, 1, 1)
+                    .description(&format!("ROI {}", id))
+                    .channel("Ir191", "DNA1"),
+                &[id as f32],
+            )?;
+        }
+
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+        let extracted =
+            mcd.extract_acquisitions(&[AcquisitionIdentifier::Id(2)], Cursor::new(Vec::new()))?;
+        let extracted = MCD::parse(Cursor::new(extracted.into_inner()))?;
+
+        let acquisitions = extracted.acquisitions();
+        assert_eq!(acquisitions.len(), 1);
+        assert_eq!(acquisitions[0].description(), "ROI 2");
+        assert_eq!(acquisitions[0].read_spectra(0, 1)?, [2.0]);
+        let slide = extracted.slide(1).expect("slide is copied");
+        assert!(slide.panorama(1).is_none());
+
+        assert!(mcd
+            .extract_acquisitions(&[AcquisitionIdentifier::Id(5)], Cursor::new(Vec::new()))
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::ChannelImage;
+
+/// Describes how hot pixels (single pixel spikes in intensity, e.g. from detector noise) are removed from a
+/// `ChannelImage`. Each pixel is compared with its (up to) 8 neighbours, ignoring missing pixels and those outside
+/// of the image, and replaced if it exceeds them by more than the threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Despeckle {
+    /// Replace pixels which exceed the median of their neighbours by more than `threshold` with the median
+    Median {
+        /// Difference in intensity above the median of the neighbours at which a pixel is replaced
+        threshold: f32,
+    },
+    /// Replace pixels which exceed the maximum of their neighbours by more than `threshold` with the maximum (as
+    /// in the hot pixel filter of steinbock), which only removes isolated spikes
+    Maximum {
+        /// Difference in intensity above the maximum of the neighbours at which a pixel is replaced
+        threshold: f32,
+    },
+}
+
+impl Despeckle {
+    /// Median filter with the given threshold
+    pub fn median(threshold: f32) -> Self {
+        Despeckle::Median { threshold }
+    }
+
+    /// Maximum filter with the given threshold (50 is typical for IMC data)
+    pub fn maximum(threshold: f32) -> Self {
+        Despeckle::Maximum { threshold }
+    }
+
+    /// Returns the replacement for the intensity `value`, given the intensities of its neighbours, or None if the
+    /// pixel should be kept
+    fn replacement(&self, value: f32, neighbours: &mut [f32]) -> Option<f32> {
+        if value.is_nan() || neighbours.is_empty() {
+            return None;
+        }
+
+        let (reference, threshold) = match *self {
+            Despeckle::Median { threshold } => {
+                neighbours.sort_unstable_by(|a, b| a.total_cmp(b));
+
+                let middle = neighbours.len() / 2;
+                let median = if neighbours.len().is_multiple_of(2) {
+                    (neighbours[middle - 1] + neighbours[middle]) / 2.0
+                } else {
+                    neighbours[middle]
+                };
+
+                (median, threshold)
+            }
+            Despeckle::Maximum { threshold } => (
+                neighbours.iter().copied().fold(f32::MIN, f32::max),
+                threshold,
+            ),
+        };
+
+        (value > reference + threshold).then_some(reference)
+    }
+}
+
+impl ChannelImage {
+    /// Returns a new `ChannelImage` with hot pixels removed using the specified method. Missing pixels remain
+    /// missing. To despeckle every image as it is read, see `MCD::with_despeckle()`.
+    pub fn despeckle(&self, method: Despeckle) -> ChannelImage {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let mut neighbours = Vec::with_capacity(8);
+
+        let data = (0..width * height)
+            .map(|index| {
+                let value = self.pixel(index);
+                let (x, y) = (index % width, index / width);
+
+                neighbours.clear();
+                for neighbour_y in y.saturating_sub(1)..(y + 2).min(height) {
+                    for neighbour_x in x.saturating_sub(1)..(x + 2).min(width) {
+                        if (neighbour_x, neighbour_y) == (x, y) {
+                            continue;
+                        }
+
+                        let neighbour = self.pixel(neighbour_y * width + neighbour_x);
+                        if !neighbour.is_nan() {
+                            neighbours.push(neighbour);
+                        }
+                    }
+                }
+
+                method.replacement(value, &mut neighbours).unwrap_or(value)
+            })
+            .collect();
+
+        self.with_data(data, self.valid_pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec, ChannelIdentifier, MCD};
+
+    use super::*;
+
+    #[test]
+    fn despeckle_hot_pixels() -> Result<()> {
+        #[rustfmt::skip]
+        let spectra = [
+            1.0, 2.0, 1.0,
+            2.0, 100.0, 2.0,
+            1.0, 2.0, 1.0,
+        ];
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 3, 3).channel("Ir191", "DNA1"),
+            spectra,
+        )])?)?;
+        let image = mcd.acquisitions()[0].channel_image(ChannelIdentifier::name("Ir191"), None)?;
+
+        let despeckled = image.despeckle(Despeckle::median(10.0));
+        assert_eq!(despeckled.intensities()[4], 1.5);
+        assert_eq!(despeckled.intensity_range(), (1.0, 2.0));
+
+        let despeckled = image.despeckle(Despeckle::maximum(10.0));
+        assert_eq!(despeckled.intensities()[4], 2.0);
+
+        // Below the threshold, nothing is replaced
+        let despeckled = image.despeckle(Despeckle::median(200.0));
+        assert_eq!(despeckled.intensities(), image.intensities());
+
+        // Applied to every image as it is read
+        let mcd = mcd.with_despeckle(Despeckle::median(10.0));
+        let image = mcd.acquisitions()[0].channel_image(ChannelIdentifier::name("Ir191"), None)?;
+        assert_eq!(image.intensities()[4], 1.5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::{MCDError, Result};
+
+/// Size of the chunks read when searching backwards through the file for the XML metadata
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Maximum number of bytes (e.g. padding) which may follow the XML metadata at the end of the file
+const MAX_TRAILING_BYTES: u64 = 1024 * 1024;
+
+/// Encoding of the XML metadata stored at the end of an .mcd file. Files written by the instrument software use
+/// UTF-16 (little endian), but files rewritten by other tools may use UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum XmlEncoding {
+    Utf16,
+    Utf8,
+}
+
+impl XmlEncoding {
+    fn name(&self) -> &'static str {
+        match self {
+            XmlEncoding::Utf16 => "UTF-16",
+            XmlEncoding::Utf8 => "UTF-8",
+        }
+    }
+
+    fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            XmlEncoding::Utf16 => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            XmlEncoding::Utf8 => text.as_bytes().to_vec(),
+        }
+    }
+
+    fn decode(&self, data: Vec<u8>) -> Result<String> {
+        match self {
+            XmlEncoding::Utf16 => {
+                let units: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect();
+
+                Ok(String::from_utf16(&units)?)
+            }
+            XmlEncoding::Utf8 => String::from_utf8(data).map_err(|error| MCDError::InvalidUtf8 {
+                source: error.utf8_error(),
+            }),
+        }
+    }
+}
+
+/// Find and decode the XML metadata at the end of an .mcd file, from the opening `<MCDSchema` tag to the closing
+/// `</MCDSchema>` tag. Anything before the opening tag (e.g. a byte order mark or the preceding binary data) and
+/// after the closing tag (e.g. padding) is ignored.
+pub(crate) fn read_xml<R: Read + Seek>(reader: &mut R) -> Result<String> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+
+    for encoding in [XmlEncoding::Utf16, XmlEncoding::Utf8] {
+        let end_tag = encoding.encode("</MCDSchema>");
+        let Some(end) = rfind(reader, &end_tag, file_len, MAX_TRAILING_BYTES)? else {
+            continue;
+        };
+        let end = end + end_tag.len() as u64;
+
+        let start = rfind(reader, &encoding.encode("<MCDSchema"), end, end)?.ok_or_else(|| {
+            MCDError::XmlNotFound {
+                found: format!(
+                    "closing </MCDSchema> tag ({}) ends at offset {}, but there is no opening <MCDSchema> tag",
+                    encoding.name(),
+                    end
+                ),
+            }
+        })?;
+
+        let mut data = vec![0; usize::try_from(end - start)?];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut data)?;
+
+        return encoding.decode(data);
+    }
+
+    // Describe the end of the file, to help identify what was written instead
+    let tail_len = file_len.min(16);
+    let mut tail = vec![0; tail_len as usize];
+    reader.seek(SeekFrom::Start(file_len - tail_len))?;
+    reader.read_exact(&mut tail)?;
+
+    Err(MCDError::XmlNotFound {
+        found: format!(
+            "no closing </MCDSchema> tag (UTF-16 or UTF-8) in the last {} bytes, and the file ends with [{}]",
+            file_len.min(MAX_TRAILING_BYTES),
+            tail.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    })
+}
+
+/// Returns the offset of the last occurrence of `needle` which ends at or before `end`, searching at most `limit`
+/// bytes back from `end`
+fn rfind<R: Read + Seek>(
+    reader: &mut R,
+    needle: &[u8],
+    end: u64,
+    limit: u64,
+) -> Result<Option<u64>> {
+    let min_offset = end.saturating_sub(limit);
+    let overlap = needle.len() as u64 - 1;
+    let mut chunk_end = end;
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE as usize);
+
+    while chunk_end - min_offset >= needle.len() as u64 {
+        let chunk_start = chunk_end.saturating_sub(CHUNK_SIZE).max(min_offset);
+
+        chunk.resize((chunk_end - chunk_start) as usize, 0);
+        reader.seek(SeekFrom::Start(chunk_start))?;
+        reader.read_exact(&mut chunk)?;
+
+        if let Some(position) = chunk
+            .windows(needle.len())
+            .rposition(|window| window == needle)
+        {
+            return Ok(Some(chunk_start + position as u64));
+        }
+
+        // Overlap consecutive chunks, so that occurrences spanning the boundary are found
+        if chunk_start == min_offset {
+            break;
+        }
+        chunk_end = chunk_start + overlap;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const XML: &str =
+        "<MCDSchema xmlns=\"http://www.fluidigm.com/IMC/MCDSchema_V2_0.xsd\"></MCDSchema>";
+
+    fn footer(prefix: &[u8], xml: &[u8], suffix: &[u8]) -> Cursor<Vec<u8>> {
+        Cursor::new([prefix, xml, suffix].concat())
+    }
+
+    #[test]
+    fn detects_xml_footer() -> Result<()> {
+        let utf16 = XmlEncoding::Utf16.encode(XML);
+
+        // Preceded by data which happens to be valid UTF-8, and by a byte order mark
+        let data = vec![b'a'; 200_000];
+        assert_eq!(read_xml(&mut footer(&data, &utf16, &[]))?, XML);
+        assert_eq!(read_xml(&mut footer(&[0xff, 0xfe], &utf16, &[]))?, XML);
+
+        // Followed by padding, with the closing tag spanning two chunks
+        let padding = vec![0; CHUNK_SIZE as usize - 3];
+        assert_eq!(read_xml(&mut footer(&data, &utf16, &padding))?, XML);
+
+        // Rewritten as UTF-8 (with a byte order mark)
+        let utf8 = XmlEncoding::Utf8.encode(XML);
+        assert_eq!(
+            read_xml(&mut footer(&[0xef, 0xbb, 0xbf], &utf8, b"\r\n"))?,
+            XML
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn describes_missing_footer() {
+        let result = read_xml(&mut footer(&[], &[], &[]));
+        assert!(matches!(result, Err(MCDError::XmlNotFound { .. })));
+
+        let closing = XmlEncoding::Utf16.encode("</MCDSchema>");
+        match read_xml(&mut footer(&[0; 10], &closing, &[])) {
+            Err(MCDError::XmlNotFound { found }) => {
+                assert!(found.contains("no opening <MCDSchema> tag"), "{}", found)
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
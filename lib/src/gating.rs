@@ -0,0 +1,694 @@
+use std::{io::Write, ops::Not, str::FromStr};
+
+use crate::{
+    error::{channel_not_found, MCDError, Result},
+    segmentation::CellSummary,
+    AcquisitionChannel, ChannelIdentifier,
+};
+
+/// A cell phenotype, described by a gating rule on the mean intensities of the cell's channels
+#[derive(Debug, Clone)]
+pub struct Phenotype {
+    description: String,
+    rule: Rule,
+}
+
+impl Phenotype {
+    /// Create a phenotype with the specified description (e.g. `CD8+ T cell`)
+    pub fn new(description: &str, rule: Rule) -> Self {
+        Phenotype {
+            description: description.to_string(),
+            rule,
+        }
+    }
+
+    /// Returns the description of the phenotype
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the rule describing the phenotype
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Returns whether the cell matches the phenotype, where `channels` are the channels of the acquisition the
+    /// cell was measured in
+    pub fn matches(&self, channels: &[AcquisitionChannel], cell: &CellSummary) -> Result<bool> {
+        self.rule.matches(channels, cell)
+    }
+}
+
+impl AsRef<Rule> for Phenotype {
+    fn as_ref(&self) -> &Rule {
+        &self.rule
+    }
+}
+
+/// Whether intensities should be above or below a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Intensity is above the threshold
+    Above,
+    /// Intensity is below the threshold
+    Below,
+}
+
+/// Whether the threshold itself is included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    /// The threshold is included (`>=`, `<=`)
+    Closed,
+    /// The threshold is excluded (`>`, `<`)
+    Open,
+}
+
+/// Comparison of an intensity with a threshold, for building rules with `Rule::threshold`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+}
+
+impl Comparison {
+    fn direction_and_interval(self) -> (Direction, Interval) {
+        match self {
+            Comparison::Greater => (Direction::Above, Interval::Open),
+            Comparison::GreaterOrEqual => (Direction::Above, Interval::Closed),
+            Comparison::Less => (Direction::Below, Interval::Open),
+            Comparison::LessOrEqual => (Direction::Below, Interval::Closed),
+        }
+    }
+}
+
+/// A gating rule, applied to the mean intensity of channels within a cell.
+///
+/// Rules can be parsed from text, where channels are identified by label (or name, if no channel has the
+/// label), for example `CD3 > 1.5 and (CD4 > 1 or CD8 >= 1) and not CD20 > q0.9`. Thresholds starting with `q`
+/// are quantiles of the channel's mean intensity across the cells being classified (see `classify_cells`).
+///
+/// Rules can also be built in code:
+///
+/// ```
+/// use imc_rs::gating::{Comparison, Rule};
+///
+/// let rule = Rule::threshold("CD3", Comparison::Greater, 1.0)
+///     .and(Rule::threshold("CD8a", Comparison::GreaterOrEqual, 0.5))
+///     .and(!Rule::quantile("CD20", Comparison::Greater, 0.9));
+/// ```
+#[derive(Debug, Clone)]
+pub enum Rule {
+    /// The mean intensity of the channel is above or below the threshold
+    Threshold(ChannelIdentifier, f32, Direction, Interval),
+    /// The mean intensity of the channel is above or below the quantile (between 0 and 1) of the mean intensity
+    /// of the channel across all cells being classified
+    Quantile(ChannelIdentifier, f64, Direction, Interval),
+    /// Both rules match
+    And(Box<Rule>, Box<Rule>),
+    /// Either rule matches
+    Or(Box<Rule>, Box<Rule>),
+    /// The rule doesn't match
+    Not(Box<Rule>),
+}
+
+impl Rule {
+    /// Create a rule comparing the mean intensity of the channel with the label (or name, if no channel has the
+    /// label) to the threshold
+    pub fn threshold(channel: &str, comparison: Comparison, threshold: f32) -> Self {
+        let (direction, interval) = comparison.direction_and_interval();
+
+        Self::Threshold(
+            ChannelIdentifier::label(channel),
+            threshold,
+            direction,
+            interval,
+        )
+    }
+
+    /// Create a rule comparing the mean intensity of the channel with the label (or name, if no channel has the
+    /// label) to the quantile (between 0 and 1) of the channel's mean intensity across the cells being classified
+    pub fn quantile(channel: &str, comparison: Comparison, quantile: f64) -> Self {
+        let (direction, interval) = comparison.direction_and_interval();
+
+        Self::Quantile(
+            ChannelIdentifier::label(channel),
+            quantile,
+            direction,
+            interval,
+        )
+    }
+
+    /// Create a rule matching when both this rule and `other` match
+    pub fn and<B: AsRef<Rule>>(self, other: B) -> Self {
+        Self::And(Box::new(self), Box::new(other.as_ref().clone()))
+    }
+
+    /// Create a rule matching when either this rule or `other` match
+    pub fn or<B: AsRef<Rule>>(self, other: B) -> Self {
+        Self::Or(Box::new(self), Box::new(other.as_ref().clone()))
+    }
+
+    /// Returns whether the cell matches the rule, where `channels` are the channels of the acquisition the cell
+    /// was measured in. Rules containing quantile thresholds must first be resolved against the population of
+    /// cells with `resolve_quantiles` (as is done by `classify_cells`).
+    pub fn matches(&self, channels: &[AcquisitionChannel], cell: &CellSummary) -> Result<bool> {
+        match self {
+            Rule::Threshold(identifier, threshold, direction, interval) => {
+                // We didn't find the channel in the list of channels, so something went wrong
+                let summary = channel_index(channels, identifier)
+                    .and_then(|index| cell.markers().get(index))
+                    .ok_or_else(|| channel_not_found(identifier, channels))?;
+
+                match (direction, interval) {
+                    (Direction::Above, Interval::Closed) => Ok(summary.mean() >= *threshold),
+                    (Direction::Above, Interval::Open) => Ok(summary.mean() > *threshold),
+                    (Direction::Below, Interval::Closed) => Ok(summary.mean() <= *threshold),
+                    (Direction::Below, Interval::Open) => Ok(summary.mean() < *threshold),
+                }
+            }
+            Rule::Quantile(..) => Err(MCDError::InvalidRule {
+                rule: format!("{:?}", self),
+                reason: "quantile thresholds must be resolved against the cells first".to_string(),
+            }),
+            Rule::And(left, right) => {
+                Ok(left.matches(channels, cell)? && right.matches(channels, cell)?)
+            }
+            Rule::Or(left, right) => {
+                Ok(left.matches(channels, cell)? || right.matches(channels, cell)?)
+            }
+            Rule::Not(rule) => Ok(!rule.matches(channels, cell)?),
+        }
+    }
+
+    /// Returns the rule with each quantile threshold replaced by the corresponding mean intensity across the
+    /// `cells` (ignoring cells where the mean is NaN), so that the rule can be applied to individual cells
+    pub fn resolve_quantiles(
+        &self,
+        channels: &[AcquisitionChannel],
+        cells: &[CellSummary],
+    ) -> Result<Rule> {
+        Ok(match self {
+            Rule::Threshold(..) => self.clone(),
+            Rule::Quantile(identifier, quantile, direction, interval) => {
+                if !(0.0..=1.0).contains(quantile) {
+                    return Err(MCDError::InvalidRule {
+                        rule: format!("{:?}", self),
+                        reason: "quantiles must be between 0 and 1".to_string(),
+                    });
+                }
+
+                let index = channel_index(channels, identifier)
+                    .ok_or_else(|| channel_not_found(identifier, channels))?;
+
+                let mut means: Vec<_> = cells
+                    .iter()
+                    .filter_map(|cell| cell.markers().get(index))
+                    .map(|summary| summary.mean())
+                    .filter(|mean| !mean.is_nan())
+                    .collect();
+                means.sort_by(f32::total_cmp);
+
+                Rule::Threshold(
+                    identifier.clone(),
+                    quantile_of(&means, *quantile),
+                    *direction,
+                    *interval,
+                )
+            }
+            Rule::And(left, right) => Rule::And(
+                Box::new(left.resolve_quantiles(channels, cells)?),
+                Box::new(right.resolve_quantiles(channels, cells)?),
+            ),
+            Rule::Or(left, right) => Rule::Or(
+                Box::new(left.resolve_quantiles(channels, cells)?),
+                Box::new(right.resolve_quantiles(channels, cells)?),
+            ),
+            Rule::Not(rule) => Rule::Not(Box::new(rule.resolve_quantiles(channels, cells)?)),
+        })
+    }
+}
+
+impl Not for Rule {
+    type Output = Rule;
+
+    fn not(self) -> Rule {
+        Rule::Not(Box::new(self))
+    }
+}
+
+/// Returns the index of the channel, identified by label falling back to name (as in rules parsed from text)
+fn channel_index(channels: &[AcquisitionChannel], identifier: &ChannelIdentifier) -> Option<usize> {
+    channels
+        .iter()
+        .position(|channel| channel.is(identifier))
+        .or_else(|| match identifier {
+            ChannelIdentifier::Label(label) => {
+                channels.iter().position(|channel| channel.name() == label)
+            }
+            _ => None,
+        })
+}
+
+/// Linearly interpolated quantile of the sorted values, or NaN if there are no values
+fn quantile_of(sorted: &[f32], quantile: f64) -> f32 {
+    if sorted.is_empty() {
+        return f32::NAN;
+    }
+
+    let position = quantile * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = (position - lower as f64) as f32;
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// Classify each of the cells (e.g. from `CellMask::summarise`), returning for each cell whether it matches each
+/// of the phenotypes (in order). `channels` are the channels of the acquisition the cells were measured in, and
+/// quantile thresholds are taken across all of the `cells`.
+pub fn classify_cells(
+    channels: &[AcquisitionChannel],
+    cells: &[CellSummary],
+    phenotypes: &[Phenotype],
+) -> Result<Vec<Vec<bool>>> {
+    let rules = phenotypes
+        .iter()
+        .map(|phenotype| phenotype.rule().resolve_quantiles(channels, cells))
+        .collect::<Result<Vec<_>>>()?;
+
+    cells
+        .iter()
+        .map(|cell| {
+            rules
+                .iter()
+                .map(|rule| rule.matches(channels, cell))
+                .collect()
+        })
+        .collect()
+}
+
+impl AsRef<Rule> for Rule {
+    fn as_ref(&self) -> &Rule {
+        self
+    }
+}
+
+impl FromStr for Rule {
+    type Err = MCDError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let tokens = tokenize(text);
+        let mut parser = RuleParser {
+            text,
+            tokens: &tokens,
+            position: 0,
+        };
+
+        let rule = parser.expression()?;
+
+        match parser.tokens.get(parser.position) {
+            Some(token) => Err(parser.error(&format!("unexpected '{}'", token))),
+            None => Ok(rule),
+        }
+    }
+}
+
+/// Split a rule into tokens: parentheses, comparison operators and words
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let operator = match c {
+            '(' | ')' => Some(c.to_string()),
+            '<' | '>' if chars.peek() == Some(&'=') => {
+                chars.next();
+                Some(format!("{}=", c))
+            }
+            '<' | '>' => Some(c.to_string()),
+            _ => None,
+        };
+
+        if operator.is_some() || c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+
+        tokens.extend(operator);
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Recursive descent parser for rules, where `and` binds more tightly than `or`
+struct RuleParser<'a> {
+    text: &'a str,
+    tokens: &'a [String],
+    position: usize,
+}
+
+impl RuleParser<'_> {
+    fn error(&self, reason: &str) -> MCDError {
+        MCDError::InvalidRule {
+            rule: self.text.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn next_is(&self, keyword: &str) -> bool {
+        self.tokens
+            .get(self.position)
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expression(&mut self) -> Result<Rule> {
+        let mut rule = self.term()?;
+
+        while self.next_is("or") {
+            self.position += 1;
+            rule = Rule::Or(Box::new(rule), Box::new(self.term()?));
+        }
+
+        Ok(rule)
+    }
+
+    fn term(&mut self) -> Result<Rule> {
+        let mut rule = self.factor()?;
+
+        while self.next_is("and") {
+            self.position += 1;
+            rule = Rule::And(Box::new(rule), Box::new(self.factor()?));
+        }
+
+        Ok(rule)
+    }
+
+    fn factor(&mut self) -> Result<Rule> {
+        if self.next_is("not") {
+            self.position += 1;
+            return Ok(!self.factor()?);
+        }
+
+        if self.next_is("(") {
+            self.position += 1;
+            let rule = self.expression()?;
+
+            if !self.next_is(")") {
+                return Err(self.error("missing ')'"));
+            }
+            self.position += 1;
+
+            return Ok(rule);
+        }
+
+        // Channel labels can contain spaces, so take all words up to the comparison operator
+        let mut channel = Vec::new();
+        let (direction, interval) = loop {
+            match self.tokens.get(self.position).map(|token| token.as_str()) {
+                Some(">") => break (Direction::Above, Interval::Open),
+                Some(">=") => break (Direction::Above, Interval::Closed),
+                Some("<") => break (Direction::Below, Interval::Open),
+                Some("<=") => break (Direction::Below, Interval::Closed),
+                Some("(") | Some(")") | None => {
+                    return Err(self.error("expected a comparison (e.g. CD3 > 1.0)"))
+                }
+                Some(token) => channel.push(token),
+            }
+
+            self.position += 1;
+        };
+        self.position += 1;
+
+        if channel.is_empty() {
+            return Err(self.error("missing channel before comparison"));
+        }
+
+        let identifier = ChannelIdentifier::label(unquote(&channel.join(" ")));
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| self.error("expected a numeric threshold"))?;
+        self.position += 1;
+
+        if let Some(quantile) = token.strip_prefix(['q', 'Q']) {
+            let quantile = quantile
+                .parse::<f64>()
+                .ok()
+                .filter(|quantile| (0.0..=1.0).contains(quantile))
+                .ok_or_else(|| self.error("expected a quantile between 0 and 1 (e.g. q0.9)"))?;
+
+            return Ok(Rule::Quantile(identifier, quantile, direction, interval));
+        }
+
+        let threshold = token
+            .parse::<f32>()
+            .map_err(|_| self.error("expected a numeric threshold"))?;
+
+        Ok(Rule::Threshold(identifier, threshold, direction, interval))
+    }
+}
+
+/// Parse phenotypes from a rules file, with one `description: rule` per line, for example
+///
+/// ```text
+/// # Lines starting with # are ignored
+/// T cell: CD3 > 1.0
+/// Cytotoxic T cell: CD3 > 1.0 and CD8a > 0.5
+/// ```
+///
+/// This is a subset of YAML (a mapping from description to rule), so rules can also be stored as .yaml files.
+pub fn parse_rules(text: &str) -> Result<Vec<Phenotype>> {
+    let mut phenotypes = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == "---" {
+            continue;
+        }
+
+        let (description, rule) = line.split_once(':').ok_or_else(|| MCDError::InvalidRule {
+            rule: line.to_string(),
+            reason: "expected 'description: rule'".to_string(),
+        })?;
+
+        phenotypes.push(Phenotype::new(
+            unquote(description.trim()),
+            unquote(rule.trim()).parse()?,
+        ));
+    }
+
+    Ok(phenotypes)
+}
+
+/// Remove matching single or double quotes surrounding the text
+fn unquote(text: &str) -> &str {
+    for quote in ['"', '\''] {
+        if text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote) {
+            return &text[1..text.len() - 1];
+        }
+    }
+
+    text
+}
+
+/// Write the cells as .csv, with one row per cell: the ID, centroid (in pixels), area (in pixels), the mean
+/// intensity of each channel (headed by the channel label) and whether the cell matches each phenotype (1 or 0,
+/// see `classify_cells`)
+pub fn cells_to_csv<W: Write>(
+    writer: W,
+    channels: &[AcquisitionChannel],
+    cells: &[CellSummary],
+    phenotypes: &[Phenotype],
+) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    let mut header = vec![
+        "cell_id".to_string(),
+        "x".to_string(),
+        "y".to_string(),
+        "area".to_string(),
+    ];
+    header.extend(channels.iter().map(|channel| {
+        if channel.label().is_empty() {
+            channel.name().to_string()
+        } else {
+            channel.label().to_string()
+        }
+    }));
+    header.extend(
+        phenotypes
+            .iter()
+            .map(|phenotype| phenotype.description().to_string()),
+    );
+    writer.write_record(&header)?;
+
+    let classifications = classify_cells(channels, cells, phenotypes)?;
+
+    for (cell, classification) in cells.iter().zip(classifications) {
+        let (x, y) = cell.centroid();
+
+        let mut record = vec![
+            cell.id().to_string(),
+            x.to_string(),
+            y.to_string(),
+            cell.num_pixels().to_string(),
+        ];
+        record.extend(
+            cell.markers()
+                .iter()
+                .map(|summary| summary.mean().to_string()),
+        );
+        record.extend(
+            classification
+                .into_iter()
+                .map(|matches| u8::from(matches).to_string()),
+        );
+
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Instant};
+
+    use super::*;
+    use crate::{mcd::synthetic_mcd, segmentation::CellMask, AcquisitionSpec, MCD};
+
+    #[test]
+    fn classify_with_quantiles_and_not() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 4, 1)
+                .channel("Er170", "CD3")
+                .channel("Dy161", "CD20"),
+            [0.0, 3.0, 1.0, 2.0, 2.0, 1.0, 3.0, 0.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        // One cell per pixel
+        let cells = CellMask::new(4, 1, vec![1, 2, 3, 4])?.summarise(acquisition)?;
+
+        let parsed: Rule = "CD3 > q0.5 and not CD20 > 2.5".parse()?;
+        let built = Rule::quantile("CD3", Comparison::Greater, 0.5).and(!Rule::threshold(
+            "CD20",
+            Comparison::Greater,
+            2.5,
+        ));
+
+        let phenotypes = [
+            Phenotype::new("parsed", parsed),
+            Phenotype::new("built", built),
+        ];
+        let classifications = classify_cells(acquisition.channels(), &cells, &phenotypes)?;
+        assert_eq!(
+            classifications,
+            [[false, false], [false, false], [true, true], [true, true]]
+        );
+
+        // Quantiles can't be applied to a single cell without the population
+        assert!(phenotypes[0]
+            .matches(acquisition.channels(), &cells[0])
+            .is_err());
+        assert!("CD3 > q1.5".parse::<Rule>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rule() {
+        let phenotypes =
+            parse_rules("# Example\nT cell: CD3 > 1.5 and (CD4 > 1 or \"Histone H3\" <= 2)\n")
+                .expect("valid rules");
+        assert_eq!(phenotypes.len(), 1);
+        assert_eq!(phenotypes[0].description(), "T cell");
+
+        match phenotypes[0].rule() {
+            Rule::And(left, right) => {
+                assert!(matches!(
+                    left.as_ref(),
+                    Rule::Threshold(_, threshold, Direction::Above, Interval::Open) if *threshold == 1.5
+                ));
+                assert!(matches!(right.as_ref(), Rule::Or(_, _)));
+            }
+            rule => panic!("unexpected rule {:?}", rule),
+        }
+
+        assert!("CD3 >".parse::<Rule>().is_err());
+        assert!("(CD3 > 1".parse::<Rule>().is_err());
+        assert!(parse_rules("CD3 > 1").is_err());
+    }
+
+    #[test]
+    fn test_load() -> Result<()> {
+        let filename = "../test/20200612_FLU_1923.mcd";
+
+        let start = Instant::now();
+        let mcd = MCD::from_path(filename)?;
+        println!("Time taken to parse .mcd: {:?}", start.elapsed());
+
+        let roi_001 = mcd
+            .find_acquisition("ROI_001")
+            .expect("ROI_001 should be present");
+
+        // Available here: https://zenodo.org/record/4139443#.Y2okw0rMLmE
+        let start = Instant::now();
+        let mask = CellMask::from_tiff(File::open("../test/20200612_FLU_1923-01_full_mask.tiff")?)?;
+        let cells = mask.summarise(roi_001)?;
+        println!("Detected {} cells.", cells.len());
+        println!("Time taken to summarise cells: {:?}", start.elapsed());
+
+        // cell types: https://github.com/camlab-bioml/astir/blob/master/tests/test-data/jackson-2020-markers.yml
+
+        let phenotype_histone = Phenotype::new(
+            "Histone+",
+            Rule::Threshold(
+                ChannelIdentifier::label("HistoneH3"),
+                2.0,
+                Direction::Above,
+                Interval::Open,
+            ),
+        );
+
+        let phenotype_cd16 = Phenotype::new(
+            "CD16+",
+            Rule::Threshold(
+                ChannelIdentifier::label("CD16"),
+                1.0,
+                Direction::Above,
+                Interval::Open,
+            ),
+        );
+
+        let combined = Phenotype::new(
+            "combined",
+            phenotype_histone.rule().clone().and(&phenotype_cd16),
+        );
+
+        let mut csv = Vec::new();
+        cells_to_csv(
+            &mut csv,
+            roi_001.channels(),
+            &cells,
+            &[phenotype_histone, phenotype_cd16, combined],
+        )?;
+
+        Ok(())
+    }
+}
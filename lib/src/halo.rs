@@ -0,0 +1,17 @@
+use std::{io::Read, path::Path};
+
+pub use crate::celltable::{
+    BoundariesIterator, Cell, CellData, CellTableOptions, Column, ColumnData, ColumnType,
+};
+use crate::{celltable, error::Result};
+
+/// Parse output from HALO cell detection stored as a .csv file, returning a representation of the cell data. Use
+/// `celltable::parse_from_path` with `CellTableOptions::halo()` to also specify the types of some columns.
+pub fn parse_from_path<P: AsRef<Path>>(path: P) -> Result<CellData> {
+    celltable::parse_from_path(path, &CellTableOptions::halo())
+}
+
+/// Parse output from HALO cell detection stored as a .csv file (see [`parse_from_path`])
+pub fn parse<R: Read>(reader: R) -> Result<CellData> {
+    celltable::parse(reader, &CellTableOptions::halo())
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use crate::{Acquisition, AcquisitionChannel, AcquisitionRef, MCD};
+
+/// Ordered view of the acquisitions in an .mcd file (see [`MCD::acquisition_map`]). Acquisitions are ordered by
+/// ID, and acquisitions with the same ID on different slides by slide and panorama.
+pub struct AcquisitionMap<'a, R> {
+    mcd: &'a MCD<R>,
+    references: &'a [AcquisitionRef],
+}
+
+impl<'a, R> AcquisitionMap<'a, R> {
+    pub(crate) fn new(mcd: &'a MCD<R>, references: &'a [AcquisitionRef]) -> Self {
+        AcquisitionMap { mcd, references }
+    }
+
+    /// Returns the number of acquisitions
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    /// Returns true if there are no acquisitions
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    /// Returns the references to all acquisitions, in order
+    pub fn references(&self) -> &'a [AcquisitionRef] {
+        self.references
+    }
+
+    /// Returns the acquisition with the given reference, or None if no such acquisition exists
+    pub fn get(&self, reference: AcquisitionRef) -> Option<&'a Acquisition<R>> {
+        find(self.mcd, reference)
+    }
+
+    /// Returns an iterator over the acquisitions, in order
+    pub fn iter(&self) -> impl Iterator<Item = &'a Acquisition<R>> + 'a {
+        let mcd = self.mcd;
+
+        self.references.iter().map(move |&reference| {
+            find(mcd, reference).expect("Index only contains acquisitions present in the .mcd file")
+        })
+    }
+}
+
+fn find<R>(mcd: &MCD<R>, reference: AcquisitionRef) -> Option<&Acquisition<R>> {
+    mcd.slide(reference.slide())?
+        .panorama(reference.panorama())?
+        .acquisition(reference.id())
+}
+
+/// Index of the channels acquired across all acquisitions in an .mcd file (see [`MCD::channel_index`]), with
+/// one entry per channel name, ordered by label and then name
+#[derive(Debug, Clone, Default)]
+pub struct ChannelIndex {
+    channels: Vec<AcquisitionChannel>,
+    positions: HashMap<String, usize>,
+}
+
+impl ChannelIndex {
+    pub(crate) fn new<R>(mcd: &MCD<R>) -> Self {
+        let mut unique = HashMap::new();
+
+        // This should be unnecessary - hopefully there is only one set of channels per dataset?
+        for acquisition in mcd.acquisition_map().iter() {
+            for channel in acquisition.channels() {
+                unique
+                    .entry(channel.name())
+                    .or_insert_with(|| channel.clone());
+            }
+        }
+
+        let mut channels: Vec<_> = unique.into_values().collect();
+        channels.sort_by(|a, b| [a.label(), a.name()].cmp(&[b.label(), b.name()]));
+
+        let positions = channels
+            .iter()
+            .enumerate()
+            .map(|(position, channel)| (channel.name().to_string(), position))
+            .collect();
+
+        ChannelIndex {
+            channels,
+            positions,
+        }
+    }
+
+    /// Returns the number of channels
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Returns true if there are no channels
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Returns all channels, in order
+    pub fn channels(&self) -> &[AcquisitionChannel] {
+        &self.channels
+    }
+
+    /// Returns the channel with the given name (e.g. Ir191)
+    pub fn get(&self, name: &str) -> Option<&AcquisitionChannel> {
+        self.position(name).map(|position| &self.channels[position])
+    }
+
+    /// Returns the position of the channel with the given name within [`ChannelIndex::channels`]
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.positions.get(name).copied()
+    }
+
+    /// Returns an iterator over the channels, in order
+    pub fn iter(&self) -> std::slice::Iter<'_, AcquisitionChannel> {
+        self.channels.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec};
+
+    use super::*;
+
+    #[test]
+    fn ordered_indexes() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([
+            (
+                AcquisitionSpec::new(2, 1, 1, 1)
+                    .channel("Ir191", "DNA1")
+                    .channel("Er170", "CD3"),
+                vec![1.0, 2.0],
+            ),
+            (
+                AcquisitionSpec::new(1, 1, 1, 1).channel("Ir191", "DNA1"),
+                vec![3.0],
+            ),
+        ])?)?;
+
+        let acquisitions = mcd.acquisition_map();
+        assert_eq!(acquisitions.len(), 2);
+        let ids: Vec<_> = acquisitions
+            .iter()
+            .map(|acquisition| acquisition.id())
+            .collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(
+            acquisitions
+                .get(AcquisitionRef::new(1, 1, 2))
+                .map(|acquisition| acquisition.id()),
+            Some(2)
+        );
+        assert!(acquisitions.get(AcquisitionRef::new(1, 1, 3)).is_none());
+
+        let channels = mcd.channel_index();
+        let names: Vec<_> = channels.iter().map(|channel| channel.name()).collect();
+        assert_eq!(names, ["Er170", "Ir191"]);
+        assert_eq!(channels.position("Ir191"), Some(1));
+        assert_eq!(
+            channels.get("Er170").map(|channel| channel.label()),
+            Some("CD3")
+        );
+
+        // The indexes are built once and then reused
+        assert!(std::ptr::eq(mcd.channel_index(), channels));
+        assert_eq!(mcd.channels().len(), 2);
+
+        Ok(())
+    }
+}
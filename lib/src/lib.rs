@@ -25,46 +25,147 @@
 //! }
 //! ```
 
+/// Async readers, so that IMC data can be served without blocking the async runtime (requires the `async` feature)
+#[cfg(feature = "async")]
+pub mod asynchronous;
+/// Statistics and other queries across cohorts of .mcd files, processed in parallel (requires the `fs` feature)
+#[cfg(feature = "fs")]
+pub mod batch;
+/// Global configuration (e.g. number of threads used for parallel processing).
+pub mod config;
 /// Convert .mcd file to .dcm file for faster access to data.
 pub mod convert;
+/// Typed points in each coordinate frame (acquisition, panorama, slide and overview) and conversions between them
+pub mod coords;
+/// Structured descriptions of IMC data (e.g. acquisitions), rendered for people or as JSON
+pub mod describe;
 /// Errors associated with parsing IMC data
 pub mod error;
+/// Export of channel images to other formats (e.g. TIFF, OME-TIFF)
+pub mod export;
+/// Filters for removing artefacts (e.g. hot pixels) from channel images
+pub mod filter;
+/// Gating of segmented cells into phenotypes using rules on channel intensities
+pub mod gating;
 pub(crate) mod mcd;
+/// Normalisation and scaling of channel intensities
+pub mod normalization;
+mod pattern;
+/// Preprocessing of channel images, such as subtracting the background measured in the background channels
+pub mod preprocessing;
+mod reader;
+/// Landmark based registration of the slide with external images, and conversion to ITK/elastix transforms
+pub mod register;
+/// Rendering of channel images (e.g. pseudocolour using colormaps, multi-channel composites, `image` crate buffers)
+pub mod render;
+/// Import of cell segmentation masks and per-cell summaries of channel intensities
+pub mod segmentation;
+/// Mergeable channel statistics, for deriving display ranges of stitched views without re-reading pixels
+pub mod statistics;
+/// Fixed size tiles of each channel at multiple resolutions (a pyramid), for deep-zoom viewers
+pub mod tiles;
+/// Timing spans around expensive operations (e.g. parsing and .dcm conversion), reported to a hook installed by
+/// the application
+pub mod trace;
 /// Transformations (e.g. affine) used for converting
 pub mod transform;
+/// Validation of the XML metadata against the known versions of the MCDSchema XSD
+pub mod validation;
 
+mod ablation;
 mod acquisition;
+mod anonymize;
+mod arithmetic;
+mod cache;
 mod calibration;
 mod channel;
+mod correction;
+mod event;
+mod extract;
+mod footer;
+mod index;
+mod metadata;
+mod panel;
 mod panorama;
+mod parse_report;
+mod plume;
+mod presence;
+mod random_access;
+mod read_plan;
+mod retry;
+mod roi;
+mod sampling;
 mod slide;
-
+mod span;
+mod spatial;
+mod stitch;
+mod tiling;
+mod xml_tree;
+
+/// Provides methods for reading in tables of cell segmentation data (e.g. from HALO, CellProfiler or steinbock)
+pub mod celltable;
 /// Provides methods for reading in cell segmentation data from Halo
 pub mod halo;
 
-pub use self::acquisition::{Acquisition, AcquisitionIdentifier, Acquisitions};
-pub use self::channel::{AcquisitionChannel, ChannelIdentifier};
-pub use self::panorama::Panorama;
-pub use self::slide::Slide;
+pub use self::acquisition::{
+    Acquisition, AcquisitionIdentifier, AcquisitionPattern, AcquisitionRef, AcquisitionSuggestion,
+    Acquisitions, DataFormat,
+};
+pub use self::anonymize::AnonymizeOptions;
+pub use self::cache::ChannelCache;
+pub use self::channel::{AcquisitionChannel, ChannelIdentifier, ChannelSummary, Isotope};
+pub use self::convert::{Bookmark, BookmarkChannel};
+pub use self::correction::{ChannelCorrection, ChannelCorrections};
+pub use self::event::{InstrumentEvent, InstrumentEventCategory};
+pub use self::index::{AcquisitionMap, ChannelIndex};
+pub use self::mcd::{AcquisitionSpec, MCDWriter, PanoramaSpec, ParseOptions, SlideSpec};
+pub use self::metadata::{
+    AcquisitionMetadata, CalibrationChannelMetadata, CalibrationMetadata, ChannelMetadata,
+    McdMetadata, PanoramaMetadata, RoiMetadata, SlideMetadata,
+};
+pub use self::panel::{Panel, PanelChannel, PanelDifference};
+pub use self::panorama::{Panorama, PanoramaType};
+pub use self::parse_report::{ParseReport, RecoveredField};
+pub use self::plume::PlumeWindow;
+pub use self::presence::ChannelPresence;
+pub use self::random_access::{ReadAt, ReadAtReader};
+pub use self::read_plan::{PlannedRead, ReadPlan};
+pub use self::retry::{RetryPolicy, RetryingReader};
+pub use self::roi::{RoiPoint, RoiShape};
+pub use self::sampling::SlideSample;
+pub use self::slide::{OverviewOptions, Slide};
+pub use self::span::ByteSpan;
+pub use self::stitch::{OverlapMode, StitchedImage};
+pub use self::tiling::{Tile, Tiling};
+pub use self::xml_tree::{parse_xml_tree, XmlElement};
 
 use error::{MCDError, Result};
+use filter::Despeckle;
 use image::io::Reader as ImageReader;
-use std::convert::TryInto;
 use std::fmt;
+#[cfg(feature = "fs")]
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 
 use std::ops::DerefMut;
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 
 use std::collections::HashMap;
 
 use calibration::{Calibration, CalibrationChannel, CalibrationFinal, CalibrationParams};
+use describe::{Describe, Description, Table};
 use mcd::{MCDParser, ParserState};
+use parse_report::Stopwatch;
+use reader::ReaderPool;
 
-use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use image::{
+    imageops::FilterType, DynamicImage, ImageFormat, ImageOutputFormat, RgbImage, RgbaImage,
+};
 use slide::{SlideFiducialMarks, SlideProfile};
+use span::IMAGE_HEADER_SIZE;
 use transform::AffineTransform;
 
 /// Print to `writer` trait
@@ -73,14 +174,21 @@ pub trait Print {
     fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result;
 }
 
+impl<T: Describe> Print for T {
+    fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
+        self.describe().write_text(writer, indent)
+    }
+}
+
 /// Represents property of having an optical image
 pub struct OpticalImage<R> {
-    reader: Arc<Mutex<BufReader<R>>>,
+    reader: Arc<ReaderPool<R>>,
 
-    // TODO: Why are we using i64 here?
-    start_offset: i64,
-    end_offset: i64,
+    // Span of the image in the file, including the header
+    span: ByteSpan,
     image_format: ImageFormat,
+    // Description of the image for errors (e.g. "Slide 1 image")
+    element: String,
 }
 
 impl<R: Read + Seek> OpticalImage<R> {
@@ -88,19 +196,13 @@ impl<R: Read + Seek> OpticalImage<R> {
     //fn has_image(&self) -> bool;
     /// Returns the binary data for the image, exactly as stored in the .mcd file
     pub fn image_data(&self) -> Result<Vec<u8>> {
-        let mut reader = self.reader.lock().or(Err(MCDError::PoisonMutex))?;
+        let mut reader = self.reader.get()?;
+        self.check_in_bounds(reader.deref_mut())?;
 
-        let start_offset = self.start_offset();
-        let image_size = self
-            .image_size()
-            .try_into()
-            .or(Err(MCDError::InvalidOffset {
-                offset: start_offset,
-            }))?;
+        let image_span = self.image_span();
+        let mut buf_u8 = vec![0; image_span.len_usize()?];
 
-        let mut buf_u8 = vec![0; image_size];
-
-        match reader.seek(SeekFrom::Start(start_offset as u64)) {
+        match reader.seek(SeekFrom::Start(image_span.start())) {
             Ok(_seek) => match reader.read_exact(&mut buf_u8) {
                 Ok(()) => Ok(buf_u8),
                 Err(error) => Err(error.into()),
@@ -111,11 +213,11 @@ impl<R: Read + Seek> OpticalImage<R> {
 
     /// Returns the dimensions of the images in pixels as a tuple (width, height)
     pub fn dimensions(&self) -> Result<(u32, u32)> {
-        let mut guard = self.reader.lock().or(Err(MCDError::PoisonMutex))?;
+        let mut guard = self.reader.get()?;
         let reader: &mut BufReader<R> = guard.deref_mut();
+        self.check_in_bounds(reader)?;
 
-        let start_offset = self.start_offset();
-        reader.seek(SeekFrom::Start(start_offset as u64))?;
+        reader.seek(SeekFrom::Start(self.image_span().start()))?;
 
         let mut reader = ImageReader::new(reader);
         reader.set_format(self.image_format());
@@ -136,11 +238,66 @@ impl<R: Read + Seek> OpticalImage<R> {
         Ok(self.dynamic_image()?.into_rgb8())
     }
 
-    fn dynamic_image(&self) -> Result<DynamicImage> {
-        let mut reader = self.reader.lock().or(Err(MCDError::PoisonMutex))?;
-        let start_offset = self.start_offset();
+    /// Returns the image re-encoded as PNG, downscaled (preserving the aspect ratio) so that neither side exceeds
+    /// `max_dimension` pixels if specified. Any metadata stored with the original image (e.g. EXIF) is not
+    /// included.
+    pub fn to_png_bytes(&self, max_dimension: Option<u32>) -> Result<Vec<u8>> {
+        self.encode(ImageOutputFormat::Png, max_dimension)
+    }
+
+    /// Returns the image re-encoded as JPEG with the specified quality (1-100), downscaled as in
+    /// [`OpticalImage::to_png_bytes`]. Any transparency is discarded, as is any metadata stored with the
+    /// original image.
+    pub fn to_jpeg_bytes(&self, quality: u8, max_dimension: Option<u32>) -> Result<Vec<u8>> {
+        self.encode(
+            ImageOutputFormat::Jpeg(quality.clamp(1, 100)),
+            max_dimension,
+        )
+    }
+
+    /// Save the image to `path` in the specified format (e.g. `ImageFormat::Tiff`), downscaled as in
+    /// [`OpticalImage::to_png_bytes`]. Any metadata stored with the original image is not included.
+    #[cfg(feature = "fs")]
+    pub fn save_as<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ImageFormat,
+        max_dimension: Option<u32>,
+    ) -> Result<()> {
+        let data = self.encode(ImageOutputFormat::from(format), max_dimension)?;
+        std::fs::write(path, data)?;
+
+        Ok(())
+    }
+
+    fn encode(&self, format: ImageOutputFormat, max_dimension: Option<u32>) -> Result<Vec<u8>> {
+        let mut image = self.dynamic_image()?;
+
+        if let Some(max_dimension) = max_dimension {
+            if image.width() > max_dimension || image.height() > max_dimension {
+                image = image.resize(max_dimension, max_dimension, FilterType::Triangle);
+            }
+        }
 
-        match reader.seek(SeekFrom::Start(start_offset as u64)) {
+        // JPEG has no alpha channel
+        if let ImageOutputFormat::Jpeg(_) = format {
+            image = DynamicImage::ImageRgb8(image.into_rgb8());
+        }
+
+        let mut data = Cursor::new(Vec::new());
+        image.write_to(&mut data, format)?;
+
+        Ok(data.into_inner())
+    }
+
+    pub(crate) fn dynamic_image(&self) -> Result<DynamicImage> {
+        let mut span = trace::span(trace::Operation::DecodeImage, || self.element.clone());
+        span.record("bytes", self.image_span().len());
+
+        let mut reader = self.reader.get()?;
+        self.check_in_bounds(reader.deref_mut())?;
+
+        match reader.seek(SeekFrom::Start(self.image_span().start())) {
             Ok(_seek) => {
                 let mut reader = ImageReader::new(reader.deref_mut());
                 reader.set_format(self.image_format);
@@ -148,20 +305,30 @@ impl<R: Read + Seek> OpticalImage<R> {
                 // Remove the limits here, as it is possible that the images are larger than 512 MB
                 reader.no_limits();
 
-                Ok(reader.decode()?)
+                let image = reader.decode()?;
+                span.record("width", image.width());
+                span.record("height", image.height());
+
+                Ok(image)
             }
             Err(error) => Err(error.into()),
         }
     }
+
+    fn check_in_bounds<S: Seek>(&self, reader: &mut S) -> Result<()> {
+        reader::check_in_bounds(reader, || self.element.clone(), self.span.end())
+    }
 }
 
 impl<R> OpticalImage<R> {
-    fn start_offset(&self) -> i64 {
-        self.start_offset + 161
+    /// Returns the span of the image data in the file, excluding the header
+    pub fn image_span(&self) -> ByteSpan {
+        self.span.skip(IMAGE_HEADER_SIZE)
     }
 
-    fn image_size(&self) -> i64 {
-        self.end_offset - self.start_offset()
+    /// Returns true if image data is stored in the file
+    pub(crate) fn is_stored(&self) -> bool {
+        !self.image_span().is_empty()
     }
 
     /// Returns the format of the stored optical image
@@ -170,7 +337,8 @@ impl<R> OpticalImage<R> {
     }
 }
 
-/// Represents an image which is acquired on a slide
+/// Represents an image which is acquired on a slide. The transform maps into a pixel frame with the y-axis
+/// flipped relative to the image rows, so prefer the typed conversions in [`coords`] to using it directly.
 pub trait OnSlide {
     /// Returns the bounding box encompasing the image area on the slide (in μm)
     fn slide_bounding_box(&self) -> BoundingBox<f64>;
@@ -179,7 +347,7 @@ pub trait OnSlide {
 }
 
 /// Represents a region of an image (in pixels)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Region {
     /// x-position of the top left corner of the region
     pub x: u32,
@@ -191,11 +359,56 @@ pub struct Region {
     pub height: u32,
 }
 
+/// Describes which pixels of an image were acquired. Pixels are acquired row by row, so if the acquisition was
+/// aborted the acquired pixels are a number of complete rows followed by a partial row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidRegion {
+    full_rows: u32,
+    partial_row_width: u32,
+    width: u32,
+}
+
+impl ValidRegion {
+    pub(crate) fn new(full_rows: u32, partial_row_width: u32, width: u32) -> Self {
+        ValidRegion {
+            full_rows,
+            partial_row_width,
+            width,
+        }
+    }
+
+    /// Returns the number of rows which were completely acquired
+    pub fn full_rows(&self) -> u32 {
+        self.full_rows
+    }
+
+    /// Returns the number of pixels acquired in the partial row (the row after the last full row), or 0 if
+    /// the acquisition stopped at the end of a row
+    pub fn partial_row_width(&self) -> u32 {
+        self.partial_row_width
+    }
+
+    /// Returns the total number of pixels acquired
+    pub fn num_pixels(&self) -> usize {
+        self.full_rows as usize * self.width as usize + self.partial_row_width as usize
+    }
+
+    /// Returns whether the pixel at (x, y) was acquired
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width
+            && (y < self.full_rows || (y == self.full_rows && x < self.partial_row_width))
+    }
+}
+
+/// Maximum number of acquisitions suggested when an identifier doesn't match any acquisition
+const MAX_SUGGESTIONS: usize = 3;
+
 /// Represents a imaging mass cytometry (*.mcd) file.
 #[derive(Debug)]
 pub struct MCD<R> {
-    reader: Arc<Mutex<std::io::BufReader<R>>>,
+    reader: Arc<ReaderPool<R>>,
     location: Option<PathBuf>,
+    read_only: bool,
 
     xmlns: Option<String>,
 
@@ -210,33 +423,59 @@ pub struct MCD<R> {
     calibrations: HashMap<u16, Calibration>,
     slide_fiducal_marks: HashMap<u16, SlideFiducialMarks>,
     slide_profiles: HashMap<u16, SlideProfile>,
-}
 
-fn find_mcd_start(chunk: &[u8], chunk_size: usize) -> usize {
-    for start_index in 0..chunk_size {
-        if let Ok(_data) = std::str::from_utf8(&chunk[start_index..]) {
-            return start_index - 1;
-        }
-    }
+    channel_cache: Option<Arc<ChannelCache>>,
 
-    0
+    // Built on first use, and reset whenever the slides are modified
+    acquisition_order: OnceLock<Vec<AcquisitionRef>>,
+    channel_index: OnceLock<ChannelIndex>,
+
+    parse_report: ParseReport,
 }
 
-fn u16_from_u8(a: &mut [u16], v: &[u8]) {
-    for i in 0..a.len() {
-        a[i] = (v[i * 2] as u16) | ((v[i * 2 + 1] as u16) << 8)
+impl<B: AsRef<[u8]>> MCD<Cursor<B>> {
+    /// Parse an .mcd file held in memory (e.g. a `Vec<u8>`, or a `&[u8]` such as the contents of a file uploaded
+    /// to a browser). This is available without the `fs` feature, for use in WebAssembly
+    /// (`wasm32-unknown-unknown`). To read only the parts of a large file which are needed, see `ReadAtReader`.
+    pub fn from_bytes(bytes: B) -> Result<Self> {
+        MCD::parse(Cursor::new(bytes))
     }
 }
 
+#[cfg(feature = "fs")]
 impl MCD<File> {
     /// Open an .mcd file from the specified path.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<MCD<File>> {
-        let mut mcd = MCD::parse(File::open(&path)?)?;
+        MCD::from_path_with_options(path, &ParseOptions::default())
+    }
+
+    /// Open an .mcd file from the specified path, parsing with the specified options (e.g. failing on unknown
+    /// tags, see [`ParseOptions`]).
+    pub fn from_path_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<MCD<File>> {
+        // Additional file handles are opened as needed, so that reads from multiple threads don't block each other
+        let file_path = path.as_ref().to_path_buf();
+        let pool = ReaderPool::with_opener(File::open(&path)?, move || File::open(&file_path));
+
+        let mut mcd = MCD::parse_pool(pool, options)?;
         mcd.set_location(path);
 
         Ok(mcd)
     }
 
+    /// Open an .mcd file from the specified path in read-only mode, which guarantees that no sidecar files are
+    /// created or modified. An existing, valid .dcm file is still used by [`MCD::with_dcm`], but any operation
+    /// which would need to create, regenerate or write to it (e.g. adding a bookmark) returns
+    /// [`MCDError::ReadOnly`]. This is intended for data on archival (or shared) storage.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<MCD<File>> {
+        let mut mcd = MCD::from_path(path)?;
+        mcd.read_only = true;
+
+        Ok(mcd)
+    }
+
     /// Returns the location (path) of the .mcd file
     pub fn location(&self) -> Option<&Path> {
         Some(self.location.as_ref()?.as_path())
@@ -269,26 +508,114 @@ impl MCD<File> {
     ///
     /// If the location is not set either automatically via [`MCD::from_path`] or manually via [`MCD::set_location`] then a [`MCDError::LocationNotSpecified`]
     /// will occur.
-    pub fn with_dcm(mut self) -> Result<Self> {
-        if std::fs::metadata(self.dcm_file().ok_or(MCDError::LocationNotSpecified)?).is_err() {
-            let dcm_file =
-                std::fs::File::create(self.dcm_file().ok_or(MCDError::LocationNotSpecified)?)?;
-            let mut dcm_file = BufWriter::new(dcm_file);
+    pub fn with_dcm(self) -> Result<Self> {
+        self.with_dcm_with_progress(&(), &convert::CancellationToken::new())
+    }
 
-            convert::convert(&self, &mut dcm_file)?;
-        }
+    /// Use a temporary file for faster access to channel images (see [`MCD::with_dcm`]), reporting progress
+    /// while the file is created. Creating the file can be cancelled with `cancel`, in which case the partially
+    /// written file is removed and [`MCDError::Cancelled`] is returned.
+    ///
+    /// An existing file is regenerated if it was written by a different version of the library, or if the .mcd
+    /// file has changed since it was created (see [`convert::open_with_options`]).
+    pub fn with_dcm_with_progress(
+        self,
+        progress: &dyn convert::Progress,
+        cancel: &convert::CancellationToken,
+    ) -> Result<Self> {
+        self.with_dcm_with_options(&convert::ConvertOptions::default(), progress, cancel)
+    }
 
-        convert::open(&mut self)?;
+    /// Use a temporary file for faster access to channel images (see [`MCD::with_dcm_with_progress`]), creating
+    /// the file with the specified options (e.g. a different compression codec, see [`convert::ConvertOptions`]).
+    pub fn with_dcm_with_options(
+        mut self,
+        options: &convert::ConvertOptions,
+        progress: &dyn convert::Progress,
+        cancel: &convert::CancellationToken,
+    ) -> Result<Self> {
+        convert::open_with_options(&mut self, options, progress, cancel)?;
 
         Ok(self)
     }
+
+    /// Returns the bookmarks stored in the .dcm file, or an empty list if there are none (or the .dcm file has
+    /// not been created, see [`MCD::with_dcm`])
+    pub fn bookmarks(&self) -> Result<Vec<Bookmark>> {
+        let dcm_path = self.dcm_file().ok_or(MCDError::LocationNotSpecified)?;
+
+        match File::open(dcm_path) {
+            Ok(dcm_file) => convert::read_bookmarks(&mut BufReader::new(dcm_file)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Store the bookmark in the .dcm file, replacing any existing bookmark with the same name. The .dcm file
+    /// must already have been created (see [`MCD::with_dcm`]). Returns `MCDError::InvalidBookmark` (leaving the
+    /// stored bookmarks unchanged) if the bookmark can't be stored, e.g. a name is longer than 65535 bytes.
+    pub fn add_bookmark(&self, bookmark: Bookmark) -> Result<()> {
+        let mut bookmarks = self.bookmarks()?;
+        bookmarks.retain(|existing| existing.name() != bookmark.name());
+        bookmarks.push(bookmark);
+
+        self.write_bookmarks(&bookmarks)
+    }
+
+    /// Remove the bookmark with the specified name from the .dcm file, returning whether it was present
+    pub fn remove_bookmark(&self, name: &str) -> Result<bool> {
+        let mut bookmarks = self.bookmarks()?;
+        let num_bookmarks = bookmarks.len();
+        bookmarks.retain(|existing| existing.name() != name);
+
+        if bookmarks.len() == num_bookmarks {
+            return Ok(false);
+        }
+
+        self.write_bookmarks(&bookmarks)?;
+
+        Ok(true)
+    }
+
+    /// Precompute the downsampled levels of the pyramid of every channel of every acquisition (see
+    /// [`tiles`]) and store them in the .dcm file, so that tiles of these levels are read rather than computed
+    /// on request. The .dcm file is created if needed (see [`MCD::with_dcm`]). Any previously stored pyramid
+    /// is replaced, and the pyramid is discarded if the .dcm file is regenerated or repaired.
+    pub fn precompute_pyramid(self) -> Result<Self> {
+        self.check_writable("precomputing the pyramid")?;
+
+        let mcd = self.with_dcm()?;
+        let dcm_path = mcd.dcm_file().ok_or(MCDError::LocationNotSpecified)?;
+
+        convert::write_pyramid(&dcm_path, &mcd)?;
+
+        // Reopen the .dcm file to read the index of the pyramid
+        mcd.with_dcm()
+    }
+
+    fn write_bookmarks(&self, bookmarks: &[Bookmark]) -> Result<()> {
+        self.check_writable("writing bookmarks")?;
+
+        let dcm_path = self.dcm_file().ok_or(MCDError::LocationNotSpecified)?;
+        let mut dcm_file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(dcm_path)?;
+
+        let length = convert::write_bookmarks(&mut dcm_file, bookmarks)?;
+        dcm_file.set_len(length)?;
+        dcm_file.sync_all()?;
+
+        Ok(())
+    }
 }
 
 impl<R: Read + Seek> MCD<R> {
-    fn new(reader: R) -> Self {
+    fn new(reader: ReaderPool<R>) -> Self {
         MCD {
-            reader: Arc::new(Mutex::new(BufReader::new(reader))),
+            reader: Arc::new(reader),
             location: None,
+            read_only: false,
             xmlns: None,
             slides: HashMap::new(),
             //panoramas: HashMap::new(),
@@ -302,42 +629,59 @@ impl<R: Read + Seek> MCD<R> {
             calibrations: HashMap::new(),
             slide_fiducal_marks: HashMap::new(),
             slide_profiles: HashMap::new(),
+            channel_cache: None,
+            acquisition_order: OnceLock::new(),
+            channel_index: OnceLock::new(),
+            parse_report: ParseReport::default(),
         }
     }
 
     /// Parse *.mcd format
     pub fn parse(reader: R) -> Result<Self> {
-        let mcd = MCD::new(reader);
-        let combined_xml = mcd.xml()?;
+        MCD::parse_with_options(reader, &ParseOptions::default())
+    }
 
-        // let mut file = std::fs::File::create("tmp.xml").unwrap();
-        // file.write_all(combined_xml.as_bytes())?;
+    /// Parse *.mcd format with the specified options (see [`ParseOptions`])
+    pub fn parse_with_options(reader: R, options: &ParseOptions) -> Result<Self> {
+        MCD::parse_pool(ReaderPool::new(reader), options)
+    }
 
-        let mut parser = MCDParser::new(mcd);
+    fn parse_pool(reader: ReaderPool<R>, options: &ParseOptions) -> Result<Self> {
+        let mut span = trace::span(trace::Operation::Parse, String::new);
+        let start = Stopwatch::start();
+        let mcd = MCD::new(reader);
+        let combined_xml = mcd.xml()?;
+        let read_duration = start.elapsed();
+        span.record("xml_bytes", combined_xml.len());
 
-        // println!("Found combined XML {}", combined_xml);
+        let mut parser = MCDParser::new(mcd, options);
 
         let mut reader = quick_xml::Reader::from_str(&combined_xml);
         // let mut buf = Vec::with_capacity(BUF_SIZE);
 
+        // Line of the XML metadata (starting from 1) at the byte position
+        let line_at = |position: usize| {
+            combined_xml.as_bytes()[..position.min(combined_xml.len())]
+                .iter()
+                .filter(|&&byte| byte == b'\n')
+                .count()
+                + 1
+        };
+
         loop {
             match reader.read_event() {
                 Ok(event) => {
-                    // println!("Event: {:?}", event);
                     parser.process(event);
 
                     // Check whether we are finished or have encounted a fatal error
                     match parser.current_state() {
                         ParserState::FatalError => {
-                            match parser.pop_error_back() {
-                                // TODO: Probably a better way of doing this..
-                                Some(value) => return Err(value),
-                                None => println!(
-                                    "A fatal error occurred when parsing, but it wasn't recorded"
-                                ),
-                            }
-
-                            break;
+                            return Err(parser.pop_error_back().unwrap_or_else(|| {
+                                MCDError::ParseFailed {
+                                    line: line_at(reader.buffer_position()),
+                                    tag: parser.current_tag().to_string(),
+                                }
+                            }));
                         }
                         ParserState::Finished => {
                             break;
@@ -346,16 +690,31 @@ impl<R: Read + Seek> MCD<R> {
                     }
                 }
                 Err(error) => {
-                    return Err(error.into());
-                    // println!("An error occurred when reading: {}", error);
-                    // break;
+                    return Err(MCDError::XmlSyntax {
+                        line: line_at(reader.buffer_position()),
+                        source: error,
+                    });
                 }
             }
 
             // buf.clear();
         }
 
-        let mcd = parser.mcd();
+        let parse_duration = start.elapsed() - read_duration;
+
+        let mut mcd = parser.mcd()?;
+        let (unknown_tags, recovered_fields) = parser.take_report();
+
+        mcd.parse_report = ParseReport {
+            xml_size: combined_xml.len(),
+            unknown_tags,
+            recovered_fields,
+            read_duration,
+            parse_duration,
+            assemble_duration: start.elapsed() - read_duration - parse_duration,
+        };
+        span.record("slides", mcd.slides.len());
+        span.record("acquisitions", mcd.acquisition_refs().len());
 
         if mcd.slides().is_empty() {
             Err(MCDError::NoSlidePresent)
@@ -364,55 +723,18 @@ impl<R: Read + Seek> MCD<R> {
         }
     }
 
-    /// Returns the raw XML metadata stored in the .mcd file
+    /// Returns the raw XML metadata stored in the .mcd file, from the opening `<MCDSchema` tag to the closing
+    /// `</MCDSchema>` tag. The XML is usually stored as UTF-16, but UTF-8 is also accepted. Returns
+    /// `MCDError::XmlNotFound` describing the end of the file if no XML metadata is found.
     pub fn xml(&self) -> Result<String> {
-        let chunk_size_i64: i64 = 1000;
-        let mut cur_offset: i64 = 0;
-
-        let chunk_size = chunk_size_i64.try_into()?;
-
-        let mut buf_u8 = vec![0; chunk_size];
+        let mut reader = self.reader.get()?;
 
-        loop {
-            let mut reader = self.reader.lock().or(Err(MCDError::PoisonMutex))?;
-
-            reader.seek(SeekFrom::End(-cur_offset - chunk_size_i64))?;
-
-            reader.read_exact(&mut buf_u8)?;
-
-            match std::str::from_utf8(&buf_u8) {
-                Ok(_data) => {}
-                Err(_error) => {
-                    // Found the final chunk, so find the start point
-                    let start_index = find_mcd_start(&buf_u8, chunk_size);
-
-                    let total_size = cur_offset + chunk_size_i64 - (start_index as i64);
-                    buf_u8 = vec![0; total_size.try_into()?];
-
-                    reader.seek(SeekFrom::End(-total_size))?;
-                    reader.read_exact(&mut buf_u8)?;
-
-                    break;
-                }
-            }
-
-            cur_offset += chunk_size_i64;
-        }
-
-        let mut combined_xml = String::new();
-
-        let mut buf_u16: Vec<u16> = vec![0; buf_u8.len() / 2];
-        u16_from_u8(&mut buf_u16, &buf_u8);
-
-        let data = String::from_utf16(&buf_u16)?;
-        combined_xml.push_str(&data);
-
-        Ok(combined_xml)
+        footer::read_xml(reader.deref_mut())
     }
 }
 
 impl<R> MCD<R> {
-    pub(crate) fn reader(&self) -> &Arc<Mutex<BufReader<R>>> {
+    pub(crate) fn reader(&self) -> &Arc<ReaderPool<R>> {
         &self.reader
     }
 
@@ -450,34 +772,97 @@ impl<R> MCD<R> {
         slides
     }
 
+    #[cfg(feature = "fs")]
     fn slides_mut(&mut self) -> &mut HashMap<u16, Slide<R>> {
+        self.acquisition_order = OnceLock::new();
+        self.channel_index = OnceLock::new();
+
         &mut self.slides
     }
 
-    /// Return a vector of references to all acquisitions in the .mcd file (iterates over all slides and all panoramas).
-    pub fn acquisitions(&self) -> Vec<&Acquisition<R>> {
-        let mut acquisitions = HashMap::new();
+    /// Keep recently read channel images in memory, up to a total of `budget_bytes`, so that repeated requests
+    /// for the same acquisition, channel and region (e.g. when redrawing a visualisation) don't read and
+    /// decompress the data again. The least recently used images are evicted once the budget is exceeded.
+    pub fn with_channel_cache(mut self, budget_bytes: usize) -> Self {
+        let cache = Arc::new(ChannelCache::new(budget_bytes));
 
-        // This should be unnecessary - hopefully there is only one set of channels per dataset?
-        for slide in self.slides.values() {
-            for panorama in slide.panoramas() {
-                for acquisition in panorama.acquisitions() {
-                    acquisitions.insert(acquisition.id(), acquisition);
+        for slide in self.slides.values_mut() {
+            for panorama in slide.panoramas_mut().values_mut() {
+                for acquisition in panorama.acquisitions_mut().values_mut() {
+                    acquisition.cache = Some(cache.clone());
                 }
             }
         }
 
-        let mut ordered_acquisitions = Vec::new();
-        for (_, acquisition) in acquisitions.drain() {
-            ordered_acquisitions.push(acquisition);
+        self.channel_cache = Some(cache);
+        self
+    }
+
+    /// Remove hot pixels from every channel image as it is read (e.g. with `Acquisition::channel_image()`), using
+    /// the specified method. Cached images are stored as read, so the method can be changed without a re-read.
+    pub fn with_despeckle(mut self, method: Despeckle) -> Self {
+        for slide in self.slides.values_mut() {
+            for panorama in slide.panoramas_mut().values_mut() {
+                for acquisition in panorama.acquisitions_mut().values_mut() {
+                    acquisition.despeckle = Some(method);
+                }
+            }
         }
 
-        ordered_acquisitions.sort_by_key(|a| a.id());
+        self
+    }
 
-        ordered_acquisitions
+    /// Returns the cache of channel images, if enabled (see [`MCD::with_channel_cache`])
+    pub fn channel_cache(&self) -> Option<&ChannelCache> {
+        self.channel_cache.as_deref()
+    }
+
+    /// Return a vector of references to all acquisitions in the .mcd file (iterates over all slides and all panoramas),
+    /// ordered by ID. Acquisitions with the same ID on different slides are all included, ordered by slide and
+    /// panorama. Use [`MCD::acquisition_map`] to avoid allocating a new vector on each call.
+    pub fn acquisitions(&self) -> Vec<&Acquisition<R>> {
+        self.acquisition_map().iter().collect()
     }
 
-    /// Return an acquisition which matches the supplied `AcquisitionIdentifier` or None if no match found
+    /// Returns an ordered view of all acquisitions in the .mcd file, in the same order as [`MCD::acquisitions`],
+    /// which can be iterated or used to look up acquisitions by reference. The order is computed once, on first
+    /// use.
+    pub fn acquisition_map(&self) -> AcquisitionMap<'_, R> {
+        let references = self.acquisition_order.get_or_init(|| {
+            let mut references = Vec::new();
+
+            for slide in self.slides.values() {
+                for panorama in slide.panoramas() {
+                    references.extend(
+                        panorama
+                            .acquisitions()
+                            .iter()
+                            .map(|acquisition| acquisition.reference()),
+                    );
+                }
+            }
+
+            references.sort_by_key(|reference| (reference.id(), *reference));
+
+            references
+        });
+
+        AcquisitionMap::new(self, references)
+    }
+
+    /// Returns the fully-qualified references to all acquisitions in the .mcd file, in the same order as
+    /// [`MCD::acquisitions`]
+    pub fn acquisition_refs(&self) -> Vec<AcquisitionRef> {
+        self.acquisition_map().references().to_vec()
+    }
+
+    /// Return an acquisition which matches the supplied `AcquisitionIdentifier` or None if no match found.
+    /// If more than one acquisition matches (e.g. the same ID is used on different slides), the first one
+    /// found is returned.
+    #[deprecated(
+        since = "0.1.10",
+        note = "may return the wrong acquisition if the identifier is ambiguous, use `find_acquisition` instead"
+    )]
     pub fn acquisition<A: Into<AcquisitionIdentifier>>(
         &self,
         identifier: A,
@@ -487,22 +872,8 @@ impl<R> MCD<R> {
         for slide in self.slides.values() {
             for panorama in slide.panoramas() {
                 for acquisition in panorama.acquisitions() {
-                    match &identifier {
-                        AcquisitionIdentifier::Id(id) => {
-                            if acquisition.id() == *id {
-                                return Some(acquisition);
-                            }
-                        }
-                        AcquisitionIdentifier::Order(order_number) => {
-                            if acquisition.order_number() == *order_number {
-                                return Some(acquisition);
-                            }
-                        }
-                        AcquisitionIdentifier::Description(description) => {
-                            if acquisition.description() == description {
-                                return Some(acquisition);
-                            }
-                        }
+                    if identifier.matches(acquisition) {
+                        return Some(acquisition);
                     }
                 }
             }
@@ -511,48 +882,134 @@ impl<R> MCD<R> {
         None
     }
 
-    /// Returns a list of acquisitions which are at least partially contained within the specified bounding box.
-    pub fn acquisitions_in(&self, region: &BoundingBox<f64>) -> Vec<&Acquisition<R>> {
-        let mut acquisitions = Vec::new();
+    /// Returns the single acquisition which matches the supplied `AcquisitionIdentifier`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCDError::InvalidAcquisition`] if no acquisition matches (suggesting the closest acquisitions,
+    /// see [`MCD::acquisition_suggestions`]), or [`MCDError::AmbiguousAcquisition`] if more than one does
+    /// (listing the matches, so that one can be chosen with an [`AcquisitionRef`]).
+    pub fn find_acquisition<A: Into<AcquisitionIdentifier>>(
+        &self,
+        identifier: A,
+    ) -> Result<&Acquisition<R>> {
+        let identifier = identifier.into();
+        let mut matching = self.acquisitions_matching(identifier.clone());
+
+        match matching.len() {
+            0 => Err(self.unknown_acquisition(identifier)),
+            1 => Ok(matching.remove(0)),
+            _ => Err(MCDError::AmbiguousAcquisition {
+                acquisition: identifier,
+                matches: matching
+                    .iter()
+                    .map(|acquisition| acquisition.reference())
+                    .collect(),
+            }),
+        }
+    }
 
-        for slide in self.slides.values() {
-            for panorama in slide.panoramas() {
-                for acquisition in panorama.acquisitions() {
-                    if acquisition.in_region(region) {
-                        acquisitions.push(acquisition);
-                    }
-                }
-            }
+    /// Returns the acquisitions most similar to the identifier, closest first, to suggest in place of an
+    /// identifier which doesn't match any acquisition (e.g. `ROI_001` for the description `ROI_01`, or nearby IDs)
+    pub fn acquisition_suggestions(
+        &self,
+        identifier: &AcquisitionIdentifier,
+    ) -> Vec<AcquisitionSuggestion> {
+        let mut candidates: Vec<_> = self
+            .acquisitions()
+            .into_iter()
+            .filter_map(|acquisition| {
+                identifier
+                    .distance(acquisition)
+                    .map(|distance| (distance, acquisition.reference(), acquisition))
+            })
+            .collect();
+        candidates.sort_by_key(|&(distance, reference, _)| (distance, reference));
+
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, _, acquisition)| AcquisitionSuggestion::new(acquisition))
+            .collect()
+    }
+
+    /// Returns [`MCDError::InvalidAcquisition`] for the identifier, with suggestions
+    pub(crate) fn unknown_acquisition(&self, identifier: AcquisitionIdentifier) -> MCDError {
+        MCDError::InvalidAcquisition {
+            suggestions: self.acquisition_suggestions(&identifier),
+            acquisition: identifier,
         }
+    }
 
-        acquisitions
+    /// Returns all acquisitions which match the supplied `AcquisitionIdentifier` (ordered by ID). This is most
+    /// useful with `AcquisitionIdentifier::Pattern`, for example:
+    ///
+    /// ```no_run
+    /// use imc_rs::{AcquisitionIdentifier, MCD};
+    ///
+    /// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+    ///
+    /// let rois = mcd.acquisitions_matching(AcquisitionIdentifier::glob("ROI_0*"));
+    /// let tumor = mcd.acquisitions_matching(AcquisitionIdentifier::regex("tumor").unwrap());
+    /// ```
+    pub fn acquisitions_matching<A: Into<AcquisitionIdentifier>>(
+        &self,
+        identifier: A,
+    ) -> Vec<&Acquisition<R>> {
+        let identifier = identifier.into();
+
+        self.acquisitions()
+            .into_iter()
+            .filter(|acquisition| identifier.matches(acquisition))
+            .collect()
+    }
+
+    /// Returns a list of acquisitions which are at least partially contained within the specified bounding box,
+    /// ordered by slide, panorama and acquisition ID. Acquisitions are looked up in a spatial index built when
+    /// parsing, so this is fast even for slides with hundreds of ROIs.
+    pub fn acquisitions_in(&self, region: &BoundingBox<f64>) -> Vec<&Acquisition<R>> {
+        self.slides()
+            .into_iter()
+            .flat_map(|slide| slide.acquisitions_in(region))
+            .collect()
     }
 
-    /// Returns a vector of all channels present within any acquisition performed on the slide, sorted by channel order number.
+    /// Returns a vector of all channels present within any acquisition performed on the slide, sorted by label
+    /// and then name. Use [`MCD::channel_index`] to avoid allocating a new vector on each call.
     pub fn channels(&self) -> Vec<&AcquisitionChannel> {
-        let mut channels = HashMap::new();
+        self.channel_index().iter().collect()
+    }
 
-        // This should be unnecessary - hopefully there is only one set of channels per dataset?
-        for slide in self.slides.values() {
-            for panorama in slide.panoramas() {
-                for acquisition in panorama.acquisitions() {
-                    for channel in acquisition.channels() {
-                        if !channels.contains_key(channel.name()) {
-                            channels.insert(channel.name(), channel);
-                        }
-                    }
-                }
-            }
-        }
+    /// Returns the index of all channels present within any acquisition, in the same order as
+    /// [`MCD::channels`], for looking up channels by name. The index is built once, on first use.
+    pub fn channel_index(&self) -> &ChannelIndex {
+        self.channel_index.get_or_init(|| ChannelIndex::new(self))
+    }
 
-        let mut ordered_channels = Vec::new();
-        for (_, channel) in channels.drain() {
-            ordered_channels.push(channel);
-        }
+    /// Returns the antibody panel used across all acquisitions (see `Panel`)
+    pub fn panel(&self) -> Panel {
+        Panel::new(self)
+    }
 
-        ordered_channels.sort_by_key(|a| [a.label(), a.name()]);
+    /// Returns whether the .mcd file was opened read-only (see [`MCD::open_read_only`])
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 
-        ordered_channels
+    /// Returns [`MCDError::ReadOnly`] if the .mcd file was opened read-only
+    pub(crate) fn check_writable(&self, operation: &str) -> Result<()> {
+        if self.read_only {
+            Err(MCDError::ReadOnly {
+                operation: operation.to_string(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the matrix of which channels were measured in which acquisitions (see `ChannelPresence`)
+    pub fn channel_presence(&self) -> ChannelPresence {
+        ChannelPresence::new(self)
     }
 
     /// Returns a vector of all channels, excluding those from the acquisitions with names matching those specified
@@ -565,11 +1022,7 @@ impl<R> MCD<R> {
                 for acquisition in panorama.acquisitions() {
                     if !exclusion_list.contains(&acquisition.description()) {
                         for channel in acquisition.channels() {
-                            if !channels.contains_key(channel.name())
-                                && channel.name() != "X"
-                                && channel.name() != "Y"
-                                && channel.name() != "Z"
-                            {
+                            if !channels.contains_key(channel.name()) && !channel.is_position() {
                                 channels.insert(channel.name(), channel);
                             }
                         }
@@ -608,6 +1061,85 @@ impl<R> MCD<R> {
         self.calibrations.get(&id)
     }
 
+    /// Returns the per-channel corrections for the acquisition, derived from the most recent calibration
+    /// performed for it (see `ChannelCorrections`). Returns None if no calibration was recorded for the
+    /// acquisition (this is always the case in version 1 of the Schema).
+    pub fn channel_corrections(&self, acquisition_id: u16) -> Option<ChannelCorrections> {
+        let calibration = self
+            .calibrations
+            .values()
+            .filter(|calibration| calibration.acquisition_id() == acquisition_id)
+            .max_by(|a, b| a.time_stamp().cmp(b.time_stamp()))?;
+
+        let channels: Vec<_> = self.calibration_channels.values().collect();
+
+        ChannelCorrections::new(acquisition_id, calibration, &channels)
+    }
+
+    /// Returns the timestamped instrument events (calibration, tuning, acquisition start/end) recorded in the
+    /// metadata, ordered by timestamp. Which events are available depends on the version of the schema (for
+    /// example, calibrations are not recorded in version 1), so this may only contain acquisition events.
+    pub fn instrument_events(&self) -> Vec<InstrumentEvent> {
+        let mut events = Vec::new();
+
+        for calibration in self.calibrations.values() {
+            events.push(InstrumentEvent::new(
+                calibration.time_stamp(),
+                InstrumentEventCategory::Calibration,
+                Some(calibration.acquisition_id()),
+                calibration.id(),
+                String::new(),
+            ));
+        }
+
+        for calibration_final in self.calibration_finals.values() {
+            events.push(InstrumentEvent::new(
+                calibration_final.time_stamp(),
+                InstrumentEventCategory::Tuning,
+                Some(calibration_final.acquisition_id()),
+                calibration_final.id(),
+                format!(
+                    "detector voltage {} - {}, dual coefficient {} - {}, helium {}",
+                    calibration_final.optimal_detector_voltage_start(),
+                    calibration_final.optimal_detector_voltage_end(),
+                    calibration_final.optimal_detector_dual_coefficient_start(),
+                    calibration_final.optimal_detector_dual_coefficient_end(),
+                    calibration_final.optimal_helium()
+                ),
+            ));
+        }
+
+        for acquisition in self.acquisitions() {
+            let description = acquisition.description().to_string();
+
+            events.push(InstrumentEvent::new(
+                acquisition.start_timestamp(),
+                InstrumentEventCategory::AcquisitionStart,
+                Some(acquisition.id()),
+                acquisition.id(),
+                description.clone(),
+            ));
+            events.push(InstrumentEvent::new(
+                acquisition.end_timestamp(),
+                InstrumentEventCategory::AcquisitionEnd,
+                Some(acquisition.id()),
+                acquisition.id(),
+                description,
+            ));
+        }
+
+        events.retain(|event| !event.timestamp().is_empty());
+
+        // Timestamps are ISO 8601 with the same offset, so can be ordered as strings
+        events.sort_by(|a, b| {
+            a.timestamp()
+                .cmp(b.timestamp())
+                .then(a.acquisition_id().cmp(&b.acquisition_id()))
+        });
+
+        events
+    }
+
     /// Returns an instance of `SlideFiducialMarks` with the specified ID, or None if none exists (this is always the case in version 1 of the Schema)
     pub fn slide_fiducal_marks(&self, id: u16) -> Option<&SlideFiducialMarks> {
         self.slide_fiducal_marks.get(&id)
@@ -619,37 +1151,29 @@ impl<R> MCD<R> {
     }
 }
 
-#[rustfmt::skip]
-impl<R> Print for MCD<R> {
-    fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
-        // writeln!(writer, "MCD File: {}", self.location)?;
+impl<R> Describe for MCD<R> {
+    fn describe(&self) -> Description {
+        let mut description = Description::new("MCD");
 
         match self.xmlns.as_ref() {
-            Some(xmlns) => writeln!(writer, "XML Namespace: {}", xmlns)?,
-            None => {
-                writeln!(writer, "WARNING: Missing namespace")?;
-            }
+            Some(xmlns) => description = description.field("XML Namespace", xmlns),
+            None => description = description.field("Warning", "Missing namespace"),
         }
 
-        for slide in self.slides.values() {
-            slide.print(writer, indent + 1)?;
+        for slide in self.slides() {
+            description = description.child(slide.describe());
         }
 
-        let channels = self.channels();
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "Channels", 25)?;
-
-        for channel in channels {
-            writeln!(
-                writer,
-                "{0: <2} | {1: <10} | {2: <10}",
-                channel.order_number(),
-                channel.name(),
-                channel.label()
-            )?;
+        let mut channels = Table::new("Channels", &["Order", "Name", "Label"]);
+        for channel in self.channels() {
+            channels = channels.row(vec![
+                channel.order_number().into(),
+                channel.name().into(),
+                channel.label().into(),
+            ]);
         }
 
-        Ok(())
+        description.table(channels)
     }
 }
 
@@ -692,7 +1216,13 @@ impl<T: num_traits::Num + Copy> BoundingBox<T> {
 }
 
 /// Represents a channel image (stored as a vector of f32).
-/// If the run was stopped mid acquisition width*height != valid_pixels
+/// If the run was stopped mid acquisition width*height != valid_pixels. Pixels which were not acquired are
+/// missing and represented as NaN, so that they do not bias statistics such as the mean.
+///
+/// Images can be combined with the arithmetic operators, pixel by pixel (e.g. `&cd8 / &cd4`), or with a
+/// scalar (e.g. `&dna - 2.0`), where missing pixels remain missing. Combining images of different sizes
+/// panics, so use [`ChannelImage::combine`] if the sizes are not known to match.
+#[derive(Debug, Clone)]
 pub struct ChannelImage {
     region: Region,
 
@@ -731,11 +1261,129 @@ impl ChannelImage {
         self.valid_pixels
     }
 
-    /// Returns the detected intensity values for this channel
+    /// Returns the extent of the acquired pixels within the image, so that partial rows of aborted acquisitions
+    /// can be excluded
+    pub fn valid_region(&self) -> ValidRegion {
+        let width = self.region.width.max(1) as usize;
+
+        ValidRegion::new(
+            (self.valid_pixels / width) as u32,
+            (self.valid_pixels % width) as u32,
+            self.region.width,
+        )
+    }
+
+    /// Returns a mask (in row-major order, `width() * height()` in length) which is true for each pixel which
+    /// was acquired. If `full_rows_only` is true, pixels in a partially acquired row are excluded.
+    pub fn valid_mask(&self, full_rows_only: bool) -> Vec<bool> {
+        let valid_region = self.valid_region();
+        let num_valid = if full_rows_only {
+            valid_region.full_rows() as usize * self.region.width as usize
+        } else {
+            self.valid_pixels
+        };
+
+        let num_pixels = (self.region.width * self.region.height) as usize;
+        (0..num_pixels).map(|index| index < num_valid).collect()
+    }
+
+    /// Returns the detected intensity values for this channel. Missing pixels are NaN.
     pub fn intensities(&self) -> &[f32] {
         &self.data
     }
 
+    /// Consumes the image, returning the intensities (`width() * height()` in length, in row-major order) without
+    /// copying them. Missing pixels, including those which were not acquired, are NaN.
+    pub fn into_intensities(self) -> Vec<f32> {
+        let mut data = self.data;
+        data.resize((self.region.width * self.region.height) as usize, f32::NAN);
+
+        data
+    }
+
+    /// Returns a copy of the intensities as an array of shape (height, width). Missing pixels, including those
+    /// which were not acquired, are NaN.
+    #[cfg(feature = "ndarray")]
+    pub fn to_array2(&self) -> ndarray::Array2<f32> {
+        self.clone().into_array2()
+    }
+
+    /// Consumes the image, returning the intensities as an array of shape (height, width) without copying them
+    /// (see [`ChannelImage::into_intensities`])
+    #[cfg(feature = "ndarray")]
+    pub fn into_array2(self) -> ndarray::Array2<f32> {
+        let shape = (self.region.height as usize, self.region.width as usize);
+
+        ndarray::Array2::from_shape_vec(shape, self.into_intensities())
+            .expect("The intensities are resized to width * height")
+    }
+
+    /// Returns a view of the intensities as an array of shape (height, width), or None if intensities are not
+    /// stored for every pixel (e.g. the image was constructed from a truncated acquisition), in which case
+    /// [`ChannelImage::to_array2`] fills the missing pixels with NaN
+    #[cfg(feature = "ndarray")]
+    pub fn view_as_array2(&self) -> Option<ndarray::ArrayView2<'_, f32>> {
+        let shape = (self.region.height as usize, self.region.width as usize);
+
+        ndarray::ArrayView2::from_shape(shape, &self.data).ok()
+    }
+
+    /// Returns the number of missing (NaN) pixels in the image, including those which were not acquired
+    pub fn num_missing_pixels(&self) -> usize {
+        let num_pixels = (self.region.width * self.region.height) as usize;
+        let num_stored = self.data.len().min(num_pixels);
+
+        num_pixels - num_stored
+            + self.data[..num_stored]
+                .iter()
+                .filter(|v| v.is_nan())
+                .count()
+    }
+
+    /// Returns the sum of the intensities. If `skip_nan` is true then missing (NaN) pixels are ignored,
+    /// otherwise the result is NaN if any pixel is missing.
+    pub fn sum(&self, skip_nan: bool) -> f64 {
+        if !skip_nan && self.num_missing_pixels() > 0 {
+            return f64::NAN;
+        }
+
+        self.data
+            .iter()
+            .filter(|value| !value.is_nan())
+            .map(|&value| value as f64)
+            .sum()
+    }
+
+    /// Returns the mean intensity. If `skip_nan` is true then missing (NaN) pixels are ignored, otherwise the
+    /// result is NaN if any pixel is missing.
+    pub fn mean(&self, skip_nan: bool) -> f64 {
+        let num_present =
+            (self.region.width * self.region.height) as usize - self.num_missing_pixels();
+
+        self.sum(skip_nan) / num_present as f64
+    }
+
+    /// Returns a copy of the image with missing (NaN) pixels, including those which were not acquired, replaced
+    /// with `value` (e.g. 0 for software which does not support NaN)
+    pub fn fill_missing(&self, value: f32) -> ChannelImage {
+        let mut data: Vec<_> = self
+            .data
+            .iter()
+            .map(|&v| if v.is_nan() { value } else { v })
+            .collect();
+        data.resize((self.region.width * self.region.height) as usize, value);
+
+        ChannelImage {
+            region: self.region,
+            acquisition_id: self.acquisition_id,
+            name: self.name.clone(),
+            label: self.label.clone(),
+            range: self.range,
+            valid_pixels: self.valid_pixels,
+            data,
+        }
+    }
+
     /// Returns the ID for the acquisition this channel belongs to.
     pub fn acquisition_id(&self) -> u16 {
         self.acquisition_id
@@ -750,6 +1398,12 @@ impl ChannelImage {
     pub fn label(&self) -> &str {
         &self.label
     }
+
+    /// Returns the isotope measured in this channel, parsed from the name (or the label, if the name does not
+    /// contain an isotope)
+    pub fn isotope(&self) -> Option<Isotope> {
+        Isotope::parse(&self.name).or_else(|| Isotope::parse(&self.label))
+    }
 }
 
 #[cfg(test)]
@@ -757,6 +1411,7 @@ mod tests {
     use image::{ImageBuffer, Pixel, Rgba};
 
     use super::*;
+    use crate::mcd::synthetic_mcd;
 
     use std::time::Instant;
 
@@ -777,7 +1432,7 @@ mod tests {
         println!("Time taken to parse .dcm: {:?}", start.elapsed());
 
         let start = Instant::now();
-        let roi_001 = mcd.acquisition("ROI_001").unwrap();
+        let roi_001 = mcd.find_acquisition("ROI_001").unwrap();
         println!("Time taken to find acquisition: {:?}", start.elapsed());
 
         let dna = roi_001.channel(ChannelIdentifier::label("DNA1")).unwrap();
@@ -837,6 +1492,184 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn aborted_image() {
+        // Acquisition aborted after 1 full row and 2 pixels of the second row
+        let image = ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 3,
+            },
+            acquisition_id: 1,
+            name: "Ir(191)".to_string(),
+            label: "DNA1".to_string(),
+            range: (1.0, 5.0),
+            valid_pixels: 5,
+            data: vec![
+                1.0,
+                2.0,
+                3.0,
+                4.0,
+                5.0,
+                f32::NAN,
+                f32::NAN,
+                f32::NAN,
+                f32::NAN,
+            ],
+        };
+
+        let valid_region = image.valid_region();
+        assert_eq!(
+            (valid_region.full_rows(), valid_region.partial_row_width()),
+            (1, 2)
+        );
+        assert!(valid_region.contains(1, 1) && !valid_region.contains(2, 1));
+        assert_eq!(
+            image
+                .valid_mask(true)
+                .iter()
+                .filter(|&&valid| valid)
+                .count(),
+            3
+        );
+        assert_eq!(
+            image
+                .valid_mask(false)
+                .iter()
+                .filter(|&&valid| valid)
+                .count(),
+            5
+        );
+
+        assert_eq!(image.num_missing_pixels(), 4);
+        assert_eq!(image.mean(true), 3.0);
+        assert!(image.mean(false).is_nan());
+        assert_eq!(image.fill_missing(0.0).sum(false), 15.0);
+    }
+
+    #[test]
+    fn suggests_similar_acquisitions() -> Result<()> {
+        let acquisitions =
+            [(1, "ROI_001"), (2, "ROI_002"), (9, "Tonsil")].map(|(id, description)| {
+                (
+                    AcquisitionSpec::new(id, 1, 1, 1)
+                        .description(description)
+                        .channel("Ir(191)", "DNA1"),
+                    [1.0],
+                )
+            });
+        let mcd = MCD::from_bytes(synthetic_mcd(acquisitions)?)?;
+
+        match mcd.find_acquisition("ROI_01") {
+            Err(MCDError::InvalidAcquisition { suggestions, .. }) => {
+                let descriptions: Vec<_> = suggestions
+                    .iter()
+                    .map(|suggestion| suggestion.description())
+                    .collect();
+                assert_eq!(descriptions, ["ROI_001", "ROI_002"]);
+            }
+            _ => panic!("ROI_01 should not match an acquisition"),
+        }
+
+        let error = mcd
+            .find_acquisition(AcquisitionIdentifier::Id(8))
+            .map(|acquisition| acquisition.id());
+        assert!(
+            matches!(&error, Err(MCDError::InvalidAcquisition { suggestions, .. })
+            if suggestions.len() == 1 && suggestions[0].reference().id() == 9)
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn channel_image_arrays() {
+        // 3x2 image of an aborted acquisition, where only the first 4 pixels were stored
+        let image = ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir191".to_string(),
+            label: "DNA1".to_string(),
+            range: (1.0, 4.0),
+            valid_pixels: 4,
+            data: vec![1.0, 2.0, 3.0, 4.0],
+        };
+
+        assert!(image.view_as_array2().is_none());
+        let array = image.to_array2();
+        assert_eq!(array.dim(), (2, 3));
+        assert_eq!(array[[1, 0]], 4.0);
+        assert!(array[[1, 2]].is_nan());
+
+        let complete = image.fill_missing(0.0);
+        let view = complete.view_as_array2();
+        assert_eq!(view.map(|view| view[[0, 2]]), Some(3.0));
+        assert_eq!(complete.into_array2()[[1, 1]], 0.0);
+    }
+
+    #[test]
+    fn reencode_optical_image() -> Result<()> {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 128])))
+            .write_to(&mut png, ImageOutputFormat::Png)?;
+
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 1, 1)
+                .channel("Ir191", "DNA1")
+                .before_ablation_image(png.into_inner()),
+            [1.0],
+        )])?)?;
+        let image = mcd.acquisitions()[0].before_ablation_image();
+
+        let decode = |data: &[u8]| image::load_from_memory(data).expect("Should decode");
+
+        let downscaled = decode(&image.to_png_bytes(Some(2))?);
+        assert_eq!((downscaled.width(), downscaled.height()), (2, 1));
+        assert_eq!(downscaled.into_rgba8().get_pixel(0, 0).0, [255, 0, 0, 128]);
+
+        let jpeg = image.to_jpeg_bytes(90, None)?;
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+        assert_eq!(decode(&jpeg).color(), image::ColorType::Rgb8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_creates_no_dcm() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("imc-rs-read-only-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("read_only.mcd");
+
+        std::fs::write(
+            &path,
+            synthetic_mcd([(
+                AcquisitionSpec::new(1, 1, 1, 1).channel("Ir(191)", "DNA1"),
+                [1.0],
+            )])?,
+        )?;
+
+        let mcd = MCD::open_read_only(&path)?;
+        assert!(mcd.is_read_only());
+        assert!(matches!(
+            mcd.add_bookmark(Bookmark::new("All", AcquisitionRef::new(1, 1, 1))),
+            Err(MCDError::ReadOnly { .. })
+        ));
+        assert!(matches!(mcd.with_dcm(), Err(MCDError::ReadOnly { .. })));
+        assert!(!path.with_extension("dcm").exists());
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
     // #[test]
     // fn test_all_in_folder() -> Result<()> {
     //     let paths = std::fs::read_dir("test/").unwrap();
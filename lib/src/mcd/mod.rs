@@ -1,4 +1,5 @@
 mod parser;
+mod writer;
 mod xml_types;
 
 use crate::{Acquisition, AcquisitionChannel, ImageFormat, Panorama, MCD};
@@ -9,4 +10,8 @@ pub(crate) use xml_types::{
 };
 
 pub use parser::MCDParser;
+pub use parser::ParseOptions;
 pub use parser::ParserState;
+#[cfg(test)]
+pub(crate) use writer::synthetic_mcd;
+pub use writer::{AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
@@ -1,12 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use quick_xml::events::Event;
+use quick_xml::events::{BytesText, Event};
 
 use crate::{
     acquisition::{DataFormat, ProfilingType},
     calibration::{Calibration, CalibrationChannel, CalibrationFinal, CalibrationParams},
     error::MCDError,
     panorama::PanoramaType,
+    parse_report::RecoveredField,
     slide::{SlideFiducialMarks, SlideProfile},
 };
 
@@ -126,12 +127,44 @@ pub enum ParserState {
     ProcessingPanoramaPixelYPos,
     ProcessingPanorama,
     Processing,
+    // Text of an element which is not modelled
+    ProcessingUnknown,
     #[allow(dead_code)]
     Error,
     FatalError, // Must stop here
     Finished,
 }
 
+/// Options controlling how the XML metadata of an .mcd file is parsed (see `MCD::parse_with_options`)
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    tolerate_unknown_tags: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            tolerate_unknown_tags: true,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Create the default options, which tolerate unknown tags
+    pub fn new() -> Self {
+        ParseOptions::default()
+    }
+
+    /// Set whether elements which are not modelled (e.g. elements added in newer versions of the schema) are
+    /// tolerated, which is the default. Tolerated elements are counted in the `ParseReport`, and their text is
+    /// recorded in the extras of the element containing them (e.g. `Acquisition::extras`). Otherwise, parsing fails
+    /// with `MCDError::UnknownTag` at the first such element.
+    pub fn tolerate_unknown_tags(mut self, tolerate: bool) -> Self {
+        self.tolerate_unknown_tags = tolerate;
+        self
+    }
+}
+
 pub struct MCDParser<R> {
     pub(crate) current_mcd: Option<MCD<R>>,
 
@@ -153,6 +186,14 @@ pub struct MCDParser<R> {
 
     roi_points: Vec<ROIPoint>,
 
+    options: ParseOptions,
+    /// Name of the most recently opened element
+    current_tag: String,
+
+    /// Number of occurrences of each tag which isn't handled
+    unknown_tags: BTreeMap<String, usize>,
+    recovered_fields: Vec<RecoveredField>,
+
     current_slide: Option<SlideXML>,
     current_panorama: Option<PanoramaXML>,
     current_calibration_final: Option<CalibrationFinalXML>,
@@ -168,7 +209,7 @@ pub struct MCDParser<R> {
 }
 
 impl<R: Read + Seek> MCDParser<R> {
-    pub fn new(mcd: MCD<R>) -> MCDParser<R> {
+    pub fn new(mcd: MCD<R>, options: &ParseOptions) -> MCDParser<R> {
         MCDParser {
             current_mcd: Some(mcd),
             state: ParserState::Start,
@@ -189,6 +230,12 @@ impl<R: Read + Seek> MCDParser<R> {
             // TODO: Do we need this?
             roi_points: Vec::new(),
 
+            options: options.clone(),
+            current_tag: String::new(),
+
+            unknown_tags: BTreeMap::new(),
+            recovered_fields: Vec::new(),
+
             current_slide: None,
             current_panorama: None,
             current_calibration_final: None,
@@ -204,7 +251,9 @@ impl<R: Read + Seek> MCDParser<R> {
         }
     }
 
-    pub fn mcd(&mut self) -> MCD<R> {
+    /// Assemble the parsed elements into an `MCD`, returning `MCDError::MissingElement` if an element refers to
+    /// another (by ID) which is not present
+    pub fn mcd(&mut self) -> Result<MCD<R>, MCDError> {
         let mut mcd = self
             .current_mcd
             .take()
@@ -217,34 +266,63 @@ impl<R: Read + Seek> MCDParser<R> {
             let acquisition = self
                 .acquisitions
                 .get_mut(&channel.acquisition_id())
-                .unwrap_or_else(|| panic!("Missing AcquisitionID {}", channel.acquisition_id()));
+                .ok_or_else(|| MCDError::MissingElement {
+                    path: format!("MCDSchema/Acquisition[ID={}]", channel.acquisition_id()),
+                })?;
             acquisition.channels_mut().push(channel);
         }
 
+        // Add the ROI points to the acquisition of the ROI (which has the same ID), in order
+        self.roi_points
+            .sort_by_key(|point| (point.order_number, point.id));
+        for point in self.roi_points.drain(0..) {
+            if let Some(acquisition) = point
+                .acquisition_roi_id
+                .and_then(|roi_id| self.acquisitions.get_mut(&roi_id))
+            {
+                acquisition.roi_points_mut().push(point.into());
+            }
+        }
+
         // Create map with Arc for sharing pointers with Panorama
         let mut acquisitions = HashMap::new();
         for (id, mut acquisition) in self.acquisitions.drain() {
             acquisition.reader = Some(reader.clone());
-            acquisition.fix_roi_positions();
+            for field in acquisition.fix_roi_positions() {
+                self.recovered_fields
+                    .push(RecoveredField::new("Acquisition", id, field));
+            }
+            if acquisition.fix_value_bytes() {
+                self.recovered_fields
+                    .push(RecoveredField::new("Acquisition", id, "ValueBytes"));
+            }
 
             acquisitions.insert(id, acquisition);
         }
 
         // Add acquisition to panorama
         for roi in &self.acquisition_rois {
-            let acquisition = acquisitions
-                .remove(roi.id.as_ref().expect("Must have ID for AcquisitionROI"))
-                .expect("Should have Acquisition with same ID as AcquisitionROI");
-
-            let panorama = self
-                .panoramas
-                .get_mut(
-                    roi.panorama_id
-                        .as_ref()
-                        .expect("Must have PanoramaID for AcquisitionROI"),
-                )
-                .expect("Should have Panorama with same ID as AcquisitionROI");
-
+            let id = roi.id.ok_or_else(|| MCDError::MissingElement {
+                path: "MCDSchema/AcquisitionROI/ID".to_string(),
+            })?;
+            let mut acquisition =
+                acquisitions
+                    .remove(&id)
+                    .ok_or_else(|| MCDError::MissingElement {
+                        path: format!("MCDSchema/Acquisition[ID={}]", id),
+                    })?;
+
+            let panorama_id = roi.panorama_id.ok_or_else(|| MCDError::MissingElement {
+                path: format!("MCDSchema/AcquisitionROI[ID={}]/PanoramaID", id),
+            })?;
+            let panorama =
+                self.panoramas
+                    .get_mut(&panorama_id)
+                    .ok_or_else(|| MCDError::MissingElement {
+                        path: format!("MCDSchema/Panorama[ID={}]", panorama_id),
+                    })?;
+
+            acquisition.panorama_id = panorama.id();
             panorama
                 .acquisitions_mut()
                 .insert(acquisition.id(), acquisition);
@@ -257,27 +335,71 @@ impl<R: Read + Seek> MCDParser<R> {
             let slide = mcd
                 .slides
                 .get_mut(&slide_id)
-                .unwrap_or_else(|| panic!("Missing Slide with ID {}", slide_id));
+                .ok_or_else(|| MCDError::MissingElement {
+                    path: format!("MCDSchema/Slide[ID={}]", slide_id),
+                })?;
             panorama.reader = Some(reader.clone());
 
-            panorama.fix_image_dimensions();
+            if panorama.fix_image_dimensions() {
+                for field in ["PixelWidth", "PixelHeight"] {
+                    self.recovered_fields
+                        .push(RecoveredField::new("Panorama", id, field));
+                }
+            }
+
+            for acquisition in panorama.acquisitions_mut().values_mut() {
+                acquisition.slide_id = slide_id;
+            }
 
             slide.panoramas_mut().insert(id, panorama);
         }
 
+        mcd.calibration_finals = std::mem::take(&mut self.calibration_finals);
+        mcd.calibration_params = std::mem::take(&mut self.calibration_params);
+        mcd.calibration_channels = std::mem::take(&mut self.calibration_channels);
+        mcd.calibrations = std::mem::take(&mut self.calibrations);
+        let mut fiducial_marks: Vec<_> = self.slide_fiducal_marks.values().cloned().collect();
+        fiducial_marks.sort_by_key(|mark| mark.id());
+        for mark in fiducial_marks {
+            if let Some(slide) = mcd.slides.get_mut(&mark.slide_id()) {
+                slide.fiducial_marks_mut().push(mark);
+            }
+        }
+
+        mcd.slide_fiducal_marks = std::mem::take(&mut self.slide_fiducal_marks);
+        mcd.slide_profiles = std::mem::take(&mut self.slide_profiles);
+
         // Update the acquisitions
         //mcd.acquisitions = acquisitions;
         for slide in mcd.slides.values_mut() {
             slide.reader = Some(reader.clone());
+            slide.build_spatial_index();
         }
 
-        mcd
+        Ok(mcd)
+    }
+
+    /// Returns the number of occurrences of each tag which wasn't handled, and the fields which were
+    /// corrected when assembling the MCD (see `mcd()`)
+    pub fn take_report(&mut self) -> (BTreeMap<String, usize>, Vec<RecoveredField>) {
+        self.recovered_fields
+            .sort_by_key(|field| (field.element(), field.id()));
+
+        (
+            std::mem::take(&mut self.unknown_tags),
+            std::mem::take(&mut self.recovered_fields),
+        )
     }
 
     pub fn current_state(&self) -> ParserState {
         self.state
     }
 
+    /// Returns the name of the element currently being parsed
+    pub fn current_tag(&self) -> &str {
+        &self.current_tag
+    }
+
     #[allow(dead_code)]
     pub fn has_errors(&self) -> bool {
         !self.errors.is_empty()
@@ -291,208 +413,277 @@ impl<R: Read + Seek> MCDParser<R> {
         self.errors.pop_back()
     }
 
+    /// Handle an element which is not modelled within the current element (named `self.current_tag`), with the
+    /// specified text, or None if the text is still to be processed. The element is recorded in the extras of the
+    /// current element if unknown tags are tolerated, and is a fatal error otherwise.
+    fn unknown_tag(&mut self, text: Option<&str>) {
+        *self
+            .unknown_tags
+            .entry(self.current_tag.clone())
+            .or_insert(0) += 1;
+
+        if !self.options.tolerate_unknown_tags {
+            self.errors.push_back(MCDError::UnknownTag {
+                name: self.current_tag.clone(),
+            });
+            self.state = ParserState::FatalError;
+
+            return;
+        }
+
+        // Recorded as empty until the text (if any) is processed
+        self.record_extra(text.unwrap_or_default());
+        if text.is_none() {
+            self.sub_state = ParserState::ProcessingUnknown;
+        }
+    }
+
+    /// Record the text of the element named `self.current_tag` in the extras of the current element. Elements
+    /// which are not within an element with extras (e.g. a new top level element) are only counted.
+    fn record_extra(&mut self, text: &str) {
+        let extras = match self.state {
+            ParserState::ProcessingSlide => {
+                self.current_slide.as_mut().map(|slide| &mut slide.extras)
+            }
+            ParserState::ProcessingPanorama => self
+                .current_panorama
+                .as_mut()
+                .map(|panorama| &mut panorama.extras),
+            ParserState::ProcessingAcquisition => self
+                .current_acquisition
+                .as_mut()
+                .map(|acquisition| &mut acquisition.extras),
+            ParserState::ProcessingAcquisitionChannel => self
+                .current_acquisition_channel
+                .as_mut()
+                .map(|channel| &mut channel.extras),
+            _ => None,
+        };
+
+        if let Some(extras) = extras {
+            extras.insert(self.current_tag.clone(), text.trim().to_string());
+        }
+    }
+
     pub fn process(&mut self, ev: Event) {
+        // Elements without content (e.g. an empty description) produce no text event, so record them as empty
+        if matches!(ev, Event::End(_)) && is_text_field(self.state, self.sub_state) {
+            self.process(Event::Text(BytesText::new("")));
+        }
+
         match &ev {
-            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
-                b"MCDSchema" => {
-                    // TODO: get xmlns
-                    //self.current_mcd = Some()
-                }
-                b"Slide" => {
-                    //Wself.current_mcd.unwrap().slide = Some(Slide::new());
-                    self.current_slide = Some(SlideXML::new());
-                    self.state = ParserState::ProcessingSlide
-                }
-                b"Panorama" => {
-                    self.current_panorama = Some(PanoramaXML::new());
-                    self.state = ParserState::ProcessingPanorama
-                }
-                b"CalibrationFinal" => {
-                    self.current_calibration_final = Some(CalibrationFinalXML::new());
-                    self.state = ParserState::ProcessingCalibrationFinal
-                }
-                b"CalibrationParams" => {
-                    self.current_calibration_params = Some(CalibrationParamsXML::new());
-                    self.state = ParserState::ProcessingCalibrationParams
-                }
-                b"CalibrationChannel" => {
-                    self.current_calibration_channel = Some(CalibrationChannelXML::new());
-                    self.state = ParserState::ProcessingCalibrationChannel
-                }
-                b"Calibration" => {
-                    self.current_calibration = Some(CalibrationXML::new());
-                    self.state = ParserState::ProcessingCalibration
-                }
-                b"SlideFiducialMarks" => {
-                    self.current_slide_fiducial_marks = Some(SlideFiducialMarksXML::new());
-                    self.state = ParserState::ProcessingSlideFiducialMarks
-                }
-                b"SlideProfile" => {
-                    self.current_slide_profile = Some(SlideProfileXML::new());
-                    self.state = ParserState::ProcessingSlideProfile
-                }
-                b"AcquisitionROI" => {
-                    self.current_acquisition_roi = Some(AcquisitionROI::new());
-                    self.state = ParserState::ProcessingAcquisitionROI
-                }
-                b"ROIPoint" => {
-                    self.current_roi_point = Some(ROIPoint::new());
-                    self.state = ParserState::ProcessingROIPoint
-                }
-                b"AcquisitionChannel" => {
-                    self.current_acquisition_channel = Some(AcquisitionChannelXML::new());
-                    self.state = ParserState::ProcessingAcquisitionChannel
-                }
-                b"Acquisition" => {
-                    self.current_acquisition = Some(AcquisitionXML::new());
-                    self.state = ParserState::ProcessingAcquisition
-                }
-                b"ID" => self.sub_state = ParserState::ProcessingID,
-                b"UID" => self.sub_state = ParserState::ProcessingUID,
-                b"Description" => self.sub_state = ParserState::ProcessingDescription,
-                b"Filename" => self.sub_state = ParserState::ProcessingFilename,
-                b"SlideType" => self.sub_state = ParserState::ProcessingSlideType,
-                b"WidthUm" => self.sub_state = ParserState::ProcessingWidthUm,
-                b"HeightUm" => self.sub_state = ParserState::ProcessingHeightUm,
-                b"ImageStartOffset" => self.sub_state = ParserState::ProcessingImageStartOffset,
-                b"ImageEndOffset" => self.sub_state = ParserState::ProcessingImageEndOffset,
-                b"ImageFile" => self.sub_state = ParserState::ProcessingImageFile,
-                b"EnergyDb" => self.sub_state = ParserState::ProcessingEnergyDb,
-                b"Frequency" => self.sub_state = ParserState::ProcessingFrequency,
-                b"FMarkSlideLength" => self.sub_state = ParserState::ProcessingFMarkSlideLength,
-                b"FMarkSlideThickness" => {
-                    self.sub_state = ParserState::ProcessingFMarkSlideThickness
-                }
-                b"Name" => self.sub_state = ParserState::ProcessingName,
-                b"SwVersion" => self.sub_state = ParserState::ProcessingSwVersion,
-                b"SlideID" => self.sub_state = ParserState::ProcessingSlideID,
-                b"SlideX1PosUm" => self.sub_state = ParserState::ProcessingSlideX1PosUm,
-                b"SlideY1PosUm" => self.sub_state = ParserState::ProcessingSlideY1PosUm,
-                b"SlideX2PosUm" => self.sub_state = ParserState::ProcessingSlideX2PosUm,
-                b"SlideY2PosUm" => self.sub_state = ParserState::ProcessingSlideY2PosUm,
-                b"SlideX3PosUm" => self.sub_state = ParserState::ProcessingSlideX3PosUm,
-                b"SlideY3PosUm" => self.sub_state = ParserState::ProcessingSlideY3PosUm,
-                b"SlideX4PosUm" => self.sub_state = ParserState::ProcessingSlideX4PosUm,
-                b"SlideY4PosUm" => self.sub_state = ParserState::ProcessingSlideY4PosUm,
-                b"PixelWidth" => self.sub_state = ParserState::ProcessingPixelWidth,
-                b"PixelHeight" => self.sub_state = ParserState::ProcessingPixelHeight,
-                b"ImageFormat" => self.sub_state = ParserState::ProcessingImageFormat,
-                b"PixelScaleCoef" => self.sub_state = ParserState::ProcessingPixelScaleCoef,
-                b"Type" => self.sub_state = ParserState::ProcessingType,
-                b"IsLocked" => self.sub_state = ParserState::ProcessingIsLocked,
-                b"RotationAngle" => self.sub_state = ParserState::ProcessingRotationAngle,
-                b"TimeStamp" | b"Timestamp" => self.sub_state = ParserState::ProcessingTimeStamp,
-                b"OptimalDetectorVoltageStart" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorVoltageStart
-                }
-                b"OptimalDetectorVoltageEnd" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorVoltageEnd
-                }
-                b"OptimalDetectorDualCoefficientStart" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficientStart
-                }
-                b"OptimalDetectorDualCoefficientEnd" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficientEnd
-                }
-                b"OptimalHelium" => self.sub_state = ParserState::ProcessingOptimalHelium,
-                b"TransientStart" => self.sub_state = ParserState::ProcessingTransientStart,
-                b"TransientCrossTalk1" => {
-                    self.sub_state = ParserState::ProcessingTransientCrossTalk1
-                }
-                b"TransientCrossTalk2" => {
-                    self.sub_state = ParserState::ProcessingTransientCrossTalk2
-                }
-                b"ReferenceEnergy" => self.sub_state = ParserState::ProcessingReferenceEnergy,
-                b"MaximumEnergy" => self.sub_state = ParserState::ProcessingMaximumEnergy,
-                b"CalibrationID" => self.sub_state = ParserState::ProcessingCalibrationID,
-                b"OptimalDetectorVoltage" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorVoltage
-                }
-                b"OptimalDetectorDualCoefficient" => {
-                    self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficient
-                }
-                b"OptimalMakeupGas" => self.sub_state = ParserState::ProcessingOptimalMakeupGas,
-                b"OptimalCurrent" => self.sub_state = ParserState::ProcessingOptimalCurrent,
-                b"OptimalX" => self.sub_state = ParserState::ProcessingOptimalX,
-                b"OptimalY" => self.sub_state = ParserState::ProcessingOptimalY,
-                b"MeanDuals" => self.sub_state = ParserState::ProcessingMeanDuals,
-                b"CoordinateX" => self.sub_state = ParserState::ProcessingCoordinateX,
-                b"CoordinateY" => self.sub_state = ParserState::ProcessingCoordinateY,
-                b"ChannelName" => self.sub_state = ParserState::ProcessingChannelName,
-                b"OrderNumber" => self.sub_state = ParserState::ProcessingOrderNumber,
-                b"AcquisitionID" => self.sub_state = ParserState::ProcessingAcquisitionID,
-                b"ChannelLabel" => {
-                    self.sub_state = ParserState::ProcessingChannelLabel;
-                }
-                b"AblationPower" => self.sub_state = ParserState::ProcessingAblationPower,
-                b"AblationDistanceBetweenShotsX" => {
-                    self.sub_state = ParserState::ProcessingAblationDistanceBetweenShotsX
-                }
-                b"AblationDistanceBetweenShotsY" => {
-                    self.sub_state = ParserState::ProcessingAblationDistanceBetweenShotsY
-                }
-                b"AblationFrequency" => self.sub_state = ParserState::ProcessingAblationFrequency,
-                b"AcquisitionROIID" => self.sub_state = ParserState::ProcessingAcquisitionROIID,
-                b"SignalType" => self.sub_state = ParserState::ProcessingSignalType,
-                b"DualCountStart" => self.sub_state = ParserState::ProcessingDualCountStart,
-                b"DataStartOffset" => self.sub_state = ParserState::ProcessingDataStartOffset,
-                b"DataEndOffset" => self.sub_state = ParserState::ProcessingDataEndOffset,
-                b"StartTimeStamp" => self.sub_state = ParserState::ProcessingStartTimeStamp,
-                b"EndTimeStamp" => self.sub_state = ParserState::ProcessingEndTimeStamp,
-                b"AfterAblationImageEndOffset" => {
-                    self.sub_state = ParserState::ProcessingAfterAblationImageEndOffset
-                }
-                b"AfterAblationImageStartOffset" => {
-                    self.sub_state = ParserState::ProcessingAfterAblationImageStartOffset
-                }
-                b"BeforeAblationImageEndOffset" => {
-                    self.sub_state = ParserState::ProcessingBeforeAblationImageEndOffset
-                }
-                b"BeforeAblationImageStartOffset" => {
-                    self.sub_state = ParserState::ProcessingBeforeAblationImageStartOffset
-                }
-                b"ROIStartXPosUm" => self.sub_state = ParserState::ProcessingROIStartXPosUm,
-                b"ROIStartYPosUm" => self.sub_state = ParserState::ProcessingROIStartYPosUm,
-                b"ROIEndXPosUm" => self.sub_state = ParserState::ProcessingROIEndXPosUm,
-                b"ROIEndYPosUm" => self.sub_state = ParserState::ProcessingROIEndYPosUm,
-                b"MovementType" => self.sub_state = ParserState::ProcessingMovementType,
-                b"SegmentDataFormat" => self.sub_state = ParserState::ProcessingSegmentDataFormat,
-                b"ValueBytes" => self.sub_state = ParserState::ProcessingValueBytes,
-                b"MaxY" => self.sub_state = ParserState::ProcessingMaxY,
-                b"MaxX" => self.sub_state = ParserState::ProcessingMaxX,
-                b"PlumeStart" => self.sub_state = ParserState::ProcessingPlumeStart,
-                b"PlumeEnd" => self.sub_state = ParserState::ProcessingPlumeEnd,
-                b"Template" => self.sub_state = ParserState::ProcessingTemplate,
-                b"ProfilingType" => self.sub_state = ParserState::ProcessingProfilingType,
-                b"PanoramaID" => self.sub_state = ParserState::ProcessingPanoramaID,
-                b"ROIType" => {
-                    // In version 2 of XSD there are empty ROIType tags, so only trigger processing of ROIType
-                    // if we have a start tag (not an empty tag)
-                    match &ev {
-                        Event::Start(_e) => self.sub_state = ParserState::ProcessingROIType,
-                        _ => self.sub_state = ParserState::Processing,
-                    };
-                }
-                b"SlideXPosUm" => self.sub_state = ParserState::ProcessingSlideXPosUm,
-                b"SlideYPosUm" => self.sub_state = ParserState::ProcessingSlideYPosUm,
-                b"PanoramaPixelXPos" => self.sub_state = ParserState::ProcessingPanoramaPixelXPos,
-                b"PanoramaPixelYPos" => self.sub_state = ParserState::ProcessingPanoramaPixelYPos,
-                _ => match std::str::from_utf8(e.local_name().as_ref()) {
-                    Ok(name) => {
-                        self.errors.push_back(MCDError::UnknownTag {
-                            name: name.to_owned(),
-                        });
-
-                        // panic!("[Start] Unknown tag name: {}", name);
-                    }
-                    Err(error) => {
-                        // println!("Failed to convert tag name: {}", error);
-                        self.errors.push_back(error.into());
+            Event::Start(e) | Event::Empty(e) => {
+                self.current_tag = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
 
-                        self.state = ParserState::FatalError
+                match e.local_name().as_ref() {
+                    b"MCDSchema" => {
+                        // TODO: get xmlns
+                        //self.current_mcd = Some()
                     }
-                },
-            },
+                    b"Slide" => {
+                        //Wself.current_mcd.unwrap().slide = Some(Slide::new());
+                        self.current_slide = Some(SlideXML::new());
+                        self.state = ParserState::ProcessingSlide
+                    }
+                    b"Panorama" => {
+                        self.current_panorama = Some(PanoramaXML::new());
+                        self.state = ParserState::ProcessingPanorama
+                    }
+                    b"CalibrationFinal" => {
+                        self.current_calibration_final = Some(CalibrationFinalXML::new());
+                        self.state = ParserState::ProcessingCalibrationFinal
+                    }
+                    b"CalibrationParams" => {
+                        self.current_calibration_params = Some(CalibrationParamsXML::new());
+                        self.state = ParserState::ProcessingCalibrationParams
+                    }
+                    b"CalibrationChannel" => {
+                        self.current_calibration_channel = Some(CalibrationChannelXML::new());
+                        self.state = ParserState::ProcessingCalibrationChannel
+                    }
+                    b"Calibration" => {
+                        self.current_calibration = Some(CalibrationXML::new());
+                        self.state = ParserState::ProcessingCalibration
+                    }
+                    b"SlideFiducialMarks" => {
+                        self.current_slide_fiducial_marks = Some(SlideFiducialMarksXML::new());
+                        self.state = ParserState::ProcessingSlideFiducialMarks
+                    }
+                    b"SlideProfile" => {
+                        self.current_slide_profile = Some(SlideProfileXML::new());
+                        self.state = ParserState::ProcessingSlideProfile
+                    }
+                    b"AcquisitionROI" => {
+                        self.current_acquisition_roi = Some(AcquisitionROI::new());
+                        self.state = ParserState::ProcessingAcquisitionROI
+                    }
+                    b"ROIPoint" => {
+                        self.current_roi_point = Some(ROIPoint::new());
+                        self.state = ParserState::ProcessingROIPoint
+                    }
+                    b"AcquisitionChannel" => {
+                        self.current_acquisition_channel = Some(AcquisitionChannelXML::new());
+                        self.state = ParserState::ProcessingAcquisitionChannel
+                    }
+                    b"Acquisition" => {
+                        self.current_acquisition = Some(AcquisitionXML::new());
+                        self.state = ParserState::ProcessingAcquisition
+                    }
+                    b"ID" => self.sub_state = ParserState::ProcessingID,
+                    b"UID" => self.sub_state = ParserState::ProcessingUID,
+                    b"Description" => self.sub_state = ParserState::ProcessingDescription,
+                    b"Filename" => self.sub_state = ParserState::ProcessingFilename,
+                    b"SlideType" => self.sub_state = ParserState::ProcessingSlideType,
+                    b"WidthUm" => self.sub_state = ParserState::ProcessingWidthUm,
+                    b"HeightUm" => self.sub_state = ParserState::ProcessingHeightUm,
+                    b"ImageStartOffset" => self.sub_state = ParserState::ProcessingImageStartOffset,
+                    b"ImageEndOffset" => self.sub_state = ParserState::ProcessingImageEndOffset,
+                    b"ImageFile" => self.sub_state = ParserState::ProcessingImageFile,
+                    b"EnergyDb" => self.sub_state = ParserState::ProcessingEnergyDb,
+                    b"Frequency" => self.sub_state = ParserState::ProcessingFrequency,
+                    b"FMarkSlideLength" => self.sub_state = ParserState::ProcessingFMarkSlideLength,
+                    b"FMarkSlideThickness" => {
+                        self.sub_state = ParserState::ProcessingFMarkSlideThickness
+                    }
+                    b"Name" => self.sub_state = ParserState::ProcessingName,
+                    b"SwVersion" => self.sub_state = ParserState::ProcessingSwVersion,
+                    b"SlideID" => self.sub_state = ParserState::ProcessingSlideID,
+                    b"SlideX1PosUm" => self.sub_state = ParserState::ProcessingSlideX1PosUm,
+                    b"SlideY1PosUm" => self.sub_state = ParserState::ProcessingSlideY1PosUm,
+                    b"SlideX2PosUm" => self.sub_state = ParserState::ProcessingSlideX2PosUm,
+                    b"SlideY2PosUm" => self.sub_state = ParserState::ProcessingSlideY2PosUm,
+                    b"SlideX3PosUm" => self.sub_state = ParserState::ProcessingSlideX3PosUm,
+                    b"SlideY3PosUm" => self.sub_state = ParserState::ProcessingSlideY3PosUm,
+                    b"SlideX4PosUm" => self.sub_state = ParserState::ProcessingSlideX4PosUm,
+                    b"SlideY4PosUm" => self.sub_state = ParserState::ProcessingSlideY4PosUm,
+                    b"PixelWidth" => self.sub_state = ParserState::ProcessingPixelWidth,
+                    b"PixelHeight" => self.sub_state = ParserState::ProcessingPixelHeight,
+                    b"ImageFormat" => self.sub_state = ParserState::ProcessingImageFormat,
+                    b"PixelScaleCoef" => self.sub_state = ParserState::ProcessingPixelScaleCoef,
+                    b"Type" => self.sub_state = ParserState::ProcessingType,
+                    b"IsLocked" => self.sub_state = ParserState::ProcessingIsLocked,
+                    b"RotationAngle" => self.sub_state = ParserState::ProcessingRotationAngle,
+                    b"TimeStamp" | b"Timestamp" => {
+                        self.sub_state = ParserState::ProcessingTimeStamp
+                    }
+                    b"OptimalDetectorVoltageStart" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorVoltageStart
+                    }
+                    b"OptimalDetectorVoltageEnd" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorVoltageEnd
+                    }
+                    b"OptimalDetectorDualCoefficientStart" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficientStart
+                    }
+                    b"OptimalDetectorDualCoefficientEnd" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficientEnd
+                    }
+                    b"OptimalHelium" => self.sub_state = ParserState::ProcessingOptimalHelium,
+                    b"TransientStart" => self.sub_state = ParserState::ProcessingTransientStart,
+                    b"TransientCrossTalk1" => {
+                        self.sub_state = ParserState::ProcessingTransientCrossTalk1
+                    }
+                    b"TransientCrossTalk2" => {
+                        self.sub_state = ParserState::ProcessingTransientCrossTalk2
+                    }
+                    b"ReferenceEnergy" => self.sub_state = ParserState::ProcessingReferenceEnergy,
+                    b"MaximumEnergy" => self.sub_state = ParserState::ProcessingMaximumEnergy,
+                    b"CalibrationID" => self.sub_state = ParserState::ProcessingCalibrationID,
+                    b"OptimalDetectorVoltage" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorVoltage
+                    }
+                    b"OptimalDetectorDualCoefficient" => {
+                        self.sub_state = ParserState::ProcessingOptimalDetectorDualCoefficient
+                    }
+                    b"OptimalMakeupGas" => self.sub_state = ParserState::ProcessingOptimalMakeupGas,
+                    b"OptimalCurrent" => self.sub_state = ParserState::ProcessingOptimalCurrent,
+                    b"OptimalX" => self.sub_state = ParserState::ProcessingOptimalX,
+                    b"OptimalY" => self.sub_state = ParserState::ProcessingOptimalY,
+                    b"MeanDuals" => self.sub_state = ParserState::ProcessingMeanDuals,
+                    b"CoordinateX" => self.sub_state = ParserState::ProcessingCoordinateX,
+                    b"CoordinateY" => self.sub_state = ParserState::ProcessingCoordinateY,
+                    b"ChannelName" => self.sub_state = ParserState::ProcessingChannelName,
+                    b"OrderNumber" => self.sub_state = ParserState::ProcessingOrderNumber,
+                    b"AcquisitionID" => self.sub_state = ParserState::ProcessingAcquisitionID,
+                    b"ChannelLabel" => {
+                        self.sub_state = ParserState::ProcessingChannelLabel;
+                    }
+                    b"AblationPower" => self.sub_state = ParserState::ProcessingAblationPower,
+                    b"AblationDistanceBetweenShotsX" => {
+                        self.sub_state = ParserState::ProcessingAblationDistanceBetweenShotsX
+                    }
+                    b"AblationDistanceBetweenShotsY" => {
+                        self.sub_state = ParserState::ProcessingAblationDistanceBetweenShotsY
+                    }
+                    b"AblationFrequency" => {
+                        self.sub_state = ParserState::ProcessingAblationFrequency
+                    }
+                    b"AcquisitionROIID" => self.sub_state = ParserState::ProcessingAcquisitionROIID,
+                    b"SignalType" => self.sub_state = ParserState::ProcessingSignalType,
+                    b"DualCountStart" => self.sub_state = ParserState::ProcessingDualCountStart,
+                    b"DataStartOffset" => self.sub_state = ParserState::ProcessingDataStartOffset,
+                    b"DataEndOffset" => self.sub_state = ParserState::ProcessingDataEndOffset,
+                    b"StartTimeStamp" => self.sub_state = ParserState::ProcessingStartTimeStamp,
+                    b"EndTimeStamp" => self.sub_state = ParserState::ProcessingEndTimeStamp,
+                    b"AfterAblationImageEndOffset" => {
+                        self.sub_state = ParserState::ProcessingAfterAblationImageEndOffset
+                    }
+                    b"AfterAblationImageStartOffset" => {
+                        self.sub_state = ParserState::ProcessingAfterAblationImageStartOffset
+                    }
+                    b"BeforeAblationImageEndOffset" => {
+                        self.sub_state = ParserState::ProcessingBeforeAblationImageEndOffset
+                    }
+                    b"BeforeAblationImageStartOffset" => {
+                        self.sub_state = ParserState::ProcessingBeforeAblationImageStartOffset
+                    }
+                    b"ROIStartXPosUm" => self.sub_state = ParserState::ProcessingROIStartXPosUm,
+                    b"ROIStartYPosUm" => self.sub_state = ParserState::ProcessingROIStartYPosUm,
+                    b"ROIEndXPosUm" => self.sub_state = ParserState::ProcessingROIEndXPosUm,
+                    b"ROIEndYPosUm" => self.sub_state = ParserState::ProcessingROIEndYPosUm,
+                    b"MovementType" => self.sub_state = ParserState::ProcessingMovementType,
+                    b"SegmentDataFormat" => {
+                        self.sub_state = ParserState::ProcessingSegmentDataFormat
+                    }
+                    b"ValueBytes" => self.sub_state = ParserState::ProcessingValueBytes,
+                    b"MaxY" => self.sub_state = ParserState::ProcessingMaxY,
+                    b"MaxX" => self.sub_state = ParserState::ProcessingMaxX,
+                    b"PlumeStart" => self.sub_state = ParserState::ProcessingPlumeStart,
+                    b"PlumeEnd" => self.sub_state = ParserState::ProcessingPlumeEnd,
+                    b"Template" => self.sub_state = ParserState::ProcessingTemplate,
+                    b"ProfilingType" => self.sub_state = ParserState::ProcessingProfilingType,
+                    b"PanoramaID" => self.sub_state = ParserState::ProcessingPanoramaID,
+                    b"ROIType" => {
+                        // In version 2 of XSD there are empty ROIType tags, so only trigger processing of ROIType
+                        // if we have a start tag (not an empty tag)
+                        match &ev {
+                            Event::Start(_e) => self.sub_state = ParserState::ProcessingROIType,
+                            _ => self.sub_state = ParserState::Processing,
+                        };
+                    }
+                    b"SlideXPosUm" => self.sub_state = ParserState::ProcessingSlideXPosUm,
+                    b"SlideYPosUm" => self.sub_state = ParserState::ProcessingSlideYPosUm,
+                    b"PanoramaPixelXPos" => {
+                        self.sub_state = ParserState::ProcessingPanoramaPixelXPos
+                    }
+                    b"PanoramaPixelYPos" => {
+                        self.sub_state = ParserState::ProcessingPanoramaPixelYPos
+                    }
+                    _ => match std::str::from_utf8(e.local_name().as_ref()) {
+                        Ok(_name) => match &ev {
+                            // An empty element has no text
+                            Event::Empty(_) => self.unknown_tag(Some("")),
+                            _ => self.unknown_tag(None),
+                        },
+                        Err(error) => {
+                            // println!("Failed to convert tag name: {}", error);
+                            self.errors.push_back(error.into());
+
+                            self.state = ParserState::FatalError
+                        }
+                    },
+                }
+            }
             Event::End(e) => match e.local_name().as_ref() {
                 b"Slide" => {
                     let slide = self.current_slide.take().unwrap();
@@ -588,10 +779,11 @@ impl<R: Read + Seek> MCDParser<R> {
                 }
                 b"MCDSchema" => self.state = ParserState::Finished,
                 _ => match std::str::from_utf8(e.local_name().into_inner()) {
-                    Ok(name) => {
-                        self.errors.push_back(MCDError::UnknownTag {
-                            name: name.to_owned(),
-                        });
+                    Ok(_name) => {
+                        // An element which is not modelled, and had no text
+                        if matches!(self.sub_state, ParserState::ProcessingUnknown) {
+                            self.sub_state = ParserState::Processing
+                        }
                     }
                     Err(error) => {
                         self.errors.push_back(error.into());
@@ -602,6 +794,15 @@ impl<R: Read + Seek> MCDParser<R> {
             },
 
             Event::Text(e) => {
+                if matches!(self.sub_state, ParserState::ProcessingUnknown) {
+                    self.record_extra(&e.unescape().unwrap_or_default());
+                    self.sub_state = ParserState::Processing;
+
+                    return;
+                }
+
+                let mut unexpected = false;
+
                 match self.state {
                     ParserState::ProcessingSlide => {
                         let slide = self.current_slide.as_mut().unwrap();
@@ -627,10 +828,10 @@ impl<R: Read + Seek> MCDParser<R> {
                                 slide.height_um = Some(text.parse().unwrap())
                             }
                             ParserState::ProcessingImageStartOffset => {
-                                slide.image_start_offset = Some(text.parse().unwrap())
+                                slide.image_start_offset = parse_offset(text)
                             }
                             ParserState::ProcessingImageEndOffset => {
-                                slide.image_end_offset = Some(text.parse().unwrap())
+                                slide.image_end_offset = parse_offset(text)
                             }
                             ParserState::ProcessingImageFile => {
                                 slide.image_file = Some(text.to_string())
@@ -695,10 +896,10 @@ impl<R: Read + Seek> MCDParser<R> {
                                 panorama.slide_y4_pos_um = Some(text.parse().unwrap())
                             }
                             ParserState::ProcessingImageStartOffset => {
-                                panorama.image_start_offset = Some(text.parse().unwrap())
+                                panorama.image_start_offset = parse_offset(text)
                             }
                             ParserState::ProcessingImageEndOffset => {
-                                panorama.image_end_offset = Some(text.parse().unwrap())
+                                panorama.image_end_offset = parse_offset(text)
                             }
                             ParserState::ProcessingPixelWidth => {
                                 panorama.pixel_width = Some(text.parse().unwrap())
@@ -735,16 +936,11 @@ impl<R: Read + Seek> MCDParser<R> {
                                 panorama.is_locked = Some(text.parse().unwrap())
                             }
                             ParserState::ProcessingRotationAngle => {
-                                println!("{:?}", text);
                                 panorama.rotation_angle = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -802,12 +998,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 calibration_final.maximum_energy = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -857,12 +1049,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 calibration_params.optimal_helium = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -888,12 +1076,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 calibration_channel.mean_duals = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -915,12 +1099,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 calibration.time_stamp = Some(text.to_string())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -946,12 +1126,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 slide_fiducal_marks.coordinate_y = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -976,12 +1152,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 slide_profile.coordinate_y = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -1020,12 +1192,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 acquisition_channel.channel_label = Some(text.to_owned())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -1070,10 +1238,10 @@ impl<R: Read + Seek> MCDParser<R> {
                                 acquisition.dual_count_start = Some(text.to_owned())
                             }
                             ParserState::ProcessingDataStartOffset => {
-                                acquisition.data_start_offset = Some(text.parse().unwrap())
+                                acquisition.data_start_offset = parse_offset(text)
                             }
                             ParserState::ProcessingDataEndOffset => {
-                                acquisition.data_end_offset = Some(text.parse().unwrap())
+                                acquisition.data_end_offset = parse_offset(text)
                             }
                             ParserState::ProcessingStartTimeStamp => {
                                 acquisition.start_timestamp = Some(text.to_owned())
@@ -1082,20 +1250,16 @@ impl<R: Read + Seek> MCDParser<R> {
                                 acquisition.end_timestamp = Some(text.to_owned())
                             }
                             ParserState::ProcessingAfterAblationImageEndOffset => {
-                                acquisition.after_ablation_image_end_offset =
-                                    Some(text.parse().unwrap())
+                                acquisition.after_ablation_image_end_offset = parse_offset(text)
                             }
                             ParserState::ProcessingAfterAblationImageStartOffset => {
-                                acquisition.after_ablation_image_start_offset =
-                                    Some(text.parse().unwrap())
+                                acquisition.after_ablation_image_start_offset = parse_offset(text)
                             }
                             ParserState::ProcessingBeforeAblationImageEndOffset => {
-                                acquisition.before_ablation_image_end_offset =
-                                    Some(text.parse().unwrap())
+                                acquisition.before_ablation_image_end_offset = parse_offset(text)
                             }
                             ParserState::ProcessingBeforeAblationImageStartOffset => {
-                                acquisition.before_ablation_image_start_offset =
-                                    Some(text.parse().unwrap())
+                                acquisition.before_ablation_image_start_offset = parse_offset(text)
                             }
                             ParserState::ProcessingROIStartXPosUm => {
                                 acquisition.roi_start_x_pos_um = Some(text.parse().unwrap())
@@ -1112,16 +1276,11 @@ impl<R: Read + Seek> MCDParser<R> {
                             ParserState::ProcessingMovementType => {
                                 acquisition.movement_type = Some(text.to_owned())
                             }
-                            ParserState::ProcessingSegmentDataFormat => match text {
-                                "Float" => {
-                                    acquisition.segment_data_format = Some(DataFormat::Float)
-                                }
-                                _ => {
-                                    panic!("Unknown segment data format: {}", text);
-                                }
-                            },
+                            ParserState::ProcessingSegmentDataFormat => {
+                                acquisition.segment_data_format = Some(DataFormat::from_name(text))
+                            }
                             ParserState::ProcessingValueBytes => {
-                                acquisition.value_bytes = Some(text.parse().unwrap())
+                                acquisition.value_bytes = text.trim().parse().ok()
                             }
                             ParserState::ProcessingMaxX => {
                                 acquisition.max_x = Some(text.parse().unwrap())
@@ -1147,12 +1306,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 }
                             },
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -1182,12 +1337,8 @@ impl<R: Read + Seek> MCDParser<R> {
                                 }
                             },
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
@@ -1219,18 +1370,18 @@ impl<R: Read + Seek> MCDParser<R> {
                                 roi_point.panorama_pixel_y_pos = Some(text.parse().unwrap())
                             }
                             ParserState::Processing => {}
-                            _ => {
-                                panic!(
-                                    "Unknown sub state {:?} in state {:?} with event {:?}",
-                                    self.sub_state, self.state, e
-                                );
-                            }
+                            // A known tag which is not expected within this element
+                            _ => unexpected = true,
                         }
 
                         self.sub_state = ParserState::Processing
                     }
                     _ => {}
                 }
+
+                if unexpected {
+                    self.unknown_tag(Some(&e.unescape().unwrap_or_default()));
+                }
                 //println!("text: {}", std::str::from_utf8(&e.unescaped()?)?);
             }
 
@@ -1240,3 +1391,168 @@ impl<R: Read + Seek> MCDParser<R> {
         }
     }
 }
+
+/// Parse an offset recorded in the XML as `u64`, so that any offset within a large file can be represented.
+/// Negative offsets (no data stored) are treated as 0.
+fn parse_offset(text: &str) -> Option<u64> {
+    let text = text.trim();
+
+    match text.parse::<u64>() {
+        Ok(offset) => Some(offset),
+        Err(_) => text.parse::<i64>().ok().map(|_| 0),
+    }
+}
+
+/// Returns true if the element being processed holds text (rather than a number), so can be empty
+fn is_text_field(state: ParserState, sub_state: ParserState) -> bool {
+    matches!(
+        (state, sub_state),
+        (
+            ParserState::ProcessingSlide,
+            ParserState::ProcessingUID
+                | ParserState::ProcessingDescription
+                | ParserState::ProcessingFilename
+                | ParserState::ProcessingSlideType
+                | ParserState::ProcessingImageFile
+                | ParserState::ProcessingName
+                | ParserState::ProcessingSwVersion
+        ) | (
+            ParserState::ProcessingPanorama | ParserState::ProcessingAcquisitionROI,
+            ParserState::ProcessingDescription
+        ) | (
+            ParserState::ProcessingAcquisition,
+            ParserState::ProcessingDescription
+                | ParserState::ProcessingSignalType
+                | ParserState::ProcessingDualCountStart
+                | ParserState::ProcessingStartTimeStamp
+                | ParserState::ProcessingEndTimeStamp
+                | ParserState::ProcessingMovementType
+                | ParserState::ProcessingTemplate
+        ) | (
+            ParserState::ProcessingAcquisitionChannel,
+            ParserState::ProcessingChannelName | ParserState::ProcessingChannelLabel
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec};
+
+    use super::*;
+
+    /// Returns an .mcd file with `edit` applied to the XML metadata
+    fn edited_mcd<F: Fn(String) -> String>(edit: F) -> Result<Vec<u8>> {
+        let mut data = synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 1, 1).channel("Ir191", "DNA1"),
+            [1.0],
+        )])?;
+
+        // The XML is written at the end of the file
+        let xml = MCD::from_bytes(data.clone())?.xml()?;
+        data.truncate(data.len() - xml.encode_utf16().count() * 2);
+        data.extend(edit(xml).encode_utf16().flat_map(u16::to_le_bytes));
+
+        Ok(data)
+    }
+
+    #[test]
+    fn tolerates_unknown_tags() -> Result<()> {
+        let data = edited_mcd(|xml| {
+            xml.replacen("<Slide>", "<Slide><NewSlideField> v3 </NewSlideField>", 1)
+                .replacen(
+                    "<Panorama>",
+                    "<Panorama><ChannelName>Misplaced</ChannelName>",
+                    1,
+                )
+                .replacen("<Acquisition>", "<Acquisition><Calibrated />", 1)
+                .replacen(
+                    "<AcquisitionChannel>",
+                    "<AcquisitionChannel><Metal>Ir</Metal>",
+                    1,
+                )
+                .replace(
+                    "</MCDSchema>",
+                    "<FutureElement><ID>1</ID></FutureElement></MCDSchema>",
+                )
+        })?;
+
+        let mcd = MCD::from_bytes(data.clone())?;
+        let slide = mcd.slide(1).expect("Slide 1 is present");
+        assert_eq!(
+            slide.extras().get("NewSlideField").map(String::as_str),
+            Some("v3")
+        );
+        let panorama = slide.panorama(1).expect("Panorama 1 is present");
+        assert_eq!(
+            panorama.extras().get("ChannelName").map(String::as_str),
+            Some("Misplaced")
+        );
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(
+            acquisition.extras().get("Calibrated").map(String::as_str),
+            Some("")
+        );
+        assert_eq!(acquisition.channels()[0].name(), "Ir191");
+        assert_eq!(
+            acquisition.channels()[0]
+                .extras()
+                .get("Metal")
+                .map(String::as_str),
+            Some("Ir")
+        );
+
+        let report = mcd.parse_report();
+        assert_eq!(report.num_unknown_tags(), 5);
+        assert_eq!(report.unknown_tags().get("FutureElement"), Some(&1));
+
+        // Unless unknown tags are not tolerated
+        let options = ParseOptions::new().tolerate_unknown_tags(false);
+        match MCD::parse_with_options(Cursor::new(data), &options) {
+            Err(MCDError::UnknownTag { name }) => assert_eq!(name, "NewSlideField"),
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn recovers_invalid_value_bytes() -> Result<()> {
+        let data = edited_mcd(|xml| {
+            xml.replace(
+                "<ValueBytes>4</ValueBytes>",
+                "<ValueBytes>four</ValueBytes>",
+            )
+        })?;
+
+        let mcd = MCD::from_bytes(data)?;
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(acquisition.value_bytes(), 4);
+        assert_eq!(acquisition.spectrum(0, 0)?, vec![1.0]);
+        assert!(mcd
+            .parse_report()
+            .recovered_fields()
+            .contains(&RecoveredField::new("Acquisition", 1, "ValueBytes")));
+
+        // The size can't be recovered for an unknown data format, so the intensities can't be read
+        let data = edited_mcd(|xml| {
+            xml.replace("<ValueBytes>4</ValueBytes>", "<ValueBytes></ValueBytes>")
+                .replace(
+                    "<SegmentDataFormat>Float</SegmentDataFormat>",
+                    "<SegmentDataFormat>Half</SegmentDataFormat>",
+                )
+        })?;
+
+        let mcd = MCD::from_bytes(data)?;
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(acquisition.value_bytes(), 0);
+        assert!(matches!(
+            acquisition.spectra(),
+            Err(MCDError::UnsupportedDataFormat { value_bytes: 0, .. })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,856 @@
+use std::{
+    fmt::{Display, Write as _},
+    io::Write,
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{
+    acquisition::DataFormat,
+    error::{MCDError, Result},
+    span::IMAGE_HEADER_SIZE,
+};
+
+/// Namespace of the .mcd schema
+const XMLNS: &str = "http://www.fluidigm.com/IMC/MCDSchema_V2_0.xsd";
+
+/// Description of a slide to be written by `MCDWriter`
+#[derive(Debug, Clone)]
+pub struct SlideSpec {
+    id: u16,
+    uid: Option<String>,
+    description: String,
+    filename: String,
+    slide_type: String,
+    width_um: f64,
+    height_um: f64,
+    image_file: String,
+    sw_version: String,
+    image: Option<Vec<u8>>,
+    fiducial_marks: Vec<(u32, u32)>,
+}
+
+impl SlideSpec {
+    /// Create a standard (75 mm x 25 mm) slide with the specified ID
+    pub fn new(id: u16) -> Self {
+        SlideSpec {
+            id,
+            uid: None,
+            description: String::new(),
+            filename: String::new(),
+            slide_type: "Slide".to_string(),
+            width_um: 75000.0,
+            height_um: 25000.0,
+            image_file: String::new(),
+            sw_version: "7.0.8493.0".to_string(),
+            image: None,
+            fiducial_marks: Vec::new(),
+        }
+    }
+
+    /// Set the unique identifier of the slide
+    pub fn uid(mut self, uid: &str) -> Self {
+        self.uid = Some(uid.to_string());
+        self
+    }
+
+    /// Set the description of the slide
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Set the filename of the .mcd file, as recorded by the instrument
+    pub fn filename(mut self, filename: &str) -> Self {
+        self.filename = filename.to_string();
+        self
+    }
+
+    /// Set the type of slide
+    pub fn slide_type(mut self, slide_type: &str) -> Self {
+        self.slide_type = slide_type.to_string();
+        self
+    }
+
+    /// Set the dimensions of the slide (in μm)
+    pub fn dimensions(mut self, width_um: f64, height_um: f64) -> Self {
+        self.width_um = width_um;
+        self.height_um = height_um;
+        self
+    }
+
+    /// Set the version of the acquisition software. Slide images are read as JPEG for version 6 and PNG
+    /// otherwise.
+    pub fn software_version(mut self, sw_version: &str) -> Self {
+        self.sw_version = sw_version.to_string();
+        self
+    }
+
+    /// Set the optical image of the slide (encoded, e.g. as PNG) and the name of the file it came from
+    pub fn image(mut self, image_file: &str, image: Vec<u8>) -> Self {
+        self.image_file = image_file.to_string();
+        self.image = Some(image);
+        self
+    }
+
+    /// Add a fiducial mark at the recorded position (stage coordinates, in μm)
+    pub fn fiducial_mark(mut self, x: u32, y: u32) -> Self {
+        self.fiducial_marks.push((x, y));
+        self
+    }
+}
+
+/// Description of a panorama to be written by `MCDWriter`
+#[derive(Debug, Clone)]
+pub struct PanoramaSpec {
+    id: u16,
+    slide_id: u16,
+    description: String,
+    corners: [(f64, f64); 4],
+    pixel_width: i64,
+    pixel_height: i64,
+    pixel_scale_coef: f64,
+    rotation_angle: Option<f64>,
+    image: Option<Vec<u8>>,
+}
+
+impl PanoramaSpec {
+    /// Create a panorama with the specified ID on the slide
+    pub fn new(id: u16, slide_id: u16) -> Self {
+        PanoramaSpec {
+            id,
+            slide_id,
+            description: String::new(),
+            corners: [(0.0, 0.0); 4],
+            pixel_width: 0,
+            pixel_height: 0,
+            pixel_scale_coef: 1.0,
+            rotation_angle: None,
+            image: None,
+        }
+    }
+
+    /// Set the description of the panorama
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Set the corners of the panorama on the slide (in μm), in the order top left, top right, bottom right,
+    /// bottom left
+    pub fn corners(mut self, corners: [(f64, f64); 4]) -> Self {
+        self.corners = corners;
+        self
+    }
+
+    /// Set the area of the slide (in μm) covered by the panorama, where the slide y-axis points up
+    pub fn bounds(self, min_x: f64, min_y: f64, width: f64, height: f64) -> Self {
+        self.corners([
+            (min_x, min_y + height),
+            (min_x + width, min_y + height),
+            (min_x + width, min_y),
+            (min_x, min_y),
+        ])
+    }
+
+    /// Set the dimensions of the panorama image (in pixels). If not set, they are read from the image.
+    pub fn dimensions(mut self, pixel_width: i64, pixel_height: i64) -> Self {
+        self.pixel_width = pixel_width;
+        self.pixel_height = pixel_height;
+        self
+    }
+
+    /// Set the scaling coefficient for pixel sizes
+    pub fn pixel_scale_coef(mut self, pixel_scale_coef: f64) -> Self {
+        self.pixel_scale_coef = pixel_scale_coef;
+        self
+    }
+
+    /// Set the rotation angle of the panorama (in degrees anti-clockwise on the slide), about the centre of its
+    /// corners
+    pub fn rotation_angle(mut self, rotation_angle: f64) -> Self {
+        self.rotation_angle = Some(rotation_angle);
+        self
+    }
+
+    /// Set the (PNG encoded) optical image of the panorama
+    pub fn image(mut self, image: Vec<u8>) -> Self {
+        self.image = Some(image);
+        self
+    }
+}
+
+/// Description of an acquisition to be written by `MCDWriter`. The channels are stored in the order they are
+/// added, which is the order of the intensities of each spectrum.
+#[derive(Debug, Clone)]
+pub struct AcquisitionSpec {
+    id: u16,
+    panorama_id: u16,
+    description: String,
+    order_number: i16,
+    width: i32,
+    height: i32,
+    ablation_power: f64,
+    ablation_distance_between_shots: (f64, f64),
+    ablation_frequency: f64,
+    signal_type: String,
+    dual_count_start: String,
+    start_timestamp: String,
+    end_timestamp: String,
+    roi_start_um: (f64, f64),
+    movement_type: String,
+    plume_start: i32,
+    plume_end: i32,
+    template: String,
+    channels: Vec<(String, String)>,
+    roi_points: Vec<(f64, f64)>,
+    before_ablation_image: Option<Vec<u8>>,
+    after_ablation_image: Option<Vec<u8>>,
+    segment_data_format: DataFormat,
+}
+
+impl AcquisitionSpec {
+    /// Create an acquisition with the specified ID within the panorama, with the specified dimensions (in
+    /// pixels)
+    pub fn new(id: u16, panorama_id: u16, width: i32, height: i32) -> Self {
+        AcquisitionSpec {
+            id,
+            panorama_id,
+            description: String::new(),
+            order_number: id as i16,
+            width,
+            height,
+            ablation_power: 0.0,
+            ablation_distance_between_shots: (1.0, 1.0),
+            ablation_frequency: 200.0,
+            signal_type: "Dual".to_string(),
+            dual_count_start: "0".to_string(),
+            start_timestamp: String::new(),
+            end_timestamp: String::new(),
+            roi_start_um: (0.0, 0.0),
+            movement_type: "XYLines".to_string(),
+            plume_start: 0,
+            plume_end: 0,
+            template: String::new(),
+            channels: Vec::new(),
+            roi_points: Vec::new(),
+            before_ablation_image: None,
+            after_ablation_image: None,
+            segment_data_format: DataFormat::Float,
+        }
+    }
+
+    /// Set the description of the acquisition
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Set the position of the acquisition in the order of acquisition
+    pub fn order_number(mut self, order_number: i16) -> Self {
+        self.order_number = order_number;
+        self
+    }
+
+    /// Set the position of the top left of the acquisition on the slide (in μm), where the slide y-axis points
+    /// up
+    pub fn position(mut self, x_um: f64, y_um: f64) -> Self {
+        self.roi_start_um = (x_um, y_um);
+        self
+    }
+
+    /// Set the distance between shots (in μm), i.e. the size of each pixel
+    pub fn distance_between_shots(mut self, x_um: f64, y_um: f64) -> Self {
+        self.ablation_distance_between_shots = (x_um, y_um);
+        self
+    }
+
+    /// Set the ablation power and frequency (in Hz)
+    pub fn ablation(mut self, power: f64, frequency: f64) -> Self {
+        self.ablation_power = power;
+        self.ablation_frequency = frequency;
+        self
+    }
+
+    /// Set the type of signal recorded
+    pub fn signal_type(mut self, signal_type: &str) -> Self {
+        self.signal_type = signal_type.to_string();
+        self
+    }
+
+    /// Set when the acquisition started and ended
+    pub fn timestamps(mut self, start: &str, end: &str) -> Self {
+        self.start_timestamp = start.to_string();
+        self.end_timestamp = end.to_string();
+        self
+    }
+
+    /// Set the window (in pushes) over which each plume was integrated
+    pub fn plume(mut self, start: i32, end: i32) -> Self {
+        self.plume_start = start;
+        self.plume_end = end;
+        self
+    }
+
+    /// Set how the stage moved during the acquisition
+    pub fn movement_type(mut self, movement_type: &str) -> Self {
+        self.movement_type = movement_type.to_string();
+        self
+    }
+
+    /// Set the dual count start recorded for the acquisition
+    pub fn dual_count_start(mut self, dual_count_start: &str) -> Self {
+        self.dual_count_start = dual_count_start.to_string();
+        self
+    }
+
+    /// Set the format in which the intensities are stored (`Float` by default). Intensities are rounded to the
+    /// nearest integer (and clamped to the range of the type) for `UInt16` and `UInt32`.
+    pub fn segment_data_format(mut self, segment_data_format: DataFormat) -> Self {
+        self.segment_data_format = segment_data_format;
+        self
+    }
+
+    /// Set the name of the template used for the acquisition
+    pub fn template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+        self
+    }
+
+    /// Add a channel (e.g. `Ir191`, `DNA1`)
+    pub fn channel(mut self, name: &str, label: &str) -> Self {
+        self.channels.push((name.to_string(), label.to_string()));
+        self
+    }
+
+    /// Set the points outlining the ROI which defined the acquisition (on the slide, in μm), e.g. for a free-hand
+    /// ROI
+    pub fn roi_points(mut self, points: &[(f64, f64)]) -> Self {
+        self.roi_points = points.to_vec();
+        self
+    }
+
+    /// Set the (PNG encoded) optical image of the acquisition region prior to ablation
+    pub fn before_ablation_image(mut self, image: Vec<u8>) -> Self {
+        self.before_ablation_image = Some(image);
+        self
+    }
+
+    /// Set the (PNG encoded) optical image of the acquisition region after ablation
+    pub fn after_ablation_image(mut self, image: Vec<u8>) -> Self {
+        self.after_ablation_image = Some(image);
+        self
+    }
+}
+
+/// Start and end offsets of some data in the file
+type Extent = (u64, u64);
+
+/// Writes an .mcd file: the optical images and spectra are written as they are added, followed by the XML
+/// describing them when finished.
+///
+/// ```no_run
+/// use std::fs::File;
+///
+/// use imc_rs::{AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+///
+/// let mut writer = MCDWriter::new(File::create("synthetic.mcd")?);
+/// writer.add_slide(SlideSpec::new(1).description("Synthetic"))?;
+/// writer.add_panorama(PanoramaSpec::new(1, 1).bounds(1000.0, 1000.0, 500.0, 500.0))?;
+///
+/// let acquisition = AcquisitionSpec::new(1, 1, 2, 1)
+///     .position(1000.0, 1500.0)
+///     .channel("X", "X")
+///     .channel("Ir191", "DNA1");
+/// writer.add_acquisition(acquisition, &[0.0, 10.0, 1.0, 12.5])?;
+///
+/// writer.finish()?;
+/// # Ok::<(), imc_rs::error::MCDError>(())
+/// ```
+pub struct MCDWriter<W: Write> {
+    writer: W,
+    offset: u64,
+
+    slides: Vec<(SlideSpec, Extent)>,
+    panoramas: Vec<(PanoramaSpec, Extent)>,
+    // (acquisition, spectra, before ablation image, after ablation image)
+    acquisitions: Vec<(AcquisitionSpec, Extent, Extent, Extent)>,
+}
+
+impl<W: Write> MCDWriter<W> {
+    /// Create a writer of an .mcd file to `writer`, which should be empty
+    pub fn new(writer: W) -> Self {
+        MCDWriter {
+            writer,
+            offset: 0,
+            slides: Vec::new(),
+            panoramas: Vec::new(),
+            acquisitions: Vec::new(),
+        }
+    }
+
+    /// Add a slide, writing its image (if any)
+    pub fn add_slide(&mut self, mut slide: SlideSpec) -> Result<()> {
+        if self.slides.iter().any(|(other, _)| other.id == slide.id) {
+            return Err(invalid(format!(
+                "slide {} has already been added",
+                slide.id
+            )));
+        }
+
+        let image = self.write_image(slide.image.take())?;
+        self.slides.push((slide, image));
+
+        Ok(())
+    }
+
+    /// Add a panorama to a slide which has already been added, writing its image (if any)
+    pub fn add_panorama(&mut self, mut panorama: PanoramaSpec) -> Result<()> {
+        if !self
+            .slides
+            .iter()
+            .any(|(slide, _)| slide.id == panorama.slide_id)
+        {
+            return Err(invalid(format!(
+                "panorama {} is on slide {}, which hasn't been added",
+                panorama.id, panorama.slide_id
+            )));
+        }
+        if self
+            .panoramas
+            .iter()
+            .any(|(other, _)| other.id == panorama.id)
+        {
+            return Err(invalid(format!(
+                "panorama {} has already been added",
+                panorama.id
+            )));
+        }
+
+        let image = self.write_image(panorama.image.take())?;
+        self.panoramas.push((panorama, image));
+
+        Ok(())
+    }
+
+    /// Add an acquisition to a panorama which has already been added, writing its images (if any) and
+    /// spectra. `spectra` holds the intensity of each channel for each pixel in turn, row by row. Fewer spectra
+    /// than pixels may be supplied, as for an acquisition which was aborted.
+    pub fn add_acquisition(&mut self, acquisition: AcquisitionSpec, spectra: &[f32]) -> Result<()> {
+        let num_channels = acquisition.channels.len().max(1);
+        if !spectra.len().is_multiple_of(num_channels) {
+            return Err(MCDError::InvalidBufferSize {
+                expected: spectra.len().next_multiple_of(num_channels),
+                actual: spectra.len(),
+            });
+        }
+
+        self.add_acquisition_with(
+            acquisition,
+            spectra.len() / num_channels,
+            |first_index, num_spectra| {
+                Ok(
+                    spectra[first_index * num_channels..(first_index + num_spectra) * num_channels]
+                        .to_vec(),
+                )
+            },
+        )
+    }
+
+    /// Add an acquisition, as for `add_acquisition()`, where `num_spectra` spectra are requested in batches
+    /// from `read_spectra(first_index, num_spectra)`
+    pub(crate) fn add_acquisition_with<F>(
+        &mut self,
+        mut acquisition: AcquisitionSpec,
+        num_spectra: usize,
+        mut read_spectra: F,
+    ) -> Result<()>
+    where
+        F: FnMut(usize, usize) -> Result<Vec<f32>>,
+    {
+        if !self
+            .panoramas
+            .iter()
+            .any(|(panorama, _)| panorama.id == acquisition.panorama_id)
+        {
+            return Err(invalid(format!(
+                "acquisition {} is in panorama {}, which hasn't been added",
+                acquisition.id, acquisition.panorama_id
+            )));
+        }
+        if self
+            .acquisitions
+            .iter()
+            .any(|(other, ..)| other.id == acquisition.id)
+        {
+            return Err(invalid(format!(
+                "acquisition {} has already been added",
+                acquisition.id
+            )));
+        }
+        if acquisition.channels.is_empty() {
+            return Err(invalid(format!(
+                "acquisition {} has no channels",
+                acquisition.id
+            )));
+        }
+
+        let num_channels = acquisition.channels.len();
+        let width = acquisition.width.max(0) as usize;
+        let num_pixels = width * acquisition.height.max(0) as usize;
+        if num_spectra > num_pixels {
+            return Err(MCDError::InvalidBufferSize {
+                expected: num_pixels * num_channels,
+                actual: num_spectra * num_channels,
+            });
+        }
+
+        let value_bytes = acquisition
+            .segment_data_format
+            .default_value_bytes()
+            .ok_or_else(|| {
+                invalid(format!(
+                    "acquisition {} has the unsupported segment data format {}",
+                    acquisition.id, acquisition.segment_data_format
+                ))
+            })?;
+
+        let before = self.write_image(acquisition.before_ablation_image.take())?;
+        let after = self.write_image(acquisition.after_ablation_image.take())?;
+
+        let start = self.offset;
+        // Spectra are written a row at a time
+        let batch_size = width.max(1);
+
+        for first_index in (0..num_spectra).step_by(batch_size) {
+            let batch = batch_size.min(num_spectra - first_index);
+            let spectra = read_spectra(first_index, batch)?;
+
+            if spectra.len() != batch * num_channels {
+                return Err(MCDError::InvalidBufferSize {
+                    expected: batch * num_channels,
+                    actual: spectra.len(),
+                });
+            }
+
+            let mut buffer = Vec::with_capacity(spectra.len() * value_bytes as usize);
+            for intensity in spectra {
+                match acquisition.segment_data_format {
+                    DataFormat::UInt16 => {
+                        buffer.write_u16::<LittleEndian>(intensity.round() as u16)?
+                    }
+                    DataFormat::UInt32 => {
+                        buffer.write_u32::<LittleEndian>(intensity.round() as u32)?
+                    }
+                    _ => buffer.write_f32::<LittleEndian>(intensity)?,
+                }
+            }
+            self.write(&buffer)?;
+        }
+
+        self.acquisitions
+            .push((acquisition, (start, self.offset), before, after));
+
+        Ok(())
+    }
+
+    /// Write the XML describing everything which has been added, returning the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        let mut xml = String::new();
+        self.write_xml(&mut xml);
+
+        let mut buffer = Vec::with_capacity(xml.len() * 2);
+        for unit in xml.encode_utf16() {
+            buffer.write_u16::<LittleEndian>(unit)?;
+        }
+        self.write(&buffer)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        self.offset += data.len() as u64;
+
+        Ok(())
+    }
+
+    fn write_image(&mut self, image: Option<Vec<u8>>) -> Result<Extent> {
+        match image {
+            Some(image) => {
+                let start = self.offset;
+                self.write(&[0; IMAGE_HEADER_SIZE as usize])?;
+                self.write(&image)?;
+
+                Ok((start, self.offset))
+            }
+            None => Ok((0, 0)),
+        }
+    }
+
+    fn write_xml(&self, xml: &mut String) {
+        xml.push_str(&format!("<MCDSchema xmlns=\"{}\">", XMLNS));
+
+        for (slide, image) in &self.slides {
+            let mut element = Element::new(xml, "Slide");
+            element.field("ID", slide.id);
+            if let Some(uid) = &slide.uid {
+                element.field("UID", uid);
+            }
+            element.field("Description", &slide.description);
+            element.field("Filename", &slide.filename);
+            element.field("SlideType", &slide.slide_type);
+            element.field("WidthUm", slide.width_um);
+            element.field("HeightUm", slide.height_um);
+            element.field("ImageStartOffset", image.0);
+            element.field("ImageEndOffset", image.1);
+            element.field("ImageFile", &slide.image_file);
+            element.field("SwVersion", &slide.sw_version);
+            element.end();
+        }
+
+        let fiducial_marks = self.slides.iter().flat_map(|(slide, _)| {
+            slide
+                .fiducial_marks
+                .iter()
+                .map(move |position| (slide.id, position))
+        });
+        for (id, (slide_id, (x, y))) in fiducial_marks.enumerate() {
+            let mut element = Element::new(xml, "SlideFiducialMarks");
+            element.field("ID", id + 1);
+            element.field("SlideID", slide_id);
+            element.field("CoordinateX", x);
+            element.field("CoordinateY", y);
+            element.end();
+        }
+
+        for (panorama, image) in &self.panoramas {
+            let mut element = Element::new(xml, "Panorama");
+            element.field("ID", panorama.id);
+            element.field("SlideID", panorama.slide_id);
+            element.field("Description", &panorama.description);
+            for (index, (x, y)) in panorama.corners.iter().enumerate() {
+                element.field(&format!("SlideX{}PosUm", index + 1), x);
+                element.field(&format!("SlideY{}PosUm", index + 1), y);
+            }
+            element.field("ImageStartOffset", image.0);
+            element.field("ImageEndOffset", image.1);
+            element.field("PixelWidth", panorama.pixel_width);
+            element.field("PixelHeight", panorama.pixel_height);
+            element.field("ImageFormat", "PNG");
+            element.field("PixelScaleCoef", panorama.pixel_scale_coef);
+            if let Some(rotation_angle) = panorama.rotation_angle {
+                element.field("RotationAngle", rotation_angle);
+            }
+            element.end();
+        }
+
+        // Acquisitions are matched to their panorama through an ROI with the same ID
+        for (acquisition, ..) in &self.acquisitions {
+            let mut element = Element::new(xml, "AcquisitionROI");
+            element.field("ID", acquisition.id);
+            element.field("PanoramaID", acquisition.panorama_id);
+            element.field("ROIType", "Acquisition");
+            element.end();
+        }
+
+        let roi_points = self.acquisitions.iter().flat_map(|(acquisition, ..)| {
+            acquisition
+                .roi_points
+                .iter()
+                .enumerate()
+                .map(move |(order_number, point)| (acquisition.id, order_number, point))
+        });
+        for (id, (acquisition_id, order_number, (x, y))) in roi_points.enumerate() {
+            let mut element = Element::new(xml, "ROIPoint");
+            element.field("ID", id + 1);
+            element.field("AcquisitionROIID", acquisition_id);
+            element.field("OrderNumber", order_number);
+            element.field("SlideXPosUm", x);
+            element.field("SlideYPosUm", y);
+            element.field("PanoramaPixelXPos", 0);
+            element.field("PanoramaPixelYPos", 0);
+            element.end();
+        }
+
+        for (acquisition, data, before, after) in &self.acquisitions {
+            let (distance_x, distance_y) = acquisition.ablation_distance_between_shots;
+            let (start_x, start_y) = acquisition.roi_start_um;
+
+            let mut element = Element::new(xml, "Acquisition");
+            element.field("ID", acquisition.id);
+            element.field("Description", &acquisition.description);
+            element.field("AblationPower", acquisition.ablation_power);
+            element.field("AblationDistanceBetweenShotsX", distance_x);
+            element.field("AblationDistanceBetweenShotsY", distance_y);
+            element.field("AblationFrequency", acquisition.ablation_frequency);
+            element.field("AcquisitionROIID", acquisition.id);
+            element.field("OrderNumber", acquisition.order_number);
+            element.field("SignalType", &acquisition.signal_type);
+            element.field("DualCountStart", &acquisition.dual_count_start);
+            element.field("DataStartOffset", data.0);
+            element.field("DataEndOffset", data.1);
+            element.field("StartTimeStamp", &acquisition.start_timestamp);
+            element.field("EndTimeStamp", &acquisition.end_timestamp);
+            element.field("AfterAblationImageStartOffset", after.0);
+            element.field("AfterAblationImageEndOffset", after.1);
+            element.field("BeforeAblationImageStartOffset", before.0);
+            element.field("BeforeAblationImageEndOffset", before.1);
+            element.field("ROIStartXPosUm", start_x);
+            element.field("ROIStartYPosUm", start_y);
+            element.field(
+                "ROIEndXPosUm",
+                start_x + acquisition.width as f64 * distance_x,
+            );
+            element.field(
+                "ROIEndYPosUm",
+                start_y - acquisition.height as f64 * distance_y,
+            );
+            element.field("MovementType", &acquisition.movement_type);
+            element.field("SegmentDataFormat", &acquisition.segment_data_format);
+            element.field(
+                "ValueBytes",
+                acquisition
+                    .segment_data_format
+                    .default_value_bytes()
+                    .unwrap_or(4),
+            );
+            element.field("MaxX", acquisition.width);
+            element.field("MaxY", acquisition.height);
+            element.field("PlumeStart", acquisition.plume_start);
+            element.field("PlumeEnd", acquisition.plume_end);
+            element.field("Template", &acquisition.template);
+            element.end();
+        }
+
+        let mut channel_id = 0u32;
+        for (acquisition, ..) in &self.acquisitions {
+            for (order_number, (name, label)) in acquisition.channels.iter().enumerate() {
+                channel_id += 1;
+
+                let mut element = Element::new(xml, "AcquisitionChannel");
+                element.field("ID", channel_id);
+                element.field("ChannelName", name);
+                element.field("OrderNumber", order_number);
+                element.field("AcquisitionID", acquisition.id);
+                element.field("ChannelLabel", label);
+                element.end();
+            }
+        }
+
+        xml.push_str("</MCDSchema>");
+    }
+}
+
+/// Writes an element of the XML, made up of fields with text content
+struct Element<'a> {
+    xml: &'a mut String,
+    name: &'static str,
+}
+
+impl<'a> Element<'a> {
+    fn new(xml: &'a mut String, name: &'static str) -> Self {
+        xml.push_str(&format!("<{}>", name));
+
+        Element { xml, name }
+    }
+
+    fn field<T: Display>(&mut self, name: &str, value: T) {
+        self.xml.push_str(&format!("<{}>", name));
+        escape(self.xml, &value.to_string());
+        self.xml.push_str(&format!("</{}>", name));
+    }
+
+    fn end(self) {
+        self.xml.push_str(&format!("</{}>", self.name));
+    }
+}
+
+/// Escape text for the XML. Non-ASCII characters are written as character references, as the XML must not
+/// contain bytes which are invalid UTF-8 (see `MCDWriter::finish`).
+fn escape(xml: &mut String, text: &str) {
+    for character in text.chars() {
+        match character {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            character if character.is_ascii() => xml.push(character),
+            character => {
+                let _ = write!(xml, "&#{};", character as u32);
+            }
+        }
+    }
+}
+
+fn invalid(reason: String) -> MCDError {
+    MCDError::InvalidStructure { reason }
+}
+
+/// Returns an .mcd file with a single slide and panorama (ID 1, 100 x 100 μm), containing the acquisitions with
+/// their spectra
+#[cfg(test)]
+pub(crate) fn synthetic_mcd<S: AsRef<[f32]>>(
+    acquisitions: impl IntoIterator<Item = (AcquisitionSpec, S)>,
+) -> Result<Vec<u8>> {
+    let mut writer = MCDWriter::new(std::io::Cursor::new(Vec::new()));
+    writer.add_slide(SlideSpec::new(1))?;
+    writer.add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 100.0, 100.0))?;
+    for (acquisition, spectra) in acquisitions {
+        writer.add_acquisition(acquisition, spectra.as_ref())?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, RgbImage};
+
+    use super::*;
+    use crate::{ChannelIdentifier, MCD};
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::new(4, 3).write_to(&mut png, ImageOutputFormat::Png)?;
+
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(SlideSpec::new(1).description("Synthetic"))?;
+        writer.add_panorama(
+            PanoramaSpec::new(2, 1)
+                .bounds(1000.0, 1000.0, 400.0, 300.0)
+                .image(png.into_inner()),
+        )?;
+
+        assert!(writer.add_panorama(PanoramaSpec::new(3, 4)).is_err());
+
+        // The final row is incomplete, as for an aborted acquisition
+        let acquisition = AcquisitionSpec::new(3, 2, 2, 2)
+            .position(1100.0, 1200.0)
+            .channel("X", "X")
+            .channel("Ir191", "DNA μ");
+        writer.add_acquisition(acquisition, &[0.0, 1.5, 1.0, 2.5, 0.0, 3.5])?;
+
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+
+        let slide = mcd.slide(1).expect("slide is written");
+        assert_eq!(slide.description(), "Synthetic");
+
+        let panorama = slide.panorama(2).expect("panorama is written");
+        assert_eq!(panorama.dimensions(), (4, 3));
+        assert_eq!(panorama.description(), "");
+
+        let acquisition = panorama.acquisition(3).expect("acquisition is written");
+        assert_eq!(acquisition.num_spectra(), 3);
+        assert_eq!(acquisition.channels()[1].label(), "DNA μ");
+
+        let image = acquisition.channel_image(ChannelIdentifier::name("Ir191"), None)?;
+        assert_eq!(image.intensities()[..3], [1.5, 2.5, 3.5]);
+        assert_eq!(image.num_valid_pixels(), 3);
+
+        Ok(())
+    }
+}
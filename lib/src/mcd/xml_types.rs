@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::{
     acquisition::{DataFormat, ProfilingType},
     panorama::PanoramaType,
@@ -61,6 +63,9 @@ pub(crate) struct AcquisitionChannelXML {
     pub(crate) order_number: Option<i16>,
     pub(crate) acquisition_id: Option<u16>,
     pub(crate) channel_label: Option<String>,
+
+    // Child elements which are not modelled, with their text
+    pub(crate) extras: BTreeMap<String, String>,
 }
 
 impl AcquisitionChannelXML {
@@ -71,6 +76,7 @@ impl AcquisitionChannelXML {
             order_number: None,
             acquisition_id: None,
             channel_label: None,
+            extras: BTreeMap::new(),
         }
     }
 }
@@ -84,6 +90,7 @@ impl From<AcquisitionChannelXML> for AcquisitionChannel {
             &channel.channel_name.expect("ChannelName is required"),
             &channel.channel_label.expect("ChannelLabel is required"),
         )
+        .with_extras(channel.extras)
     }
 }
 
@@ -99,14 +106,14 @@ pub(crate) struct AcquisitionXML {
     pub(crate) order_number: Option<i16>,
     pub(crate) signal_type: Option<String>,
     pub(crate) dual_count_start: Option<String>,
-    pub(crate) data_start_offset: Option<i64>,
-    pub(crate) data_end_offset: Option<i64>,
+    pub(crate) data_start_offset: Option<u64>,
+    pub(crate) data_end_offset: Option<u64>,
     pub(crate) start_timestamp: Option<String>,
     pub(crate) end_timestamp: Option<String>,
-    pub(crate) after_ablation_image_start_offset: Option<i64>,
-    pub(crate) after_ablation_image_end_offset: Option<i64>,
-    pub(crate) before_ablation_image_start_offset: Option<i64>,
-    pub(crate) before_ablation_image_end_offset: Option<i64>,
+    pub(crate) after_ablation_image_start_offset: Option<u64>,
+    pub(crate) after_ablation_image_end_offset: Option<u64>,
+    pub(crate) before_ablation_image_start_offset: Option<u64>,
+    pub(crate) before_ablation_image_end_offset: Option<u64>,
     pub(crate) roi_start_x_pos_um: Option<f64>,
     pub(crate) roi_start_y_pos_um: Option<f64>,
     pub(crate) roi_end_x_pos_um: Option<f64>,
@@ -120,6 +127,9 @@ pub(crate) struct AcquisitionXML {
     pub(crate) plume_end: Option<i32>,
     pub(crate) template: Option<String>,
     pub(crate) profiling_type: Option<ProfilingType>,
+
+    // Child elements which are not modelled, with their text
+    pub(crate) extras: BTreeMap<String, String>,
 }
 
 impl AcquisitionXML {
@@ -156,6 +166,7 @@ impl AcquisitionXML {
             plume_end: None,
             template: None,
             profiling_type: None,
+            extras: BTreeMap::new(),
         }
     }
 }
@@ -169,8 +180,8 @@ pub(crate) struct SlideXML {
     pub(crate) width_um: Option<f64>,
     pub(crate) height_um: Option<f64>,
 
-    pub(crate) image_start_offset: Option<i64>,
-    pub(crate) image_end_offset: Option<i64>,
+    pub(crate) image_start_offset: Option<u64>,
+    pub(crate) image_end_offset: Option<u64>,
     pub(crate) image_file: Option<String>,
 
     pub(crate) energy_db: Option<u32>,
@@ -180,6 +191,9 @@ pub(crate) struct SlideXML {
     pub(crate) name: Option<String>,
 
     pub(crate) sw_version: Option<String>,
+
+    // Child elements which are not modelled, with their text
+    pub(crate) extras: BTreeMap<String, String>,
 }
 
 impl SlideXML {
@@ -201,6 +215,7 @@ impl SlideXML {
             fmark_slide_thickness: None,
             name: None,
             sw_version: None,
+            extras: BTreeMap::new(),
         }
     }
 }
@@ -219,8 +234,8 @@ pub(crate) struct PanoramaXML {
     pub(crate) slide_x4_pos_um: Option<f64>,
     pub(crate) slide_y4_pos_um: Option<f64>,
 
-    pub(crate) image_start_offset: Option<i64>,
-    pub(crate) image_end_offset: Option<i64>,
+    pub(crate) image_start_offset: Option<u64>,
+    pub(crate) image_end_offset: Option<u64>,
     pub(crate) pixel_width: Option<i64>,
     pub(crate) pixel_height: Option<i64>,
     pub(crate) image_format: Option<ImageFormat>,
@@ -229,6 +244,9 @@ pub(crate) struct PanoramaXML {
     pub(crate) panorama_type: Option<PanoramaType>,
     pub(crate) is_locked: Option<bool>,
     pub(crate) rotation_angle: Option<f64>,
+
+    // Child elements which are not modelled, with their text
+    pub(crate) extras: BTreeMap<String, String>,
 }
 
 impl PanoramaXML {
@@ -254,6 +272,7 @@ impl PanoramaXML {
             panorama_type: None,
             is_locked: None,
             rotation_angle: None,
+            extras: BTreeMap::new(),
         }
     }
 }
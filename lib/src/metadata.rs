@@ -0,0 +1,437 @@
+use crate::{
+    calibration::{Calibration, CalibrationChannel},
+    Acquisition, AcquisitionChannel, OnSlide, Panorama, Slide, MCD,
+};
+
+/// Plain-data copy of the metadata of an .mcd file (see `MCD::metadata()`), without any readers, so that it can
+/// be stored, compared or (with the `serde` feature) serialised to any format supported by serde (e.g. JSON,
+/// YAML or TOML)
+#[derive(Debug, Clone, PartialEq)]
+pub struct McdMetadata {
+    /// XML namespace of the metadata, if specified
+    pub xmlns: Option<String>,
+    /// Slides, ordered by ID
+    pub slides: Vec<SlideMetadata>,
+    /// Calibrations performed during the run, ordered by ID
+    pub calibrations: Vec<CalibrationMetadata>,
+}
+
+/// Metadata describing a slide
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlideMetadata {
+    /// ID of the slide
+    pub id: u16,
+    /// Unique identifier of the slide, if recorded
+    pub uid: Option<String>,
+    /// Description of the slide
+    pub description: String,
+    /// Name of the file the slide was recorded in
+    pub filename: String,
+    /// Type of slide
+    pub slide_type: String,
+    /// Width of the slide (in μm)
+    pub width_in_um: f64,
+    /// Height of the slide (in μm)
+    pub height_in_um: f64,
+    /// Version of the software used to acquire the slide
+    pub software_version: String,
+    /// Panoramas on the slide, ordered by ID
+    pub panoramas: Vec<PanoramaMetadata>,
+}
+
+/// Metadata describing a panorama
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanoramaMetadata {
+    /// ID of the panorama
+    pub id: u16,
+    /// Description of the panorama
+    pub description: String,
+    /// Corners of the panorama on the slide (in μm), from the top left, clockwise
+    pub slide_corners: [(f64, f64); 4],
+    /// Width of the panorama image (in pixels)
+    pub pixel_width: i64,
+    /// Height of the panorama image (in pixels)
+    pub pixel_height: i64,
+    /// Pixel scale coefficient of the panorama image
+    pub pixel_scale_coef: f64,
+    /// Acquisitions in the panorama, ordered by ID
+    pub acquisitions: Vec<AcquisitionMetadata>,
+}
+
+/// Metadata describing an acquisition
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcquisitionMetadata {
+    /// ID of the acquisition
+    pub id: u16,
+    /// Description of the acquisition
+    pub description: String,
+    /// Number specifying the order in which the acquisition was acquired
+    pub order_number: i16,
+    /// Width of the acquisition (in pixels)
+    pub width: i32,
+    /// Height of the acquisition (in pixels)
+    pub height: i32,
+    /// Region of interest ablated on the slide
+    pub roi: RoiMetadata,
+    /// Frequency (in Hz) of the laser
+    pub ablation_frequency: f64,
+    /// Power of the laser
+    pub ablation_power: f64,
+    /// Distance between laser shots in x and y (in μm)
+    pub distance_between_shots: (f64, f64),
+    /// Signal type recorded
+    pub signal_type: String,
+    /// Intensity above which the analog signal is converted to counts, when dual counting
+    pub dual_count_start: String,
+    /// Format in which the intensities are stored (e.g. `Float`)
+    pub segment_data_format: String,
+    /// Number of bytes used to store each intensity
+    pub value_bytes: u8,
+    /// Time the acquisition started
+    pub start_timestamp: String,
+    /// Time the acquisition ended
+    pub end_timestamp: String,
+    /// First push of the window over which the signal was integrated
+    pub plume_start: i32,
+    /// End (exclusive) of the window over which the signal was integrated
+    pub plume_end: i32,
+    /// Channels measured in the acquisition, in the order they are stored
+    pub channels: Vec<ChannelMetadata>,
+}
+
+/// Region of interest of an acquisition on the slide (in μm)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoiMetadata {
+    /// Minimum x coordinate of the region
+    pub x: f64,
+    /// Minimum y coordinate of the region
+    pub y: f64,
+    /// Width of the region
+    pub width: f64,
+    /// Height of the region
+    pub height: f64,
+}
+
+/// Metadata describing a channel of an acquisition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMetadata {
+    /// Name of the channel (e.g. `Ir(191)`)
+    pub name: String,
+    /// Label (target) given to the channel
+    pub label: String,
+    /// Position in which the channel is stored in each spectrum
+    pub order_number: i16,
+    /// Metal measured in the channel, if it could be determined
+    pub metal: Option<String>,
+    /// Mass of the isotope measured in the channel, if it could be determined
+    pub mass: Option<u16>,
+}
+
+/// Metadata describing a calibration
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationMetadata {
+    /// ID of the calibration
+    pub id: u16,
+    /// ID of the acquisition the calibration was performed for
+    pub acquisition_id: u16,
+    /// Time the calibration was performed
+    pub time_stamp: String,
+    /// Mean dual counts recorded for each channel during the calibration
+    pub channels: Vec<CalibrationChannelMetadata>,
+}
+
+/// Metadata describing the calibration of a single channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationChannelMetadata {
+    /// Name of the channel
+    pub name: String,
+    /// Mean dual counts recorded for the channel
+    pub mean_duals: f64,
+}
+
+impl<R> From<&Slide<R>> for SlideMetadata {
+    fn from(slide: &Slide<R>) -> Self {
+        SlideMetadata {
+            id: slide.id(),
+            uid: slide.uid().map(|uid| uid.to_string()),
+            description: slide.description().to_string(),
+            filename: slide.filename().to_string(),
+            slide_type: slide.slide_type().to_string(),
+            width_in_um: slide.width_in_um(),
+            height_in_um: slide.height_in_um(),
+            software_version: slide.software_version().to_string(),
+            panoramas: slide
+                .panoramas()
+                .into_iter()
+                .map(PanoramaMetadata::from)
+                .collect(),
+        }
+    }
+}
+
+impl<R> From<&Panorama<R>> for PanoramaMetadata {
+    fn from(panorama: &Panorama<R>) -> Self {
+        let (pixel_width, pixel_height) = panorama.dimensions();
+
+        PanoramaMetadata {
+            id: panorama.id(),
+            description: panorama.description().to_string(),
+            slide_corners: panorama.slide_corners(),
+            pixel_width,
+            pixel_height,
+            pixel_scale_coef: panorama.pixel_scale_coef(),
+            acquisitions: panorama
+                .acquisitions()
+                .into_iter()
+                .map(AcquisitionMetadata::from)
+                .collect(),
+        }
+    }
+}
+
+impl<R> From<&Acquisition<R>> for AcquisitionMetadata {
+    fn from(acquisition: &Acquisition<R>) -> Self {
+        let bounds = acquisition.slide_bounding_box();
+        let window = acquisition.plume_window();
+
+        AcquisitionMetadata {
+            id: acquisition.id(),
+            description: acquisition.description().to_string(),
+            order_number: acquisition.order_number(),
+            width: acquisition.width(),
+            height: acquisition.height(),
+            roi: RoiMetadata {
+                x: bounds.min_x,
+                y: bounds.min_y,
+                width: bounds.width,
+                height: bounds.height,
+            },
+            ablation_frequency: acquisition.ablation_frequency(),
+            ablation_power: acquisition.ablation_power(),
+            distance_between_shots: acquisition.ablation_distance_between_shots(),
+            signal_type: acquisition.signal_type().to_string(),
+            dual_count_start: acquisition.dual_count_start().to_string(),
+            segment_data_format: acquisition.segment_data_format().to_string(),
+            value_bytes: acquisition.value_bytes(),
+            start_timestamp: acquisition.start_timestamp().to_string(),
+            end_timestamp: acquisition.end_timestamp().to_string(),
+            plume_start: window.start(),
+            plume_end: window.end(),
+            channels: acquisition
+                .channels()
+                .iter()
+                .map(ChannelMetadata::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&AcquisitionChannel> for ChannelMetadata {
+    fn from(channel: &AcquisitionChannel) -> Self {
+        let isotope = channel.isotope();
+
+        ChannelMetadata {
+            name: channel.name().to_string(),
+            label: channel.label().to_string(),
+            order_number: channel.order_number(),
+            metal: isotope
+                .as_ref()
+                .map(|isotope| isotope.element().to_string()),
+            mass: isotope.map(|isotope| isotope.mass()),
+        }
+    }
+}
+
+impl CalibrationMetadata {
+    fn new<'a, I>(calibration: &Calibration, channels: I) -> Self
+    where
+        I: Iterator<Item = &'a CalibrationChannel>,
+    {
+        let mut channels: Vec<_> = channels
+            .filter(|channel| channel.calibration_id() == calibration.id())
+            .collect();
+        channels.sort_by_key(|channel| channel.id());
+
+        CalibrationMetadata {
+            id: calibration.id(),
+            acquisition_id: calibration.acquisition_id(),
+            time_stamp: calibration.time_stamp().to_string(),
+            channels: channels
+                .into_iter()
+                .map(|channel| CalibrationChannelMetadata {
+                    name: channel.name().to_string(),
+                    mean_duals: channel.mean_duals(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<R> MCD<R> {
+    /// Returns a plain-data copy of the metadata of the .mcd file: the slides, panoramas, acquisitions (with their
+    /// regions of interest and channels) and calibrations
+    pub fn metadata(&self) -> McdMetadata {
+        let mut calibrations: Vec<_> = self.calibrations.values().collect();
+        calibrations.sort_by_key(|calibration| calibration.id());
+
+        McdMetadata {
+            xmlns: self.xmlns.clone(),
+            slides: self.slides().into_iter().map(SlideMetadata::from).collect(),
+            calibrations: calibrations
+                .into_iter()
+                .map(|calibration| {
+                    CalibrationMetadata::new(calibration, self.calibration_channels.values())
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use serde_core::{ser::SerializeStruct, Serialize, Serializer};
+
+    use super::*;
+
+    /// Implement `Serialize` for a struct by serialising each of the listed fields
+    macro_rules! serialize_fields {
+        ($type:ty, [$($field:ident),+]) => {
+            impl Serialize for $type {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    let fields = [$(stringify!($field)),+];
+                    let mut state = serializer.serialize_struct(stringify!($type), fields.len())?;
+                    $(state.serialize_field(stringify!($field), &self.$field)?;)+
+                    state.end()
+                }
+            }
+        };
+    }
+
+    serialize_fields!(McdMetadata, [xmlns, slides, calibrations]);
+    serialize_fields!(
+        SlideMetadata,
+        [
+            id,
+            uid,
+            description,
+            filename,
+            slide_type,
+            width_in_um,
+            height_in_um,
+            software_version,
+            panoramas
+        ]
+    );
+    serialize_fields!(
+        PanoramaMetadata,
+        [
+            id,
+            description,
+            slide_corners,
+            pixel_width,
+            pixel_height,
+            pixel_scale_coef,
+            acquisitions
+        ]
+    );
+    serialize_fields!(
+        AcquisitionMetadata,
+        [
+            id,
+            description,
+            order_number,
+            width,
+            height,
+            roi,
+            ablation_frequency,
+            ablation_power,
+            distance_between_shots,
+            signal_type,
+            dual_count_start,
+            segment_data_format,
+            value_bytes,
+            start_timestamp,
+            end_timestamp,
+            plume_start,
+            plume_end,
+            channels
+        ]
+    );
+    serialize_fields!(RoiMetadata, [x, y, width, height]);
+    serialize_fields!(ChannelMetadata, [name, label, order_number, metal, mass]);
+    serialize_fields!(
+        CalibrationMetadata,
+        [id, acquisition_id, time_stamp, channels]
+    );
+    serialize_fields!(CalibrationChannelMetadata, [name, mean_duals]);
+
+    /// Implement `Serialize` for a type holding a reader by serialising its metadata
+    macro_rules! serialize_as {
+        ($type:ty, $metadata:ty) => {
+            impl<R> Serialize for $type {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    <$metadata>::from(self).serialize(serializer)
+                }
+            }
+        };
+    }
+
+    serialize_as!(Slide<R>, SlideMetadata);
+    serialize_as!(Panorama<R>, PanoramaMetadata);
+    serialize_as!(Acquisition<R>, AcquisitionMetadata);
+
+    impl Serialize for AcquisitionChannel {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            ChannelMetadata::from(self).serialize(serializer)
+        }
+    }
+
+    impl<R> Serialize for MCD<R> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.metadata().serialize(serializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{error::Result, AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec};
+
+    use super::*;
+
+    #[test]
+    fn metadata_tree() -> Result<()> {
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(SlideSpec::new(1).description("Slide"))?;
+        writer.add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 100.0, 100.0))?;
+        writer.add_acquisition(
+            AcquisitionSpec::new(1, 1, 1, 1)
+                .position(10.0, 50.0)
+                .channel("Ir(191)", "DNA1"),
+            &[1.0],
+        )?;
+
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+        let metadata = mcd.metadata();
+
+        assert_eq!(metadata.slides.len(), 1);
+        assert_eq!(metadata.slides[0].description, "Slide");
+
+        let acquisition = &metadata.slides[0].panoramas[0].acquisitions[0];
+        assert_eq!(acquisition.roi.x, 10.0);
+        assert_eq!(
+            acquisition.channels[0],
+            ChannelMetadata {
+                name: "Ir(191)".to_string(),
+                label: "DNA1".to_string(),
+                order_number: 0,
+                metal: Some("Ir".to_string()),
+                mass: Some(191),
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{
+    error::{MCDError, Result},
+    ChannelImage,
+};
+
+/// Describes how the intensities of a `ChannelImage` should be normalised or scaled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Linearly scale intensities so that the minimum is 0 and the maximum is 1
+    MinMax,
+    /// Apply `asinh(x / cofactor)`, commonly used with a cofactor of 5 for IMC data
+    Arcsinh {
+        /// Cofactor by which the intensities are divided before applying asinh
+        cofactor: f32,
+    },
+    /// Clip intensities to the `lower` and `upper` percentiles (in the range 0-100), then linearly scale to 0-1
+    Percentile {
+        /// Lower percentile (0-100), intensities below this are set to 0
+        lower: f32,
+        /// Upper percentile (0-100), intensities above this are set to 1
+        upper: f32,
+    },
+    /// Apply `ln(1 + x)`
+    Log,
+}
+
+impl Normalization {
+    /// Arcsinh scaling with the given cofactor
+    pub fn arcsinh(cofactor: f32) -> Self {
+        Normalization::Arcsinh { cofactor }
+    }
+
+    /// Percentile clipping using the given lower and upper percentiles (0-100)
+    pub fn percentile(lower: f32, upper: f32) -> Self {
+        Normalization::Percentile { lower, upper }
+    }
+
+    /// Normalise `data` into `output`. Only the first `valid_pixels` values are considered when determining the
+    /// limits for scaling, any remaining values in `output` are set to NaN. Missing (NaN) values remain NaN.
+    pub(crate) fn apply(&self, data: &[f32], valid_pixels: usize, output: &mut [f32]) {
+        let valid_pixels = valid_pixels.min(data.len()).min(output.len());
+        let valid_data = &data[..valid_pixels];
+
+        match *self {
+            Normalization::MinMax => {
+                let (min_value, max_value) = min_max(valid_data);
+                scale_linear(valid_data, min_value, max_value, output);
+            }
+            Normalization::Arcsinh { cofactor } => {
+                for (out, &value) in output.iter_mut().zip(valid_data) {
+                    *out = (value / cofactor).asinh();
+                }
+            }
+            Normalization::Percentile { lower, upper } => {
+                let mut sorted: Vec<_> = valid_data
+                    .iter()
+                    .copied()
+                    .filter(|value| !value.is_nan())
+                    .collect();
+                sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+
+                let min_value = percentile(&sorted, lower);
+                let max_value = percentile(&sorted, upper);
+                scale_linear(valid_data, min_value, max_value, output);
+            }
+            Normalization::Log => {
+                for (out, &value) in output.iter_mut().zip(valid_data) {
+                    *out = value.ln_1p();
+                }
+            }
+        }
+
+        for out in output.iter_mut().skip(valid_pixels) {
+            *out = f32::NAN;
+        }
+    }
+}
+
+impl ChannelImage {
+    /// Returns a new `ChannelImage` with the intensities normalised using the specified method
+    pub fn normalized(&self, method: Normalization) -> ChannelImage {
+        let mut data = vec![0.0; self.data.len()];
+        method.apply(&self.data, self.valid_pixels, &mut data);
+
+        let (min_value, max_value) = min_max(&data[..self.valid_pixels.min(data.len())]);
+
+        ChannelImage {
+            region: self.region,
+            acquisition_id: self.acquisition_id,
+            name: self.name.clone(),
+            label: self.label.clone(),
+            range: (min_value, max_value),
+            valid_pixels: self.valid_pixels,
+            data,
+        }
+    }
+
+    /// Normalise the intensities using the specified method, writing the result into `buffer`. The buffer must
+    /// be the same length as `intensities()`.
+    pub fn normalized_into(&self, method: Normalization, buffer: &mut [f32]) -> Result<()> {
+        if buffer.len() != self.data.len() {
+            return Err(MCDError::InvalidBufferSize {
+                expected: self.data.len(),
+                actual: buffer.len(),
+            });
+        }
+
+        method.apply(&self.data, self.valid_pixels, buffer);
+
+        Ok(())
+    }
+}
+
+fn min_max(data: &[f32]) -> (f32, f32) {
+    let mut min_value = f32::MAX;
+    let mut max_value = f32::MIN;
+
+    // NaN (missing) values are ignored, as comparisons with NaN are always false
+    for &value in data {
+        if value < min_value {
+            min_value = value;
+        }
+        if value > max_value {
+            max_value = value;
+        }
+    }
+
+    (min_value, max_value)
+}
+
+fn scale_linear(data: &[f32], min_value: f32, max_value: f32, output: &mut [f32]) {
+    let range = max_value - min_value;
+
+    for (out, &value) in output.iter_mut().zip(data) {
+        *out = if value.is_nan() {
+            f32::NAN
+        } else if range > 0.0 {
+            ((value - min_value) / range).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Determine the value at the given percentile (0-100) of the sorted data, using linear interpolation
+fn percentile(sorted: &[f32], percentile: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let position = (percentile.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f32;
+    let lower_index = position.floor() as usize;
+    let upper_index = position.ceil() as usize;
+    let fraction = position - lower_index as f32;
+
+    sorted[lower_index] + (sorted[upper_index] - sorted[lower_index]) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization_methods() {
+        let data = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let mut output = [0.0; 5];
+
+        Normalization::MinMax.apply(&data, 5, &mut output);
+        assert_eq!(output, [0.0, 0.25, 0.5, 0.75, 1.0]);
+
+        Normalization::percentile(25.0, 75.0).apply(&data, 5, &mut output);
+        assert_eq!(output, [0.0, 0.0, 0.5, 1.0, 1.0]);
+
+        Normalization::arcsinh(5.0).apply(&data, 5, &mut output);
+        assert!((output[4] - (0.8f32).asinh()).abs() < f32::EPSILON);
+
+        // Pixels beyond the number of valid pixels are missing
+        Normalization::Log.apply(&data, 3, &mut output);
+        assert!((output[2] - (3.0f32).ln()).abs() < 1e-6);
+        assert!(output[3..].iter().all(|value| value.is_nan()));
+
+        // Missing pixels are ignored when determining limits
+        let data = [f32::NAN, 1.0, 2.0, 3.0, 5.0];
+        Normalization::MinMax.apply(&data, 5, &mut output);
+        assert!(output[0].is_nan());
+        assert_eq!(output[1..], [0.0, 0.25, 0.5, 1.0]);
+    }
+}
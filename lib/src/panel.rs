@@ -0,0 +1,303 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{channel::Isotope, error::Result, MCD};
+
+/// A single channel (metal) of a `Panel`, recording the target label used in each acquisition it was
+/// measured in
+#[derive(Debug, Clone)]
+pub struct PanelChannel {
+    name: String,
+    isotope: Option<Isotope>,
+    labels: Vec<(u16, String)>,
+}
+
+impl PanelChannel {
+    /// Returns the name of the channel
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the isotope measured in the channel, if it could be determined from the name or labels
+    pub fn isotope(&self) -> Option<&Isotope> {
+        self.isotope.as_ref()
+    }
+
+    /// Returns the metal (element symbol) measured in the channel
+    pub fn metal(&self) -> Option<&str> {
+        self.isotope.as_ref().map(|isotope| isotope.element())
+    }
+
+    /// Returns the mass of the isotope measured in the channel
+    pub fn mass(&self) -> Option<u16> {
+        self.isotope.as_ref().map(|isotope| isotope.mass())
+    }
+
+    /// Returns the IDs of the acquisitions containing the channel
+    pub fn acquisitions(&self) -> Vec<u16> {
+        self.labels.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Returns the (acquisition ID, label) for each acquisition containing the channel
+    pub fn labels(&self) -> &[(u16, String)] {
+        &self.labels
+    }
+
+    /// Returns the label used for the channel in the acquisition, or None if the channel is not present
+    pub fn label(&self, acquisition_id: u16) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(id, _)| *id == acquisition_id)
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// Returns the most commonly used label for the channel (the earliest acquisition's label in case of a tie)
+    pub fn consensus_label(&self) -> &str {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, label) in &self.labels {
+            *counts.entry(label.as_str()).or_default() += 1;
+        }
+
+        let mut consensus = "";
+        let mut consensus_count = 0;
+        for (_, label) in &self.labels {
+            let count = counts.get(label.as_str()).copied().unwrap_or_default();
+
+            if count > consensus_count {
+                consensus = label;
+                consensus_count = count;
+            }
+        }
+
+        consensus
+    }
+
+    /// Returns whether the same label is used in all acquisitions containing the channel
+    pub fn is_consistent(&self) -> bool {
+        self.labels
+            .windows(2)
+            .all(|labels| labels[0].1 == labels[1].1)
+    }
+}
+
+/// A difference in the panel between acquisitions, as reported by `Panel::differences()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PanelDifference {
+    /// The channel was not measured in some acquisitions
+    Missing {
+        /// Name of the channel
+        channel: String,
+        /// IDs of the acquisitions which do not contain the channel
+        acquisitions: Vec<u16>,
+    },
+    /// Different labels were given to the channel in different acquisitions
+    LabelMismatch {
+        /// Name of the channel
+        channel: String,
+        /// (acquisition ID, label) for each acquisition containing the channel
+        labels: Vec<(u16, String)>,
+    },
+}
+
+/// The antibody panel used across all acquisitions in an .mcd file, describing which channels (metals) were
+/// measured in which acquisition and the target label given to each. Useful for validating that the same
+/// panel was used throughout a study.
+///
+/// ```no_run
+/// use imc_rs::{Panel, MCD};
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let panel = Panel::new(&mcd);
+///
+/// for difference in panel.differences() {
+///     println!("{:?}", difference);
+/// }
+///
+/// panel.to_csv(std::fs::File::create("panel.csv").unwrap()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Panel {
+    acquisitions: Vec<u16>,
+    channels: Vec<PanelChannel>,
+}
+
+impl Panel {
+    /// Create the panel from all channels in the .mcd file (see `MCD::channels()`), excluding the X, Y and Z
+    /// position channels. Channels are ordered by mass, then name.
+    pub fn new<R>(mcd: &MCD<R>) -> Self {
+        let acquisitions = mcd.acquisitions();
+
+        let mut channels: Vec<_> = mcd
+            .channels()
+            .into_iter()
+            .filter(|channel| !channel.is_position())
+            .map(|channel| {
+                let labels: Vec<_> = acquisitions
+                    .iter()
+                    .filter_map(|acquisition| {
+                        acquisition
+                            .channels()
+                            .iter()
+                            .find(|c| c.name() == channel.name())
+                            .map(|c| (acquisition.id(), c.label().to_string()))
+                    })
+                    .collect();
+
+                PanelChannel {
+                    name: channel.name().to_string(),
+                    isotope: channel.isotope(),
+                    labels,
+                }
+            })
+            .collect();
+
+        channels.sort_by(|a, b| a.mass().cmp(&b.mass()).then_with(|| a.name.cmp(&b.name)));
+
+        Panel {
+            acquisitions: acquisitions
+                .iter()
+                .map(|acquisition| acquisition.id())
+                .collect(),
+            channels,
+        }
+    }
+
+    /// Returns the IDs of all acquisitions included in the panel
+    pub fn acquisitions(&self) -> &[u16] {
+        &self.acquisitions
+    }
+
+    /// Returns all channels in the panel
+    pub fn channels(&self) -> &[PanelChannel] {
+        &self.channels
+    }
+
+    /// Returns the channel with the specified name, if present in the panel
+    pub fn channel(&self, name: &str) -> Option<&PanelChannel> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    /// Returns the consensus panel: the channels measured in every acquisition, with their most commonly used
+    /// label (see `PanelChannel::consensus_label()`)
+    pub fn consensus(&self) -> Vec<(&PanelChannel, &str)> {
+        self.channels
+            .iter()
+            .filter(|channel| channel.labels.len() == self.acquisitions.len())
+            .map(|channel| (channel, channel.consensus_label()))
+            .collect()
+    }
+
+    /// Returns all differences between acquisitions: channels which are missing from some acquisitions and
+    /// channels given different labels in different acquisitions
+    pub fn differences(&self) -> Vec<PanelDifference> {
+        let mut differences = Vec::new();
+
+        for channel in &self.channels {
+            let missing: Vec<_> = self
+                .acquisitions
+                .iter()
+                .copied()
+                .filter(|&id| channel.label(id).is_none())
+                .collect();
+
+            if !missing.is_empty() {
+                differences.push(PanelDifference::Missing {
+                    channel: channel.name.clone(),
+                    acquisitions: missing,
+                });
+            }
+
+            if !channel.is_consistent() {
+                differences.push(PanelDifference::LabelMismatch {
+                    channel: channel.name.clone(),
+                    labels: channel.labels.clone(),
+                });
+            }
+        }
+
+        differences
+    }
+
+    /// Write the panel as .csv, with one row per channel (name, metal, mass, consensus label) followed by the
+    /// label used in each acquisition (empty if the channel is not present)
+    pub fn to_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        let mut header = vec![
+            "channel".to_string(),
+            "metal".to_string(),
+            "mass".to_string(),
+            "label".to_string(),
+        ];
+        header.extend(
+            self.acquisitions
+                .iter()
+                .map(|id| format!("acquisition_{}", id)),
+        );
+        writer.write_record(&header)?;
+
+        for channel in &self.channels {
+            let mut record = vec![
+                channel.name.clone(),
+                channel.metal().unwrap_or_default().to_string(),
+                channel
+                    .mass()
+                    .map(|mass| mass.to_string())
+                    .unwrap_or_default(),
+                channel.consensus_label().to_string(),
+            ];
+            record.extend(
+                self.acquisitions
+                    .iter()
+                    .map(|&id| channel.label(id).unwrap_or_default().to_string()),
+            );
+
+            writer.write_record(&record)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panel_differences() {
+        let panel = Panel {
+            acquisitions: vec![1, 2],
+            channels: vec![
+                PanelChannel {
+                    name: "Ir(191)".to_string(),
+                    isotope: Isotope::parse("Ir(191)"),
+                    labels: vec![(1, "DNA1".to_string()), (2, "DNA1".to_string())],
+                },
+                PanelChannel {
+                    name: "Sm(152)".to_string(),
+                    isotope: Isotope::parse("Sm(152)"),
+                    labels: vec![(2, "CD45".to_string())],
+                },
+            ],
+        };
+
+        assert_eq!(panel.consensus().len(), 1);
+        assert_eq!(
+            panel.differences(),
+            vec![PanelDifference::Missing {
+                channel: "Sm(152)".to_string(),
+                acquisitions: vec![1],
+            }]
+        );
+
+        let mut csv = Vec::new();
+        assert!(panel.to_csv(&mut csv).is_ok());
+        assert_eq!(
+            String::from_utf8_lossy(&csv),
+            "channel,metal,mass,label,acquisition_1,acquisition_2\n\
+             Ir(191),Ir,191,DNA1,DNA1,DNA1\n\
+             Sm(152),Sm,152,CD45,,CD45\n"
+        );
+    }
+}
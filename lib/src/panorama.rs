@@ -1,29 +1,46 @@
 use core::fmt;
 use std::{
-    collections::HashMap,
-    io::{BufReader, Read, Seek},
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    io::{Read, Seek},
+    sync::Arc,
 };
 
 use image::ImageFormat;
 use nalgebra::Vector2;
 
 use crate::{
-    mcd::PanoramaXML, transform::AffineTransform, Acquisition, BoundingBox, OnSlide, OpticalImage,
-    Print,
+    describe::{Describe, Description, Value},
+    mcd::PanoramaXML,
+    reader::ReaderPool,
+    transform::AffineTransform,
+    Acquisition, BoundingBox, ByteSpan, OnSlide, OpticalImage, Print,
 };
 
-#[derive(Debug)]
+/// Origin of a panorama image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PanoramaType {
+    /// A panorama without an image, only defining an area of the slide
     Default,
+    /// An image imported from an external source (e.g. a slide scanner)
     Imported,
+    /// An image captured by the instrument
     Instrument,
 }
 
+impl fmt::Display for PanoramaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PanoramaType::Default => write!(f, "Default"),
+            PanoramaType::Imported => write!(f, "Imported"),
+            PanoramaType::Instrument => write!(f, "Instrument"),
+        }
+    }
+}
+
 /// Represents a panorama (containing one or more acquisitions)
 #[derive(Debug)]
 pub struct Panorama<R> {
-    pub(crate) reader: Option<Arc<Mutex<BufReader<R>>>>,
+    pub(crate) reader: Option<Arc<ReaderPool<R>>>,
 
     id: u16,
     slide_id: u16,
@@ -37,8 +54,7 @@ pub struct Panorama<R> {
     slide_x4_pos_um: f64,
     slide_y4_pos_um: f64,
 
-    image_start_offset: i64,
-    image_end_offset: i64,
+    image: ByteSpan,
     pixel_width: i64,
     pixel_height: i64,
     image_format: ImageFormat,
@@ -47,19 +63,25 @@ pub struct Panorama<R> {
     panorama_type: Option<PanoramaType>,
     is_locked: Option<bool>,
     rotation_angle: Option<f64>,
+    extras: BTreeMap<String, String>,
 
     acquisitions: HashMap<u16, Acquisition<R>>,
 }
 
 impl<R: Read + Seek> Panorama<R> {
-    pub(crate) fn fix_image_dimensions(&mut self) {
+    /// Determine the dimensions from the image if they are missing, returning whether they were missing
+    pub(crate) fn fix_image_dimensions(&mut self) -> bool {
         if self.has_image() && (self.pixel_width == 0 || self.pixel_height == 0) {
             let image = self.image().unwrap();
             let dims = image.dimensions().unwrap();
 
             self.pixel_width = dims.0 as i64;
             self.pixel_height = dims.1 as i64;
+
+            return true;
         }
+
+        false
     }
 }
 
@@ -84,14 +106,25 @@ impl<R> Panorama<R> {
         (self.pixel_width, self.pixel_height)
     }
 
+    /// Returns the positions of the corners of the panorama on the slide (in μm), in the order top left, top
+    /// right, bottom right, bottom left
+    pub fn slide_corners(&self) -> [(f64, f64); 4] {
+        [
+            (self.slide_x1_pos_um, self.slide_y1_pos_um),
+            (self.slide_x2_pos_um, self.slide_y2_pos_um),
+            (self.slide_x3_pos_um, self.slide_y3_pos_um),
+            (self.slide_x4_pos_um, self.slide_y4_pos_um),
+        ]
+    }
+
     /// Returns a scaling coefficient for pixel sizes
     pub fn pixel_scale_coef(&self) -> f64 {
         self.pixel_scale_coef
     }
 
     /// Returns the type of the panorama image, if known. This is unknown in the first version of the schema
-    pub fn panorama_type(&self) -> Option<&PanoramaType> {
-        self.panorama_type.as_ref()
+    pub fn panorama_type(&self) -> Option<PanoramaType> {
+        self.panorama_type
     }
 
     /// Returns whether the panorama is locked or not (if known). This is unknown in the first version of the schema
@@ -99,11 +132,59 @@ impl<R> Panorama<R> {
         self.is_locked
     }
 
-    /// Returns the rotation angle of the panorama (if known). This is unknown in the first version of the schema
+    /// Returns the rotation angle of the panorama (if known), in degrees anti-clockwise on the slide. The rotation
+    /// is applied when placing the panorama on the slide only if the recorded corners (see `slide_corners`) are
+    /// an upright, axis-aligned rectangle, as otherwise the rotation is already reflected in them. This is unknown
+    /// in the first version of the schema
     pub fn rotation_angle(&self) -> Option<f64> {
         self.rotation_angle
     }
 
+    /// Returns the child elements of the `Panorama` element in the XML metadata which are not modelled here (e.g.
+    /// fields added in newer versions of the schema), with their text, ordered by name
+    pub fn extras(&self) -> &BTreeMap<String, String> {
+        &self.extras
+    }
+
+    /// Returns the corners of the panorama on the slide (in μm), in the same order as `slide_corners`, rotated
+    /// about their centre by the rotation angle unless the rotation has already been applied to the recorded
+    /// corners (i.e. they are not an upright, axis-aligned rectangle)
+    fn placed_slide_corners(&self) -> [(f64, f64); 4] {
+        let corners = self.slide_corners();
+
+        let angle = match self.rotation_angle {
+            Some(angle) if angle != 0.0 => angle.to_radians(),
+            _ => return corners,
+        };
+
+        let [top_left, top_right, bottom_right, bottom_left] = corners;
+        let same = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        // Upright, with the slide y-axis pointing up (so a half turn applied to the corners is also detected)
+        let upright = same(top_left.1, top_right.1)
+            && same(bottom_left.1, bottom_right.1)
+            && same(top_left.0, bottom_left.0)
+            && same(top_right.0, bottom_right.0)
+            && top_left.0 < top_right.0
+            && top_left.1 > bottom_left.1;
+
+        if !upright {
+            return corners;
+        }
+
+        let centre_x = corners.iter().map(|corner| corner.0).sum::<f64>() / 4.0;
+        let centre_y = corners.iter().map(|corner| corner.1).sum::<f64>() / 4.0;
+        let (sin, cos) = angle.sin_cos();
+
+        corners.map(|(x, y)| {
+            let (dx, dy) = (x - centre_x, y - centre_y);
+
+            (
+                centre_x + dx * cos - dy * sin,
+                centre_y + dx * sin + dy * cos,
+            )
+        })
+    }
+
     /// Returns a sorted (acsending) list of acquisition IDs
     pub fn acquisition_ids(&self) -> Vec<u16> {
         let mut ids: Vec<u16> = Vec::with_capacity(self.acquisitions.len());
@@ -143,7 +224,7 @@ impl<R> Panorama<R> {
 
     /// Returns true if an image is associated with this panorama
     pub fn has_image(&self) -> bool {
-        (self.image_end_offset - self.image_start_offset) > 0
+        !self.image.is_empty()
     }
 
     /// Returns the optical image
@@ -151,9 +232,9 @@ impl<R> Panorama<R> {
         if self.has_image() {
             Some(OpticalImage {
                 reader: self.reader.as_ref()?.clone(),
-                start_offset: self.image_start_offset,
-                end_offset: self.image_end_offset,
+                span: self.image,
                 image_format: self.image_format,
+                element: format!("Panorama {} image", self.id),
             })
         } else {
             None
@@ -195,34 +276,24 @@ impl<T: Seek + Read> OpticalImage for Panorama<T> {
 impl<R> OnSlide for Panorama<R> {
     /// Returns the bounding box encompasing the panorama image area on the slide (in μm)
     fn slide_bounding_box(&self) -> BoundingBox<f64> {
-        let min_x = f64::min(
-            self.slide_x1_pos_um,
-            f64::min(
-                self.slide_x2_pos_um,
-                f64::min(self.slide_x3_pos_um, self.slide_x4_pos_um),
-            ),
-        );
-        let min_y = f64::min(
-            self.slide_y1_pos_um,
-            f64::min(
-                self.slide_y2_pos_um,
-                f64::min(self.slide_y3_pos_um, self.slide_y4_pos_um),
-            ),
-        );
-        let max_x = f64::max(
-            self.slide_x1_pos_um,
-            f64::max(
-                self.slide_x2_pos_um,
-                f64::max(self.slide_x3_pos_um, self.slide_x4_pos_um),
-            ),
-        );
-        let max_y = f64::max(
-            self.slide_y1_pos_um,
-            f64::max(
-                self.slide_y2_pos_um,
-                f64::max(self.slide_y3_pos_um, self.slide_y4_pos_um),
-            ),
-        );
+        let corners = self.placed_slide_corners();
+
+        let min_x = corners
+            .iter()
+            .map(|corner| corner.0)
+            .fold(f64::MAX, f64::min);
+        let min_y = corners
+            .iter()
+            .map(|corner| corner.1)
+            .fold(f64::MAX, f64::min);
+        let max_x = corners
+            .iter()
+            .map(|corner| corner.0)
+            .fold(f64::MIN, f64::max);
+        let max_y = corners
+            .iter()
+            .map(|corner| corner.1)
+            .fold(f64::MIN, f64::max);
 
         BoundingBox {
             min_x,
@@ -232,18 +303,21 @@ impl<R> OnSlide for Panorama<R> {
         }
     }
 
-    /// Returns the affine transformation from pixel coordinates within the panorama to to the slide coordinates (μm)
+    /// Returns the affine transformation from pixel coordinates within the panorama to to the slide coordinates (μm),
+    /// taking into account the rotation angle of the panorama (see `rotation_angle`)
     fn to_slide_transform(&self) -> AffineTransform<f64> {
         if !self.has_image() {
             return AffineTransform::identity();
         }
 
+        let corners = self.placed_slide_corners();
+
         let mut moving_points = Vec::new();
         let mut fixed_points = Vec::new();
 
-        moving_points.push(Vector2::new(self.slide_x1_pos_um, self.slide_y1_pos_um));
-        moving_points.push(Vector2::new(self.slide_x2_pos_um, self.slide_y2_pos_um));
-        moving_points.push(Vector2::new(self.slide_x3_pos_um, self.slide_y3_pos_um));
+        moving_points.push(Vector2::new(corners[0].0, corners[0].1));
+        moving_points.push(Vector2::new(corners[1].0, corners[1].1));
+        moving_points.push(Vector2::new(corners[2].0, corners[2].1));
         //moving_points.push(Vector2::new(self.slide_x4_pos_um, self.slide_y4_pos_um));
 
         // println!(
@@ -263,89 +337,38 @@ impl<R> OnSlide for Panorama<R> {
     }
 }
 
-#[rustfmt::skip]
-impl<R> Print for Panorama<R> {
-    fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "Panorama", 42)?;
-
-        writeln!(writer, "{:indent$}{: <20} | {}", "", "ID", self.id, indent = indent)?;
-        writeln!(writer, "{:indent$}{: <20} | {}", "", "Slide ID", self.slide_id, indent = indent)?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | {}",
-            "",
-            "Description",
-            self.description,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | ({:.4} μm, {:.4} μm)",
-            "",
-            "Slide coordinates",
-            self.slide_x1_pos_um,
-            self.slide_y1_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | ({:.4} μm, {:.4} μm)",
-            "",
-            "",
-            self.slide_x2_pos_um,
-            self.slide_y2_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | ({:.4} μm, {:.4} μm)",
-            "",
-            "",
-            self.slide_x3_pos_um,
-            self.slide_y3_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | ({:.4} μm, {:.4} μm)",
-            "",
-            "",
-            self.slide_x4_pos_um,
-            self.slide_y4_pos_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | {} x {}",
-            "",
-            "Dimensions (pixels)",
-            self.pixel_width,
-            self.pixel_height,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <20} | {}",
-            "",
-            "Pixel scale coef",
-            self.pixel_scale_coef,
-            indent = indent
-        )?;
-
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "", 42)?;
-
-        writeln!(
-            writer,
-            "{} acquisition(s) with ids: {:?}",
-            self.acquisitions.len(),
-            self.acquisition_ids()
-        )?;
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "", 42)?;
+impl<R> Describe for Panorama<R> {
+    fn describe(&self) -> Description {
+        let mut description = Description::new("Panorama")
+            .field("ID", self.id)
+            .field("Slide ID", self.slide_id)
+            .field("Description", &self.description)
+            .field(
+                "Slide coordinates",
+                vec![
+                    Value::Position(self.slide_x1_pos_um, self.slide_y1_pos_um),
+                    Value::Position(self.slide_x2_pos_um, self.slide_y2_pos_um),
+                    Value::Position(self.slide_x3_pos_um, self.slide_y3_pos_um),
+                    Value::Position(self.slide_x4_pos_um, self.slide_y4_pos_um),
+                ],
+            )
+            .field(
+                "Dimensions (pixels)",
+                Value::Size(self.pixel_width as f64, self.pixel_height as f64),
+            )
+            .field("Pixel scale coef", self.pixel_scale_coef);
+
+        if let Some(panorama_type) = self.panorama_type {
+            description = description.field("Type", panorama_type.to_string());
+        }
+        if let Some(is_locked) = self.is_locked {
+            description = description.field("Locked", is_locked);
+        }
+        if let Some(rotation_angle) = self.rotation_angle {
+            description = description.field("Rotation angle", rotation_angle);
+        }
 
-        Ok(())
+        description.field("Acquisition IDs", self.acquisition_ids())
     }
 }
 
@@ -371,8 +394,7 @@ impl<R> From<PanoramaXML> for Panorama<R> {
             slide_y3_pos_um: panorama.slide_y3_pos_um.unwrap(),
             slide_x4_pos_um: panorama.slide_x4_pos_um.unwrap(),
             slide_y4_pos_um: panorama.slide_y4_pos_um.unwrap(),
-            image_start_offset: panorama.image_start_offset.unwrap(),
-            image_end_offset: panorama.image_end_offset.unwrap(),
+            image: ByteSpan::from_xml(panorama.image_start_offset, panorama.image_end_offset),
             pixel_width: panorama.pixel_width.unwrap(),
             pixel_height: panorama.pixel_height.unwrap(),
             image_format: panorama.image_format.unwrap(),
@@ -381,8 +403,106 @@ impl<R> From<PanoramaXML> for Panorama<R> {
             panorama_type: panorama.panorama_type,
             is_locked: panorama.is_locked,
             rotation_angle: panorama.rotation_angle,
+            extras: panorama.extras,
 
             acquisitions: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, RgbImage};
+
+    use crate::{
+        coords::PanoramaPixel, error::Result, MCDWriter, OnSlide, PanoramaSpec, SlideSpec, MCD,
+    };
+
+    /// Write a panorama with a 4x2 pixel image and read it back
+    fn parse_panorama(panorama: PanoramaSpec) -> Result<MCD<Cursor<Vec<u8>>>> {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::new(4, 2).write_to(&mut png, ImageOutputFormat::Png)?;
+
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(SlideSpec::new(1))?;
+        writer.add_panorama(panorama.image(png.into_inner()))?;
+
+        MCD::parse(Cursor::new(writer.finish()?.into_inner()))
+    }
+
+    fn assert_near((x, y): (f64, f64), (expected_x, expected_y): (f64, f64)) {
+        assert!(
+            (x - expected_x).abs() < 1e-6 && (y - expected_y).abs() < 1e-6,
+            "({}, {}) != ({}, {})",
+            x,
+            y,
+            expected_x,
+            expected_y
+        );
+    }
+
+    #[test]
+    fn rotated_to_slide() -> Result<()> {
+        let mcd = parse_panorama(
+            PanoramaSpec::new(1, 1)
+                .bounds(0.0, 0.0, 400.0, 200.0)
+                .rotation_angle(90.0),
+        )?;
+        let panorama = mcd
+            .slide(1)
+            .and_then(|slide| slide.panorama(1))
+            .expect("panorama is written");
+        assert_eq!(panorama.rotation_angle(), Some(90.0));
+
+        // The corners are rotated anti-clockwise about the centre (200, 100)
+        for (pixel, expected) in [
+            ((0.0, 0.0), (100.0, -100.0)),
+            ((4.0, 0.0), (100.0, 300.0)),
+            ((4.0, 2.0), (300.0, 300.0)),
+            ((0.0, 2.0), (300.0, -100.0)),
+            ((2.0, 1.0), (200.0, 100.0)),
+        ] {
+            let slide = PanoramaPixel::new(pixel.0, pixel.1)
+                .to_slide(panorama)
+                .expect("panorama has an image");
+            assert_near((slide.x, slide.y), expected);
+        }
+
+        let bounding_box = panorama.slide_bounding_box();
+        assert_near((bounding_box.min_x, bounding_box.min_y), (100.0, -100.0));
+        assert_near((bounding_box.width, bounding_box.height), (200.0, 400.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_corners_are_not_rotated_again() -> Result<()> {
+        // The quarter turn is already reflected in the recorded corners
+        let mcd = parse_panorama(
+            PanoramaSpec::new(1, 1)
+                .corners([
+                    (100.0, -100.0),
+                    (100.0, 300.0),
+                    (300.0, 300.0),
+                    (300.0, -100.0),
+                ])
+                .rotation_angle(90.0),
+        )?;
+        let panorama = mcd
+            .slide(1)
+            .and_then(|slide| slide.panorama(1))
+            .expect("panorama is written");
+
+        let corner = PanoramaPixel::new(0.0, 0.0)
+            .to_slide(panorama)
+            .expect("panorama has an image");
+        assert_near((corner.x, corner.y), (100.0, -100.0));
+
+        let bounding_box = panorama.slide_bounding_box();
+        assert_near((bounding_box.width, bounding_box.height), (200.0, 400.0));
+
+        Ok(())
+    }
+}
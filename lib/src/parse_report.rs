@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{
+    describe::{Describe, Description, Table},
+    MCD,
+};
+
+/// A field which was missing or invalid in the XML metadata, and was corrected (or derived from other fields)
+/// when parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredField {
+    element: &'static str,
+    id: u16,
+    field: &'static str,
+}
+
+impl RecoveredField {
+    pub(crate) fn new(element: &'static str, id: u16, field: &'static str) -> Self {
+        RecoveredField { element, id, field }
+    }
+
+    /// Returns the name of the element containing the field (e.g. `Acquisition`)
+    pub fn element(&self) -> &'static str {
+        self.element
+    }
+
+    /// Returns the ID of the element containing the field
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Returns the name of the field (e.g. `ROIEndXPosUm`)
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+}
+
+impl fmt::Display for RecoveredField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.element, self.id, self.field)
+    }
+}
+
+/// Statistics and warnings gathered while parsing an .mcd file (see `MCD::parse_report`), so that files which
+/// parsed successfully but may not have been read as intended can be flagged for review.
+///
+/// The durations are always zero on `wasm32-unknown-unknown`, where no clock is available.
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    pub(crate) xml_size: usize,
+    pub(crate) unknown_tags: BTreeMap<String, usize>,
+    pub(crate) recovered_fields: Vec<RecoveredField>,
+
+    pub(crate) read_duration: Duration,
+    pub(crate) parse_duration: Duration,
+    pub(crate) assemble_duration: Duration,
+}
+
+impl ParseReport {
+    /// Returns the size (in bytes) of the XML metadata
+    pub fn xml_size(&self) -> usize {
+        self.xml_size
+    }
+
+    /// Returns the number of occurrences of each tag which is not handled by the parser, ordered by name. The text
+    /// of these tags is available from the `extras` of the element containing them (e.g. `Acquisition::extras`).
+    pub fn unknown_tags(&self) -> &BTreeMap<String, usize> {
+        &self.unknown_tags
+    }
+
+    /// Returns the total number of tags which were not handled by the parser
+    pub fn num_unknown_tags(&self) -> usize {
+        self.unknown_tags.values().sum()
+    }
+
+    /// Returns the fields which were missing or invalid and were corrected when parsing
+    pub fn recovered_fields(&self) -> &[RecoveredField] {
+        &self.recovered_fields
+    }
+
+    /// Returns the time taken to read the XML metadata from the file
+    pub fn read_duration(&self) -> Duration {
+        self.read_duration
+    }
+
+    /// Returns the time taken to parse the XML metadata
+    pub fn parse_duration(&self) -> Duration {
+        self.parse_duration
+    }
+
+    /// Returns the time taken to assemble the slides, panoramas and acquisitions from the parsed metadata
+    pub fn assemble_duration(&self) -> Duration {
+        self.assemble_duration
+    }
+
+    /// Returns the total time taken to read and parse the metadata
+    pub fn total_duration(&self) -> Duration {
+        self.read_duration + self.parse_duration + self.assemble_duration
+    }
+
+    /// Returns whether the metadata was parsed without ignoring any tags or correcting any fields
+    pub fn is_clean(&self) -> bool {
+        self.unknown_tags.is_empty() && self.recovered_fields.is_empty()
+    }
+}
+
+/// Measures the time taken by each stage of parsing. On `wasm32-unknown-unknown` there is no clock
+/// (`Instant::now` panics), so no time is measured.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Stopwatch {
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Returns the time elapsed since the stopwatch was started
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        return self.start.elapsed();
+
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        return Duration::ZERO;
+    }
+}
+
+impl Describe for ParseReport {
+    fn describe(&self) -> Description {
+        let unknown_tags = self.unknown_tags.iter().fold(
+            Table::new("Unknown tags", &["Tag", "Occurrences"]),
+            |table, (tag, count)| table.row(vec![tag.as_str().into(), (*count).into()]),
+        );
+
+        let recovered_fields = self.recovered_fields.iter().fold(
+            Table::new("Recovered fields", &["Element", "ID", "Field"]),
+            |table, field| {
+                table.row(vec![
+                    field.element().into(),
+                    field.id().into(),
+                    field.field().into(),
+                ])
+            },
+        );
+
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        Description::new("Parse report")
+            .field("XML size (bytes)", self.xml_size)
+            .field("Clean", self.is_clean())
+            .field("Read time (ms)", milliseconds(self.read_duration))
+            .field("Parse time (ms)", milliseconds(self.parse_duration))
+            .field("Assemble time (ms)", milliseconds(self.assemble_duration))
+            .table(unknown_tags)
+            .table(recovered_fields)
+    }
+}
+
+impl fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.describe().write_text(f, 0)
+    }
+}
+
+impl<R> MCD<R> {
+    /// Returns the statistics and warnings gathered while parsing the .mcd file, such as the tags which were
+    /// ignored, the fields which had to be corrected and the time taken by each phase of parsing
+    pub fn parse_report(&self) -> &ParseReport {
+        &self.parse_report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn report_recovered_fields() -> Result<()> {
+        // Recorded 1000x too large, as in some version 2 files
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 2)
+                .position(10_000_000.0, 50.0)
+                .channel("Ir191", "DNA1"),
+            [0.0; 4],
+        )])?)?;
+        let report = mcd.parse_report();
+
+        assert!(report.xml_size() > 0);
+        assert!(report.unknown_tags().is_empty());
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.recovered_fields(),
+            [RecoveredField::new("Acquisition", 1, "ROIStartXPosUm")]
+        );
+        assert!(report.total_duration() >= report.parse_duration());
+
+        Ok(())
+    }
+}
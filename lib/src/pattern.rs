@@ -0,0 +1,133 @@
+/// Returns true if the whole of `text` matches the glob `pattern`. Supports `*` (any sequence of characters),
+/// `?` (any single character) and character classes such as `[abc]`, `[a-z]` and `[!abc]`.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let mut p = 0;
+    let mut t = 0;
+    // Position in the pattern after the last `*` and the position in the text it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pattern[p..], text[t]),
+            Some(&c) => (c == text[t]).then_some(1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(length), _) => {
+                p += length;
+                t += 1;
+            }
+            (None, Some((star_p, star_t))) => {
+                // Let the last `*` absorb one more character and try again
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match a character class at the start of `pattern` against `c`. Returns the length of the class in the pattern
+/// if it matches. An unterminated class is treated as a literal `[`.
+fn match_class(pattern: &[char], c: char) -> Option<usize> {
+    let end = match pattern.iter().skip(2).position(|&p| p == ']') {
+        Some(end) => end + 2,
+        None => return (c == '[').then_some(1),
+    };
+
+    let (negate, class) = match pattern[1] {
+        '!' | '^' => (true, &pattern[2..end]),
+        _ => (false, &pattern[1..end]),
+    };
+
+    let mut matched = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            matched |= class[index] <= c && c <= class[index + 2];
+            index += 3;
+        } else {
+            matched |= class[index] == c;
+            index += 1;
+        }
+    }
+
+    (matched != negate).then_some(end + 1)
+}
+
+/// Returns the number of single character insertions, deletions or substitutions needed to turn `a` into `b`
+/// (the Levenshtein distance)
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Returns the (case insensitive) edit distance between the query and the description, or 0 if one contains the
+/// other. Returns None if the distance is more than a third of the length of the query (and more than 2).
+pub(crate) fn text_distance(query: &str, description: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let description = description.to_lowercase();
+
+    if query.is_empty() || description.is_empty() {
+        return None;
+    }
+
+    if description.contains(&query) || query.contains(&description) {
+        return Some(0);
+    }
+
+    let distance = edit_distance(&query, &description);
+    (distance <= (query.chars().count() / 3).max(2)).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("ROI_0*", "ROI_001"));
+        assert!(!glob_match("ROI_0*", "XROI_001"));
+        assert!(glob_match("*tumor*", "lung tumor 2"));
+        assert!(glob_match("ROI_00?", "ROI_003"));
+        assert!(!glob_match("ROI_00?", "ROI_0031"));
+        assert!(glob_match("ROI_[0-1][!5]*", "ROI_04"));
+        assert!(!glob_match("ROI_[0-1][!5]*", "ROI_05"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("[", "["));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("ROI_01", "ROI_001"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
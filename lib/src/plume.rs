@@ -0,0 +1,221 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    error::{channel_not_found, MCDError, Result},
+    Acquisition, ChannelIdentifier, ChannelImage, Region,
+};
+
+/// Window of pushes (detector readouts) over which the signal of each laser shot (plume) is integrated, from
+/// `start` up to (but not including) `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlumeWindow {
+    start: i32,
+    end: i32,
+}
+
+impl PlumeWindow {
+    /// Create a window covering pushes `start..end`
+    pub fn new(start: i32, end: i32) -> Self {
+        PlumeWindow { start, end }
+    }
+
+    /// Returns the first push of the window
+    pub fn start(&self) -> i32 {
+        self.start
+    }
+
+    /// Returns the end (exclusive) of the window
+    pub fn end(&self) -> i32 {
+        self.end
+    }
+
+    /// Returns the number of pushes covered by the window
+    pub fn len(&self) -> usize {
+        (self.end - self.start).max(0) as usize
+    }
+
+    /// Returns true if the window covers no pushes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tests whether this window lies entirely within `other`
+    pub fn is_within(&self, other: &PlumeWindow) -> bool {
+        self.start >= other.start && self.end <= other.end
+    }
+}
+
+impl<R: Read + Seek> Acquisition<R> {
+    /// Returns the number of pushes stored for each pixel if the acquisition holds the raw spectrum of every
+    /// push of the plume window (rather than the intensities integrated by the instrument), which is required
+    /// to integrate over a different window. This is the case when one spectrum is stored for each push of the
+    /// plume window of every pixel.
+    pub fn pushes_per_pixel(&self) -> Option<usize> {
+        let pushes = self.plume_window().len();
+        let num_pixels = self.width().max(0) as usize * self.height().max(0) as usize;
+
+        if pushes > 1 && num_pixels > 0 && self.num_spectra() == num_pixels * pushes {
+            Some(pushes)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the images of the channels with the intensity of each pixel integrated over `window`, optionally
+    /// restricted to a region (in pixels).
+    ///
+    /// If `window` is the window recorded for the acquisition, these are the stored images (see
+    /// `channel_images()`). Otherwise the acquisition must hold the raw spectrum of each push (see
+    /// `pushes_per_pixel()`) and `window` must lie within the recorded window, as intensities integrated by the
+    /// instrument can't be separated back into pushes.
+    pub fn windowed_channel_images<C: AsRef<ChannelIdentifier>>(
+        &self,
+        identifiers: &[C],
+        window: PlumeWindow,
+        region: Option<Region>,
+    ) -> Result<Vec<ChannelImage>> {
+        let recorded = self.plume_window();
+
+        let pushes = match self.pushes_per_pixel() {
+            Some(pushes) => pushes,
+            None if window == recorded => return self.channel_images(identifiers, region),
+            None => return Err(invalid_window(
+                window,
+                "the acquisition holds intensities integrated by the instrument, not raw pushes",
+            )),
+        };
+
+        if window.is_empty() || !window.is_within(&recorded) {
+            return Err(invalid_window(
+                window,
+                &format!(
+                    "the window must lie within the recorded pushes {}..{}",
+                    recorded.start, recorded.end
+                ),
+            ));
+        }
+
+        let channels = identifiers
+            .iter()
+            .map(|identifier| {
+                self.channel(identifier)
+                    .ok_or_else(|| channel_not_found(identifier.as_ref(), self.channels()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let order_numbers: Vec<_> = channels
+            .iter()
+            .map(|channel| channel.order_number() as usize)
+            .collect();
+
+        let region = region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.width().max(0) as u32,
+            height: self.height().max(0) as u32,
+        });
+
+        let num_pixels = region.width as usize * region.height as usize;
+        let mut data = vec![Vec::with_capacity(num_pixels); channels.len()];
+        let first_push = (window.start - recorded.start) as usize;
+
+        for y in region.y..region.y + region.height {
+            let first_pixel = y as usize * self.width() as usize + region.x as usize;
+            let spectra =
+                self.read_spectra(first_pixel * pushes, region.width as usize * pushes)?;
+
+            let integrated = integrate(
+                &spectra,
+                self.channels().len(),
+                pushes,
+                first_push..first_push + window.len(),
+            );
+
+            for pixel in integrated.chunks_exact(self.channels().len()) {
+                for (channel_data, &order_number) in data.iter_mut().zip(&order_numbers) {
+                    channel_data.push(pixel[order_number]);
+                }
+            }
+        }
+
+        Ok(data
+            .into_iter()
+            .zip(channels)
+            .map(|(data, channel)| {
+                let range = data
+                    .iter()
+                    .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                        (min.min(value), max.max(value))
+                    });
+
+                ChannelImage {
+                    region,
+                    acquisition_id: channel.acquisition_id(),
+                    name: channel.name().to_string(),
+                    label: channel.label().to_string(),
+                    range,
+                    // Raw pushes are only available for complete acquisitions
+                    valid_pixels: num_pixels,
+                    data,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Sum the spectra of the pushes within `window` for each pixel, where each pixel is made up of `pushes`
+/// consecutive spectra of `num_channels` intensities
+fn integrate(
+    spectra: &[f32],
+    num_channels: usize,
+    pushes: usize,
+    window: std::ops::Range<usize>,
+) -> Vec<f32> {
+    spectra
+        .chunks_exact(num_channels * pushes)
+        .flat_map(|pixel| {
+            let mut integrated = vec![0.0; num_channels];
+
+            for spectrum in pixel
+                .chunks_exact(num_channels)
+                .take(window.end)
+                .skip(window.start)
+            {
+                for (sum, intensity) in integrated.iter_mut().zip(spectrum) {
+                    *sum += intensity;
+                }
+            }
+
+            integrated
+        })
+        .collect()
+}
+
+fn invalid_window(window: PlumeWindow, reason: &str) -> MCDError {
+    MCDError::InvalidPlumeWindow {
+        start: window.start,
+        end: window.end,
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrates_pushes_in_window() {
+        // Two pixels of two channels, with three pushes each
+        #[rustfmt::skip]
+        let spectra = [
+            1.0, 10.0, 2.0, 20.0, 4.0, 40.0,
+            0.0, 1.0, 0.0, 2.0, 5.0, 3.0,
+        ];
+
+        assert_eq!(integrate(&spectra, 2, 3, 0..3), [7.0, 70.0, 5.0, 6.0]);
+        assert_eq!(integrate(&spectra, 2, 3, 1..3), [6.0, 60.0, 5.0, 5.0]);
+
+        let recorded = PlumeWindow::new(2, 5);
+        assert!(PlumeWindow::new(3, 5).is_within(&recorded));
+        assert!(!PlumeWindow::new(1, 4).is_within(&recorded));
+    }
+}
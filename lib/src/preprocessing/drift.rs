@@ -0,0 +1,262 @@
+use std::io::{Read, Seek};
+
+use crate::{error::Result, Acquisition, ChannelIdentifier, ChannelImage};
+
+/// Describes how the trend in intensity over the course of an acquisition (by row, as rows are ablated in turn) is
+/// estimated from the mean intensity of each row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detrend {
+    /// Fit a straight line (least squares), for a steady loss of sensitivity
+    Linear,
+    /// Fit a locally weighted straight line (LOESS, with tricube weights) around each row, which follows changes
+    /// in sensitivity which aren't steady
+    Loess {
+        /// Fraction (0-1] of the rows included in each local fit. Larger spans give a smoother trend.
+        span: f64,
+    },
+}
+
+impl Detrend {
+    /// LOESS with the given span (fraction of rows in each local fit)
+    pub fn loess(span: f64) -> Self {
+        Detrend::Loess { span }
+    }
+}
+
+/// Mean intensity of a channel in each row of an acquisition, ordered by acquisition time, for detecting drift in
+/// the sensitivity of the instrument over long ablations (see `Acquisition::drift_profile()`)
+#[derive(Debug, Clone)]
+pub struct DriftProfile {
+    window: usize,
+    row_means: Vec<f64>,
+    rolling_means: Vec<f64>,
+}
+
+impl DriftProfile {
+    fn new(image: &ChannelImage, window: usize) -> Self {
+        let width = image.width() as usize;
+        let window = window.max(1);
+
+        let row_means: Vec<_> = (0..image.height() as usize)
+            .map(|row| {
+                let (sum, count) = (0..width)
+                    .map(|x| image.pixel(row * width + x))
+                    .filter(|value| !value.is_nan())
+                    .fold((0.0, 0), |(sum, count), value| {
+                        (sum + value as f64, count + 1)
+                    });
+
+                if count > 0 {
+                    sum / count as f64
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect();
+
+        let rolling_means = (0..row_means.len())
+            .map(|row| {
+                let start = row.saturating_sub(window / 2);
+                let end = (row + window - window / 2).min(row_means.len());
+
+                mean(&row_means[start..end])
+            })
+            .collect();
+
+        DriftProfile {
+            window,
+            row_means,
+            rolling_means,
+        }
+    }
+
+    /// Returns the number of rows in the rolling window
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Returns the mean intensity of each row. Rows without any acquired pixels are NaN.
+    pub fn row_means(&self) -> &[f64] {
+        &self.row_means
+    }
+
+    /// Returns the mean intensity of the rows within the window centred on each row, ignoring rows without any
+    /// acquired pixels
+    pub fn rolling_means(&self) -> &[f64] {
+        &self.rolling_means
+    }
+
+    /// Returns the trend in intensity at each row, estimated using the specified method. The trend is NaN for
+    /// every row if no rows were acquired.
+    pub fn trend(&self, method: Detrend) -> Vec<f64> {
+        let points: Vec<_> = self
+            .row_means
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_finite())
+            .map(|(row, &value)| (row as f64, value))
+            .collect();
+
+        (0..self.row_means.len())
+            .map(|row| {
+                let row = row as f64;
+                let (intercept, slope) = match method {
+                    Detrend::Linear => fit_line(points.iter().map(|&(x, y)| (x, y, 1.0))),
+                    Detrend::Loess { span } => fit_local_line(&points, row, span),
+                };
+
+                intercept + slope * row
+            })
+            .collect()
+    }
+
+    /// Returns the change in intensity from the first to the last row of the linear trend, relative to the
+    /// intensity at the first row (e.g. -0.2 for a 20% loss of sensitivity over the acquisition)
+    pub fn relative_change(&self) -> f64 {
+        let trend = self.trend(Detrend::Linear);
+
+        match (trend.first(), trend.last()) {
+            (Some(first), Some(last)) => (last - first) / first,
+            _ => f64::NAN,
+        }
+    }
+}
+
+impl<R: Read + Seek> Acquisition<R> {
+    /// Returns the mean intensity of the channel in each row of the acquisition, along with the mean over a rolling
+    /// window of `window` rows. As rows are ablated in turn, this describes how the intensity changes over the
+    /// course of the acquisition, so that drift in sensitivity can be detected (and corrected with
+    /// `ChannelImage::detrend()`).
+    pub fn drift_profile<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        window: usize,
+    ) -> Result<DriftProfile> {
+        Ok(self.channel_image(identifier, None)?.drift_profile(window))
+    }
+}
+
+impl ChannelImage {
+    /// Returns the mean intensity of each row of the image, along with the mean over a rolling window of `window`
+    /// rows (see `Acquisition::drift_profile()`)
+    pub fn drift_profile(&self, window: usize) -> DriftProfile {
+        DriftProfile::new(self, window)
+    }
+
+    /// Returns a new `ChannelImage` with the drift in intensity between rows removed, by scaling each row so that
+    /// the trend (estimated using the specified method) is constant at its mean. Rows where the trend is not
+    /// positive are left unchanged.
+    pub fn detrend(&self, method: Detrend) -> ChannelImage {
+        let trend = self.drift_profile(1).trend(method);
+        let mean_trend = mean(&trend);
+        let width = self.width() as usize;
+
+        let data = (0..width * self.height() as usize)
+            .map(|index| {
+                let value = self.pixel(index);
+                let row_trend = trend[index / width];
+
+                if row_trend > 0.0 && mean_trend.is_finite() {
+                    (value as f64 * mean_trend / row_trend) as f32
+                } else {
+                    value
+                }
+            })
+            .collect();
+
+        self.with_data(data, self.valid_pixels)
+    }
+}
+
+/// Returns the mean of the values, ignoring NaN (NaN if all are NaN)
+fn mean(values: &[f64]) -> f64 {
+    let (sum, count) = values
+        .iter()
+        .filter(|value| !value.is_nan())
+        .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+
+    sum / count as f64
+}
+
+/// Weighted least squares fit of a straight line to the points (x, y, weight), returning (intercept, slope). The
+/// line is flat if all points share an x, and NaN if there are no points.
+fn fit_line(points: impl Iterator<Item = (f64, f64, f64)> + Clone) -> (f64, f64) {
+    let total_weight: f64 = points.clone().map(|(_, _, weight)| weight).sum();
+    let mean_x = points.clone().map(|(x, _, weight)| weight * x).sum::<f64>() / total_weight;
+    let mean_y = points.clone().map(|(_, y, weight)| weight * y).sum::<f64>() / total_weight;
+
+    let (covariance, variance) =
+        points.fold((0.0, 0.0), |(covariance, variance), (x, y, weight)| {
+            (
+                covariance + weight * (x - mean_x) * (y - mean_y),
+                variance + weight * (x - mean_x).powi(2),
+            )
+        });
+
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+
+    (mean_y - slope * mean_x, slope)
+}
+
+/// Fit a straight line to the nearest `span` fraction of the points to `x`, weighted by the tricube of their
+/// distance to `x`
+fn fit_local_line(points: &[(f64, f64)], x: f64, span: f64) -> (f64, f64) {
+    let num_points = ((span.clamp(0.0, 1.0) * points.len() as f64).ceil() as usize)
+        .clamp(2.min(points.len()), points.len());
+
+    let mut distances: Vec<_> = points.iter().map(|&(px, _)| (px - x).abs()).collect();
+    distances.sort_unstable_by(|a, b| a.total_cmp(b));
+    let max_distance = match num_points {
+        0 => return (f64::NAN, f64::NAN),
+        num_points => distances[num_points - 1],
+    };
+
+    let weighted = points.iter().filter_map(move |&(px, py)| {
+        let distance = (px - x).abs();
+        if distance > max_distance {
+            return None;
+        }
+
+        // Scale the distances slightly, so that the furthest points included still have some weight
+        let weight = (1.0 - (distance / (max_distance * 1.01 + f64::EPSILON)).powi(3)).powi(3);
+        Some((px, py, weight))
+    });
+
+    fit_line(weighted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    #[test]
+    fn drift_profile_and_detrend() -> Result<()> {
+        // Sensitivity increasing steadily over the acquisition
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 4).channel("Ir191", "DNA1"),
+            [1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 4.0, 4.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        let profile = acquisition.drift_profile(ChannelIdentifier::name("Ir191"), 3)?;
+        assert_eq!(profile.row_means(), [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(profile.rolling_means(), [1.5, 2.0, 3.0, 3.5]);
+        assert!((profile.relative_change() - 3.0).abs() < 1e-9);
+
+        let image = acquisition.channel_image(ChannelIdentifier::name("Ir191"), None)?;
+        for method in [Detrend::Linear, Detrend::loess(0.75)] {
+            let detrended = image.detrend(method);
+            assert!(detrended
+                .intensities()
+                .iter()
+                .all(|&value| (value - 2.5).abs() < 1e-4));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    error::{MCDError, Result},
+    Acquisition, AcquisitionChannel, ChannelIdentifier, ChannelImage, Isotope, Region, MCD,
+};
+
+mod drift;
+
+pub use drift::{Detrend, DriftProfile};
+
+/// Describes how the background signal (e.g. from the carrier gas) is estimated from the background channel
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Background {
+    /// Subtract the intensity of the background channel at each pixel
+    PerPixel,
+    /// Subtract the median intensity of the background channel over the whole acquisition, which is more robust
+    /// to the noise in the background channel at low counts
+    #[default]
+    PerAcquisition,
+    /// Subtract a known background intensity (e.g. from `calibration_background()`), in which case no background
+    /// channel is required
+    Constant(f32),
+}
+
+/// Options for subtracting the background from channel images (see `subtract_background()`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundSubtraction {
+    background: Background,
+    channel: Option<ChannelIdentifier>,
+    scale: f32,
+    clip: bool,
+}
+
+impl Default for BackgroundSubtraction {
+    fn default() -> Self {
+        BackgroundSubtraction {
+            background: Background::default(),
+            channel: None,
+            scale: 1.0,
+            clip: true,
+        }
+    }
+}
+
+impl BackgroundSubtraction {
+    /// Subtract the background estimated as described by `background`, with the default options (the background
+    /// channel found by `background_channels()`, a scale of 1 and clipping negative intensities to 0)
+    pub fn new(background: Background) -> Self {
+        BackgroundSubtraction {
+            background,
+            ..Default::default()
+        }
+    }
+
+    /// Use the channel matching `identifier` as the background channel, rather than the first channel returned
+    /// by `background_channels()`
+    pub fn channel<C: Into<ChannelIdentifier>>(mut self, identifier: C) -> Self {
+        self.channel = Some(identifier.into());
+        self
+    }
+
+    /// Multiply the background by `scale` before it is subtracted, for when the background in the measured
+    /// channels differs from that in the background channel
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Set whether intensities which are negative after subtracting the background are set to 0 (the default)
+    pub fn clip(mut self, clip: bool) -> Self {
+        self.clip = clip;
+        self
+    }
+
+    fn subtract(&self, intensity: f32, background: f32) -> f32 {
+        let corrected = intensity - self.scale * background;
+
+        if self.clip {
+            corrected.max(0.0)
+        } else {
+            corrected
+        }
+    }
+}
+
+/// Returns whether the channel name or label describes a background channel (e.g. `190BCKG` or the xenon
+/// isotopes, such as Xe131, which are present in the argon carrier gas)
+pub fn is_background_channel(channel: &AcquisitionChannel) -> bool {
+    is_background(channel.name()) || is_background(channel.label())
+}
+
+/// Returns the background channels of the acquisition (see `is_background_channel()`), in acquisition order
+pub fn background_channels<R>(acquisition: &Acquisition<R>) -> Vec<&AcquisitionChannel> {
+    acquisition
+        .channels()
+        .iter()
+        .filter(|channel| is_background_channel(channel))
+        .collect()
+}
+
+/// Returns the background intensity of the acquisition recorded in its most recent calibration, the mean dual
+/// counts of the background calibration channels. Returns None if there was no calibration, or no background
+/// channel was calibrated.
+pub fn calibration_background<R>(mcd: &MCD<R>, acquisition_id: u16) -> Option<f32> {
+    let calibration = mcd
+        .calibrations
+        .values()
+        .filter(|calibration| calibration.acquisition_id() == acquisition_id)
+        .max_by(|a, b| a.time_stamp().cmp(b.time_stamp()))?;
+
+    let background: Vec<_> = mcd
+        .calibration_channels
+        .values()
+        .filter(|channel| {
+            channel.calibration_id() == calibration.id() && is_background(channel.name())
+        })
+        .map(|channel| channel.mean_duals())
+        .collect();
+
+    if background.is_empty() {
+        None
+    } else {
+        Some((background.iter().sum::<f64>() / background.len() as f64) as f32)
+    }
+}
+
+/// Returns the images of the channels matching `identifiers` (within `region`, or the whole acquisition if None)
+/// with the background subtracted as described by `options`. Missing pixels remain missing.
+///
+/// ```no_run
+/// use imc_rs::{
+///     preprocessing::{self, Background, BackgroundSubtraction},
+///     ChannelIdentifier, MCD,
+/// };
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let acquisition = mcd.find_acquisition("ROI_001").unwrap();
+///
+/// let options = BackgroundSubtraction::new(Background::PerPixel).channel(ChannelIdentifier::name("Xe131"));
+/// let images = preprocessing::subtract_background(
+///     acquisition,
+///     &[ChannelIdentifier::label("CD3")],
+///     None,
+///     &options,
+/// )
+/// .unwrap();
+/// ```
+pub fn subtract_background<R: Read + Seek, C: AsRef<ChannelIdentifier>>(
+    acquisition: &Acquisition<R>,
+    identifiers: &[C],
+    region: Option<Region>,
+    options: &BackgroundSubtraction,
+) -> Result<Vec<ChannelImage>> {
+    let images = acquisition.channel_images(identifiers, region)?;
+
+    let subtract_constant = |background: f32| {
+        images
+            .iter()
+            .map(|image| image.map(|intensity| options.subtract(intensity, background)))
+            .collect()
+    };
+
+    match options.background {
+        Background::Constant(background) => Ok(subtract_constant(background)),
+        Background::PerAcquisition => {
+            let background =
+                acquisition.channel_image(background_channel(acquisition, options)?, None)?;
+
+            Ok(subtract_constant(median(background.intensities())))
+        }
+        Background::PerPixel => {
+            let background =
+                acquisition.channel_image(background_channel(acquisition, options)?, region)?;
+
+            images
+                .iter()
+                .map(|image| {
+                    image.combine(&background, |intensity, background| {
+                        options.subtract(intensity, background)
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Returns the identifier of the background channel to use for the acquisition
+fn background_channel<R>(
+    acquisition: &Acquisition<R>,
+    options: &BackgroundSubtraction,
+) -> Result<ChannelIdentifier> {
+    if let Some(identifier) = &options.channel {
+        return Ok(identifier.clone());
+    }
+
+    background_channels(acquisition)
+        .first()
+        .map(|channel| ChannelIdentifier::name(channel.name()))
+        .ok_or(MCDError::NoBackgroundChannel {
+            acquisition_id: acquisition.id(),
+        })
+}
+
+fn is_background(text: &str) -> bool {
+    let lowercase = text.to_ascii_lowercase();
+
+    lowercase.contains("bckg")
+        || lowercase.contains("background")
+        || Isotope::parse(text).is_some_and(|isotope| isotope.element() == "Xe")
+}
+
+/// Returns the median of the intensities, ignoring missing (NaN) pixels (0 if all are missing)
+fn median(intensities: &[f32]) -> f32 {
+    let mut sorted: Vec<_> = intensities
+        .iter()
+        .copied()
+        .filter(|value| !value.is_nan())
+        .collect();
+    sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+
+    match sorted.len() {
+        0 => 0.0,
+        len if len.is_multiple_of(2) => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+        len => sorted[len / 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn subtract_background_channel() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 3, 1)
+                .channel("Er170", "CD3")
+                .channel("190BCKG", "190BCKG"),
+            [10.0, 1.0, 10.0, 2.0, 1.0, 6.0],
+        )])?)?;
+        let acquisition = mcd.acquisitions()[0];
+
+        let channels = background_channels(acquisition);
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].name(), "190BCKG");
+
+        let cd3 = [ChannelIdentifier::label("CD3")];
+        let subtract = |options: BackgroundSubtraction| -> Result<Vec<f32>> {
+            Ok(subtract_background(acquisition, &cd3, None, &options)?[0]
+                .intensities()
+                .to_vec())
+        };
+
+        assert_eq!(
+            subtract(BackgroundSubtraction::new(Background::PerPixel))?,
+            [9.0, 8.0, 0.0]
+        );
+        assert_eq!(
+            subtract(BackgroundSubtraction::new(Background::PerPixel).clip(false))?,
+            [9.0, 8.0, -5.0]
+        );
+        assert_eq!(
+            subtract(BackgroundSubtraction::new(Background::PerAcquisition))?,
+            [8.0, 8.0, 0.0]
+        );
+        assert_eq!(
+            subtract(BackgroundSubtraction::new(Background::Constant(0.5)).scale(2.0))?,
+            [9.0, 9.0, 0.0]
+        );
+
+        // An explicit background channel is used instead of the detected one
+        let options = BackgroundSubtraction::new(Background::PerPixel).channel(cd3[0].clone());
+        assert_eq!(subtract(options)?, [0.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::Write;
+
+use crate::{error::Result, Acquisition, AcquisitionRef, MCD};
+
+/// Matrix recording which channels (metals) were measured in which acquisitions. The panel can change part way
+/// through a run (e.g. when a channel is added to later acquisitions), so not every channel is present in every
+/// acquisition. The X, Y and Z position channels are present in all acquisitions and so are not included.
+///
+/// ```no_run
+/// use imc_rs::MCD;
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let presence = mcd.channel_presence();
+///
+/// println!("Measured in all acquisitions: {:?}", presence.intersection());
+/// presence.to_csv(std::fs::File::create("presence.csv").unwrap()).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelPresence {
+    acquisitions: Vec<AcquisitionRef>,
+    channels: Vec<String>,
+    // present[channel][acquisition]
+    present: Vec<Vec<bool>>,
+}
+
+impl ChannelPresence {
+    /// Create the matrix for all acquisitions in the .mcd file. Channels are ordered as in `Panel`.
+    pub fn new<R>(mcd: &MCD<R>) -> Self {
+        Self::from_acquisitions(&mcd.acquisitions())
+    }
+
+    /// Create the matrix for the specified acquisitions
+    pub(crate) fn from_acquisitions<R>(acquisitions: &[&Acquisition<R>]) -> Self {
+        let mut channels: Vec<(Option<u16>, String)> = Vec::new();
+
+        for acquisition in acquisitions {
+            for channel in acquisition.channels() {
+                if channel.is_position() || channels.iter().any(|(_, name)| name == channel.name())
+                {
+                    continue;
+                }
+
+                let mass = channel.isotope().map(|isotope| isotope.mass());
+                channels.push((mass, channel.name().to_string()));
+            }
+        }
+
+        channels.sort();
+
+        let channels: Vec<String> = channels.into_iter().map(|(_, name)| name).collect();
+        let present = channels
+            .iter()
+            .map(|name| {
+                acquisitions
+                    .iter()
+                    .map(|acquisition| {
+                        acquisition
+                            .channels()
+                            .iter()
+                            .any(|channel| channel.name() == name)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        ChannelPresence {
+            acquisitions: acquisitions
+                .iter()
+                .map(|acquisition| acquisition.reference())
+                .collect(),
+            channels,
+            present,
+        }
+    }
+
+    /// Returns the acquisitions (columns) of the matrix
+    pub fn acquisitions(&self) -> &[AcquisitionRef] {
+        &self.acquisitions
+    }
+
+    /// Returns the names of the channels (rows) of the matrix
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    /// Returns whether the channel was measured in the acquisition
+    pub fn is_present(&self, acquisition: AcquisitionRef, channel: &str) -> bool {
+        match (
+            self.acquisitions.iter().position(|a| *a == acquisition),
+            self.channels.iter().position(|c| c == channel),
+        ) {
+            (Some(acquisition), Some(channel)) => self.present[channel][acquisition],
+            _ => false,
+        }
+    }
+
+    /// Returns the channels measured in at least one acquisition
+    pub fn union(&self) -> Vec<&str> {
+        self.channels_where(|present| present.iter().any(|&present| present))
+    }
+
+    /// Returns the channels measured in every acquisition
+    pub fn intersection(&self) -> Vec<&str> {
+        self.channels_where(|present| present.iter().all(|&present| present))
+    }
+
+    /// Returns the matrix restricted to the acquisitions on the slide
+    pub fn for_slide(&self, slide_id: u16) -> ChannelPresence {
+        self.filter(|acquisition| acquisition.slide() == slide_id)
+    }
+
+    /// Returns the matrix restricted to the acquisitions in the panorama
+    pub fn for_panorama(&self, slide_id: u16, panorama_id: u16) -> ChannelPresence {
+        self.filter(|acquisition| {
+            acquisition.slide() == slide_id && acquisition.panorama() == panorama_id
+        })
+    }
+
+    /// Write the matrix as .csv, with one row per channel and one column per acquisition (1 if the channel is
+    /// present, 0 otherwise)
+    pub fn to_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        let mut header = vec!["channel".to_string()];
+        header.extend(self.acquisitions.iter().map(|acquisition| {
+            format!(
+                "slide_{}_panorama_{}_acquisition_{}",
+                acquisition.slide(),
+                acquisition.panorama(),
+                acquisition.id()
+            )
+        }));
+        writer.write_record(&header)?;
+
+        for (channel, present) in self.channels.iter().zip(&self.present) {
+            let mut record = vec![channel.as_str()];
+            record.extend(
+                present
+                    .iter()
+                    .map(|&present| if present { "1" } else { "0" }),
+            );
+
+            writer.write_record(&record)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn channels_where<F: Fn(&[bool]) -> bool>(&self, predicate: F) -> Vec<&str> {
+        self.channels
+            .iter()
+            .zip(&self.present)
+            .filter(|(_, present)| predicate(present))
+            .map(|(channel, _)| channel.as_str())
+            .collect()
+    }
+
+    fn filter<F: Fn(&AcquisitionRef) -> bool>(&self, predicate: F) -> ChannelPresence {
+        let columns: Vec<_> = self
+            .acquisitions
+            .iter()
+            .enumerate()
+            .filter(|(_, acquisition)| predicate(acquisition))
+            .map(|(index, _)| index)
+            .collect();
+
+        let mut channels = Vec::new();
+        let mut present = Vec::new();
+
+        for (channel, row) in self.channels.iter().zip(&self.present) {
+            let row: Vec<_> = columns.iter().map(|&index| row[index]).collect();
+
+            // Only keep channels measured in the remaining acquisitions
+            if row.iter().any(|&present| present) {
+                channels.push(channel.clone());
+                present.push(row);
+            }
+        }
+
+        ChannelPresence {
+            acquisitions: columns
+                .iter()
+                .map(|&index| self.acquisitions[index])
+                .collect(),
+            channels,
+            present,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_and_intersection() {
+        let presence = ChannelPresence {
+            acquisitions: vec![
+                AcquisitionRef::new(1, 1, 1),
+                AcquisitionRef::new(1, 2, 2),
+                AcquisitionRef::new(2, 3, 1),
+            ],
+            channels: vec!["Sm152".to_string(), "Ir191".to_string()],
+            present: vec![vec![false, true, true], vec![true, true, true]],
+        };
+
+        assert_eq!(presence.union(), ["Sm152", "Ir191"]);
+        assert_eq!(presence.intersection(), ["Ir191"]);
+        assert!(presence.is_present(AcquisitionRef::new(2, 3, 1), "Sm152"));
+        assert!(!presence.is_present(AcquisitionRef::new(1, 1, 1), "Sm152"));
+
+        assert_eq!(presence.for_slide(2).intersection(), ["Sm152", "Ir191"]);
+        assert_eq!(presence.for_panorama(1, 1).channels(), ["Ir191"]);
+
+        let mut csv = Vec::new();
+        assert!(presence.to_csv(&mut csv).is_ok());
+        assert_eq!(
+            String::from_utf8_lossy(&csv),
+            "channel,slide_1_panorama_1_acquisition_1,slide_1_panorama_2_acquisition_2,\
+             slide_2_panorama_3_acquisition_1\n\
+             Sm152,0,1,1\n\
+             Ir191,1,1,1\n"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Source of data which can be read from any offset, without a notion of a current position. This is the
+/// interface offered by many sources which aren't files, such as a JavaScript `File` or `Blob` in a browser
+/// (read a slice of bytes with `FileReaderSync`, from a web worker) or an object in cloud storage (read with
+/// range requests). Wrap the source in a `ReadAtReader` to parse it with `MCD::parse`.
+pub trait ReadAt {
+    /// Returns the total size of the data in bytes
+    fn size(&self) -> io::Result<u64>;
+
+    /// Read bytes starting at `offset` into `buf`, returning the number of bytes read. Fewer bytes than requested
+    /// may be read, and 0 is returned at the end of the data.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+/// Reader over a `ReadAt` source, tracking the current position so that the source can be used wherever a
+/// `Read + Seek` reader is expected (e.g. `MCD::parse`).
+///
+/// As .mcd files are parsed by seeking to the metadata at the end of the file, only the parts of the file which
+/// are needed are read (e.g. the metadata and panorama images can be shown without reading the acquisitions).
+///
+/// ```
+/// use std::io;
+///
+/// use imc_rs::{ReadAt, ReadAtReader, MCD};
+///
+/// // e.g. a JavaScript `File`, accessed through wasm-bindgen
+/// struct BrowserFile(Vec<u8>);
+///
+/// impl ReadAt for BrowserFile {
+///     fn size(&self) -> io::Result<u64> {
+///         Ok(self.0.len() as u64)
+///     }
+///
+///     fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+///         let data = self.0.get(offset as usize..).unwrap_or_default();
+///         let length = data.len().min(buf.len());
+///         buf[..length].copy_from_slice(&data[..length]);
+///
+///         Ok(length)
+///     }
+/// }
+///
+/// // Not a valid .mcd file, so parsing fails
+/// assert!(MCD::parse(ReadAtReader::new(BrowserFile(vec![0; 16]))).is_err());
+/// ```
+#[derive(Debug)]
+pub struct ReadAtReader<T> {
+    source: T,
+    position: u64,
+}
+
+impl<T> ReadAtReader<T> {
+    /// Wrap the source, starting at the beginning of the data
+    pub fn new(source: T) -> Self {
+        ReadAtReader {
+            source,
+            position: 0,
+        }
+    }
+
+    /// Returns a reference to the wrapped source
+    pub fn get_ref(&self) -> &T {
+        &self.source
+    }
+
+    /// Returns the wrapped source
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+impl<T: ReadAt> Read for ReadAtReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.source.read_at(self.position, buf)?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<T: ReadAt> Seek for ReadAtReader<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.source.size()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec, ChannelIdentifier, MCD};
+
+    struct Source {
+        data: Vec<u8>,
+    }
+
+    impl ReadAt for Source {
+        fn size(&self) -> io::Result<u64> {
+            Ok(self.data.len() as u64)
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+            let data = self.data.get(offset as usize..).unwrap_or_default();
+            let length = data.len().min(buf.len());
+            buf[..length].copy_from_slice(&data[..length]);
+
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn parse_in_memory() -> Result<()> {
+        let data = synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1).channel("Ir191", "DNA1"),
+            [1.0, 2.0],
+        )])?;
+
+        let mcd = MCD::from_bytes(data.clone())?;
+        let image = mcd.acquisitions()[0].channel_image(ChannelIdentifier::name("Ir191"), None)?;
+        assert_eq!(image.intensities(), [1.0, 2.0]);
+
+        let source = Source { data };
+        let mcd = MCD::parse(ReadAtReader::new(&source))?;
+        let image = mcd.acquisitions()[0].channel_image(ChannelIdentifier::name("Ir191"), None)?;
+        assert_eq!(image.intensities(), [1.0, 2.0]);
+
+        let mut reader = ReadAtReader::new(&source);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-4))?,
+            source.data.len() as u64 - 4
+        );
+        assert!(reader
+            .seek(SeekFrom::Current(-(source.data.len() as i64)))
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    error::{channel_not_found, MCDError, Result},
+    Acquisition, AcquisitionRef, ChannelIdentifier, ChannelImage, Region, MCD,
+};
+
+/// A single channel image requested from a `ReadPlan`
+#[derive(Debug, Clone)]
+struct ReadRequest {
+    acquisition: AcquisitionRef,
+    channel: ChannelIdentifier,
+    region: Option<Region>,
+}
+
+/// A set of channel image reads, which are reordered and coalesced before being performed so that the file is
+/// read as few times as possible and in order. Requests for the same acquisition with overlapping (or adjacent)
+/// regions are combined into a single read of all of their channels, and the reads are ordered by where the
+/// acquisitions are stored in the file.
+///
+/// This is useful for batch jobs which need many images at once (e.g. exporting or quantifying a set of
+/// regions), especially without a .dcm file, where reading any channel requires reading whole spectra.
+#[derive(Debug, Clone, Default)]
+pub struct ReadPlan {
+    requests: Vec<ReadRequest>,
+}
+
+/// A read performed by a `ReadPlan`: the channels of a region of an acquisition, read together
+#[derive(Debug, Clone)]
+pub struct PlannedRead {
+    acquisition: AcquisitionRef,
+    region: Region,
+    channels: Vec<ChannelIdentifier>,
+    // (index of the request, index of the channel, region of the request)
+    requests: Vec<(usize, usize, Region)>,
+}
+
+impl PlannedRead {
+    /// Returns the acquisition which is read
+    pub fn acquisition(&self) -> AcquisitionRef {
+        self.acquisition
+    }
+
+    /// Returns the region (in pixels) which is read
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Returns the channels which are read
+    pub fn channels(&self) -> &[ChannelIdentifier] {
+        &self.channels
+    }
+
+    /// Returns the number of requests served by the read
+    pub fn num_requests(&self) -> usize {
+        self.requests.len()
+    }
+}
+
+impl ReadPlan {
+    /// Create an empty plan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the image of the channel of the acquisition, optionally restricted to a region (in pixels)
+    pub fn read<C: Into<ChannelIdentifier>>(
+        mut self,
+        acquisition: AcquisitionRef,
+        channel: C,
+        region: Option<Region>,
+    ) -> Self {
+        self.requests.push(ReadRequest {
+            acquisition,
+            channel: channel.into(),
+            region,
+        });
+        self
+    }
+
+    /// Returns the number of requested images
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Returns true if no images have been requested
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Returns the reads which would be performed, in the order they would be performed
+    pub fn steps<R: Read + Seek>(&self, mcd: &MCD<R>) -> Result<Vec<PlannedRead>> {
+        self.steps_with(|reference| mcd.find_acquisition(reference))
+    }
+
+    /// Perform the reads, returning the requested images in the order they were requested
+    pub fn execute<R: Read + Seek>(&self, mcd: &MCD<R>) -> Result<Vec<ChannelImage>> {
+        self.execute_with(|reference| mcd.find_acquisition(reference))
+    }
+
+    /// Plan the reads, using `resolve` to look up each acquisition
+    pub(crate) fn steps_with<'a, R: Read + Seek + 'a, F>(
+        &self,
+        resolve: F,
+    ) -> Result<Vec<PlannedRead>>
+    where
+        F: Fn(AcquisitionRef) -> Result<&'a Acquisition<R>>,
+    {
+        // (offset of the acquisition data, request index, canonical channel, region)
+        let mut resolved = Vec::with_capacity(self.requests.len());
+
+        for (index, request) in self.requests.iter().enumerate() {
+            let acquisition = resolve(request.acquisition)?;
+            let channel = acquisition
+                .channel(&request.channel)
+                .ok_or_else(|| channel_not_found(&request.channel, acquisition.channels()))?;
+            let region = request.region.unwrap_or(Region {
+                x: 0,
+                y: 0,
+                width: acquisition.width().max(0) as u32,
+                height: acquisition.height().max(0) as u32,
+            });
+
+            resolved.push((
+                acquisition.data_span().start(),
+                index,
+                ChannelIdentifier::from(channel),
+                region,
+            ));
+        }
+
+        // Group by acquisition (in file order), then by position within the acquisition
+        resolved.sort_by_key(|&(offset, index, _, region)| {
+            (offset, self.requests[index].acquisition, region.y, region.x)
+        });
+
+        let mut steps: Vec<PlannedRead> = Vec::new();
+
+        for (_, index, channel, region) in resolved {
+            let acquisition = self.requests[index].acquisition;
+
+            let step = match steps.last_mut() {
+                Some(step) if step.acquisition == acquisition && touches(&step.region, &region) => {
+                    step.region = bounding_region(&step.region, &region);
+                    step
+                }
+                _ => {
+                    steps.push(PlannedRead {
+                        acquisition,
+                        region,
+                        channels: Vec::new(),
+                        requests: Vec::new(),
+                    });
+
+                    let last = steps.len() - 1;
+                    &mut steps[last]
+                }
+            };
+
+            let channel_index = match step.channels.iter().position(|c| *c == channel) {
+                Some(channel_index) => channel_index,
+                None => {
+                    step.channels.push(channel);
+                    step.channels.len() - 1
+                }
+            };
+
+            step.requests.push((index, channel_index, region));
+        }
+
+        Ok(steps)
+    }
+
+    /// Perform the reads, using `resolve` to look up each acquisition
+    pub(crate) fn execute_with<'a, R: Read + Seek + 'a, F>(
+        &self,
+        resolve: F,
+    ) -> Result<Vec<ChannelImage>>
+    where
+        F: Fn(AcquisitionRef) -> Result<&'a Acquisition<R>>,
+    {
+        let mut images: Vec<Option<ChannelImage>> = vec![None; self.requests.len()];
+
+        for step in self.steps_with(&resolve)? {
+            let acquisition = resolve(step.acquisition)?;
+            let read = acquisition.channel_images(&step.channels, Some(step.region))?;
+
+            for (index, channel_index, region) in step.requests {
+                let image = &read[channel_index];
+
+                images[index] = Some(if region == step.region {
+                    image.clone()
+                } else {
+                    acquisition.crop(image, region)
+                });
+            }
+        }
+
+        images
+            .into_iter()
+            .map(|image| {
+                image.ok_or(MCDError::InvalidBufferSize {
+                    expected: self.requests.len(),
+                    actual: 0,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<R> Acquisition<R> {
+    /// Returns the part of the image (read from this acquisition) within the region, which must be contained
+    /// within the region of the image
+    fn crop(&self, image: &ChannelImage, region: Region) -> ChannelImage {
+        let mut data = Vec::with_capacity(region.width as usize * region.height as usize);
+
+        for y in region.y..region.y + region.height {
+            let start = (y - image.region.y) as usize * image.region.width as usize
+                + (region.x - image.region.x) as usize;
+
+            data.extend_from_slice(&image.data[start..start + region.width as usize]);
+        }
+
+        // NaN (missing) pixels are ignored, as comparisons with NaN are always false
+        let range = data
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (
+                    if value < min { value } else { min },
+                    if value > max { value } else { max },
+                )
+            });
+
+        ChannelImage {
+            region,
+            acquisition_id: image.acquisition_id,
+            name: image.name.clone(),
+            label: image.label.clone(),
+            range,
+            valid_pixels: self.valid_pixels_in(&region),
+            data,
+        }
+    }
+}
+
+/// Tests whether the regions overlap or share an edge
+fn touches(a: &Region, b: &Region) -> bool {
+    a.x <= b.x + b.width && b.x <= a.x + a.width && a.y <= b.y + b.height && b.y <= a.y + a.height
+}
+
+fn bounding_region(a: &Region, b: &Region) -> Region {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+
+    Region {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn regions_coalesce() {
+        assert!(touches(&region(0, 0, 10, 10), &region(10, 5, 10, 10)));
+        assert!(!touches(&region(0, 0, 10, 10), &region(11, 0, 10, 10)));
+
+        assert_eq!(
+            bounding_region(&region(0, 0, 10, 10), &region(5, 20, 10, 10)),
+            region(0, 0, 15, 30)
+        );
+    }
+
+    #[test]
+    fn images_follow_request_order() -> Result<()> {
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 1)
+                .channel("Ir191", "A")
+                .channel("Er170", "B"),
+            [1.0, 10.0, 2.0, 20.0],
+        )])?)?;
+        let reference = AcquisitionRef::new(1, 1, 1);
+        let intensities = |plan: ReadPlan| -> Result<Vec<(String, Vec<f32>)>> {
+            Ok(plan
+                .execute(&mcd)?
+                .into_iter()
+                .map(|image| (image.label().to_string(), image.intensities().to_vec()))
+                .collect())
+        };
+
+        // Channels requested in the reverse of the order they are stored in
+        let plan = ReadPlan::new()
+            .read(reference, ChannelIdentifier::label("B"), None)
+            .read(reference, ChannelIdentifier::label("A"), None);
+        assert_eq!(
+            intensities(plan)?,
+            [
+                ("B".to_string(), vec![10.0, 20.0]),
+                ("A".to_string(), vec![1.0, 2.0])
+            ]
+        );
+
+        // The same channel requested twice
+        let plan = ReadPlan::new()
+            .read(reference, ChannelIdentifier::label("A"), None)
+            .read(reference, ChannelIdentifier::label("A"), None);
+        assert_eq!(
+            intensities(plan)?,
+            [
+                ("A".to_string(), vec![1.0, 2.0]),
+                ("A".to_string(), vec![1.0, 2.0])
+            ]
+        );
+
+        Ok(())
+    }
+}
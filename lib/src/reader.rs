@@ -0,0 +1,240 @@
+use std::{
+    fmt,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex},
+};
+
+#[cfg(feature = "fs")]
+use crate::config;
+use crate::error::{MCDError, Result};
+
+type ReaderOpener<R> = Box<dyn Fn() -> io::Result<BufReader<R>> + Send + Sync>;
+
+struct PoolState<R> {
+    idle: Vec<BufReader<R>>,
+    num_open: usize,
+}
+
+/// A pool of readers onto the same underlying data.
+///
+/// Each caller takes exclusive use of a reader from the pool for the duration of a read, so concurrent reads
+/// (e.g. `channel_image` calls on different acquisitions from different threads) don't serialise on a
+/// single reader. When the pool was created with an opener (e.g. [`crate::MCD::from_path`] reopens the file)
+/// new readers are opened on demand, up to `max_readers`. Otherwise, the pool contains a single reader which
+/// callers take in turn.
+pub(crate) struct ReaderPool<R> {
+    state: Mutex<PoolState<R>>,
+    available: Condvar,
+    opener: Option<ReaderOpener<R>>,
+    max_readers: usize,
+}
+
+impl<R: Read> ReaderPool<R> {
+    /// Create a pool containing only the supplied reader
+    pub(crate) fn new(reader: R) -> Self {
+        ReaderPool {
+            state: Mutex::new(PoolState {
+                idle: vec![BufReader::new(reader)],
+                num_open: 1,
+            }),
+            available: Condvar::new(),
+            opener: None,
+            max_readers: 1,
+        }
+    }
+
+    /// Create a pool starting with the supplied reader, where additional readers can be created by calling `opener`
+    #[cfg(feature = "fs")]
+    pub(crate) fn with_opener<F>(reader: R, opener: F) -> Self
+    where
+        F: Fn() -> io::Result<R> + Send + Sync + 'static,
+    {
+        ReaderPool {
+            state: Mutex::new(PoolState {
+                idle: vec![BufReader::new(reader)],
+                num_open: 1,
+            }),
+            available: Condvar::new(),
+            opener: Some(Box::new(move || opener().map(BufReader::new))),
+            max_readers: config::threads().max(1),
+        }
+    }
+}
+
+impl<R> ReaderPool<R> {
+    /// Take a reader from the pool, opening a new one if none are idle and the pool is not at capacity, otherwise
+    /// waiting until one is returned. The reader is returned to the pool when the `PooledReader` is dropped.
+    pub(crate) fn get(&self) -> Result<PooledReader<'_, R>> {
+        let mut state = self.state.lock().or(Err(MCDError::PoisonMutex))?;
+
+        loop {
+            if let Some(reader) = state.idle.pop() {
+                return Ok(PooledReader {
+                    pool: self,
+                    reader: Some(reader),
+                });
+            }
+
+            if let Some(opener) = &self.opener {
+                if state.num_open < self.max_readers {
+                    state.num_open += 1;
+                    drop(state);
+
+                    return match opener() {
+                        Ok(reader) => Ok(PooledReader {
+                            pool: self,
+                            reader: Some(reader),
+                        }),
+                        Err(error) => {
+                            let mut state = self.state.lock().or(Err(MCDError::PoisonMutex))?;
+                            state.num_open -= 1;
+                            self.available.notify_one();
+
+                            Err(error.into())
+                        }
+                    };
+                }
+            }
+
+            state = self.available.wait(state).or(Err(MCDError::PoisonMutex))?;
+        }
+    }
+
+    fn put(&self, reader: BufReader<R>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        state.idle.push(reader);
+        self.available.notify_one();
+    }
+}
+
+impl<R> fmt::Debug for ReaderPool<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ReaderPool");
+
+        if let Ok(state) = self.state.lock() {
+            debug
+                .field("idle", &state.idle.len())
+                .field("open", &state.num_open);
+        }
+
+        debug.field("max_readers", &self.max_readers).finish()
+    }
+}
+
+/// Check that data ending at `end_offset` lies within the file, so that truncated files (e.g. an interrupted copy)
+/// are reported with the offending element rather than as an unexpected end of file. `element` is only called to
+/// describe the data if it is out of bounds.
+pub(crate) fn check_in_bounds<S: Seek, F: FnOnce() -> String>(
+    reader: &mut S,
+    element: F,
+    end_offset: u64,
+) -> Result<()> {
+    let position = reader.stream_position()?;
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+
+    if end_offset > file_len {
+        return Err(MCDError::OffsetOutOfBounds {
+            element: element(),
+            offset: end_offset,
+            file_len,
+        });
+    }
+
+    Ok(())
+}
+
+/// A reader taken from a `ReaderPool`, which is returned to the pool when dropped
+pub(crate) struct PooledReader<'a, R> {
+    pool: &'a ReaderPool<R>,
+    reader: Option<BufReader<R>>,
+}
+
+impl<'a, R> Deref for PooledReader<'a, R> {
+    type Target = BufReader<R>;
+
+    fn deref(&self) -> &Self::Target {
+        self.reader
+            .as_ref()
+            .expect("Reader is only taken when returning to the pool")
+    }
+}
+
+impl<'a, R> DerefMut for PooledReader<'a, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.reader
+            .as_mut()
+            .expect("Reader is only taken when returning to the pool")
+    }
+}
+
+impl<'a, R> Drop for PooledReader<'a, R> {
+    fn drop(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            self.pool.put(reader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use super::*;
+
+    #[test]
+    fn pool_opens_additional_readers() {
+        let data = vec![1u8, 2, 3, 4];
+        let opener_data = data.clone();
+        let mut pool = ReaderPool::with_opener(Cursor::new(data), move || {
+            Ok(Cursor::new(opener_data.clone()))
+        });
+        // Independent of the number of threads configured
+        pool.max_readers = 2;
+
+        let mut first = pool.get().expect("first reader");
+        first.seek(SeekFrom::Start(2)).expect("seek");
+
+        // A second reader must not share the position of the first
+        let mut second = pool.get().expect("second reader");
+        let mut byte = [0u8; 1];
+        second.read_exact(&mut byte).expect("read");
+        assert_eq!(byte[0], 1);
+        drop(second);
+
+        let mut byte = [0u8; 1];
+        first.read_exact(&mut byte).expect("read");
+        assert_eq!(byte[0], 3);
+        drop(first);
+
+        // Idle readers are reused rather than opening more
+        let _reader = pool.get().expect("reused reader");
+        assert_eq!(pool.state.lock().expect("state").num_open, 2);
+    }
+
+    #[test]
+    fn reports_data_beyond_end_of_file() {
+        let mut reader = Cursor::new(vec![0u8; 16]);
+        reader.seek(SeekFrom::Start(4)).expect("seek");
+
+        check_in_bounds(&mut reader, || unreachable!(), 16).expect("within bounds");
+        assert_eq!(reader.stream_position().expect("position"), 4);
+
+        match check_in_bounds(&mut reader, || "Acquisition 1 data".to_string(), 20) {
+            Err(MCDError::OffsetOutOfBounds {
+                element,
+                offset,
+                file_len,
+            }) => {
+                assert_eq!(element, "Acquisition 1 data");
+                assert_eq!((offset, file_len), (20, 16));
+            }
+            other => panic!("expected OffsetOutOfBounds, got {:?}", other),
+        }
+    }
+}
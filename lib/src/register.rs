@@ -0,0 +1,585 @@
+//! Landmark based registration between the slide and an external image (e.g. a brightfield or
+//! immunofluorescence image of the same tissue).
+//!
+//! Pairs of corresponding points (e.g. fiducial marks or landmarks picked in both images) are used to estimate
+//! an [`AffineTransform`] with [`estimate_affine_ransac`], which rejects pairs which don't agree with the
+//! majority. The resulting transform can be exchanged with ITK and elastix via their parameter files, in which
+//! the slide is the fixed image and the external image the moving image.
+//!
+//! ```
+//! use imc_rs::register;
+//!
+//! let external = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 10.0), (5.0, 5.0)];
+//! // The last pair was misplaced
+//! let slide = [(100.0, 200.0), (120.0, 200.0), (100.0, 220.0), (120.0, 220.0), (150.0, 150.0)];
+//!
+//! let registration = register::estimate_affine_ransac(&external, &slide).unwrap();
+//! assert_eq!(registration.inliers(), [0, 1, 2, 3]);
+//!
+//! let parameters = register::to_elastix_parameters(registration.transform()).unwrap();
+//! ```
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::{
+    error::{MCDError, Result},
+    transform::AffineTransform,
+};
+
+/// Number of pairs of points needed to define an affine transform
+const SAMPLE_SIZE: usize = 3;
+/// Maximum number of times the transform is refined using the inliers of the previous fit
+const REFINEMENTS: usize = 3;
+
+/// Options controlling the outlier rejection of [`estimate_affine_ransac_with`]
+#[derive(Debug, Clone)]
+pub struct RansacOptions {
+    threshold: f64,
+    iterations: usize,
+    min_inliers: usize,
+    seed: u64,
+}
+
+impl Default for RansacOptions {
+    fn default() -> Self {
+        RansacOptions {
+            threshold: 5.0,
+            iterations: 1000,
+            min_inliers: SAMPLE_SIZE,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl RansacOptions {
+    /// Create the default options (5 unit threshold, 1000 iterations)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum distance (in the units of the second set of points, e.g. μm on the slide) between a
+    /// transformed point and its pair for the pair to be considered an inlier
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the number of random samples of pairs to try
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Set the minimum number of inliers for the registration to succeed (at least 3)
+    pub fn min_inliers(mut self, min_inliers: usize) -> Self {
+        self.min_inliers = min_inliers.max(SAMPLE_SIZE);
+        self
+    }
+
+    /// Set the seed of the random sampling, so that the result is reproducible
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Result of [`estimate_affine_ransac`]
+#[derive(Debug, Clone)]
+pub struct Registration {
+    transform: AffineTransform<f64>,
+    inliers: Vec<usize>,
+    rms_error: f64,
+}
+
+impl Registration {
+    /// Returns the transform mapping the first set of points to the second (towards the slide)
+    pub fn transform(&self) -> &AffineTransform<f64> {
+        &self.transform
+    }
+
+    /// Returns the indices of the pairs of points which agree with the transform (in ascending order)
+    pub fn inliers(&self) -> &[usize] {
+        &self.inliers
+    }
+
+    /// Returns the indices of the pairs of points which were rejected as outliers (in ascending order)
+    pub fn outliers(&self, num_points: usize) -> Vec<usize> {
+        (0..num_points)
+            .filter(|index| self.inliers.binary_search(index).is_err())
+            .collect()
+    }
+
+    /// Returns the root mean square distance between the transformed inliers and their pairs
+    pub fn rms_error(&self) -> f64 {
+        self.rms_error
+    }
+}
+
+/// Estimate the affine transform mapping `points_a` (e.g. landmarks in an external image) to the corresponding
+/// `points_b` (e.g. the same landmarks on the slide, in μm), rejecting outliers with RANSAC using the default
+/// [`RansacOptions`]. See [`estimate_affine_ransac_with`].
+pub fn estimate_affine_ransac(
+    points_a: &[(f64, f64)],
+    points_b: &[(f64, f64)],
+) -> Result<Registration> {
+    estimate_affine_ransac_with(points_a, points_b, &RansacOptions::default())
+}
+
+/// Estimate the affine transform mapping `points_a` to the corresponding `points_b`, rejecting outliers with
+/// RANSAC. Random samples of three pairs are used to find the transform which the most pairs agree with (to
+/// within `options.threshold`), which is then refined by a least squares fit to all of those pairs.
+///
+/// Fails if the number of points differ, or no transform is agreed on by `options.min_inliers` pairs (e.g. if
+/// the points are collinear).
+pub fn estimate_affine_ransac_with(
+    points_a: &[(f64, f64)],
+    points_b: &[(f64, f64)],
+    options: &RansacOptions,
+) -> Result<Registration> {
+    if points_a.len() != points_b.len() {
+        return Err(MCDError::InvalidRegistration {
+            reason: format!(
+                "{} points can't be paired with {} points",
+                points_a.len(),
+                points_b.len()
+            ),
+        });
+    }
+    if points_a.len() < options.min_inliers {
+        return Err(MCDError::InvalidRegistration {
+            reason: format!(
+                "{} pairs of points were supplied, but at least {} are required",
+                points_a.len(),
+                options.min_inliers
+            ),
+        });
+    }
+
+    let mut best: Option<Vec<usize>> = None;
+    let mut random = XorShift::new(options.seed);
+
+    for _ in 0..options.iterations {
+        let sample = random.sample(points_a.len());
+        let matrix = match fit(points_a, points_b, &sample) {
+            Some(matrix) => matrix,
+            None => continue,
+        };
+
+        let inliers = inliers(&matrix, points_a, points_b, options.threshold);
+        if best.as_ref().is_none_or(|best| inliers.len() > best.len()) {
+            let all_agree = inliers.len() == points_a.len();
+            best = Some(inliers);
+
+            if all_agree {
+                break;
+            }
+        }
+    }
+
+    // Refine using all of the inliers, which may in turn change which pairs are inliers
+    let mut inliers = best.unwrap_or_default();
+    let mut matrix = None;
+    for _ in 0..REFINEMENTS {
+        if inliers.len() < options.min_inliers {
+            break;
+        }
+
+        let refined = match fit(points_a, points_b, &inliers) {
+            Some(refined) => refined,
+            None => break,
+        };
+        let refined_inliers = self::inliers(&refined, points_a, points_b, options.threshold);
+        if refined_inliers.len() < options.min_inliers {
+            break;
+        }
+
+        matrix = Some(refined);
+        if refined_inliers == inliers {
+            break;
+        }
+        inliers = refined_inliers;
+    }
+
+    let matrix = match matrix {
+        Some(matrix) if inliers.len() >= options.min_inliers => matrix,
+        _ => {
+            return Err(MCDError::InvalidRegistration {
+                reason: format!(
+                    "no transform was agreed on by at least {} of the {} pairs of points",
+                    options.min_inliers,
+                    points_a.len()
+                ),
+            })
+        }
+    };
+
+    let rms_error = (inliers
+        .iter()
+        .map(|&index| squared_error(&matrix, points_a[index], points_b[index]))
+        .sum::<f64>()
+        / inliers.len() as f64)
+        .sqrt();
+
+    Ok(Registration {
+        transform: AffineTransform::from_matrix(matrix),
+        inliers,
+        rms_error,
+    })
+}
+
+/// Least squares fit of the affine matrix mapping the selected `points_a` onto `points_b`, or None if the
+/// selected points are collinear
+fn fit(
+    points_a: &[(f64, f64)],
+    points_b: &[(f64, f64)],
+    selected: &[usize],
+) -> Option<Matrix3<f64>> {
+    // Solve the normal equations (AᵀA) X = Aᵀb, where each row of A is (x, y, 1)
+    let mut ata = Matrix3::zeros();
+    let mut atb_x = Vector3::zeros();
+    let mut atb_y = Vector3::zeros();
+
+    for &index in selected {
+        let (x, y) = points_a[index];
+        let row = Vector3::new(x, y, 1.0);
+
+        ata += row * row.transpose();
+        atb_x += row * points_b[index].0;
+        atb_y += row * points_b[index].1;
+    }
+
+    // Reject (nearly) collinear points, relative to the spread of the points
+    let scale = ata.m11 + ata.m22;
+    if ata.determinant().abs() <= f64::EPSILON * scale * scale * selected.len() as f64 {
+        return None;
+    }
+
+    let inverse = ata.try_inverse()?;
+    let (x, y) = (inverse * atb_x, inverse * atb_y);
+
+    let matrix = Matrix3::new(x[0], x[1], x[2], y[0], y[1], y[2], 0.0, 0.0, 1.0);
+    if matrix.iter().all(|value| value.is_finite()) {
+        Some(matrix)
+    } else {
+        None
+    }
+}
+
+fn squared_error(matrix: &Matrix3<f64>, a: (f64, f64), b: (f64, f64)) -> f64 {
+    let x = matrix.m11 * a.0 + matrix.m12 * a.1 + matrix.m13;
+    let y = matrix.m21 * a.0 + matrix.m22 * a.1 + matrix.m23;
+
+    (x - b.0).powi(2) + (y - b.1).powi(2)
+}
+
+/// Indices of the pairs of points which agree with the matrix to within `threshold`
+fn inliers(
+    matrix: &Matrix3<f64>,
+    points_a: &[(f64, f64)],
+    points_b: &[(f64, f64)],
+    threshold: f64,
+) -> Vec<usize> {
+    let threshold = threshold * threshold;
+
+    (0..points_a.len())
+        .filter(|&index| squared_error(matrix, points_a[index], points_b[index]) <= threshold)
+        .collect()
+}
+
+/// Small, seedable pseudorandom number generator for sampling the pairs of points
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must not be zero
+        XorShift(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Three distinct indices below `n`
+    fn sample(&mut self, n: usize) -> [usize; SAMPLE_SIZE] {
+        let mut sample = [0; SAMPLE_SIZE];
+        let mut count = 0;
+
+        while count < SAMPLE_SIZE {
+            let index = (self.next() % n as u64) as usize;
+            if !sample[..count].contains(&index) {
+                sample[count] = index;
+                count += 1;
+            }
+        }
+
+        sample
+    }
+}
+
+/// Returns the (2D) affine parameters used by ITK and elastix (a11 a12 a21 a22 tx ty) of the matrix
+fn itk_parameters(matrix: &Matrix3<f64>) -> [f64; 6] {
+    [
+        matrix.m11, matrix.m12, matrix.m21, matrix.m22, matrix.m13, matrix.m23,
+    ]
+}
+
+fn from_itk_parameters_and_centre(
+    parameters: &[f64],
+    centre: &[f64],
+) -> Result<AffineTransform<f64>> {
+    if parameters.len() != 6 {
+        return Err(MCDError::InvalidTransformParameters {
+            reason: format!(
+                "expected 6 affine parameters, but found {}",
+                parameters.len()
+            ),
+        });
+    }
+    let (cx, cy) = match centre {
+        [] => (0.0, 0.0),
+        [cx, cy] => (*cx, *cy),
+        _ => {
+            return Err(MCDError::InvalidTransformParameters {
+                reason: format!("expected a 2D centre of rotation, but found {:?}", centre),
+            })
+        }
+    };
+
+    // T(x) = A(x - c) + t + c
+    let (a11, a12, a21, a22, tx, ty) = (
+        parameters[0],
+        parameters[1],
+        parameters[2],
+        parameters[3],
+        parameters[4],
+        parameters[5],
+    );
+    let from_slide = Matrix3::new(
+        a11,
+        a12,
+        tx + cx - a11 * cx - a12 * cy,
+        a21,
+        a22,
+        ty + cy - a21 * cx - a22 * cy,
+        0.0,
+        0.0,
+        1.0,
+    );
+    let to_slide =
+        from_slide
+            .try_inverse()
+            .ok_or_else(|| MCDError::InvalidTransformParameters {
+                reason: "the affine matrix can't be inverted".into(),
+            })?;
+
+    Ok(AffineTransform::from_matrix(to_slide))
+}
+
+/// Parse a list of numbers following a key, reporting which key couldn't be parsed
+fn parse_numbers<'a, I: Iterator<Item = &'a str>>(key: &str, values: I) -> Result<Vec<f64>> {
+    values
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| MCDError::InvalidTransformParameters {
+                    reason: format!("'{}' in {} is not a number", value, key),
+                })
+        })
+        .collect()
+}
+
+/// Write the transform as an ITK transform file (`.tfm`) describing an `AffineTransform_double_2_2`. As ITK
+/// transforms map points from the fixed to the moving image, this is the mapping from the slide to the external
+/// space. Returns None if the transform can't be inverted.
+pub fn to_itk_parameters(transform: &AffineTransform<f64>) -> Option<String> {
+    let parameters = itk_parameters(transform.from_slide_matrix()?);
+
+    Some(format!(
+        "#Insight Transform File V1.0\n#Transform 0\nTransform: AffineTransform_double_2_2\nParameters: {}\nFixedParameters: 0 0\n",
+        join(&parameters)
+    ))
+}
+
+/// Parse an ITK transform file (`.tfm`) containing a 2D affine transform (`AffineTransform_double_2_2`), as
+/// written by [`to_itk_parameters`], into the transform towards the slide
+pub fn from_itk_parameters(text: &str) -> Result<AffineTransform<f64>> {
+    let mut transform_type = None;
+    let mut parameters = None;
+    let mut centre = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if let Some((key, value)) = line.split_once(':') {
+            match key.trim() {
+                "Transform" => transform_type = Some(value.trim()),
+                "Parameters" => parameters = Some(parse_numbers(key, value.split_whitespace())?),
+                "FixedParameters" => centre = parse_numbers(key, value.split_whitespace())?,
+                _ => {}
+            }
+        }
+    }
+
+    match transform_type {
+        Some("AffineTransform_double_2_2") | Some("AffineTransform_float_2_2") => {}
+        Some(other) => {
+            return Err(MCDError::InvalidTransformParameters {
+                reason: format!("unsupported transform type {}", other),
+            })
+        }
+        None => {
+            return Err(MCDError::InvalidTransformParameters {
+                reason: "no Transform is specified".into(),
+            })
+        }
+    }
+
+    let parameters = parameters.ok_or_else(|| MCDError::InvalidTransformParameters {
+        reason: "no Parameters are specified".into(),
+    })?;
+
+    from_itk_parameters_and_centre(&parameters, &centre)
+}
+
+/// Write the transform as an elastix transform parameter file (`TransformParameters.0.txt`) describing a 2D
+/// `AffineTransform`. As for ITK, this is the mapping from the slide (fixed image) to the external space (moving
+/// image). Returns None if the transform can't be inverted.
+pub fn to_elastix_parameters(transform: &AffineTransform<f64>) -> Option<String> {
+    let parameters = itk_parameters(transform.from_slide_matrix()?);
+
+    Some(format!(
+        "(Transform \"AffineTransform\")\n(NumberOfParameters 6)\n(TransformParameters {})\n(InitialTransformParametersFileName \"NoInitialTransform\")\n(FixedImageDimension 2)\n(MovingImageDimension 2)\n(CenterOfRotationPoint 0 0)\n",
+        join(&parameters)
+    ))
+}
+
+/// Parse an elastix transform parameter file containing a 2D `AffineTransform`, as written by
+/// [`to_elastix_parameters`], into the transform towards the slide. Initial transforms are not followed, so the
+/// file must not refer to one.
+pub fn from_elastix_parameters(text: &str) -> Result<AffineTransform<f64>> {
+    let mut transform_type = None;
+    let mut parameters = None;
+    let mut centre = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        // Each parameter is of the form (Name value ...), with strings in quotes and comments following //
+        let line = line.split("//").next().unwrap_or_default().trim();
+        let line = match line
+            .strip_prefix('(')
+            .and_then(|line| line.strip_suffix(')'))
+        {
+            Some(line) => line,
+            None => continue,
+        };
+
+        let mut values = line.split_whitespace();
+        let key = values.next().unwrap_or_default();
+        let mut values = values.map(|value| value.trim_matches('"'));
+
+        match key {
+            "Transform" => transform_type = values.next(),
+            "TransformParameters" => parameters = Some(parse_numbers(key, values)?),
+            "CenterOfRotationPoint" => centre = parse_numbers(key, values)?,
+            "InitialTransformParametersFileName" => match values.next() {
+                None | Some("NoInitialTransform") => {}
+                Some(file) => {
+                    return Err(MCDError::InvalidTransformParameters {
+                        reason: format!("initial transforms ({}) are not supported", file),
+                    })
+                }
+            },
+            _ => {}
+        }
+    }
+
+    match transform_type {
+        Some("AffineTransform") => {}
+        Some(other) => {
+            return Err(MCDError::InvalidTransformParameters {
+                reason: format!("unsupported transform type {}", other),
+            })
+        }
+        None => {
+            return Err(MCDError::InvalidTransformParameters {
+                reason: "no Transform is specified".into(),
+            })
+        }
+    }
+
+    let parameters = parameters.ok_or_else(|| MCDError::InvalidTransformParameters {
+        reason: "no TransformParameters are specified".into(),
+    })?;
+
+    from_itk_parameters_and_centre(&parameters, &centre)
+}
+
+fn join(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ransac_rejects_outliers_and_round_trips() -> Result<()> {
+        // Rotated by 90°, scaled by 2 and translated
+        let map = |(x, y): (f64, f64)| (100.0 - 2.0 * y, 50.0 + 2.0 * x);
+        let mut points_a: Vec<_> = (0..20)
+            .map(|index| ((index % 5) as f64 * 7.0, (index / 5) as f64 * 11.0))
+            .collect();
+        let mut points_b: Vec<_> = points_a.iter().copied().map(map).collect();
+        // Misplaced landmarks
+        points_a.push((3.0, 3.0));
+        points_b.push((500.0, -20.0));
+        points_a.push((10.0, 30.0));
+        points_b.push((0.0, 0.0));
+
+        let registration = estimate_affine_ransac(&points_a, &points_b)?;
+        assert_eq!(registration.inliers(), (0..20).collect::<Vec<_>>());
+        assert_eq!(registration.outliers(points_a.len()), [20, 21]);
+        assert!(registration.rms_error() < 1e-9);
+
+        let transform = registration.transform();
+        let point = transform
+            .transform_to_slide(1.0, 2.0)
+            .expect("Transform should be invertible");
+        assert!((point.x - 96.0).abs() < 1e-9 && (point.y - 52.0).abs() < 1e-9);
+
+        for parsed in [
+            from_itk_parameters(
+                &to_itk_parameters(transform).expect("Transform should be invertible"),
+            )?,
+            from_elastix_parameters(
+                &to_elastix_parameters(transform).expect("Transform should be invertible"),
+            )?,
+        ] {
+            let point = parsed
+                .transform_to_slide(1.0, 2.0)
+                .expect("Transform should be invertible");
+            assert!((point.x - 96.0).abs() < 1e-9 && (point.y - 52.0).abs() < 1e-9);
+        }
+
+        // Elastix parameters with a centre of rotation, mapping slide (fixed) points to external points
+        let parsed = from_elastix_parameters(
+            "(Transform \"AffineTransform\")\n(TransformParameters 1 0 0 1 5 -5) // shift\n(CenterOfRotationPoint 10 10)",
+        )?;
+        let point = parsed
+            .transform_from_slide(0.0, 0.0)
+            .expect("Transform should be invertible");
+        assert!((point.x - 5.0).abs() < 1e-9 && (point.y + 5.0).abs() < 1e-9);
+
+        let collinear: Vec<_> = (0..5).map(|index| (index as f64, index as f64)).collect();
+        assert!(matches!(
+            estimate_affine_ransac(&collinear, &collinear),
+            Err(MCDError::InvalidRegistration { .. })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use image::Rgba;
+
+use crate::error::MCDError;
+
+/// Viridis colormap, sampled at 9 evenly spaced points between 0 and 1
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [71, 44, 122],
+    [59, 82, 139],
+    [44, 114, 142],
+    [33, 145, 140],
+    [40, 174, 128],
+    [94, 201, 98],
+    [173, 220, 48],
+    [253, 231, 37],
+];
+
+/// Magma colormap, sampled at 9 evenly spaced points between 0 and 1
+const MAGMA: [[u8; 3]; 9] = [
+    [0, 0, 4],
+    [28, 16, 68],
+    [79, 18, 123],
+    [129, 37, 129],
+    [181, 54, 122],
+    [229, 80, 100],
+    [251, 135, 97],
+    [254, 194, 135],
+    [252, 253, 191],
+];
+
+/// Colormap used to convert intensities into colours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    /// Perceptually uniform colormap from dark blue to yellow
+    Viridis,
+    /// Perceptually uniform colormap from black through purple to light yellow
+    Magma,
+    /// Black to white
+    Grayscale,
+    /// Black to the specified (red, green, blue) colour
+    SingleHue([u8; 3]),
+}
+
+impl Colormap {
+    /// Returns the colour for the `value`, which is clamped to the range 0-1
+    pub fn color(&self, value: f32) -> Rgba<u8> {
+        let value = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, 1.0)
+        };
+
+        let [r, g, b] = match self {
+            Colormap::Viridis => interpolate(&VIRIDIS, value),
+            Colormap::Magma => interpolate(&MAGMA, value),
+            Colormap::Grayscale => {
+                let intensity = (value * 255.0).round() as u8;
+                [intensity, intensity, intensity]
+            }
+            Colormap::SingleHue(color) => color.map(|c| (c as f32 * value).round() as u8),
+        };
+
+        Rgba([r, g, b, 255])
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = MCDError;
+
+    /// Parse a colormap from its name (`viridis`, `magma`, `grayscale`, `red`, `green`, `blue`) or a single hue
+    /// specified as a hex colour (e.g. `#00ff00`)
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let colormap = match name.to_ascii_lowercase().as_str() {
+            "viridis" => Colormap::Viridis,
+            "magma" => Colormap::Magma,
+            "grayscale" | "greyscale" | "gray" | "grey" => Colormap::Grayscale,
+            "red" => Colormap::SingleHue([255, 0, 0]),
+            "green" => Colormap::SingleHue([0, 255, 0]),
+            "blue" => Colormap::SingleHue([0, 0, 255]),
+            hex if hex.len() == 7 && hex.starts_with('#') => {
+                let component = |index: usize| {
+                    u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| {
+                        MCDError::InvalidColormap {
+                            name: name.to_string(),
+                        }
+                    })
+                };
+
+                Colormap::SingleHue([component(1)?, component(3)?, component(5)?])
+            }
+            _ => {
+                return Err(MCDError::InvalidColormap {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        Ok(colormap)
+    }
+}
+
+fn interpolate(table: &[[u8; 3]], value: f32) -> [u8; 3] {
+    let position = value * (table.len() - 1) as f32;
+    let lower = (position.floor() as usize).min(table.len() - 1);
+    let upper = (lower + 1).min(table.len() - 1);
+    let fraction = position - lower as f32;
+
+    let mut color = [0; 3];
+    for (index, component) in color.iter_mut().enumerate() {
+        let start = table[lower][index] as f32;
+        let end = table[upper][index] as f32;
+
+        *component = (start + (end - start) * fraction).round() as u8;
+    }
+
+    color
+}
@@ -0,0 +1,369 @@
+use std::io::{Read, Seek};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    coords::SlidePoint, error::Result, Acquisition, BoundingBox, ChannelIdentifier, ChannelImage,
+    OnSlide, Region, MCD,
+};
+
+/// Describes how the colour of a layer is combined with the layers beneath it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Add the colours together (clamped to the maximum intensity)
+    #[default]
+    Additive,
+    /// Take the maximum of each colour component
+    Max,
+    /// Screen blending, `1 - (1 - a)(1 - b)`, which brightens without saturating as quickly as additive
+    Screen,
+}
+
+impl BlendMode {
+    fn blend(&self, below: f32, above: f32) -> f32 {
+        match self {
+            BlendMode::Additive => (below + above).min(1.0),
+            BlendMode::Max => below.max(above),
+            BlendMode::Screen => 1.0 - (1.0 - below) * (1.0 - above),
+        }
+    }
+}
+
+/// A single channel within a `Composite`, displayed using the specified colour
+#[derive(Debug, Clone)]
+pub struct CompositeLayer {
+    identifier: ChannelIdentifier,
+    color: [u8; 3],
+    range: Option<(f32, f32)>,
+    blend_mode: BlendMode,
+}
+
+impl CompositeLayer {
+    /// Create a layer displaying the channel in the specified (red, green, blue) colour
+    pub fn new<C: Into<ChannelIdentifier>>(identifier: C, color: [u8; 3]) -> Self {
+        CompositeLayer {
+            identifier: identifier.into(),
+            color,
+            range: None,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    /// Set the intensity range (min, max) mapped to the colour. If not set, the intensity range of the data is used.
+    pub fn range(mut self, min_value: f32, max_value: f32) -> Self {
+        self.range = Some((min_value, max_value));
+        self
+    }
+
+    /// Set how this layer is combined with the layers beneath it
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Returns the identifier of the channel displayed in this layer
+    pub fn identifier(&self) -> &ChannelIdentifier {
+        &self.identifier
+    }
+
+    /// Returns the colour used to display this layer
+    pub fn color(&self) -> [u8; 3] {
+        self.color
+    }
+
+    fn contribution(&self, intensity: f32, range: (f32, f32)) -> [f32; 3] {
+        let scale = range.1 - range.0;
+        let value = if scale > 0.0 && !intensity.is_nan() {
+            ((intensity - range.0) / scale).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        self.color.map(|c| c as f32 / 255.0 * value)
+    }
+}
+
+/// Builder for multi-channel composite images, where each channel is assigned a colour.
+///
+/// ```no_run
+/// use imc_rs::{ChannelIdentifier, MCD};
+/// use imc_rs::render::{BlendMode, Composite, CompositeLayer};
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let acquisition = mcd.find_acquisition("ROI_001").unwrap();
+///
+/// let image = Composite::new()
+///     .channel(ChannelIdentifier::label("DNA1"), [0, 0, 255])
+///     .layer(
+///         CompositeLayer::new(ChannelIdentifier::label("CD3"), [0, 255, 0])
+///             .range(0.0, 20.0)
+///             .blend_mode(BlendMode::Screen),
+///     )
+///     .render(acquisition, None)
+///     .unwrap();
+///
+/// image.save("composite.png").unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Composite {
+    layers: Vec<CompositeLayer>,
+}
+
+impl Composite {
+    /// Create an empty composite
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a channel displayed in the specified (red, green, blue) colour, using the default range and blend mode
+    pub fn channel<C: Into<ChannelIdentifier>>(self, identifier: C, color: [u8; 3]) -> Self {
+        self.layer(CompositeLayer::new(identifier, color))
+    }
+
+    /// Add a layer to the composite. Layers are blended in the order they are added.
+    pub fn layer(mut self, layer: CompositeLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Returns the layers in the composite
+    pub fn layers(&self) -> &[CompositeLayer] {
+        &self.layers
+    }
+
+    /// Render the composite from channel images, which must be supplied in the same order as the layers.
+    /// Pixels which were not acquired, or are missing (NaN) in all images, are fully transparent.
+    pub fn render_images(&self, images: &[ChannelImage]) -> RgbaImage {
+        let (width, height, valid_pixels) = images
+            .first()
+            .map(|image| (image.width(), image.height(), image.valid_pixels))
+            .unwrap_or((0, 0, 0));
+
+        let ranges: Vec<_> = self
+            .layers
+            .iter()
+            .zip(images)
+            .map(|(layer, image)| layer.range.unwrap_or(image.range))
+            .collect();
+
+        let mut output = RgbaImage::new(width, height);
+
+        for (index, pixel) in output.pixels_mut().enumerate() {
+            if index >= valid_pixels
+                || images
+                    .iter()
+                    .all(|image| image.data.get(index).is_none_or(|value| value.is_nan()))
+            {
+                continue;
+            }
+
+            *pixel = self.blend(images.iter().zip(&ranges).enumerate().filter_map(
+                |(layer, (image, &range))| {
+                    image
+                        .data
+                        .get(index)
+                        .map(|&intensity| (layer, intensity, range))
+                },
+            ));
+        }
+
+        output
+    }
+
+    /// Render the composite for the acquisition, optionally restricted to a region (in pixels)
+    pub fn render<R: Read + Seek>(
+        &self,
+        acquisition: &Acquisition<R>,
+        region: Option<Region>,
+    ) -> Result<RgbaImage> {
+        let identifiers: Vec<_> = self.layers.iter().map(|layer| &layer.identifier).collect();
+        let images = acquisition.channel_images(&identifiers, region)?;
+
+        Ok(self.render_images(&images))
+    }
+
+    /// Render the composite for a region of the slide (in μm), combining all acquisitions which overlap the
+    /// region. The output image is `width` pixels wide, with the height determined by the aspect ratio of the
+    /// region. The top left pixel corresponds to (`region.min_x`, `region.min_y`).
+    ///
+    /// Channels which are not present in an acquisition are ignored for that acquisition. If no ranges are set,
+    /// the maximum intensity range across all overlapping acquisitions is used for each layer. Where acquisitions
+    /// overlap, the last acquisition is shown.
+    pub fn render_slide_region<R: Read + Seek>(
+        &self,
+        mcd: &MCD<R>,
+        region: &BoundingBox<f64>,
+        width: u32,
+    ) -> Result<RgbaImage> {
+        let height = if region.width > 0.0 {
+            (width as f64 * region.height / region.width).round() as u32
+        } else {
+            0
+        };
+        let scale = region.width / width.max(1) as f64;
+
+        let mut acquisition_images = Vec::new();
+        for acquisition in mcd.acquisitions_in(region) {
+            let images: Vec<_> = self
+                .layers
+                .iter()
+                .map(|layer| match acquisition.channel(&layer.identifier) {
+                    Some(channel) => acquisition.channel_image(channel, None).map(Some),
+                    None => Ok(None),
+                })
+                .collect::<Result<_>>()?;
+
+            acquisition_images.push((acquisition, images));
+        }
+
+        let ranges: Vec<_> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                layer.range.unwrap_or_else(|| {
+                    acquisition_images
+                        .iter()
+                        .filter_map(|(_, images)| images[index].as_ref())
+                        .fold((f32::MAX, f32::MIN), |range, image| {
+                            (range.0.min(image.range.0), range.1.max(image.range.1))
+                        })
+                })
+            })
+            .collect();
+
+        let mut output = RgbaImage::new(width, height);
+
+        for (acquisition, images) in &acquisition_images {
+            let acquisition_width = acquisition.width().max(0) as f64;
+            let acquisition_height = acquisition.height().max(0) as f64;
+
+            let bounding_box = acquisition.slide_bounding_box();
+            let min_x = (((bounding_box.min_x - region.min_x) / scale)
+                .floor()
+                .max(0.0) as u32)
+                .min(width);
+            let max_x = (((bounding_box.max_x() - region.min_x) / scale)
+                .ceil()
+                .max(0.0) as u32)
+                .min(width);
+            let min_y = (((bounding_box.min_y - region.min_y) / scale)
+                .floor()
+                .max(0.0) as u32)
+                .min(height);
+            let max_y = (((bounding_box.max_y() - region.min_y) / scale)
+                .ceil()
+                .max(0.0) as u32)
+                .min(height);
+
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let slide_x = region.min_x + (x as f64 + 0.5) * scale;
+                    let slide_y = region.min_y + (y as f64 + 0.5) * scale;
+
+                    let point = match SlidePoint::new(slide_x, slide_y).to_acquisition(acquisition)
+                    {
+                        Some(point) => point,
+                        None => continue,
+                    };
+
+                    let pixel_x = point.x.floor();
+                    let pixel_y = point.y.floor();
+
+                    if pixel_x < 0.0
+                        || pixel_y < 0.0
+                        || pixel_x >= acquisition_width
+                        || pixel_y >= acquisition_height
+                    {
+                        continue;
+                    }
+
+                    let index = (pixel_y * acquisition_width + pixel_x) as usize;
+                    if index >= acquisition.num_spectra() {
+                        continue;
+                    }
+
+                    let pixel = self.blend(images.iter().zip(&ranges).enumerate().filter_map(
+                        |(layer, (image, &range))| {
+                            image
+                                .as_ref()
+                                .and_then(|image| image.data.get(index))
+                                .map(|&intensity| (layer, intensity, range))
+                        },
+                    ));
+
+                    output.put_pixel(x, y, pixel);
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Blend the (layer index, intensity, range) values for a single pixel into an opaque colour
+    fn blend<I: Iterator<Item = (usize, f32, (f32, f32))>>(&self, values: I) -> Rgba<u8> {
+        let mut color = [0.0f32; 3];
+
+        for (layer, intensity, range) in values {
+            let layer = &self.layers[layer];
+            let contribution = layer.contribution(intensity, range);
+
+            for (component, value) in color.iter_mut().zip(contribution) {
+                *component = layer.blend_mode.blend(*component, value);
+            }
+        }
+
+        let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+        Rgba([r, g, b, 255])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_image(data: Vec<f32>) -> ChannelImage {
+        ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 1,
+            },
+            acquisition_id: 1,
+            name: String::new(),
+            label: String::new(),
+            range: (0.0, 10.0),
+            valid_pixels: data.len(),
+            data,
+        }
+    }
+
+    #[test]
+    fn render_images() {
+        let composite = Composite::new()
+            .channel(ChannelIdentifier::label("DNA1"), [0, 0, 255])
+            .layer(
+                CompositeLayer::new(ChannelIdentifier::label("CD3"), [255, 0, 255]).range(0.0, 5.0),
+            );
+
+        let image = composite.render_images(&[
+            channel_image(vec![10.0, 5.0]),
+            channel_image(vec![5.0, 0.0]),
+        ]);
+
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 255, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 128, 255]));
+
+        let composite = Composite::new()
+            .channel(ChannelIdentifier::label("DNA1"), [0, 0, 255])
+            .layer(
+                CompositeLayer::new(ChannelIdentifier::label("CD3"), [0, 0, 255])
+                    .blend_mode(BlendMode::Max),
+            );
+        let image = composite.render_images(&[channel_image(vec![5.0]), channel_image(vec![2.0])]);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 128, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,194 @@
+use image::{Rgba, RgbaImage};
+
+/// Width (in pixels, before scaling) of a glyph in the built-in font
+pub(crate) const GLYPH_WIDTH: u32 = 5;
+/// Height (in pixels, before scaling) of a glyph in the built-in font
+pub(crate) const GLYPH_HEIGHT: u32 = 7;
+
+/// Returns the 5x7 bitmap for the character (one byte per row, lowest 5 bits used). Lower case letters are
+/// drawn using the upper case glyph and unsupported characters are drawn as a space.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        _ => [0x00; 7],
+    }
+}
+
+/// Set the pixel if it lies within the image
+pub(crate) fn put_pixel(image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+/// Fill the rectangle with its top left corner at (x, y), clipped to the image
+pub(crate) fn fill_rectangle(
+    image: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+    color: Rgba<u8>,
+) {
+    for y in y..(y + height as i64) {
+        for x in x..(x + width as i64) {
+            put_pixel(image, x, y, color);
+        }
+    }
+}
+
+/// Draw a line between the two points (Bresenham), `thickness` pixels wide
+pub(crate) fn draw_line(
+    image: &mut RgbaImage,
+    start: (f64, f64),
+    end: (f64, f64),
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    let (mut x, mut y) = (start.0.round() as i64, start.1.round() as i64);
+    let (end_x, end_y) = (end.0.round() as i64, end.1.round() as i64);
+
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let offset = thickness as i64 / 2;
+
+    loop {
+        fill_rectangle(image, x - offset, y - offset, thickness, thickness, color);
+
+        if x == end_x && y == end_y {
+            break;
+        }
+
+        let error2 = 2 * error;
+        if error2 >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if error2 <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Draw the outline of the closed polygon described by `points`
+pub(crate) fn draw_polygon(
+    image: &mut RgbaImage,
+    points: &[(f64, f64)],
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    for (index, &start) in points.iter().enumerate() {
+        let end = points[(index + 1) % points.len()];
+
+        draw_line(image, start, end, thickness, color);
+    }
+}
+
+/// Returns the (width, height) in pixels of `text` drawn with the specified scale
+pub(crate) fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let num_chars = text.chars().count() as u32;
+
+    (
+        (num_chars * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale,
+        GLYPH_HEIGHT * scale,
+    )
+}
+
+/// Draw `text` using the built-in 5x7 font, with the top left corner at (x, y). Each font pixel is drawn as a
+/// `scale` x `scale` block.
+pub(crate) fn draw_text(
+    image: &mut RgbaImage,
+    text: &str,
+    x: i64,
+    y: i64,
+    scale: u32,
+    color: Rgba<u8>,
+) {
+    let scale = scale.max(1);
+
+    for (index, c) in text.chars().enumerate() {
+        let glyph_x = x + (index as i64) * ((GLYPH_WIDTH + 1) * scale) as i64;
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    fill_rectangle(
+                        image,
+                        glyph_x + (column * scale) as i64,
+                        y + (row as u32 * scale) as i64,
+                        scale,
+                        scale,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_text_and_lines() {
+        let mut image = RgbaImage::new(20, 10);
+        let white = Rgba([255, 255, 255, 255]);
+
+        assert_eq!(text_size("1 mm", 1), (23, 7));
+
+        draw_text(&mut image, "1", 0, 0, 1, white);
+        // Top of the '1' glyph is in the middle column
+        assert_eq!(image.get_pixel(2, 0), &white);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+
+        // Lines are clipped to the image
+        draw_line(&mut image, (-5.0, 9.0), (25.0, 9.0), 1, white);
+        assert!((0..20).all(|x| image.get_pixel(x, 9) == &white));
+    }
+}
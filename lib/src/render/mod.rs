@@ -0,0 +1,158 @@
+use image::{DynamicImage, ImageBuffer, Luma, Rgba, RgbaImage};
+
+use crate::ChannelImage;
+
+mod colormap;
+mod composite;
+pub(crate) mod draw;
+
+pub use colormap::Colormap;
+pub use composite::{BlendMode, Composite, CompositeLayer};
+
+impl ChannelImage {
+    /// Render the channel image as an RGBA image using the specified colormap. Intensities are linearly scaled
+    /// between the (min, max) of `range`, or the intensity range of the image if `None`. Pixels which were not
+    /// acquired (e.g. the acquisition was aborted) or are otherwise missing (NaN) are fully transparent.
+    pub fn to_rgba(&self, colormap: Colormap, range: Option<(f32, f32)>) -> RgbaImage {
+        let (min_value, max_value) = range.unwrap_or(self.range);
+        let scale = max_value - min_value;
+
+        let mut image = RgbaImage::new(self.width(), self.height());
+
+        for (pixel, output) in image.pixels_mut().enumerate() {
+            *output = match self.data.get(pixel) {
+                Some(&intensity) if pixel < self.valid_pixels && !intensity.is_nan() => {
+                    let value = if scale > 0.0 {
+                        (intensity - min_value) / scale
+                    } else {
+                        0.0
+                    };
+
+                    colormap.color(value)
+                }
+                _ => Rgba([0, 0, 0, 0]),
+            };
+        }
+
+        image
+    }
+
+    /// Returns the intensities as a single channel floating point image (e.g. to resize with
+    /// `image::imageops`), without scaling. Missing pixels, including those which were not acquired, are NaN.
+    pub fn to_luma32f(&self) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+        let num_pixels = (self.width() * self.height()) as usize;
+        let mut data = self.data.clone();
+        data.resize(num_pixels, f32::NAN);
+
+        ImageBuffer::from_raw(self.width(), self.height(), data)
+            .expect("The intensities are resized to width * height")
+    }
+
+    /// Returns the intensities as a 16-bit greyscale image, linearly scaled so that the (min, max) of `range`,
+    /// or the intensity range of the image if `None`, map to 0 and 65535. Intensities outside the range are
+    /// clamped, and missing pixels (as in [`ChannelImage::to_rgba`]) are 0.
+    pub fn to_luma16(&self, range: Option<(f32, f32)>) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        let (min_value, max_value) = range.unwrap_or(self.range);
+        let scale = max_value - min_value;
+
+        let mut image = ImageBuffer::new(self.width(), self.height());
+
+        for (pixel, output) in image.pixels_mut().enumerate() {
+            *output = match self.data.get(pixel) {
+                Some(&intensity) if pixel < self.valid_pixels && !intensity.is_nan() => {
+                    let value = if scale > 0.0 {
+                        ((intensity - min_value) / scale).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+
+                    Luma([(value * u16::MAX as f32).round() as u16])
+                }
+                _ => Luma([0]),
+            };
+        }
+
+        image
+    }
+
+    /// Returns the intensities as a 16-bit greyscale `DynamicImage` (see [`ChannelImage::to_luma16`]), e.g. to
+    /// save in any format supported by the `image` crate
+    pub fn to_dynamic_image(&self, range: Option<(f32, f32)>) -> DynamicImage {
+        DynamicImage::ImageLuma16(self.to_luma16(range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Region;
+
+    #[test]
+    fn to_rgba() {
+        let image = ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir191".to_string(),
+            label: "DNA1".to_string(),
+            range: (0.0, 10.0),
+            valid_pixels: 3,
+            data: vec![0.0, 5.0, 10.0],
+        };
+
+        let rgba = image.to_rgba(Colormap::Grayscale, None);
+        assert_eq!(rgba.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(rgba.get_pixel(1, 0), &Rgba([128, 128, 128, 255]));
+        assert_eq!(rgba.get_pixel(0, 1), &Rgba([255, 255, 255, 255]));
+        assert_eq!(rgba.get_pixel(1, 1), &Rgba([0, 0, 0, 0]));
+
+        let rgba = image.to_rgba(Colormap::SingleHue([0, 255, 0]), Some((0.0, 5.0)));
+        assert_eq!(rgba.get_pixel(0, 1), &Rgba([0, 255, 0, 255]));
+
+        assert_eq!(Colormap::Viridis.color(0.0), Rgba([68, 1, 84, 255]));
+        assert_eq!(
+            "#00FF80".parse::<Colormap>().expect("hex colormap"),
+            Colormap::SingleHue([0, 255, 128])
+        );
+        assert!("rainbow".parse::<Colormap>().is_err());
+        assert_eq!(Colormap::Magma.color(1.0), Rgba([252, 253, 191, 255]));
+    }
+
+    #[test]
+    fn to_luma() {
+        let image = ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 2,
+            },
+            acquisition_id: 1,
+            name: "Ir191".to_string(),
+            label: "DNA1".to_string(),
+            range: (0.0, 10.0),
+            valid_pixels: 3,
+            data: vec![0.0, 5.0, 10.0],
+        };
+
+        let luma = image.to_luma32f();
+        assert_eq!(luma.get_pixel(1, 0), &Luma([5.0]));
+        assert!(luma.get_pixel(1, 1)[0].is_nan());
+
+        let luma = image.to_luma16(Some((0.0, 5.0)));
+        assert_eq!(luma.get_pixel(0, 0), &Luma([0]));
+        assert_eq!(luma.get_pixel(1, 0), &Luma([65535]));
+        assert_eq!(luma.get_pixel(0, 1), &Luma([65535]));
+        assert_eq!(luma.get_pixel(1, 1), &Luma([0]));
+
+        let dynamic = image.to_dynamic_image(None);
+        assert_eq!(
+            dynamic.as_luma16().map(|luma| luma.get_pixel(1, 0)[0]),
+            Some(32768)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    thread,
+    time::Duration,
+};
+
+/// Policy describing how a `RetryingReader` retries reads which fail with a transient error. After each failed
+/// attempt the reader waits for the backoff, which is multiplied by `backoff_multiplier` (up to `max_backoff`)
+/// before the next attempt.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    initial_backoff: Duration,
+    backoff_multiplier: u32,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 2,
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create the default policy: up to 5 retries, waiting 100 ms before the first and doubling each time (up
+    /// to 5 s)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of times a failed read is retried before the error is returned
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time waited before the first retry
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Set the factor by which the time waited increases after each retry
+    pub fn backoff_multiplier(mut self, multiplier: u32) -> Self {
+        self.backoff_multiplier = multiplier;
+        self
+    }
+
+    /// Set the maximum time waited before a retry
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Returns the time to wait before the specified retry (starting from 0)
+    fn backoff(&self, retry: usize) -> Duration {
+        let mut backoff = self.initial_backoff;
+
+        for _ in 0..retry {
+            backoff = backoff.saturating_mul(self.backoff_multiplier);
+
+            if backoff >= self.max_backoff {
+                break;
+            }
+        }
+
+        backoff.min(self.max_backoff)
+    }
+}
+
+/// Returns whether the error is likely to be transient (e.g. a dropped connection to a network filesystem), so
+/// the read is worth retrying
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::WouldBlock
+    )
+}
+
+/// Reader which retries reads and seeks that fail with a transient error (`Interrupted`, `TimedOut` or
+/// `WouldBlock`), according to a `RetryPolicy`. Before each retry the reader seeks back to the position of the
+/// failed read, as a partially completed read may have moved the underlying reader.
+///
+/// This is useful for long analyses of data on network filesystems (SMB or NFS mounts), which would otherwise
+/// fail on a single transient read error.
+///
+/// ```no_run
+/// use imc_rs::{RetryPolicy, RetryingReader, MCD};
+///
+/// let file = std::fs::File::open("/mnt/share/20200612_FLU_1923.mcd").unwrap();
+/// let reader = RetryingReader::new(file, RetryPolicy::new().max_retries(10));
+///
+/// let mcd = MCD::parse(reader).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RetryingReader<R> {
+    inner: R,
+    policy: RetryPolicy,
+    position: Option<u64>,
+}
+
+impl<R> RetryingReader<R> {
+    /// Wrap the reader, retrying according to the policy
+    pub fn new(inner: R, policy: RetryPolicy) -> Self {
+        RetryingReader {
+            inner,
+            policy,
+            position: None,
+        }
+    }
+
+    /// Returns the retry policy
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Returns a reference to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Seek> RetryingReader<R> {
+    /// Call `operation` until it succeeds, fails with an error which isn't transient or runs out of retries
+    fn retry<T, F>(&mut self, mut operation: F) -> io::Result<T>
+    where
+        F: FnMut(&mut R) -> io::Result<T>,
+    {
+        let mut retry = 0;
+
+        loop {
+            let error = match operation(&mut self.inner) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            if !is_transient(&error) || retry >= self.policy.max_retries {
+                // The position of the underlying reader is no longer known
+                self.position = None;
+                return Err(error);
+            }
+
+            thread::sleep(self.policy.backoff(retry));
+            retry += 1;
+
+            if let Some(position) = self.position {
+                // Failing to restore the position is also retried
+                if let Err(error) = self.inner.seek(SeekFrom::Start(position)) {
+                    if !is_transient(&error) {
+                        self.position = None;
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    fn position(&mut self) -> io::Result<u64> {
+        match self.position {
+            Some(position) => Ok(position),
+            None => {
+                let position = self.retry(|inner| inner.stream_position())?;
+                self.position = Some(position);
+
+                Ok(position)
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for RetryingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.position()?;
+        let read = self.retry(|inner| inner.read(buf))?;
+        self.position = Some(position + read as u64);
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for RetryingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Relative seeks are made absolute, so that they can be safely repeated
+        let pos = match pos {
+            SeekFrom::Current(offset) => {
+                let position = self.position()?;
+
+                match position.checked_add_signed(offset) {
+                    Some(position) => SeekFrom::Start(position),
+                    None => pos,
+                }
+            }
+            pos => pos,
+        };
+
+        let position = self.retry(|inner| inner.seek(pos))?;
+        self.position = Some(position);
+
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Reader which fails every other read with the specified error, after reading a single byte
+    struct Flaky {
+        inner: Cursor<Vec<u8>>,
+        kind: ErrorKind,
+        fail: bool,
+    }
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.fail = !self.fail;
+
+            if self.fail {
+                // Move the position as a partially completed read would
+                self.inner.read_exact(&mut [0])?;
+                Err(io::Error::new(self.kind, "flaky"))
+            } else {
+                self.inner.read(buf)
+            }
+        }
+    }
+
+    impl Seek for Flaky {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn flaky(kind: ErrorKind) -> RetryingReader<Flaky> {
+        RetryingReader::new(
+            Flaky {
+                inner: Cursor::new(vec![1, 2, 3, 4]),
+                kind,
+                fail: false,
+            },
+            RetryPolicy::new().initial_backoff(Duration::ZERO),
+        )
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let mut reader = flaky(ErrorKind::TimedOut);
+        let mut data = [0; 4];
+
+        assert!(reader.read_exact(&mut data).is_ok());
+        assert_eq!(data, [1, 2, 3, 4]);
+
+        let mut reader = flaky(ErrorKind::PermissionDenied);
+        assert!(reader.read_exact(&mut data).is_err());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(3));
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{coords::SlidePoint, mcd::ROIPoint, BoundingBox};
+
+/// A vertex of the region of interest (ROI) drawn to define an acquisition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoiPoint {
+    id: u16,
+    order_number: i16,
+    slide_x_pos_um: f64,
+    slide_y_pos_um: f64,
+    panorama_pixel_x_pos: i32,
+    panorama_pixel_y_pos: i32,
+}
+
+impl RoiPoint {
+    /// Returns the ID of the point
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Returns the position of the point in the outline of the ROI
+    pub fn order_number(&self) -> i16 {
+        self.order_number
+    }
+
+    /// Returns the position of the point on the slide (in μm)
+    pub fn slide_position(&self) -> SlidePoint {
+        SlidePoint::new(self.slide_x_pos_um, self.slide_y_pos_um)
+    }
+
+    /// Returns the position of the point in the panorama image (in pixels)
+    pub fn panorama_position(&self) -> (i32, i32) {
+        (self.panorama_pixel_x_pos, self.panorama_pixel_y_pos)
+    }
+}
+
+impl From<ROIPoint> for RoiPoint {
+    fn from(point: ROIPoint) -> Self {
+        RoiPoint {
+            id: point.id.unwrap_or_default(),
+            order_number: point.order_number.unwrap_or_default(),
+            slide_x_pos_um: point.slide_x_pos_um.unwrap_or_default(),
+            slide_y_pos_um: point.slide_y_pos_um.unwrap_or_default(),
+            panorama_pixel_x_pos: point.panorama_pixel_x_pos.unwrap_or_default(),
+            panorama_pixel_y_pos: point.panorama_pixel_y_pos.unwrap_or_default(),
+        }
+    }
+}
+
+/// Shape of the region of the slide (in μm) covered by an acquisition
+#[derive(Debug, Clone)]
+pub enum RoiShape {
+    /// An axis-aligned rectangle
+    Rectangle(BoundingBox<f64>),
+    /// A polygon (e.g. a free-hand ROI), described by its vertices
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl RoiShape {
+    /// Returns the shape outlined by the ROI points (ordered by order number): a rectangle if they are the
+    /// corners of an axis-aligned rectangle, otherwise a polygon. Returns None for fewer than 3 points.
+    pub(crate) fn from_points(points: &[RoiPoint]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+
+        let vertices: Vec<_> = points
+            .iter()
+            .map(|point| (point.slide_x_pos_um, point.slide_y_pos_um))
+            .collect();
+
+        let mut xs: Vec<f64> = vertices.iter().map(|vertex| vertex.0).collect();
+        let mut ys: Vec<f64> = vertices.iter().map(|vertex| vertex.1).collect();
+        xs.sort_by(f64::total_cmp);
+        xs.dedup();
+        ys.sort_by(f64::total_cmp);
+        ys.dedup();
+
+        if vertices.len() == 4 && xs.len() == 2 && ys.len() == 2 {
+            Some(RoiShape::Rectangle(BoundingBox {
+                min_x: xs[0],
+                min_y: ys[0],
+                width: xs[1] - xs[0],
+                height: ys[1] - ys[0],
+            }))
+        } else {
+            Some(RoiShape::Polygon(vertices))
+        }
+    }
+
+    /// Returns the bounding box of the shape
+    pub fn bounding_box(&self) -> BoundingBox<f64> {
+        match self {
+            RoiShape::Rectangle(rectangle) => rectangle.clone(),
+            RoiShape::Polygon(vertices) => {
+                let (min_x, min_y, max_x, max_y) = vertices.iter().fold(
+                    (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                    |(min_x, min_y, max_x, max_y), &(x, y)| {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    },
+                );
+
+                BoundingBox {
+                    min_x,
+                    min_y,
+                    width: max_x - min_x,
+                    height: max_y - min_y,
+                }
+            }
+        }
+    }
+
+    /// Tests whether the point (slide coordinates) lies within the shape, using the even-odd rule for polygons
+    pub fn contains(&self, point: SlidePoint) -> bool {
+        match self {
+            RoiShape::Rectangle(rectangle) => {
+                point.x >= rectangle.min_x
+                    && point.x <= rectangle.max_x()
+                    && point.y >= rectangle.min_y
+                    && point.y <= rectangle.max_y()
+            }
+            RoiShape::Polygon(vertices) => polygon_contains(vertices, point.x, point.y),
+        }
+    }
+
+    /// Tests whether the shape is (at least partially) contained within the region (slide coordinates)
+    pub fn intersects(&self, region: &BoundingBox<f64>) -> bool {
+        let bounding_box = self.bounding_box();
+
+        let overlaps = bounding_box.min_x < region.max_x()
+            && bounding_box.max_x() > region.min_x
+            && bounding_box.max_y() > region.min_y
+            && bounding_box.min_y < region.max_y();
+
+        let vertices = match self {
+            RoiShape::Rectangle(_) => return overlaps,
+            RoiShape::Polygon(_) if !overlaps => return false,
+            RoiShape::Polygon(vertices) => vertices,
+        };
+
+        let inside_region = |&(x, y): &(f64, f64)| {
+            x > region.min_x && x < region.max_x() && y > region.min_y && y < region.max_y()
+        };
+
+        let corners = [
+            (region.min_x, region.min_y),
+            (region.max_x(), region.min_y),
+            (region.max_x(), region.max_y()),
+            (region.min_x, region.max_y()),
+        ];
+
+        // Either a vertex lies within the region, the region lies within the polygon or the edges cross
+        vertices.iter().any(inside_region)
+            || polygon_contains(
+                vertices,
+                (region.min_x + region.max_x()) / 2.0,
+                (region.min_y + region.max_y()) / 2.0,
+            )
+            || edges(vertices)
+                .any(|edge| edges(&corners).any(|region_edge| segments_cross(edge, region_edge)))
+    }
+}
+
+/// Returns the edges of the polygon, including the edge closing the polygon
+fn edges(vertices: &[(f64, f64)]) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+}
+
+/// Tests whether the segments cross (touching at an end point does not count)
+fn segments_cross(a: ((f64, f64), (f64, f64)), b: ((f64, f64), (f64, f64))) -> bool {
+    let orientation = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        ((q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0)).signum()
+    };
+
+    let (d1, d2) = (orientation(b.0, b.1, a.0), orientation(b.0, b.1, a.1));
+    let (d3, d4) = (orientation(a.0, a.1, b.0), orientation(a.0, a.1, b.1));
+
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+/// Tests whether the point lies within the polygon, using the even-odd rule
+pub(crate) fn polygon_contains(vertices: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match vertices.last() {
+        Some(previous) => previous,
+        None => return false,
+    };
+
+    for vertex in vertices {
+        if (vertex.1 > y) != (previous.1 > y)
+            && x < (previous.0 - vertex.0) * (y - vertex.1) / (previous.1 - vertex.1) + vertex.0
+        {
+            inside = !inside;
+        }
+
+        previous = vertex;
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{error::Result, AcquisitionSpec, MCDWriter, PanoramaSpec, SlideSpec, MCD};
+
+    #[test]
+    fn polygon_contains_centres() {
+        // Triangle covering the lower left half of a 4x4 square
+        let triangle = [(0.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+
+        assert!(polygon_contains(&triangle, 0.5, 3.5));
+        assert!(polygon_contains(&triangle, 1.5, 2.5));
+        assert!(!polygon_contains(&triangle, 2.5, 1.5));
+        assert!(!polygon_contains(&triangle, 5.0, 5.0));
+        assert!(!polygon_contains(&[], 0.5, 0.5));
+    }
+
+    #[test]
+    fn free_hand_roi_containment() -> Result<()> {
+        // A triangular ROI covering the upper left half of the acquisition's bounding box
+        let triangle = [(0.0, 100.0), (100.0, 100.0), (0.0, 0.0)];
+
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(SlideSpec::new(1))?;
+        writer.add_panorama(PanoramaSpec::new(1, 1).bounds(0.0, 0.0, 200.0, 200.0))?;
+        writer.add_acquisition(
+            AcquisitionSpec::new(1, 1, 100, 100)
+                .position(0.0, 100.0)
+                .roi_points(&triangle)
+                .channel("X", "X"),
+            &vec![0.0; 100 * 100],
+        )?;
+
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+        let acquisition = mcd.acquisitions()[0];
+        assert_eq!(acquisition.roi_points().len(), 3);
+
+        let shape = acquisition.roi_shape();
+        assert!(matches!(shape, RoiShape::Polygon(_)));
+        assert!(shape.contains(SlidePoint::new(10.0, 90.0)));
+        assert!(!shape.contains(SlidePoint::new(90.0, 10.0)));
+
+        let region = |min_x, min_y| BoundingBox {
+            min_x,
+            min_y,
+            width: 20.0,
+            height: 20.0,
+        };
+        assert_eq!(mcd.acquisitions_in(&region(10.0, 70.0)).len(), 1);
+        // Within the bounding box, but outside of the triangle
+        assert!(mcd.acquisitions_in(&region(70.0, 10.0)).is_empty());
+
+        Ok(())
+    }
+}
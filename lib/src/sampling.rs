@@ -0,0 +1,213 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    coords::{AcquisitionPixel, SlidePoint},
+    error::Result,
+    Acquisition, AcquisitionRef, ChannelIdentifier, ChannelImage, Region, MCD,
+};
+
+/// Intensity of a channel sampled from an acquisition at a position on the slide
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlideSample {
+    acquisition: AcquisitionRef,
+    pixel: AcquisitionPixel,
+    intensity: f32,
+}
+
+impl SlideSample {
+    /// Returns the acquisition the intensity was sampled from
+    pub fn acquisition(&self) -> AcquisitionRef {
+        self.acquisition
+    }
+
+    /// Returns the position within the acquisition (in pixels) corresponding to the position on the slide
+    pub fn pixel(&self) -> AcquisitionPixel {
+        self.pixel
+    }
+
+    /// Returns the intensity, bilinearly interpolated between the centres of the neighbouring pixels
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+}
+
+impl<R: Read + Seek> MCD<R> {
+    /// Returns the intensity of the channel at the position on the slide (in μm), sampled from each acquisition
+    /// whose ROI contains the position (ordered by slide, panorama and acquisition ID). The intensity is
+    /// bilinearly interpolated between the centres of the neighbouring pixels, ignoring pixels which were not
+    /// acquired, so the result is empty if no acquired pixel lies under the position.
+    ///
+    /// Use `intensities_at_slide_positions` to sample many positions, which reads each acquisition only once.
+    pub fn intensity_at_slide_position<C: Into<ChannelIdentifier>>(
+        &self,
+        x_um: f64,
+        y_um: f64,
+        channel: C,
+    ) -> Result<Vec<SlideSample>> {
+        Ok(self
+            .intensities_at_slide_positions(&[SlidePoint::new(x_um, y_um)], channel)?
+            .pop()
+            .unwrap_or_default())
+    }
+
+    /// Returns the intensities of the channel at each of the positions on the slide (in μm), as described by
+    /// `intensity_at_slide_position`. For each acquisition, only the pixels around the positions it contains are
+    /// read (in a single read).
+    pub fn intensities_at_slide_positions<C: Into<ChannelIdentifier>>(
+        &self,
+        points: &[SlidePoint],
+        channel: C,
+    ) -> Result<Vec<Vec<SlideSample>>> {
+        let channel = channel.into();
+        let mut samples = vec![Vec::new(); points.len()];
+
+        for acquisition in self.acquisitions() {
+            let shape = acquisition.roi_shape();
+            let bounds = shape.bounding_box();
+
+            let contained: Vec<_> = points
+                .iter()
+                .enumerate()
+                .filter(|(_, point)| {
+                    point.x >= bounds.min_x
+                        && point.x <= bounds.max_x()
+                        && point.y >= bounds.min_y
+                        && point.y <= bounds.max_y()
+                        && shape.contains(**point)
+                })
+                .filter_map(|(index, point)| Some((index, point.to_acquisition(acquisition)?)))
+                .collect();
+
+            let region = match sampled_region(acquisition, &contained) {
+                Some(region) => region,
+                None => continue,
+            };
+            let image = acquisition.channel_image(channel.clone(), Some(region))?;
+
+            for (index, pixel) in contained {
+                if let Some(intensity) = interpolate(&image, &region, pixel) {
+                    samples[index].push(SlideSample {
+                        acquisition: acquisition.reference(),
+                        pixel,
+                        intensity,
+                    });
+                }
+            }
+        }
+
+        Ok(samples)
+    }
+}
+
+/// Returns the region of the acquisition covering the neighbouring pixels of each of the positions, or None if
+/// there are no positions within the acquisition
+fn sampled_region<R>(
+    acquisition: &Acquisition<R>,
+    pixels: &[(usize, AcquisitionPixel)],
+) -> Option<Region> {
+    let (width, height) = (acquisition.width(), acquisition.height());
+    if pixels.is_empty() || width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let (min_x, min_y, max_x, max_y) = pixels.iter().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(min_x, min_y, max_x, max_y), (_, pixel)| {
+            (
+                min_x.min(pixel.x),
+                min_y.min(pixel.y),
+                max_x.max(pixel.x),
+                max_y.max(pixel.y),
+            )
+        },
+    );
+
+    let column = |x: f64| ((x - 0.5).floor().max(0.0) as i32).min(width - 1) as u32;
+    let row = |y: f64| ((y - 0.5).floor().max(0.0) as i32).min(height - 1) as u32;
+
+    let (min_column, min_row) = (column(min_x), row(min_y));
+    let max_column = (column(max_x) + 1).min(width as u32 - 1);
+    let max_row = (row(max_y) + 1).min(height as u32 - 1);
+
+    Some(Region {
+        x: min_column,
+        y: min_row,
+        width: max_column - min_column + 1,
+        height: max_row - min_row + 1,
+    })
+}
+
+/// Bilinearly interpolate the intensity at the pixel position (within the acquisition) between the centres of
+/// the neighbouring pixels of the image, which covers `region` of the acquisition. Pixels which were not acquired
+/// are ignored, returning None if none of the neighbouring pixels were acquired.
+fn interpolate(image: &ChannelImage, region: &Region, pixel: AcquisitionPixel) -> Option<f32> {
+    // Positions relative to the pixel centres, clamped so that positions beyond the outermost centres take the
+    // value of the outermost pixels
+    let x = (pixel.x - 0.5 - region.x as f64).clamp(0.0, (region.width - 1) as f64);
+    let y = (pixel.y - 0.5 - region.y as f64).clamp(0.0, (region.height - 1) as f64);
+
+    let (column, row) = (x.floor() as u32, y.floor() as u32);
+    let (fx, fy) = (x - column as f64, y - row as f64);
+
+    let mut total = 0.0;
+    let mut total_weight = 0.0;
+
+    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let weight = if dx == 0 { 1.0 - fx } else { fx } * if dy == 0 { 1.0 - fy } else { fy };
+        if weight == 0.0 || column + dx >= region.width || row + dy >= region.height {
+            continue;
+        }
+
+        let index = ((row + dy) * region.width + column + dx) as usize;
+        if let Some(&intensity) = image.intensities().get(index) {
+            if !intensity.is_nan() {
+                total += weight * intensity as f64;
+                total_weight += weight;
+            }
+        }
+    }
+
+    if total_weight > 0.0 {
+        Some((total / total_weight) as f32)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn interpolated_slide_samples() -> Result<()> {
+        // 2x2 pixels (1 μm each) with the first row stored at the top (y = 11 μm)
+        let mcd = MCD::from_bytes(synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 2, 2)
+                .position(10.0, 11.0)
+                .channel("Ir191", "DNA1"),
+            [0.0, 1.0, 2.0, 3.0],
+        )])?)?;
+
+        // Centre of the acquisition, equidistant from each pixel centre
+        let samples =
+            mcd.intensity_at_slide_position(11.0, 10.0, ChannelIdentifier::label("DNA1"))?;
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].acquisition().id(), 1);
+        assert_eq!(samples[0].intensity(), 1.5);
+
+        let samples = mcd.intensities_at_slide_positions(
+            &[
+                SlidePoint::new(10.5, 10.5),
+                SlidePoint::new(11.0, 10.5),
+                SlidePoint::new(50.0, 50.0),
+            ],
+            ChannelIdentifier::label("DNA1"),
+        )?;
+        assert_eq!(samples[0][0].intensity(), 0.0);
+        assert_eq!(samples[1][0].intensity(), 0.5);
+        assert!(samples[2].is_empty());
+
+        Ok(())
+    }
+}
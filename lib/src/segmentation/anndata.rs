@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use hdf5::{types::VarLenUnicode, File, Group, Location};
+use ndarray::Array2;
+
+use super::CellSummary;
+use crate::{coords::AcquisitionPixel, error::Result, Acquisition, ChannelIdentifier};
+
+/// Write the cells of one or more acquisitions (e.g. from `CellMask::summarise`) as an AnnData (.h5ad) file,
+/// which can be read directly by scanpy (`scanpy.read_h5ad`) and squidpy.
+///
+/// * `X` holds the mean intensity of each channel (columns) within each cell (rows), excluding the X, Y and Z
+///   position channels
+/// * `obs` holds the acquisition ID, cell ID (within the mask) and area (in pixels) of each cell
+/// * `var` is indexed by channel label (or name, if the channel has no label), and holds the channel name
+/// * `obsm/spatial` holds the position of the centroid of each cell on the slide (in μm)
+///
+/// The channels are those of the first acquisition, matched by name in the others (with NaN where an acquisition
+/// lacks a channel).
+///
+/// ```no_run
+/// use imc_rs::{segmentation::{self, CellMask}, MCD};
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let acquisition = mcd.find_acquisition("ROI_001").unwrap();
+/// let cells = CellMask::from_path("../test/20200612_FLU_1923-01_full_mask.tiff")
+///     .unwrap()
+///     .summarise(acquisition)
+///     .unwrap();
+///
+/// segmentation::to_anndata("cells.h5ad", &[(acquisition, cells.as_slice())]).unwrap();
+/// ```
+pub fn to_anndata<R, P: AsRef<Path>>(
+    path: P,
+    acquisitions: &[(&Acquisition<R>, &[CellSummary])],
+) -> Result<()> {
+    let channels: Vec<_> = acquisitions
+        .first()
+        .map(|(acquisition, _)| acquisition.marker_channels())
+        .unwrap_or_default();
+    let num_cells = acquisitions.iter().map(|(_, cells)| cells.len()).sum();
+
+    let mut intensities = Vec::with_capacity(num_cells * channels.len());
+    let mut spatial = Vec::with_capacity(num_cells * 2);
+    let mut index = Vec::with_capacity(num_cells);
+    let mut acquisition_ids = Vec::with_capacity(num_cells);
+    let mut cell_ids = Vec::with_capacity(num_cells);
+    let mut areas = Vec::with_capacity(num_cells);
+
+    for (acquisition, cells) in acquisitions {
+        // Position of each of the channels (of the first acquisition) within this acquisition
+        let positions: Vec<_> = channels
+            .iter()
+            .map(|channel| {
+                let identifier = ChannelIdentifier::name(channel.name());
+                acquisition
+                    .channels()
+                    .iter()
+                    .position(|other| other.is(&identifier))
+            })
+            .collect();
+
+        for cell in cells.iter() {
+            intensities.extend(positions.iter().map(|position| {
+                position
+                    .and_then(|position| cell.markers().get(position))
+                    .map_or(f32::NAN, |summary| summary.mean())
+            }));
+
+            // The centroid is in pixel indices, so offset to the centre of the pixel
+            let (x, y) = cell.centroid();
+            let point = AcquisitionPixel::new(x + 0.5, y + 0.5).to_slide(acquisition);
+            spatial.extend(point.map_or([f64::NAN; 2], |point| [point.x, point.y]));
+
+            index.push(format!("{}_{}", acquisition.id(), cell.id()));
+            acquisition_ids.push(acquisition.id());
+            cell_ids.push(cell.id());
+            areas.push(cell.num_pixels() as u64);
+        }
+    }
+
+    let file = File::create(path)?;
+    encoding(&file, "anndata", "0.1.0")?;
+
+    let x = Array2::from_shape_vec((num_cells, channels.len()), intensities)
+        .map_err(|error| hdf5::Error::from(error.to_string()))?;
+    let dataset = file.new_dataset_builder().with_data(&x).create("X")?;
+    encoding(&dataset, "array", "0.2.0")?;
+
+    let obs = dataframe(&file, "obs", &index, &["acquisition_id", "cell_id", "area"])?;
+    array(&obs, "acquisition_id", &acquisition_ids)?;
+    array(&obs, "cell_id", &cell_ids)?;
+    array(&obs, "area", &areas)?;
+
+    let var_index: Vec<_> = channels
+        .iter()
+        .map(|channel| {
+            if channel.label().is_empty() {
+                channel.name().to_string()
+            } else {
+                channel.label().to_string()
+            }
+        })
+        .collect();
+    let var = dataframe(&file, "var", &var_index, &["channel_name"])?;
+    let names: Vec<_> = channels.iter().map(|channel| channel.name()).collect();
+    string_array(&var, "channel_name", &names)?;
+
+    let obsm = dict(&file, "obsm")?;
+    let spatial = Array2::from_shape_vec((num_cells, 2), spatial)
+        .map_err(|error| hdf5::Error::from(error.to_string()))?;
+    let dataset = obsm
+        .new_dataset_builder()
+        .with_data(&spatial)
+        .create("spatial")?;
+    encoding(&dataset, "array", "0.2.0")?;
+
+    for name in ["varm", "obsp", "varp", "layers", "uns"] {
+        dict(&file, name)?;
+    }
+
+    Ok(())
+}
+
+fn unicode(value: &str) -> hdf5::Result<VarLenUnicode> {
+    value
+        .parse()
+        .map_err(|error: hdf5::types::StringError| hdf5::Error::from(error.to_string()))
+}
+
+fn string_attr(location: &Location, name: &str, value: &str) -> hdf5::Result<()> {
+    location
+        .new_attr::<VarLenUnicode>()
+        .create(name)?
+        .write_scalar(&unicode(value)?)
+}
+
+/// Set the AnnData encoding (type and version) of the group or dataset
+fn encoding(location: &Location, encoding_type: &str, version: &str) -> hdf5::Result<()> {
+    string_attr(location, "encoding-type", encoding_type)?;
+    string_attr(location, "encoding-version", version)
+}
+
+fn dict(parent: &Group, name: &str) -> hdf5::Result<Group> {
+    let group = parent.create_group(name)?;
+    encoding(&group, "dict", "0.1.0")?;
+
+    Ok(group)
+}
+
+/// Create a dataframe with the index (and the names of the columns, which must then be added)
+fn dataframe<S: AsRef<str>>(
+    parent: &Group,
+    name: &str,
+    index: &[S],
+    columns: &[&str],
+) -> hdf5::Result<Group> {
+    let group = parent.create_group(name)?;
+    encoding(&group, "dataframe", "0.2.0")?;
+    string_attr(&group, "_index", "_index")?;
+
+    let columns = columns
+        .iter()
+        .map(|column| unicode(column))
+        .collect::<hdf5::Result<Vec<_>>>()?;
+    group
+        .new_attr_builder()
+        .with_data(columns.as_slice())
+        .create("column-order")?;
+
+    string_array(&group, "_index", index)?;
+
+    Ok(group)
+}
+
+fn string_array<S: AsRef<str>>(group: &Group, name: &str, values: &[S]) -> hdf5::Result<()> {
+    let values = values
+        .iter()
+        .map(|value| unicode(value.as_ref()))
+        .collect::<hdf5::Result<Vec<_>>>()?;
+
+    let dataset = group
+        .new_dataset_builder()
+        .with_data(values.as_slice())
+        .create(name)?;
+    encoding(&dataset, "string-array", "0.2.0")
+}
+
+fn array<T: hdf5::H5Type>(group: &Group, name: &str, values: &[T]) -> hdf5::Result<()> {
+    let dataset = group.new_dataset_builder().with_data(values).create(name)?;
+    encoding(&dataset, "array", "0.2.0")
+}
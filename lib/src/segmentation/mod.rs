@@ -0,0 +1,311 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+};
+
+use tiff::decoder::{Decoder, DecodingResult};
+
+use crate::{
+    error::{MCDError, Result},
+    Acquisition, AcquisitionChannel, ChannelIdentifier,
+};
+
+#[cfg(feature = "hdf5")]
+mod anndata;
+
+#[cfg(feature = "hdf5")]
+pub use anndata::to_anndata;
+
+/// A cell segmentation mask, where each pixel holds the ID of the cell it belongs to (0 being background).
+/// Masks are typically generated by segmentation software (e.g. CellProfiler, Mesmer) from the exported
+/// images, so they have the same dimensions as the acquisition.
+#[derive(Debug, Clone)]
+pub struct CellMask {
+    width: u32,
+    height: u32,
+    labels: Vec<u32>,
+}
+
+impl CellMask {
+    /// Create a mask from the cell ID of each pixel (in row-major order)
+    pub fn new(width: u32, height: u32, labels: Vec<u32>) -> Result<Self> {
+        if labels.len() != width as usize * height as usize {
+            return Err(MCDError::InvalidBufferSize {
+                expected: width as usize * height as usize,
+                actual: labels.len(),
+            });
+        }
+
+        Ok(CellMask {
+            width,
+            height,
+            labels,
+        })
+    }
+
+    /// Read a mask from a single channel TIFF with integer pixels (8, 16, 32 or 64 bit)
+    pub fn from_tiff<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut decoder = Decoder::new(reader)?;
+        let (width, height) = decoder.dimensions()?;
+
+        let labels = match decoder.read_image()? {
+            DecodingResult::U8(data) => data.into_iter().map(u32::from).collect(),
+            DecodingResult::U16(data) => data.into_iter().map(u32::from).collect(),
+            DecodingResult::U32(data) => data,
+            DecodingResult::U64(data) => data
+                .into_iter()
+                .map(|label| label.try_into())
+                .collect::<std::result::Result<Vec<u32>, _>>()?,
+            _ => {
+                return Err(MCDError::InvalidMask {
+                    reason: "pixels must be unsigned integers".to_string(),
+                })
+            }
+        };
+
+        Self::new(width, height, labels)
+    }
+
+    /// Read a mask from a TIFF file (see `CellMask::from_tiff()`)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_tiff(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the width of the mask in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the mask in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the ID of the cell at (x, y), 0 being background (or outside of the mask)
+    pub fn label(&self, x: u32, y: u32) -> u32 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+
+        self.labels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Returns the ID of the cell at each pixel (in row-major order), 0 being background
+    pub fn labels(&self) -> &[u32] {
+        &self.labels
+    }
+
+    /// Returns the IDs of all cells in the mask, in ascending order
+    pub fn cell_ids(&self) -> Vec<u32> {
+        self.cells().into_keys().collect()
+    }
+
+    /// Returns the indices (in row-major order) of the pixels of each cell
+    fn cells(&self) -> BTreeMap<u32, Vec<usize>> {
+        let mut cells: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+
+        for (index, &label) in self.labels.iter().enumerate() {
+            if label > 0 {
+                cells.entry(label).or_default().push(index);
+            }
+        }
+
+        cells
+    }
+
+    /// Summarise the intensities of every channel of the acquisition within each cell. The mask must have the
+    /// same dimensions as the acquisition. Missing (not acquired) pixels are excluded from the summaries.
+    pub fn summarise<R: Read + Seek>(
+        &self,
+        acquisition: &Acquisition<R>,
+    ) -> Result<Vec<CellSummary>> {
+        if self.width as i32 != acquisition.width() || self.height as i32 != acquisition.height() {
+            return Err(MCDError::InvalidMask {
+                reason: format!(
+                    "mask is {}x{} pixels but acquisition {} is {}x{}",
+                    self.width,
+                    self.height,
+                    acquisition.id(),
+                    acquisition.width(),
+                    acquisition.height()
+                ),
+            });
+        }
+
+        let identifiers: Vec<_> = acquisition
+            .channels()
+            .iter()
+            .map(ChannelIdentifier::from)
+            .collect();
+        let images = acquisition.channel_images(&identifiers, None)?;
+
+        let summaries = self
+            .cells()
+            .into_iter()
+            .map(|(id, pixels)| {
+                let (sum_x, sum_y) = pixels.iter().fold((0.0, 0.0), |(x, y), &index| {
+                    (
+                        x + (index % self.width as usize) as f64,
+                        y + (index / self.width as usize) as f64,
+                    )
+                });
+
+                let markers = images
+                    .iter()
+                    .map(|image| {
+                        let mut intensities: Vec<_> = pixels
+                            .iter()
+                            .filter_map(|&index| image.intensities().get(index).copied())
+                            .collect();
+
+                        Summary::from_intensities(&mut intensities)
+                    })
+                    .collect();
+
+                CellSummary {
+                    id,
+                    num_pixels: pixels.len(),
+                    centroid: (sum_x / pixels.len() as f64, sum_y / pixels.len() as f64),
+                    markers,
+                }
+            })
+            .collect();
+
+        Ok(summaries)
+    }
+}
+
+/// Summary statistics of the intensities of a channel within a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    mean: f32,
+    std_dev: f32,
+    range: (f32, f32),
+    median: f32,
+}
+
+impl Summary {
+    /// Summarise the intensities, ignoring missing (NaN) values. All statistics are NaN if no intensities
+    /// remain. The intensities are sorted in place.
+    pub fn from_intensities(intensities: &mut Vec<f32>) -> Self {
+        intensities.retain(|intensity| !intensity.is_nan());
+        intensities.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        if intensities.is_empty() {
+            return Summary {
+                mean: f32::NAN,
+                std_dev: f32::NAN,
+                range: (f32::NAN, f32::NAN),
+                median: f32::NAN,
+            };
+        }
+
+        let count = intensities.len() as f32;
+        let mean = intensities.iter().sum::<f32>() / count;
+        let variance = intensities
+            .iter()
+            .map(|intensity| (intensity - mean).powi(2))
+            .sum::<f32>()
+            / count;
+
+        let mid_point = intensities.len() / 2;
+        let median = if intensities.len().is_multiple_of(2) {
+            (intensities[mid_point - 1] + intensities[mid_point]) * 0.5
+        } else {
+            intensities[mid_point]
+        };
+
+        Summary {
+            mean,
+            std_dev: variance.sqrt(),
+            range: (intensities[0], intensities[intensities.len() - 1]),
+            median,
+        }
+    }
+
+    /// Returns the mean intensity
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    /// Returns the (population) standard deviation of the intensities
+    pub fn std_dev(&self) -> f32 {
+        self.std_dev
+    }
+
+    /// Returns a pair (min, max) of the intensities
+    pub fn range(&self) -> (f32, f32) {
+        self.range
+    }
+
+    /// Returns the median intensity
+    pub fn median(&self) -> f32 {
+        self.median
+    }
+}
+
+/// Measurements of a single cell of a `CellMask` (see `CellMask::summarise()`)
+#[derive(Debug, Clone)]
+pub struct CellSummary {
+    id: u32,
+    num_pixels: usize,
+    centroid: (f64, f64),
+    markers: Vec<Summary>,
+}
+
+impl CellSummary {
+    /// Returns the ID of the cell in the mask
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the area of the cell in pixels
+    pub fn num_pixels(&self) -> usize {
+        self.num_pixels
+    }
+
+    /// Returns the centroid (x, y) of the cell in pixels
+    pub fn centroid(&self) -> (f64, f64) {
+        self.centroid
+    }
+
+    /// Returns the summary of each channel, in the same order as `Acquisition::channels()`
+    pub fn markers(&self) -> &[Summary] {
+        &self.markers
+    }
+
+    /// Returns the summary of the channel matching the identifier, where `channels` are the channels of the
+    /// acquisition the cell was measured in
+    pub fn marker<C: AsRef<ChannelIdentifier>>(
+        &self,
+        channels: &[AcquisitionChannel],
+        identifier: C,
+    ) -> Option<&Summary> {
+        channels
+            .iter()
+            .position(|channel| channel.is(identifier.as_ref()))
+            .and_then(|index| self.markers.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_ignores_missing() {
+        let mut intensities = vec![4.0, f32::NAN, 1.0, 3.0, 2.0];
+        let summary = Summary::from_intensities(&mut intensities);
+
+        assert_eq!(summary.mean(), 2.5);
+        assert_eq!(summary.median(), 2.5);
+        assert_eq!(summary.range(), (1.0, 4.0));
+
+        let mask = CellMask::new(2, 2, vec![0, 2, 2, 1]).expect("valid mask");
+        assert_eq!(mask.cell_ids(), vec![1, 2]);
+        assert_eq!(mask.label(1, 1), 1);
+        assert!(CellMask::new(2, 2, vec![0, 1]).is_err());
+    }
+}
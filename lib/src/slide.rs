@@ -1,8 +1,8 @@
 use core::fmt;
 use std::{
-    collections::HashMap,
-    io::{BufReader, Read, Seek},
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    io::{Read, Seek},
+    sync::Arc,
 };
 
 use image::Pixel;
@@ -10,9 +10,16 @@ use image::{imageops::FilterType, ImageBuffer, ImageFormat, Rgba, RgbaImage};
 
 use crate::{
     channel::ChannelIdentifier,
+    coords::{AcquisitionPixel, OverviewFrame, OverviewPixel, PanoramaPixel, SlidePoint},
+    describe::{Describe, Description, Value},
     error::MCDError,
     mcd::{SlideFiducialMarksXML, SlideProfileXML},
-    OnSlide, OpticalImage, Panorama, Print,
+    reader::ReaderPool,
+    register,
+    render::draw,
+    spatial::SpatialIndex,
+    transform::AffineTransform,
+    Acquisition, BoundingBox, ByteSpan, OnSlide, OpticalImage, Panorama, Print, Tiling,
 };
 
 use crate::mcd::SlideXML;
@@ -20,7 +27,7 @@ use crate::mcd::SlideXML;
 /// Represents a slide (contains multiple panoramas) in the *.mcd format
 #[derive(Debug)]
 pub struct Slide<R> {
-    pub(crate) reader: Option<Arc<Mutex<BufReader<R>>>>,
+    pub(crate) reader: Option<Arc<ReaderPool<R>>>,
 
     id: u16,
     // The newer version of the XSD doesn't have a UID field anymore
@@ -31,8 +38,7 @@ pub struct Slide<R> {
     width_um: f64,
     height_um: f64,
 
-    image_start_offset: i64,
-    image_end_offset: i64,
+    image: ByteSpan,
     image_file: String,
 
     // New terms in version 2 of the XSD are included as optional
@@ -43,8 +49,13 @@ pub struct Slide<R> {
     name: Option<String>,
 
     sw_version: String,
+    extras: BTreeMap<String, String>,
 
     panoramas: HashMap<u16, Panorama<R>>,
+    /// Fiducial marks on the slide, ordered by ID
+    fiducial_marks: Vec<SlideFiducialMarks>,
+    /// Bounding boxes of the acquisitions, keyed by (panorama ID, acquisition ID)
+    spatial_index: SpatialIndex<(u16, u16)>,
 }
 
 impl<R> From<SlideXML> for Slide<R> {
@@ -59,8 +70,7 @@ impl<R> From<SlideXML> for Slide<R> {
             slide_type: slide.slide_type.unwrap(),
             width_um: slide.width_um.unwrap(),
             height_um: slide.height_um.unwrap(),
-            image_start_offset: slide.image_start_offset.unwrap(),
-            image_end_offset: slide.image_end_offset.unwrap(),
+            image: ByteSpan::from_xml(slide.image_start_offset, slide.image_end_offset),
             image_file: slide.image_file.unwrap(),
             sw_version: slide.sw_version.unwrap(),
 
@@ -69,8 +79,135 @@ impl<R> From<SlideXML> for Slide<R> {
             fmark_slide_length: slide.fmark_slide_length,
             fmark_slide_thickness: slide.fmark_slide_thickness,
             name: slide.name,
+            extras: slide.extras,
 
             panoramas: HashMap::new(),
+            fiducial_marks: Vec::new(),
+            spatial_index: SpatialIndex::default(),
+        }
+    }
+}
+
+/// Options controlling which annotations are drawn on a slide overview image (see
+/// [`Slide::create_overview_image_with_options`]). By default no annotations are drawn.
+#[derive(Debug, Clone)]
+pub struct OverviewOptions {
+    acquisition_outlines: bool,
+    roi_labels: bool,
+    panorama_borders: bool,
+    scale_bar: bool,
+
+    acquisition_color: [u8; 3],
+    panorama_color: [u8; 3],
+    text_color: [u8; 3],
+    line_width: u32,
+    text_scale: u32,
+
+    fiducial_transform: Option<AffineTransform<f64>>,
+}
+
+impl Default for OverviewOptions {
+    fn default() -> Self {
+        OverviewOptions {
+            acquisition_outlines: false,
+            roi_labels: false,
+            panorama_borders: false,
+            scale_bar: false,
+
+            acquisition_color: [255, 255, 0],
+            panorama_color: [0, 255, 255],
+            text_color: [255, 255, 255],
+            line_width: 1,
+            text_scale: 1,
+
+            fiducial_transform: None,
+        }
+    }
+}
+
+impl OverviewOptions {
+    /// Create options with no annotations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create options with all annotations enabled
+    pub fn all() -> Self {
+        OverviewOptions {
+            acquisition_outlines: true,
+            roi_labels: true,
+            panorama_borders: true,
+            scale_bar: true,
+            ..Default::default()
+        }
+    }
+
+    /// Draw the outline of each acquisition
+    pub fn acquisition_outlines(mut self, draw: bool) -> Self {
+        self.acquisition_outlines = draw;
+        self
+    }
+
+    /// Label each acquisition with its description
+    pub fn roi_labels(mut self, draw: bool) -> Self {
+        self.roi_labels = draw;
+        self
+    }
+
+    /// Draw the border of each panorama
+    pub fn panorama_borders(mut self, draw: bool) -> Self {
+        self.panorama_borders = draw;
+        self
+    }
+
+    /// Draw a scale bar in the bottom left corner
+    pub fn scale_bar(mut self, draw: bool) -> Self {
+        self.scale_bar = draw;
+        self
+    }
+
+    /// Set the (red, green, blue) colour used for acquisition outlines
+    pub fn acquisition_color(mut self, color: [u8; 3]) -> Self {
+        self.acquisition_color = color;
+        self
+    }
+
+    /// Set the (red, green, blue) colour used for panorama borders
+    pub fn panorama_color(mut self, color: [u8; 3]) -> Self {
+        self.panorama_color = color;
+        self
+    }
+
+    /// Set the (red, green, blue) colour used for labels and the scale bar
+    pub fn text_color(mut self, color: [u8; 3]) -> Self {
+        self.text_color = color;
+        self
+    }
+
+    /// Set the width (in pixels) of lines
+    pub fn line_width(mut self, line_width: u32) -> Self {
+        self.line_width = line_width.max(1);
+        self
+    }
+
+    /// Set the scale of the text (each font pixel is drawn as a `text_scale` x `text_scale` block)
+    pub fn text_scale(mut self, text_scale: u32) -> Self {
+        self.text_scale = text_scale.max(1);
+        self
+    }
+
+    /// Correct the positions of the panoramas and acquisitions with the transform (e.g. from
+    /// [`Slide::fiducial_transform`]) before drawing them, so that they line up with the slide image
+    pub fn fiducial_transform(mut self, transform: AffineTransform<f64>) -> Self {
+        self.fiducial_transform = Some(transform);
+        self
+    }
+
+    /// Returns the transform of a panorama or acquisition followed by the fiducial correction (if any)
+    fn corrected(&self, transform: AffineTransform<f64>) -> AffineTransform<f64> {
+        match &self.fiducial_transform {
+            Some(fiducial_transform) => transform.compose(fiducial_transform).unwrap_or(transform),
+            None => transform,
         }
     }
 }
@@ -85,6 +222,18 @@ impl<R: Read + Seek> Slide<R> {
         &self,
         width: u32,
         channel_to_show: Option<(&ChannelIdentifier, Option<f32>)>,
+    ) -> Result<RgbaImage, MCDError> {
+        self.create_overview_image_with_options(width, channel_to_show, &OverviewOptions::default())
+    }
+
+    /// Create an overview image of the slide scaled to the supplied width (see [`Slide::create_overview_image`]),
+    /// with annotations (acquisition outlines, ROI labels, panorama borders and a scale bar) drawn as specified
+    /// in `options`.
+    pub fn create_overview_image_with_options(
+        &self,
+        width: u32,
+        channel_to_show: Option<(&ChannelIdentifier, Option<f32>)>,
+        options: &OverviewOptions,
     ) -> Result<RgbaImage, MCDError> {
         let slide_image = self.image().dynamic_image().unwrap();
 
@@ -119,6 +268,7 @@ impl<R: Read + Seek> Slide<R> {
         //return Ok(slide_image.to_rgba8());
 
         let scale = self.width_in_um() / width as f64;
+        let frame = OverviewFrame::new(scale, output_image_height);
 
         for panorama in self.panoramas() {
             if panorama.has_image() {
@@ -126,52 +276,52 @@ impl<R: Read + Seek> Slide<R> {
 
                 //let panorama_image = panorama_image.to_rgba8();
 
-                //let bounding_box = panorama.slide_bounding_box();
-                let transform = panorama.to_slide_transform();
-
-                //println!("[Panorama] Bounding box = {:?}", bounding_box);
-                //println!("[Panorama] Transform = {:?}", transform);
-
                 let (width, height) = panorama.dimensions();
-
-                // Transform each coordinate
-                let top_left = transform.transform_to_slide(0.0, 0.0).unwrap();
-                let top_right = transform.transform_to_slide(width as f64, 0.0).unwrap();
-                let bottom_left = transform.transform_to_slide(0.0, height as f64).unwrap();
-                let bottom_right = transform
-                    .transform_to_slide(width as f64, height as f64)
-                    .unwrap();
-
-                let min_x = top_left[0].min(top_right[0].min(bottom_left[0].min(bottom_right[0])));
-                let min_y = top_left[1].min(top_right[1].min(bottom_left[1].min(bottom_right[1])));
-                let max_x = top_left[0].max(top_right[0].max(bottom_left[0].max(bottom_right[0])));
-                let max_y = top_left[1].max(top_right[1].max(bottom_left[1].max(bottom_right[1])));
-
-                let min_x_pixel = (min_x / scale).floor() as u32;
-                let max_x_pixel = (max_x / scale).floor() as u32;
-                let min_y_pixel = (min_y / scale).floor() as u32;
-                let max_y_pixel = (max_y / scale).floor() as u32;
+                let transform = options.corrected(panorama.to_slide_transform());
+
+                // Find the area of the overview covered by the panorama
+                let corners: Vec<_> = [(0, 0), (width, 0), (width, height), (0, height)]
+                    .iter()
+                    .filter_map(|&(x, y)| {
+                        PanoramaPixel::new(x as f64, y as f64).to_slide_with(panorama, &transform)
+                    })
+                    .map(|point| point.to_overview(&frame))
+                    .collect();
+
+                let min_x = corners.iter().map(|c| c.x).fold(f64::MAX, f64::min);
+                let min_y = corners.iter().map(|c| c.y).fold(f64::MAX, f64::min);
+                let max_x = corners.iter().map(|c| c.x).fold(f64::MIN, f64::max);
+                let max_y = corners.iter().map(|c| c.y).fold(f64::MIN, f64::max);
+
+                let min_x_pixel = (min_x.floor().max(0.0) as u32).min(resized_image.width());
+                let max_x_pixel = (max_x.floor().max(0.0) as u32).min(resized_image.width());
+                let min_y_pixel = (min_y.floor().max(0.0) as u32).min(resized_image.height());
+                let max_y_pixel = (max_y.floor().max(0.0) as u32).min(resized_image.height());
 
                 for y in min_y_pixel..max_y_pixel {
                     for x in min_x_pixel..max_x_pixel {
-                        let new_point = transform
-                            .transform_from_slide(x as f64 * scale, y as f64 * scale)
-                            .unwrap();
+                        let point = match OverviewPixel::new(x as f64, y as f64)
+                            .to_slide(&frame)
+                            .to_panorama_with(panorama, &transform)
+                        {
+                            Some(point) => point,
+                            None => continue,
+                        };
 
-                        let pixel_x = new_point[0].round() as i32;
-                        let pixel_y = panorama_image.height() as i32 - new_point[1].round() as i32;
+                        let pixel_x = point.x.round() as i32;
+                        let pixel_y = point.y.round() as i32;
 
                         if pixel_x < 0
                             || pixel_y < 0
-                            || pixel_x >= width as i32
-                            || pixel_y >= height as i32
+                            || pixel_x >= panorama_image.width() as i32
+                            || pixel_y >= panorama_image.height() as i32
                         {
                             continue;
                         }
 
                         let pixel = *panorama_image.get_pixel(pixel_x as u32, pixel_y as u32);
 
-                        resized_image.put_pixel(x, output_image_height - y, pixel);
+                        resized_image.put_pixel(x, y, pixel);
                     }
                 }
 
@@ -196,31 +346,31 @@ impl<R: Read + Seek> Slide<R> {
                     //println!("[Acquisition] Transform = {:?}", transform);
 
                     //let bounding_box = acquisition.slide_bounding_box();
-                    let transform = acquisition.to_slide_transform();
                     let data = acquisition.channel_image(identifier, None)?;
+                    let transform = options.corrected(acquisition.to_slide_transform());
 
                     let max_value = match max_value {
                         Some(value) => value,
                         None => data.range.1,
                     };
 
-                    let mut index = 0;
-
                     let mut acq_image: ImageBuffer<Rgba<u8>, Vec<u8>> =
                         ImageBuffer::new(data.width(), data.height());
 
                     for y in 0..data.height() {
-                        if index >= data.valid_pixels {
-                            break;
-                        }
-
                         for x in 0..data.width() {
+                            let index = (y * data.width() + x) as usize;
                             if index >= data.valid_pixels {
                                 break;
                             }
 
-                            let new_point =
-                                transform.transform_to_slide(x as f64, y as f64).unwrap();
+                            let overview_point = match AcquisitionPixel::new(x as f64, y as f64)
+                                .to_slide_with(acquisition, &transform)
+                                .map(|point| point.to_overview(&frame))
+                            {
+                                Some(point) => point,
+                                None => continue,
+                            };
 
                             let g = ((data.data[index] / max_value) * 255.0) as u8;
                             let g = g as f64 / 255.0;
@@ -232,11 +382,18 @@ impl<R: Read + Seek> Slide<R> {
 
                             //let pixel = Rgba::from_channels(0, g, 0, g);
 
+                            let (overview_x, overview_y) =
+                                (overview_point.x.round(), overview_point.y.round());
+                            if overview_x < 0.0
+                                || overview_y < 0.0
+                                || overview_x >= resized_image.width() as f64
+                                || overview_y >= resized_image.height() as f64
+                            {
+                                continue;
+                            }
+
                             let current_pixel = resized_image
-                                .get_pixel_mut(
-                                    (new_point[0] / scale).round() as u32,
-                                    ((new_point[1]) / scale).round() as u32,
-                                )
+                                .get_pixel_mut(overview_x as u32, overview_y as u32)
                                 .channels_mut();
 
                             let r = (current_pixel[0] as f64 / 255.0) * (1.0 - g);
@@ -246,8 +403,6 @@ impl<R: Read + Seek> Slide<R> {
                             current_pixel[0] = (r * 255.0) as u8;
                             current_pixel[1] = (g * 255.0) as u8;
                             current_pixel[2] = (b * 255.0) as u8;
-
-                            index += 1;
                         }
                     }
                 }
@@ -260,8 +415,125 @@ impl<R: Read + Seek> Slide<R> {
             }
         }
 
+        self.draw_annotations(&mut resized_image, &frame, options);
+
         Ok(resized_image)
     }
+
+    fn draw_annotations(
+        &self,
+        image: &mut RgbaImage,
+        frame: &OverviewFrame,
+        options: &OverviewOptions,
+    ) {
+        let text_color = Rgba([
+            options.text_color[0],
+            options.text_color[1],
+            options.text_color[2],
+            255,
+        ]);
+
+        for panorama in self.panoramas() {
+            if options.panorama_borders && panorama.has_image() {
+                let (width, height) = panorama.dimensions();
+                let transform = options.corrected(panorama.to_slide_transform());
+
+                let corners: Vec<_> = [(0, 0), (width, 0), (width, height), (0, height)]
+                    .iter()
+                    .filter_map(|&(x, y)| {
+                        PanoramaPixel::new(x as f64, y as f64).to_slide_with(panorama, &transform)
+                    })
+                    .map(|point| point.to_overview(frame))
+                    .map(|point| (point.x, point.y))
+                    .collect();
+
+                let [r, g, b] = options.panorama_color;
+                draw::draw_polygon(image, &corners, options.line_width, Rgba([r, g, b, 255]));
+            }
+
+            for acquisition in panorama.acquisitions() {
+                let (width, height) = (acquisition.width(), acquisition.height());
+                let transform = options.corrected(acquisition.to_slide_transform());
+
+                let corners: Vec<_> = [(0, 0), (width, 0), (width, height), (0, height)]
+                    .iter()
+                    .filter_map(|&(x, y)| {
+                        AcquisitionPixel::new(x as f64, y as f64)
+                            .to_slide_with(acquisition, &transform)
+                    })
+                    .map(|point| point.to_overview(frame))
+                    .map(|point| (point.x, point.y))
+                    .collect();
+
+                if corners.is_empty() {
+                    continue;
+                }
+
+                if options.acquisition_outlines {
+                    let [r, g, b] = options.acquisition_color;
+                    draw::draw_polygon(image, &corners, options.line_width, Rgba([r, g, b, 255]));
+                }
+
+                if options.roi_labels {
+                    let min_x = corners.iter().map(|c| c.0).fold(f64::MAX, f64::min);
+                    let min_y = corners.iter().map(|c| c.1).fold(f64::MAX, f64::min);
+                    let (_, text_height) =
+                        draw::text_size(acquisition.description(), options.text_scale);
+
+                    draw::draw_text(
+                        image,
+                        acquisition.description(),
+                        min_x.round() as i64,
+                        min_y.round() as i64 - (text_height + options.line_width + 1) as i64,
+                        options.text_scale,
+                        text_color,
+                    );
+                }
+            }
+        }
+
+        if options.scale_bar {
+            draw_scale_bar(image, frame.um_per_pixel(), options, text_color);
+        }
+    }
+}
+
+/// Draw a scale bar (with a length of 1, 2 or 5 x 10^n μm) in the bottom left corner of the image
+fn draw_scale_bar(image: &mut RgbaImage, scale: f64, options: &OverviewOptions, color: Rgba<u8>) {
+    // Aim for a scale bar around 1/5 of the width of the image
+    let target_um = image.width() as f64 * scale / 5.0;
+    if target_um <= 0.0 {
+        return;
+    }
+
+    let magnitude = 10f64.powf(target_um.log10().floor());
+    let length_um = [5.0, 2.0, 1.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|&length| length <= target_um)
+        .unwrap_or(magnitude);
+
+    let label = if length_um >= 1000.0 {
+        format!("{} mm", length_um / 1000.0)
+    } else {
+        format!("{} um", length_um)
+    };
+
+    let length_px = (length_um / scale).round() as u32;
+    let margin = (image.width() / 50).max(2) as i64;
+    let thickness = (options.line_width * 2).max(2);
+    let (_, text_height) = draw::text_size(&label, options.text_scale);
+
+    let bar_y = image.height() as i64 - margin - thickness as i64;
+    draw::fill_rectangle(image, margin, bar_y, length_px, thickness, color);
+    draw::draw_text(
+        image,
+        &label,
+        margin,
+        bar_y - text_height as i64 - thickness as i64,
+        options.text_scale,
+        color,
+    );
 }
 
 impl<R> Slide<R> {
@@ -283,6 +555,11 @@ impl<R> Slide<R> {
         &self.description
     }
 
+    /// Returns the type of the slide
+    pub fn slide_type(&self) -> &str {
+        &self.slide_type
+    }
+
     /// Returns the width of the slide in μm
     pub fn width_in_um(&self) -> f64 {
         self.width_um
@@ -308,6 +585,12 @@ impl<R> Slide<R> {
         &self.sw_version
     }
 
+    /// Returns the child elements of the `Slide` element in the XML metadata which are not modelled here (e.g.
+    /// fields added in newer versions of the schema), with their text, ordered by name
+    pub fn extras(&self) -> &BTreeMap<String, String> {
+        &self.extras
+    }
+
     /// Returns the energy in Db
     pub fn energy_db(&self) -> Option<u32> {
         self.energy_db
@@ -357,9 +640,9 @@ impl<R> Slide<R> {
     pub fn image(&self) -> OpticalImage<R> {
         OpticalImage {
             reader: self.reader.as_ref().unwrap().clone(),
-            start_offset: self.image_start_offset,
-            end_offset: self.image_end_offset,
+            span: self.image,
             image_format: self.image_format(),
+            element: format!("Slide {} image", self.id),
         }
     }
 
@@ -377,6 +660,18 @@ impl<R> Slide<R> {
     //     }
     // }
 
+    /// Propose a grid of acquisitions covering the region of the slide (in μm), with square tiles of `tile_size`
+    /// μm overlapping by `overlap` μm. The resulting `Tiling` can be exported as a template (.csv or .json) for
+    /// planning acquisitions.
+    pub fn proposed_tiling(
+        &self,
+        region: &BoundingBox<f64>,
+        tile_size: f64,
+        overlap: f64,
+    ) -> Result<Tiling, MCDError> {
+        Tiling::new(self.id, region, tile_size, overlap)
+    }
+
     /// Returns a vector of panorama ids sorted by ID number. This allocates a new vector on each call.
     pub fn panorama_ids(&self) -> Vec<u16> {
         let mut ids: Vec<u16> = Vec::with_capacity(self.panoramas.len());
@@ -413,98 +708,110 @@ impl<R> Slide<R> {
     pub(crate) fn panoramas_mut(&mut self) -> &mut HashMap<u16, Panorama<R>> {
         &mut self.panoramas
     }
-}
 
-#[rustfmt::skip]
-impl<R> Print for Slide<R> {
-    fn print<W: fmt::Write + ?Sized>(&self, writer: &mut W, indent: usize) -> fmt::Result {
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "Slide", 36)?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "ID",
-            self.id,
-            indent = indent
-        )?;
+    /// Returns the fiducial marks recorded on the slide, ordered by ID (always empty in version 1 of the Schema)
+    pub fn fiducial_marks(&self) -> &[SlideFiducialMarks] {
+        &self.fiducial_marks
+    }
 
-        if let Some(uid) = &self.uid {
-                writeln!(
-                    writer,
-                    "{:indent$}{: <16} | {}",
-                    "",
-                    "UID",
-                    uid,
-                    indent = indent
-                )?;
-        }
+    pub(crate) fn fiducial_marks_mut(&mut self) -> &mut Vec<SlideFiducialMarks> {
+        &mut self.fiducial_marks
+    }
 
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "Description",
-            self.description,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "Filename",
-            self.filename,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "Type",
-            self.slide_type,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {} μm x {} μm ",
-            "",
-            "Dimensions",
-            self.width_um,
-            self.height_um,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "Image File",
-            self.image_file,
-            indent = indent
-        )?;
-        writeln!(
-            writer,
-            "{:indent$}{: <16} | {}",
-            "",
-            "Software Version",
-            self.sw_version,
-            indent = indent
-        )?;
+    /// Derive the transform correcting positions recorded by the instrument (stage coordinates, in μm) to the
+    /// slide, from the recorded fiducial marks and the `reference` positions (in μm) of the same marks, e.g. as
+    /// located on a scanned image of the slide. The reference positions are paired with the marks in order of ID.
+    ///
+    /// At least three marks are required, and marks which disagree with the others are rejected (see
+    /// [`register::estimate_affine_ransac`]). The transform can be applied to overview images with
+    /// [`OverviewOptions::fiducial_transform`].
+    pub fn fiducial_transform(
+        &self,
+        reference: &[(f64, f64)],
+    ) -> Result<AffineTransform<f64>, MCDError> {
+        let recorded: Vec<_> = self
+            .fiducial_marks
+            .iter()
+            .map(|mark| (mark.coordinate_x as f64, mark.coordinate_y as f64))
+            .collect();
+
+        let registration = register::estimate_affine_ransac(&recorded, reference)?;
+
+        Ok(registration.transform().clone())
+    }
+
+    /// Build the index over the bounding boxes of the acquisitions, once all panoramas have been added
+    pub(crate) fn build_spatial_index(&mut self) {
+        let entries = self
+            .panoramas()
+            .into_iter()
+            .flat_map(|panorama| panorama.acquisitions())
+            .map(|acquisition| {
+                (
+                    (acquisition.panorama_id, acquisition.id()),
+                    acquisition.roi_shape().bounding_box(),
+                )
+            })
+            .collect();
+
+        self.spatial_index = SpatialIndex::new(entries);
+    }
+
+    fn indexed_acquisition(&self, &(panorama_id, acquisition_id): &(u16, u16)) -> &Acquisition<R> {
+        self.panorama(panorama_id)
+            .and_then(|panorama| panorama.acquisition(acquisition_id))
+            .expect("Spatial index should only contain acquisitions that exist")
+    }
+
+    /// Returns the acquisitions on the slide which are at least partially contained within the specified
+    /// bounding box (slide coordinates), ordered by panorama and acquisition ID
+    pub fn acquisitions_in(&self, region: &BoundingBox<f64>) -> Vec<&Acquisition<R>> {
+        self.spatial_index
+            .query(region)
+            .into_iter()
+            .map(|key| self.indexed_acquisition(key))
+            .filter(|acquisition| acquisition.in_region(region))
+            .collect()
+    }
+
+    /// Returns the acquisition closest to the position on the slide (distance from the point to the bounding box
+    /// of the acquisition, which is 0 for acquisitions containing the point), with the distance in μm
+    pub fn nearest_acquisition(&self, point: SlidePoint) -> Option<(&Acquisition<R>, f64)> {
+        self.spatial_index
+            .nearest(point.x, point.y)
+            .map(|(key, distance)| (self.indexed_acquisition(key), distance))
+    }
+
+    /// Returns each pair of acquisitions on the slide whose bounding boxes overlap, for example to find ROIs
+    /// which were (partially) ablated twice
+    pub fn overlapping_acquisitions(&self) -> Vec<(&Acquisition<R>, &Acquisition<R>)> {
+        self.spatial_index
+            .overlaps()
+            .into_iter()
+            .map(|(a, b)| (self.indexed_acquisition(a), self.indexed_acquisition(b)))
+            .collect()
+    }
+}
 
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "", 36)?;
+impl<R> Describe for Slide<R> {
+    fn describe(&self) -> Description {
+        let mut description = Description::new("Slide").field("ID", self.id);
 
-        writeln!(
-            writer,
-            "{:indent$}{} panorama(s) with ids: {:?}",
-            "",
-            self.panoramas.len(),
-            self.panorama_ids(),
-            indent = indent + 1
-        )?;
-        write!(writer, "{:indent$}", "", indent = indent)?;
-        writeln!(writer, "{:-^1$}", "", 36)?;
+        if let Some(uid) = &self.uid {
+            description = description.field("UID", uid);
+        }
 
-        Ok(())
+        description
+            .field("Description", &self.description)
+            .field("Filename", &self.filename)
+            .field("Type", &self.slide_type)
+            .field(
+                "Dimensions (μm)",
+                Value::Size(self.width_um, self.height_um),
+            )
+            .field("Image File", &self.image_file)
+            .field("Software Version", &self.sw_version)
+            .field("Panorama IDs", self.panorama_ids())
     }
 }
 
@@ -514,7 +821,8 @@ impl<R> fmt::Display for Slide<R> {
     }
 }
 
-#[derive(Debug)]
+/// Fiducial mark recorded on a slide (in version 2 of the Schema)
+#[derive(Debug, Clone)]
 pub struct SlideFiducialMarks {
     id: u16,
     slide_id: u16,
@@ -523,15 +831,19 @@ pub struct SlideFiducialMarks {
 }
 
 impl SlideFiducialMarks {
+    /// Returns the ID of the fiducial mark
     pub fn id(&self) -> u16 {
         self.id
     }
+    /// Returns the ID of the slide the mark is on
     pub fn slide_id(&self) -> u16 {
         self.slide_id
     }
+    /// Returns the recorded x position of the mark (stage coordinates, in μm)
     pub fn coordinate_x(&self) -> u32 {
         self.coordinate_x
     }
+    /// Returns the recorded y position of the mark (stage coordinates, in μm)
     pub fn coordinate_y(&self) -> u32 {
         self.coordinate_y
     }
@@ -583,3 +895,38 @@ impl From<SlideProfileXML> for SlideProfile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{error::Result, MCDWriter, SlideSpec, MCD};
+
+    #[test]
+    fn fiducial_transform_from_marks() -> Result<()> {
+        let mut writer = MCDWriter::new(Cursor::new(Vec::new()));
+        writer.add_slide(
+            SlideSpec::new(1)
+                .fiducial_mark(1000, 1000)
+                .fiducial_mark(70000, 1000)
+                .fiducial_mark(1000, 24000),
+        )?;
+        let mcd = MCD::parse(Cursor::new(writer.finish()?.into_inner()))?;
+        let slide = mcd.slide(1).expect("Slide 1 should exist");
+
+        assert_eq!(slide.fiducial_marks().len(), 3);
+        assert_eq!(slide.fiducial_marks()[1].coordinate_x(), 70000);
+
+        // The stage is offset from the slide by (10, -5) μm
+        let reference = [(1010.0, 995.0), (70010.0, 995.0), (1010.0, 23995.0)];
+        let transform = slide.fiducial_transform(&reference)?;
+        let point = transform
+            .transform_to_slide(500.0, 500.0)
+            .expect("Transform should be invertible");
+        assert!((point.x - 510.0).abs() < 1e-6 && (point.y - 495.0).abs() < 1e-6);
+
+        assert!(slide.fiducial_transform(&reference[..2]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::{MCDError, Result};
+
+/// Size of the header preceding each optical image stored in an .mcd file (the offsets recorded in the XML
+/// include the header)
+pub(crate) const IMAGE_HEADER_SIZE: u64 = 161;
+
+/// Range of bytes `[start, end)` in an .mcd file, such as the spectra of an acquisition or an optical image.
+///
+/// Offsets are stored as `u64` throughout, so that data beyond 2 GB or 4 GB in large (e.g. 100 GB multi-slide)
+/// files is addressed correctly on all platforms. The end of a span is never before its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ByteSpan {
+    start: u64,
+    end: u64,
+}
+
+impl ByteSpan {
+    /// Create a span from `start` (inclusive) to `end` (exclusive), returning `MCDError::InvalidByteSpan` if the
+    /// span ends before it starts
+    pub fn new(start: u64, end: u64) -> Result<Self> {
+        if end < start {
+            return Err(MCDError::InvalidByteSpan { start, end });
+        }
+
+        Ok(ByteSpan { start, end })
+    }
+
+    /// Create a span from the offsets recorded in the XML, which may be missing or inconsistent (e.g. an image
+    /// which was not captured is recorded with a start and end offset of 0). Invalid spans are treated as empty.
+    pub(crate) fn from_xml(start: Option<u64>, end: Option<u64>) -> Self {
+        let start = start.unwrap_or(0);
+
+        ByteSpan {
+            start,
+            end: end.unwrap_or(0).max(start),
+        }
+    }
+
+    /// Returns the offset of the first byte in the span
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the offset immediately after the last byte in the span
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Returns the number of bytes in the span
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Returns true if the span contains no bytes
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns the span with the first `bytes` bytes removed (e.g. to skip a header), which is empty if the span
+    /// is shorter than `bytes`
+    pub fn skip(&self, bytes: u64) -> Self {
+        let start = self.start.saturating_add(bytes).min(self.end);
+
+        ByteSpan {
+            start,
+            end: self.end,
+        }
+    }
+
+    /// Returns the number of bytes in the span as a `usize` (e.g. to allocate a buffer for reading the span),
+    /// returning `MCDError::InvalidByteSpan` if the span cannot be addressed on this platform
+    pub fn len_usize(&self) -> Result<usize> {
+        usize::try_from(self.len()).or(Err(MCDError::InvalidByteSpan {
+            start: self.start,
+            end: self.end,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_beyond_4gb() -> Result<()> {
+        let start = 100 * 1024 * 1024 * 1024;
+        let span = ByteSpan::new(start, start + 1000)?;
+        assert_eq!(span.len(), 1000);
+        assert_eq!(span.len_usize()?, 1000);
+        assert_eq!(
+            span.skip(IMAGE_HEADER_SIZE).start(),
+            start + IMAGE_HEADER_SIZE
+        );
+        assert!(span.skip(2000).is_empty());
+
+        assert!(matches!(
+            ByteSpan::new(start, 0),
+            Err(MCDError::InvalidByteSpan { .. })
+        ));
+
+        // Images which were not captured
+        assert!(ByteSpan::from_xml(Some(0), Some(0)).is_empty());
+        assert!(ByteSpan::from_xml(Some(start), Some(0)).is_empty());
+        assert!(ByteSpan::from_xml(None, None).is_empty());
+
+        Ok(())
+    }
+}
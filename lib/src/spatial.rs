@@ -0,0 +1,261 @@
+use crate::BoundingBox;
+
+/// Uniform grid over bounding boxes (slide coordinates, μm), so that region queries, nearest lookups and
+/// overlap detection only consider the boxes in nearby cells rather than every box. Each box is stored in every
+/// cell it covers, and the grid is sized to hold roughly one box per cell.
+#[derive(Debug, Clone)]
+pub(crate) struct SpatialIndex<K> {
+    entries: Vec<(K, BoundingBox<f64>)>,
+
+    min_x: f64,
+    min_y: f64,
+    cell_width: f64,
+    cell_height: f64,
+    columns: usize,
+    rows: usize,
+    /// Indices into `entries` of the boxes covering each cell, row by row
+    cells: Vec<Vec<usize>>,
+}
+
+impl<K> Default for SpatialIndex<K> {
+    fn default() -> Self {
+        SpatialIndex::new(Vec::new())
+    }
+}
+
+impl<K> SpatialIndex<K> {
+    /// Create an index over the boxes. Queries return keys in the order the boxes are supplied.
+    pub(crate) fn new(entries: Vec<(K, BoundingBox<f64>)>) -> Self {
+        let min_x = entries
+            .iter()
+            .map(|(_, b)| b.min_x)
+            .fold(f64::MAX, f64::min);
+        let min_y = entries
+            .iter()
+            .map(|(_, b)| b.min_y)
+            .fold(f64::MAX, f64::min);
+        let max_x = entries
+            .iter()
+            .map(|(_, b)| b.max_x())
+            .fold(f64::MIN, f64::max);
+        let max_y = entries
+            .iter()
+            .map(|(_, b)| b.max_y())
+            .fold(f64::MIN, f64::max);
+
+        let cells_per_side = (entries.len() as f64).sqrt().ceil().max(1.0) as usize;
+        let cell_size = |extent: f64| {
+            if extent > 0.0 {
+                extent / cells_per_side as f64
+            } else {
+                1.0
+            }
+        };
+
+        let mut index = SpatialIndex {
+            min_x: if entries.is_empty() { 0.0 } else { min_x },
+            min_y: if entries.is_empty() { 0.0 } else { min_y },
+            cell_width: cell_size(max_x - min_x),
+            cell_height: cell_size(max_y - min_y),
+            columns: cells_per_side,
+            rows: cells_per_side,
+            cells: vec![Vec::new(); cells_per_side * cells_per_side],
+            entries,
+        };
+
+        for (entry, (_, bounding_box)) in index.entries.iter().enumerate() {
+            let (columns, rows) = index.cell_range(bounding_box);
+
+            for row in rows {
+                for column in columns.clone() {
+                    index.cells[row * index.columns + column].push(entry);
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Returns the (clamped) column and row of the cell containing the position
+    fn cell(&self, x: f64, y: f64) -> (usize, usize) {
+        let column = ((x - self.min_x) / self.cell_width).floor().max(0.0) as usize;
+        let row = ((y - self.min_y) / self.cell_height).floor().max(0.0) as usize;
+
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    /// Returns the ranges of columns and rows of the cells covered by the box
+    fn cell_range(
+        &self,
+        bounding_box: &BoundingBox<f64>,
+    ) -> (
+        std::ops::RangeInclusive<usize>,
+        std::ops::RangeInclusive<usize>,
+    ) {
+        let (min_column, min_row) = self.cell(bounding_box.min_x, bounding_box.min_y);
+        let (max_column, max_row) = self.cell(bounding_box.max_x(), bounding_box.max_y());
+
+        (min_column..=max_column, min_row..=max_row)
+    }
+
+    /// Returns the indices of the entries in the cells covered by the box, in order and without repeats
+    fn candidates(&self, bounding_box: &BoundingBox<f64>) -> Vec<usize> {
+        let (columns, rows) = self.cell_range(bounding_box);
+
+        let mut candidates: Vec<usize> = rows
+            .flat_map(|row| {
+                columns
+                    .clone()
+                    .flat_map(move |column| &self.cells[row * self.columns + column])
+            })
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        candidates
+    }
+
+    /// Returns the keys of the boxes which are at least partially contained within the region
+    pub(crate) fn query(&self, region: &BoundingBox<f64>) -> Vec<&K> {
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+
+        self.candidates(region)
+            .into_iter()
+            .map(|entry| &self.entries[entry])
+            .filter(|(_, bounding_box)| intersects(bounding_box, region))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Returns the key of the box closest to the position (a box containing the position is at distance 0),
+    /// together with the distance. Ties are resolved in favour of the earliest box.
+    pub(crate) fn nearest(&self, x: f64, y: f64) -> Option<(&K, f64)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let (column, row) = self.cell(x, y);
+        let min_cell_size = self.cell_width.min(self.cell_height);
+        let mut best: Option<(usize, f64)> = None;
+
+        for ring in 0..self.columns.max(self.rows) {
+            let min_column = column.saturating_sub(ring);
+            let max_column = (column + ring).min(self.columns - 1);
+            let min_row = row.saturating_sub(ring);
+            let max_row = (row + ring).min(self.rows - 1);
+
+            for ring_row in min_row..=max_row {
+                for ring_column in min_column..=max_column {
+                    // Only the cells on the edge of the ring are new
+                    if ring_row.abs_diff(row) != ring && ring_column.abs_diff(column) != ring {
+                        continue;
+                    }
+
+                    for &entry in &self.cells[ring_row * self.columns + ring_column] {
+                        let distance = distance(&self.entries[entry].1, x, y);
+
+                        if best.is_none_or(|(best_entry, best_distance)| {
+                            distance < best_distance
+                                || (distance == best_distance && entry < best_entry)
+                        }) {
+                            best = Some((entry, distance));
+                        }
+                    }
+                }
+            }
+
+            // Boxes in cells further out are at least this far away
+            if let Some((_, best_distance)) = best {
+                if best_distance <= ring as f64 * min_cell_size {
+                    break;
+                }
+            }
+        }
+
+        best.map(|(entry, distance)| (&self.entries[entry].0, distance))
+    }
+
+    /// Returns each pair of keys whose boxes overlap (with a positive area), in order
+    pub(crate) fn overlaps(&self) -> Vec<(&K, &K)> {
+        let mut pairs = Vec::new();
+
+        for (entry, (_, bounding_box)) in self.entries.iter().enumerate() {
+            for other in self.candidates(bounding_box) {
+                if other > entry && intersects(bounding_box, &self.entries[other].1) {
+                    pairs.push((entry, other));
+                }
+            }
+        }
+
+        pairs
+            .into_iter()
+            .map(|(entry, other)| (&self.entries[entry].0, &self.entries[other].0))
+            .collect()
+    }
+}
+
+/// Tests whether the boxes overlap, matching `Acquisition::in_region`
+fn intersects(a: &BoundingBox<f64>, b: &BoundingBox<f64>) -> bool {
+    a.min_x < b.max_x() && a.max_x() > b.min_x && a.max_y() > b.min_y && a.min_y < b.max_y()
+}
+
+/// Returns the distance from the position to the closest point of the box
+fn distance(bounding_box: &BoundingBox<f64>, x: f64, y: f64) -> f64 {
+    let dx = (bounding_box.min_x - x)
+        .max(x - bounding_box.max_x())
+        .max(0.0);
+    let dy = (bounding_box.min_y - y)
+        .max(y - bounding_box.max_y())
+        .max(0.0);
+
+    dx.hypot(dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounding_box(min_x: f64, min_y: f64, width: f64, height: f64) -> BoundingBox<f64> {
+        BoundingBox {
+            min_x,
+            min_y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn query_nearest_and_overlaps() {
+        let mut entries: Vec<_> = (0..100)
+            .map(|i| {
+                let (x, y) = ((i % 10) as f64 * 100.0, (i / 10) as f64 * 100.0);
+                (i, bounding_box(x, y, 50.0, 50.0))
+            })
+            .collect();
+        entries.push((100, bounding_box(20.0, 20.0, 100.0, 100.0)));
+
+        let index = SpatialIndex::new(entries);
+
+        assert_eq!(
+            index.query(&bounding_box(140.0, 40.0, 20.0, 80.0)),
+            [&1, &11]
+        );
+        assert!(index
+            .query(&bounding_box(60.0, 960.0, 30.0, 30.0))
+            .is_empty());
+
+        assert_eq!(index.nearest(975.0, 975.0), Some((&99, 25.0 * 2f64.sqrt())));
+        assert_eq!(index.nearest(325.0, 525.0), Some((&53, 0.0)));
+        assert_eq!(index.nearest(-300.0, -400.0), Some((&0, 500.0)));
+
+        assert_eq!(
+            index.overlaps(),
+            [(&0, &100), (&1, &100), (&10, &100), (&11, &100)]
+        );
+
+        assert!(SpatialIndex::<u16>::default().nearest(0.0, 0.0).is_none());
+    }
+}
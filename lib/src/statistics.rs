@@ -0,0 +1,504 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use crate::{
+    coords::SlidePoint, error::Result, Acquisition, BoundingBox, ChannelIdentifier, ChannelImage,
+    Region, MCD,
+};
+
+/// Number of histogram bins per doubling of intensity
+const BINS_PER_OCTAVE: f32 = 16.0;
+/// Number of histogram bins, covering intensities up to 2^24
+const NUM_BINS: usize = 24 * BINS_PER_OCTAVE as usize;
+
+/// Returns the histogram bin for the intensity. Bins are logarithmically spaced so that histograms of
+/// different images can be combined without knowing the intensity range in advance.
+fn bin(intensity: f32) -> usize {
+    if intensity <= 0.0 {
+        0
+    } else {
+        ((intensity.ln_1p() / std::f32::consts::LN_2 * BINS_PER_OCTAVE) as usize).min(NUM_BINS - 1)
+    }
+}
+
+/// Returns the lowest intensity in the histogram bin
+fn bin_start(bin: usize) -> f32 {
+    (bin as f32 / BINS_PER_OCTAVE).exp2() - 1.0
+}
+
+/// Coarse summary statistics of channel intensities which can be combined analytically, allowing statistics
+/// (and so display ranges) of large areas to be derived from precomputed statistics of smaller areas, without
+/// reading the pixels again.
+///
+/// Mean and standard deviation are exact, while quantiles are approximated from a histogram with
+/// logarithmically spaced bins (~4% relative bin width).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelStatistics {
+    count: u64,
+    num_missing: u64,
+    min: f32,
+    max: f32,
+    sum: f64,
+    sum_squares: f64,
+    histogram: Vec<u64>,
+}
+
+impl Default for ChannelStatistics {
+    fn default() -> Self {
+        ChannelStatistics {
+            count: 0,
+            num_missing: 0,
+            min: f32::MAX,
+            max: f32::MIN,
+            sum: 0.0,
+            sum_squares: 0.0,
+            histogram: vec![0; NUM_BINS],
+        }
+    }
+}
+
+impl ChannelStatistics {
+    /// Create empty statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calculate statistics of the intensities. NaN values are ignored.
+    pub fn from_intensities(intensities: &[f32]) -> Self {
+        let mut statistics = Self::default();
+
+        for &intensity in intensities {
+            statistics.add(intensity);
+        }
+
+        statistics
+    }
+
+    /// Calculate statistics of the image. Missing pixels (NaN, including those not acquired) are counted
+    /// separately and excluded from all other statistics.
+    pub fn from_image(image: &ChannelImage) -> Self {
+        let valid_pixels = image.num_valid_pixels().min(image.intensities().len());
+
+        let mut statistics = Self::from_intensities(&image.intensities()[..valid_pixels]);
+        statistics.num_missing +=
+            (image.width() as u64 * image.height() as u64).saturating_sub(valid_pixels as u64);
+
+        statistics
+    }
+
+    /// Add a single intensity. NaN (missing) values are counted, but otherwise ignored.
+    pub fn add(&mut self, intensity: f32) {
+        if intensity.is_nan() {
+            self.num_missing += 1;
+            return;
+        }
+
+        self.count += 1;
+        self.min = self.min.min(intensity);
+        self.max = self.max.max(intensity);
+        self.sum += intensity as f64;
+        self.sum_squares += intensity as f64 * intensity as f64;
+        self.histogram[bin(intensity)] += 1;
+    }
+
+    /// Combine these statistics with `other`, as if calculated from both sets of intensities
+    pub fn merge(&mut self, other: &ChannelStatistics) {
+        self.count += other.count;
+        self.num_missing += other.num_missing;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.sum_squares += other.sum_squares;
+
+        for (count, other) in self.histogram.iter_mut().zip(&other.histogram) {
+            *count += other;
+        }
+    }
+
+    /// Returns the number of intensities included in the statistics (excluding missing values)
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of missing (NaN) values, which are excluded from the statistics
+    pub fn num_missing(&self) -> u64 {
+        self.num_missing
+    }
+
+    /// Returns the minimum intensity, or None if empty
+    pub fn min(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// Returns the maximum intensity, or None if empty
+    pub fn max(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Returns the mean intensity, or None if empty
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Returns the (population) standard deviation of the intensities, or None if empty
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+
+        Some(
+            (self.sum_squares / self.count as f64 - mean * mean)
+                .max(0.0)
+                .sqrt(),
+        )
+    }
+
+    /// Returns the approximate intensity at the specified quantile (0 - 1), or None if empty
+    pub fn quantile(&self, quantile: f64) -> Option<f32> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let target = quantile.clamp(0.0, 1.0) * self.count as f64;
+        let mut cumulative = 0.0;
+
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            if cumulative + count as f64 >= target {
+                // Interpolate linearly within the bin
+                let fraction = ((target - cumulative) / count as f64) as f32;
+                let start = bin_start(bin);
+                let value = start + (bin_start(bin + 1) - start) * fraction;
+
+                return Some(value.clamp(self.min, self.max));
+            }
+
+            cumulative += count as f64;
+        }
+
+        Some(self.max)
+    }
+
+    /// Returns a display range (min, max) between the `lower` and `upper` quantiles (e.g. 0.01 and 0.99),
+    /// or None if empty
+    pub fn display_range(&self, lower: f64, upper: f64) -> Option<(f32, f32)> {
+        Some((self.quantile(lower)?, self.quantile(upper)?))
+    }
+}
+
+/// A single resolution level of a `StatisticsPyramid`
+#[derive(Debug, Clone)]
+struct Level {
+    tile_size: u32,
+    num_tiles_x: u32,
+    num_tiles_y: u32,
+    tiles: Vec<ChannelStatistics>,
+}
+
+impl Level {
+    fn tile(&self, x: u32, y: u32) -> &ChannelStatistics {
+        &self.tiles[(y * self.num_tiles_x + x) as usize]
+    }
+}
+
+/// Multi-resolution statistics of a single channel of an acquisition. The finest level holds statistics of
+/// square tiles of pixels, with each subsequent level combining 2x2 tiles from the level below, until a single
+/// tile covers the whole acquisition. Statistics of any region can then be derived by combining a small number
+/// of tiles.
+#[derive(Debug, Clone)]
+pub struct StatisticsPyramid {
+    acquisition_id: u16,
+    width: u32,
+    height: u32,
+    levels: Vec<Level>,
+}
+
+impl StatisticsPyramid {
+    /// Calculate the statistics pyramid of the image, with tiles of `tile_size` x `tile_size` pixels at the
+    /// finest level
+    pub fn new(image: &ChannelImage, tile_size: u32) -> Self {
+        let tile_size = tile_size.max(1);
+        let width = image.width();
+        let height = image.height();
+
+        let num_tiles_x = width.div_ceil(tile_size).max(1);
+        let num_tiles_y = height.div_ceil(tile_size).max(1);
+        let mut tiles = vec![ChannelStatistics::default(); (num_tiles_x * num_tiles_y) as usize];
+
+        let valid_pixels = image.num_valid_pixels().min(image.intensities().len());
+        for (index, &intensity) in image.intensities()[..valid_pixels].iter().enumerate() {
+            let x = index as u32 % width.max(1) / tile_size;
+            let y = index as u32 / width.max(1) / tile_size;
+
+            tiles[(y * num_tiles_x + x) as usize].add(intensity);
+        }
+
+        let mut levels = vec![Level {
+            tile_size,
+            num_tiles_x,
+            num_tiles_y,
+            tiles,
+        }];
+
+        while let Some(level) = levels.last() {
+            if level.num_tiles_x == 1 && level.num_tiles_y == 1 {
+                break;
+            }
+
+            let num_tiles_x = level.num_tiles_x.div_ceil(2);
+            let num_tiles_y = level.num_tiles_y.div_ceil(2);
+            let mut tiles = Vec::with_capacity((num_tiles_x * num_tiles_y) as usize);
+
+            for y in 0..num_tiles_y {
+                for x in 0..num_tiles_x {
+                    let mut statistics = ChannelStatistics::default();
+
+                    for child_y in (y * 2)..(y * 2 + 2).min(level.num_tiles_y) {
+                        for child_x in (x * 2)..(x * 2 + 2).min(level.num_tiles_x) {
+                            statistics.merge(level.tile(child_x, child_y));
+                        }
+                    }
+
+                    tiles.push(statistics);
+                }
+            }
+
+            let coarser = Level {
+                tile_size: level.tile_size * 2,
+                num_tiles_x,
+                num_tiles_y,
+                tiles,
+            };
+            levels.push(coarser);
+        }
+
+        StatisticsPyramid {
+            acquisition_id: image.acquisition_id(),
+            width,
+            height,
+            levels,
+        }
+    }
+
+    /// Returns the ID of the acquisition
+    pub fn acquisition_id(&self) -> u16 {
+        self.acquisition_id
+    }
+
+    /// Returns the number of resolution levels
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the statistics of the whole acquisition
+    pub fn statistics(&self) -> &ChannelStatistics {
+        // There is always at least one level, and the last level contains a single tile
+        &self.levels[self.levels.len() - 1].tiles[0]
+    }
+
+    /// Returns the approximate statistics of the region (in pixels). All tiles which overlap the region are
+    /// included, using the coarsest level with tiles no larger than the region, so the statistics may include
+    /// pixels up to one tile outside the region.
+    pub fn region(&self, region: &Region) -> ChannelStatistics {
+        let mut statistics = ChannelStatistics::default();
+
+        let region_end_x = (region.x + region.width).min(self.width);
+        let region_end_y = (region.y + region.height).min(self.height);
+        if region.x >= region_end_x || region.y >= region_end_y {
+            return statistics;
+        }
+
+        let region_size = region.width.min(region.height);
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|level| level.tile_size <= region_size)
+            .unwrap_or(&self.levels[0]);
+
+        for y in (region.y / level.tile_size)..=((region_end_y - 1) / level.tile_size) {
+            for x in (region.x / level.tile_size)..=((region_end_x - 1) / level.tile_size) {
+                statistics.merge(level.tile(x, y));
+            }
+        }
+
+        statistics
+    }
+}
+
+/// Cache of precomputed `StatisticsPyramid`s, used to derive statistics (e.g. display ranges) of channels in
+/// stitched views of the slide without reading the pixels each time the view changes.
+///
+/// ```no_run
+/// use imc_rs::{BoundingBox, ChannelIdentifier, MCD};
+/// use imc_rs::statistics::StatisticsCache;
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let mut cache = StatisticsCache::new(64);
+///
+/// let region = BoundingBox { min_x: 0.0, min_y: 0.0, width: 10000.0, height: 10000.0 };
+/// let statistics = cache
+///     .slide_statistics(&mcd, &region, &ChannelIdentifier::label("DNA1"))
+///     .unwrap();
+///
+/// println!("{:?}", statistics.display_range(0.01, 0.99));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatisticsCache {
+    tile_size: u32,
+    pyramids: HashMap<(u16, String), StatisticsPyramid>,
+}
+
+impl StatisticsCache {
+    /// Create an empty cache, where pyramids have tiles of `tile_size` x `tile_size` pixels at the finest level
+    pub fn new(tile_size: u32) -> Self {
+        StatisticsCache {
+            tile_size,
+            pyramids: HashMap::new(),
+        }
+    }
+
+    /// Returns the statistics pyramid of the channel in the acquisition, calculating it if not already cached.
+    /// Returns None if the channel is not present in the acquisition.
+    pub fn pyramid<R: Read + Seek>(
+        &mut self,
+        acquisition: &Acquisition<R>,
+        identifier: &ChannelIdentifier,
+    ) -> Result<Option<&StatisticsPyramid>> {
+        let channel = match acquisition.channel(identifier) {
+            Some(channel) => channel,
+            None => return Ok(None),
+        };
+
+        let key = (acquisition.id(), channel.name().to_string());
+
+        if !self.pyramids.contains_key(&key) {
+            let image = acquisition.channel_image(channel, None)?;
+            self.pyramids
+                .insert(key.clone(), StatisticsPyramid::new(&image, self.tile_size));
+        }
+
+        Ok(self.pyramids.get(&key))
+    }
+
+    /// Returns the combined statistics of the channel across all acquisitions overlapping the region of the
+    /// slide (in μm). Only the parts of each acquisition within the region are included (at tile resolution).
+    pub fn slide_statistics<R: Read + Seek>(
+        &mut self,
+        mcd: &MCD<R>,
+        region: &BoundingBox<f64>,
+        identifier: &ChannelIdentifier,
+    ) -> Result<ChannelStatistics> {
+        let mut statistics = ChannelStatistics::default();
+
+        for acquisition in mcd.acquisitions_in(region) {
+            let pixel_region = match pixel_region(acquisition, region) {
+                Some(pixel_region) => pixel_region,
+                None => continue,
+            };
+
+            if let Some(pyramid) = self.pyramid(acquisition, identifier)? {
+                statistics.merge(&pyramid.region(&pixel_region));
+            }
+        }
+
+        Ok(statistics)
+    }
+}
+
+/// Returns the region of the acquisition (in pixels) covered by the region of the slide (in μm)
+fn pixel_region<R>(acquisition: &Acquisition<R>, region: &BoundingBox<f64>) -> Option<Region> {
+    let width = acquisition.width().max(0) as f64;
+    let height = acquisition.height().max(0) as f64;
+
+    let mut min = (f64::MAX, f64::MAX);
+    let mut max = (f64::MIN, f64::MIN);
+
+    for (x, y) in [
+        (region.min_x, region.min_y),
+        (region.max_x(), region.min_y),
+        (region.min_x, region.max_y()),
+        (region.max_x(), region.max_y()),
+    ] {
+        let point = SlidePoint::new(x, y).to_acquisition(acquisition)?;
+        let (x, y) = (point.x, point.y);
+
+        min = (min.0.min(x), min.1.min(y));
+        max = (max.0.max(x), max.1.max(y));
+    }
+
+    let min_x = min.0.floor().clamp(0.0, width) as u32;
+    let min_y = min.1.floor().clamp(0.0, height) as u32;
+    let max_x = max.0.ceil().clamp(0.0, width) as u32;
+    let max_y = max.1.ceil().clamp(0.0, height) as u32;
+
+    (max_x > min_x && max_y > min_y).then_some(Region {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x,
+        height: max_y - min_y,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_image(width: u32, height: u32, data: Vec<f32>) -> ChannelImage {
+        ChannelImage {
+            region: Region {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            acquisition_id: 1,
+            name: String::new(),
+            label: String::new(),
+            range: (0.0, 0.0),
+            valid_pixels: data.len(),
+            data,
+        }
+    }
+
+    #[test]
+    fn merged_statistics_match_combined() {
+        let first: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let second: Vec<f32> = (100..1000).map(|i| i as f32).collect();
+
+        let mut merged = ChannelStatistics::from_intensities(&first);
+        merged.merge(&ChannelStatistics::from_intensities(&second));
+
+        let all: Vec<f32> = first.into_iter().chain(second).collect();
+        let combined = ChannelStatistics::from_intensities(&all);
+
+        assert_eq!(merged, combined);
+        assert_eq!(merged.mean(), Some(499.5));
+
+        let median = merged.quantile(0.5).unwrap_or_default();
+        assert!((median - 500.0).abs() < 500.0 * 0.05, "median {}", median);
+    }
+
+    #[test]
+    fn pyramid_regions() {
+        let data: Vec<f32> = (0..100).map(|i| (i % 10) as f32).collect();
+        let pyramid = StatisticsPyramid::new(&channel_image(10, 10, data), 2);
+
+        // 5x5, 3x3, 2x2 and 1x1 tiles
+        assert_eq!(pyramid.num_levels(), 4);
+        assert_eq!(pyramid.statistics().count(), 100);
+        assert_eq!(pyramid.statistics().max(), Some(9.0));
+
+        let left = pyramid.region(&Region {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 10,
+        });
+        assert_eq!(left.count(), 40);
+        assert_eq!(left.max(), Some(3.0));
+    }
+}
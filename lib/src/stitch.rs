@@ -0,0 +1,258 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    coords::SlidePoint,
+    error::{MCDError, Result},
+    BoundingBox, ChannelIdentifier, OnSlide, MCD,
+};
+
+/// How the intensities of acquisitions which overlap are combined when stitching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlapMode {
+    /// Use the first acquisition (ordered by slide, panorama and acquisition ID) with an acquired pixel
+    #[default]
+    First,
+    /// Use the maximum intensity
+    Max,
+    /// Use the mean intensity
+    Mean,
+}
+
+/// Image of a channel covering a region of the slide, assembled from all acquisitions overlapping the region
+/// (see `MCD::stitched_region`)
+#[derive(Debug, Clone)]
+pub struct StitchedImage {
+    region: BoundingBox<f64>,
+    pixel_size: f64,
+    width: u32,
+    height: u32,
+    data: Vec<f32>,
+}
+
+impl StitchedImage {
+    /// Returns the region of the slide (in μm) covered by the image
+    pub fn region(&self) -> &BoundingBox<f64> {
+        &self.region
+    }
+
+    /// Returns the width and height of each pixel (in μm)
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    /// Returns the width of the image (in pixels)
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the image (in pixels)
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the intensities row by row, with NaN for pixels not covered by any acquired pixel
+    pub fn intensities(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Returns the number of pixels covered by at least one acquired pixel
+    pub fn num_covered_pixels(&self) -> usize {
+        self.data.iter().filter(|value| !value.is_nan()).count()
+    }
+
+    /// Returns the minimum and maximum intensity of the covered pixels
+    pub fn intensity_range(&self) -> (f32, f32) {
+        self.data
+            .iter()
+            .filter(|value| !value.is_nan())
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            })
+    }
+}
+
+impl<R: Read + Seek> MCD<R> {
+    /// Assemble a single image of the channel covering a region of the slide (in μm), resampling all acquisitions
+    /// which overlap the region onto a grid of `pixel_size_um` pixels. The top left pixel corresponds to
+    /// (`region.min_x`, `region.min_y`), matching `Composite::render_slide_region`.
+    ///
+    /// Each pixel takes the intensity of the acquired pixel under its centre (nearest neighbour), with
+    /// acquisitions which overlap combined according to `overlap`. Acquisitions without the channel are ignored,
+    /// and pixels not covered by any acquired pixel are NaN.
+    pub fn stitched_region<C: Into<ChannelIdentifier>>(
+        &self,
+        channel: C,
+        region: &BoundingBox<f64>,
+        pixel_size_um: f64,
+        overlap: OverlapMode,
+    ) -> Result<StitchedImage> {
+        if !(pixel_size_um > 0.0 && pixel_size_um.is_finite()) {
+            return Err(MCDError::InvalidPixelSize {
+                pixel_size: pixel_size_um,
+            });
+        }
+
+        let identifier = channel.into();
+        let width = (region.width / pixel_size_um).ceil().max(0.0) as u32;
+        let height = (region.height / pixel_size_um).ceil().max(0.0) as u32;
+
+        let mut data = vec![f32::NAN; width as usize * height as usize];
+        // Number of acquisitions contributing to each pixel, only needed for the mean
+        let mut counts = vec![0u32; data.len()];
+
+        for acquisition in self.acquisitions_in(region) {
+            if acquisition.channel(&identifier).is_none() {
+                continue;
+            }
+
+            let pixels = match acquisition.pixels_in(region) {
+                Some(pixels) if pixels.width > 0 && pixels.height > 0 => pixels,
+                _ => continue,
+            };
+            let image = acquisition.channel_image(identifier.clone(), Some(pixels))?;
+
+            let bounding_box = acquisition.slide_bounding_box();
+            let column_range = |min: f64, max: f64| {
+                let first = ((min - region.min_x) / pixel_size_um).floor().max(0.0) as u32;
+                let last = ((max - region.min_x) / pixel_size_um).ceil().max(0.0) as u32;
+                first.min(width)..last.min(width)
+            };
+            let row_range = |min: f64, max: f64| {
+                let first = ((min - region.min_y) / pixel_size_um).floor().max(0.0) as u32;
+                let last = ((max - region.min_y) / pixel_size_um).ceil().max(0.0) as u32;
+                first.min(height)..last.min(height)
+            };
+
+            for y in row_range(bounding_box.min_y, bounding_box.max_y()) {
+                for x in column_range(bounding_box.min_x, bounding_box.max_x()) {
+                    let point = SlidePoint::new(
+                        region.min_x + (x as f64 + 0.5) * pixel_size_um,
+                        region.min_y + (y as f64 + 0.5) * pixel_size_um,
+                    );
+
+                    let pixel = match point.to_acquisition(acquisition) {
+                        Some(pixel) => pixel,
+                        None => continue,
+                    };
+
+                    let (column, row) = (
+                        pixel.x.floor() - pixels.x as f64,
+                        pixel.y.floor() - pixels.y as f64,
+                    );
+                    if column < 0.0
+                        || row < 0.0
+                        || column >= pixels.width as f64
+                        || row >= pixels.height as f64
+                    {
+                        continue;
+                    }
+
+                    let intensity = match image
+                        .intensities()
+                        .get(row as usize * pixels.width as usize + column as usize)
+                    {
+                        Some(&intensity) if !intensity.is_nan() => intensity,
+                        _ => continue,
+                    };
+
+                    let index = y as usize * width as usize + x as usize;
+                    let value = &mut data[index];
+
+                    if value.is_nan() {
+                        *value = intensity;
+                    } else {
+                        match overlap {
+                            OverlapMode::First => {}
+                            OverlapMode::Max => *value = value.max(intensity),
+                            OverlapMode::Mean => *value += intensity,
+                        }
+                    }
+                    counts[index] += 1;
+                }
+            }
+        }
+
+        if overlap == OverlapMode::Mean {
+            for (value, &count) in data.iter_mut().zip(&counts) {
+                if count > 1 {
+                    *value /= count as f32;
+                }
+            }
+        }
+
+        Ok(StitchedImage {
+            region: region.clone(),
+            pixel_size: pixel_size_um,
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcd::synthetic_mcd, AcquisitionSpec};
+
+    #[test]
+    fn stitch_overlapping_acquisitions() -> Result<()> {
+        // Two 2x1 acquisitions (1 μm pixels) overlapping by one pixel, and a third without the channel
+        let mcd = MCD::from_bytes(synthetic_mcd([
+            (
+                AcquisitionSpec::new(1, 1, 2, 1)
+                    .position(10.0, 11.0)
+                    .channel("Ir191", "DNA1"),
+                [1.0, 2.0],
+            ),
+            (
+                AcquisitionSpec::new(2, 1, 2, 1)
+                    .position(11.0, 11.0)
+                    .channel("Ir191", "DNA1"),
+                [4.0, 8.0],
+            ),
+            (
+                AcquisitionSpec::new(3, 1, 2, 1)
+                    .position(12.0, 11.0)
+                    .channel("Ir193", "DNA2"),
+                [16.0, 32.0],
+            ),
+        ])?)?;
+
+        let region = BoundingBox {
+            min_x: 10.0,
+            min_y: 10.0,
+            width: 4.0,
+            height: 1.0,
+        };
+        let stitch =
+            |overlap| mcd.stitched_region(ChannelIdentifier::label("DNA1"), &region, 1.0, overlap);
+
+        let first = stitch(OverlapMode::First)?;
+        assert_eq!((first.width(), first.height()), (4, 1));
+        assert_eq!(&first.intensities()[..3], [1.0, 2.0, 8.0]);
+        assert!(first.intensities()[3].is_nan());
+        assert_eq!(first.num_covered_pixels(), 3);
+
+        assert_eq!(
+            &stitch(OverlapMode::Max)?.intensities()[..3],
+            [1.0, 4.0, 8.0]
+        );
+        assert_eq!(
+            &stitch(OverlapMode::Mean)?.intensities()[..3],
+            [1.0, 3.0, 8.0]
+        );
+
+        assert!(matches!(
+            mcd.stitched_region(
+                ChannelIdentifier::label("DNA1"),
+                &region,
+                0.0,
+                OverlapMode::First
+            ),
+            Err(MCDError::InvalidPixelSize { .. })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{Read, Seek};
+
+use crate::{
+    channel::AcquisitionChannel,
+    error::{channel_not_found, MCDError, Result},
+    Acquisition, ChannelIdentifier, ChannelImage, Region,
+};
+
+/// Width and height of each tile (in pixels)
+pub const TILE_SIZE: u32 = 256;
+
+/// Describes the levels of the pyramid of an acquisition, and the tiles covering each level. Level 0 is the
+/// full resolution image, and each subsequent level is downsampled by a further factor of 2, down to the
+/// first level which fits within a single tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileGrid {
+    width: u32,
+    height: u32,
+}
+
+impl TileGrid {
+    /// Create the grid for a full resolution image with the specified width and height (in pixels)
+    pub fn new(width: u32, height: u32) -> Self {
+        TileGrid { width, height }
+    }
+
+    /// Returns the number of levels in the pyramid
+    pub fn num_levels(&self) -> u32 {
+        let mut num_levels = 1;
+
+        while self.width.max(self.height) as u64 > (TILE_SIZE as u64) << (num_levels - 1) {
+            num_levels += 1;
+        }
+
+        num_levels
+    }
+
+    /// Returns the factor by which the level is downsampled from the full resolution image
+    pub fn downsampling(level: u32) -> u32 {
+        1 << level
+    }
+
+    /// Returns the (width, height) of the level (in pixels), or None if there is no such level
+    pub fn level_size(&self, level: u32) -> Option<(u32, u32)> {
+        if level >= self.num_levels() {
+            return None;
+        }
+
+        let factor = Self::downsampling(level);
+
+        Some((self.width.div_ceil(factor), self.height.div_ceil(factor)))
+    }
+
+    /// Returns the number of (columns, rows) of tiles covering the level, or None if there is no such level
+    pub fn num_tiles(&self, level: u32) -> Option<(u32, u32)> {
+        let (width, height) = self.level_size(level)?;
+
+        Some((width.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE)))
+    }
+
+    /// Returns the region (in pixels of the level) covered by the tile, or None if there is no such tile. Tiles
+    /// at the right and bottom edges of the level may be smaller than `TILE_SIZE`.
+    pub fn tile_region(&self, level: u32, x: u32, y: u32) -> Option<Region> {
+        let (width, height) = self.level_size(level)?;
+        let (num_x, num_y) = self.num_tiles(level)?;
+
+        if x >= num_x || y >= num_y {
+            return None;
+        }
+
+        Some(Region {
+            x: x * TILE_SIZE,
+            y: y * TILE_SIZE,
+            width: TILE_SIZE.min(width - x * TILE_SIZE),
+            height: TILE_SIZE.min(height - y * TILE_SIZE),
+        })
+    }
+
+    /// Returns the region of the full resolution image covered by the region of the level
+    pub(crate) fn source_region(&self, level: u32, region: &Region) -> Region {
+        let factor = Self::downsampling(level);
+        let x = (region.x * factor).min(self.width);
+        let y = (region.y * factor).min(self.height);
+
+        Region {
+            x,
+            y,
+            width: (region.width * factor).min(self.width - x),
+            height: (region.height * factor).min(self.height - y),
+        }
+    }
+}
+
+/// Downsample the image by `factor`, where each pixel of the result is the mean of the (up to) `factor` x
+/// `factor` pixels it covers, ignoring missing (NaN) pixels. Pixels covering only missing pixels are missing.
+pub(crate) fn downsample(data: &[f32], width: u32, height: u32, factor: u32) -> Vec<f32> {
+    let (width, height, factor) = (width as usize, height as usize, factor.max(1) as usize);
+    let (out_width, out_height) = (width.div_ceil(factor), height.div_ceil(factor));
+
+    let mut downsampled = Vec::with_capacity(out_width * out_height);
+
+    for out_y in 0..out_height {
+        for out_x in 0..out_width {
+            let mut sum = 0.0f64;
+            let mut count = 0usize;
+
+            for y in (out_y * factor)..((out_y + 1) * factor).min(height) {
+                for x in (out_x * factor)..((out_x + 1) * factor).min(width) {
+                    match data.get(y * width + x) {
+                        Some(value) if !value.is_nan() => {
+                            sum += *value as f64;
+                            count += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            downsampled.push(if count > 0 {
+                (sum / count as f64) as f32
+            } else {
+                f32::NAN
+            });
+        }
+    }
+
+    downsampled
+}
+
+impl<R> Acquisition<R> {
+    /// Returns the grid of tiles for each level of the pyramid of the acquisition
+    pub fn tile_grid(&self) -> TileGrid {
+        TileGrid::new(self.width().max(0) as u32, self.height().max(0) as u32)
+    }
+
+    /// Create an image of the channel from pixels downsampled by `factor`. A pixel is acquired if the first
+    /// pixel it covers was, so (as for the full resolution image) the acquired pixels come first.
+    pub(crate) fn downsampled_image(
+        &self,
+        channel: &AcquisitionChannel,
+        factor: u32,
+        region: Region,
+        data: Vec<f32>,
+    ) -> ChannelImage {
+        let valid_region = self.valid_region();
+
+        let valid_pixels = (region.y..region.y + region.height)
+            .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| valid_region.contains(x * factor, y * factor))
+            .count();
+
+        // NaN (missing) pixels are ignored, as comparisons with NaN are always false
+        let range = data
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (
+                    if value < min { value } else { min },
+                    if value > max { value } else { max },
+                )
+            });
+
+        ChannelImage {
+            region,
+            acquisition_id: channel.acquisition_id(),
+            name: channel.name().to_string(),
+            label: channel.label().to_string(),
+            range,
+            valid_pixels,
+            data,
+        }
+    }
+}
+
+impl<R: Read + Seek> Acquisition<R> {
+    /// Returns the tile (`x`, `y`) of the `level` of the pyramid (see `TileGrid`) for the channel. The region of
+    /// the returned image is in pixels of the level.
+    ///
+    /// Tiles of level 0 are read as for `channel_image()`. Tiles of the downsampled levels are read from the
+    /// .dcm file if the pyramid has been precomputed (see `MCD::precompute_pyramid()`), and are otherwise
+    /// computed from the full resolution pixels they cover.
+    pub fn tile<C: Into<ChannelIdentifier>>(
+        &self,
+        identifier: C,
+        level: u32,
+        x: u32,
+        y: u32,
+    ) -> Result<ChannelImage> {
+        let identifier = identifier.into();
+        let grid = self.tile_grid();
+        let region = grid
+            .tile_region(level, x, y)
+            .ok_or(MCDError::InvalidTile { level, x, y })?;
+
+        if level == 0 {
+            return self.channel_image(identifier, Some(region));
+        }
+
+        let channel = self
+            .channel(&identifier)
+            .ok_or_else(|| channel_not_found(&identifier, self.channels()))?;
+
+        let stored = match &self.dcm_location {
+            Some(location) => location.read_tile(channel.order_number() as u16, level, x, y)?,
+            None => None,
+        };
+
+        let data = match stored {
+            Some(data) => data,
+            None => {
+                let source = grid.source_region(level, &region);
+                let image = self.channel_image(identifier, Some(source))?;
+
+                downsample(
+                    &image.data,
+                    source.width,
+                    source.height,
+                    TileGrid::downsampling(level),
+                )
+            }
+        };
+
+        Ok(self.downsampled_image(channel, TileGrid::downsampling(level), region, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_levels() {
+        let grid = TileGrid::new(1000, 300);
+
+        assert_eq!(grid.num_levels(), 3);
+        assert_eq!(grid.level_size(0), Some((1000, 300)));
+        assert_eq!(grid.level_size(2), Some((250, 75)));
+        assert_eq!(grid.level_size(3), None);
+
+        assert_eq!(grid.num_tiles(0), Some((4, 2)));
+        assert_eq!(grid.num_tiles(2), Some((1, 1)));
+
+        let edge = grid.tile_region(0, 3, 1).expect("tile exists");
+        assert_eq!(
+            (edge.x, edge.y, edge.width, edge.height),
+            (768, 256, 232, 44)
+        );
+        assert!(grid.tile_region(0, 4, 0).is_none());
+
+        let source = grid.source_region(
+            1,
+            &Region {
+                x: 256,
+                y: 0,
+                width: 244,
+                height: 150,
+            },
+        );
+        assert_eq!((source.x, source.width, source.height), (512, 488, 300));
+
+        assert_eq!(TileGrid::new(1, 1).num_levels(), 1);
+    }
+
+    #[test]
+    fn downsample_ignores_missing() {
+        #[rustfmt::skip]
+        let data = [
+            1.0, 3.0, 5.0,
+            f32::NAN, 5.0, f32::NAN,
+            7.0, 7.0, 7.0,
+        ];
+
+        let downsampled = downsample(&data, 3, 3, 2);
+
+        assert_eq!(downsampled[..3], [3.0, 5.0, 7.0]);
+        assert!(downsample(&[f32::NAN; 4], 2, 2, 2)[0].is_nan());
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::Write;
+
+use crate::{
+    error::{MCDError, Result},
+    BoundingBox,
+};
+
+/// A single tile of a `Tiling`, describing a proposed acquisition (ROI) on the slide
+#[derive(Debug, Clone)]
+pub struct Tile {
+    name: String,
+    row: u32,
+    column: u32,
+    bounding_box: BoundingBox<f64>,
+}
+
+impl Tile {
+    /// Returns the name of the tile (`ROI_001`, `ROI_002`, ... in row-major order)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the row of the tile within the grid (0 being the top row)
+    pub fn row(&self) -> u32 {
+        self.row
+    }
+
+    /// Returns the column of the tile within the grid (0 being the leftmost column)
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// Returns the region of the slide (in μm) covered by the tile
+    pub fn bounding_box(&self) -> &BoundingBox<f64> {
+        &self.bounding_box
+    }
+}
+
+/// A grid of tiles covering a region of the slide, proposed as acquisitions (see `Slide::proposed_tiling()`).
+/// The tiling can be written as .csv or .json, to be used as a template when planning acquisitions.
+#[derive(Debug, Clone)]
+pub struct Tiling {
+    slide_id: u16,
+    tile_size: f64,
+    overlap: f64,
+    num_rows: u32,
+    num_columns: u32,
+    tiles: Vec<Tile>,
+}
+
+impl Tiling {
+    /// Create a grid of `tile_size` x `tile_size` μm tiles covering the region (in μm), with adjacent tiles
+    /// overlapping by `overlap` μm. Tiles start at the top left of the region, and the last row and column may
+    /// extend beyond the region so that it is completely covered.
+    pub(crate) fn new(
+        slide_id: u16,
+        region: &BoundingBox<f64>,
+        tile_size: f64,
+        overlap: f64,
+    ) -> Result<Self> {
+        let step = tile_size - overlap;
+
+        if !(tile_size > 0.0 && overlap >= 0.0 && step > 0.0) {
+            return Err(MCDError::InvalidTiling { tile_size, overlap });
+        }
+
+        let num_tiles = |length: f64| ((length - overlap) / step).ceil().max(1.0) as u32;
+        let num_columns = num_tiles(region.width);
+        let num_rows = num_tiles(region.height);
+
+        let mut tiles = Vec::with_capacity((num_rows * num_columns) as usize);
+        for row in 0..num_rows {
+            for column in 0..num_columns {
+                tiles.push(Tile {
+                    name: format!("ROI_{:03}", tiles.len() + 1),
+                    row,
+                    column,
+                    bounding_box: BoundingBox {
+                        min_x: region.min_x + column as f64 * step,
+                        min_y: region.min_y + row as f64 * step,
+                        width: tile_size,
+                        height: tile_size,
+                    },
+                });
+            }
+        }
+
+        Ok(Tiling {
+            slide_id,
+            tile_size,
+            overlap,
+            num_rows,
+            num_columns,
+            tiles,
+        })
+    }
+
+    /// Returns the ID of the slide the tiling was proposed for
+    pub fn slide_id(&self) -> u16 {
+        self.slide_id
+    }
+
+    /// Returns the size (width and height, in μm) of each tile
+    pub fn tile_size(&self) -> f64 {
+        self.tile_size
+    }
+
+    /// Returns the overlap (in μm) between adjacent tiles
+    pub fn overlap(&self) -> f64 {
+        self.overlap
+    }
+
+    /// Returns the number of rows in the grid
+    pub fn num_rows(&self) -> u32 {
+        self.num_rows
+    }
+
+    /// Returns the number of columns in the grid
+    pub fn num_columns(&self) -> u32 {
+        self.num_columns
+    }
+
+    /// Returns all tiles, in row-major order
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// Write the tiling as .csv, with one row per tile
+    pub fn to_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        writer.write_record([
+            "name", "row", "column", "min_x_um", "min_y_um", "max_x_um", "max_y_um",
+        ])?;
+
+        for tile in &self.tiles {
+            writer.write_record([
+                tile.name.clone(),
+                tile.row.to_string(),
+                tile.column.to_string(),
+                tile.bounding_box.min_x.to_string(),
+                tile.bounding_box.min_y.to_string(),
+                tile.bounding_box.max_x().to_string(),
+                tile.bounding_box.max_y().to_string(),
+            ])?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Write the tiling as .json, including the slide ID, tile size and overlap
+    pub fn to_json<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"slide_id\": {},", self.slide_id)?;
+        writeln!(writer, "  \"tile_size_um\": {},", self.tile_size)?;
+        writeln!(writer, "  \"overlap_um\": {},", self.overlap)?;
+        writeln!(writer, "  \"rows\": {},", self.num_rows)?;
+        writeln!(writer, "  \"columns\": {},", self.num_columns)?;
+        writeln!(writer, "  \"tiles\": [")?;
+
+        for (index, tile) in self.tiles.iter().enumerate() {
+            let separator = if index + 1 < self.tiles.len() {
+                ","
+            } else {
+                ""
+            };
+
+            writeln!(
+                writer,
+                "    {{\"name\": \"{}\", \"row\": {}, \"column\": {}, \"min_x_um\": {}, \"min_y_um\": {}, \"max_x_um\": {}, \"max_y_um\": {}}}{}",
+                tile.name,
+                tile.row,
+                tile.column,
+                tile.bounding_box.min_x,
+                tile.bounding_box.min_y,
+                tile.bounding_box.max_x(),
+                tile.bounding_box.max_y(),
+                separator
+            )?;
+        }
+
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiling_covers_region() -> Result<()> {
+        let region = BoundingBox {
+            min_x: 100.0,
+            min_y: 200.0,
+            width: 1000.0,
+            height: 400.0,
+        };
+        let tiling = Tiling::new(1, &region, 500.0, 50.0)?;
+
+        // Columns start at 100, 550, 1000 (reaching 1500) and a single row reaches 700
+        assert_eq!((tiling.num_rows(), tiling.num_columns()), (1, 3));
+        let last = &tiling.tiles()[2];
+        assert_eq!(last.name(), "ROI_003");
+        assert_eq!(last.bounding_box().min_x, 1000.0);
+        assert!(Tiling::new(1, &region, 50.0, 50.0).is_err());
+
+        let mut csv = Vec::new();
+        tiling.to_csv(&mut csv)?;
+        assert_eq!(String::from_utf8_lossy(&csv).lines().count(), 4);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use crate::parse_report::Stopwatch;
+
+type Hook = Arc<dyn Fn(&SpanRecord) + Send + Sync>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Checked before a span is started, so that spans cost almost nothing when no hook is installed
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Operation timed by a span
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Reading and parsing the XML metadata of an .mcd file
+    Parse,
+    /// Converting an .mcd file to a .dcm file
+    ConvertDcm,
+    /// Reading and decompressing chunks of channel images from a .dcm file
+    ReadChunks,
+    /// Decoding an optical image (e.g. a panorama)
+    DecodeImage,
+}
+
+impl Operation {
+    /// Returns the name of the operation (e.g. `read_chunks`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Parse => "parse",
+            Operation::ConvertDcm => "convert_dcm",
+            Operation::ReadChunks => "read_chunks",
+            Operation::DecodeImage => "decode_image",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A completed span, passed to the hook installed with [`set_hook`]
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    operation: Operation,
+    target: String,
+    fields: Vec<(&'static str, String)>,
+    duration: Duration,
+}
+
+impl SpanRecord {
+    /// Returns the operation which was timed
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Returns what the operation was performed on (e.g. `Panorama 1 image`), which is empty if there is no
+    /// more specific description than the operation
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the fields recorded for the operation (e.g. the number of bytes read), in the order they were
+    /// recorded
+    pub fn fields(&self) -> &[(&'static str, String)] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the given name, if recorded
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the time taken by the operation. This is always zero on `wasm32-unknown-unknown`, where no clock
+    /// is available.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl fmt::Display for SpanRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if !self.target.is_empty() {
+            write!(f, " [{}]", self.target)?;
+        }
+        write!(f, " took {:?}", self.duration)?;
+
+        for (name, value) in &self.fields {
+            write!(f, " {}={}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Install `hook` to be called as each span (parsing, .dcm conversion, chunk reads and image decoding)
+/// completes, replacing any previously installed hook. This allows applications to diagnose slow files, for
+/// example by forwarding the spans to `tracing` or `log`.
+///
+/// ```
+/// imc_rs::trace::set_hook(|span| eprintln!("{}", span));
+///
+/// // e.g. "parse took 1.2ms xml_bytes=20480 slides=1 acquisitions=4"
+/// # imc_rs::trace::clear_hook();
+/// ```
+pub fn set_hook<F: Fn(&SpanRecord) + Send + Sync + 'static>(hook: F) {
+    replace_hook(Some(Arc::new(hook)));
+}
+
+/// Remove the hook installed with [`set_hook`], if any
+pub fn clear_hook() {
+    replace_hook(None);
+}
+
+fn replace_hook(hook: Option<Hook>) {
+    let enabled = hook.is_some();
+
+    match HOOK.write() {
+        Ok(mut current) => *current = hook,
+        Err(poisoned) => *poisoned.into_inner() = hook,
+    }
+    ENABLED.store(enabled, Ordering::Release);
+}
+
+fn hook() -> Option<Hook> {
+    match HOOK.read() {
+        Ok(hook) => hook.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Span timing an operation, which is reported to the hook (if any) when dropped
+pub(crate) struct Span {
+    active: Option<(SpanRecord, Stopwatch)>,
+}
+
+impl Span {
+    /// Record a field of the operation (e.g. the number of bytes read)
+    pub(crate) fn record<V: fmt::Display>(&mut self, name: &'static str, value: V) {
+        if let Some((record, _)) = &mut self.active {
+            record.fields.push((name, value.to_string()));
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((mut record, stopwatch)) = self.active.take() {
+            record.duration = stopwatch.elapsed();
+
+            if let Some(hook) = hook() {
+                hook(&record);
+            }
+        }
+    }
+}
+
+/// Start a span timing `operation`. The target is only described if a hook is installed.
+pub(crate) fn span<F: FnOnce() -> String>(operation: Operation, target: F) -> Span {
+    if !ENABLED.load(Ordering::Acquire) {
+        return Span { active: None };
+    }
+
+    Span {
+        active: Some((
+            SpanRecord {
+                operation,
+                target: target(),
+                fields: Vec::new(),
+                duration: Duration::ZERO,
+            },
+            Stopwatch::start(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{error::Result, mcd::synthetic_mcd, AcquisitionSpec, MCD};
+
+    #[test]
+    fn reports_parse_spans() -> Result<()> {
+        let data = synthetic_mcd([(
+            AcquisitionSpec::new(1, 1, 1, 1).channel("Ir191", "DNA1"),
+            [1.0],
+        )])?;
+
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let recorded = spans.clone();
+        set_hook(move |span| {
+            if let Ok(mut spans) = recorded.lock() {
+                spans.push(span.clone());
+            }
+        });
+        let mcd = MCD::from_bytes(data);
+        clear_hook();
+        mcd?;
+
+        // Other tests may parse files at the same time, so only check that a parse was reported
+        let spans = spans.lock().or(Err(crate::error::MCDError::PoisonMutex))?;
+        let parse = spans
+            .iter()
+            .find(|span| span.operation() == Operation::Parse && span.field("slides") == Some("1"));
+        assert!(parse.is_some_and(|span| span.field("xml_bytes").is_some()));
+
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 extern crate num_traits;
 
-use nalgebra::{DMatrix, Dim, Matrix3, VecStorage, Vector2, Vector3, QR};
+use nalgebra::{DMatrix, Dim, Matrix3, RealField, VecStorage, Vector2, Vector3, QR};
 
-#[derive(Debug)]
+mod tps;
+
+pub use tps::ThinPlateSpline;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Describes the direction in which the transform is performed
 pub enum Direction {
     /// Slide is "fixed" and the transform describes transformation from another space to the slide space
@@ -33,8 +37,39 @@ impl<T> TransformScalar for T where
 {
 }
 
+/// A mapping between an external space (e.g. the pixels of an image) and the slide space, which can be used in
+/// place of the `AffineTransform` from `OnSlide::to_slide_transform` (see the `_with` conversions in
+/// [`crate::coords`])
+pub trait Transform2D<T> {
+    /// Transforms a point in the external space to the slide space, or returns None if the transform is not
+    /// defined in this direction
+    fn to_slide(&self, x: T, y: T) -> Option<(T, T)>;
+
+    /// Transforms a point in slide space to the external space, or returns None if the transform is not defined
+    /// in this direction
+    #[allow(clippy::wrong_self_convention)]
+    fn from_slide(&self, x: T, y: T) -> Option<(T, T)>;
+}
+
 /// AffineTransform describes a mapping from one space to another while preserving parallel lines.
-#[derive(Debug)]
+///
+/// Transforms can be built up from translations, scalings and rotations, composed with other transforms and
+/// decomposed again:
+///
+/// ```
+/// use imc_rs::transform::AffineTransform;
+///
+/// let transform = AffineTransform::scaling(2.0, 2.0)
+///     .then_rotate(std::f64::consts::FRAC_PI_2)
+///     .then_translate(10.0, 0.0);
+///
+/// let point = transform.transform_to_slide(1.0, 0.0).unwrap();
+/// assert!((point.x - 10.0).abs() < 1e-9 && (point.y - 2.0).abs() < 1e-9);
+///
+/// let decomposition = transform.decompose();
+/// assert!((decomposition.scale.0 - 2.0).abs() < 1e-9);
+/// ```
+#[derive(Debug, Clone)]
 pub struct AffineTransform<T>
 where
     T: TransformScalar,
@@ -171,5 +206,292 @@ where
         Some(point)
     }
 
-    //pub fn transform_point(&self,
+    /// Create a transform (towards the slide) from the matrix, which is applied to points (x, y, 1)
+    pub fn from_matrix(matrix: Matrix3<T>) -> Self {
+        AffineTransform {
+            direction: Direction::ToSlide,
+            matrix,
+            inv_matix: matrix.try_inverse(),
+        }
+    }
+
+    /// Returns the transform (towards the slide) translating points by (`tx`, `ty`)
+    pub fn translation(tx: T, ty: T) -> Self {
+        let mut matrix = Matrix3::identity();
+        matrix.m13 = tx;
+        matrix.m23 = ty;
+
+        Self::from_matrix(matrix)
+    }
+
+    /// Returns the transform (towards the slide) scaling points by `sx` and `sy` about the origin
+    pub fn scaling(sx: T, sy: T) -> Self {
+        let mut matrix = Matrix3::identity();
+        matrix.m11 = sx;
+        matrix.m22 = sy;
+
+        Self::from_matrix(matrix)
+    }
+
+    /// Returns the direction in which the transform is performed
+    pub fn direction(&self) -> &Direction {
+        &self.direction
+    }
+
+    /// Returns the matrix in the direction of the transform, or None if the other transform has no matrix in
+    /// that direction (it can't be inverted)
+    fn matrix_in(&self, direction: Direction) -> Option<&Matrix3<T>> {
+        match direction {
+            Direction::ToSlide => self.to_slide_matrix(),
+            Direction::FromSlide => self.from_slide_matrix(),
+        }
+    }
+
+    /// Returns the transform applying this transform followed by `other` (in the direction of this transform),
+    /// or None if `other` is in the other direction and can't be inverted
+    pub fn compose(&self, other: &AffineTransform<T>) -> Option<AffineTransform<T>> {
+        let matrix = other.matrix_in(self.direction)? * self.matrix;
+
+        Some(AffineTransform {
+            direction: self.direction,
+            matrix,
+            inv_matix: matrix.try_inverse(),
+        })
+    }
+
+    /// Apply the matrix after this transform (in the direction of this transform)
+    fn then(self, matrix: Matrix3<T>) -> Self {
+        let matrix = matrix * self.matrix;
+
+        AffineTransform {
+            direction: self.direction,
+            matrix,
+            inv_matix: matrix.try_inverse(),
+        }
+    }
+
+    /// Follow this transform by a translation of (`tx`, `ty`)
+    pub fn then_translate(self, tx: T, ty: T) -> Self {
+        self.then(Self::translation(tx, ty).matrix)
+    }
+
+    /// Follow this transform by scaling by `sx` and `sy` about the origin
+    pub fn then_scale(self, sx: T, sy: T) -> Self {
+        self.then(Self::scaling(sx, sy).matrix)
+    }
+
+    /// Transforms the points in the external space to the slide space
+    pub fn transform_points_to_slide(&self, points: &[(T, T)]) -> Option<Vec<(T, T)>> {
+        Some(transform_points(self.to_slide_matrix()?, points))
+    }
+
+    /// Transforms the points in slide space to the external space
+    pub fn transform_points_from_slide(&self, points: &[(T, T)]) -> Option<Vec<(T, T)>> {
+        Some(transform_points(self.from_slide_matrix()?, points))
+    }
+
+    /// Transforms the points (an array with one row of (x, y) per point) in the external space to the slide
+    /// space. Returns None if the array does not have 2 columns or there is no transform towards the slide.
+    #[cfg(feature = "ndarray")]
+    pub fn transform_array_to_slide(
+        &self,
+        points: ndarray::ArrayView2<T>,
+    ) -> Option<ndarray::Array2<T>> {
+        transform_array(self.to_slide_matrix()?, points)
+    }
+
+    /// Transforms the points (an array with one row of (x, y) per point) in slide space to the external space.
+    /// Returns None if the array does not have 2 columns or there is no transform from the slide.
+    #[cfg(feature = "ndarray")]
+    pub fn transform_array_from_slide(
+        &self,
+        points: ndarray::ArrayView2<T>,
+    ) -> Option<ndarray::Array2<T>> {
+        transform_array(self.from_slide_matrix()?, points)
+    }
+}
+
+impl<T: TransformScalar> Transform2D<T> for AffineTransform<T> {
+    fn to_slide(&self, x: T, y: T) -> Option<(T, T)> {
+        let point = self.transform_to_slide(x, y)?;
+
+        Some((point.x, point.y))
+    }
+
+    fn from_slide(&self, x: T, y: T) -> Option<(T, T)> {
+        let point = self.transform_from_slide(x, y)?;
+
+        Some((point.x, point.y))
+    }
+}
+
+/// Decomposition of an affine transform into a scaling, followed by a shear (along x), a rotation and a
+/// translation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decomposition<T> {
+    /// Translation (x, y)
+    pub translation: (T, T),
+    /// Rotation (anti-clockwise, in radians)
+    pub rotation: T,
+    /// Scale along x and y. The scale along y is negative if the transform includes a reflection.
+    pub scale: (T, T),
+    /// Shear factor along x (0 if there is no shear)
+    pub shear: T,
+}
+
+impl<T> AffineTransform<T>
+where
+    T: TransformScalar + RealField,
+{
+    /// Returns the transform (towards the slide) rotating points anti-clockwise by `angle` (in radians) about the
+    /// origin
+    pub fn rotation(angle: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+
+        let mut matrix = Matrix3::identity();
+        matrix.m11 = cos;
+        matrix.m12 = -sin;
+        matrix.m21 = sin;
+        matrix.m22 = cos;
+
+        Self::from_matrix(matrix)
+    }
+
+    /// Follow this transform by rotating anti-clockwise by `angle` (in radians) about the origin
+    pub fn then_rotate(self, angle: T) -> Self {
+        self.then(Self::rotation(angle).matrix)
+    }
+
+    /// Decompose the transform (in its direction) into a scaling, shear, rotation and translation, such that the
+    /// transform is equivalent to `scaling(scale.0, scale.1)`, followed by the shear, `then_rotate(rotation)` and
+    /// `then_translate(translation.0, translation.1)`
+    pub fn decompose(&self) -> Decomposition<T> {
+        let (a, b, c, d) = (
+            self.matrix.m11,
+            self.matrix.m12,
+            self.matrix.m21,
+            self.matrix.m22,
+        );
+
+        let scale_x = a.hypot(c);
+        let rotation = c.atan2(a);
+        let scale_y = (a * d - b * c) / scale_x;
+        let shear = (a * b + c * d) / (scale_x * scale_y);
+
+        Decomposition {
+            translation: (self.matrix.m13, self.matrix.m23),
+            rotation,
+            scale: (scale_x, scale_y),
+            shear,
+        }
+    }
+}
+
+fn transform_points<T: TransformScalar>(matrix: &Matrix3<T>, points: &[(T, T)]) -> Vec<(T, T)> {
+    points
+        .iter()
+        .map(|&(x, y)| {
+            let point = matrix * Vector3::new(x, y, T::one());
+            (point.x, point.y)
+        })
+        .collect()
+}
+
+#[cfg(feature = "ndarray")]
+fn transform_array<T: TransformScalar>(
+    matrix: &Matrix3<T>,
+    points: ndarray::ArrayView2<T>,
+) -> Option<ndarray::Array2<T>> {
+    if points.ncols() != 2 {
+        return None;
+    }
+
+    let mut transformed = ndarray::Array2::zeros(points.raw_dim());
+    for (point, mut output) in points.rows().into_iter().zip(transformed.rows_mut()) {
+        let point = matrix * Vector3::new(point[0], point[1], T::one());
+        output[0] = point.x;
+        output[1] = point.y;
+    }
+
+    Some(transformed)
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use serde_core::{ser::SerializeStruct, Serialize, Serializer};
+
+    use super::*;
+
+    impl Serialize for Direction {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(match self {
+                Direction::ToSlide => "ToSlide",
+                Direction::FromSlide => "FromSlide",
+            })
+        }
+    }
+
+    /// The matrix is serialised row by row
+    impl<T: TransformScalar + Serialize> Serialize for AffineTransform<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let matrix: [[T; 3]; 3] =
+                [0, 1, 2].map(|row| [0, 1, 2].map(|column| self.matrix[(row, column)]));
+
+            let mut state = serializer.serialize_struct("AffineTransform", 2)?;
+            state.serialize_field("direction", &self.direction)?;
+            state.serialize_field("matrix", &matrix)?;
+            state.end()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn compose_and_decompose() {
+        let transform = AffineTransform::scaling(2.0, 3.0)
+            .then_rotate(0.5)
+            .then_translate(10.0, -5.0);
+
+        let decomposition = transform.decompose();
+        assert_close(decomposition.scale, (2.0, 3.0));
+        assert_close((decomposition.rotation, decomposition.shear), (0.5, 0.0));
+        assert_close(decomposition.translation, (10.0, -5.0));
+
+        // Composing with the inverse gives the identity
+        let inverse = AffineTransform::from_matrix(
+            *transform
+                .from_slide_matrix()
+                .expect("Transform should be invertible"),
+        );
+        let identity = transform
+            .compose(&inverse)
+            .expect("Inverse should have a matrix");
+        let points = identity
+            .transform_points_to_slide(&[(1.0, 2.0), (-3.0, 4.0)])
+            .expect("Transform should be invertible");
+        assert_close(points[0], (1.0, 2.0));
+        assert_close(points[1], (-3.0, 4.0));
+
+        let translated = AffineTransform::translation(1.0, 1.0)
+            .compose(&AffineTransform::scaling(2.0, 2.0))
+            .expect("Transform should be invertible");
+        assert_close(
+            translated
+                .transform_points_from_slide(&[(4.0, 4.0)])
+                .expect("Transform should be invertible")[0],
+            (1.0, 1.0),
+        );
+    }
 }
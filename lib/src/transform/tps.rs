@@ -0,0 +1,205 @@
+use nalgebra::DMatrix;
+
+use super::Transform2D;
+
+/// Thin plate spline mapping from an external space to the slide space, which passes exactly through each pair of
+/// control points (unless smoothed) while bending as little as possible in between. This captures the local,
+/// non-linear distortions (e.g. of a brightfield image of the tissue) which an `AffineTransform` can't.
+///
+/// The mapping from the slide is a second spline fitted to the control points in the opposite direction, so is
+/// only an approximate inverse between the control points.
+///
+/// ```
+/// use imc_rs::transform::{ThinPlateSpline, Transform2D};
+///
+/// let moving = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 10.0), (5.0, 5.0)];
+/// let fixed = [(100.0, 100.0), (120.0, 100.0), (100.0, 120.0), (120.0, 120.0), (111.0, 111.0)];
+///
+/// let spline = ThinPlateSpline::from_points(&moving, &fixed).unwrap();
+/// let (x, y) = spline.to_slide(5.0, 5.0).unwrap();
+/// assert!((x - 111.0).abs() < 1e-6 && (y - 111.0).abs() < 1e-6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThinPlateSpline {
+    to_slide: Spline,
+    from_slide: Spline,
+}
+
+impl ThinPlateSpline {
+    /// Fit the spline mapping each of the `moving_points` (external space) onto the corresponding `fixed_points`
+    /// (slide space). Returns None if there are fewer than 3 pairs of points, the number of points differ or the
+    /// points are collinear.
+    pub fn from_points(moving_points: &[(f64, f64)], fixed_points: &[(f64, f64)]) -> Option<Self> {
+        Self::smoothed(moving_points, fixed_points, 0.0)
+    }
+
+    /// Fit the spline as with `from_points`, but trading off passing through the control points against the
+    /// bending of the spline. Larger values of `smoothing` give a smoother spline, tending towards the affine
+    /// transform which best fits the points, which is more robust to errors in the placement of control points.
+    pub fn smoothed(
+        moving_points: &[(f64, f64)],
+        fixed_points: &[(f64, f64)],
+        smoothing: f64,
+    ) -> Option<Self> {
+        Some(ThinPlateSpline {
+            to_slide: Spline::fit(moving_points, fixed_points, smoothing)?,
+            from_slide: Spline::fit(fixed_points, moving_points, smoothing)?,
+        })
+    }
+
+    /// Returns the control points in the external space
+    pub fn moving_points(&self) -> &[(f64, f64)] {
+        &self.to_slide.points
+    }
+
+    /// Returns the control points in slide space
+    pub fn fixed_points(&self) -> &[(f64, f64)] {
+        &self.from_slide.points
+    }
+}
+
+impl Transform2D<f64> for ThinPlateSpline {
+    fn to_slide(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        Some(self.to_slide.evaluate(x, y))
+    }
+
+    fn from_slide(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        Some(self.from_slide.evaluate(x, y))
+    }
+}
+
+/// Thin plate spline in one direction: an affine part plus a weighted sum of the radial basis function centred on
+/// each control point
+#[derive(Debug, Clone)]
+struct Spline {
+    points: Vec<(f64, f64)>,
+    /// Weight of the basis function of each control point, for x and y
+    weights: Vec<(f64, f64)>,
+    /// Coefficients (constant, x, y) of the affine part, for x and y
+    affine: [(f64, f64); 3],
+}
+
+impl Spline {
+    fn fit(source: &[(f64, f64)], target: &[(f64, f64)], smoothing: f64) -> Option<Self> {
+        let n = source.len();
+        if n < 3 || n != target.len() {
+            return None;
+        }
+
+        // Solve [K + smoothing * I, P; P^T, 0] [w; a] = [v; 0]
+        let mut system = DMatrix::zeros(n + 3, n + 3);
+        for (i, &a) in source.iter().enumerate() {
+            for (j, &b) in source.iter().enumerate() {
+                system[(i, j)] = basis(a, b);
+            }
+            system[(i, i)] += smoothing;
+
+            for (k, value) in [1.0, a.0, a.1].into_iter().enumerate() {
+                system[(i, n + k)] = value;
+                system[(n + k, i)] = value;
+            }
+        }
+
+        let mut values = DMatrix::zeros(n + 3, 2);
+        for (i, &(x, y)) in target.iter().enumerate() {
+            values[(i, 0)] = x;
+            values[(i, 1)] = y;
+        }
+
+        let solution = system.lu().solve(&values)?;
+        if solution.iter().any(|value| !value.is_finite()) {
+            return None;
+        }
+
+        let coefficient = |row: usize| (solution[(row, 0)], solution[(row, 1)]);
+
+        Some(Spline {
+            points: source.to_vec(),
+            weights: (0..n).map(coefficient).collect(),
+            affine: [coefficient(n), coefficient(n + 1), coefficient(n + 2)],
+        })
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> (f64, f64) {
+        let [constant, along_x, along_y] = self.affine;
+        let basis_values: Vec<_> = self
+            .points
+            .iter()
+            .map(|&point| basis(point, (x, y)))
+            .collect();
+
+        let bend = |weight: fn(&(f64, f64)) -> f64| {
+            self.weights
+                .iter()
+                .zip(basis_values.iter())
+                .map(|(w, u)| weight(w) * u)
+                .sum::<f64>()
+        };
+
+        (
+            constant.0 + along_x.0 * x + along_y.0 * y + bend(|w| w.0),
+            constant.1 + along_x.1 * x + along_y.1 * y + bend(|w| w.1),
+        )
+    }
+}
+
+/// Radial basis function of the thin plate spline, U(r) = r² log(r)
+fn basis(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let r2 = (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+
+    if r2 > 0.0 {
+        0.5 * r2 * r2.ln()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spline_interpolates_control_points() {
+        let moving = [
+            (0.0, 0.0),
+            (100.0, 0.0),
+            (0.0, 100.0),
+            (100.0, 100.0),
+            (50.0, 50.0),
+        ];
+        // A bulge in the middle which an affine transform can't describe
+        let fixed = [
+            (0.0, 0.0),
+            (200.0, 0.0),
+            (0.0, 200.0),
+            (200.0, 200.0),
+            (110.0, 110.0),
+        ];
+
+        let spline =
+            ThinPlateSpline::from_points(&moving, &fixed).expect("Points should not be collinear");
+
+        for (&(x, y), &expected) in moving.iter().zip(&fixed) {
+            let point = spline.to_slide(x, y).expect("Spline is always defined");
+            assert!((point.0 - expected.0).abs() < 1e-6 && (point.1 - expected.1).abs() < 1e-6);
+
+            let point = spline
+                .from_slide(expected.0, expected.1)
+                .expect("Spline is always defined");
+            assert!((point.0 - x).abs() < 1e-6 && (point.1 - y).abs() < 1e-6);
+        }
+
+        // Heavy smoothing tends towards the best fitting affine transform
+        let smoothed = ThinPlateSpline::smoothed(&moving, &fixed, 1e12)
+            .expect("Points should not be collinear");
+        let point = smoothed
+            .to_slide(50.0, 50.0)
+            .expect("Spline is always defined");
+        assert!((point.0 - 102.0).abs() < 0.1);
+
+        assert!(
+            ThinPlateSpline::from_points(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)], &moving[..3])
+                .is_none()
+        );
+    }
+}
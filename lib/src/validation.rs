@@ -0,0 +1,759 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{Read, Seek},
+};
+
+use quick_xml::events::Event;
+
+use crate::{
+    describe::{Describe, Description, Table},
+    error::Result,
+    reader::ReaderPool,
+    MCD,
+};
+
+/// Known version of the MCDSchema XSD, identified by the namespace of the `MCDSchema` element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// The original schema (`MCDSchema.xsd`)
+    V1,
+    /// Version 2 of the schema (`MCDSchema_V2_0.xsd`), which adds calibrations, slide fiducial marks and profiles
+    V2,
+}
+
+impl SchemaVersion {
+    /// Returns the schema version with the specified namespace, or None if it is not known
+    pub fn from_xmlns(xmlns: &str) -> Option<Self> {
+        if xmlns.contains("MCDSchema_V2") {
+            Some(SchemaVersion::V2)
+        } else if xmlns.ends_with("MCDSchema.xsd") {
+            Some(SchemaVersion::V1)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaVersion::V1 => write!(f, "v1"),
+            SchemaVersion::V2 => write!(f, "v2"),
+        }
+    }
+}
+
+/// How serious a `ValidationIssue` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The file is inconsistent, so may not be read correctly (or at all)
+    Error,
+    /// The file deviates from the schema, but can still be read
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found when validating the XML of an .mcd file. Elements are identified by their tag (e.g.
+/// `Acquisition`) and the value of their `ID` (if present).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The namespace of the `MCDSchema` element is missing or not a known schema version
+    UnknownSchema {
+        /// The namespace, if present
+        xmlns: Option<String>,
+    },
+    /// A required child element is missing
+    MissingElement {
+        /// Tag of the element
+        element: String,
+        /// ID of the element
+        id: Option<String>,
+        /// Tag of the missing child element
+        name: String,
+    },
+    /// An element is not part of any known schema version
+    UnknownTag {
+        /// Tag of the parent element
+        parent: String,
+        /// Tag of the unknown element
+        name: String,
+    },
+    /// An element is not part of the schema version declared by the file
+    WrongVersion {
+        /// Tag of the parent element
+        parent: String,
+        /// Tag of the element
+        name: String,
+        /// The schema version declared by the file
+        version: SchemaVersion,
+    },
+    /// More than one element of the same type has the same ID
+    DuplicateId {
+        /// Tag of the elements
+        element: String,
+        /// The repeated ID
+        id: String,
+    },
+    /// An element refers (by ID) to an element which does not exist
+    MissingReference {
+        /// Tag of the element
+        element: String,
+        /// ID of the element
+        id: Option<String>,
+        /// Tag of the child element holding the reference (e.g. `AcquisitionID`)
+        field: String,
+        /// Tag of the element referred to (e.g. `Acquisition`)
+        target: String,
+        /// ID of the element referred to
+        target_id: String,
+    },
+}
+
+impl ValidationIssue {
+    /// Returns how serious the issue is
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationIssue::UnknownSchema { .. }
+            | ValidationIssue::UnknownTag { .. }
+            | ValidationIssue::WrongVersion { .. } => Severity::Warning,
+            ValidationIssue::MissingElement { .. }
+            | ValidationIssue::DuplicateId { .. }
+            | ValidationIssue::MissingReference { .. } => Severity::Error,
+        }
+    }
+}
+
+/// Formats the element tag with its ID (if known)
+fn element_name(element: &str, id: &Option<String>) -> String {
+    match id {
+        Some(id) => format!("{} {}", element, id),
+        None => element.to_string(),
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::UnknownSchema { xmlns: Some(xmlns) } => {
+                write!(f, "Unknown schema namespace {}", xmlns)
+            }
+            ValidationIssue::UnknownSchema { xmlns: None } => write!(f, "Missing schema namespace"),
+            ValidationIssue::MissingElement { element, id, name } => {
+                write!(f, "{} is missing {}", element_name(element, id), name)
+            }
+            ValidationIssue::UnknownTag { parent, name } => {
+                write!(f, "Unknown element {} in {}", name, parent)
+            }
+            ValidationIssue::WrongVersion {
+                parent,
+                name,
+                version,
+            } => write!(
+                f,
+                "Element {} in {} is not part of schema {}",
+                name, parent, version
+            ),
+            ValidationIssue::DuplicateId { element, id } => {
+                write!(f, "More than one {} has ID {}", element, id)
+            }
+            ValidationIssue::MissingReference {
+                element,
+                id,
+                field,
+                target,
+                target_id,
+            } => write!(
+                f,
+                "{} refers to {} {} ({}), which does not exist",
+                element_name(element, id),
+                target,
+                target_id,
+                field
+            ),
+        }
+    }
+}
+
+/// Result of validating the XML of an .mcd file against the known versions of the MCDSchema XSD (see
+/// [`validate`] and [`MCD::validate`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    xmlns: Option<String>,
+    version: Option<SchemaVersion>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns the namespace of the `MCDSchema` element, if present
+    pub fn xmlns(&self) -> Option<&str> {
+        self.xmlns.as_deref()
+    }
+
+    /// Returns the schema version declared by the file, if known
+    pub fn version(&self) -> Option<SchemaVersion> {
+        self.version
+    }
+
+    /// Returns all issues found, in the order they appear in the XML
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Returns the issues with the specified severity
+    pub fn issues_with(&self, severity: Severity) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity() == severity)
+    }
+
+    /// Returns true if no errors were found (there may still be warnings)
+    pub fn is_valid(&self) -> bool {
+        self.issues_with(Severity::Error).next().is_none()
+    }
+}
+
+impl Describe for ValidationReport {
+    fn describe(&self) -> Description {
+        let version = match self.version {
+            Some(version) => version.to_string(),
+            None => "unknown".to_string(),
+        };
+
+        let table = self.issues.iter().fold(
+            Table::new("Issues", &["Severity", "Issue"]),
+            |table, issue| {
+                table.row(vec![
+                    issue.severity().to_string().into(),
+                    issue.to_string().into(),
+                ])
+            },
+        );
+
+        Description::new("Validation")
+            .field("Schema version", version)
+            .field("Valid", self.is_valid())
+            .field("Errors", self.issues_with(Severity::Error).count())
+            .field("Warnings", self.issues_with(Severity::Warning).count())
+            .table(table)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.describe().write_text(f, 0)
+    }
+}
+
+/// Child elements of an element type in the schema
+struct ElementSchema {
+    name: &'static str,
+    /// Children which must be present
+    required: &'static [&'static str],
+    /// Children which may be present in either version
+    optional: &'static [&'static str],
+    /// Children only present in version 1
+    v1_only: &'static [&'static str],
+    /// Children only present in version 2
+    v2_only: &'static [&'static str],
+    /// Children holding the ID of another element (child, target element)
+    references: &'static [(&'static str, &'static str)],
+    /// Whether the element type is only present in version 2
+    is_v2_only: bool,
+}
+
+impl ElementSchema {
+    fn is_known(&self, child: &str) -> bool {
+        [self.required, self.optional, self.v1_only, self.v2_only]
+            .iter()
+            .any(|children| children.contains(&child))
+    }
+
+    fn in_version(&self, child: &str, version: SchemaVersion) -> bool {
+        match version {
+            SchemaVersion::V1 => !self.v2_only.contains(&child),
+            SchemaVersion::V2 => !self.v1_only.contains(&child),
+        }
+    }
+}
+
+const SCHEMA: &[ElementSchema] = &[
+    ElementSchema {
+        name: "Slide",
+        required: &["ID"],
+        optional: &[
+            "Description",
+            "Filename",
+            "SlideType",
+            "WidthUm",
+            "HeightUm",
+            "ImageStartOffset",
+            "ImageEndOffset",
+            "ImageFile",
+            "SwVersion",
+        ],
+        v1_only: &["UID"],
+        v2_only: &[
+            "EnergyDb",
+            "Frequency",
+            "FMarkSlideLength",
+            "FMarkSlideThickness",
+            "Name",
+        ],
+        references: &[],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "Panorama",
+        required: &["ID", "SlideID"],
+        optional: &[
+            "Description",
+            "SlideX1PosUm",
+            "SlideY1PosUm",
+            "SlideX2PosUm",
+            "SlideY2PosUm",
+            "SlideX3PosUm",
+            "SlideY3PosUm",
+            "SlideX4PosUm",
+            "SlideY4PosUm",
+            "ImageStartOffset",
+            "ImageEndOffset",
+            "PixelWidth",
+            "PixelHeight",
+            "ImageFormat",
+            "PixelScaleCoef",
+        ],
+        v1_only: &[],
+        v2_only: &["Type", "IsLocked", "RotationAngle"],
+        references: &[("SlideID", "Slide")],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "AcquisitionROI",
+        required: &["ID", "PanoramaID"],
+        optional: &["ROIType"],
+        v1_only: &[],
+        v2_only: &["Description"],
+        references: &[("PanoramaID", "Panorama")],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "ROIPoint",
+        required: &["ID", "AcquisitionROIID"],
+        optional: &[
+            "OrderNumber",
+            "SlideXPosUm",
+            "SlideYPosUm",
+            "PanoramaPixelXPos",
+            "PanoramaPixelYPos",
+        ],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("AcquisitionROIID", "AcquisitionROI")],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "Acquisition",
+        required: &[
+            "ID",
+            "AcquisitionROIID",
+            "DataStartOffset",
+            "DataEndOffset",
+            "SegmentDataFormat",
+            "ValueBytes",
+        ],
+        optional: &[
+            "Description",
+            "AblationPower",
+            "AblationDistanceBetweenShotsX",
+            "AblationDistanceBetweenShotsY",
+            "AblationFrequency",
+            "OrderNumber",
+            "SignalType",
+            "DualCountStart",
+            "StartTimeStamp",
+            "EndTimeStamp",
+            "AfterAblationImageStartOffset",
+            "AfterAblationImageEndOffset",
+            "BeforeAblationImageStartOffset",
+            "BeforeAblationImageEndOffset",
+            "ROIStartXPosUm",
+            "ROIStartYPosUm",
+            "ROIEndXPosUm",
+            "ROIEndYPosUm",
+            "MovementType",
+        ],
+        v1_only: &[],
+        v2_only: &[
+            "MaxX",
+            "MaxY",
+            "PlumeStart",
+            "PlumeEnd",
+            "Template",
+            "ProfilingType",
+        ],
+        references: &[("AcquisitionROIID", "AcquisitionROI")],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "AcquisitionChannel",
+        required: &["ID", "ChannelName", "OrderNumber", "AcquisitionID"],
+        optional: &["ChannelLabel"],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("AcquisitionID", "Acquisition")],
+        is_v2_only: false,
+    },
+    ElementSchema {
+        name: "Calibration",
+        required: &["ID"],
+        optional: &["AcquisitionID", "TimeStamp", "Timestamp"],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("AcquisitionID", "Acquisition")],
+        is_v2_only: true,
+    },
+    ElementSchema {
+        name: "CalibrationFinal",
+        required: &["ID"],
+        optional: &[
+            "AcquisitionID",
+            "TimeStamp",
+            "Timestamp",
+            "OptimalDetectorVoltageStart",
+            "OptimalDetectorVoltageEnd",
+            "OptimalDetectorDualCoefficientStart",
+            "OptimalDetectorDualCoefficientEnd",
+            "OptimalHelium",
+            "TransientStart",
+            "TransientCrossTalk1",
+            "TransientCrossTalk2",
+            "ReferenceEnergy",
+            "MaximumEnergy",
+        ],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("AcquisitionID", "Acquisition")],
+        is_v2_only: true,
+    },
+    ElementSchema {
+        name: "CalibrationParams",
+        required: &["CalibrationID"],
+        optional: &[
+            "OptimalDetectorVoltage",
+            "OptimalDetectorDualCoefficient",
+            "OptimalMakeupGas",
+            "OptimalCurrent",
+            "OptimalX",
+            "OptimalY",
+            "TransientStart",
+            "TransientCrossTalk1",
+            "TransientCrossTalk2",
+            "OptimalHelium",
+        ],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("CalibrationID", "Calibration")],
+        is_v2_only: true,
+    },
+    ElementSchema {
+        name: "CalibrationChannel",
+        required: &["ID", "CalibrationID"],
+        optional: &["Name", "MeanDuals"],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("CalibrationID", "Calibration")],
+        is_v2_only: true,
+    },
+    ElementSchema {
+        name: "SlideFiducialMarks",
+        required: &["ID", "SlideID"],
+        optional: &["CoordinateX", "CoordinateY"],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("SlideID", "Slide")],
+        is_v2_only: true,
+    },
+    ElementSchema {
+        name: "SlideProfile",
+        required: &["ID", "SlideID"],
+        optional: &["CoordinateX", "CoordinateY"],
+        v1_only: &[],
+        v2_only: &[],
+        references: &[("SlideID", "Slide")],
+        is_v2_only: true,
+    },
+];
+
+/// An element directly within `MCDSchema`, with the text of each of its children
+struct Record {
+    schema: &'static ElementSchema,
+    children: HashMap<String, String>,
+}
+
+impl Record {
+    fn id(&self) -> Option<String> {
+        self.children.get("ID").cloned()
+    }
+}
+
+/// Validate the XML of the .mcd file read from `reader`. Unlike `MCD::parse()`, this does not fail (or panic)
+/// on inconsistent files, so can be used to find out why a file cannot be opened.
+///
+/// ```no_run
+/// use imc_rs::validation;
+///
+/// let file = std::fs::File::open("../test/20200612_FLU_1923.mcd").unwrap();
+/// let report = validation::validate(file).unwrap();
+///
+/// for issue in report.issues() {
+///     println!("{}: {}", issue.severity(), issue);
+/// }
+/// ```
+pub fn validate<R: Read + Seek>(reader: R) -> Result<ValidationReport> {
+    validate_xml(&MCD::new(ReaderPool::new(reader)).xml()?)
+}
+
+/// Validate the XML of an .mcd file (see [`MCD::xml`])
+pub fn validate_xml(xml: &str) -> Result<ValidationReport> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+
+    let mut xmlns = None;
+    let mut issues = Vec::new();
+    let mut records = Vec::new();
+
+    // Tags of the currently open elements
+    let mut open: Vec<String> = Vec::new();
+    let mut current: Option<Record> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader.read_event()?;
+
+        match &event {
+            Event::Start(element) | Event::Empty(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+
+                match open.len() {
+                    0 if name == "MCDSchema" => {
+                        for attribute in element.attributes().flatten() {
+                            if attribute.key.as_ref() == b"xmlns" {
+                                xmlns = Some(attribute.unescape_value()?.into_owned());
+                            }
+                        }
+                    }
+                    1 => match SCHEMA.iter().find(|schema| schema.name == name) {
+                        Some(schema) => {
+                            current = Some(Record {
+                                schema,
+                                children: HashMap::new(),
+                            })
+                        }
+                        None => issues.push(ValidationIssue::UnknownTag {
+                            parent: open[0].clone(),
+                            name: name.clone(),
+                        }),
+                    },
+                    2 => {
+                        if let Some(record) = &current {
+                            if !record.schema.is_known(&name) {
+                                issues.push(ValidationIssue::UnknownTag {
+                                    parent: record.schema.name.to_string(),
+                                    name: name.clone(),
+                                });
+                            }
+                        }
+                        text.clear();
+                    }
+                    _ => issues.push(ValidationIssue::UnknownTag {
+                        parent: open.last().cloned().unwrap_or_default(),
+                        name: name.clone(),
+                    }),
+                }
+
+                if matches!(event, Event::Start(_)) {
+                    open.push(name);
+                } else if open.len() == 2 {
+                    // An empty child element has no text
+                    if let Some(record) = &mut current {
+                        record.children.insert(name, String::new());
+                    }
+                } else if open.len() == 1 {
+                    records.extend(current.take());
+                }
+            }
+            Event::Text(value) if open.len() == 3 => text.push_str(&value.unescape()?),
+            Event::CData(value) if open.len() == 3 => {
+                text.push_str(&String::from_utf8_lossy(value))
+            }
+            Event::End(_) => {
+                let name = open.pop().unwrap_or_default();
+
+                match open.len() {
+                    2 => {
+                        if let Some(record) = &mut current {
+                            record.children.insert(name, text.trim().to_string());
+                        }
+                    }
+                    1 => records.extend(current.take()),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let version = xmlns.as_deref().and_then(SchemaVersion::from_xmlns);
+    if version.is_none() {
+        issues.insert(
+            0,
+            ValidationIssue::UnknownSchema {
+                xmlns: xmlns.clone(),
+            },
+        );
+    }
+
+    check_records(&records, version, &mut issues);
+
+    Ok(ValidationReport {
+        xmlns,
+        version,
+        issues,
+    })
+}
+
+/// Check the required children, schema version and IDs of each record
+fn check_records(
+    records: &[Record],
+    version: Option<SchemaVersion>,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let mut ids: HashMap<&str, HashSet<String>> = HashMap::new();
+
+    for record in records {
+        let element = record.schema.name;
+
+        if version == Some(SchemaVersion::V1) && record.schema.is_v2_only {
+            issues.push(ValidationIssue::WrongVersion {
+                parent: "MCDSchema".to_string(),
+                name: element.to_string(),
+                version: SchemaVersion::V1,
+            });
+        }
+
+        if let Some(version) = version {
+            let mut children: Vec<_> = record
+                .children
+                .keys()
+                .filter(|child| {
+                    record.schema.is_known(child) && !record.schema.in_version(child, version)
+                })
+                .collect();
+            children.sort();
+
+            for child in children {
+                issues.push(ValidationIssue::WrongVersion {
+                    parent: element.to_string(),
+                    name: child.to_string(),
+                    version,
+                });
+            }
+        }
+
+        for &required in record.schema.required {
+            if !record.children.contains_key(required) {
+                issues.push(ValidationIssue::MissingElement {
+                    element: element.to_string(),
+                    id: record.id(),
+                    name: required.to_string(),
+                });
+            }
+        }
+
+        if let Some(id) = record.id() {
+            if !ids.entry(element).or_default().insert(id.clone()) {
+                issues.push(ValidationIssue::DuplicateId {
+                    element: element.to_string(),
+                    id,
+                });
+            }
+        }
+    }
+
+    for record in records {
+        for &(field, target) in record.schema.references {
+            let target_id = match record.children.get(field) {
+                Some(target_id) if !target_id.is_empty() => target_id,
+                _ => continue,
+            };
+
+            if !ids
+                .get(target)
+                .is_some_and(|target_ids| target_ids.contains(target_id))
+            {
+                issues.push(ValidationIssue::MissingReference {
+                    element: record.schema.name.to_string(),
+                    id: record.id(),
+                    field: field.to_string(),
+                    target: target.to_string(),
+                    target_id: target_id.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> MCD<R> {
+    /// Validate the XML of the .mcd file against the known versions of the MCDSchema XSD (see
+    /// [`validation::validate`](crate::validation::validate))
+    pub fn validate(&self) -> Result<ValidationReport> {
+        validate_xml(&self.xml()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_inconsistent_ids() -> Result<()> {
+        let xml = r#"<MCDSchema xmlns="http://www.fluidigm.com/IMC/MCDSchema.xsd">
+            <Slide><ID>1</ID><UID>abc</UID></Slide>
+            <Panorama><ID>1</ID><SlideID>1</SlideID><RotationAngle>0</RotationAngle></Panorama>
+            <Panorama><ID>1</ID><SlideID>2</SlideID></Panorama>
+            <AcquisitionChannel><ID>1</ID><ChannelName>X</ChannelName><OrderNumber>0</OrderNumber>
+                <AcquisitionID>3</AcquisitionID><Colour>red</Colour></AcquisitionChannel>
+        </MCDSchema>"#;
+
+        let report = validate_xml(xml)?;
+        assert_eq!(report.version(), Some(SchemaVersion::V1));
+        assert!(!report.is_valid());
+
+        let issues: Vec<_> = report
+            .issues()
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                "Unknown element Colour in AcquisitionChannel",
+                "Element RotationAngle in Panorama is not part of schema v1",
+                "More than one Panorama has ID 1",
+                "Panorama 1 refers to Slide 2 (SlideID), which does not exist",
+                "AcquisitionChannel 1 refers to Acquisition 3 (AcquisitionID), which does not exist",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use quick_xml::events::{BytesStart, Event};
+
+use crate::{
+    error::{MCDError, Result},
+    MCD,
+};
+
+/// Element of the XML metadata stored in an .mcd file, with its attributes, text and children exactly as
+/// recorded. This includes elements which are not (yet) modelled by `MCD`, so that fields added in newer versions
+/// of the schema can be accessed without an update to this crate.
+///
+/// ```no_run
+/// use imc_rs::MCD;
+///
+/// let mcd = MCD::from_path("../test/20200612_FLU_1923.mcd").unwrap();
+/// let tree = mcd.metadata_xml_tree().unwrap();
+///
+/// if let Some(acquisition) = tree.entity("Acquisition", 1) {
+///     for (name, value) in acquisition.fields() {
+///         println!("{}: {}", name, value);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    fn from_start(element: &BytesStart) -> Result<Self> {
+        let mut attributes = Vec::new();
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(quick_xml::Error::from)?;
+            attributes.push((
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                attribute.unescape_value()?.into_owned(),
+            ));
+        }
+
+        Ok(XmlElement {
+            name: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+            attributes,
+            text: String::new(),
+            children: Vec::new(),
+        })
+    }
+
+    /// Returns the name of the element (e.g. `Acquisition`), without any namespace prefix
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the attributes of the element as (name, value) pairs, in the order they appear
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Returns the value of the attribute with the given name, if present
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the text within the element, with leading and trailing whitespace removed
+    pub fn text(&self) -> &str {
+        self.text.trim()
+    }
+
+    /// Returns the child elements, in the order they appear
+    pub fn children(&self) -> &[XmlElement] {
+        &self.children
+    }
+
+    /// Returns the child elements with the given name (e.g. all `Acquisition` elements within `MCDSchema`)
+    pub fn children_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Returns the first child element with the given name
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Returns the text of the first child element with the given name (e.g. `field("ID")`)
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text())
+    }
+
+    /// Returns the text of each child element which has no children itself, keyed by the name of the child. This
+    /// is the natural representation of the entities in the metadata (e.g. `Slide` or `Acquisition`), which
+    /// record each field as a child element. If a name is repeated, the first value is kept.
+    pub fn fields(&self) -> HashMap<String, String> {
+        let mut fields = HashMap::new();
+
+        for child in self
+            .children
+            .iter()
+            .filter(|child| child.children.is_empty())
+        {
+            fields
+                .entry(child.name.clone())
+                .or_insert_with(|| child.text().to_string());
+        }
+
+        fields
+    }
+
+    /// Returns the child element with the given name and `ID` field (e.g. `entity("Acquisition", 1)`)
+    pub fn entity(&self, name: &str, id: u16) -> Option<&XmlElement> {
+        self.children.iter().find(|child| {
+            child.name == name && child.field("ID").and_then(|value| value.parse().ok()) == Some(id)
+        })
+    }
+}
+
+/// Parse XML (e.g. from [`MCD::xml`]) into a tree of elements, returning the root element
+pub fn parse_xml_tree(xml: &str) -> Result<XmlElement> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+
+    // Currently open elements, the last of which is the innermost
+    let mut open: Vec<XmlElement> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let event = reader.read_event()?;
+
+        match event {
+            Event::Start(element) => open.push(XmlElement::from_start(&element)?),
+            Event::Empty(element) => {
+                let element = XmlElement::from_start(&element)?;
+
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = root.or(Some(element)),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(text) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Event::End(_) => {
+                if let Some(element) = open.pop() {
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => root = root.or(Some(element)),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    root.ok_or(MCDError::MissingElement {
+        path: "MCDSchema".to_string(),
+    })
+}
+
+impl<R: Read + Seek> MCD<R> {
+    /// Returns the XML metadata of the .mcd file as a tree of elements (rooted at `MCDSchema`), including any
+    /// elements which are not modelled by `MCD` (see [`XmlElement`])
+    pub fn metadata_xml_tree(&self) -> Result<XmlElement> {
+        parse_xml_tree(&self.xml()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_unknown_elements() -> Result<()> {
+        let xml = r#"<MCDSchema xmlns="http://www.fluidigm.com/IMC/MCDSchema_V2_0.xsd">
+            <Slide><ID>1</ID><Description>Slide &amp; tissue</Description></Slide>
+            <Acquisition><ID>2</ID><NewField>42</NewField><Empty /></Acquisition>
+            <FutureElement Kind="test"><Nested><ID>1</ID></Nested></FutureElement>
+        </MCDSchema>"#;
+
+        let tree = parse_xml_tree(xml)?;
+        assert_eq!(tree.name(), "MCDSchema");
+        assert_eq!(
+            tree.attribute("xmlns"),
+            Some("http://www.fluidigm.com/IMC/MCDSchema_V2_0.xsd")
+        );
+        assert_eq!(tree.children().len(), 3);
+
+        assert_eq!(
+            tree.entity("Slide", 1)
+                .and_then(|slide| slide.field("Description")),
+            Some("Slide & tissue")
+        );
+
+        let acquisition = tree.entity("Acquisition", 2);
+        let fields = acquisition.map(XmlElement::fields).unwrap_or_default();
+        assert_eq!(fields.get("NewField").map(String::as_str), Some("42"));
+        assert_eq!(fields.get("Empty").map(String::as_str), Some(""));
+        assert!(tree.entity("Acquisition", 1).is_none());
+
+        let future = tree.child("FutureElement");
+        assert_eq!(
+            future.and_then(|element| element.attribute("Kind")),
+            Some("test")
+        );
+        assert_eq!(
+            future
+                .and_then(|element| element.child("Nested"))
+                .and_then(|nested| nested.field("ID")),
+            Some("1")
+        );
+
+        assert!(matches!(
+            parse_xml_tree(""),
+            Err(MCDError::MissingElement { .. })
+        ));
+
+        Ok(())
+    }
+}